@@ -0,0 +1,476 @@
+//! Memory footprint estimates derived from GGUF metadata.
+//!
+//! These are rough figures for capacity planning — model weights are
+//! taken from the file size, the KV cache from the attention shape, and
+//! the compute buffer from the head count and vocabulary size —
+//! not a replacement for what llama.cpp actually allocates.
+//!
+//! [`estimate_parameters`] and [`estimate_quantized_bytes`] size the
+//! weights from tensor infos instead, without reading any tensor data.
+
+use serde::Serialize;
+
+use crate::reader::QuickScanResult;
+use crate::types::{GGUFValue, TensorInfo};
+
+/// Storage size of a KV cache element type: `bytes` per block of
+/// `block_len` values (1 for plain float types).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct KvElemSize {
+    pub bytes: u64,
+    pub block_len: u64,
+}
+
+impl KvElemSize {
+    pub const F32: Self = Self::new(4, 1);
+    pub const F16: Self = Self::new(2, 1);
+    pub const BF16: Self = Self::new(2, 1);
+    pub const Q8_0: Self = Self::new(34, 32);
+    pub const Q5_1: Self = Self::new(24, 32);
+    pub const Q5_0: Self = Self::new(22, 32);
+    pub const Q4_1: Self = Self::new(20, 32);
+    pub const Q4_0: Self = Self::new(18, 32);
+    pub const IQ4_NL: Self = Self::new(18, 32);
+
+    const fn new(bytes: u64, block_len: u64) -> Self {
+        Self { bytes, block_len }
+    }
+
+    /// Bytes for a row of `n` values.
+    pub fn row_bytes(&self, n: u64) -> u64 {
+        n.div_ceil(self.block_len) * self.bytes
+    }
+}
+
+/// llama.cpp's default physical batch size (`n_ubatch`).
+pub const DEFAULT_N_UBATCH: u64 = 512;
+
+/// Attention shape needed to size the KV cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct KvDims {
+    pub n_layer: u64,
+    pub n_head: u64,
+    pub n_head_kv: u64,
+    pub key_length: u64,
+    pub value_length: u64,
+}
+
+impl KvDims {
+    /// Read the attention shape from `{arch}.*` metadata keys.
+    ///
+    /// `head_count_kv` defaults to `head_count` (no GQA), and key/value
+    /// lengths default to `embedding_length / head_count`.
+    pub fn from_scan(scan: &QuickScanResult) -> Option<Self> {
+        let arch = scan.architecture.as_deref().unwrap_or("llama");
+        let get = |suffix: &str| {
+            let key = format!("{arch}.{suffix}");
+            scan.metadata
+                .iter()
+                .find(|kv| kv.key == key)
+                .and_then(|kv| max_u64(&kv.value))
+        };
+
+        let n_layer = get("block_count")?;
+        let n_head = get("attention.head_count")?.max(1);
+        let n_head_kv = get("attention.head_count_kv").unwrap_or(n_head);
+        let head_dim = u64::from(scan.embedding_length?) / n_head;
+
+        Some(Self {
+            n_layer,
+            n_head,
+            n_head_kv,
+            key_length: get("attention.key_length").unwrap_or(head_dim),
+            value_length: get("attention.value_length").unwrap_or(head_dim),
+        })
+    }
+
+    /// KV cache size for `n_ctx` positions with keys stored as `type_k`
+    /// and values as `type_v`.
+    pub fn cache_bytes(&self, n_ctx: u64, type_k: KvElemSize, type_v: KvElemSize) -> u64 {
+        let per_position = type_k.row_bytes(self.n_head_kv * self.key_length)
+            + type_v.row_bytes(self.n_head_kv * self.value_length);
+        n_ctx * self.n_layer * per_position
+    }
+
+    /// Size of the f32 `KQ` score matrix materialised per layer without
+    /// flash attention: one `n_ctx × n_ubatch` slice per head.  The buffer
+    /// is reused across layers, so it does not scale with `n_layer`.
+    pub fn attention_scores_bytes(&self, n_ctx: u64, n_ubatch: u64) -> u64 {
+        n_ctx * n_ubatch.min(n_ctx) * self.n_head * 4
+    }
+
+    /// Layers that end up on the GPU for `n_gpu_layers` (negative: all),
+    /// out of the repeating blocks plus the output layer.
+    fn offloaded_layers(&self, n_gpu_layers: i32) -> u64 {
+        let n_total = self.n_layer + 1;
+        u64::try_from(n_gpu_layers).map_or(n_total, |n| n.min(n_total))
+    }
+}
+
+/// Per-layer arrays (e.g. variable GQA) are sized by their largest entry.
+fn max_u64(value: &GGUFValue) -> Option<u64> {
+    match value {
+        GGUFValue::Array(items) => items.iter().filter_map(GGUFValue::as_u64).max(),
+        v => v.as_u64(),
+    }
+}
+
+//  Weights from tensor shapes
+
+/// Storage of a `ggml_type`: bytes per block and weights per block, as
+/// in ggml's type traits.  `None` for types not listed.
+fn ggml_block_size(t: u32) -> Option<(u64, u64)> {
+    const QK_K: u64 = 256;
+    let size = match t {
+        0 => (4, 1),            // F32
+        1 | 30 => (2, 1),       // F16, BF16
+        2 | 20 => (18, 32),     // Q4_0, IQ4_NL
+        3 => (20, 32),          // Q4_1
+        6 => (22, 32),          // Q5_0
+        7 => (24, 32),          // Q5_1
+        8 => (34, 32),          // Q8_0
+        9 => (36, 32),          // Q8_1
+        10 => (84, QK_K),       // Q2_K
+        11 | 21 => (110, QK_K), // Q3_K, IQ3_S
+        12 => (144, QK_K),      // Q4_K
+        13 => (176, QK_K),      // Q5_K
+        14 => (210, QK_K),      // Q6_K
+        15 => (292, QK_K),      // Q8_K
+        16 | 35 => (66, QK_K),  // IQ2_XXS, TQ2_0
+        17 => (74, QK_K),       // IQ2_XS
+        18 => (98, QK_K),       // IQ3_XXS
+        19 => (50, QK_K),       // IQ1_S
+        22 => (82, QK_K),       // IQ2_S
+        23 => (136, QK_K),      // IQ4_XS
+        24 => (1, 1),           // I8
+        25 => (2, 1),           // I16
+        26 => (4, 1),           // I32
+        27 | 28 => (8, 1),      // I64, F64
+        29 => (56, QK_K),       // IQ1_M
+        34 => (54, QK_K),       // TQ1_0
+        39 => (17, 32),         // MXFP4
+        _ => return None,
+    };
+    Some(size)
+}
+
+/// Average storage per weight of a `ggml_type`, in bits: 4.5 for
+/// `Q4_K`, 16 for `F16`.
+pub fn ggml_bits_per_weight(t: u32) -> Option<f64> {
+    ggml_block_size(t).map(|(bytes, block_len)| (bytes * 8) as f64 / block_len as f64)
+}
+
+/// Bytes the data of `tensor` takes up given its ggml type; `None` for
+/// types not listed.
+pub fn ggml_tensor_bytes(tensor: &TensorInfo) -> Option<u64> {
+    let (bytes, block_len) = ggml_block_size(tensor.ggml_type)?;
+    tensor.n_elements().div_ceil(block_len).checked_mul(bytes)
+}
+
+/// Parameter count of a model: the element counts of its tensors summed.
+/// Every tensor in a GGUF file is a weight (norms and biases included),
+/// matching what llama.cpp reports as `n_params`.
+pub fn estimate_parameters(tensors: &[TensorInfo]) -> u64 {
+    tensors
+        .iter()
+        .fold(0, |n, t| n.saturating_add(t.n_elements()))
+}
+
+/// Bytes the tensors' data takes up given their ggml types.  Tensors of
+/// a type missing from the table are not counted.
+pub fn estimate_quantized_bytes(tensors: &[TensorInfo]) -> u64 {
+    tensors
+        .iter()
+        .filter_map(ggml_tensor_bytes)
+        .fold(0, u64::saturating_add)
+}
+
+/// Estimated resident memory for a model at a given context size.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct MemoryEstimate {
+    pub weights_bytes: u64,
+    pub kv_cache_bytes: u64,
+    /// Logits for one micro-batch plus the attention score buffer; the
+    /// latter is zero when flash attention is on, since the fused kernel
+    /// never materialises the full matrix.
+    pub compute_bytes: u64,
+    pub total_bytes: u64,
+    /// The part of `total_bytes` in system RAM.
+    pub host_bytes: u64,
+    /// The part of `total_bytes` on the GPU: the offloaded layers' weights
+    /// and KV cache, and the compute buffer if any layer is offloaded.
+    pub device_bytes: u64,
+}
+
+/// The load settings that change the footprint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextShape {
+    /// `0` means the model's trained context length.
+    pub n_ctx: u32,
+    /// Pass `false` when it may be off, so the score buffer is counted.
+    pub flash_attn: bool,
+    pub type_k: KvElemSize,
+    pub type_v: KvElemSize,
+    /// Layers offloaded to the GPU, as in `ModelParams`; negative means
+    /// all of them.  Pass `0` when the build cannot offload.
+    pub n_gpu_layers: i32,
+}
+
+impl Default for ContextShape {
+    fn default() -> Self {
+        Self {
+            n_ctx: 0,
+            flash_attn: false,
+            type_k: KvElemSize::F16,
+            type_v: KvElemSize::F16,
+            n_gpu_layers: 0,
+        }
+    }
+}
+
+/// Estimate the memory needed to run `scan` with a `ctx`-shaped context,
+/// split into host and device parts by `ctx.n_gpu_layers`.
+///
+/// Split models are assumed to have equally sized parts (`split.count`),
+/// and the weights spread evenly over the layers.
+pub fn estimate_memory(scan: &QuickScanResult, ctx: &ContextShape) -> MemoryEstimate {
+    let parts = scan
+        .metadata
+        .iter()
+        .find(|kv| kv.key == "split.count")
+        .and_then(|kv| kv.value.as_u64())
+        .unwrap_or(1)
+        .max(1);
+    let weights_bytes = scan.file_size * parts;
+
+    let n_ctx = match ctx.n_ctx {
+        0 => scan.context_length.unwrap_or(0),
+        n => n,
+    };
+    let dims = KvDims::from_scan(scan);
+    let kv_cache_bytes = dims
+        .map(|dims| dims.cache_bytes(u64::from(n_ctx), ctx.type_k, ctx.type_v))
+        .unwrap_or(0);
+    let n_vocab = u64::from(scan.tokenizer().n_vocab.unwrap_or(0));
+    let logits_bytes = n_vocab * DEFAULT_N_UBATCH.min(u64::from(n_ctx).max(1)) * 4;
+    let compute_bytes = logits_bytes
+        + match dims {
+            Some(dims) if !ctx.flash_attn => {
+                dims.attention_scores_bytes(u64::from(n_ctx), DEFAULT_N_UBATCH)
+            }
+            _ => 0,
+        };
+    let total_bytes = weights_bytes + kv_cache_bytes + compute_bytes;
+
+    let device_bytes = match (dims, ctx.n_gpu_layers) {
+        (_, 0) => 0,
+        // Without a layer count, any offload is taken to mean all of it
+        (None, _) => total_bytes,
+        (Some(dims), n_gpu_layers) => {
+            let offloaded = dims.offloaded_layers(n_gpu_layers);
+            let kv_layers = offloaded.min(dims.n_layer);
+            weights_bytes * offloaded / (dims.n_layer + 1)
+                + kv_cache_bytes * kv_layers / dims.n_layer.max(1)
+                + compute_bytes
+        }
+    };
+
+    MemoryEstimate {
+        weights_bytes,
+        kv_cache_bytes,
+        compute_bytes,
+        total_bytes,
+        host_bytes: total_bytes - device_bytes,
+        device_bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::types::{GGUFHeader, GGUFMetadataKV, GGUFValueType};
+
+    const GIB: u64 = 1 << 30;
+
+    /// An 8 GiB llama-shaped model: 32 layers, 32 heads, 8 KV heads of 128
+    /// and a 32k vocabulary.
+    fn llama_scan() -> QuickScanResult {
+        let kv = |key: &str, value| GGUFMetadataKV {
+            key: key.into(),
+            value_type: match value {
+                GGUFValue::SkippedArray { .. } => GGUFValueType::Array,
+                _ => GGUFValueType::Uint32,
+            },
+            value,
+        };
+        QuickScanResult {
+            file_path: PathBuf::from("llama.gguf"),
+            file_size: 8 * GIB,
+            header: GGUFHeader {
+                version: 3,
+                tensor_count: 291,
+                metadata_kv_count: 4,
+            },
+            architecture: Some("llama".into()),
+            name: None,
+            file_type: None,
+            file_type_name: None,
+            context_length: Some(8192),
+            embedding_length: Some(4096),
+            chat_template: None,
+            param_count: None,
+            metadata: vec![
+                kv("llama.block_count", GGUFValue::Uint32(32)),
+                kv("llama.attention.head_count", GGUFValue::Uint32(32)),
+                kv("llama.attention.head_count_kv", GGUFValue::Uint32(8)),
+                kv(
+                    "tokenizer.ggml.tokens",
+                    GGUFValue::SkippedArray {
+                        len: 32000,
+                        elem_type: GGUFValueType::String,
+                    },
+                ),
+            ],
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn memory_estimate_adds_kv_cache_and_compute_buffers() {
+        let scan = llama_scan();
+        let ctx = ContextShape {
+            n_ctx: 4096,
+            flash_attn: true,
+            ..Default::default()
+        };
+        let est = estimate_memory(&scan, &ctx);
+        assert_eq!(est.weights_bytes, 8 * GIB);
+        // K and V: 32 layers × 4096 positions × 8 heads × 128 × 2 bytes
+        assert_eq!(est.kv_cache_bytes, 2 * 32 * 4096 * 8 * 128 * 2);
+        // Flash attention leaves only the logits of one micro-batch
+        assert_eq!(est.compute_bytes, 32000 * 512 * 4);
+        assert_eq!(est.host_bytes, est.total_bytes);
+        assert_eq!(est.device_bytes, 0);
+
+        let no_fa = estimate_memory(
+            &scan,
+            &ContextShape {
+                flash_attn: false,
+                ..ctx
+            },
+        );
+        assert_eq!(no_fa.compute_bytes - est.compute_bytes, 4096 * 512 * 32 * 4);
+
+        // The trained context length is the default
+        let full = estimate_memory(&scan, &ContextShape { n_ctx: 0, ..ctx });
+        assert_eq!(full.kv_cache_bytes, 2 * est.kv_cache_bytes);
+    }
+
+    #[test]
+    fn memory_estimate_splits_by_offloaded_layers() {
+        let scan = llama_scan();
+        let ctx = ContextShape {
+            n_ctx: 4096,
+            flash_attn: true,
+            ..Default::default()
+        };
+        let cpu = estimate_memory(&scan, &ctx);
+
+        let all = estimate_memory(
+            &scan,
+            &ContextShape {
+                n_gpu_layers: -1,
+                ..ctx
+            },
+        );
+        assert_eq!(all.total_bytes, cpu.total_bytes);
+        assert_eq!(all.device_bytes, all.total_bytes);
+        assert_eq!(all.host_bytes, 0);
+        assert_eq!(
+            estimate_memory(
+                &scan,
+                &ContextShape {
+                    n_gpu_layers: 99,
+                    ..ctx
+                }
+            ),
+            all
+        );
+
+        // Half the blocks: the output layer's share of weights stays on the host
+        let half = estimate_memory(
+            &scan,
+            &ContextShape {
+                n_gpu_layers: 16,
+                ..ctx
+            },
+        );
+        assert_eq!(half.total_bytes, cpu.total_bytes);
+        assert_eq!(
+            half.device_bytes,
+            8 * GIB * 16 / 33 + cpu.kv_cache_bytes / 2 + cpu.compute_bytes
+        );
+        assert_eq!(half.host_bytes + half.device_bytes, half.total_bytes);
+    }
+
+    fn tensor(dims: &[u64], ggml_type: u32) -> TensorInfo {
+        let mut shape = [1; 4];
+        shape[..dims.len()].copy_from_slice(dims);
+        TensorInfo {
+            name: String::new(),
+            n_dims: dims.len() as u32,
+            dims: shape,
+            ggml_type,
+            offset: 0,
+        }
+    }
+
+    #[test]
+    fn parameters_sum_every_tensor() {
+        assert_eq!(estimate_parameters(&[]), 0);
+
+        // One llama-style block: attention, FFN and the two norms
+        let (d, ff) = (4096, 11008);
+        let block = [
+            tensor(&[d, d], 12),
+            tensor(&[d, 1024], 12),
+            tensor(&[d, 1024], 12),
+            tensor(&[d, d], 12),
+            tensor(&[d, ff], 12),
+            tensor(&[d, ff], 12),
+            tensor(&[ff, d], 14),
+            tensor(&[d], 0),
+            tensor(&[d], 0),
+        ];
+        assert_eq!(
+            estimate_parameters(&block),
+            2 * d * d + 2 * d * 1024 + 3 * d * ff + 2 * d
+        );
+
+        // A 4-D convolution kernel counts all its dimensions
+        assert_eq!(estimate_parameters(&[tensor(&[3, 3, 64, 128], 1)]), 73_728);
+    }
+
+    #[test]
+    fn quantized_bytes_follow_the_block_layout() {
+        // 256 Q4_K weights fill exactly one 144-byte block
+        assert_eq!(estimate_quantized_bytes(&[tensor(&[256], 12)]), 144);
+        assert_eq!(ggml_tensor_bytes(&tensor(&[512, 2], 12)), Some(576));
+        assert_eq!(ggml_tensor_bytes(&tensor(&[4096], 1000)), None);
+        assert_eq!(ggml_bits_per_weight(12), Some(4.5));
+        assert_eq!(ggml_bits_per_weight(8), Some(8.5));
+
+        let tensors = [
+            tensor(&[4096, 32000], 14), // Q6_K embeddings
+            tensor(&[4096, 4096], 8),   // Q8_0
+            tensor(&[4096], 0),         // F32 norm
+            tensor(&[4096], 1000),      // unknown type: skipped
+        ];
+        assert_eq!(
+            estimate_quantized_bytes(&tensors),
+            4096 * 32000 / 256 * 210 + 4096 * 4096 / 32 * 34 + 4096 * 4
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Model features for the dashboard's badges, guessed from metadata.
+//!
+//! All heuristics live in [`detect_features`]; they are hints for the
+//! user, not something requests are routed on (see
+//! [`Capability`](crate::types::Capability) for that).
+
+use crate::reader::QuickScanResult;
+use crate::types::{Capability, GGUFValue, ModelFeature};
+
+/// Architectures of models that always reason before answering.
+pub const REASONING_ARCHITECTURES: &[&str] = &["gpt-oss"];
+
+/// Chat template fragments that only reasoning models' templates contain.
+const REASONING_TEMPLATE_MARKERS: &[&str] = &["<think>", "reasoning_content"];
+
+/// The features of the model described by `scan`; `has_projector` says
+/// whether a vision projector was found for it.
+///
+/// * `moe` — `{arch}.expert_count` is above zero
+/// * `vision` — `has_projector`
+/// * `embedding` — the model serves [`Capability::Embeddings`] (pooling
+///   declared, or a BERT-family architecture)
+/// * `instruct` — a chat template is present
+/// * `reasoning` — the chat template handles `<think>` blocks or
+///   `reasoning_content`, or the architecture is in
+///   [`REASONING_ARCHITECTURES`]
+pub fn detect_features(scan: &QuickScanResult, has_projector: bool) -> Vec<ModelFeature> {
+    let arch = scan.architecture.as_deref().unwrap_or("llama");
+    let template = scan.chat_template.as_deref();
+
+    let moe = scan
+        .get(&format!("{arch}.expert_count"))
+        .and_then(GGUFValue::as_u32)
+        .is_some_and(|n| n > 0);
+    let embedding = scan.capabilities().contains(&Capability::Embeddings);
+    let reasoning = REASONING_ARCHITECTURES.contains(&arch)
+        || template.is_some_and(|t| REASONING_TEMPLATE_MARKERS.iter().any(|m| t.contains(m)));
+
+    [
+        (ModelFeature::Moe, moe),
+        (ModelFeature::Vision, has_projector),
+        (ModelFeature::Embedding, embedding),
+        (ModelFeature::Instruct, template.is_some()),
+        (ModelFeature::Reasoning, reasoning),
+    ]
+    .into_iter()
+    .filter_map(|(feature, present)| present.then_some(feature))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::types::{GGUFHeader, GGUFMetadataKV, GGUFValueType};
+
+    fn scan(arch: &str, template: Option<&str>, metadata: &[(&str, u32)]) -> QuickScanResult {
+        QuickScanResult {
+            file_path: PathBuf::from("model.gguf"),
+            file_size: 0,
+            header: GGUFHeader {
+                version: 3,
+                tensor_count: 0,
+                metadata_kv_count: metadata.len() as u64,
+            },
+            architecture: Some(arch.into()),
+            name: None,
+            file_type: None,
+            file_type_name: None,
+            context_length: None,
+            embedding_length: None,
+            chat_template: template.map(String::from),
+            param_count: None,
+            metadata: metadata
+                .iter()
+                .map(|&(key, value)| GGUFMetadataKV {
+                    key: key.into(),
+                    value_type: GGUFValueType::Uint32,
+                    value: GGUFValue::Uint32(value),
+                })
+                .collect(),
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn features_follow_the_metadata() {
+        use ModelFeature::*;
+
+        assert!(detect_features(&scan("llama", None, &[]), false).is_empty());
+        assert_eq!(
+            detect_features(&scan("llama", Some("{{ messages }}"), &[]), true),
+            [Vision, Instruct]
+        );
+
+        let qwen3_moe = scan(
+            "qwen3moe",
+            Some("{%- if '<think>' in content %}"),
+            &[("qwen3moe.expert_count", 128)],
+        );
+        assert_eq!(
+            detect_features(&qwen3_moe, false),
+            [Moe, Instruct, Reasoning]
+        );
+        // Dense models may still carry the key
+        let dense = scan("llama", None, &[("llama.expert_count", 0)]);
+        assert!(detect_features(&dense, false).is_empty());
+
+        assert_eq!(
+            detect_features(&scan("gpt-oss", None, &[]), false),
+            [Reasoning]
+        );
+        assert_eq!(
+            detect_features(&scan("bert", None, &[]), false),
+            [Embedding]
+        );
+        let pooled = scan("qwen3", None, &[("qwen3.pooling_type", 3)]);
+        assert_eq!(detect_features(&pooled, false), [Embedding]);
+    }
+}
@@ -0,0 +1,227 @@
+//! Guesses from a model's file name, for files whose metadata can't be
+//! read (truncated downloads, missing permissions).
+//!
+//! Community uploads follow a loose convention, e.g.
+//! `Meta-Llama-3.1-8B-Instruct-Q4_K_M.gguf`: name, size label, fine-tune
+//! and quantization separated by `-`, `_` or `.`.
+
+/// Quantization names as they appear in file names, longest first where
+/// one is a prefix of another.
+const QUANT_NAMES: &[&str] = &[
+    "Q4_0_4_4", "Q4_0_4_8", "Q4_0_8_8", "IQ2_XXS", "IQ3_XXS", "IQ2_XS", "IQ3_XS", "IQ4_XS",
+    "IQ4_NL", "Q2_K_S", "Q3_K_S", "Q3_K_M", "Q3_K_L", "Q4_K_S", "Q4_K_M", "Q4_K_L", "Q5_K_S",
+    "Q5_K_M", "Q5_K_L", "Q6_K_L", "IQ1_S", "IQ1_M", "IQ2_S", "IQ2_M", "IQ3_S", "IQ3_M", "TQ1_0",
+    "TQ2_0", "MXFP4", "Q2_K", "Q3_K", "Q4_K", "Q5_K", "Q6_K", "Q4_0", "Q4_1", "Q5_0", "Q5_1",
+    "Q8_0", "BF16", "F16", "F32",
+];
+
+/// Words marking an instruction or chat tune.
+const INSTRUCT_MARKERS: &[&str] = &["instruct", "chat", "it", "inst"];
+
+/// What a model's file name suggests about it, see
+/// [`parse_model_filename`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilenameHints {
+    /// Size label as written, with the unit upper-cased: `7B`, `1.5B`,
+    /// `8x7B`.
+    pub parameters: Option<String>,
+    /// Parameters the label stands for; `None` for mixture-of-experts
+    /// labels (`8x7B`), whose total isn't the product.
+    pub param_count: Option<u64>,
+    /// Quantization, upper-cased: `Q4_K_M`, `IQ2_XS`, `BF16`.
+    pub quantization: Option<String>,
+    /// Named as an instruct or chat tune.
+    pub instruct: bool,
+}
+
+/// Guess size, quantization and tuning from `file_name`.
+///
+/// Sizes are tokens like `7B`, `70b`, `1.5B`, `350M` or `8x22B`; `K`
+/// isn't accepted, as `4k` or `128K` name context lengths.  The last
+/// quantization name wins, so `Q8_0` in `…-Q8_0-Q4_K_M.gguf` (an
+/// upstream quant re-quantized) isn't reported.
+pub fn parse_model_filename(file_name: &str) -> FilenameHints {
+    let stem = file_name.strip_suffix(".gguf").unwrap_or(file_name);
+    let words: Vec<&str> = stem
+        .split(['-', '_', ' '])
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    // `1.5B` is one word, but so is `13b.Q4` in `codellama-13b.Q4_K_M`
+    let size = words
+        .iter()
+        .find_map(|w| parse_size(w).or_else(|| w.split('.').find_map(parse_size)));
+    FilenameHints {
+        parameters: size.as_ref().map(|(label, _)| label.clone()),
+        param_count: size.and_then(|(_, count)| count),
+        quantization: find_quantization(stem).map(String::from),
+        instruct: words
+            .iter()
+            .flat_map(|w| w.split('.'))
+            .any(|w| INSTRUCT_MARKERS.iter().any(|m| w.eq_ignore_ascii_case(m))),
+    }
+}
+
+/// A size label and the parameter count it stands for.
+fn parse_size(word: &str) -> Option<(String, Option<u64>)> {
+    let (number, unit) = word.split_at(word.char_indices().last()?.0);
+    let scale = match unit {
+        "M" | "m" => 1e6,
+        "B" | "b" => 1e9,
+        "T" | "t" => 1e12,
+        _ => return None,
+    };
+    let (experts, number) = match number.split_once(['x', 'X']) {
+        Some((experts, number)) => (Some(experts), number),
+        None => (None, number),
+    };
+    let digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    let is_number = |s: &str| {
+        s.starts_with(|c: char| c.is_ascii_digit())
+            && s.chars().all(|c| c.is_ascii_digit() || c == '.')
+    };
+    if !is_number(number) || experts.is_some_and(|e| !digits(e)) {
+        return None;
+    }
+    let value: f64 = number.parse().ok()?;
+    let unit = unit.to_ascii_uppercase();
+    Some(match experts {
+        Some(experts) => (format!("{experts}x{number}{unit}"), None),
+        None => (
+            format!("{number}{unit}"),
+            Some((value * scale).round() as u64),
+        ),
+    })
+}
+
+/// The last known quantization name in `stem` that stands as a word of
+/// its own.
+fn find_quantization(stem: &str) -> Option<&'static str> {
+    let upper = stem.to_ascii_uppercase();
+    let bytes = upper.as_bytes();
+    let boundary = |i: usize| i == 0 || i >= bytes.len() || !bytes[i].is_ascii_alphanumeric();
+    let mut found = None;
+    for start in 0..bytes.len() {
+        if start > 0 && bytes[start - 1].is_ascii_alphanumeric() {
+            continue;
+        }
+        // Names that extend another (Q4_K_M, Q4_K) are listed first
+        let name = QUANT_NAMES.iter().find(|name| {
+            bytes[start..].starts_with(name.as_bytes()) && boundary(start + name.len())
+        });
+        if let Some(name) = name {
+            found = Some(*name);
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hints(
+        parameters: Option<&str>,
+        param_count: Option<u64>,
+        quantization: Option<&str>,
+        instruct: bool,
+    ) -> FilenameHints {
+        FilenameHints {
+            parameters: parameters.map(String::from),
+            param_count,
+            quantization: quantization.map(String::from),
+            instruct,
+        }
+    }
+
+    #[test]
+    fn real_world_file_names() {
+        let cases = [
+            (
+                "Meta-Llama-3.1-8B-Instruct-Q4_K_M.gguf",
+                hints(Some("8B"), Some(8_000_000_000), Some("Q4_K_M"), true),
+            ),
+            (
+                "qwen2.5-coder-1.5b-instruct-q8_0.gguf",
+                hints(Some("1.5B"), Some(1_500_000_000), Some("Q8_0"), true),
+            ),
+            (
+                "gemma-2-9b-it-IQ2_XS.gguf",
+                hints(Some("9B"), Some(9_000_000_000), Some("IQ2_XS"), true),
+            ),
+            (
+                "llama-2-70b-chat.Q5_K_S.gguf",
+                hints(Some("70B"), Some(70_000_000_000), Some("Q5_K_S"), true),
+            ),
+            (
+                "mixtral-8x7b-instruct-v0.1.Q3_K_M.gguf",
+                hints(Some("8x7B"), None, Some("Q3_K_M"), true),
+            ),
+            (
+                "Phi-3-mini-4k-instruct-fp16.gguf",
+                hints(None, None, None, true),
+            ),
+            (
+                "Qwen3-30B-A3B-UD-IQ1_M.gguf",
+                hints(Some("30B"), Some(30_000_000_000), Some("IQ1_M"), false),
+            ),
+            (
+                "SmolLM2-360M-Q4_K_M-00001-of-00002.gguf",
+                hints(Some("360M"), Some(360_000_000), Some("Q4_K_M"), false),
+            ),
+            (
+                "DeepSeek-R1-Distill-Qwen-14B-Q4_0_4_8.gguf",
+                hints(Some("14B"), Some(14_000_000_000), Some("Q4_0_4_8"), false),
+            ),
+            (
+                "nomic-embed-text-v1.5.f16.gguf",
+                hints(None, None, Some("F16"), false),
+            ),
+            (
+                "Mistral-Nemo-Base-2407-bf16.gguf",
+                hints(None, None, Some("BF16"), false),
+            ),
+            (
+                "gpt-oss-120b-MXFP4.gguf",
+                hints(Some("120B"), Some(120_000_000_000), Some("MXFP4"), false),
+            ),
+            (
+                "codellama-13b.Q4_K_M.gguf",
+                hints(Some("13B"), Some(13_000_000_000), Some("Q4_K_M"), false),
+            ),
+            (
+                "tinyllama-1.1b-chat-v1.0.Q2_K.gguf",
+                hints(Some("1.1B"), Some(1_100_000_000), Some("Q2_K"), true),
+            ),
+            (
+                "通义千问-7B-Chat-Q4_K_M.gguf",
+                hints(Some("7B"), Some(7_000_000_000), Some("Q4_K_M"), true),
+            ),
+            ("model.gguf", hints(None, None, None, false)),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(parse_model_filename(name), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn look_alikes_are_not_taken_for_sizes_or_quants() {
+        // Context lengths, versions and words that merely contain a
+        // quant name
+        let h = parse_model_filename("Yi-9B-200K-Q4_KM-Bit-F16x.gguf");
+        assert_eq!(h.parameters.as_deref(), Some("9B"));
+        assert_eq!(h.quantization, None);
+        assert!(!h.instruct);
+
+        let h = parse_model_filename("reflection-v2-128k-edit.gguf");
+        assert_eq!(h, FilenameHints::default());
+
+        // The later quant is the file's own
+        let h = parse_model_filename("Model-7B-Q8_0-Q4_K_S.gguf");
+        assert_eq!(h.quantization.as_deref(), Some("Q4_K_S"));
+
+        // "it" counts as a whole word only
+        assert!(!parse_model_filename("bitnet-b1.58-2B-4T.gguf").instruct);
+        assert!(parse_model_filename("gemma-3-1b-it.gguf").instruct);
+    }
+}
@@ -0,0 +1,90 @@
+//! SHA-256 of model files, for integrity checks.
+//!
+//! Files are streamed through the hasher in fixed-size chunks, so even
+//! multi-gigabyte models are hashed in constant memory.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::types::GGUFError;
+
+/// Bytes read per chunk while hashing.
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Lowercase hex SHA-256 of the file at `path`.
+pub fn hash_file(path: &Path) -> Result<String, GGUFError> {
+    hash_file_with_progress(path, |_| {})
+}
+
+/// Like [`hash_file`], calling `on_progress` with the number of bytes
+/// hashed so far after every chunk.
+pub fn hash_file_with_progress(
+    path: &Path,
+    mut on_progress: impl FnMut(u64),
+) -> Result<String, GGUFError> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut done: u64 = 0;
+    loop {
+        let n = match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        hasher.update(&buf[..n]);
+        done += n as u64;
+        on_progress(done);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Whether `s` is a SHA-256 in hex (either case).
+pub fn is_sha256_hex(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_match_known_digests() {
+        let dir = std::env::temp_dir().join(format!("gguf-parser-hash-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let empty = dir.join("empty.gguf");
+        fs::write(&empty, b"").unwrap();
+        assert_eq!(
+            hash_file(&empty).unwrap(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+
+        // Spans several chunks; progress ends at the file size
+        let large = dir.join("large.gguf");
+        fs::write(&large, vec![b'a'; 2 * CHUNK_SIZE + 3]).unwrap();
+        let mut seen = Vec::new();
+        let digest = hash_file_with_progress(&large, |n| seen.push(n)).unwrap();
+        assert_eq!(seen.last(), Some(&(2 * CHUNK_SIZE as u64 + 3)));
+        assert_eq!(
+            digest,
+            "94444f79251aaf2956ac6d46ef04f4e1cb8aa188d97f21fecd00e068e2b235b2"
+        );
+
+        assert!(hash_file(&dir.join("missing.gguf")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sha256_hex_is_64_hex_digits() {
+        let digest = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert!(is_sha256_hex(digest));
+        assert!(is_sha256_hex(&digest.to_uppercase()));
+        assert!(!is_sha256_hex(&digest[1..]));
+        assert!(!is_sha256_hex(&"g".repeat(64)));
+    }
+}
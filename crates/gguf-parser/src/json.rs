@@ -0,0 +1,197 @@
+//! Plain JSON rendering of GGUF metadata values.
+//!
+//! The derived serde representation tags every value with its type
+//! (`{"Uint32": 32768}`); API consumers get plain JSON instead:
+//!
+//! * integers as numbers, except 64-bit ones beyond ±(2^53 − 1), which
+//!   JavaScript can't hold exactly, as decimal strings
+//! * floats as numbers (`f32`s by their shortest decimal form), NaN and
+//!   infinities as the strings `"NaN"`, `"Infinity"` and `"-Infinity"`
+//! * arrays element by element; a [`GGUFValue::SkippedArray`] as `null`
+//!
+//! The JSON no longer says which type a value had, so
+//! [`GGUFValue::from_json`] reads it back against a value of the type
+//! wanted.
+
+use serde_json::Value;
+
+use crate::types::GGUFValue;
+
+/// Largest integer a JSON number (an IEEE double) holds exactly.
+pub const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+impl From<&GGUFValue> for Value {
+    fn from(value: &GGUFValue) -> Self {
+        use GGUFValue as V;
+        match value {
+            V::Uint8(v) => (*v).into(),
+            V::Int8(v) => (*v).into(),
+            V::Uint16(v) => (*v).into(),
+            V::Int16(v) => (*v).into(),
+            V::Uint32(v) => (*v).into(),
+            V::Int32(v) => (*v).into(),
+            V::Uint64(v) if *v > MAX_SAFE_INTEGER => v.to_string().into(),
+            V::Uint64(v) => (*v).into(),
+            V::Int64(v) if v.unsigned_abs() > MAX_SAFE_INTEGER => v.to_string().into(),
+            V::Int64(v) => (*v).into(),
+            // `to_string` is the shortest form that reads back as the same
+            // f32; widening instead would print 0.1 as 0.10000000149011612
+            V::Float32(v) if v.is_finite() => float(v.to_string().parse().unwrap_or(0.0)),
+            V::Float32(v) => float(f64::from(*v)),
+            V::Float64(v) => float(*v),
+            V::Bool(v) => (*v).into(),
+            V::String(v) => v.clone().into(),
+            V::Array(items) => items.iter().map(Value::from).collect(),
+            V::SkippedArray { .. } => Value::Null,
+        }
+    }
+}
+
+fn float(v: f64) -> Value {
+    match v {
+        v if v.is_nan() => "NaN".into(),
+        f64::INFINITY => "Infinity".into(),
+        f64::NEG_INFINITY => "-Infinity".into(),
+        v => v.into(),
+    }
+}
+
+/// A float as rendered by [`float`].
+fn parse_float(json: &Value) -> Option<f64> {
+    match json {
+        Value::String(s) => match s.as_str() {
+            "NaN" => Some(f64::NAN),
+            "Infinity" => Some(f64::INFINITY),
+            "-Infinity" => Some(f64::NEG_INFINITY),
+            _ => None,
+        },
+        _ => json.as_f64(),
+    }
+}
+
+/// A number, or a decimal string for 64-bit values.
+fn parse_int<T: std::str::FromStr + TryFrom<u64> + TryFrom<i64>>(json: &Value) -> Option<T> {
+    match json {
+        Value::String(s) => s.parse().ok(),
+        _ => json
+            .as_u64()
+            .and_then(|v| T::try_from(v).ok())
+            .or_else(|| json.as_i64().and_then(|v| T::try_from(v).ok())),
+    }
+}
+
+impl GGUFValue {
+    /// Read back `json` as rendered by `From<&GGUFValue>`, as a value of
+    /// the same type as `like`.  Array elements take the type of `like`'s
+    /// first element; `None` if `json` doesn't fit the type.
+    pub fn from_json(json: &Value, like: &GGUFValue) -> Option<GGUFValue> {
+        use GGUFValue as V;
+        Some(match like {
+            V::Uint8(_) => V::Uint8(parse_int(json)?),
+            V::Int8(_) => V::Int8(parse_int(json)?),
+            V::Uint16(_) => V::Uint16(parse_int(json)?),
+            V::Int16(_) => V::Int16(parse_int(json)?),
+            V::Uint32(_) => V::Uint32(parse_int(json)?),
+            V::Int32(_) => V::Int32(parse_int(json)?),
+            V::Uint64(_) => V::Uint64(parse_int(json)?),
+            V::Int64(_) => V::Int64(parse_int(json)?),
+            V::Float32(_) => V::Float32(parse_float(json)? as f32),
+            V::Float64(_) => V::Float64(parse_float(json)?),
+            V::Bool(_) => V::Bool(json.as_bool()?),
+            V::String(_) => V::String(json.as_str()?.to_string()),
+            V::Array(items) => {
+                let json = json.as_array()?;
+                match items.first() {
+                    Some(item) => V::Array(
+                        json.iter()
+                            .map(|v| Self::from_json(v, item))
+                            .collect::<Option<_>>()?,
+                    ),
+                    None if json.is_empty() => V::Array(Vec::new()),
+                    None => return None,
+                }
+            }
+            V::SkippedArray { len, elem_type } => {
+                json.is_null().then_some(())?;
+                V::SkippedArray {
+                    len: *len,
+                    elem_type: *elem_type,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::types::GGUFValueType;
+
+    /// Render `value`, check the JSON, and read it back.
+    fn round_trip(value: GGUFValue, expected: Value) {
+        let rendered = Value::from(&value);
+        assert_eq!(rendered, expected, "{value:?}");
+        let back = GGUFValue::from_json(&rendered, &value).unwrap();
+        // Compare through the tagged form: NaN != NaN as a float
+        assert_eq!(
+            serde_json::to_string(&back).unwrap(),
+            serde_json::to_string(&value).unwrap()
+        );
+    }
+
+    #[test]
+    fn every_value_type_round_trips() {
+        use GGUFValue as V;
+
+        round_trip(V::Uint8(u8::MAX), json!(255));
+        round_trip(V::Int8(i8::MIN), json!(-128));
+        round_trip(V::Uint16(u16::MAX), json!(65535));
+        round_trip(V::Int16(i16::MIN), json!(-32768));
+        round_trip(V::Uint32(32768), json!(32768));
+        round_trip(V::Int32(-1), json!(-1));
+        round_trip(V::Uint64(MAX_SAFE_INTEGER), json!(MAX_SAFE_INTEGER));
+        round_trip(V::Uint64(u64::MAX), json!("18446744073709551615"));
+        round_trip(
+            V::Int64(-(MAX_SAFE_INTEGER as i64)),
+            json!(-9007199254740991i64),
+        );
+        round_trip(V::Int64(i64::MIN), json!("-9223372036854775808"));
+        round_trip(V::Float32(0.1), json!(0.1));
+        round_trip(V::Float32(1e6), json!(1e6));
+        round_trip(V::Float32(f32::NAN), json!("NaN"));
+        round_trip(V::Float32(f32::NEG_INFINITY), json!("-Infinity"));
+        round_trip(V::Float64(-2.5e-300), json!(-2.5e-300));
+        round_trip(V::Float64(f64::INFINITY), json!("Infinity"));
+        round_trip(V::Bool(true), json!(true));
+        round_trip(V::String("<s>".into()), json!("<s>"));
+        round_trip(
+            V::Array(vec![V::String("a".into()), V::String("b".into())]),
+            json!(["a", "b"]),
+        );
+        round_trip(
+            V::Array(vec![V::Array(vec![V::Uint64(u64::MAX)])]),
+            json!([["18446744073709551615"]]),
+        );
+        round_trip(V::Array(Vec::new()), json!([]));
+        round_trip(
+            V::SkippedArray {
+                len: 151_936,
+                elem_type: GGUFValueType::String,
+            },
+            Value::Null,
+        );
+    }
+
+    #[test]
+    fn mistyped_json_is_rejected() {
+        use GGUFValue as V;
+
+        assert!(GGUFValue::from_json(&json!(256), &V::Uint8(0)).is_none());
+        assert!(GGUFValue::from_json(&json!(-1), &V::Uint32(0)).is_none());
+        assert!(GGUFValue::from_json(&json!("x"), &V::Float32(0.0)).is_none());
+        assert!(GGUFValue::from_json(&json!(1), &V::String(String::new())).is_none());
+        assert!(GGUFValue::from_json(&json!([1, "x"]), &V::Array(vec![V::Int32(0)])).is_none());
+    }
+}
@@ -1,16 +1,51 @@
 //! Pure-Rust GGUF file format parser.
 //!
 //! Provides fast metadata extraction from `.gguf` files **without**
-//! depending on llama.cpp.  Two modes are supported:
+//! depending on llama.cpp.  Three modes are supported:
 //!
-//! * **quick scan** — reads only the first ~128 KiB to extract model
-//!   name, architecture, quantisation type, context length, etc.
+//! * **quick scan** — reads the metadata near the start of the file,
+//!   skipping vocabulary-sized arrays, to extract model name,
+//!   architecture, quantisation type, context length, etc.
 //! * **directory scan** — recursively discovers all `.gguf` models in
 //!   a directory tree, grouping split files and detecting mmproj
 //!   companions.
+//! * **tensor scan** — skips over the metadata and reads the tensor
+//!   info section (names, shapes, ggml types, offsets).
+//!
+//! The quick and tensor scans also read from any `Read + Seek` source
+//! ([`scan_from`], [`scan_tensors_from`]), e.g. a file still being
+//! downloaded, or from memory ([`quick_scan_bytes`]).
+//!
+//! [`estimate_memory`] turns quick-scan metadata into a rough RAM
+//! footprint (weights, KV cache and attention scores) for capacity planning,
+//! and [`estimate_parameters`] sizes a model from its tensor infos.
+//! [`hash_file`] computes the SHA-256 used for integrity checks, and
+//! [`detect_features`] derives the badges the dashboard shows;
+//! [`parse_model_filename`] stands in for metadata that can't be read.
+//! Metadata values convert to plain JSON (see [`json`]) for API output.
 
+pub mod estimate;
+pub mod features;
+pub mod filename;
+pub mod hash;
+pub mod json;
 pub mod reader;
 pub mod types;
 
-pub use reader::{ModelEntry, QuickScanResult, quick_scan, scan_directory};
-pub use types::{GGUFHeader, GGUFMetadataKV, GGUFValue, GGUFValueType, file_type_name};
+pub use estimate::{
+    ContextShape, KvDims, KvElemSize, MemoryEstimate, estimate_memory, estimate_parameters,
+    estimate_quantized_bytes, ggml_bits_per_weight, ggml_tensor_bytes,
+};
+pub use features::detect_features;
+pub use filename::{FilenameHints, parse_model_filename};
+pub use hash::{hash_file, hash_file_with_progress, is_sha256_hex};
+pub use reader::{
+    FileScan, ModelEntry, NoScanCache, QuickScanResult, ScanCache, ScanOptions, TokenizerMeta,
+    WalkOptions, WeightTotals, disambiguate_ids, disambiguated_id, full_scan, missing_split_parts,
+    quick_scan, quick_scan_bytes, quick_scan_from, scan_directory, scan_directory_cached,
+    scan_file, scan_from, scan_tensors, scan_tensors_from, scan_with, split_part_names,
+};
+pub use types::{
+    Capability, GGUFHeader, GGUFMetadataKV, GGUFValue, GGUFValueType, MetadataSource, ModelFeature,
+    ModelKind, TensorInfo, file_type_name, ggml_type_name, param_count_label,
+};
@@ -1,13 +1,16 @@
 //! GGUF file reader — quick-scan mode for fast metadata extraction.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::{self, BufReader, Read, Seek};
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
+use crate::estimate::{estimate_parameters, estimate_quantized_bytes};
+use crate::features::detect_features;
+use crate::filename::parse_model_filename;
 use crate::types::*;
 
 /// Maximum bytes to read in quick-scan mode.
@@ -16,6 +19,15 @@ use crate::types::*;
 /// still being fast (< 10 ms on modern hardware with OS page cache).
 const QUICK_SCAN_LIMIT: u64 = 8 * 1024 * 1024;
 
+/// Arrays longer than this are skipped by [`quick_scan`].  Per-layer
+/// arrays stay well below it; vocabulary-sized ones (tokens, merges,
+/// scores) are far above.
+const QUICK_SCAN_MAX_ARRAY_LEN: u64 = 1024;
+
+/// Largest `tensor_count` [`scan_tensors`] accepts; real models have a
+/// few thousand tensors.
+const MAX_TENSOR_COUNT: u64 = 1 << 20;
+
 //  Public result types
 
 /// Outcome of a quick scan on a single `.gguf` file.
@@ -31,8 +43,154 @@ pub struct QuickScanResult {
     pub context_length: Option<u32>,
     pub embedding_length: Option<u32>,
     pub chat_template: Option<String>,
+    /// Sum of the element counts of this file's tensors, when every tensor
+    /// info fit within the scan window.  For split models this covers only
+    /// the one part.
+    #[serde(default)]
+    pub param_count: Option<u64>,
     /// All metadata KVs that fit within the scan window.
     pub metadata: Vec<GGUFMetadataKV>,
+    /// The scan window ended before the last metadata KV, so keys may be
+    /// missing; [`full_scan`] reads them all.
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// Vocabulary details from `tokenizer.ggml.*` metadata.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenizerMeta {
+    /// Length of `tokenizer.ggml.tokens`, if it fit in the scan window.
+    pub n_vocab: Option<u32>,
+    pub bos_token_id: Option<u32>,
+    pub eos_token_id: Option<u32>,
+    pub eot_token_id: Option<u32>,
+    pub add_bos_token: Option<bool>,
+}
+
+impl QuickScanResult {
+    /// Value of metadata key `key`, if it was within the scan window.
+    pub fn get(&self, key: &str) -> Option<&GGUFValue> {
+        self.metadata
+            .iter()
+            .find(|kv| kv.key == key)
+            .map(|kv| &kv.value)
+    }
+
+    pub fn tokenizer(&self) -> TokenizerMeta {
+        let id = |key: &str| self.get(key).and_then(GGUFValue::as_u32);
+        TokenizerMeta {
+            n_vocab: match self.get("tokenizer.ggml.tokens") {
+                Some(GGUFValue::Array(tokens)) => Some(tokens.len() as u32),
+                Some(GGUFValue::SkippedArray { len, .. }) => Some(*len as u32),
+                _ => None,
+            },
+            bos_token_id: id("tokenizer.ggml.bos_token_id"),
+            eos_token_id: id("tokenizer.ggml.eos_token_id"),
+            eot_token_id: id("tokenizer.ggml.eot_token_id"),
+            add_bos_token: self
+                .get("tokenizer.ggml.add_bos_token")
+                .and_then(GGUFValue::as_bool),
+        }
+    }
+
+    /// Text of token `id` from `tokenizer.ggml.tokens`; `None` if the scan
+    /// skipped the array.
+    pub fn token_text(&self, id: u32) -> Option<&str> {
+        match self.get("tokenizer.ggml.tokens")? {
+            GGUFValue::Array(tokens) => tokens.get(id as usize)?.as_str(),
+            _ => None,
+        }
+    }
+
+    /// Total parameter count of the model: `general.parameter_count` if
+    /// present, otherwise the tensor element sum for unsplit files.
+    pub fn parameter_count(&self) -> Option<u64> {
+        let meta = self
+            .get("general.parameter_count")
+            .and_then(GGUFValue::as_u64);
+        let n_split = self.get("split.count").and_then(GGUFValue::as_u32);
+        meta.or(self.param_count.filter(|_| n_split.unwrap_or(1) <= 1))
+    }
+
+    /// What the model can be used for, judged from its architecture: CLIP
+    /// files are vision projectors, encoders and models declaring a
+    /// pooling type produce embeddings, everything else generates text.
+    /// LoRA adapters serve nothing on their own.
+    pub fn capabilities(&self) -> Vec<Capability> {
+        match self.kind() {
+            ModelKind::LoraAdapter => return Vec::new(),
+            ModelKind::Mmproj => return vec![Capability::VisionProjector],
+            ModelKind::Base => {}
+        }
+        let arch = self.architecture.as_deref().unwrap_or("llama");
+        let pooled = self.get(&format!("{arch}.pooling_type")).is_some();
+        if pooled || EMBEDDING_ARCHITECTURES.contains(&arch) {
+            return vec![Capability::Embeddings];
+        }
+        vec![Capability::Chat]
+    }
+
+    /// A CLIP/mmproj vision projector: `general.architecture` is `clip`,
+    /// or `clip.*` keys are present.
+    pub fn is_vision_projector(&self) -> bool {
+        self.architecture.as_deref() == Some("clip")
+            || self.metadata.iter().any(|kv| kv.key.starts_with("clip."))
+    }
+
+    /// A LoRA adapter: `general.type` is `adapter` or `adapter.type` is
+    /// `lora`.
+    pub fn is_lora_adapter(&self) -> bool {
+        let str_of = |key: &str| self.get(key).and_then(GGUFValue::as_str);
+        str_of("general.type") == Some("adapter") || str_of("adapter.type") == Some("lora")
+    }
+
+    /// All metadata as plain JSON values by key; see [`crate::json`].
+    pub fn metadata_json(&self) -> serde_json::Map<String, serde_json::Value> {
+        self.metadata
+            .iter()
+            .map(|kv| (kv.key.clone(), serde_json::Value::from(&kv.value)))
+            .collect()
+    }
+
+    /// The sort of file this is, see [`ModelKind`].
+    pub fn kind(&self) -> ModelKind {
+        if self.is_lora_adapter() {
+            ModelKind::LoraAdapter
+        } else if self.is_vision_projector() {
+            ModelKind::Mmproj
+        } else {
+            ModelKind::Base
+        }
+    }
+
+    /// The model a LoRA adapter was trained against:
+    /// `adapter.lora.base_model`, else `general.base_model.0.name`.
+    pub fn adapter_base_model(&self) -> Option<String> {
+        ["adapter.lora.base_model", "general.base_model.0.name"]
+            .iter()
+            .find_map(|key| self.get(key).and_then(GGUFValue::as_str))
+            .map(String::from)
+    }
+
+    /// Whether a field [`scan_directory`] reports is unset, e.g. because
+    /// its key lay past a truncated scan window.
+    fn missing_key_fields(&self) -> bool {
+        self.architecture.is_none()
+            || self.name.is_none()
+            || self.file_type.is_none()
+            || self.context_length.is_none()
+            || self.chat_template.is_none()
+    }
+
+    /// Human parameter count such as `7.6B`, falling back to
+    /// `general.size_label` when the count is unknown.
+    pub fn parameters_label(&self) -> Option<String> {
+        self.parameter_count().map(param_count_label).or_else(|| {
+            self.get("general.size_label")
+                .and_then(GGUFValue::as_str)
+                .map(String::from)
+        })
+    }
 }
 
 /// An entry in the model catalogue produced by [`scan_directory`].
@@ -40,49 +198,159 @@ pub struct QuickScanResult {
 pub struct ModelEntry {
     pub id: String,
     pub name: String,
+    /// Where the scan found the model, possibly through a symlink.
     pub path: PathBuf,
+    /// `path` with symlinks resolved.  Files reachable through several
+    /// paths are listed once.
+    #[serde(default)]
+    pub canonical_path: PathBuf,
+    /// Bytes on disk, summed over all parts of a split model.
     pub file_size: u64,
     pub architecture: Option<String>,
     pub quantization: Option<String>,
     pub context_length: Option<u32>,
+    /// Human parameter count, see [`QuickScanResult::parameters_label`].
+    #[serde(default)]
+    pub parameters: Option<String>,
+    /// Total parameter count: `general.parameter_count` when present,
+    /// otherwise [`estimate_parameters`] over the tensors of every part.
+    #[serde(default)]
+    pub param_count: Option<u64>,
+    /// Average weight storage per parameter, from
+    /// [`estimate_quantized_bytes`]; about 0.6 for `Q4_K_M`.
+    #[serde(default)]
+    pub bytes_per_param: Option<f64>,
+    /// See [`QuickScanResult::capabilities`]; empty if the file couldn't
+    /// be read.
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+    /// See [`QuickScanResult::kind`].
+    #[serde(default)]
+    pub kind: ModelKind,
+    /// Badges such as `moe` or `reasoning`; see [`detect_features`].
+    #[serde(default)]
+    pub features: Vec<ModelFeature>,
+    /// For LoRA adapters, the model they were trained against; see
+    /// [`QuickScanResult::adapter_base_model`].
+    #[serde(default)]
+    pub base_model: Option<String>,
     pub is_split: bool,
+    /// Part files in order of their part number; `path` is the first.
     pub split_parts: Vec<PathBuf>,
+    /// Part numbers (1-based) of a split model not found next to the
+    /// others; such a model can't be loaded.
+    #[serde(default)]
+    pub missing_parts: Vec<u16>,
     pub mmproj_path: Option<PathBuf>,
+    /// Another model in the catalogue has the same file stem; see
+    /// [`disambiguate_ids`].
+    #[serde(default)]
+    pub collision: bool,
+    /// `filename` when the metadata couldn't be read and the fields above
+    /// are guesses from the file name.
+    #[serde(default)]
+    pub metadata_source: MetadataSource,
+    /// Why the metadata couldn't be read.
+    #[serde(default)]
+    pub scan_error: Option<String>,
 }
 
 //  Quick scan
 
+/// How much of a file's metadata a scan reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Metadata KVs starting past this many bytes are not read; the
+    /// result is then flagged `truncated`.
+    pub window: u64,
+    /// Arrays longer than this are stepped over and kept as
+    /// [`GGUFValue::SkippedArray`]; `None` reads every element.
+    pub max_array_len: Option<u64>,
+}
+
+impl ScanOptions {
+    /// What [`quick_scan`] uses: the scan window and array skipping.
+    pub const QUICK: Self = Self {
+        window: QUICK_SCAN_LIMIT,
+        max_array_len: Some(QUICK_SCAN_MAX_ARRAY_LEN),
+    };
+    /// Every KV and every array element.
+    pub const FULL: Self = Self {
+        window: u64::MAX,
+        max_array_len: None,
+    };
+}
+
 /// Read just enough of `path` to extract model metadata.
 ///
-/// Targets < 10 ms per file on modern hardware.
+/// Targets well under a millisecond per file with a warm page cache:
+/// vocabulary-sized arrays are skipped rather than materialised (see
+/// [`ScanOptions::QUICK`]), and metadata past the first
+/// [`QUICK_SCAN_LIMIT`] bytes is not read, in which case the result is
+/// flagged `truncated`.
 pub fn quick_scan(path: &Path) -> Result<QuickScanResult, GGUFError> {
-    let file = fs::File::open(path)?;
-    let file_size = file.metadata()?.len();
-    let limit = file_size.min(QUICK_SCAN_LIMIT);
+    scan_with(path, &ScanOptions::QUICK)
+}
 
-    let mut reader = BufReader::new(file);
+/// Like [`quick_scan`], but reads every metadata KV however far into the
+/// file it goes, arrays included.  Individual strings and arrays are
+/// still size-checked.
+pub fn full_scan(path: &Path) -> Result<QuickScanResult, GGUFError> {
+    scan_with(path, &ScanOptions::FULL)
+}
 
-    //  Magic
-    let magic = read_u32(&mut reader)?;
-    if magic != GGUF_MAGIC {
-        return Err(GGUFError::InvalidMagic(magic));
-    }
+/// Scan the metadata of `path` as `options` say.
+pub fn scan_with(path: &Path, options: &ScanOptions) -> Result<QuickScanResult, GGUFError> {
+    let file = fs::File::open(path)?;
+    let file_size = file.metadata()?.len();
+    let mut scan = scan_from(BufReader::new(file), file_size, options)?;
+    scan.file_path = path.to_path_buf();
+    debug!(
+        path = %path.display(),
+        architecture = ?scan.architecture,
+        name = ?scan.name,
+        truncated = scan.truncated,
+        "scan complete"
+    );
+    Ok(scan)
+}
 
-    //  Version
-    let version = read_u32(&mut reader)?;
-    if version > GGUF_VERSION_MAX {
-        return Err(GGUFError::UnsupportedVersion(version));
-    }
+/// [`quick_scan`] of a GGUF held in `reader`, whose whole size is
+/// `total_size`.
+pub fn quick_scan_from<R: Read + Seek>(
+    reader: R,
+    total_size: u64,
+) -> Result<QuickScanResult, GGUFError> {
+    scan_from(reader, total_size, &ScanOptions::QUICK)
+}
 
-    //  Counts
-    let tensor_count = read_u64(&mut reader)?;
-    let metadata_kv_count = read_u64(&mut reader)?;
+/// [`quick_scan`] of a GGUF in memory.
+pub fn quick_scan_bytes(data: &[u8]) -> Result<QuickScanResult, GGUFError> {
+    quick_scan_from(Cursor::new(data), data.len() as u64)
+}
 
-    let header = GGUFHeader {
-        version,
+/// Scan the metadata of a GGUF held in `reader` as `options` say.
+/// `file_path` of the result is left empty.
+///
+/// `reader` may hold just the start of a file of `total_size` bytes
+/// (e.g. the first chunk of a download): metadata running past its end is
+/// treated like metadata past the scan window, and the result is flagged
+/// `truncated`.
+pub fn scan_from<R: Read + Seek>(
+    mut reader: R,
+    total_size: u64,
+    options: &ScanOptions,
+) -> Result<QuickScanResult, GGUFError> {
+    let limit = total_size.min(options.window);
+    // Bytes actually at hand; fewer than `total_size` for a prefix
+    let available = reader.seek(SeekFrom::End(0))?;
+    reader.rewind()?;
+    let header = read_header(&mut reader)?;
+    let GGUFHeader {
         tensor_count,
         metadata_kv_count,
-    };
+        ..
+    } = header;
 
     //  Read metadata KVs (within the scan window)
     let mut metadata = Vec::new();
@@ -91,13 +359,22 @@ pub fn quick_scan(path: &Path) -> Result<QuickScanResult, GGUFError> {
         if pos >= limit {
             break; // past our quick-scan window
         }
-        match read_kv(&mut reader) {
+        match read_kv(&mut reader, options.max_array_len) {
             Ok(kv) => metadata.push(kv),
             Err(GGUFError::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(GGUFError::TruncatedHeader) if available < total_size => break,
             Err(e) => return Err(e),
         }
     }
 
+    //  Sum tensor shapes (only if all metadata was read)
+    let truncated = (metadata.len() as u64) < metadata_kv_count;
+    let param_count = if !truncated {
+        count_tensor_elements(&mut reader, tensor_count, limit).filter(|&n| n > 0)
+    } else {
+        None
+    };
+
     //  Extract well-known keys
     let kv_map: HashMap<&str, &GGUFValue> = metadata
         .iter()
@@ -130,11 +407,9 @@ pub fn quick_scan(path: &Path) -> Result<QuickScanResult, GGUFError> {
         .and_then(|v| v.as_str())
         .map(String::from);
 
-    debug!(path = %path.display(), architecture = ?architecture, name = ?name, "quick scan complete");
-
     Ok(QuickScanResult {
-        file_path: path.to_path_buf(),
-        file_size,
+        file_path: PathBuf::new(),
+        file_size: total_size,
         header,
         architecture,
         name,
@@ -143,119 +418,664 @@ pub fn quick_scan(path: &Path) -> Result<QuickScanResult, GGUFError> {
         context_length,
         embedding_length,
         chat_template,
+        param_count,
         metadata,
+        truncated,
     })
 }
 
+//  Tensor scan
+
+/// Read the tensor info section of `path`.
+///
+/// Metadata values are skipped over rather than parsed, so this also
+/// works for files whose metadata runs past the quick-scan window (e.g.
+/// very large vocabularies).
+pub fn scan_tensors(path: &Path) -> Result<Vec<TensorInfo>, GGUFError> {
+    scan_tensors_from(BufReader::new(fs::File::open(path)?))
+}
+
+/// [`scan_tensors`] of a GGUF held in `reader`.
+pub fn scan_tensors_from<R: Read + Seek>(mut reader: R) -> Result<Vec<TensorInfo>, GGUFError> {
+    let header = read_header(&mut reader)?;
+    if header.tensor_count > MAX_TENSOR_COUNT {
+        return Err(GGUFError::Other(format!(
+            "tensor count {} too large",
+            header.tensor_count
+        )));
+    }
+    for _ in 0..header.metadata_kv_count {
+        skip_kv(&mut reader)?;
+    }
+    (0..header.tensor_count)
+        .map(|_| read_tensor_info(&mut reader))
+        .collect()
+}
+
 //  Directory scan
 
 /// Recursively discover GGUF models in `dir`.
+///
+/// Vision projectors are recognised by their metadata (see
+/// [`QuickScanResult::is_vision_projector`]) and attached as `mmproj_path`
+/// to the models whose names they share.  Projectors that match no model
+/// are listed as entries of their own, for the user to pair by hand.
+///
+/// The parts of a split model (`name-00001-of-00003.gguf`, …) form one
+/// entry, described by the lowest-numbered part present; parts that
+/// aren't there are listed in `missing_parts`.
+///
+/// Symlinked files are followed, symlinked directories only if
+/// [`WalkOptions::follow_dir_symlinks`] is set.  A file reachable through
+/// several paths (symlinks, hard links) is listed once, under the path
+/// that involves no symlink if there is one.
 pub fn scan_directory(dir: &Path) -> Result<Vec<ModelEntry>, GGUFError> {
-    let mut gguf_files: Vec<PathBuf> = Vec::new();
-    walk_dir(dir, &mut gguf_files)?;
-    gguf_files.sort();
+    scan_directory_cached(dir, WalkOptions::default(), &mut NoScanCache)
+}
+
+/// How [`scan_directory`] walks the directory tree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WalkOptions {
+    /// Descend into symlinked directories.  Directories already walked
+    /// (e.g. a link back to a parent) are skipped, so cycles end.
+    pub follow_dir_symlinks: bool,
+}
+
+/// What [`scan_directory`] reads from one file, see [`scan_file`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileScan {
+    /// Quick scan of the metadata; `None` if the file couldn't be read.
+    pub scan: Option<QuickScanResult>,
+    /// Weights of the file's tensors; `None` if their infos couldn't be
+    /// read.
+    pub weights: Option<WeightTotals>,
+    /// Why the quick scan failed, if it did.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Parameters and weight storage summed over a file's tensors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeightTotals {
+    /// See [`estimate_parameters`].
+    pub params: u64,
+    /// See [`estimate_quantized_bytes`].
+    pub bytes: u64,
+}
+
+/// Keeps [`FileScan`]s between [`scan_directory_cached`] runs, so files
+/// that haven't changed aren't read again.  Implementations decide when
+/// a stored scan is stale, e.g. by the file's size and mtime.
+pub trait ScanCache {
+    /// The stored scan of `path`, if still current.
+    fn get(&mut self, path: &Path) -> Option<FileScan>;
+    /// Store a fresh scan of `path`.
+    fn put(&mut self, path: &Path, scan: &FileScan);
+}
+
+/// A [`ScanCache`] that keeps nothing: every file is read.
+pub struct NoScanCache;
+
+impl ScanCache for NoScanCache {
+    fn get(&mut self, _path: &Path) -> Option<FileScan> {
+        None
+    }
+
+    fn put(&mut self, _path: &Path, _scan: &FileScan) {}
+}
+
+/// Read what [`scan_directory`] needs from `path`: a quick scan, redone
+/// without a window if it cut off keys the catalogue shows, and the
+/// weights of the tensors.
+pub fn scan_file(path: &Path) -> FileScan {
+    let scan = quick_scan(path);
+    let error = scan.as_ref().err().map(|e| {
+        debug!(path = %path.display(), error = %e, "quick scan failed");
+        e.to_string()
+    });
+    let scan = scan.ok().map(|scan| {
+        if scan.truncated && scan.missing_key_fields() {
+            debug!(path = %path.display(), "metadata past the quick-scan window; rescanning");
+            let unbounded = ScanOptions {
+                window: u64::MAX,
+                ..ScanOptions::QUICK
+            };
+            scan_with(path, &unbounded).unwrap_or(scan)
+        } else {
+            scan
+        }
+    });
+    let weights = match scan_tensors(path) {
+        Ok(tensors) => Some(WeightTotals {
+            params: estimate_parameters(&tensors),
+            bytes: estimate_quantized_bytes(&tensors),
+        }),
+        Err(e) => {
+            debug!(path = %path.display(), error = %e, "no tensor infos; weights not estimated");
+            None
+        }
+    };
+    FileScan {
+        scan,
+        weights,
+        error,
+    }
+}
+
+/// [`scan_directory`], taking file scans from `cache` where it has them
+/// and storing the rest.
+pub fn scan_directory_cached(
+    dir: &Path,
+    walk: WalkOptions,
+    cache: &mut dyn ScanCache,
+) -> Result<Vec<ModelEntry>, GGUFError> {
+    let gguf_files = walk_dir(dir, walk)?;
+
+    let mut files: HashMap<&Path, FileScan> = HashMap::new();
+    for path in &gguf_files {
+        let file = cache.get(path).unwrap_or_else(|| {
+            let file = scan_file(path);
+            cache.put(path, &file);
+            file
+        });
+        files.insert(path, file);
+    }
+
+    // Split sets by base path and part count → (part number, path)
+    let mut split_sets: HashMap<(PathBuf, u16), Vec<(u16, PathBuf)>> = HashMap::new();
+    for path in &gguf_files {
+        let fname = path.file_name().unwrap_or_default().to_string_lossy();
+        if let Some(split) = parse_split_name(&fname) {
+            split_sets
+                .entry((path.with_file_name(&split.base), split.count))
+                .or_default()
+                .push((split.index, path.clone()));
+        }
+    }
+    for parts in split_sets.values_mut() {
+        parts.sort();
+    }
 
     let mut entries: Vec<ModelEntry> = Vec::new();
-    let mut seen_bases: HashMap<String, usize> = HashMap::new();
+    // Name keys and scans of each model entry, for matching projectors
+    // and detecting features once they're matched
+    let mut model_keys: Vec<Vec<Vec<String>>> = Vec::new();
+    let mut model_scans: Vec<Option<QuickScanResult>> = Vec::new();
+    let mut projectors: Vec<(PathBuf, Option<QuickScanResult>, Option<String>)> = Vec::new();
 
     for path in &gguf_files {
         let fname = path.file_name().unwrap_or_default().to_string_lossy();
 
-        // Skip mmproj companion files (handled below).
-        if fname.contains("-mmproj-") || fname.contains("_mmproj_") {
+        let split = parse_split_name(&fname);
+        let parts = split
+            .as_ref()
+            .map(|s| &split_sets[&(path.with_file_name(&s.base), s.count)]);
+        // The other parts are gathered into the first one's entry
+        if let Some(parts) = parts
+            && parts[0].1 != *path
+        {
             continue;
         }
 
-        // Detect split files: `name-00001-of-00003.gguf`
-        if let Some(base) = detect_split_base(&fname) {
-            if let Some(&idx) = seen_bases.get(&base) {
-                entries[idx].split_parts.push(path.clone());
-                entries[idx].is_split = true;
-                continue;
-            }
-            // First part of a split set — create entry.
-            seen_bases.insert(base.clone(), entries.len());
+        // Weights stay behind for `estimate_weights`
+        let (scan, error) = files
+            .get_mut(path.as_path())
+            .map_or((None, None), |f| (f.scan.take(), f.error.take()));
+
+        // Unreadable files fall back to the conventional file name
+        let is_projector = scan.as_ref().map_or_else(
+            || fname.contains("-mmproj-") || fname.contains("_mmproj_"),
+            |scan| scan.kind() == ModelKind::Mmproj,
+        );
+        if is_projector {
+            projectors.push((path.clone(), scan, error));
+            continue;
         }
 
-        let scan = quick_scan(path).ok();
-        let name = scan
-            .as_ref()
-            .and_then(|s| s.name.clone())
-            .unwrap_or_else(|| fname.trim_end_matches(".gguf").to_string());
-
-        let id = generate_model_id(path);
-
-        entries.push(ModelEntry {
-            id,
-            name,
-            path: path.clone(),
-            file_size: scan.as_ref().map_or(0, |s| s.file_size),
-            architecture: scan.as_ref().and_then(|s| s.architecture.clone()),
-            quantization: scan.as_ref().and_then(|s| s.file_type_name.clone()),
-            context_length: scan.as_ref().and_then(|s| s.context_length),
-            is_split: false,
-            split_parts: vec![path.clone()],
-            mmproj_path: None,
-        });
+        let mut entry = new_entry(path, scan.as_ref(), error);
+        if let (Some(split), Some(parts)) = (split, parts) {
+            add_split_parts(&mut entry, split.count, parts);
+        }
+        model_keys.push(name_keys(path, scan.as_ref()));
+        model_scans.push(scan);
+        entries.push(entry);
     }
 
-    // Associate mmproj files with their parent model.
-    for path in &gguf_files {
-        let fname = path.file_name().unwrap_or_default().to_string_lossy();
-        if !fname.contains("-mmproj-") && !fname.contains("_mmproj_") {
-            continue;
+    // Associate projectors with their models.
+    for (path, scan, error) in projectors {
+        let keys = name_keys(&path, scan.as_ref());
+        let matched = match_projector(&entries[..model_keys.len()], &model_keys, &path, &keys);
+        if matched.is_empty() {
+            debug!(path = %path.display(), "no model for vision projector; listing it on its own");
+            let mut entry = new_entry(&path, scan.as_ref(), error);
+            entry.capabilities = vec![Capability::VisionProjector];
+            entry.kind = ModelKind::Mmproj;
+            entries.push(entry);
         }
-        // Try to match by directory (sibling) and name prefix.
-        let parent = path.parent();
-        for entry in &mut entries {
-            if entry.path.parent() == parent && entry.mmproj_path.is_none() {
-                entry.mmproj_path = Some(path.clone());
-                break;
-            }
+        for idx in matched {
+            entries[idx].mmproj_path.get_or_insert_with(|| path.clone());
         }
     }
 
+    for (entry, scan) in entries.iter_mut().zip(&model_scans) {
+        if let Some(scan) = scan {
+            entry.features = detect_features(scan, entry.mmproj_path.is_some());
+        }
+    }
+
+    for entry in &mut entries {
+        estimate_weights(entry, &files);
+    }
+
+    disambiguate_ids(&mut entries);
     Ok(entries)
 }
 
+/// A catalogue entry for `path`, filled in from `scan`, or from the file
+/// name if the scan failed with `scan_error`.
+fn new_entry(
+    path: &Path,
+    scan: Option<&QuickScanResult>,
+    scan_error: Option<String>,
+) -> ModelEntry {
+    let fname = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = scan
+        .and_then(|s| s.name.clone())
+        .unwrap_or_else(|| fname.trim_end_matches(".gguf").to_string());
+
+    let mut entry = ModelEntry {
+        id: generate_model_id(path),
+        name,
+        path: path.to_path_buf(),
+        canonical_path: fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+        file_size: scan.map_or(0, |s| s.file_size),
+        architecture: scan.and_then(|s| s.architecture.clone()),
+        quantization: scan.and_then(|s| s.file_type_name.clone()),
+        context_length: scan.and_then(|s| s.context_length),
+        parameters: scan.and_then(QuickScanResult::parameters_label),
+        param_count: scan.and_then(QuickScanResult::parameter_count),
+        bytes_per_param: None,
+        capabilities: scan.map(QuickScanResult::capabilities).unwrap_or_default(),
+        kind: scan.map_or(ModelKind::Base, QuickScanResult::kind),
+        features: Vec::new(),
+        base_model: scan
+            .filter(|s| s.is_lora_adapter())
+            .and_then(QuickScanResult::adapter_base_model),
+        is_split: false,
+        split_parts: vec![path.to_path_buf()],
+        missing_parts: Vec::new(),
+        mmproj_path: None,
+        collision: false,
+        metadata_source: MetadataSource::Scanned,
+        scan_error: None,
+    };
+    if scan.is_none() {
+        fill_from_filename(&mut entry, &fname);
+        entry.scan_error = scan_error;
+    }
+    entry
+}
+
+/// Stand in for metadata that couldn't be read with guesses from the
+/// file name (see [`parse_model_filename`]).
+fn fill_from_filename(entry: &mut ModelEntry, file_name: &str) {
+    let hints = parse_model_filename(file_name);
+    entry.file_size = fs::metadata(&entry.path).map_or(0, |m| m.len());
+    entry.parameters = hints.parameters;
+    entry.param_count = hints.param_count;
+    entry.quantization = hints.quantization;
+    if hints.instruct {
+        entry.features = vec![ModelFeature::Instruct];
+    }
+    entry.metadata_source = MetadataSource::Filename;
+}
+
+/// Make `entry` cover the split set `parts` of `count` parts: list them,
+/// note the ones missing and add up their sizes.
+fn add_split_parts(entry: &mut ModelEntry, count: u16, parts: &[(u16, PathBuf)]) {
+    entry.is_split = count > 1;
+    entry.split_parts = parts.iter().map(|(_, path)| path.clone()).collect();
+    entry.missing_parts = (1..=count)
+        .filter(|i| !parts.iter().any(|(index, _)| index == i))
+        .collect();
+    entry.file_size = parts
+        .iter()
+        .map(|(_, path)| fs::metadata(path).map_or(0, |m| m.len()))
+        .sum();
+    if !entry.missing_parts.is_empty() {
+        debug!(id = %entry.id, missing = ?entry.missing_parts, "split model is incomplete");
+    }
+}
+
+/// Names a file goes by, as lowercase tokens split on `-`, `_` and
+/// spaces: its file stem and `general.name` / `general.basename`, plus
+/// `general.base_model.0.name` for projectors.  `mmproj` tokens are
+/// dropped so a projector's name lines up with its model's.
+fn name_keys(path: &Path, scan: Option<&QuickScanResult>) -> Vec<Vec<String>> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut names = vec![stem.into_owned()];
+    if let Some(scan) = scan {
+        for key in [
+            "general.name",
+            "general.basename",
+            "general.base_model.0.name",
+        ] {
+            if let Some(name) = scan.get(key).and_then(GGUFValue::as_str) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+        .iter()
+        .map(|name| {
+            name.to_lowercase()
+                .split(['-', '_', ' '])
+                .filter(|t| !t.is_empty() && *t != "mmproj")
+                .map(String::from)
+                .collect::<Vec<_>>()
+        })
+        .filter(|tokens| !tokens.is_empty())
+        .collect()
+}
+
+/// Indices of the models (out of `models`, with [`name_keys`] in `keys`)
+/// that the projector at `path` belongs to.  LoRA adapters are never
+/// picked.
+///
+/// The models sharing the longest leading run of name tokens with the
+/// projector win; several tie when they are quantisations of the same
+/// model, and all of them get it.  A single shared token (`qwen2.5` of
+/// `Qwen2.5-VL`) only counts if it is the whole name.  Without any shared
+/// name, the projector goes to the only model in its directory, if there
+/// is just one.
+fn match_projector(
+    models: &[ModelEntry],
+    keys: &[Vec<Vec<String>>],
+    path: &Path,
+    projector_keys: &[Vec<String>],
+) -> Vec<usize> {
+    let shared_prefix = |a: &[String], b: &[String]| {
+        let n = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+        if n >= 2 || n == a.len() || n == b.len() {
+            n
+        } else {
+            0
+        }
+    };
+    let is_model = |i: usize| models[i].kind == ModelKind::Base;
+    let scores: Vec<usize> = keys
+        .iter()
+        .enumerate()
+        .map(|(i, model_keys)| {
+            if !is_model(i) {
+                return 0;
+            }
+            model_keys
+                .iter()
+                .flat_map(|m| projector_keys.iter().map(|p| shared_prefix(m, p)))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let best = scores.iter().copied().max().unwrap_or(0);
+    if best > 0 {
+        return (0..models.len()).filter(|&i| scores[i] == best).collect();
+    }
+    let siblings: Vec<usize> = (0..models.len())
+        .filter(|&i| is_model(i) && models[i].path.parent() == path.parent())
+        .collect();
+    match siblings.as_slice() {
+        [only] => vec![*only],
+        _ => Vec::new(),
+    }
+}
+
+/// Fill in `param_count` and `bytes_per_param` of `entry` from the
+/// weights of all its parts in `files`.  A count already taken from
+/// `general.parameter_count` is kept; a mismatch is only logged.
+/// Incomplete split models are left alone.
+fn estimate_weights(entry: &mut ModelEntry, files: &HashMap<&Path, FileScan>) {
+    if !entry.missing_parts.is_empty() {
+        return;
+    }
+    let mut totals = WeightTotals::default();
+    for part in &entry.split_parts {
+        let Some(weights) = files.get(part.as_path()).and_then(|f| f.weights) else {
+            return;
+        };
+        totals.params += weights.params;
+        totals.bytes += weights.bytes;
+    }
+    let estimated = totals.params;
+    if estimated == 0 {
+        return;
+    }
+    match entry.param_count {
+        Some(declared) if declared != estimated => debug!(
+            id = %entry.id,
+            declared,
+            estimated,
+            "general.parameter_count disagrees with the tensor shapes"
+        ),
+        Some(_) => {}
+        None => {
+            entry.param_count = Some(estimated);
+            entry.parameters = Some(param_count_label(estimated));
+        }
+    }
+    entry.bytes_per_param = Some(totals.bytes as f64 / estimated as f64);
+}
+
+/// Make model ids unique (case-insensitively) across `entries`.
+///
+/// The first entry with a given id keeps it; later ones get a short hash
+/// of their parent directory appended (see [`disambiguated_id`]).  All
+/// entries involved are flagged with `collision`.
+pub fn disambiguate_ids(entries: &mut [ModelEntry]) {
+    let mut first: HashMap<String, usize> = HashMap::new();
+    for i in 0..entries.len() {
+        let key = entries[i].id.to_lowercase();
+        let Some(&owner) = first.get(&key) else {
+            first.insert(key, i);
+            continue;
+        };
+        entries[owner].collision = true;
+        let entry = &mut entries[i];
+        entry.collision = true;
+        entry.id = disambiguated_id(&entry.id, &entry.path);
+        if first.contains_key(&entry.id.to_lowercase()) {
+            // Same stem in the same directory (e.g. `.gguf` vs `.GGUF`).
+            entry.id = format!("{}-{:06x}", entry.id, short_hash(&entry.path));
+        }
+        first.insert(entry.id.to_lowercase(), i);
+    }
+}
+
+/// `id` suffixed with a short, stable hash of `path`'s parent directory.
+pub fn disambiguated_id(id: &str, path: &Path) -> String {
+    format!("{id}-{:06x}", short_hash(path.parent().unwrap_or(path)))
+}
+
+/// 24-bit FNV-1a hash of a path, stable across runs and platforms.
+fn short_hash(path: &Path) -> u32 {
+    let hash = path
+        .to_string_lossy()
+        .bytes()
+        .fold(0x811c_9dc5_u32, |h, b| {
+            (h ^ u32::from(b)).wrapping_mul(0x0100_0193)
+        });
+    hash & 0x00ff_ffff
+}
+
 //  Internal helpers
 
-fn walk_dir(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), GGUFError> {
+/// Collect the `.gguf` files under `dir`, one path per file, sorted.
+///
+/// Symlinks are resolved after the real tree is walked, so a file
+/// reachable both ways is listed under its real path.
+fn walk_dir(dir: &Path, options: WalkOptions) -> Result<Vec<PathBuf>, GGUFError> {
     if !dir.is_dir() {
-        return Ok(());
+        return Ok(Vec::new());
     }
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_dir() {
-            walk_dir(&path, out)?;
-        } else if path.extension().and_then(|e| e.to_str()) == Some("gguf") {
-            out.push(path);
+    let mut walk = Walk {
+        options,
+        visited: HashSet::new(),
+        linked_dirs: Vec::new(),
+        linked_files: Vec::new(),
+        files: Vec::new(),
+    };
+    walk.tree(dir)?;
+    let mut files = std::mem::take(&mut walk.files);
+    files.sort();
+    let mut linked = Vec::new();
+    while !walk.linked_dirs.is_empty() {
+        for dir in std::mem::take(&mut walk.linked_dirs) {
+            walk.tree(&dir)?;
         }
+        linked.append(&mut walk.files);
     }
-    Ok(())
+    linked.append(&mut walk.linked_files);
+    linked.sort();
+
+    // Real paths first, so they win over links to the same file
+    files.append(&mut linked);
+    let mut seen = HashSet::new();
+    files.retain(|path| {
+        let first = FileId::of(path).is_none_or(|id| seen.insert(id));
+        if !first {
+            debug!(path = %path.display(), "file already found through another path");
+        }
+        first
+    });
+    files.sort();
+    Ok(files)
+}
+
+struct Walk {
+    options: WalkOptions,
+    /// Canonical paths of the directories walked so far.
+    visited: HashSet<PathBuf>,
+    linked_dirs: Vec<PathBuf>,
+    linked_files: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+}
+
+impl Walk {
+    fn tree(&mut self, dir: &Path) -> Result<(), GGUFError> {
+        if !self.visited.insert(fs::canonicalize(dir)?) {
+            debug!(dir = %dir.display(), "directory already walked; skipping");
+            return Ok(());
+        }
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let is_gguf = path.extension().and_then(|e| e.to_str()) == Some("gguf");
+            let file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                match fs::metadata(&path) {
+                    Ok(target) if target.is_dir() && self.options.follow_dir_symlinks => {
+                        self.linked_dirs.push(path);
+                    }
+                    Ok(target) if target.is_dir() => {
+                        debug!(path = %path.display(), "not following directory symlink");
+                    }
+                    Ok(target) if target.is_file() && is_gguf => self.linked_files.push(path),
+                    Ok(_) => {}
+                    Err(e) => debug!(path = %path.display(), error = %e, "dangling symlink"),
+                }
+            } else if file_type.is_dir() {
+                self.tree(&path)?;
+            } else if is_gguf {
+                self.files.push(path);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Identity of the file behind a path: device and inode where there are
+/// such, otherwise the canonical path.
+#[derive(PartialEq, Eq, Hash)]
+enum FileId {
+    #[cfg(unix)]
+    Inode(u64, u64),
+    #[cfg(not(unix))]
+    Path(PathBuf),
 }
 
-fn detect_split_base(filename: &str) -> Option<String> {
-    // Pattern: `<base>-NNNNN-of-NNNNN.gguf`
+impl FileId {
+    fn of(path: &Path) -> Option<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let meta = fs::metadata(path).ok()?;
+            Some(Self::Inode(meta.dev(), meta.ino()))
+        }
+        #[cfg(not(unix))]
+        {
+            fs::canonicalize(path).ok().map(Self::Path)
+        }
+    }
+}
+
+/// A split part's file name: `<base>-NNNNN-of-NNNNN.gguf`.
+struct SplitName {
+    base: String,
+    /// 1-based part number.
+    index: u16,
+    count: u16,
+}
+
+fn parse_split_name(filename: &str) -> Option<SplitName> {
     let name = filename.strip_suffix(".gguf")?;
-    let parts: Vec<&str> = name.rsplitn(4, '-').collect();
-    if parts.len() >= 4
-        && parts[0].chars().all(|c| c.is_ascii_digit())
-        && parts[1] == "of"
-        && parts[2].chars().all(|c| c.is_ascii_digit())
-    {
-        Some(
-            parts[3..]
-                .iter()
-                .rev()
-                .copied()
-                .collect::<Vec<_>>()
-                .join("-"),
-        )
-    } else {
-        None
+    let (rest, count) = name.rsplit_once("-of-")?;
+    let (base, index) = rest.rsplit_once('-')?;
+    let number = |s: &str| {
+        s.chars()
+            .all(|c| c.is_ascii_digit())
+            .then(|| s.parse::<u16>().ok())
+            .flatten()
+    };
+    let (index, count) = (number(index)?, number(count)?);
+    if base.is_empty() || index == 0 || index > count {
+        return None;
     }
+    Some(SplitName {
+        base: base.to_string(),
+        index,
+        count,
+    })
+}
+
+/// Part numbers of the split model `path` (any of its parts) that don't
+/// exist next to it; empty for files that aren't split parts.
+pub fn missing_split_parts(path: &Path) -> Vec<u16> {
+    let fname = path.file_name().unwrap_or_default().to_string_lossy();
+    let Some(names) = split_part_names(&fname) else {
+        return Vec::new();
+    };
+    (1..)
+        .zip(names)
+        .filter(|(_, name)| !path.with_file_name(name).is_file())
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// All part file names of a split model, given the name of any part.
+///
+/// `x-00002-of-00003.gguf` → `x-00001-of-00003.gguf` … `x-00003-of-00003.gguf`.
+/// Returns `None` for files that aren't split parts.
+pub fn split_part_names(filename: &str) -> Option<Vec<String>> {
+    let SplitName { base, count, .. } = parse_split_name(filename)?;
+    let name = filename.strip_suffix(".gguf")?;
+    let (_, count_digits) = name.rsplit_once("-of-")?;
+    let width = count_digits.len();
+    Some(
+        (1..=count)
+            .map(|i| format!("{base}-{i:0width$}-of-{count:0width$}.gguf"))
+            .collect(),
+    )
 }
 
 fn generate_model_id(path: &Path) -> String {
@@ -266,6 +1086,20 @@ fn generate_model_id(path: &Path) -> String {
         .replace(' ', "-")
 }
 
+/// Sum of the element counts of the `tensor_count` tensor infos at the
+/// reader's position, or `None` if they run past `limit` or don't parse.
+fn count_tensor_elements(r: &mut (impl Read + Seek), tensor_count: u64, limit: u64) -> Option<u64> {
+    let mut total: u64 = 0;
+    for _ in 0..tensor_count {
+        if r.stream_position().ok()? >= limit {
+            return None;
+        }
+        let info = read_tensor_info(r).ok()?;
+        total = total.checked_add(info.n_elements())?;
+    }
+    Some(total)
+}
+
 //  Binary reading primitives
 
 fn read_u32(r: &mut impl Read) -> Result<u32, GGUFError> {
@@ -345,7 +1179,13 @@ fn read_bool(r: &mut impl Read) -> Result<bool, GGUFError> {
     Ok(v != 0)
 }
 
-fn read_value(r: &mut impl Read, vtype: GGUFValueType) -> Result<GGUFValue, GGUFError> {
+/// Read a value of type `vtype`; arrays longer than `max_array_len` are
+/// skipped over.
+fn read_value(
+    r: &mut (impl Read + Seek),
+    vtype: GGUFValueType,
+    max_array_len: Option<u64>,
+) -> Result<GGUFValue, GGUFError> {
     match vtype {
         GGUFValueType::Uint8 => Ok(GGUFValue::Uint8(read_u8(r)?)),
         GGUFValueType::Int8 => Ok(GGUFValue::Int8(read_i8(r)?)),
@@ -358,13 +1198,18 @@ fn read_value(r: &mut impl Read, vtype: GGUFValueType) -> Result<GGUFValue, GGUF
         GGUFValueType::String => Ok(GGUFValue::String(read_string(r)?)),
         GGUFValueType::Array => {
             let elem_type = GGUFValueType::try_from(read_u32(r)?)?;
-            let count = read_u64(r)? as usize;
+            let len = read_u64(r)?;
+            if max_array_len.is_some_and(|max| len > max) {
+                skip_array(r, elem_type, len)?;
+                return Ok(GGUFValue::SkippedArray { len, elem_type });
+            }
+            let count = len as usize;
             if count > 10_000_000 {
                 return Err(GGUFError::Other(format!("array length {count} too large")));
             }
             let mut arr = Vec::with_capacity(count.min(1024));
             for _ in 0..count {
-                arr.push(read_value(r, elem_type)?);
+                arr.push(read_value(r, elem_type, max_array_len)?);
             }
             Ok(GGUFValue::Array(arr))
         }
@@ -374,14 +1219,755 @@ fn read_value(r: &mut impl Read, vtype: GGUFValueType) -> Result<GGUFValue, GGUF
     }
 }
 
-fn read_kv(r: &mut impl Read) -> Result<GGUFMetadataKV, GGUFError> {
+fn read_header(r: &mut impl Read) -> Result<GGUFHeader, GGUFError> {
+    let magic = read_u32(r)?;
+    if magic != GGUF_MAGIC {
+        return Err(GGUFError::InvalidMagic(magic));
+    }
+    let version = read_u32(r)?;
+    if version > GGUF_VERSION_MAX {
+        return Err(GGUFError::UnsupportedVersion(version));
+    }
+    Ok(GGUFHeader {
+        version,
+        tensor_count: read_u64(r)?,
+        metadata_kv_count: read_u64(r)?,
+    })
+}
+
+fn read_tensor_info(r: &mut impl Read) -> Result<TensorInfo, GGUFError> {
+    let name = read_string(r)?;
+    let n_dims = read_u32(r)?;
+    if n_dims > GGML_MAX_DIMS {
+        return Err(GGUFError::Other(format!(
+            "tensor '{name}' has {n_dims} dimensions"
+        )));
+    }
+    let mut dims = [1u64; GGML_MAX_DIMS as usize];
+    for d in dims.iter_mut().take(n_dims as usize) {
+        *d = read_u64(r)?;
+    }
+    Ok(TensorInfo {
+        name,
+        n_dims,
+        dims,
+        ggml_type: read_u32(r)?,
+        offset: read_u64(r)?,
+    })
+}
+
+//  Skipping (for values a scan doesn't keep)
+
+/// Encoded size of a fixed-size value type.
+fn fixed_size(vtype: GGUFValueType) -> Option<u64> {
+    match vtype {
+        GGUFValueType::Uint8 | GGUFValueType::Int8 | GGUFValueType::Bool => Some(1),
+        GGUFValueType::Uint16 | GGUFValueType::Int16 => Some(2),
+        GGUFValueType::Uint32 | GGUFValueType::Int32 | GGUFValueType::Float32 => Some(4),
+        GGUFValueType::Uint64 | GGUFValueType::Int64 | GGUFValueType::Float64 => Some(8),
+        GGUFValueType::String | GGUFValueType::Array => None,
+    }
+}
+
+fn skip_bytes(r: &mut impl Seek, n: u64) -> Result<(), GGUFError> {
+    let n = i64::try_from(n).map_err(|_| GGUFError::Other(format!("skip of {n} bytes")))?;
+    r.seek_relative(n)?;
+    Ok(())
+}
+
+fn skip_string(r: &mut (impl Read + Seek)) -> Result<(), GGUFError> {
+    let len = read_u64(r)?;
+    skip_bytes(r, len)
+}
+
+fn skip_value(r: &mut (impl Read + Seek), vtype: GGUFValueType) -> Result<(), GGUFError> {
+    if let Some(size) = fixed_size(vtype) {
+        return skip_bytes(r, size);
+    }
+    match vtype {
+        GGUFValueType::String => skip_string(r),
+        _ => {
+            let elem_type = GGUFValueType::try_from(read_u32(r)?)?;
+            let count = read_u64(r)?;
+            skip_array(r, elem_type, count)
+        }
+    }
+}
+
+/// Step over the `count` elements of an array whose header was read:
+/// one seek for fixed-size elements, one per element for strings.
+fn skip_array(
+    r: &mut (impl Read + Seek),
+    elem_type: GGUFValueType,
+    count: u64,
+) -> Result<(), GGUFError> {
+    match fixed_size(elem_type) {
+        Some(size) => skip_bytes(
+            r,
+            count
+                .checked_mul(size)
+                .ok_or_else(|| GGUFError::Other(format!("array length {count} too large")))?,
+        ),
+        None => (0..count).try_for_each(|_| skip_value(r, elem_type)),
+    }
+}
+
+fn skip_kv(r: &mut (impl Read + Seek)) -> Result<(), GGUFError> {
+    skip_string(r)?;
+    let vtype = GGUFValueType::try_from(read_u32(r)?)?;
+    skip_value(r, vtype)
+}
+
+fn read_kv(
+    r: &mut (impl Read + Seek),
+    max_array_len: Option<u64>,
+) -> Result<GGUFMetadataKV, GGUFError> {
     let key = read_string(r)?;
     let vtype_raw = read_u32(r)?;
     let vtype = GGUFValueType::try_from(vtype_raw)?;
-    let value = read_value(r, vtype)?;
+    let value = read_value(r, vtype, max_array_len)?;
     Ok(GGUFMetadataKV {
         key,
         value_type: vtype,
         value,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal GGUF file built by hand, written as `<name>.gguf` into a
+    /// temporary directory of its own.
+    struct Fixture {
+        data: Vec<u8>,
+        dir: PathBuf,
+        path: PathBuf,
+    }
+
+    impl Fixture {
+        fn new(name: &str, n_tensors: u64, n_kvs: u64) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("gguf-parser-{}-{name}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let mut f = Self {
+                data: Vec::new(),
+                path: dir.join(format!("{name}.gguf")),
+                dir,
+            };
+            f.u32(GGUF_MAGIC).u32(3).u64(n_tensors).u64(n_kvs);
+            f
+        }
+
+        fn u32(&mut self, v: u32) -> &mut Self {
+            self.data.extend_from_slice(&v.to_le_bytes());
+            self
+        }
+
+        fn u64(&mut self, v: u64) -> &mut Self {
+            self.data.extend_from_slice(&v.to_le_bytes());
+            self
+        }
+
+        fn str(&mut self, s: &str) -> &mut Self {
+            self.u64(s.len() as u64);
+            self.data.extend_from_slice(s.as_bytes());
+            self
+        }
+
+        fn kv_u32(&mut self, key: &str, value: u32) -> &mut Self {
+            self.str(key).u32(GGUFValueType::Uint32 as u32).u32(value)
+        }
+
+        fn kv_str(&mut self, key: &str, value: &str) -> &mut Self {
+            self.str(key).u32(GGUFValueType::String as u32).str(value)
+        }
+
+        fn kv_array(&mut self, key: &str, elem: GGUFValueType, items: &[&str]) -> &mut Self {
+            self.str(key)
+                .u32(GGUFValueType::Array as u32)
+                .u32(elem as u32)
+                .u64(items.len() as u64);
+            for item in items {
+                match elem {
+                    GGUFValueType::String => self.str(item),
+                    _ => self.u32(item.parse().unwrap()),
+                };
+            }
+            self
+        }
+
+        fn tensor(&mut self, name: &str, dims: &[u64], ggml_type: u32, offset: u64) -> &mut Self {
+            self.str(name).u32(dims.len() as u32);
+            for &d in dims {
+                self.u64(d);
+            }
+            self.u32(ggml_type).u64(offset)
+        }
+
+        fn write(&self) -> &Path {
+            fs::write(&self.path, &self.data).unwrap();
+            &self.path
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn tensor_infos_follow_skipped_metadata() {
+        let mut f = Fixture::new("tensors", 2, 3);
+        f.kv_str("general.architecture", "llama")
+            .kv_array(
+                "tokenizer.ggml.tokens",
+                GGUFValueType::String,
+                &["<s>", "</s>", "hello"],
+            )
+            .kv_array(
+                "llama.feed_forward_length",
+                GGUFValueType::Uint32,
+                &["11008", "11008"],
+            )
+            .tensor("token_embd.weight", &[4096, 32000], 12, 0)
+            .tensor("output_norm.weight", &[4096], 0, 70_778_880);
+
+        let tensors = scan_tensors_from(Cursor::new(&f.data)).unwrap();
+        assert_eq!(
+            tensors,
+            [
+                TensorInfo {
+                    name: "token_embd.weight".into(),
+                    n_dims: 2,
+                    dims: [4096, 32000, 1, 1],
+                    ggml_type: 12,
+                    offset: 0,
+                },
+                TensorInfo {
+                    name: "output_norm.weight".into(),
+                    n_dims: 1,
+                    dims: [4096, 1, 1, 1],
+                    ggml_type: 0,
+                    offset: 70_778_880,
+                },
+            ]
+        );
+        assert_eq!(ggml_type_name(tensors[0].ggml_type), "Q4_K");
+
+        // The quick scan agrees on the parameter count
+        let scan = quick_scan_bytes(&f.data).unwrap();
+        assert_eq!(scan.metadata.len(), 3);
+        assert_eq!(scan.param_count, Some(4096 * 32000 + 4096));
+    }
+
+    #[test]
+    fn metadata_past_the_window_needs_a_full_scan() {
+        // Ten ~900 KB strings push the later keys past the 8 MiB window
+        let filler = "x".repeat(900_000);
+        let mut f = Fixture::new("past-window", 0, 13);
+        f.kv_str("general.architecture", "qwen2");
+        for i in 0..10 {
+            f.kv_str(&format!("filler.{i}"), &filler);
+        }
+        f.kv_u32("qwen2.context_length", 32768)
+            .kv_str("tokenizer.chat_template", "{{ messages }}");
+        let path = f.write();
+
+        let quick = quick_scan(path).unwrap();
+        assert!(quick.truncated);
+        assert_eq!(quick.architecture.as_deref(), Some("qwen2"));
+        assert_eq!(quick.context_length, None);
+
+        let full = full_scan(path).unwrap();
+        assert!(!full.truncated);
+        assert_eq!(full.metadata.len(), 13);
+        assert_eq!(full.context_length, Some(32768));
+        assert_eq!(full.chat_template.as_deref(), Some("{{ messages }}"));
+
+        // The directory scan retries in full
+        let entries = scan_directory(&f.dir).unwrap();
+        assert_eq!(entries[0].context_length, Some(32768));
+    }
+
+    #[test]
+    fn quick_scan_skips_vocabulary_sized_arrays() {
+        let tokens: Vec<String> = (0..2000).map(|i| format!("tok{i}")).collect();
+        let tokens: Vec<&str> = tokens.iter().map(String::as_str).collect();
+        let types = vec!["1"; 2000];
+        let mut f = Fixture::new("skip-arrays", 0, 4);
+        f.kv_array("tokenizer.ggml.tokens", GGUFValueType::String, &tokens)
+            .kv_array("tokenizer.ggml.token_type", GGUFValueType::Uint32, &types)
+            .kv_array(
+                "llama.attention.head_count_kv",
+                GGUFValueType::Uint32,
+                &["8", "4"],
+            )
+            .kv_u32("tokenizer.ggml.bos_token_id", 1);
+
+        let quick = quick_scan_bytes(&f.data).unwrap();
+        assert!(!quick.truncated);
+        assert!(matches!(
+            quick.get("tokenizer.ggml.tokens"),
+            Some(GGUFValue::SkippedArray {
+                len: 2000,
+                elem_type: GGUFValueType::String
+            })
+        ));
+        assert!(matches!(
+            quick.get("tokenizer.ggml.token_type"),
+            Some(GGUFValue::SkippedArray { len: 2000, .. })
+        ));
+        // Short arrays and the keys after the skipped ones are still read
+        assert!(matches!(
+            quick.get("llama.attention.head_count_kv"),
+            Some(GGUFValue::Array(items)) if items.len() == 2
+        ));
+        let tokenizer = quick.tokenizer();
+        assert_eq!(tokenizer.n_vocab, Some(2000));
+        assert_eq!(tokenizer.bos_token_id, Some(1));
+        assert_eq!(quick.token_text(1), None);
+
+        let full = scan_from(
+            Cursor::new(&f.data),
+            f.data.len() as u64,
+            &ScanOptions::FULL,
+        )
+        .unwrap();
+        assert_eq!(full.tokenizer().n_vocab, Some(2000));
+        assert_eq!(full.token_text(1), Some("tok1"));
+    }
+
+    #[test]
+    fn directory_scan_sizes_weights_across_split_parts() {
+        let mut first = Fixture::new("model-00001-of-00002", 1, 1);
+        first
+            .kv_u32("split.count", 2)
+            .tensor("token_embd.weight", &[4096, 32000], 12, 0);
+        first.write();
+        let mut second = Fixture::new("model-00002-of-00002", 2, 0);
+        second
+            .tensor("output.weight", &[4096, 32000], 14, 0)
+            .tensor("output_norm.weight", &[4096], 0, 107_520_000);
+        fs::write(first.dir.join("model-00002-of-00002.gguf"), &second.data).unwrap();
+
+        let entries = scan_directory(&first.dir).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].is_split);
+        assert!(entries[0].missing_parts.is_empty());
+        assert_eq!(
+            entries[0].file_size,
+            (first.data.len() + second.data.len()) as u64
+        );
+        let n = 2 * 4096 * 32000 + 4096;
+        assert_eq!(entries[0].param_count, Some(n));
+        assert_eq!(entries[0].parameters.as_deref(), Some("262.1M"));
+        let bytes = 4096 * 32000 / 256 * (144 + 210) + 4096 * 4;
+        assert_eq!(entries[0].bytes_per_param, Some(bytes as f64 / n as f64));
+    }
+
+    #[test]
+    fn lora_adapters_are_flagged_and_never_get_projectors() {
+        let f = Fixture::new("adapters", 0, 0);
+        write_gguf(
+            &f.dir,
+            "Llama-3-8B-Q4_K_M.gguf",
+            &[("general.architecture", "llama")],
+        );
+        write_gguf(
+            &f.dir,
+            "Llama-3-8B-lora-f16.gguf",
+            &[
+                ("general.architecture", "llama"),
+                ("general.type", "adapter"),
+                ("adapter.type", "lora"),
+                ("general.base_model.0.name", "Llama 3 8B"),
+            ],
+        );
+        write_gguf(
+            &f.dir,
+            "tuned.gguf",
+            &[
+                ("adapter.type", "lora"),
+                ("adapter.lora.base_model", "Qwen2.5-7B"),
+                ("general.base_model.0.name", "ignored"),
+            ],
+        );
+        write_gguf(
+            &f.dir,
+            "mmproj-Llama-3-8B-f16.gguf",
+            &[("general.architecture", "clip")],
+        );
+
+        let entries = scan_directory(&f.dir).unwrap();
+        assert_eq!(entries.len(), 3);
+        let model = &entries[0];
+        assert_eq!(model.kind, ModelKind::Base);
+        assert_eq!(model.capabilities, [Capability::Chat]);
+        assert_eq!(
+            mmproj_of(&entries, "llama-3-8b-q4_k_m"),
+            Some("mmproj-Llama-3-8B-f16.gguf")
+        );
+
+        let lora = &entries[1];
+        assert_eq!(lora.kind, ModelKind::LoraAdapter);
+        assert!(lora.capabilities.is_empty());
+        assert_eq!(lora.base_model.as_deref(), Some("Llama 3 8B"));
+        assert_eq!(lora.mmproj_path, None);
+        assert_eq!(entries[2].kind, ModelKind::LoraAdapter);
+        assert_eq!(entries[2].base_model.as_deref(), Some("Qwen2.5-7B"));
+    }
+
+    #[test]
+    fn cached_file_scans_are_reused() {
+        #[derive(Default)]
+        struct Memo {
+            files: HashMap<PathBuf, FileScan>,
+            puts: usize,
+        }
+
+        impl ScanCache for Memo {
+            fn get(&mut self, path: &Path) -> Option<FileScan> {
+                self.files.get(path).cloned()
+            }
+
+            fn put(&mut self, path: &Path, scan: &FileScan) {
+                self.puts += 1;
+                self.files.insert(path.to_path_buf(), scan.clone());
+            }
+        }
+
+        let mut f = Fixture::new("cached", 1, 1);
+        f.kv_str("general.name", "Cached")
+            .tensor("token_embd.weight", &[64, 32], 1, 0);
+        f.write();
+        write_gguf(
+            &f.dir,
+            "mmproj-cached-f16.gguf",
+            &[("general.architecture", "clip")],
+        );
+
+        let mut memo = Memo::default();
+        let first = scan_directory_cached(&f.dir, WalkOptions::default(), &mut memo).unwrap();
+        assert_eq!(memo.puts, 2);
+        let weights = memo.files[&f.path].weights;
+        assert_eq!(
+            weights,
+            Some(WeightTotals {
+                params: 2048,
+                bytes: 4096
+            })
+        );
+
+        let second = scan_directory_cached(&f.dir, WalkOptions::default(), &mut memo).unwrap();
+        assert_eq!(memo.puts, 2);
+        assert_eq!(
+            serde_json::to_value(&first).unwrap(),
+            serde_json::to_value(&second).unwrap()
+        );
+        assert_eq!(second[0].name, "Cached");
+        assert_eq!(second[0].param_count, Some(2048));
+        assert!(second[0].mmproj_path.is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_followed_once_and_cycles_end() {
+        use std::os::unix::fs::symlink;
+
+        let root = Fixture::new("symlinks", 0, 0);
+        let outside = Fixture::new("symlinks-outside", 0, 0);
+        let real = root.dir.join("real");
+        let shared = outside.dir.join("shared");
+        fs::create_dir_all(&real).unwrap();
+        fs::create_dir_all(&shared).unwrap();
+        write_gguf(&real, "alpha.gguf", &[("general.name", "Alpha")]);
+        write_gguf(&outside.dir, "beta.gguf", &[("general.name", "Beta")]);
+        write_gguf(&shared, "gamma.gguf", &[("general.name", "Gamma")]);
+        // A second name for a file already in the tree, one for a file
+        // outside it, a linked directory and a link back to the root
+        symlink(real.join("alpha.gguf"), root.dir.join("current.gguf")).unwrap();
+        symlink(outside.dir.join("beta.gguf"), root.dir.join("beta.gguf")).unwrap();
+        symlink(&shared, root.dir.join("shared")).unwrap();
+        symlink(&root.dir, real.join("loop")).unwrap();
+
+        let names = |entries: &[ModelEntry]| {
+            let mut names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+            names.sort();
+            names.join(",")
+        };
+
+        let entries = scan_directory(&root.dir).unwrap();
+        assert_eq!(names(&entries), "Alpha,Beta");
+        let alpha = entries.iter().find(|e| e.name == "Alpha").unwrap();
+        assert_eq!(alpha.path, real.join("alpha.gguf"));
+        let beta = entries.iter().find(|e| e.name == "Beta").unwrap();
+        assert_eq!(beta.path, root.dir.join("beta.gguf"));
+        assert_eq!(
+            beta.canonical_path,
+            fs::canonicalize(outside.dir.join("beta.gguf")).unwrap()
+        );
+
+        let follow = WalkOptions {
+            follow_dir_symlinks: true,
+        };
+        let entries = scan_directory_cached(&root.dir, follow, &mut NoScanCache).unwrap();
+        assert_eq!(names(&entries), "Alpha,Beta,Gamma");
+        let alpha = entries.iter().find(|e| e.name == "Alpha").unwrap();
+        assert_eq!(alpha.path, real.join("alpha.gguf"));
+        let gamma = entries.iter().find(|e| e.name == "Gamma").unwrap();
+        assert_eq!(gamma.path, root.dir.join("shared").join("gamma.gguf"));
+    }
+
+    #[test]
+    fn unreadable_files_fall_back_to_their_file_names() {
+        let f = Fixture::new("unreadable", 0, 0);
+        write_gguf(&f.dir, "fine.gguf", &[("general.architecture", "llama")]);
+        let broken = "Meta-Llama-3.1-8B-Instruct-Q4_K_M.gguf";
+        fs::write(f.dir.join(broken), b"<html>not found</html>").unwrap();
+
+        let entries = scan_directory(&f.dir).unwrap();
+        let fine = entries.iter().find(|e| e.id == "fine").unwrap();
+        assert_eq!(fine.metadata_source, MetadataSource::Scanned);
+        assert_eq!(fine.scan_error, None);
+
+        let broken = entries.iter().find(|e| e.id != "fine").unwrap();
+        assert_eq!(broken.metadata_source, MetadataSource::Filename);
+        assert!(
+            broken
+                .scan_error
+                .as_deref()
+                .is_some_and(|e| e.contains("magic")),
+            "{:?}",
+            broken.scan_error
+        );
+        assert_eq!(broken.file_size, 22);
+        assert_eq!(broken.parameters.as_deref(), Some("8B"));
+        assert_eq!(broken.param_count, Some(8_000_000_000));
+        assert_eq!(broken.quantization.as_deref(), Some("Q4_K_M"));
+        assert_eq!(broken.features, [ModelFeature::Instruct]);
+        assert_eq!(broken.architecture, None);
+    }
+
+    #[test]
+    fn incomplete_split_sets_list_their_missing_parts() {
+        // Part 1 missing; `shard-10` sorts before `shard-9` by name
+        let f = Fixture::new("incomplete", 0, 0);
+        write_gguf(&f.dir, "shard-10-of-10.gguf", &[]);
+        write_gguf(
+            &f.dir,
+            "shard-9-of-10.gguf",
+            &[("general.name", "Shard Nine")],
+        );
+        write_gguf(&f.dir, "other-00001-of-00002.gguf", &[]);
+
+        let entries = scan_directory(&f.dir).unwrap();
+        assert_eq!(entries.len(), 2);
+        let other = &entries[0];
+        assert_eq!(other.missing_parts, [2]);
+        assert_eq!(other.param_count, None);
+
+        let shard = &entries[1];
+        assert_eq!(shard.id, "shard-9-of-10");
+        assert_eq!(shard.name, "Shard Nine");
+        assert!(shard.is_split);
+        let names: Vec<_> = shard
+            .split_parts
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["shard-9-of-10.gguf", "shard-10-of-10.gguf"]);
+        assert_eq!(shard.missing_parts, (1..=8).collect::<Vec<u16>>());
+        let size = |name: &str| fs::metadata(f.dir.join(name)).unwrap().len();
+        assert_eq!(
+            shard.file_size,
+            size("shard-9-of-10.gguf") + size("shard-10-of-10.gguf")
+        );
+
+        assert_eq!(
+            missing_split_parts(&f.dir.join("other-00001-of-00002.gguf")),
+            [2]
+        );
+        assert!(missing_split_parts(&f.dir.join("shard.gguf")).is_empty());
+    }
+
+    #[test]
+    fn declared_parameter_count_wins_over_tensor_shapes() {
+        let mut f = Fixture::new("declared", 1, 1);
+        f.kv_u32("general.parameter_count", 1_000_000).tensor(
+            "token_embd.weight",
+            &[1024, 1024],
+            8,
+            0,
+        );
+        f.write();
+
+        let entries = scan_directory(&f.dir).unwrap();
+        assert_eq!(entries[0].param_count, Some(1_000_000));
+        assert_eq!(entries[0].parameters.as_deref(), Some("1.0M"));
+        // Storage per parameter still comes from the tensors
+        assert_eq!(entries[0].bytes_per_param, Some(34.0 / 32.0));
+    }
+
+    /// Write a tensor-less GGUF with string `kvs` as `dir/file`.
+    fn write_gguf(dir: &Path, file: &str, kvs: &[(&str, &str)]) {
+        let mut f = Fixture::new(file, 0, kvs.len() as u64);
+        for (key, value) in kvs {
+            f.kv_str(key, value);
+        }
+        fs::write(dir.join(file), &f.data).unwrap();
+    }
+
+    fn mmproj_of<'a>(entries: &'a [ModelEntry], id: &str) -> Option<&'a str> {
+        let entry = entries.iter().find(|e| e.id == id).unwrap();
+        entry.mmproj_path.as_deref()?.file_name()?.to_str()
+    }
+
+    #[test]
+    fn projectors_pair_with_the_model_they_are_named_after() {
+        let f = Fixture::new("projectors", 0, 0);
+        let dir = &f.dir;
+        let clip = [("general.architecture", "clip")];
+        write_gguf(
+            dir,
+            "Llama-3.2-3B-Instruct-Q4_K_M.gguf",
+            &[("general.architecture", "llama")],
+        );
+        write_gguf(
+            dir,
+            "Qwen2-VL-7B-Instruct-Q4_K_M.gguf",
+            &[("general.architecture", "qwen2vl")],
+        );
+        write_gguf(
+            dir,
+            "Qwen2-VL-7B-Instruct-Q8_0.gguf",
+            &[("general.architecture", "qwen2vl")],
+        );
+        write_gguf(dir, "mmproj-Qwen2-VL-7B-Instruct-f16.gguf", &clip);
+        // No `mmproj` in the name: found by its `clip.*` keys and paired
+        // through its base model
+        write_gguf(
+            dir,
+            "vision.gguf",
+            &[
+                ("clip.projector_type", "mlp"),
+                ("general.base_model.0.name", "Llama 3.2 3B Instruct"),
+            ],
+        );
+        // Matches nothing
+        write_gguf(dir, "mmproj-gemma-3-4b-it-f16.gguf", &clip);
+
+        let entries = scan_directory(dir).unwrap();
+        let ids: Vec<&str> = entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "llama-3.2-3b-instruct-q4_k_m",
+                "qwen2-vl-7b-instruct-q4_k_m",
+                "qwen2-vl-7b-instruct-q8_0",
+                "mmproj-gemma-3-4b-it-f16",
+            ]
+        );
+        assert_eq!(
+            mmproj_of(&entries, "llama-3.2-3b-instruct-q4_k_m"),
+            Some("vision.gguf")
+        );
+        assert_eq!(entries[0].features, [ModelFeature::Vision]);
+        assert!(entries[3].features.is_empty());
+        // Both quantisations share the one projector
+        for id in ["qwen2-vl-7b-instruct-q4_k_m", "qwen2-vl-7b-instruct-q8_0"] {
+            assert_eq!(
+                mmproj_of(&entries, id),
+                Some("mmproj-Qwen2-VL-7B-Instruct-f16.gguf")
+            );
+        }
+        assert_eq!(entries[3].capabilities, [Capability::VisionProjector]);
+        assert_eq!(entries[3].mmproj_path, None);
+    }
+
+    #[test]
+    fn projectors_fall_back_to_a_lone_sibling_model() {
+        let f = Fixture::new("lone-sibling", 0, 0);
+        write_gguf(&f.dir, "model.gguf", &[("general.architecture", "llama")]);
+        write_gguf(
+            &f.dir,
+            "projector.gguf",
+            &[("general.architecture", "clip")],
+        );
+        let entries = scan_directory(&f.dir).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(mmproj_of(&entries, "model"), Some("projector.gguf"));
+
+        // With a second model the pairing is ambiguous
+        write_gguf(&f.dir, "other.gguf", &[("general.architecture", "llama")]);
+        let entries = scan_directory(&f.dir).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(mmproj_of(&entries, "model"), None);
+        assert_eq!(entries[2].capabilities, [Capability::VisionProjector]);
+    }
+
+    #[test]
+    fn malformed_tensor_sections_are_errors() {
+        let mut f = Fixture::new("five-dims", 1, 0);
+        f.tensor("t", &[1, 2, 3, 4, 5], 0, 0);
+        assert!(matches!(
+            scan_tensors_from(Cursor::new(&f.data)),
+            Err(GGUFError::Other(msg)) if msg.contains("5 dimensions")
+        ));
+
+        let mut f = Fixture::new("truncated", 2, 0);
+        f.tensor("t", &[8], 0, 0);
+        assert!(scan_tensors_from(Cursor::new(&f.data)).is_err());
+
+        let f = Fixture::new("too-many", MAX_TENSOR_COUNT + 1, 0);
+        assert!(scan_tensors_from(Cursor::new(&f.data)).is_err());
+    }
+
+    #[test]
+    fn the_start_of_a_file_is_enough_for_its_header() {
+        let tokens: Vec<String> = (0..2000).map(|i| format!("tok{i}")).collect();
+        let tokens: Vec<&str> = tokens.iter().map(String::as_str).collect();
+        let mut f = Fixture::new("prefix", 1, 3);
+        f.kv_str("general.architecture", "llama")
+            .kv_array("tokenizer.ggml.tokens", GGUFValueType::String, &tokens)
+            .kv_u32("llama.context_length", 4096)
+            .tensor("token_embd.weight", &[64, 32], 1, 0);
+
+        // As of the first chunk of a download
+        let head = &f.data[..256];
+        let total = 1 << 30;
+        let scan = quick_scan_from(Cursor::new(head), total).unwrap();
+        assert_eq!(scan.header.version, 3);
+        assert_eq!(scan.architecture.as_deref(), Some("llama"));
+        assert_eq!(scan.file_size, total);
+        assert!(scan.truncated);
+        assert_eq!(scan.context_length, None);
+        assert_eq!(scan.param_count, None);
+        assert_eq!(scan.file_path, PathBuf::new());
+
+        let whole = quick_scan_bytes(&f.data).unwrap();
+        assert!(!whole.truncated);
+        assert_eq!(whole.context_length, Some(4096));
+        assert_eq!(whole.param_count, Some(2048));
+    }
+
+    #[test]
+    fn bytes_that_are_not_gguf_are_rejected() {
+        assert!(matches!(
+            quick_scan_bytes(b"<!DOCTYPE html><html>"),
+            Err(GGUFError::InvalidMagic(_))
+        ));
+        assert!(matches!(
+            quick_scan_bytes(b"GGU"),
+            Err(GGUFError::TruncatedHeader)
+        ));
+
+        let mut f = Fixture::new("future", 0, 0);
+        f.data[4..8].copy_from_slice(&(GGUF_VERSION_MAX + 1).to_le_bytes());
+        assert!(matches!(
+            quick_scan_bytes(&f.data),
+            Err(GGUFError::UnsupportedVersion(v)) if v == GGUF_VERSION_MAX + 1
+        ));
+    }
+}
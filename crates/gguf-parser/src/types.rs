@@ -8,6 +8,9 @@ pub const GGUF_MAGIC: u32 = 0x4655_4747;
 /// Maximum GGUF version we support.
 pub const GGUF_VERSION_MAX: u32 = 3;
 
+/// Maximum number of dimensions of a ggml tensor.
+pub const GGML_MAX_DIMS: u32 = 4;
+
 //  Value type tag
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -59,6 +62,28 @@ pub struct GGUFHeader {
     pub metadata_kv_count: u64,
 }
 
+//  Tensor info
+
+/// One entry of the tensor info section that follows the metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TensorInfo {
+    pub name: String,
+    pub n_dims: u32,
+    /// Extent of each dimension, innermost first; unused ones are 1.
+    pub dims: [u64; GGML_MAX_DIMS as usize],
+    /// A `ggml_type`; see [`ggml_type_name`].
+    pub ggml_type: u32,
+    /// Offset of the tensor's data from the start of the data section.
+    pub offset: u64,
+}
+
+impl TensorInfo {
+    /// Total element count (saturating, for corrupt shapes).
+    pub fn n_elements(&self) -> u64 {
+        self.dims.iter().fold(1, |n, &d| n.saturating_mul(d))
+    }
+}
+
 //  Metadata KV
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,6 +108,12 @@ pub enum GGUFValue {
     Uint64(u64),
     Int64(i64),
     Float64(f64),
+    /// An array the scan stepped over without reading its elements; see
+    /// [`ScanOptions::max_array_len`](crate::ScanOptions::max_array_len).
+    SkippedArray {
+        len: u64,
+        elem_type: GGUFValueType,
+    },
 }
 
 impl GGUFValue {
@@ -111,6 +142,13 @@ impl GGUFValue {
         }
     }
 
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
     pub fn as_f32(&self) -> Option<f32> {
         match self {
             Self::Float32(v) => Some(*v),
@@ -120,6 +158,145 @@ impl GGUFValue {
     }
 }
 
+//  Model capability
+
+/// What a model file can be used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    /// Text generation (chat and completions).
+    Chat,
+    /// Pooled embeddings only, e.g. BERT-style encoders.
+    Embeddings,
+    /// A CLIP/mmproj vision projector, used alongside a chat model.
+    VisionProjector,
+}
+
+impl Capability {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Chat => "chat",
+            Self::Embeddings => "embeddings",
+            Self::VisionProjector => "vision-projector",
+        }
+    }
+}
+
+impl std::fmt::Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+//  Model kind
+
+/// What sort of file a catalogue entry is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModelKind {
+    /// A model that can be loaded on its own.
+    #[default]
+    Base,
+    /// A LoRA adapter, applied on top of the model it was trained against.
+    LoraAdapter,
+    /// A CLIP/mmproj vision projector no model was found for.
+    Mmproj,
+}
+
+impl ModelKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Base => "base",
+            Self::LoraAdapter => "lora-adapter",
+            Self::Mmproj => "mmproj",
+        }
+    }
+}
+
+impl std::fmt::Display for ModelKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+//  Metadata source
+
+/// Where a catalogue entry's details come from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MetadataSource {
+    /// The file's GGUF metadata.
+    #[default]
+    Scanned,
+    /// Guesses from the file name; the metadata couldn't be read.
+    Filename,
+}
+
+impl MetadataSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Scanned => "scanned",
+            Self::Filename => "filename",
+        }
+    }
+}
+
+impl std::fmt::Display for MetadataSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+//  Model feature
+
+/// A trait of a model worth badging, see [`detect_features`].
+///
+/// [`detect_features`]: crate::features::detect_features
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModelFeature {
+    /// Mixture of experts.
+    Moe,
+    /// Has a vision projector to go with it.
+    Vision,
+    /// Produces pooled embeddings.
+    Embedding,
+    /// Ships a chat template, i.e. is tuned for chat.
+    Instruct,
+    /// Thinks out loud before answering.
+    Reasoning,
+}
+
+impl ModelFeature {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Moe => "moe",
+            Self::Vision => "vision",
+            Self::Embedding => "embedding",
+            Self::Instruct => "instruct",
+            Self::Reasoning => "reasoning",
+        }
+    }
+}
+
+impl std::fmt::Display for ModelFeature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Architectures that are encoders producing pooled embeddings.
+pub const EMBEDDING_ARCHITECTURES: &[&str] = &[
+    "bert",
+    "nomic-bert",
+    "nomic-bert-moe",
+    "jina-bert-v2",
+    "jina-bert-v3",
+    "modern-bert",
+    "neo-bert",
+    "t5encoder",
+];
+
 //  Error
 
 #[derive(Debug, thiserror::Error)]
@@ -143,6 +320,59 @@ pub enum GGUFError {
     Other(String),
 }
 
+//  Parameter count → human label
+
+/// Short human form of a parameter count: `7_615_616_512` → `"7.6B"`.
+pub fn param_count_label(n: u64) -> String {
+    const UNITS: &[(f64, &str)] = &[(1e12, "T"), (1e9, "B"), (1e6, "M"), (1e3, "K")];
+    let n_f = n as f64;
+    UNITS.iter().find(|(scale, _)| n_f >= *scale).map_or_else(
+        || n.to_string(),
+        |(scale, unit)| format!("{:.1}{unit}", n_f / scale),
+    )
+}
+
+//  ggml type → name
+
+/// Name of a `ggml_type` as llama.cpp prints it.
+pub fn ggml_type_name(t: u32) -> &'static str {
+    match t {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        6 => "Q5_0",
+        7 => "Q5_1",
+        8 => "Q8_0",
+        9 => "Q8_1",
+        10 => "Q2_K",
+        11 => "Q3_K",
+        12 => "Q4_K",
+        13 => "Q5_K",
+        14 => "Q6_K",
+        15 => "Q8_K",
+        16 => "IQ2_XXS",
+        17 => "IQ2_XS",
+        18 => "IQ3_XXS",
+        19 => "IQ1_S",
+        20 => "IQ4_NL",
+        21 => "IQ3_S",
+        22 => "IQ2_S",
+        23 => "IQ4_XS",
+        24 => "I8",
+        25 => "I16",
+        26 => "I32",
+        27 => "I64",
+        28 => "F64",
+        29 => "IQ1_M",
+        30 => "BF16",
+        34 => "TQ1_0",
+        35 => "TQ2_0",
+        39 => "MXFP4",
+        _ => "unknown",
+    }
+}
+
 //  File-type ↔ human name
 
 /// Map a `general.file_type` value to a short quantisation name.
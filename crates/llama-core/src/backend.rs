@@ -1,10 +1,18 @@
 //! Global llama.cpp backend initialization and system queries.
 
 use std::ffi::CStr;
-use std::sync::Once;
-use tracing::{debug, info};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Once, OnceLock};
+use tracing::{debug, info, warn};
 
 static BACKEND_INIT: Once = Once::new();
+static NUMA_STRATEGY: OnceLock<NumaStrategy> = OnceLock::new();
+/// Set once any model has loaded; NUMA placement is decided by then.
+static MODEL_LOADED: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn note_model_loaded() {
+    MODEL_LOADED.store(true, Ordering::Relaxed);
+}
 
 /// RAII guard for the llama.cpp backend.
 ///
@@ -27,13 +35,43 @@ impl LlamaBackend {
     }
 
     /// Initialize NUMA optimizations.
+    ///
+    /// Call before loading any model: ggml only honours the first call,
+    /// and weights already mapped keep their placement.  Later calls are
+    /// ignored with a warning.
     pub fn numa_init(&self, strategy: NumaStrategy) {
-        unsafe {
-            llama_sys::llama_numa_init(strategy.as_raw());
+        if MODEL_LOADED.load(Ordering::Relaxed) {
+            warn!(
+                ?strategy,
+                "NUMA strategy must be set before models load; ignoring it"
+            );
+            return;
+        }
+        if let Some(current) = NUMA_STRATEGY.get() {
+            if *current != strategy {
+                warn!(
+                    ?strategy,
+                    ?current,
+                    "NUMA is already initialized; ignoring the new strategy"
+                );
+            }
+            return;
+        }
+        if strategy != NumaStrategy::Disabled {
+            unsafe {
+                llama_sys::llama_numa_init(strategy.as_raw());
+            }
         }
+        let _ = NUMA_STRATEGY.set(strategy);
         debug!(?strategy, "NUMA initialized");
     }
 
+    /// The NUMA strategy in effect ([`Disabled`](NumaStrategy::Disabled)
+    /// unless [`numa_init`](Self::numa_init) set one).
+    pub fn numa_strategy() -> NumaStrategy {
+        NUMA_STRATEGY.get().copied().unwrap_or_default()
+    }
+
     /// Set the global log callback, bridging llama.cpp logs to the Rust
     /// `tracing` subsystem. Call once after backend init.
     pub fn set_log_callback(&self) {
@@ -65,6 +103,22 @@ impl LlamaBackend {
         debug!("llama.cpp log callback installed");
     }
 
+    /// Whether llama.cpp can memory-map model files.
+    pub fn supports_mmap() -> bool {
+        unsafe { llama_sys::llama_supports_mmap() }
+    }
+
+    /// Whether llama.cpp can lock model memory to prevent swapping.
+    pub fn supports_mlock() -> bool {
+        unsafe { llama_sys::llama_supports_mlock() }
+    }
+
+    /// Whether this build can offload layers to a GPU.  When `false`,
+    /// `n_gpu_layers` is ignored and everything runs on the CPU.
+    pub fn supports_gpu_offload() -> bool {
+        unsafe { llama_sys::llama_supports_gpu_offload() }
+    }
+
     /// Return a human-readable system information string.
     pub fn system_info() -> String {
         unsafe {
@@ -75,6 +129,80 @@ impl LlamaBackend {
     }
 }
 
+//  Devices
+
+/// A ggml compute device and its memory.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub description: String,
+    /// Backend the device belongs to, e.g. `CUDA`, `Vulkan`, `Metal`, `CPU`.
+    pub backend: String,
+    /// GPU or integrated GPU (as opposed to CPU / accelerator).
+    pub is_gpu: bool,
+    pub free_bytes: u64,
+    pub total_bytes: u64,
+}
+
+impl LlamaBackend {
+    /// Enumerate the compute devices registered with ggml.
+    pub fn devices(&self) -> Vec<DeviceInfo> {
+        let cstr = |p: *const std::ffi::c_char| {
+            if p.is_null() {
+                String::new()
+            } else {
+                unsafe { CStr::from_ptr(p) }.to_string_lossy().into_owned()
+            }
+        };
+
+        (0..unsafe { llama_sys::ggml_backend_dev_count() })
+            .filter_map(|i| {
+                let dev = unsafe { llama_sys::ggml_backend_dev_get(i) };
+                if dev.is_null() {
+                    return None;
+                }
+                let (mut free, mut total) = (0usize, 0usize);
+                unsafe { llama_sys::ggml_backend_dev_memory(dev, &mut free, &mut total) };
+                let dev_type = unsafe { llama_sys::ggml_backend_dev_type(dev) };
+                let reg = unsafe { llama_sys::ggml_backend_dev_backend_reg(dev) };
+                Some(DeviceInfo {
+                    name: cstr(unsafe { llama_sys::ggml_backend_dev_name(dev) }),
+                    description: cstr(unsafe { llama_sys::ggml_backend_dev_description(dev) }),
+                    backend: if reg.is_null() {
+                        String::new()
+                    } else {
+                        cstr(unsafe { llama_sys::ggml_backend_reg_name(reg) })
+                    },
+                    is_gpu: is_gpu(dev_type),
+                    free_bytes: free as u64,
+                    total_bytes: total as u64,
+                })
+            })
+            .collect()
+    }
+
+    /// GPU devices only; empty in CPU-only builds or without a usable GPU.
+    pub fn gpu_devices(&self) -> Vec<DeviceInfo> {
+        self.devices().into_iter().filter(|d| d.is_gpu).collect()
+    }
+}
+
+/// Number of GPU devices registered with ggml, which is what
+/// `main_gpu` and `tensor_split` index into.
+pub(crate) fn gpu_device_count() -> usize {
+    (0..unsafe { llama_sys::ggml_backend_dev_count() })
+        .filter(|&i| {
+            let dev = unsafe { llama_sys::ggml_backend_dev_get(i) };
+            !dev.is_null() && is_gpu(unsafe { llama_sys::ggml_backend_dev_type(dev) })
+        })
+        .count()
+}
+
+fn is_gpu(dev_type: llama_sys::ggml_backend_dev_type) -> bool {
+    dev_type == llama_sys::ggml_backend_dev_type_GGML_BACKEND_DEVICE_TYPE_GPU
+        || dev_type == llama_sys::ggml_backend_dev_type_GGML_BACKEND_DEVICE_TYPE_IGPU
+}
+
 // Backend is process-global; we never explicitly free it during normal
 // execution — it is cleaned up at process exit.
 impl Drop for LlamaBackend {
@@ -83,11 +211,18 @@ impl Drop for LlamaBackend {
 
 //  NUMA strategy
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How ggml spreads threads and memory over NUMA nodes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum NumaStrategy {
+    #[default]
     Disabled,
+    /// Spread execution evenly over all nodes.
     Distribute,
+    /// Only spawn threads on the node the process started on.
     Isolate,
+    /// Use the CPU map provided by `numactl`.
+    #[serde(rename = "numactl")]
     NUMACtl,
     Mirror,
 }
@@ -1,9 +1,25 @@
 //! Safe wrapper around `llama_batch`.
 
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BatchError {
+    #[error("Batch is full ({capacity} tokens)")]
+    Full { capacity: i32 },
+
+    #[error("Token belongs to {given} sequences, but the batch allows {max}")]
+    TooManySequences { given: usize, max: i32 },
+
+    #[error("Sequence id {seq_id} is out of range; the context holds {n_seq_max} sequences")]
+    SequenceOutOfRange { seq_id: i32, n_seq_max: u32 },
+}
+
 /// RAII batch of tokens to feed into the decoder.
 pub struct LlamaBatch {
     inner: llama_sys::llama_batch,
     capacity: i32,
+    embd: i32,
+    n_seq_max: i32,
     /// `true` when we own the internal allocations (and must free them).
     owned: bool,
 }
@@ -18,6 +34,8 @@ impl LlamaBatch {
         Self {
             inner,
             capacity: n_tokens_max,
+            embd,
+            n_seq_max,
             owned: true,
         }
     }
@@ -32,24 +50,73 @@ impl LlamaBatch {
         self.inner.n_tokens
     }
 
+    /// Number of tokens the batch can hold before it has to grow.
+    pub fn capacity(&self) -> i32 {
+        self.capacity
+    }
+
     /// Remove all tokens.
     pub fn clear(&mut self) {
         self.inner.n_tokens = 0;
     }
 
+    /// Make room for at least `n_tokens` tokens, keeping the ones stored.
+    pub fn ensure_capacity(&mut self, n_tokens: i32) {
+        if n_tokens <= self.capacity {
+            return;
+        }
+        let capacity = n_tokens.max(self.capacity.saturating_mul(2));
+        let grown = unsafe { llama_sys::llama_batch_init(capacity, self.embd, self.n_seq_max) };
+        let n = self.inner.n_tokens as usize;
+        let old = self.inner;
+        unsafe {
+            if self.embd > 0 {
+                std::ptr::copy_nonoverlapping(old.embd, grown.embd, n * self.embd as usize);
+            } else {
+                std::ptr::copy_nonoverlapping(old.token, grown.token, n);
+            }
+            std::ptr::copy_nonoverlapping(old.pos, grown.pos, n);
+            std::ptr::copy_nonoverlapping(old.n_seq_id, grown.n_seq_id, n);
+            std::ptr::copy_nonoverlapping(old.logits, grown.logits, n);
+            for i in 0..n {
+                let n_seq = *old.n_seq_id.add(i) as usize;
+                std::ptr::copy_nonoverlapping(*old.seq_id.add(i), *grown.seq_id.add(i), n_seq);
+            }
+            if self.owned {
+                llama_sys::llama_batch_free(old);
+            }
+        }
+        self.inner = grown;
+        self.inner.n_tokens = n as i32;
+        self.capacity = capacity;
+        self.owned = true;
+    }
+
     /// Push a token into the batch.
     ///
     /// * `token`   — token id
     /// * `pos`     — absolute position
     /// * `seq_ids` — sequence ids this token belongs to
     /// * `logits`  — request logits output for this position
-    pub fn add(&mut self, token: i32, pos: i32, seq_ids: &[i32], logits: bool) {
+    pub fn add(
+        &mut self,
+        token: i32,
+        pos: i32,
+        seq_ids: &[i32],
+        logits: bool,
+    ) -> Result<(), BatchError> {
         let i = self.inner.n_tokens as usize;
-        assert!(
-            (i as i32) < self.capacity,
-            "LlamaBatch capacity ({}) exceeded",
-            self.capacity
-        );
+        if i as i32 >= self.capacity {
+            return Err(BatchError::Full {
+                capacity: self.capacity,
+            });
+        }
+        if seq_ids.len() > self.n_seq_max as usize {
+            return Err(BatchError::TooManySequences {
+                given: seq_ids.len(),
+                max: self.n_seq_max,
+            });
+        }
 
         unsafe {
             *self.inner.token.add(i) = token;
@@ -61,6 +128,54 @@ impl LlamaBatch {
             *self.inner.logits.add(i) = i8::from(logits);
         }
         self.inner.n_tokens += 1;
+        Ok(())
+    }
+
+    /// Check that every token's sequence ids lie in `0..n_seq_max`, the
+    /// range a context was created for; llama.cpp aborts on others.
+    pub fn check_seq_ids(&self, n_seq_max: u32) -> Result<(), BatchError> {
+        for i in 0..self.inner.n_tokens as usize {
+            let n_seq = unsafe { *self.inner.n_seq_id.add(i) } as usize;
+            let ids = unsafe { std::slice::from_raw_parts(*self.inner.seq_id.add(i), n_seq) };
+            if let Some(&seq_id) = ids.iter().find(|&&id| id < 0 || id as u32 >= n_seq_max) {
+                return Err(BatchError::SequenceOutOfRange { seq_id, n_seq_max });
+            }
+        }
+        Ok(())
+    }
+
+    /// Append `tokens` to sequence `seq_id` at consecutive positions from
+    /// `start_pos`, growing the batch as needed.  Logits are requested
+    /// for the last token only, or for every token.
+    pub fn add_sequence(
+        &mut self,
+        tokens: &[i32],
+        seq_id: i32,
+        start_pos: i32,
+        logits_last_only: bool,
+    ) -> Result<(), BatchError> {
+        self.ensure_capacity(self.n_tokens() + tokens.len() as i32);
+        for (i, &token) in tokens.iter().enumerate() {
+            let logits = !logits_last_only || i == tokens.len() - 1;
+            self.add(token, start_pos + i as i32, &[seq_id], logits)?;
+        }
+        Ok(())
+    }
+
+    /// Token id, position, sequence ids and logits flag of entry `i`.
+    #[cfg(test)]
+    fn entry(&self, i: usize) -> (i32, i32, Vec<i32>, bool) {
+        assert!(i < self.inner.n_tokens as usize);
+        unsafe {
+            let n_seq = *self.inner.n_seq_id.add(i) as usize;
+            let seq_ids = std::slice::from_raw_parts(*self.inner.seq_id.add(i), n_seq).to_vec();
+            (
+                *self.inner.token.add(i),
+                *self.inner.pos.add(i),
+                seq_ids,
+                *self.inner.logits.add(i) != 0,
+            )
+        }
     }
 }
 
@@ -71,3 +186,71 @@ impl Drop for LlamaBatch {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_reports_a_full_batch() {
+        let mut batch = LlamaBatch::new(2, 0, 1);
+        batch.add(1, 0, &[0], false).unwrap();
+        batch.add(2, 1, &[0], true).unwrap();
+        assert_eq!(
+            batch.add(3, 2, &[0], true),
+            Err(BatchError::Full { capacity: 2 })
+        );
+        assert_eq!(
+            LlamaBatch::new(2, 0, 1).add(1, 0, &[0, 1], true),
+            Err(BatchError::TooManySequences { given: 2, max: 1 })
+        );
+        assert_eq!(batch.n_tokens(), 2);
+    }
+
+    #[test]
+    fn growing_keeps_stored_tokens() {
+        let mut batch = LlamaBatch::new(2, 0, 2);
+        batch.add(10, 0, &[0, 1], false).unwrap();
+        batch.add(11, 1, &[1], true).unwrap();
+
+        batch.ensure_capacity(3);
+        assert_eq!(batch.capacity(), 4);
+        assert_eq!(batch.entry(0), (10, 0, vec![0, 1], false));
+        assert_eq!(batch.entry(1), (11, 1, vec![1], true));
+        batch.add(12, 2, &[0], true).unwrap();
+
+        // Never shrinks
+        batch.ensure_capacity(1);
+        assert_eq!(batch.capacity(), 4);
+        assert_eq!(batch.n_tokens(), 3);
+    }
+
+    #[test]
+    fn add_sequence_grows_and_marks_the_last_token() {
+        let mut batch = LlamaBatch::new(1, 0, 1);
+        batch.add_sequence(&[5, 6, 7], 0, 4, true).unwrap();
+        assert_eq!(batch.n_tokens(), 3);
+        assert!(batch.capacity() >= 3);
+        assert_eq!(batch.entry(0), (5, 4, vec![0], false));
+        assert_eq!(batch.entry(2), (7, 6, vec![0], true));
+
+        batch.clear();
+        batch.add_sequence(&[8, 9], 0, 0, false).unwrap();
+        assert!(batch.entry(0).3 && batch.entry(1).3);
+    }
+
+    #[test]
+    fn seq_ids_are_checked_against_the_context() {
+        let mut batch = LlamaBatch::new(4, 0, 2);
+        batch.add(1, 0, &[0], false).unwrap();
+        batch.add(2, 0, &[1, 3], true).unwrap();
+        assert_eq!(batch.check_seq_ids(4), Ok(()));
+        assert_eq!(
+            batch.check_seq_ids(2),
+            Err(BatchError::SequenceOutOfRange {
+                seq_id: 3,
+                n_seq_max: 2
+            })
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! llama-bench style throughput measurement.
+//!
+//! Each repetition clears the KV cache, decodes a synthetic prompt of
+//! `n_prompt` tokens and then generates `n_gen` tokens greedily.  Speeds
+//! come from llama.cpp's own perf counters; time-to-first-token is wall
+//! time from the start of prompt processing to the first sampled token.
+
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::batch::LlamaBatch;
+use crate::context::LlamaContext;
+use crate::error::{LlamaError, Result};
+use crate::sampler::SamplerChain;
+
+/// What to measure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchParams {
+    /// Synthetic prompt length (prompt-processing phase).
+    pub n_prompt: u32,
+    /// Tokens generated after the prompt (token-generation phase).
+    pub n_gen: u32,
+    pub repetitions: u32,
+}
+
+/// Mean and sample standard deviation of a series.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Stats {
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+impl Stats {
+    pub fn of(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let stddev = if values.len() > 1 {
+            (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+        } else {
+            0.0
+        };
+        Self { mean, stddev }
+    }
+}
+
+/// Measurements of one repetition.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BenchRun {
+    pub pp_tokens_per_sec: f64,
+    pub tg_tokens_per_sec: f64,
+    pub ttft_ms: f64,
+}
+
+/// Aggregated result of [`run_bench`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BenchReport {
+    pub n_prompt: u32,
+    pub n_gen: u32,
+    pub repetitions: u32,
+    /// Prompt processing, tokens/s.
+    pub pp: Stats,
+    /// Token generation, tokens/s.
+    pub tg: Stats,
+    pub ttft_ms: Stats,
+    pub runs: Vec<BenchRun>,
+}
+
+/// Run the benchmark on `ctx`.  The KV cache is left cleared.
+pub fn run_bench(ctx: &mut LlamaContext, params: &BenchParams) -> Result<BenchReport> {
+    if params.n_prompt == 0 || params.n_gen == 0 || params.repetitions == 0 {
+        return Err(LlamaError::InvalidArgument(
+            "n_prompt, n_gen and repetitions must be at least 1".into(),
+        ));
+    }
+    let needed = params.n_prompt + params.n_gen;
+    if needed > ctx.n_ctx() {
+        return Err(LlamaError::InvalidArgument(format!(
+            "benchmark needs {needed} tokens of context, but the context holds {}",
+            ctx.n_ctx()
+        )));
+    }
+
+    let prompt = synthetic_prompt(ctx, params.n_prompt);
+    let mut runs = Vec::with_capacity(params.repetitions as usize);
+    for _ in 0..params.repetitions {
+        runs.push(run_once(ctx, &prompt, params.n_gen)?);
+    }
+    ctx.kv_cache_clear();
+
+    let series = |f: fn(&BenchRun) -> f64| Stats::of(&runs.iter().map(f).collect::<Vec<_>>());
+    Ok(BenchReport {
+        n_prompt: params.n_prompt,
+        n_gen: params.n_gen,
+        repetitions: params.repetitions,
+        pp: series(|r| r.pp_tokens_per_sec),
+        tg: series(|r| r.tg_tokens_per_sec),
+        ttft_ms: series(|r| r.ttft_ms),
+        runs,
+    })
+}
+
+fn run_once(ctx: &mut LlamaContext, prompt: &[i32], n_gen: u32) -> Result<BenchRun> {
+    ctx.kv_cache_clear();
+    ctx.perf_reset();
+    let start = Instant::now();
+
+    //  Prompt processing, in n_batch sized chunks
+    let n_batch = ctx.n_batch().max(1) as usize;
+    let mut batch = LlamaBatch::new(n_batch as i32, 0, 1);
+    for (i, chunk) in prompt.chunks(n_batch).enumerate() {
+        batch.clear();
+        for (j, &tok) in chunk.iter().enumerate() {
+            let pos = i * n_batch + j;
+            batch.add(tok, pos as i32, &[0], pos == prompt.len() - 1)?;
+        }
+        ctx.decode(&mut batch)?;
+    }
+
+    //  Token generation (greedy, EOS ignored)
+    let mut sampler = SamplerChain::new(true);
+    sampler.add_greedy();
+    let mut ttft_ms = 0.0;
+    for (i, pos) in (0..n_gen).zip(prompt.len() as i32..) {
+        let token = sampler.sample(ctx, batch.n_tokens() - 1);
+        if i == 0 {
+            ttft_ms = start.elapsed().as_secs_f64() * 1000.0;
+        }
+        batch.clear();
+        batch.add(token, pos, &[0], true)?;
+        ctx.decode(&mut batch)?;
+    }
+
+    let perf = ctx.perf();
+    Ok(BenchRun {
+        pp_tokens_per_sec: perf.prompt_tokens_per_sec(),
+        tg_tokens_per_sec: perf.generation_tokens_per_sec(),
+        ttft_ms,
+    })
+}
+
+/// Deterministic pseudo-random tokens, led by BOS when the model uses it.
+fn synthetic_prompt(ctx: &LlamaContext, n_prompt: u32) -> Vec<i32> {
+    let model = ctx.model();
+    let n_vocab = model.n_vocab().max(1) as u64;
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut tokens: Vec<i32> = (0..n_prompt)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            ((state >> 33) % n_vocab) as i32
+        })
+        .collect();
+    if model.add_bos() {
+        tokens[0] = model.token_bos();
+    }
+    tokens
+}
@@ -1,7 +1,15 @@
 //! Chat-template formatting.
+//!
+//! Templates are first handed to `llama_chat_apply_template`, which only
+//! recognises a fixed list of well-known formats.  When that fails, the
+//! model's embedded Jinja template is executed directly with minijinja.
 
 use std::ffi::CString;
 
+use minijinja::{Environment, ErrorKind};
+use tracing::debug;
+
+use crate::error::{LlamaError, Result};
 use crate::model::LlamaModel;
 
 /// A single chat message (role + content).
@@ -81,11 +89,129 @@ pub fn apply_template(
 }
 
 /// Convenience: apply the model's own chat template.
+///
+/// Falls back to rendering the GGUF's Jinja template when llama.cpp does
+/// not recognise it.
 pub fn apply_model_template(
     model: &LlamaModel,
     messages: &[ChatMessage],
     add_assistant: bool,
 ) -> Option<String> {
     let tmpl = model.chat_template();
-    apply_template(tmpl.as_deref(), messages, add_assistant)
+    if let Some(prompt) = apply_template(tmpl.as_deref(), messages, add_assistant) {
+        return Some(prompt);
+    }
+
+    let jinja = model.jinja_template()?;
+    let bos = model.token_bos_text().unwrap_or_default();
+    let eos = model.token_eos_text().unwrap_or_default();
+    match jinja.render(messages, add_assistant, &bos, &eos) {
+        Ok(prompt) => Some(prompt),
+        Err(e) => {
+            debug!("Jinja fallback failed: {e}");
+            None
+        }
+    }
+}
+
+//  Template detection
+
+/// Markers that identify well-known template formats, most specific
+/// first.  Follows llama.cpp's `llm_chat_detect_template`.
+const TEMPLATE_MARKERS: &[(&[&str], &str)] = &[
+    (&["<|im_start|>", "<|im_sep|>"], "phi4"),
+    (&["<|im_start|>"], "chatml"),
+    (&["<|start_header_id|>", "<|end_header_id|>"], "llama3"),
+    (&["[INST]", "<<SYS>>"], "llama2"),
+    (&["[SYSTEM_PROMPT]"], "mistral-v7"),
+    (&["[INST]"], "mistral"),
+    (&["<start_of_turn>"], "gemma"),
+    (&["<|START_OF_TURN_TOKEN|>"], "command-r"),
+    (&["<|assistant|>", "<|end|>"], "phi3"),
+    (&["[gMASK]<sop>"], "chatglm4"),
+    (&["<|user|>", "<|endoftext|>"], "zephyr"),
+    (&["<｜Assistant｜>"], "deepseek3"),
+    (&["### Instruction:", "<|EOT|>"], "deepseek"),
+    (&["GPT4 Correct "], "openchat"),
+    (&["USER: ", "ASSISTANT: "], "vicuna"),
+];
+
+/// Name of the well-known format `template` follows (e.g. `chatml`,
+/// `llama3`), or `None` if it is not recognised.
+pub fn template_name(template: &str) -> Option<&'static str> {
+    TEMPLATE_MARKERS
+        .iter()
+        .find(|(markers, _)| markers.iter().all(|m| template.contains(m)))
+        .map(|&(_, name)| name)
+}
+
+//  Jinja fallback
+
+const TEMPLATE_NAME: &str = "chat";
+
+/// A chat template compiled with minijinja.
+///
+/// Rendering follows the Hugging Face `apply_chat_template` conventions:
+/// `trim_blocks` / `lstrip_blocks` are enabled, Python string methods are
+/// emulated, and `raise_exception` is available to templates.
+pub struct JinjaTemplate {
+    env: Environment<'static>,
+}
+
+impl JinjaTemplate {
+    /// Parse and compile `source`.
+    pub fn compile(source: &str) -> Result<Self> {
+        let mut env = Environment::new();
+        env.set_trim_blocks(true);
+        env.set_lstrip_blocks(true);
+        env.set_unknown_method_callback(minijinja_contrib::pycompat::unknown_method_callback);
+        env.add_function(
+            "raise_exception",
+            |msg: String| -> std::result::Result<String, minijinja::Error> {
+                Err(minijinja::Error::new(ErrorKind::InvalidOperation, msg))
+            },
+        );
+        env.add_template_owned(TEMPLATE_NAME, source.to_owned())
+            .map_err(|e| LlamaError::TemplateError(e.to_string()))?;
+        Ok(Self { env })
+    }
+
+    /// Render `messages` with the standard template variables.
+    pub fn render(
+        &self,
+        messages: &[ChatMessage],
+        add_generation_prompt: bool,
+        bos_token: &str,
+        eos_token: &str,
+    ) -> Result<String> {
+        let tmpl = self
+            .env
+            .get_template(TEMPLATE_NAME)
+            .map_err(|e| LlamaError::TemplateError(e.to_string()))?;
+        tmpl.render(minijinja::context! {
+            messages => messages,
+            add_generation_prompt => add_generation_prompt,
+            bos_token => bos_token,
+            eos_token => eos_token,
+        })
+        .map_err(|e| LlamaError::TemplateError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn template_names_prefer_the_most_specific_match() {
+        let chatml = "{% for m in messages %}<|im_start|>{{ m.role }}\n{{ m.content }}<|im_end|>{% endfor %}";
+        assert_eq!(template_name(chatml), Some("chatml"));
+        assert_eq!(
+            template_name("<|start_header_id|>user<|end_header_id|>"),
+            Some("llama3")
+        );
+        assert_eq!(template_name("[INST] <<SYS>> hi [/INST]"), Some("llama2"));
+        assert_eq!(template_name("[INST] hi [/INST]"), Some("mistral"));
+        assert_eq!(template_name("{{ messages }}"), None);
+    }
 }
@@ -2,10 +2,10 @@
 
 use std::sync::Arc;
 
-use tracing::debug;
+use tracing::{debug, warn};
 
 use crate::batch::LlamaBatch;
-use crate::error::{LlamaError, Result};
+use crate::error::{DECODE_NO_KV_SLOT, LlamaError, Result};
 use crate::model::LlamaModel;
 
 /// Owns a `llama_context` pointer and its parent model reference.
@@ -22,19 +22,39 @@ unsafe impl Send for LlamaContext {}
 impl LlamaContext {
     /// Create a new inference context.
     pub fn new(model: Arc<LlamaModel>, params: &ContextParams) -> Result<Self> {
-        let mut raw = unsafe { llama_sys::llama_context_default_params() };
-        raw.n_ctx = params.n_ctx;
-        raw.n_batch = params.n_batch;
-        raw.n_ubatch = params.n_ubatch;
-        raw.n_threads = params.n_threads;
-        raw.n_threads_batch = params.n_threads_batch;
-        raw.embeddings = params.embeddings;
+        let n_ctx_train = model.n_ctx_train().max(0) as u32;
+        if params.n_ctx > n_ctx_train && n_ctx_train > 0 && !params.rope_scaling_configured() {
+            warn!(
+                n_ctx = params.n_ctx,
+                n_ctx_train,
+                "Context is longer than the model was trained on and no RoPE scaling \
+                 is set; quality will degrade past {n_ctx_train} tokens. Consider {}",
+                suggested_rope_scaling(params.n_ctx, n_ctx_train)
+            );
+        }
+
+        params.check_cache_types()?;
+        let max_seq = unsafe { llama_sys::llama_max_parallel_sequences() } as u32;
+        if params.n_seq_max == 0 || params.n_seq_max > max_seq {
+            return Err(LlamaError::ContextCreationFailed(format!(
+                "n_seq_max must be between 1 and {max_seq}, got {}",
+                params.n_seq_max
+            )));
+        }
 
+        let raw = params.to_raw();
         let ctx = unsafe { llama_sys::llama_init_from_model(model.as_ptr(), raw) };
         if ctx.is_null() {
-            return Err(LlamaError::ContextCreationFailed(
-                "llama_init_from_model returned null".into(),
-            ));
+            // With `auto`, flash attention may have resolved to off
+            let reason = match params.type_v {
+                Some(t) if t.is_quantized() => format!(
+                    "llama_init_from_model returned null; a quantized V cache ({}) \
+                     needs flash attention, which this backend may not support",
+                    t.name()
+                ),
+                _ => "llama_init_from_model returned null".into(),
+            };
+            return Err(LlamaError::ContextCreationFailed(reason));
         }
 
         debug!(n_ctx = params.n_ctx, "Context created");
@@ -51,23 +71,59 @@ impl LlamaContext {
         &self.model
     }
 
+    /// The model, for use while the context is borrowed mutably.
+    pub(crate) fn shared_model(&self) -> Arc<LlamaModel> {
+        self.model.clone()
+    }
+
     pub fn n_ctx(&self) -> u32 {
         unsafe { llama_sys::llama_n_ctx(self.ptr) }
     }
 
+    /// Positions each sequence gets: llama.cpp splits [`n_ctx`](Self::n_ctx)
+    /// evenly between the [`n_seq_max`](Self::n_seq_max) sequences.
+    pub fn n_ctx_seq(&self) -> u32 {
+        self.n_ctx() / self.n_seq_max().max(1)
+    }
+
     pub fn n_batch(&self) -> u32 {
         unsafe { llama_sys::llama_n_batch(self.ptr) }
     }
 
+    /// Sequences the context holds; ids run from 0 to `n_seq_max - 1`.
+    pub fn n_seq_max(&self) -> u32 {
+        unsafe { llama_sys::llama_n_seq_max(self.ptr) }
+    }
+
+    /// How embeddings are pooled per sequence; `None` if llama.cpp
+    /// reports a type this crate doesn't know.
+    pub fn pooling_type(&self) -> Option<PoolingType> {
+        PoolingType::from_raw(unsafe { llama_sys::llama_pooling_type(self.ptr) })
+    }
+
     //  Core operations
 
     /// Decode (process) a batch of tokens.
+    ///
+    /// Before reporting that no KV cache slot fits the batch, llama.cpp
+    /// has already optimized the cache and retried, so that is returned
+    /// as [`LlamaError::KvCacheFull`] with the cache usage rather than
+    /// retried here.  Other positive return codes are
+    /// [`LlamaError::DecodeIncomplete`] and negative ones
+    /// [`LlamaError::DecodeFailed`].  Sequence ids past the context's
+    /// [`n_seq_max`](Self::n_seq_max) are refused up front.
     pub fn decode(&mut self, batch: &mut LlamaBatch) -> Result<()> {
+        batch.check_seq_ids(self.n_seq_max())?;
         let rc = unsafe { llama_sys::llama_decode(self.ptr, batch.raw()) };
-        if rc != 0 {
-            return Err(LlamaError::DecodeFailed(rc));
+        match rc {
+            0 => Ok(()),
+            DECODE_NO_KV_SLOT => Err(LlamaError::KvCacheFull {
+                used: self.kv_cache_used(),
+                capacity: self.n_ctx(),
+            }),
+            rc if rc > 0 => Err(LlamaError::DecodeIncomplete(rc)),
+            rc => Err(LlamaError::DecodeFailed(rc)),
         }
-        Ok(())
     }
 
     /// Logits for the token at index `i` in the last batch.
@@ -94,27 +150,128 @@ impl LlamaContext {
         }
     }
 
-    //  KV cache
+    /// Pooled embedding of sequence `seq_id` from the last batch (only
+    /// with `embeddings = true` and a pooling type other than `none`).
+    pub fn get_embeddings_seq(&self, seq_id: i32) -> Option<&[f32]> {
+        self.embeddings_seq(seq_id, self.model.n_embd().max(0) as usize)
+    }
 
-    pub fn kv_cache_clear(&mut self) {
+    /// Classifier scores of sequence `seq_id` from the last batch, one per
+    /// [`LlamaModel::cls_labels`] entry.  Only set with `rank` pooling;
+    /// other pooling types yield `None`.
+    pub fn get_classification(&self, seq_id: i32) -> Option<&[f32]> {
+        if self.pooling_type() != Some(PoolingType::Rank) {
+            return None;
+        }
+        self.embeddings_seq(seq_id, self.model.n_cls_out() as usize)
+    }
+
+    fn embeddings_seq(&self, seq_id: i32, len: usize) -> Option<&[f32]> {
         unsafe {
-            let mem = llama_sys::llama_get_memory(self.ptr);
-            if !mem.is_null() {
-                llama_sys::llama_memory_clear(mem, false);
+            let p = llama_sys::llama_get_embeddings_seq(self.ptr, seq_id);
+            if p.is_null() {
+                None
+            } else {
+                Some(std::slice::from_raw_parts(p, len))
             }
         }
     }
 
+    //  KV cache
+    //
+    // Ranges of positions are half-open, `[p0, p1)`; a negative `p0`
+    // means from the start and a negative `p1` to the end.  A negative
+    // `seq_id` matches every sequence.
+
+    fn memory(&self) -> Option<llama_sys::llama_memory_t> {
+        let mem = unsafe { llama_sys::llama_get_memory(self.ptr) };
+        (!mem.is_null()).then_some(mem)
+    }
+
+    pub fn kv_cache_clear(&mut self) {
+        if let Some(mem) = self.memory() {
+            unsafe { llama_sys::llama_memory_clear(mem, false) };
+        }
+    }
+
+    /// An upper bound on the KV cache cells in use: the position span of
+    /// every sequence the context has room for, summed and capped at
+    /// [`n_ctx`](Self::n_ctx).  Cells shared between sequences (see
+    /// [`kv_cache_seq_cp`](Self::kv_cache_seq_cp)) count once per
+    /// sequence, and a span with holes counts whole.
+    pub fn kv_cache_used(&self) -> u32 {
+        (0..self.n_seq_max() as i32)
+            .filter_map(|seq_id| {
+                let min = self.kv_cache_seq_pos_min(seq_id)?;
+                let max = self.kv_cache_seq_pos_max(seq_id)?;
+                Some((max - min + 1) as u32)
+            })
+            .sum::<u32>()
+            .min(self.n_ctx())
+    }
+
+    /// Remove the positions `[p0, p1)` of `seq_id`.  Returns `false` if
+    /// the cache can't drop part of a sequence (a recurrent model), in
+    /// which case nothing is removed.
     pub fn kv_cache_seq_rm(&mut self, seq_id: i32, p0: i32, p1: i32) -> bool {
-        unsafe {
-            let mem = llama_sys::llama_get_memory(self.ptr);
-            if mem.is_null() {
-                return false;
-            }
-            llama_sys::llama_memory_seq_rm(mem, seq_id, p0, p1)
+        match self.memory() {
+            Some(mem) => unsafe { llama_sys::llama_memory_seq_rm(mem, seq_id, p0, p1) },
+            None => false,
+        }
+    }
+
+    /// Make the positions `[p0, p1)` of `src` part of `dst` as well, so
+    /// `dst` can continue from a prefix decoded once.  The cells are
+    /// shared, not duplicated; `dst` must be below the context's
+    /// `n_seq_max`.
+    pub fn kv_cache_seq_cp(&mut self, src: i32, dst: i32, p0: i32, p1: i32) {
+        if let Some(mem) = self.memory() {
+            unsafe { llama_sys::llama_memory_seq_cp(mem, src, dst, p0, p1) };
+        }
+    }
+
+    /// Remove every sequence other than `seq_id`.
+    pub fn kv_cache_seq_keep(&mut self, seq_id: i32) {
+        if let Some(mem) = self.memory() {
+            unsafe { llama_sys::llama_memory_seq_keep(mem, seq_id) };
+        }
+    }
+
+    /// Add `delta` to the positions `[p0, p1)` of `seq_id`, e.g. a
+    /// negative one to close the gap after removing earlier tokens.
+    /// The next token decoded for the sequence must use the shifted
+    /// positions; check [`kv_cache_can_shift`](Self::kv_cache_can_shift)
+    /// first.
+    pub fn kv_cache_seq_add(&mut self, seq_id: i32, p0: i32, p1: i32, delta: i32) {
+        if let Some(mem) = self.memory() {
+            unsafe { llama_sys::llama_memory_seq_add(mem, seq_id, p0, p1, delta) };
         }
     }
 
+    /// Whether positions can be shifted with
+    /// [`kv_cache_seq_add`](Self::kv_cache_seq_add).
+    pub fn kv_cache_can_shift(&self) -> bool {
+        self.memory()
+            .is_some_and(|mem| unsafe { llama_sys::llama_memory_can_shift(mem) })
+    }
+
+    /// Smallest position held for `seq_id`, `None` when it's empty.
+    /// Everything from here to [`kv_cache_seq_pos_max`](Self::kv_cache_seq_pos_max)
+    /// is in the cache.
+    pub fn kv_cache_seq_pos_min(&self, seq_id: i32) -> Option<i32> {
+        let mem = self.memory()?;
+        let pos = unsafe { llama_sys::llama_memory_seq_pos_min(mem, seq_id) };
+        (pos >= 0).then_some(pos)
+    }
+
+    /// Largest position held for `seq_id`, `None` when it's empty.  The
+    /// next token of the sequence goes at this position plus one.
+    pub fn kv_cache_seq_pos_max(&self, seq_id: i32) -> Option<i32> {
+        let mem = self.memory()?;
+        let pos = unsafe { llama_sys::llama_memory_seq_pos_max(mem, seq_id) };
+        (pos >= 0).then_some(pos)
+    }
+
     //  Performance
 
     pub fn perf(&self) -> PerfData {
@@ -145,14 +302,162 @@ impl Drop for LlamaContext {
 
 //  ContextParams
 
+/// Element type of the KV cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KvCacheType {
+    F32,
+    F16,
+    Bf16,
+    Q8_0,
+    Q4_0,
+    Q4_1,
+    Iq4Nl,
+    Q5_0,
+    Q5_1,
+}
+
+impl KvCacheType {
+    fn as_ggml(self) -> llama_sys::ggml_type {
+        match self {
+            Self::F32 => llama_sys::ggml_type_GGML_TYPE_F32,
+            Self::F16 => llama_sys::ggml_type_GGML_TYPE_F16,
+            Self::Bf16 => llama_sys::ggml_type_GGML_TYPE_BF16,
+            Self::Q8_0 => llama_sys::ggml_type_GGML_TYPE_Q8_0,
+            Self::Q4_0 => llama_sys::ggml_type_GGML_TYPE_Q4_0,
+            Self::Q4_1 => llama_sys::ggml_type_GGML_TYPE_Q4_1,
+            Self::Iq4Nl => llama_sys::ggml_type_GGML_TYPE_IQ4_NL,
+            Self::Q5_0 => llama_sys::ggml_type_GGML_TYPE_Q5_0,
+            Self::Q5_1 => llama_sys::ggml_type_GGML_TYPE_Q5_1,
+        }
+    }
+
+    /// Block-quantized types; a quantized V cache needs flash attention.
+    pub fn is_quantized(self) -> bool {
+        !matches!(self, Self::F32 | Self::F16 | Self::Bf16)
+    }
+
+    /// Name as used by llama.cpp's `--cache-type-k/v`, e.g. `q8_0`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::F32 => "f32",
+            Self::F16 => "f16",
+            Self::Bf16 => "bf16",
+            Self::Q8_0 => "q8_0",
+            Self::Q4_0 => "q4_0",
+            Self::Q4_1 => "q4_1",
+            Self::Iq4Nl => "iq4_nl",
+            Self::Q5_0 => "q5_0",
+            Self::Q5_1 => "q5_1",
+        }
+    }
+}
+
+/// How RoPE positions are scaled to reach past the training context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RopeScalingType {
+    None,
+    Linear,
+    Yarn,
+}
+
+impl RopeScalingType {
+    fn as_raw(self) -> llama_sys::llama_rope_scaling_type {
+        match self {
+            Self::None => llama_sys::llama_rope_scaling_type_LLAMA_ROPE_SCALING_TYPE_NONE,
+            Self::Linear => llama_sys::llama_rope_scaling_type_LLAMA_ROPE_SCALING_TYPE_LINEAR,
+            Self::Yarn => llama_sys::llama_rope_scaling_type_LLAMA_ROPE_SCALING_TYPE_YARN,
+        }
+    }
+}
+
+/// How token embeddings are combined into one vector per sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolingType {
+    /// Per-token embeddings, no pooling.
+    None,
+    Mean,
+    /// The first (CLS) token's embedding.
+    Cls,
+    /// The last token's embedding.
+    Last,
+    /// Classifier head output: reranker and sequence classification
+    /// scores.
+    Rank,
+}
+
+impl PoolingType {
+    fn as_raw(self) -> llama_sys::llama_pooling_type {
+        match self {
+            Self::None => llama_sys::llama_pooling_type_LLAMA_POOLING_TYPE_NONE,
+            Self::Mean => llama_sys::llama_pooling_type_LLAMA_POOLING_TYPE_MEAN,
+            Self::Cls => llama_sys::llama_pooling_type_LLAMA_POOLING_TYPE_CLS,
+            Self::Last => llama_sys::llama_pooling_type_LLAMA_POOLING_TYPE_LAST,
+            Self::Rank => llama_sys::llama_pooling_type_LLAMA_POOLING_TYPE_RANK,
+        }
+    }
+
+    fn from_raw(raw: llama_sys::llama_pooling_type) -> Option<Self> {
+        [Self::None, Self::Mean, Self::Cls, Self::Last, Self::Rank]
+            .into_iter()
+            .find(|t| t.as_raw() == raw)
+    }
+}
+
+/// Settings for a `n_ctx` context on a model trained on `n_ctx_train`
+/// tokens, as `key=value` pairs.
+fn suggested_rope_scaling(n_ctx: u32, n_ctx_train: u32) -> String {
+    format!(
+        "rope_scaling_type=yarn rope_freq_scale={:.4} yarn_orig_ctx={n_ctx_train}",
+        n_ctx_train as f32 / n_ctx as f32
+    )
+}
+
+/// Context parameters.  The RoPE and YaRN fields are `None` to keep the
+/// model's own values (llama.cpp's "unset" defaults).
 #[derive(Debug, Clone)]
 pub struct ContextParams {
     pub n_ctx: u32,
     pub n_batch: u32,
     pub n_ubatch: u32,
+    /// Sequences the KV cache can hold; sequence ids run from 0 to
+    /// `n_seq_max - 1`.  Each gets `n_ctx / n_seq_max` positions.
+    pub n_seq_max: u32,
     pub n_threads: i32,
     pub n_threads_batch: i32,
     pub embeddings: bool,
+    /// Pooling of embeddings. `None` = the model's own (from its
+    /// metadata).
+    pub pooling_type: Option<PoolingType>,
+    /// Flash attention on/off. `None` = llama.cpp decides (on where the
+    /// backend supports it).
+    pub flash_attn: Option<bool>,
+    /// K cache element type. `None` = f16.
+    pub type_k: Option<KvCacheType>,
+    /// V cache element type. `None` = f16; quantized types need flash
+    /// attention.
+    pub type_v: Option<KvCacheType>,
+    pub rope_scaling_type: Option<RopeScalingType>,
+    /// RoPE base frequency.
+    pub rope_freq_base: Option<f32>,
+    /// RoPE frequency scale; the inverse of the context extension, e.g.
+    /// 0.25 for four times the training context.
+    pub rope_freq_scale: Option<f32>,
+    /// YaRN extrapolation mix factor.
+    pub yarn_ext_factor: Option<f32>,
+    /// YaRN magnitude scaling factor.
+    pub yarn_attn_factor: Option<f32>,
+    /// YaRN low correction dimension.
+    pub yarn_beta_fast: Option<f32>,
+    /// YaRN high correction dimension.
+    pub yarn_beta_slow: Option<f32>,
+    /// Context size the model was originally trained on, for YaRN.
+    pub yarn_orig_ctx: Option<u32>,
+    /// Fraction of holes in the KV cache that triggers defragmentation;
+    /// negative disables it.
+    pub defrag_thold: f32,
 }
 
 impl Default for ContextParams {
@@ -164,13 +469,110 @@ impl Default for ContextParams {
             n_ctx: 0, // 0 → use model's training context size
             n_batch: 2048,
             n_ubatch: 512,
+            n_seq_max: 1,
             n_threads: threads,
             n_threads_batch: threads,
             embeddings: false,
+            pooling_type: None,
+            flash_attn: None,
+            type_k: None,
+            type_v: None,
+            rope_scaling_type: None,
+            rope_freq_base: None,
+            rope_freq_scale: None,
+            yarn_ext_factor: None,
+            yarn_attn_factor: None,
+            yarn_beta_fast: None,
+            yarn_beta_slow: None,
+            yarn_orig_ctx: None,
+            defrag_thold: -1.0,
         }
     }
 }
 
+impl ContextParams {
+    /// Requested flash attention mode: `enabled`, `disabled` or `auto`.
+    ///
+    /// llama.cpp does not expose what `auto` resolved to, nor whether an
+    /// explicit request was downgraded because the backend lacks the
+    /// kernel (it logs a warning instead), so this is the request as sent.
+    pub fn flash_attn_mode(&self) -> &'static str {
+        match self.flash_attn {
+            Some(true) => "enabled",
+            Some(false) => "disabled",
+            None => "auto",
+        }
+    }
+
+    /// Refuse combinations llama.cpp would reject, with a reason.
+    fn check_cache_types(&self) -> Result<()> {
+        match self.type_v {
+            Some(t) if t.is_quantized() && self.flash_attn == Some(false) => {
+                Err(LlamaError::ContextCreationFailed(format!(
+                    "a quantized V cache ({}) requires flash attention; \
+                     enable flash_attn or use an f16 V cache",
+                    t.name()
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether any setting stretches RoPE past the training context.
+    fn rope_scaling_configured(&self) -> bool {
+        self.rope_scaling_type
+            .is_some_and(|t| t != RopeScalingType::None)
+            || self.rope_freq_scale.is_some()
+            || self.rope_freq_base.is_some()
+    }
+
+    fn to_raw(&self) -> llama_sys::llama_context_params {
+        let mut raw = unsafe { llama_sys::llama_context_default_params() };
+        raw.n_ctx = self.n_ctx;
+        raw.n_batch = self.n_batch;
+        raw.n_ubatch = self.n_ubatch;
+        raw.n_seq_max = self.n_seq_max;
+        raw.n_threads = self.n_threads;
+        raw.n_threads_batch = self.n_threads_batch;
+        raw.embeddings = self.embeddings;
+        if let Some(t) = self.pooling_type {
+            raw.pooling_type = t.as_raw();
+        }
+        if let Some(enabled) = self.flash_attn {
+            raw.flash_attn_type = if enabled {
+                llama_sys::llama_flash_attn_type_LLAMA_FLASH_ATTN_TYPE_ENABLED
+            } else {
+                llama_sys::llama_flash_attn_type_LLAMA_FLASH_ATTN_TYPE_DISABLED
+            };
+        }
+        if let Some(t) = self.type_k {
+            raw.type_k = t.as_ggml();
+        }
+        if let Some(t) = self.type_v {
+            raw.type_v = t.as_ggml();
+        }
+        if let Some(t) = self.rope_scaling_type {
+            raw.rope_scaling_type = t.as_raw();
+        }
+        let set = |field: &mut f32, value: Option<f32>| {
+            if let Some(v) = value {
+                *field = v;
+            }
+        };
+        set(&mut raw.rope_freq_base, self.rope_freq_base);
+        set(&mut raw.rope_freq_scale, self.rope_freq_scale);
+        set(&mut raw.yarn_ext_factor, self.yarn_ext_factor);
+        set(&mut raw.yarn_attn_factor, self.yarn_attn_factor);
+        set(&mut raw.yarn_beta_fast, self.yarn_beta_fast);
+        set(&mut raw.yarn_beta_slow, self.yarn_beta_slow);
+        if let Some(n) = self.yarn_orig_ctx {
+            raw.yarn_orig_ctx = n;
+        }
+        raw.defrag_thold = self.defrag_thold;
+        raw
+    }
+}
+
 //  PerfData
 
 #[derive(Debug, Clone)]
@@ -202,3 +604,131 @@ impl PerfData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unset_rope_fields_keep_llama_defaults() {
+        let defaults = unsafe { llama_sys::llama_context_default_params() };
+        let raw = ContextParams::default().to_raw();
+        assert_eq!(raw.rope_scaling_type, defaults.rope_scaling_type);
+        assert_eq!(
+            raw.rope_freq_base.to_bits(),
+            defaults.rope_freq_base.to_bits()
+        );
+        assert_eq!(
+            raw.rope_freq_scale.to_bits(),
+            defaults.rope_freq_scale.to_bits()
+        );
+        assert_eq!(
+            raw.yarn_ext_factor.to_bits(),
+            defaults.yarn_ext_factor.to_bits()
+        );
+        assert_eq!(
+            raw.yarn_attn_factor.to_bits(),
+            defaults.yarn_attn_factor.to_bits()
+        );
+        assert_eq!(
+            raw.yarn_beta_fast.to_bits(),
+            defaults.yarn_beta_fast.to_bits()
+        );
+        assert_eq!(
+            raw.yarn_beta_slow.to_bits(),
+            defaults.yarn_beta_slow.to_bits()
+        );
+        assert_eq!(raw.yarn_orig_ctx, defaults.yarn_orig_ctx);
+        assert_eq!(raw.n_seq_max, defaults.n_seq_max);
+        assert_eq!(raw.defrag_thold.to_bits(), defaults.defrag_thold.to_bits());
+        assert!(!ContextParams::default().rope_scaling_configured());
+    }
+
+    #[test]
+    fn rope_fields_map_onto_raw_params() {
+        let params = ContextParams {
+            n_ctx: 32768,
+            rope_scaling_type: Some(RopeScalingType::Yarn),
+            rope_freq_scale: Some(0.25),
+            yarn_orig_ctx: Some(8192),
+            yarn_beta_fast: Some(32.0),
+            ..Default::default()
+        };
+        assert!(params.rope_scaling_configured());
+        let raw = params.to_raw();
+        assert_eq!(
+            raw.rope_scaling_type,
+            llama_sys::llama_rope_scaling_type_LLAMA_ROPE_SCALING_TYPE_YARN
+        );
+        assert_eq!(raw.rope_freq_scale, 0.25);
+        assert_eq!(raw.yarn_orig_ctx, 8192);
+        assert_eq!(raw.yarn_beta_fast, 32.0);
+        assert_eq!(
+            suggested_rope_scaling(32768, 8192),
+            "rope_scaling_type=yarn rope_freq_scale=0.2500 yarn_orig_ctx=8192"
+        );
+    }
+
+    #[test]
+    fn pooling_type_round_trips_through_raw() {
+        for t in [
+            PoolingType::None,
+            PoolingType::Mean,
+            PoolingType::Cls,
+            PoolingType::Last,
+            PoolingType::Rank,
+        ] {
+            assert_eq!(PoolingType::from_raw(t.as_raw()), Some(t));
+        }
+        assert_eq!(
+            PoolingType::from_raw(llama_sys::llama_pooling_type_LLAMA_POOLING_TYPE_UNSPECIFIED),
+            None
+        );
+
+        let defaults = unsafe { llama_sys::llama_context_default_params() };
+        assert_eq!(
+            ContextParams::default().to_raw().pooling_type,
+            defaults.pooling_type
+        );
+        let rank = ContextParams {
+            embeddings: true,
+            pooling_type: Some(PoolingType::Rank),
+            ..Default::default()
+        };
+        assert_eq!(
+            rank.to_raw().pooling_type,
+            llama_sys::llama_pooling_type_LLAMA_POOLING_TYPE_RANK
+        );
+    }
+
+    #[test]
+    fn quantized_v_cache_needs_flash_attn() {
+        let params = ContextParams {
+            type_k: Some(KvCacheType::Q8_0),
+            type_v: Some(KvCacheType::Q4_0),
+            flash_attn: Some(false),
+            ..Default::default()
+        };
+        let err = params.check_cache_types().unwrap_err().to_string();
+        assert!(
+            err.contains("q4_0") && err.contains("flash attention"),
+            "{err}"
+        );
+
+        // A quantized K cache alone is fine, as is leaving the choice to llama.cpp
+        let k_only = ContextParams {
+            type_v: None,
+            ..params.clone()
+        };
+        assert!(k_only.check_cache_types().is_ok());
+        let auto = ContextParams {
+            flash_attn: None,
+            ..params
+        };
+        assert!(auto.check_cache_types().is_ok());
+
+        let raw = auto.to_raw();
+        assert_eq!(raw.type_k, llama_sys::ggml_type_GGML_TYPE_Q8_0);
+        assert_eq!(raw.type_v, llama_sys::ggml_type_GGML_TYPE_Q4_0);
+    }
+}
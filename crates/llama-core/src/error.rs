@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::batch::BatchError;
+
 #[derive(Error, Debug)]
 pub enum LlamaError {
     #[error("Failed to load model from '{path}': {reason}")]
@@ -8,15 +10,36 @@ pub enum LlamaError {
     #[error("Failed to create context: {0}")]
     ContextCreationFailed(String),
 
+    /// `llama_decode` failed hard (negative return code).
     #[error("Decode failed with code {0}")]
     DecodeFailed(i32),
 
+    /// `llama_decode` found no room in the KV cache for the batch.
+    #[error(
+        "KV cache is full: {used} of {capacity} tokens in use; \
+         lower max_tokens, load the model with a larger ctx_size or \
+         enable context shifting"
+    )]
+    KvCacheFull { used: u32, capacity: u32 },
+
+    /// `llama_decode` stopped without processing the batch (positive
+    /// return code other than [`DECODE_NO_KV_SLOT`]); the context is
+    /// left as it was.
+    #[error("Decode did not complete (code {0})")]
+    DecodeIncomplete(i32),
+
     #[error("Encode failed with code {0}")]
     EncodeFailed(i32),
 
+    #[error(transparent)]
+    Batch(#[from] BatchError),
+
     #[error("Tokenization failed: {0}")]
     TokenizationFailed(String),
 
+    #[error("Chat template error: {0}")]
+    TemplateError(String),
+
     #[error("Sampler error: {0}")]
     SamplerError(String),
 
@@ -32,8 +55,68 @@ pub enum LlamaError {
     #[error("FFI panic: {0}")]
     FfiPanic(String),
 
+    #[error("Model loading is paused for maintenance: {0}")]
+    LoadingPaused(String),
+
+    #[error("{0}")]
+    InsufficientMemory(String),
+
+    #[error("{0}")]
+    InvalidArgument(String),
+
     #[error("{0}")]
     Other(String),
 }
 
+/// `llama_decode` return code when no KV cache slot fits the batch.
+pub const DECODE_NO_KV_SLOT: i32 = 1;
+
+impl LlamaError {
+    /// Stable machine-readable code reported to API clients.
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            Self::ModelLoadFailed { .. } => "invalid_model",
+            Self::ContextCreationFailed(_) => "context_creation_failed",
+            Self::DecodeFailed(_) => "decode_failed",
+            Self::KvCacheFull { .. } => "kv_cache_full",
+            Self::DecodeIncomplete(_) => "decode_incomplete",
+            Self::Batch(_) => "invalid_batch",
+            Self::EncodeFailed(_) => "encode_failed",
+            Self::TokenizationFailed(_) => "tokenization_failed",
+            Self::TemplateError(_) => "chat_template_error",
+            Self::SamplerError(_) => "sampler_error",
+            Self::BackendNotInitialized => "backend_not_initialized",
+            Self::ModelNotLoaded => "model_not_loaded",
+            Self::LoadingPaused(_) => "loading_paused",
+            Self::InsufficientMemory(_) => "insufficient_memory",
+            Self::InvalidArgument(_) => "invalid_argument",
+            Self::NullPointer | Self::FfiPanic(_) | Self::Other(_) => "internal_error",
+        }
+    }
+
+    /// A decode error that leaves the context usable, so the caller may
+    /// keep what it has or try again with a smaller batch.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(self, Self::KvCacheFull { .. } | Self::DecodeIncomplete(_))
+    }
+
+    /// HTTP status code for a request that failed with this error.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            Self::TokenizationFailed(_)
+            | Self::TemplateError(_)
+            | Self::SamplerError(_)
+            | Self::InvalidArgument(_) => 400,
+            Self::InsufficientMemory(_) => 409,
+            Self::ModelLoadFailed { .. } => 502,
+            Self::KvCacheFull { .. }
+            | Self::DecodeIncomplete(_)
+            | Self::ContextCreationFailed(_)
+            | Self::ModelNotLoaded
+            | Self::LoadingPaused(_) => 503,
+            _ => 500,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, LlamaError>;
@@ -0,0 +1,81 @@
+//! Fill-in-the-middle (infill) prompt assembly.
+//!
+//! Mirrors llama.cpp server's `format_infill`: optional repo-level
+//! context, then `<PRE>prefix<SUF>suffix<MID>prompt`.
+
+use crate::error::Result;
+use crate::model::LlamaModel;
+
+/// FIM special tokens of a vocabulary.
+///
+/// `rep` and `sep` are only present in models trained on repo-level
+/// context (e.g. Qwen2.5-Coder).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FimTokens {
+    pub pre: i32,
+    pub suf: i32,
+    pub mid: i32,
+    pub rep: Option<i32>,
+    pub sep: Option<i32>,
+}
+
+/// A file of extra context sent with an infill request.
+#[derive(Debug, Clone, Default)]
+pub struct InfillChunk {
+    pub filename: String,
+    pub text: String,
+}
+
+/// Separator between extra chunks for models without `<SEP>`.
+const CHUNK_SEPARATOR: &str = "\n\n--- snippet ---\n\n";
+
+/// Tokenize an infill prompt for `model`.
+///
+/// `extra` chunks precede the prefix.  With `<REP>`/`<SEP>` tokens they
+/// use the repo-level format (`<REP>myproject\n<SEP>{filename}\n{text}…`);
+/// otherwise they are joined with a plain-text separator.  `prompt` is
+/// appended after `<MID>` as the start of the completion.
+pub fn infill_tokens(
+    model: &LlamaModel,
+    fim: &FimTokens,
+    prefix: &str,
+    suffix: &str,
+    prompt: &str,
+    extra: &[InfillChunk],
+) -> Result<Vec<i32>> {
+    let vocab = model.vocab();
+    let tok = |text: &str| vocab.tokenize(text, false, false);
+
+    let mut tokens = Vec::new();
+    if model.add_bos() {
+        tokens.push(model.token_bos());
+    }
+
+    if let Some(rep) = fim.rep {
+        tokens.push(rep);
+        tokens.extend(tok("myproject\n")?);
+    }
+    for chunk in extra {
+        match fim.sep {
+            Some(sep) => {
+                tokens.push(sep);
+                tokens.extend(tok(&format!("{}\n", chunk.filename))?);
+            }
+            None => tokens.extend(tok(CHUNK_SEPARATOR)?),
+        }
+        tokens.extend(tok(&chunk.text)?);
+    }
+    if let Some(sep) = fim.sep {
+        // The current file's name isn't part of the request.
+        tokens.push(sep);
+        tokens.extend(tok("filename\n")?);
+    }
+
+    tokens.push(fim.pre);
+    tokens.extend(tok(prefix)?);
+    tokens.push(fim.suf);
+    tokens.extend(tok(suffix)?);
+    tokens.push(fim.mid);
+    tokens.extend(tok(prompt)?);
+    Ok(tokens)
+}
@@ -1,12 +1,16 @@
 //! Streaming token generation.
 
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use tokio::sync::mpsc;
 use tracing::debug;
 
 use crate::batch::LlamaBatch;
 use crate::context::LlamaContext;
+use crate::error::LlamaError;
 use crate::sampler::SamplingParams;
-use crate::token::token_to_piece;
+use crate::vocab::LlamaVocab;
 
 /// Parameters for a generation request.
 #[derive(Debug, Clone)]
@@ -19,6 +23,12 @@ pub struct GenerateRequest {
     pub stop_words: Vec<String>,
     /// Sampling configuration.
     pub sampling_params: SamplingParams,
+    /// Stream control tokens (e.g. `<|im_end|>`) as text instead of
+    /// dropping them.
+    pub return_special: bool,
+    /// Keep going past end-of-generation tokens, until `max_tokens` or a
+    /// stop word; the tokens themselves are not output.
+    pub ignore_eos: bool,
 }
 
 /// Events emitted during streaming generation.
@@ -33,17 +43,80 @@ pub enum GenerateEvent {
         completion_tokens: u32,
     },
     /// An error occurred mid-generation.
-    Error(String),
+    Error(GenerateError),
+}
+
+/// Why a generation failed.  Failures caused by a [`LlamaError`] keep its
+/// HTTP status and code so servers can report them like other errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerateError {
+    pub message: String,
+    /// [`LlamaError::http_status`]; 503 when cancelled, else 500.
+    pub status: u16,
+    /// [`LlamaError::error_code`], if a `LlamaError` caused it.
+    pub code: Option<&'static str>,
+}
+
+impl GenerateError {
+    /// `err`, with `context` before its message.
+    pub fn llama(context: &str, err: &LlamaError) -> Self {
+        Self {
+            message: format!("{context}: {err}"),
+            status: err.http_status(),
+            code: Some(err.error_code()),
+        }
+    }
+
+    /// A failure with no `LlamaError` behind it.
+    pub fn other(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            status: 500,
+            code: None,
+        }
+    }
+
+    /// A [`CancelToken`] stopped the generation.
+    pub fn cancelled() -> Self {
+        Self {
+            message: CANCELLED.into(),
+            status: 503,
+            code: None,
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.message == CANCELLED && self.code.is_none()
+    }
+}
+
+impl From<LlamaError> for GenerateError {
+    fn from(err: LlamaError) -> Self {
+        Self {
+            message: err.to_string(),
+            status: err.http_status(),
+            code: Some(err.error_code()),
+        }
+    }
+}
+
+impl std::fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum FinishReason {
-    /// Natural stop (EOS / EOT token).
+    /// Natural stop (an end-of-generation token).
     Stop,
     /// Reached `max_tokens`.
     Length,
     /// Matched a stop word.
     StopWord(String),
+    /// Cut short by the caller's time limit.  Never produced by
+    /// [`generate_blocking`] itself.
+    Timeout,
 }
 
 impl std::fmt::Display for FinishReason {
@@ -52,35 +125,180 @@ impl std::fmt::Display for FinishReason {
             Self::Stop => write!(f, "stop"),
             Self::Length => write!(f, "length"),
             Self::StopWord(w) => write!(f, "stop_word:{w}"),
+            Self::Timeout => write!(f, "timeout"),
         }
     }
 }
 
+/// Error message sent when a [`CancelToken`] stops a generation.
+pub const CANCELLED: &str = "generation cancelled";
+
+/// Shared flag that asks running generations to stop.
+///
+/// Clones observe the same flag; it is checked before the prompt is
+/// decoded and before every generation step.  A [`child`](Self::child)
+/// token is also cancelled by its parents, but cancelling it leaves them
+/// alone.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+    parents: Vec<Arc<AtomicBool>>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that is cancelled along with this one, or on its own.
+    pub fn child(&self) -> Self {
+        let mut parents = self.parents.clone();
+        parents.push(self.flag.clone());
+        Self {
+            flag: Arc::default(),
+            parents,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        std::iter::once(&self.flag)
+            .chain(&self.parents)
+            .any(|f| f.load(Ordering::Relaxed))
+    }
+}
+
+/// The vocabulary queries the generation loop makes about a sampled
+/// token.
+pub(crate) trait TokenVocab {
+    fn is_eog(&self, token: i32) -> bool;
+    fn is_control(&self, token: i32) -> bool;
+    fn piece(&self, token: i32, special: bool) -> String;
+}
+
+impl TokenVocab for LlamaVocab<'_> {
+    fn is_eog(&self, token: i32) -> bool {
+        LlamaVocab::is_eog(self, token)
+    }
+    fn is_control(&self, token: i32) -> bool {
+        LlamaVocab::is_control(self, token)
+    }
+    fn piece(&self, token: i32, special: bool) -> String {
+        self.token_to_piece_with(token, special)
+    }
+}
+
+/// What a sampled token means for the output.
+#[derive(Debug, PartialEq, Eq)]
+enum Sampled {
+    /// An end-of-generation token.
+    Stop,
+    /// A control token the caller did not ask for.
+    Hidden,
+    Text(String),
+}
+
+/// Classify `token`; with `ignore_eos`, end-of-generation tokens are
+/// hidden instead of stopping.
+fn classify(
+    vocab: &impl TokenVocab,
+    token: i32,
+    return_special: bool,
+    ignore_eos: bool,
+) -> Sampled {
+    if vocab.is_eog(token) {
+        if ignore_eos {
+            Sampled::Hidden
+        } else {
+            Sampled::Stop
+        }
+    } else if vocab.is_control(token) && !return_special {
+        Sampled::Hidden
+    } else {
+        Sampled::Text(vocab.piece(token, return_special))
+    }
+}
+
+/// How many leading tokens of `tokens` a KV cache holding `cached` (as
+/// sequence 0, from position 0) already has.  At least one token is left
+/// over, since decoding it yields the logits generation starts from.
+pub fn reusable_prefix(cached: &[i32], tokens: &[i32]) -> usize {
+    let shared = cached
+        .iter()
+        .zip(tokens)
+        .take_while(|(a, b)| a == b)
+        .count();
+    shared.min(tokens.len().saturating_sub(1))
+}
+
+/// Trim sequence 0 of `ctx`, which holds `cached` and possibly tokens
+/// generated after it, down to the prefix it shares with `tokens`.
+///
+/// Returns the number of tokens kept, to pass to
+/// [`generate_blocking_from`].  Caches that can't drop a tail (recurrent
+/// models) are cleared instead, keeping nothing.
+pub fn reuse_kv_prefix(ctx: &mut LlamaContext, cached: &[i32], tokens: &[i32]) -> usize {
+    let keep = reusable_prefix(cached, tokens);
+    if keep > 0 && ctx.kv_cache_seq_rm(0, keep as i32, -1) {
+        return keep;
+    }
+    ctx.kv_cache_clear();
+    0
+}
+
 /// Run a synchronous (blocking) generation loop.
 ///
 /// This is intended to be called inside `tokio::task::spawn_blocking`.
 /// Produced tokens are sent over `tx`; the function returns when
-/// generation finishes or the receiver is dropped.
+/// generation finishes, the receiver is dropped or `cancel` fires, the
+/// latter ending the stream with an [`GenerateEvent::Error`].
 pub fn generate_blocking(
     ctx: &mut LlamaContext,
     request: &GenerateRequest,
     tx: mpsc::Sender<GenerateEvent>,
+    cancel: &CancelToken,
+) {
+    generate_blocking_from(ctx, request, 0, tx, cancel);
+}
+
+/// [`generate_blocking`] for a context whose sequence 0 already holds the
+/// first `n_past` prompt tokens (see [`reuse_kv_prefix`]); only the rest
+/// are decoded.  `prompt_tokens` still counts the whole prompt.
+pub fn generate_blocking_from(
+    ctx: &mut LlamaContext,
+    request: &GenerateRequest,
+    n_past: usize,
+    tx: mpsc::Sender<GenerateEvent>,
+    cancel: &CancelToken,
 ) {
-    let vocab = ctx.model().vocab();
-    let n_ctx = ctx.n_ctx() as i32;
-    let eos = ctx.model().token_eos();
-    let eot = ctx.model().token_eot();
+    let model = ctx.shared_model();
+    let vocab = model.vocab();
+    let n_ctx = ctx.n_ctx_seq() as i32;
 
     //  Prompt processing
-    let batch_cap = request.tokens.len().max(1) as i32;
+    let new_tokens = &request.tokens[n_past.min(request.tokens.len())..];
+    let batch_cap = new_tokens.len().max(1) as i32;
     let mut batch = LlamaBatch::new(batch_cap, 0, 1);
-    for (i, &tok) in request.tokens.iter().enumerate() {
-        let logits = i == request.tokens.len() - 1;
-        batch.add(tok, i as i32, &[0], logits);
+    if let Err(e) = batch.add_sequence(new_tokens, 0, n_past as i32, true) {
+        let _ = tx.blocking_send(GenerateEvent::Error(GenerateError::llama(
+            "prompt decode",
+            &e.into(),
+        )));
+        return;
     }
 
+    if cancel.is_cancelled() {
+        let _ = tx.blocking_send(GenerateEvent::Error(GenerateError::cancelled()));
+        return;
+    }
     if let Err(e) = ctx.decode(&mut batch) {
-        let _ = tx.blocking_send(GenerateEvent::Error(format!("prompt decode: {e}")));
+        let _ = tx.blocking_send(GenerateEvent::Error(GenerateError::llama(
+            "prompt decode",
+            &e,
+        )));
         return;
     }
 
@@ -88,10 +306,16 @@ pub fn generate_blocking(
     let mut n_cur = request.tokens.len() as i32;
     let mut completion_tokens = 0u32;
     let mut generated_text = String::new();
-    let mut sampler = request.sampling_params.clone().into_chain();
+    let mut sampler = request.sampling_params.build_chain();
 
     //  Token generation loop
     loop {
+        if cancel.is_cancelled() {
+            debug!("Generation cancelled (token)");
+            let _ = tx.blocking_send(GenerateEvent::Error(GenerateError::cancelled()));
+            break;
+        }
+
         // Max-tokens guard
         if completion_tokens >= request.max_tokens {
             let _ = tx.blocking_send(GenerateEvent::Done {
@@ -105,40 +329,45 @@ pub fn generate_blocking(
         let new_token = sampler.sample(ctx, batch.n_tokens() - 1);
         completion_tokens += 1;
 
-        // EOS / EOT
-        if new_token == eos || new_token == eot {
-            let _ = tx.blocking_send(GenerateEvent::Done {
-                finish_reason: FinishReason::Stop,
-                prompt_tokens,
-                completion_tokens,
-            });
-            break;
-        }
-
-        let piece = token_to_piece(vocab, new_token);
-        generated_text.push_str(&piece);
-
-        // Stop-word check
-        let mut stopped = false;
-        for sw in &request.stop_words {
-            if generated_text.ends_with(sw.as_str()) {
+        match classify(
+            &vocab,
+            new_token,
+            request.return_special,
+            request.ignore_eos,
+        ) {
+            Sampled::Stop => {
                 let _ = tx.blocking_send(GenerateEvent::Done {
-                    finish_reason: FinishReason::StopWord(sw.clone()),
+                    finish_reason: FinishReason::Stop,
                     prompt_tokens,
                     completion_tokens,
                 });
-                stopped = true;
                 break;
             }
-        }
-        if stopped {
-            break;
-        }
+            // Still decoded below so the model sees it
+            Sampled::Hidden => {}
+            Sampled::Text(piece) => {
+                generated_text.push_str(&piece);
 
-        // Send token to receiver
-        if tx.blocking_send(GenerateEvent::Token(piece)).is_err() {
-            debug!("Generation cancelled (receiver dropped)");
-            break;
+                // Stop-word check
+                if let Some(sw) = request
+                    .stop_words
+                    .iter()
+                    .find(|sw| generated_text.ends_with(sw.as_str()))
+                {
+                    let _ = tx.blocking_send(GenerateEvent::Done {
+                        finish_reason: FinishReason::StopWord(sw.clone()),
+                        prompt_tokens,
+                        completion_tokens,
+                    });
+                    break;
+                }
+
+                // Send token to receiver
+                if tx.blocking_send(GenerateEvent::Token(piece)).is_err() {
+                    debug!("Generation cancelled (receiver dropped)");
+                    break;
+                }
+            }
         }
 
         // Context-size guard
@@ -153,12 +382,97 @@ pub fn generate_blocking(
 
         // Next decode step
         batch.clear();
-        batch.add(new_token, n_cur, &[0], true);
+        let added = batch.add(new_token, n_cur, &[0], true);
         n_cur += 1;
 
-        if let Err(e) = ctx.decode(&mut batch) {
-            let _ = tx.blocking_send(GenerateEvent::Error(format!("decode: {e}")));
-            break;
+        match added
+            .map_err(LlamaError::from)
+            .and_then(|()| ctx.decode(&mut batch))
+        {
+            Ok(()) => {}
+            // A full KV cache included: unlike the context-size guard it
+            // is not a token limit, so clients see the error and its hint
+            Err(e) => {
+                let _ = tx.blocking_send(GenerateEvent::Error(GenerateError::llama("decode", &e)));
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tokens 2 and 7 end generation; 3 is a control token.
+    struct FakeVocab;
+
+    impl TokenVocab for FakeVocab {
+        fn is_eog(&self, token: i32) -> bool {
+            [2, 7].contains(&token)
         }
+        fn is_control(&self, token: i32) -> bool {
+            [2, 3, 7].contains(&token)
+        }
+        fn piece(&self, token: i32, special: bool) -> String {
+            match token {
+                3 if special => "<|im_start|>".into(),
+                3 => String::new(),
+                t => format!("t{t}"),
+            }
+        }
+    }
+
+    #[test]
+    fn reusable_prefix_leaves_a_token_to_decode() {
+        assert_eq!(reusable_prefix(&[], &[1, 2, 3]), 0);
+        assert_eq!(reusable_prefix(&[1, 2, 9, 9], &[1, 2, 3, 4]), 2);
+        assert_eq!(reusable_prefix(&[1, 2], &[1, 2, 3, 4]), 2);
+        // Same prompt again: the last token is decoded anew
+        assert_eq!(reusable_prefix(&[1, 2, 3], &[1, 2, 3]), 2);
+        // A shorter prompt than the cache
+        assert_eq!(reusable_prefix(&[1, 2, 3, 4], &[1, 2]), 1);
+        assert_eq!(reusable_prefix(&[1], &[]), 0);
+    }
+
+    #[test]
+    fn every_eog_token_stops() {
+        for return_special in [false, true] {
+            assert_eq!(
+                classify(&FakeVocab, 2, return_special, false),
+                Sampled::Stop
+            );
+            assert_eq!(
+                classify(&FakeVocab, 7, return_special, false),
+                Sampled::Stop
+            );
+        }
+        assert_eq!(
+            classify(&FakeVocab, 5, false, false),
+            Sampled::Text("t5".into())
+        );
+    }
+
+    #[test]
+    fn ignored_eog_tokens_are_hidden() {
+        for return_special in [false, true] {
+            assert_eq!(
+                classify(&FakeVocab, 2, return_special, true),
+                Sampled::Hidden
+            );
+        }
+        assert_eq!(
+            classify(&FakeVocab, 5, false, true),
+            Sampled::Text("t5".into())
+        );
+    }
+
+    #[test]
+    fn control_tokens_are_hidden_unless_requested() {
+        assert_eq!(classify(&FakeVocab, 3, false, false), Sampled::Hidden);
+        assert_eq!(
+            classify(&FakeVocab, 3, true, false),
+            Sampled::Text("<|im_start|>".into())
+        );
     }
 }
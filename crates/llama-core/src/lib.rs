@@ -1,29 +1,36 @@
 //! Safe Rust wrapper around the llama.cpp C API.
 //!
 //! Provides RAII-managed types for model loading, context creation,
-//! sampling, tokenization, and streaming text generation.
-
-// Public API intentionally wraps raw llama.cpp pointers (e.g. *const llama_vocab)
-// in a safe higher-level interface.  The pointers are always valid for the
-// lifetime of the parent RAII object, so suppress the clippy lint.
-#![allow(clippy::not_unsafe_ptr_arg_deref)]
+//! sampling, tokenization, fill-in-the-middle prompts, streaming
+//! text generation, and throughput benchmarks.
 
 pub mod backend;
 pub mod batch;
+pub mod bench;
 pub mod chat;
 pub mod context;
 pub mod error;
+pub mod fim;
 pub mod generate;
 pub mod model;
 pub mod sampler;
 pub mod token;
+pub mod vocab;
 
-pub use backend::LlamaBackend;
-pub use batch::LlamaBatch;
-pub use chat::{ChatMessage, apply_template};
-pub use context::{ContextParams, LlamaContext, PerfData};
+pub use backend::{DeviceInfo, LlamaBackend, NumaStrategy};
+pub use batch::{BatchError, LlamaBatch};
+pub use bench::{BenchParams, BenchReport, BenchRun, Stats, run_bench};
+pub use chat::{ChatMessage, JinjaTemplate, apply_model_template, apply_template, template_name};
+pub use context::{
+    ContextParams, KvCacheType, LlamaContext, PerfData, PoolingType, RopeScalingType,
+};
 pub use error::{LlamaError, Result};
-pub use generate::{FinishReason, GenerateEvent, GenerateRequest};
-pub use model::{LlamaModel, ModelParams};
+pub use fim::{FimTokens, InfillChunk, infill_tokens};
+pub use generate::{CancelToken, FinishReason, GenerateError, GenerateEvent, GenerateRequest};
+pub use model::{
+    KvOverride, KvOverrideValue, LlamaModel, ModelInfo, ModelParams, ProgressCallback, SplitMode,
+};
 pub use sampler::{SamplerChain, SamplingParams};
+#[allow(deprecated)]
 pub use token::{detokenize, token_to_piece, tokenize};
+pub use vocab::{LlamaVocab, TokenAttr, VocabType};
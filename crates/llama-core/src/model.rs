@@ -1,16 +1,23 @@
 //! Safe RAII wrapper around `llama_model`.
 
-use std::ffi::{CStr, CString};
+use std::ffi::{CStr, CString, c_void};
+use std::panic::{AssertUnwindSafe, catch_unwind};
 use std::path::Path;
 use std::ptr;
+use std::sync::{Arc, OnceLock};
 
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
+use crate::chat::JinjaTemplate;
 use crate::error::{LlamaError, Result};
+use crate::fim::FimTokens;
+use crate::vocab::{LlamaVocab, VocabType};
 
 /// Owns a `llama_model` pointer and frees it on drop.
 pub struct LlamaModel {
     ptr: *mut llama_sys::llama_model,
+    /// Compiled chat template, parsed on first use.
+    jinja: OnceLock<Option<JinjaTemplate>>,
 }
 
 // Safety: llama_model is internally read-only after creation.
@@ -29,10 +36,14 @@ impl LlamaModel {
             reason: "Path contains null byte".into(),
         })?;
 
-        let mut raw = unsafe { llama_sys::llama_model_default_params() };
-        raw.n_gpu_layers = params.n_gpu_layers;
-        raw.use_mmap = params.use_mmap;
-        raw.use_mlock = params.use_mlock;
+        params.validate(crate::backend::gpu_device_count())?;
+        let mut buffers = RawBuffers::default();
+        let mut raw = params.to_raw(&mut buffers);
+        if let Some(cb) = &params.progress {
+            // `cb` outlives the synchronous load call below.
+            raw.progress_callback = Some(progress_trampoline);
+            raw.progress_callback_user_data = cb as *const ProgressCallback as *mut c_void;
+        }
 
         info!(path = %path.display(), "Loading model…");
         let model = unsafe { llama_sys::llama_model_load_from_file(c_path.as_ptr(), raw) };
@@ -45,7 +56,11 @@ impl LlamaModel {
         }
 
         info!(path = %path.display(), "Model loaded");
-        Ok(Self { ptr: model })
+        crate::backend::note_model_loaded();
+        Ok(Self {
+            ptr: model,
+            jinja: OnceLock::new(),
+        })
     }
 
     //  Accessors
@@ -54,9 +69,9 @@ impl LlamaModel {
         self.ptr
     }
 
-    /// Vocabulary handle (valid for the lifetime of the model).
-    pub fn vocab(&self) -> *const llama_sys::llama_vocab {
-        unsafe { llama_sys::llama_model_get_vocab(self.ptr) }
+    /// The model's vocabulary.
+    pub fn vocab(&self) -> LlamaVocab<'_> {
+        unsafe { LlamaVocab::from_raw(llama_sys::llama_model_get_vocab(self.ptr)) }
     }
 
     pub fn n_params(&self) -> u64 {
@@ -92,6 +107,49 @@ impl LlamaModel {
         unsafe { llama_sys::llama_model_n_embd(self.ptr) }
     }
 
+    pub fn n_layer(&self) -> i32 {
+        unsafe { llama_sys::llama_model_n_layer(self.ptr) }
+    }
+
+    /// Attention (query) heads per layer.
+    pub fn n_head(&self) -> i32 {
+        unsafe { llama_sys::llama_model_n_head(self.ptr) }
+    }
+
+    /// Key/value heads per layer; fewer than [`n_head`](Self::n_head)
+    /// with grouped-query attention.
+    pub fn n_head_kv(&self) -> i32 {
+        unsafe { llama_sys::llama_model_n_head_kv(self.ptr) }
+    }
+
+    /// RoPE frequency scale the model was trained with (1.0 unless it
+    /// was fine-tuned for a stretched context).
+    pub fn rope_freq_scale_train(&self) -> f32 {
+        unsafe { llama_sys::llama_model_rope_freq_scale_train(self.ptr) }
+    }
+
+    /// RoPE base frequency from `{arch}.rope.freq_base`, if set.
+    pub fn rope_freq_base(&self) -> Option<f32> {
+        let arch = self.meta_val_str("general.architecture")?;
+        self.meta_val_str(&format!("{arch}.rope.freq_base"))?
+            .parse()
+            .ok()
+    }
+
+    /// Shape and tokenizer summary.
+    pub fn info(&self) -> ModelInfo {
+        ModelInfo {
+            n_ctx_train: self.n_ctx_train(),
+            n_embd: self.n_embd(),
+            n_layer: self.n_layer(),
+            n_head: self.n_head(),
+            n_head_kv: self.n_head_kv(),
+            rope_freq_base: self.rope_freq_base(),
+            rope_freq_scale_train: self.rope_freq_scale_train(),
+            vocab_type: self.vocab().vocab_type(),
+        }
+    }
+
     /// Built-in chat template, if any.
     pub fn chat_template(&self) -> Option<String> {
         unsafe {
@@ -104,30 +162,58 @@ impl LlamaModel {
         }
     }
 
+    /// Built-in chat template compiled for the Jinja fallback renderer.
+    ///
+    /// The template is parsed once and cached for the model's lifetime.
+    pub fn jinja_template(&self) -> Option<&JinjaTemplate> {
+        self.jinja
+            .get_or_init(|| {
+                let source = self.chat_template()?;
+                match JinjaTemplate::compile(&source) {
+                    Ok(t) => Some(t),
+                    Err(e) => {
+                        warn!("Chat template failed to compile: {e}");
+                        None
+                    }
+                }
+            })
+            .as_ref()
+    }
+
     /// Read an arbitrary metadata string by key.
     pub fn meta_val_str(&self, key: &str) -> Option<String> {
         let c_key = CString::new(key).ok()?;
-        let mut buf = vec![0u8; 512];
-        let len = unsafe {
-            llama_sys::llama_model_meta_val_str(
-                self.ptr,
-                c_key.as_ptr(),
-                buf.as_mut_ptr() as *mut std::ffi::c_char,
-                buf.len(),
-            )
-        };
-        if len > 0 {
-            buf.truncate(len as usize);
-            Some(String::from_utf8_lossy(&buf).into_owned())
-        } else {
-            None
-        }
+        read_meta_string(|buf, len| unsafe {
+            llama_sys::llama_model_meta_val_str(self.ptr, c_key.as_ptr(), buf, len)
+        })
+        .filter(|v| !v.is_empty())
     }
 
     pub fn meta_count(&self) -> i32 {
         unsafe { llama_sys::llama_model_meta_count(self.ptr) }
     }
 
+    /// Key of the `i`-th metadata entry.
+    pub fn meta_key_by_index(&self, i: i32) -> Option<String> {
+        read_meta_string(|buf, len| unsafe {
+            llama_sys::llama_model_meta_key_by_index(self.ptr, i, buf, len)
+        })
+    }
+
+    /// Value of the `i`-th metadata entry, as formatted by llama.cpp.
+    pub fn meta_val_str_by_index(&self, i: i32) -> Option<String> {
+        read_meta_string(|buf, len| unsafe {
+            llama_sys::llama_model_meta_val_str_by_index(self.ptr, i, buf, len)
+        })
+    }
+
+    /// All metadata as `(key, value)` strings, as parsed by llama.cpp.
+    pub fn metadata(&self) -> Vec<(String, String)> {
+        (0..self.meta_count())
+            .filter_map(|i| Some((self.meta_key_by_index(i)?, self.meta_val_str_by_index(i)?)))
+            .collect()
+    }
+
     pub fn has_encoder(&self) -> bool {
         unsafe { llama_sys::llama_model_has_encoder(self.ptr) }
     }
@@ -136,22 +222,95 @@ impl LlamaModel {
         unsafe { llama_sys::llama_model_has_decoder(self.ptr) }
     }
 
+    /// Outputs of the classifier head (1 for rerankers and reward models).
+    pub fn n_cls_out(&self) -> u32 {
+        unsafe { llama_sys::llama_model_n_cls_out(self.ptr) }
+    }
+
+    /// Label of classifier output `i`, from `classifier.output_labels`.
+    pub fn cls_label(&self, i: u32) -> Option<String> {
+        unsafe {
+            let p = llama_sys::llama_model_cls_label(self.ptr, i);
+            if p.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(p).to_string_lossy().into_owned())
+            }
+        }
+    }
+
+    /// Labels of all classifier outputs; empty if the model has none.
+    pub fn cls_labels(&self) -> Vec<String> {
+        (0..self.n_cls_out())
+            .map_while(|i| self.cls_label(i))
+            .collect()
+    }
+
     //  Vocabulary helpers
 
     pub fn n_vocab(&self) -> i32 {
-        unsafe { llama_sys::llama_vocab_n_tokens(self.vocab()) }
+        self.vocab().n_tokens()
     }
     pub fn token_bos(&self) -> i32 {
-        unsafe { llama_sys::llama_vocab_bos(self.vocab()) }
+        self.vocab().bos()
     }
     pub fn token_eos(&self) -> i32 {
-        unsafe { llama_sys::llama_vocab_eos(self.vocab()) }
+        self.vocab().eos()
     }
     pub fn token_eot(&self) -> i32 {
-        unsafe { llama_sys::llama_vocab_eot(self.vocab()) }
+        self.vocab().eot()
+    }
+
+    /// Whether tokenization should prepend BOS.
+    pub fn add_bos(&self) -> bool {
+        self.vocab().add_bos()
+    }
+
+    /// Fill-in-the-middle tokens, or `None` if the vocab lacks any of
+    /// `<PRE>`, `<SUF>` and `<MID>`.
+    pub fn fim_tokens(&self) -> Option<FimTokens> {
+        let vocab = self.vocab().as_ptr();
+        // LLAMA_TOKEN_NULL is -1.
+        let token = |t: i32| (t >= 0).then_some(t);
+        unsafe {
+            Some(FimTokens {
+                pre: token(llama_sys::llama_vocab_fim_pre(vocab))?,
+                suf: token(llama_sys::llama_vocab_fim_suf(vocab))?,
+                mid: token(llama_sys::llama_vocab_fim_mid(vocab))?,
+                rep: token(llama_sys::llama_vocab_fim_rep(vocab)),
+                sep: token(llama_sys::llama_vocab_fim_sep(vocab)),
+            })
+        }
+    }
+
+    /// Raw text of the BOS token (e.g. `<s>`), if the vocab defines one.
+    pub fn token_bos_text(&self) -> Option<String> {
+        self.token_text(self.token_bos())
+    }
+
+    /// Raw text of the EOS token (e.g. `</s>`), if the vocab defines one.
+    pub fn token_eos_text(&self) -> Option<String> {
+        self.token_text(self.token_eos())
+    }
+
+    fn token_text(&self, token: i32) -> Option<String> {
+        self.vocab().token_get_text(token)
     }
 }
 
+/// A model's shape and tokenizer type, as shown on the dashboard.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct ModelInfo {
+    pub n_ctx_train: i32,
+    pub n_embd: i32,
+    pub n_layer: i32,
+    pub n_head: i32,
+    pub n_head_kv: i32,
+    pub rope_freq_base: Option<f32>,
+    pub rope_freq_scale_train: f32,
+    pub vocab_type: VocabType,
+}
+
 impl Drop for LlamaModel {
     fn drop(&mut self) {
         if !self.ptr.is_null() {
@@ -163,23 +322,451 @@ impl Drop for LlamaModel {
 
 //  ModelParams
 
+/// Receives the fraction (0.0–1.0) of model weights loaded so far.
+pub type ProgressCallback = Arc<dyn Fn(f32) + Send + Sync>;
+
+/// Bridges llama.cpp's C progress callback to a [`ProgressCallback`].
+unsafe extern "C" fn progress_trampoline(progress: f32, user_data: *mut c_void) -> bool {
+    let cb = unsafe { &*(user_data as *const ProgressCallback) };
+    // Unwinding across the FFI boundary is UB; a panicking callback
+    // aborts the load instead.
+    catch_unwind(AssertUnwindSafe(|| cb(progress))).is_ok()
+}
+
+/// How a model is spread across several GPUs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitMode {
+    /// Everything on `main_gpu`.
+    None,
+    /// Whole layers (and their KV cache) per GPU.
+    #[default]
+    Layer,
+    /// Each layer split by rows; `main_gpu` holds intermediate results
+    /// and the KV cache.
+    Row,
+}
+
+impl SplitMode {
+    fn as_raw(self) -> llama_sys::llama_split_mode {
+        match self {
+            Self::None => llama_sys::llama_split_mode_LLAMA_SPLIT_MODE_NONE,
+            Self::Layer => llama_sys::llama_split_mode_LLAMA_SPLIT_MODE_LAYER,
+            Self::Row => llama_sys::llama_split_mode_LLAMA_SPLIT_MODE_ROW,
+        }
+    }
+}
+
+/// A GGUF metadata value that replaces the file's at load time, like
+/// llama.cpp's `--override-kv`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct KvOverride {
+    pub key: String,
+    #[serde(flatten)]
+    pub value: KvOverrideValue,
+}
+
+/// Serialized as `{"type": "bool", "value": false}`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum KvOverrideValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+}
+
+/// Size of the C key and string value buffers, terminator included.
+const KV_OVERRIDE_LEN: usize = 128;
+
+impl KvOverride {
+    fn to_raw(&self) -> llama_sys::llama_model_kv_override {
+        let mut raw = llama_sys::llama_model_kv_override::default();
+        copy_c_str(&mut raw.key, &self.key);
+        match &self.value {
+            KvOverrideValue::Int(v) => {
+                raw.tag = llama_sys::llama_model_kv_override_type_LLAMA_KV_OVERRIDE_TYPE_INT;
+                raw.__bindgen_anon_1.val_i64 = *v;
+            }
+            KvOverrideValue::Float(v) => {
+                raw.tag = llama_sys::llama_model_kv_override_type_LLAMA_KV_OVERRIDE_TYPE_FLOAT;
+                raw.__bindgen_anon_1.val_f64 = *v;
+            }
+            KvOverrideValue::Bool(v) => {
+                raw.tag = llama_sys::llama_model_kv_override_type_LLAMA_KV_OVERRIDE_TYPE_BOOL;
+                raw.__bindgen_anon_1.val_bool = *v;
+            }
+            KvOverrideValue::Str(v) => {
+                raw.tag = llama_sys::llama_model_kv_override_type_LLAMA_KV_OVERRIDE_TYPE_STR;
+                copy_c_str(unsafe { &mut raw.__bindgen_anon_1.val_str }, v);
+            }
+        }
+        raw
+    }
+}
+
+/// Copy `s` into `dst` NUL-terminated, cut at a char boundary if it
+/// doesn't fit.
+fn copy_c_str(dst: &mut [std::ffi::c_char; KV_OVERRIDE_LEN], s: &str) {
+    let mut n = s.len().min(KV_OVERRIDE_LEN - 1);
+    while !s.is_char_boundary(n) {
+        n -= 1;
+    }
+    for (d, &b) in dst.iter_mut().zip(&s.as_bytes()[..n]) {
+        *d = b as std::ffi::c_char;
+    }
+    dst[n] = 0;
+}
+
 /// Parameters for [`LlamaModel::load_from_file`].
-#[derive(Debug, Clone)]
+///
+/// `split_mode`, `main_gpu` and `tensor_split` only place the layers that
+/// `n_gpu_layers` offloads; with no offloaded layers they have no effect.
+#[derive(Clone)]
 pub struct ModelParams {
     /// Layers to offload to GPU. -1 = all.
     pub n_gpu_layers: i32,
+    pub split_mode: SplitMode,
+    /// GPU index for the whole model with [`SplitMode::None`], or for
+    /// intermediate results with [`SplitMode::Row`].
+    pub main_gpu: i32,
+    /// Relative share of the offloaded layers per GPU, e.g. `[3.0, 1.0]`.
+    /// Empty splits in proportion to free memory.
+    pub tensor_split: Vec<f32>,
+    /// GGUF metadata to override, e.g. a wrong
+    /// `tokenizer.ggml.add_bos_token`.
+    pub kv_overrides: Vec<KvOverride>,
     /// Use memory-mapped I/O.
     pub use_mmap: bool,
     /// Lock model memory (prevent swapping).
     pub use_mlock: bool,
+    /// Load only the vocabulary, for tokenizing; such a model can't back
+    /// a context.
+    pub vocab_only: bool,
+    /// Called periodically while weights are loading.
+    pub progress: Option<ProgressCallback>,
+}
+
+impl std::fmt::Debug for ModelParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ModelParams")
+            .field("n_gpu_layers", &self.n_gpu_layers)
+            .field("split_mode", &self.split_mode)
+            .field("main_gpu", &self.main_gpu)
+            .field("tensor_split", &self.tensor_split)
+            .field("kv_overrides", &self.kv_overrides)
+            .field("use_mmap", &self.use_mmap)
+            .field("use_mlock", &self.use_mlock)
+            .field("vocab_only", &self.vocab_only)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
 }
 
 impl Default for ModelParams {
     fn default() -> Self {
         Self {
             n_gpu_layers: -1,
+            split_mode: SplitMode::default(),
+            main_gpu: 0,
+            tensor_split: Vec::new(),
+            kv_overrides: Vec::new(),
             use_mmap: true,
             use_mlock: false,
+            vocab_only: false,
+            progress: None,
         }
     }
 }
+
+impl ModelParams {
+    /// Check the GPU placement against the `n_gpus` GPUs available.
+    pub fn validate(&self, n_gpus: usize) -> Result<()> {
+        let invalid = |msg: String| Err(LlamaError::InvalidArgument(msg));
+        if self.main_gpu < 0 {
+            return invalid(format!(
+                "main_gpu must not be negative, got {}",
+                self.main_gpu
+            ));
+        }
+        if n_gpus > 0 && self.main_gpu as usize >= n_gpus {
+            return invalid(format!(
+                "main_gpu is {}, but only {n_gpus} GPU(s) are available",
+                self.main_gpu
+            ));
+        }
+        for kv in &self.kv_overrides {
+            if kv.key.is_empty() || kv.key.len() >= KV_OVERRIDE_LEN || kv.key.contains('\0') {
+                return invalid(format!(
+                    "kv_overrides key '{}' must be 1 to {} bytes without NUL",
+                    kv.key,
+                    KV_OVERRIDE_LEN - 1
+                ));
+            }
+        }
+        if self.tensor_split.is_empty() {
+            return Ok(());
+        }
+        if self.tensor_split.len() > n_gpus {
+            return invalid(format!(
+                "tensor_split has {} entries, but only {n_gpus} GPU(s) are available",
+                self.tensor_split.len()
+            ));
+        }
+        if self
+            .tensor_split
+            .iter()
+            .any(|&share| !share.is_finite() || share < 0.0)
+        {
+            return invalid("tensor_split entries must be finite and non-negative".into());
+        }
+        if self.tensor_split.iter().all(|&share| share == 0.0) {
+            return invalid("tensor_split must give at least one GPU a share".into());
+        }
+        Ok(())
+    }
+
+    /// Raw llama.cpp parameters, without the progress callback.  The
+    /// result points into `buffers`, which must outlive it.
+    ///
+    /// llama.cpp reads `tensor_split` for every device it knows about, so
+    /// the split is padded with zeros; overrides end with an empty-key
+    /// entry.  String values longer than 127 bytes are cut short.
+    fn to_raw(&self, buffers: &mut RawBuffers) -> llama_sys::llama_model_params {
+        let mut raw = unsafe { llama_sys::llama_model_default_params() };
+        raw.n_gpu_layers = self.n_gpu_layers;
+        raw.split_mode = self.split_mode.as_raw();
+        raw.main_gpu = self.main_gpu;
+        raw.use_mmap = self.use_mmap;
+        raw.use_mlock = self.use_mlock;
+        raw.vocab_only = self.vocab_only;
+        if !self.tensor_split.is_empty() {
+            let max_devices = unsafe { llama_sys::llama_max_devices() };
+            let split = &mut buffers.tensor_split;
+            split.clear();
+            split.extend_from_slice(&self.tensor_split);
+            split.resize(max_devices.max(self.tensor_split.len()), 0.0);
+            raw.tensor_split = split.as_ptr();
+        }
+        if !self.kv_overrides.is_empty() {
+            let overrides = &mut buffers.kv_overrides;
+            overrides.clear();
+            overrides.extend(self.kv_overrides.iter().map(KvOverride::to_raw));
+            overrides.push(llama_sys::llama_model_kv_override::default());
+            raw.kv_overrides = overrides.as_ptr();
+        }
+        raw
+    }
+}
+
+/// Arrays the raw model params point into.
+#[derive(Default)]
+struct RawBuffers {
+    tensor_split: Vec<f32>,
+    kv_overrides: Vec<llama_sys::llama_model_kv_override>,
+}
+
+/// Call a snprintf-style llama.cpp getter, growing the buffer if the
+/// value didn't fit.
+fn read_meta_string(read: impl Fn(*mut std::ffi::c_char, usize) -> i32) -> Option<String> {
+    let mut buf = vec![0u8; 512];
+    loop {
+        let len = read(buf.as_mut_ptr() as *mut std::ffi::c_char, buf.len());
+        if len < 0 {
+            return None;
+        }
+        let len = len as usize;
+        if len < buf.len() {
+            buf.truncate(len);
+            return Some(String::from_utf8_lossy(&buf).into_owned());
+        }
+        buf.resize(len + 1, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gpu_placement_maps_onto_raw_params() {
+        let params = ModelParams {
+            n_gpu_layers: 20,
+            split_mode: SplitMode::Row,
+            main_gpu: 1,
+            tensor_split: vec![3.0, 1.0],
+            ..Default::default()
+        };
+        params.validate(2).unwrap();
+
+        let mut buffers = RawBuffers::default();
+        let raw = params.to_raw(&mut buffers);
+        let split = &buffers.tensor_split;
+        assert_eq!(raw.n_gpu_layers, 20);
+        assert_eq!(
+            raw.split_mode,
+            llama_sys::llama_split_mode_LLAMA_SPLIT_MODE_ROW
+        );
+        assert_eq!(raw.main_gpu, 1);
+        assert_eq!(raw.tensor_split, split.as_ptr());
+        assert_eq!(
+            split.len(),
+            unsafe { llama_sys::llama_max_devices() }.max(2)
+        );
+        assert_eq!(&split[..2], &[3.0, 1.0]);
+        assert!(split[2..].iter().all(|&s| s == 0.0));
+
+        // An empty split leaves llama.cpp's default
+        let raw = ModelParams::default().to_raw(&mut buffers);
+        assert!(raw.tensor_split.is_null());
+        assert!(raw.kv_overrides.is_null());
+        assert!(!raw.vocab_only);
+        assert_eq!(
+            raw.split_mode,
+            llama_sys::llama_split_mode_LLAMA_SPLIT_MODE_LAYER
+        );
+    }
+
+    #[test]
+    fn kv_overrides_become_a_terminated_c_array() {
+        let kv = |key: &str, value| KvOverride {
+            key: key.into(),
+            value,
+        };
+        let long = "é".repeat(100);
+        let params = ModelParams {
+            kv_overrides: vec![
+                kv("llama.context_length", KvOverrideValue::Int(8192)),
+                kv("llama.rope.freq_base", KvOverrideValue::Float(0.5)),
+                kv("tokenizer.ggml.add_bos_token", KvOverrideValue::Bool(false)),
+                kv("general.name", KvOverrideValue::Str(long.clone())),
+            ],
+            ..Default::default()
+        };
+        params.validate(0).unwrap();
+
+        let mut buffers = RawBuffers::default();
+        let raw = params.to_raw(&mut buffers);
+        let raw = unsafe { std::slice::from_raw_parts(raw.kv_overrides, 5) };
+        let text = |s: &[std::ffi::c_char]| {
+            unsafe { CStr::from_ptr(s.as_ptr()) }
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+        unsafe {
+            assert_eq!(text(&raw[0].key), "llama.context_length");
+            assert_eq!(
+                raw[0].tag,
+                llama_sys::llama_model_kv_override_type_LLAMA_KV_OVERRIDE_TYPE_INT
+            );
+            assert_eq!(raw[0].__bindgen_anon_1.val_i64, 8192);
+            assert_eq!(raw[1].__bindgen_anon_1.val_f64, 0.5);
+            assert!(!raw[2].__bindgen_anon_1.val_bool);
+            assert_eq!(
+                raw[3].tag,
+                llama_sys::llama_model_kv_override_type_LLAMA_KV_OVERRIDE_TYPE_STR
+            );
+            // 63 two-byte chars fit before the terminator
+            let value = text(&raw[3].__bindgen_anon_1.val_str);
+            assert_eq!(value.len(), 126);
+            assert!(long.starts_with(&value));
+        }
+        assert_eq!(raw[4].key[0], 0);
+
+        let too_long = ModelParams {
+            kv_overrides: vec![kv(&"k".repeat(128), KvOverrideValue::Int(1))],
+            ..Default::default()
+        };
+        assert!(matches!(
+            too_long.validate(0),
+            Err(LlamaError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn kv_overrides_serialize_with_a_type_tag() {
+        let kv: KvOverride = serde_json::from_value(serde_json::json!({
+            "key": "tokenizer.ggml.add_bos_token", "type": "bool", "value": false,
+        }))
+        .unwrap();
+        assert_eq!(kv.value, KvOverrideValue::Bool(false));
+        assert_eq!(
+            serde_json::to_value(&kv).unwrap()["type"],
+            serde_json::json!("bool")
+        );
+    }
+
+    #[test]
+    fn gpu_placement_is_checked_against_the_devices() {
+        let params = |main_gpu, tensor_split: &[f32]| ModelParams {
+            main_gpu,
+            tensor_split: tensor_split.to_vec(),
+            ..Default::default()
+        };
+        assert!(params(0, &[]).validate(0).is_ok());
+        assert!(params(0, &[1.0, 0.0]).validate(2).is_ok());
+
+        for (bad, n_gpus) in [
+            (params(-1, &[]), 1),
+            (params(2, &[]), 2),
+            (params(0, &[1.0, 1.0, 1.0]), 2),
+            (params(0, &[1.0]), 0),
+            (params(0, &[1.0, -1.0]), 2),
+            (params(0, &[f32::NAN]), 1),
+            (params(0, &[0.0, 0.0]), 2),
+        ] {
+            assert!(
+                matches!(bad.validate(n_gpus), Err(LlamaError::InvalidArgument(_))),
+                "{bad:?} with {n_gpus} GPU(s)"
+            );
+        }
+    }
+
+    /// An snprintf-style getter over `value`, counting its calls.
+    fn snprintf_getter(
+        value: &[u8],
+        calls: &std::cell::Cell<usize>,
+    ) -> impl Fn(*mut std::ffi::c_char, usize) -> i32 {
+        move |buf, len| {
+            calls.set(calls.get() + 1);
+            let n = value.len().min(len.saturating_sub(1));
+            unsafe {
+                std::ptr::copy_nonoverlapping(value.as_ptr(), buf as *mut u8, n);
+                *buf.add(n) = 0;
+            }
+            value.len() as i32
+        }
+    }
+
+    #[test]
+    fn meta_strings_longer_than_the_buffer_are_read_whole() {
+        let calls = std::cell::Cell::new(0);
+        let short = "qwen2";
+        assert_eq!(
+            read_meta_string(snprintf_getter(short.as_bytes(), &calls)).as_deref(),
+            Some(short)
+        );
+        assert_eq!(calls.get(), 1);
+
+        let calls = std::cell::Cell::new(0);
+        let template = "{% for m in messages %}".repeat(200);
+        assert_eq!(
+            read_meta_string(snprintf_getter(template.as_bytes(), &calls)),
+            Some(template)
+        );
+        assert_eq!(calls.get(), 2);
+
+        assert_eq!(read_meta_string(|_, _| -1), None);
+    }
+
+    #[test]
+    fn non_utf8_meta_bytes_are_replaced() {
+        let calls = std::cell::Cell::new(0);
+        let value = b"caf\xe9 \xff\xfe";
+        assert_eq!(
+            read_meta_string(snprintf_getter(value, &calls)).as_deref(),
+            Some("caf\u{fffd} \u{fffd}\u{fffd}")
+        );
+    }
+}
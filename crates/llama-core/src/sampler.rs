@@ -1,5 +1,7 @@
 //! Sampler chain construction and token sampling.
 
+use std::ffi::CStr;
+
 use crate::context::LlamaContext;
 
 /// RAII wrapper around a `llama_sampler` chain.
@@ -76,6 +78,50 @@ impl SamplerChain {
     pub fn sample(&mut self, ctx: &LlamaContext, idx: i32) -> i32 {
         unsafe { llama_sys::llama_sampler_sample(self.ptr, ctx.as_ptr(), idx) }
     }
+
+    /// Forget what earlier sampling left behind (penalty history,
+    /// mirostat state, the RNG) so the chain can serve a new sequence.
+    pub fn reset(&mut self) {
+        unsafe { llama_sys::llama_sampler_reset(self.ptr) }
+    }
+
+    /// Copy the chain with its current state.  `None` if one of its
+    /// samplers can't be cloned.
+    pub fn try_clone(&self) -> Option<Self> {
+        let ptr = unsafe { llama_sys::llama_sampler_clone(self.ptr) };
+        (!ptr.is_null()).then_some(Self { ptr })
+    }
+
+    //  Inspection
+
+    /// Number of samplers in the chain.
+    pub fn len(&self) -> usize {
+        unsafe { llama_sys::llama_sampler_chain_n(self.ptr) }.max(0) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Name of the `i`th sampler, e.g. `top-k`.
+    pub fn name(&self, i: usize) -> Option<String> {
+        if i >= self.len() {
+            return None;
+        }
+        unsafe {
+            let smpl = llama_sys::llama_sampler_chain_get(self.ptr, i as i32);
+            if smpl.is_null() {
+                return None;
+            }
+            let name = llama_sys::llama_sampler_name(smpl);
+            (!name.is_null()).then(|| CStr::from_ptr(name).to_string_lossy().into_owned())
+        }
+    }
+
+    /// Names of the samplers in order.
+    pub fn names(&self) -> Vec<String> {
+        (0..self.len()).filter_map(|i| self.name(i)).collect()
+    }
 }
 
 impl Drop for SamplerChain {
@@ -148,7 +194,14 @@ impl Default for SamplingParams {
 
 impl SamplingParams {
     /// Build and return a ready-to-use [`SamplerChain`].
+    #[deprecated(note = "use `build_chain`, which borrows the params")]
     pub fn into_chain(self) -> SamplerChain {
+        self.build_chain()
+    }
+
+    /// Build a ready-to-use [`SamplerChain`].  Each call returns a new
+    /// chain with fresh state.
+    pub fn build_chain(&self) -> SamplerChain {
         let mut chain = SamplerChain::new(false);
 
         if self.repeat_penalty != 1.0
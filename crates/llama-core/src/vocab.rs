@@ -0,0 +1,318 @@
+//! Borrowed handle to a model's vocabulary.
+
+use std::ffi::{CStr, CString};
+use std::marker::PhantomData;
+
+use crate::error::{LlamaError, Result};
+use crate::model::LlamaModel;
+
+/// Per-token attribute flags (`llama_token_attr`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenAttr(u32);
+
+impl TokenAttr {
+    pub const UNKNOWN: Self = Self(llama_sys::llama_token_attr_LLAMA_TOKEN_ATTR_UNKNOWN);
+    pub const UNUSED: Self = Self(llama_sys::llama_token_attr_LLAMA_TOKEN_ATTR_UNUSED);
+    pub const NORMAL: Self = Self(llama_sys::llama_token_attr_LLAMA_TOKEN_ATTR_NORMAL);
+    pub const CONTROL: Self = Self(llama_sys::llama_token_attr_LLAMA_TOKEN_ATTR_CONTROL);
+    pub const USER_DEFINED: Self = Self(llama_sys::llama_token_attr_LLAMA_TOKEN_ATTR_USER_DEFINED);
+    pub const BYTE: Self = Self(llama_sys::llama_token_attr_LLAMA_TOKEN_ATTR_BYTE);
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Whether every flag in `other` is set.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// Tokenizer algorithm of a vocabulary (`llama_vocab_type`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VocabType {
+    /// No vocabulary.
+    None,
+    /// SentencePiece byte-level BPE (LLaMA, Mistral).
+    Spm,
+    /// GPT-2 style byte-level BPE.
+    Bpe,
+    /// BERT WordPiece.
+    Wpm,
+    /// T5 Unigram.
+    Ugm,
+    /// RWKV greedy tokenization.
+    Rwkv,
+    /// A type newer than this crate.
+    Other,
+}
+
+impl VocabType {
+    fn from_raw(raw: llama_sys::llama_vocab_type) -> Self {
+        match raw {
+            0 => Self::None,
+            1 => Self::Spm,
+            2 => Self::Bpe,
+            3 => Self::Wpm,
+            4 => Self::Ugm,
+            5 => Self::Rwkv,
+            _ => Self::Other,
+        }
+    }
+
+    /// The type llama.cpp picks for a GGUF `tokenizer.ggml.model` value.
+    pub fn from_gguf_model(name: &str) -> Self {
+        match name {
+            "no_vocab" => Self::None,
+            "llama" => Self::Spm,
+            "gpt2" => Self::Bpe,
+            "bert" => Self::Wpm,
+            "t5" => Self::Ugm,
+            "rwkv" => Self::Rwkv,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// A model's vocabulary, valid while the model is borrowed.
+#[derive(Clone, Copy)]
+pub struct LlamaVocab<'m> {
+    ptr: *const llama_sys::llama_vocab,
+    _model: PhantomData<&'m LlamaModel>,
+}
+
+impl<'m> LlamaVocab<'m> {
+    /// Safety: `ptr` must be the vocabulary of a model alive for `'m`.
+    pub(crate) unsafe fn from_raw(ptr: *const llama_sys::llama_vocab) -> Self {
+        Self {
+            ptr,
+            _model: PhantomData,
+        }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const llama_sys::llama_vocab {
+        self.ptr
+    }
+
+    pub fn n_tokens(&self) -> i32 {
+        unsafe { llama_sys::llama_vocab_n_tokens(self.ptr) }
+    }
+
+    pub fn vocab_type(&self) -> VocabType {
+        VocabType::from_raw(unsafe { llama_sys::llama_vocab_type(self.ptr) })
+    }
+
+    pub fn bos(&self) -> i32 {
+        unsafe { llama_sys::llama_vocab_bos(self.ptr) }
+    }
+
+    pub fn eos(&self) -> i32 {
+        unsafe { llama_sys::llama_vocab_eos(self.ptr) }
+    }
+
+    pub fn eot(&self) -> i32 {
+        unsafe { llama_sys::llama_vocab_eot(self.ptr) }
+    }
+
+    /// Whether tokenization should prepend BOS.
+    pub fn add_bos(&self) -> bool {
+        unsafe { llama_sys::llama_vocab_get_add_bos(self.ptr) }
+    }
+
+    /// Whether `token` ends generation (EOS, EOT, EOM and the like).
+    pub fn is_eog(&self, token: i32) -> bool {
+        unsafe { llama_sys::llama_vocab_is_eog(self.ptr, token) }
+    }
+
+    /// Whether `token` is a control token such as `<|im_start|>`.
+    pub fn is_control(&self, token: i32) -> bool {
+        unsafe { llama_sys::llama_vocab_is_control(self.ptr, token) }
+    }
+
+    /// Raw text of `token` as stored in the vocab, or `None` for ids
+    /// outside it (including `LLAMA_TOKEN_NULL`).
+    pub fn token_get_text(&self, token: i32) -> Option<String> {
+        if token < 0 || token >= self.n_tokens() {
+            return None;
+        }
+        unsafe {
+            let p = llama_sys::llama_vocab_get_text(self.ptr, token);
+            if p.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(p).to_string_lossy().into_owned())
+            }
+        }
+    }
+
+    pub fn token_get_attr(&self, token: i32) -> TokenAttr {
+        TokenAttr(unsafe { llama_sys::llama_vocab_get_attr(self.ptr, token) })
+    }
+
+    /// Tokenize `text`.
+    pub fn tokenize(&self, text: &str, add_special: bool, parse_special: bool) -> Result<Vec<i32>> {
+        let c_text = CString::new(text)
+            .map_err(|_| LlamaError::TokenizationFailed("text contains null byte".into()))?;
+
+        // First call: query required buffer size (returns negative count).
+        let n = unsafe {
+            llama_sys::llama_tokenize(
+                self.ptr,
+                c_text.as_ptr(),
+                text.len() as i32,
+                std::ptr::null_mut(),
+                0,
+                add_special,
+                parse_special,
+            )
+        };
+
+        let capacity = (-n) as usize;
+        let mut tokens = vec![0i32; capacity];
+
+        let actual = unsafe {
+            llama_sys::llama_tokenize(
+                self.ptr,
+                c_text.as_ptr(),
+                text.len() as i32,
+                tokens.as_mut_ptr(),
+                tokens.len() as i32,
+                add_special,
+                parse_special,
+            )
+        };
+
+        if actual < 0 {
+            return Err(LlamaError::TokenizationFailed(format!(
+                "llama_tokenize returned {actual}"
+            )));
+        }
+
+        tokens.truncate(actual as usize);
+        Ok(tokens)
+    }
+
+    /// Tokenize a rendered chat prompt: special tokens are parsed and BOS
+    /// is added as the vocab asks, once even when the template already
+    /// starts with `bos_token`.
+    pub fn tokenize_prompt(&self, prompt: &str) -> Result<Vec<i32>> {
+        let mut tokens = self.tokenize(prompt, true, true)?;
+        dedup_leading_bos(&mut tokens, self.bos());
+        Ok(tokens)
+    }
+
+    /// Convert a single token id to its text piece.  Control tokens
+    /// render as nothing; see [`token_to_piece_with`](Self::token_to_piece_with).
+    pub fn token_to_piece(&self, token: i32) -> String {
+        self.token_to_piece_with(token, false)
+    }
+
+    /// Like [`token_to_piece`](Self::token_to_piece), but with `special`
+    /// set control tokens render as their text.
+    pub fn token_to_piece_with(&self, token: i32, special: bool) -> String {
+        let mut buf = vec![0u8; 128];
+        let len = unsafe {
+            llama_sys::llama_token_to_piece(
+                self.ptr,
+                token,
+                buf.as_mut_ptr() as *mut std::ffi::c_char,
+                buf.len() as i32,
+                0, // lstrip
+                special,
+            )
+        };
+
+        if len < 0 {
+            // Buffer too small — retry.
+            buf.resize((-len) as usize, 0);
+            let len = unsafe {
+                llama_sys::llama_token_to_piece(
+                    self.ptr,
+                    token,
+                    buf.as_mut_ptr() as *mut std::ffi::c_char,
+                    buf.len() as i32,
+                    0,
+                    special,
+                )
+            };
+            if len > 0 {
+                buf.truncate(len as usize);
+            } else {
+                return String::new();
+            }
+        } else {
+            buf.truncate(len as usize);
+        }
+
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+
+    /// Detokenize a token sequence back to text.
+    pub fn detokenize(&self, tokens: &[i32]) -> Result<String> {
+        let mut buf = vec![0u8; tokens.len() * 16];
+        let len = unsafe {
+            llama_sys::llama_detokenize(
+                self.ptr,
+                tokens.as_ptr(),
+                tokens.len() as i32,
+                buf.as_mut_ptr() as *mut std::ffi::c_char,
+                buf.len() as i32,
+                false,
+                false,
+            )
+        };
+
+        if len < 0 {
+            buf.resize((-len) as usize, 0);
+            let len2 = unsafe {
+                llama_sys::llama_detokenize(
+                    self.ptr,
+                    tokens.as_ptr(),
+                    tokens.len() as i32,
+                    buf.as_mut_ptr() as *mut std::ffi::c_char,
+                    buf.len() as i32,
+                    false,
+                    false,
+                )
+            };
+            if len2 > 0 {
+                buf.truncate(len2 as usize);
+            } else {
+                return Err(LlamaError::TokenizationFailed(
+                    "detokenize failed on retry".into(),
+                ));
+            }
+        } else {
+            buf.truncate(len as usize);
+        }
+
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+}
+
+/// Drop the BOS tokenization prepended to a prompt that began with one.
+fn dedup_leading_bos(tokens: &mut Vec<i32>, bos: i32) {
+    if bos >= 0 && tokens.starts_with(&[bos, bos]) {
+        tokens.remove(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leading_bos_is_kept_once() {
+        let mut tokens = vec![1, 1, 7, 1];
+        dedup_leading_bos(&mut tokens, 1);
+        assert_eq!(tokens, [1, 7, 1]);
+        dedup_leading_bos(&mut tokens, 1);
+        assert_eq!(tokens, [1, 7, 1]);
+
+        // No BOS in the vocab
+        let mut tokens = vec![-1, -1, 7];
+        dedup_leading_bos(&mut tokens, -1);
+        assert_eq!(tokens, [-1, -1, 7]);
+    }
+}
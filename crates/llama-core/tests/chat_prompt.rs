@@ -0,0 +1,36 @@
+//! Rendering and tokenizing chat prompts with a real model.
+//!
+//! Run with `LLAMA_TEST_MODEL=/path/to/model.gguf cargo test -p llama-core
+//! --features model-tests`; the model needs a BOS token.
+
+#![cfg(feature = "model-tests")]
+
+use llama_core::{ChatMessage, JinjaTemplate, LlamaBackend, LlamaModel, ModelParams};
+
+#[test]
+fn templates_that_emit_bos_get_it_once() {
+    let _backend = LlamaBackend::init();
+    let path = std::env::var("LLAMA_TEST_MODEL").expect("LLAMA_TEST_MODEL is not set");
+    let params = ModelParams {
+        n_gpu_layers: 0,
+        ..Default::default()
+    };
+    let model = LlamaModel::load_from_file(path.as_ref(), &params).unwrap();
+    let bos_text = model.token_bos_text().expect("the model has no BOS token");
+
+    let template = JinjaTemplate::compile(
+        "{{ bos_token }}{% for m in messages %}{{ m.role }}: {{ m.content }}\n{% endfor %}",
+    )
+    .unwrap();
+    let messages = [ChatMessage {
+        role: "user".into(),
+        content: "Hello".into(),
+    }];
+    let prompt = template.render(&messages, true, &bos_text, "").unwrap();
+    assert!(prompt.starts_with(&bos_text));
+
+    let vocab = model.vocab();
+    let tokens = vocab.tokenize_prompt(&prompt).unwrap();
+    assert_eq!(tokens[0], vocab.bos());
+    assert_eq!(tokens.iter().filter(|&&t| t == vocab.bos()).count(), 1);
+}
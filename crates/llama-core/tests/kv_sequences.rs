@@ -0,0 +1,145 @@
+//! KV cache sequence operations against a real model.
+//!
+//! Run with `LLAMA_TEST_MODEL=/path/to/model.gguf cargo test -p llama-core
+//! --features model-tests`; any small model will do.
+
+#![cfg(feature = "model-tests")]
+
+use std::sync::Arc;
+
+use llama_core::generate::{generate_blocking_from, reuse_kv_prefix};
+use llama_core::{
+    ContextParams, LlamaBackend, LlamaBatch, LlamaContext, LlamaModel, ModelParams, SamplerChain,
+};
+
+const STEPS: usize = 8;
+
+fn context() -> LlamaContext {
+    let path = std::env::var("LLAMA_TEST_MODEL").expect("LLAMA_TEST_MODEL is not set");
+    let params = ModelParams {
+        n_gpu_layers: 0,
+        ..Default::default()
+    };
+    let model = Arc::new(LlamaModel::load_from_file(path.as_ref(), &params).unwrap());
+    let params = ContextParams {
+        n_ctx: 512,
+        n_seq_max: 2,
+        ..Default::default()
+    };
+    LlamaContext::new(model, &params).unwrap()
+}
+
+/// Greedily continue `seq_id` from `token` at `pos`, returning the tokens.
+fn continue_greedy(ctx: &mut LlamaContext, seq_id: i32, mut token: i32, mut pos: i32) -> Vec<i32> {
+    let mut sampler = SamplerChain::new(true);
+    sampler.add_greedy();
+    let mut batch = LlamaBatch::new(1, 0, 1);
+    let mut out = Vec::with_capacity(STEPS);
+    for _ in 0..STEPS {
+        batch.clear();
+        batch.add(token, pos, &[seq_id], true).unwrap();
+        ctx.decode(&mut batch).unwrap();
+        token = sampler.sample(ctx, 0);
+        out.push(token);
+        pos += 1;
+    }
+    out
+}
+
+#[test]
+fn copied_prefix_continues_like_the_original() {
+    let _backend = LlamaBackend::init();
+    let mut ctx = context();
+    let prompt = ctx
+        .model()
+        .vocab()
+        .tokenize("The capital of France is", true, false)
+        .unwrap();
+    let n = prompt.len() as i32;
+
+    let mut batch = LlamaBatch::new(n, 0, 1);
+    batch.add_sequence(&prompt, 0, 0, true).unwrap();
+    ctx.decode(&mut batch).unwrap();
+    let mut sampler = SamplerChain::new(true);
+    sampler.add_greedy();
+    let first = sampler.sample(&ctx, n - 1);
+
+    assert_eq!(ctx.kv_cache_seq_pos_min(0), Some(0));
+    assert_eq!(ctx.kv_cache_seq_pos_max(0), Some(n - 1));
+    assert_eq!(ctx.kv_cache_seq_pos_max(1), None);
+
+    ctx.kv_cache_seq_cp(0, 1, -1, -1);
+    assert_eq!(ctx.kv_cache_seq_pos_max(1), Some(n - 1));
+
+    let original = continue_greedy(&mut ctx, 0, first, n);
+    assert_eq!(ctx.kv_cache_seq_pos_max(0), Some(n - 1 + STEPS as i32));
+
+    // Seq 1 only has the prefix, so it continues exactly like seq 0 did
+    ctx.kv_cache_seq_keep(1);
+    assert_eq!(ctx.kv_cache_seq_pos_max(0), None);
+    assert_eq!(ctx.kv_cache_seq_pos_max(1), Some(n - 1));
+    let copy = continue_greedy(&mut ctx, 1, first, n);
+    assert_eq!(copy, original);
+
+    // Dropping the prompt's first token and shifting the rest down
+    if ctx.kv_cache_can_shift() {
+        assert!(ctx.kv_cache_seq_rm(1, 0, 1));
+        ctx.kv_cache_seq_add(1, 1, -1, -1);
+        assert_eq!(ctx.kv_cache_seq_pos_min(1), Some(0));
+        assert_eq!(ctx.kv_cache_seq_pos_max(1), Some(n - 2 + STEPS as i32));
+    }
+}
+
+/// Greedy generation of `tokens` after `n_past` of them are in the cache.
+fn generate_text(ctx: &mut LlamaContext, tokens: &[i32], n_past: usize) -> String {
+    let request = llama_core::GenerateRequest {
+        tokens: tokens.to_vec(),
+        max_tokens: STEPS as u32,
+        stop_words: Vec::new(),
+        sampling_params: llama_core::SamplingParams {
+            temperature: 0.0,
+            ..Default::default()
+        },
+        return_special: false,
+        ignore_eos: false,
+    };
+    let (tx, mut rx) = tokio::sync::mpsc::channel(STEPS + 1);
+    generate_blocking_from(ctx, &request, n_past, tx, &llama_core::CancelToken::new());
+    let mut text = String::new();
+    while let Ok(event) = rx.try_recv() {
+        if let llama_core::GenerateEvent::Token(piece) = event {
+            text.push_str(&piece);
+        }
+    }
+    text
+}
+
+#[test]
+fn reused_prefix_generates_like_a_fresh_prompt() {
+    let _backend = LlamaBackend::init();
+    let mut ctx = context();
+    let vocab = ctx.model().vocab();
+    let first = vocab
+        .tokenize("The capital of France is", true, false)
+        .unwrap();
+    let second = vocab
+        .tokenize(
+            "The capital of France is Paris. The capital of Italy is",
+            true,
+            false,
+        )
+        .unwrap();
+
+    ctx.kv_cache_clear();
+    let fresh = generate_text(&mut ctx, &second, 0);
+
+    // The first turn leaves its prompt and output in the cache
+    ctx.kv_cache_clear();
+    generate_text(&mut ctx, &first, 0);
+    let n_past = reuse_kv_prefix(&mut ctx, &first, &second);
+    assert_eq!(
+        ctx.kv_cache_seq_pos_max(0),
+        Some(n_past as i32 - 1).filter(|_| n_past > 0)
+    );
+    assert_eq!(generate_text(&mut ctx, &second, n_past), fresh);
+}
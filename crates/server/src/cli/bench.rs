@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::cli::BenchArgs;
+
+/// One offload configuration's results.
+#[derive(Debug, Serialize)]
+struct BenchRow {
+    model: String,
+    n_gpu_layers: i32,
+    ctx_size: u32,
+    report: llama_core::BenchReport,
+}
+
+pub async fn execute(args: BenchArgs) -> anyhow::Result<()> {
+    let _backend = llama_core::LlamaBackend::init();
+
+    let params = llama_core::BenchParams {
+        n_prompt: args.pp,
+        n_gen: args.tg,
+        repetitions: args.repeat,
+    };
+    let model_name = args
+        .model
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut rows = Vec::new();
+    for &n_gpu_layers in &args.n_gpu_layers {
+        info!(model = %args.model.display(), n_gpu_layers, "Benchmarking");
+        let report = tokio::task::spawn_blocking({
+            let args = args.clone();
+            move || bench_once(&args, n_gpu_layers, &params)
+        })
+        .await??;
+        rows.push(BenchRow {
+            model: model_name.clone(),
+            n_gpu_layers,
+            ctx_size: args.ctx_size,
+            report,
+        });
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+    } else {
+        print_table(&rows);
+    }
+    Ok(())
+}
+
+/// Load the model with `n_gpu_layers`, benchmark it and unload it again,
+/// so every configuration starts from the same state.
+fn bench_once(
+    args: &BenchArgs,
+    n_gpu_layers: i32,
+    params: &llama_core::BenchParams,
+) -> anyhow::Result<llama_core::BenchReport> {
+    let model_params = llama_core::ModelParams {
+        n_gpu_layers,
+        ..Default::default()
+    };
+    let model = Arc::new(llama_core::LlamaModel::load_from_file(
+        &args.model,
+        &model_params,
+    )?);
+
+    let mut ctx_params = llama_core::ContextParams {
+        n_ctx: args.ctx_size,
+        ..Default::default()
+    };
+    if let Some(threads) = args.threads {
+        ctx_params.n_threads = threads;
+        ctx_params.n_threads_batch = threads;
+    }
+    let mut ctx = llama_core::LlamaContext::new(model, &ctx_params)?;
+
+    let report = llama_core::run_bench(&mut ctx, params)?;
+    // The context holds the last reference to the model.
+    drop(ctx);
+    Ok(report)
+}
+
+fn print_table(rows: &[BenchRow]) {
+    println!("{:<40} {:>5} {:>8} {:>22}", "Model", "NGL", "Test", "t/s");
+    println!("{}", "-".repeat(78));
+    for row in rows {
+        let r = &row.report;
+        let tests = [
+            (format!("pp{}", r.n_prompt), r.pp),
+            (format!("tg{}", r.n_gen), r.tg),
+        ];
+        for (test, stats) in tests {
+            println!(
+                "{:<40} {:>5} {:>8} {:>22}",
+                row.model,
+                row.n_gpu_layers,
+                test,
+                format!("{:.2} ± {:.2}", stats.mean, stats.stddev)
+            );
+        }
+        println!(
+            "{:<40} {:>5} {:>8} {:>22}",
+            row.model,
+            row.n_gpu_layers,
+            "ttft",
+            format!("{:.1} ± {:.1} ms", r.ttft_ms.mean, r.ttft_ms.stddev)
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use std::io::{self, IsTerminal, Read, Write};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::cli::CompleteArgs;
+use crate::cli::generation::{Completion, Settings, load_model, render_prompt, stream_completion};
+
+/// Exit codes for scripts driving `complete`.
+mod exit {
+    pub const OTHER: i32 = 1;
+    pub const GENERATION: i32 = 2;
+    pub const PROMPT_TOO_LONG: i32 = 3;
+}
+
+#[derive(Debug, thiserror::Error)]
+enum CompleteError {
+    #[error("generation failed: {0}")]
+    Generation(String),
+
+    #[error("the prompt is {tokens} tokens, but the context holds {n_ctx}")]
+    PromptTooLong { tokens: usize, n_ctx: u32 },
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl CompleteError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Self::Generation(_) => exit::GENERATION,
+            Self::PromptTooLong { .. } => exit::PROMPT_TOO_LONG,
+            Self::Other(_) => exit::OTHER,
+        }
+    }
+}
+
+pub async fn execute(args: CompleteArgs) -> anyhow::Result<()> {
+    if let Err(e) = complete(args).await {
+        eprintln!("Error: {e:#}");
+        std::process::exit(e.exit_code());
+    }
+    Ok(())
+}
+
+async fn complete(args: CompleteArgs) -> Result<(), CompleteError> {
+    let input = read_prompt(&args)?;
+    let (model, ctx) = load_model(&args.model, &args.load)?;
+
+    let prompt = build_prompt(&model, &args, input);
+    let tokens = model
+        .vocab()
+        .tokenize_prompt(&prompt)
+        .map_err(anyhow::Error::from)?;
+    let n_ctx = ctx.n_ctx();
+    if tokens.len() >= n_ctx as usize {
+        return Err(CompleteError::PromptTooLong {
+            tokens: tokens.len(),
+            n_ctx,
+        });
+    }
+    let prompt_tokens = tokens.len();
+    let request = Settings::from_args(&args.sampling).request(tokens);
+
+    let ctx = Arc::new(Mutex::new(ctx));
+    let mut stdout = io::stdout();
+    let completion = stream_completion(&ctx, request, Vec::new(), |piece| {
+        if args.json {
+            return Ok(());
+        }
+        stdout.write_all(piece.as_bytes())?;
+        stdout.flush()
+    })
+    .await?;
+
+    if args.json {
+        let report = Report::new(&completion, prompt_tokens);
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).map_err(anyhow::Error::from)?
+        );
+    } else if !completion.text.ends_with('\n') {
+        println!();
+    }
+    completion
+        .outcome
+        .map(drop)
+        .map_err(CompleteError::Generation)
+}
+
+/// The prompt argument, or else `--file`, or else stdin when it isn't a
+/// terminal.
+fn read_prompt(args: &CompleteArgs) -> anyhow::Result<String> {
+    let read_stdin = || -> anyhow::Result<String> {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        Ok(input)
+    };
+    match (&args.prompt, &args.file) {
+        (Some(prompt), _) => Ok(prompt.clone()),
+        (None, Some(path)) if path.as_os_str() == "-" => read_stdin(),
+        (None, Some(path)) => std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("cannot read {}: {e}", path.display())),
+        (None, None) if io::stdin().is_terminal() => {
+            anyhow::bail!("give a prompt, --file or pipe one on stdin")
+        }
+        (None, None) => read_stdin(),
+    }
+}
+
+/// The prompt as the model sees it.  Unless `--raw`, `input` goes through
+/// the chat template as a user message; a model without a template gets
+/// it as is, or as a plain transcript with `--chat` or `--system`.
+fn build_prompt(model: &llama_core::LlamaModel, args: &CompleteArgs, input: String) -> String {
+    if args.raw {
+        return input;
+    }
+    let mut messages = Vec::new();
+    if let Some(system) = &args.system {
+        messages.push(llama_core::ChatMessage {
+            role: "system".into(),
+            content: system.clone(),
+        });
+    }
+    messages.push(llama_core::ChatMessage {
+        role: "user".into(),
+        content: input.trim_end().into(),
+    });
+    match llama_core::apply_model_template(model, &messages, true) {
+        Some(prompt) => prompt,
+        None if args.chat || args.system.is_some() => render_prompt(model, &messages, true),
+        None => input,
+    }
+}
+
+/// `complete --json` output.
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    text: &'a str,
+    /// `stop`, `length` or `stop_word:<word>`; `null` after an error.
+    finish_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+    usage: Usage,
+    timings: Timings,
+}
+
+#[derive(Debug, Serialize)]
+struct Usage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    total_tokens: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct Timings {
+    prompt_ms: f64,
+    prompt_tokens_per_second: f64,
+    generation_ms: f64,
+    generation_tokens_per_second: f64,
+}
+
+impl<'a> Report<'a> {
+    fn new(completion: &'a Completion, prompt_tokens: usize) -> Self {
+        let perf = &completion.perf;
+        let (finish_reason, error, completion_tokens) = match &completion.outcome {
+            Ok(finish) => (
+                Some(finish.reason.to_string()),
+                None,
+                finish.completion_tokens,
+            ),
+            Err(e) => (None, Some(e.as_str()), perf.n_eval.max(0) as u32),
+        };
+        let prompt_tokens = prompt_tokens as u32;
+        Self {
+            text: &completion.text,
+            finish_reason,
+            error,
+            usage: Usage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+            },
+            timings: Timings {
+                prompt_ms: perf.t_p_eval_ms,
+                prompt_tokens_per_second: perf.prompt_tokens_per_sec(),
+                generation_ms: perf.t_eval_ms,
+                generation_tokens_per_second: perf.generation_tokens_per_sec(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::generation::Finish;
+
+    fn completion(outcome: Result<Finish, String>) -> Completion {
+        Completion {
+            text: "Paris.".into(),
+            outcome,
+            n_past: 0,
+            perf: llama_core::PerfData {
+                t_start_ms: 0.0,
+                t_load_ms: 0.0,
+                t_p_eval_ms: 250.0,
+                t_eval_ms: 500.0,
+                n_p_eval: 25,
+                n_eval: 3,
+            },
+        }
+    }
+
+    #[test]
+    fn report_has_text_usage_and_timings() {
+        let done = completion(Ok(Finish {
+            reason: llama_core::FinishReason::Stop,
+            prompt_tokens: 25,
+            completion_tokens: 4,
+        }));
+        let json = serde_json::to_value(Report::new(&done, 25)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "text": "Paris.",
+                "finish_reason": "stop",
+                "usage": {"prompt_tokens": 25, "completion_tokens": 4, "total_tokens": 29},
+                "timings": {
+                    "prompt_ms": 250.0,
+                    "prompt_tokens_per_second": 100.0,
+                    "generation_ms": 500.0,
+                    "generation_tokens_per_second": 6.0,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn failures_are_reported_and_exit_non_zero() {
+        let failed = completion(Err("decode: out of memory".into()));
+        let json = serde_json::to_value(Report::new(&failed, 25)).unwrap();
+        assert_eq!(json["finish_reason"], serde_json::Value::Null);
+        assert_eq!(json["error"], "decode: out of memory");
+        assert_eq!(json["usage"]["completion_tokens"], 3);
+
+        assert_eq!(
+            CompleteError::Generation("x".into()).exit_code(),
+            exit::GENERATION
+        );
+        let too_long = CompleteError::PromptTooLong {
+            tokens: 5000,
+            n_ctx: 4096,
+        };
+        assert_eq!(too_long.exit_code(), exit::PROMPT_TOO_LONG);
+        assert_eq!(
+            too_long.to_string(),
+            "the prompt is 5000 tokens, but the context holds 4096"
+        );
+    }
+}
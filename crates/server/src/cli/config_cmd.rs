@@ -1,18 +1,25 @@
-use crate::cli::ConfigArgs;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::cli::{ConfigArgs, GlobalArgs};
 use crate::config::AppConfig;
 
-pub async fn execute(args: ConfigArgs) -> anyhow::Result<()> {
+pub async fn execute(global: GlobalArgs, args: ConfigArgs) -> anyhow::Result<()> {
     match args.action {
-        crate::cli::ConfigAction::Show => {
-            let cfg = AppConfig::load_or_default()?;
-            println!("{}", serde_json::to_string_pretty(&cfg)?);
+        crate::cli::ConfigAction::Show { show_secrets } => {
+            let cfg = AppConfig::load_or_default(global.config.as_deref())?;
+            let mut json = serde_json::to_value(&cfg)?;
+            if !show_secrets {
+                mask_secrets(&mut json);
+            }
+            println!("{}", serde_json::to_string_pretty(&json)?);
         }
         crate::cli::ConfigAction::Set { key, value } => {
-            let mut cfg = AppConfig::load_or_default()?;
-            match key.as_str() {
-                "port" => cfg.port = value.parse()?,
-                "host" => cfg.host = value,
-                _ => anyhow::bail!("Unknown config key: {key}"),
+            let cfg = AppConfig::load_or_default(global.config.as_deref())?;
+            let (cfg, warnings) = set(&cfg, &key, value.as_deref()).map_err(anyhow::Error::msg)?;
+            for warning in warnings {
+                eprintln!("Warning: {warning}");
             }
             cfg.save()?;
             println!("Configuration updated.");
@@ -20,3 +27,366 @@ pub async fn execute(args: ConfigArgs) -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+//  Keys
+
+/// How `config set` reads a value for a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    /// Text; an empty value clears it.
+    OptionalText,
+    /// `true`/`false`, `on`/`off` or `yes`/`no`.
+    Bool,
+    /// An integer; the config's own type bounds it.
+    Number,
+    /// A byte count with an optional `K`/`M`/`G`/`T` suffix.
+    Bytes,
+    Port,
+    /// A port; empty or `0` clears it.
+    OptionalPort,
+    /// A path; empty clears it.  Missing files get a warning.
+    OptionalPath,
+    /// Edited with `<key>.add`, `.remove` and `.clear`.
+    PathList,
+    /// Edited with `<key>.add`, `.remove` and `.clear`.
+    TextList,
+    LogLevel,
+    Numa,
+}
+
+impl Kind {
+    fn is_list(self) -> bool {
+        matches!(self, Kind::PathList | Kind::TextList)
+    }
+}
+
+/// Every key `config set` accepts.  Values are stored through the JSON
+/// form of [`AppConfig`], so a new field needs only its entry here.
+const KEYS: &[(&str, Kind)] = &[
+    ("host", Kind::Text),
+    ("port", Kind::Port),
+    ("model_dirs", Kind::PathList),
+    ("api_key", Kind::OptionalText),
+    ("default_ctx_size", Kind::Number),
+    ("default_n_gpu_layers", Kind::Number),
+    ("max_models", Kind::Number),
+    ("max_memory_bytes", Kind::Bytes),
+    ("idle_timeout_secs", Kind::Number),
+    ("auto_load_models", Kind::Bool),
+    ("load_wait_timeout_secs", Kind::Number),
+    ("pinned_models", Kind::TextList),
+    ("allow_external_paths", Kind::Bool),
+    ("follow_dir_symlinks", Kind::Bool),
+    ("rescan_interval_secs", Kind::Number),
+    ("system_metrics_enabled", Kind::Bool),
+    ("system_metrics_interval_secs", Kind::Number),
+    ("stats_flush_interval_secs", Kind::Number),
+    ("request_log_enabled", Kind::Bool),
+    ("log_prompts", Kind::Bool),
+    ("request_log_retention_days", Kind::Number),
+    ("chat_retention_days", Kind::Number),
+    ("rate_limit_per_minute", Kind::Number),
+    ("model_ops_rate_limit_per_minute", Kind::Number),
+    ("max_body_bytes", Kind::Bytes),
+    ("generation_timeout_secs", Kind::Number),
+    ("compression_enabled", Kind::Bool),
+    ("tls_cert", Kind::OptionalPath),
+    ("tls_key", Kind::OptionalPath),
+    ("http_redirect_port", Kind::OptionalPort),
+    ("log_stream_level", Kind::LogLevel),
+    ("numa", Kind::Numa),
+    ("db_path", Kind::OptionalText),
+];
+
+/// Keys whose values `config show` masks.
+const SECRETS: &[&str] = &["api_key"];
+
+/// `cfg` with `key` set to `value`, plus warnings worth printing.  `key`
+/// is a field name, or `<list>.add`/`.remove`/`.clear` for list fields.
+fn set(
+    cfg: &AppConfig,
+    key: &str,
+    value: Option<&str>,
+) -> Result<(AppConfig, Vec<String>), String> {
+    let (name, op) = match key.rsplit_once('.') {
+        Some((name, op)) => (name, Some(op)),
+        None => (key, None),
+    };
+    let Some(&(name, kind)) = KEYS.iter().find(|(k, _)| *k == name) else {
+        return Err(format!(
+            "Unknown config key: {key} (valid keys: {})",
+            valid_keys()
+        ));
+    };
+
+    let mut json = serde_json::to_value(cfg).map_err(|e| e.to_string())?;
+    let mut warnings = Vec::new();
+    let field = &mut json[name];
+    match (kind.is_list(), op, value) {
+        (false, None, Some(value)) => {
+            *field = parse(name, kind, value)?;
+            if kind == Kind::OptionalPath {
+                warnings.extend(missing_path(value));
+            }
+        }
+        (true, Some("add"), Some(value)) => {
+            let list = field.as_array_mut().ok_or("not a list")?;
+            let item = Value::from(value);
+            if list.contains(&item) {
+                warnings.push(format!("{value} is already in {name}"));
+            } else {
+                list.push(item);
+            }
+            if kind == Kind::PathList {
+                warnings.extend(missing_path(value));
+            }
+        }
+        (true, Some("remove"), Some(value)) => {
+            let list = field.as_array_mut().ok_or("not a list")?;
+            let before = list.len();
+            list.retain(|item| item.as_str() != Some(value));
+            if list.len() == before {
+                return Err(format!("{value} is not in {name}"));
+            }
+        }
+        (true, Some("clear"), None) => *field = Value::Array(Vec::new()),
+        (true, Some("clear"), Some(_)) => return Err(format!("{key} takes no value")),
+        (true, None, _) => {
+            return Err(format!(
+                "{name} is a list; use {name}.add, {name}.remove or {name}.clear"
+            ));
+        }
+        (false, None, None) | (true, Some("add" | "remove"), None) => {
+            return Err(format!("{key} needs a value"));
+        }
+        // `port.add`, `model_dirs.sort`
+        _ => {
+            return Err(format!(
+                "Unknown config key: {key} (valid keys: {})",
+                valid_keys()
+            ));
+        }
+    }
+    let cfg = AppConfig {
+        // Not part of the JSON, so it doesn't survive the round trip
+        file: cfg.file.clone(),
+        ..serde_json::from_value(json).map_err(|e| format!("Invalid {name}: {e}"))?
+    };
+    Ok((cfg, warnings))
+}
+
+/// `value` as the JSON `kind` stores.
+fn parse(name: &str, kind: Kind, value: &str) -> Result<Value, String> {
+    let invalid = |what: &str| format!("Invalid {name}: '{value}' is not {what}");
+    Ok(match kind {
+        Kind::Text => value.into(),
+        Kind::OptionalText | Kind::OptionalPath if value.is_empty() => Value::Null,
+        Kind::OptionalText | Kind::OptionalPath => value.into(),
+        Kind::Bool => match value.to_ascii_lowercase().as_str() {
+            "true" | "on" | "yes" => true.into(),
+            "false" | "off" | "no" => false.into(),
+            _ => return Err(invalid("true or false")),
+        },
+        Kind::Number => value
+            .parse::<i64>()
+            .map_err(|_| invalid("a whole number"))?
+            .into(),
+        Kind::Bytes => crate::cli::parse_size(value)
+            .map_err(|e| format!("Invalid {name}: {e}"))?
+            .into(),
+        Kind::OptionalPort if value.is_empty() || value == "0" => Value::Null,
+        Kind::Port | Kind::OptionalPort => match value.parse::<u16>() {
+            Ok(port) if port > 0 => port.into(),
+            _ => return Err(format!("Invalid {name}: must be between 1 and 65535")),
+        },
+        Kind::LogLevel => match value.to_ascii_lowercase().as_str() {
+            level @ ("error" | "warn" | "info" | "debug" | "trace") => level.into(),
+            _ => return Err(invalid("error, warn, info, debug or trace")),
+        },
+        Kind::Numa => crate::cli::parse_numa(value)
+            .map(|numa| serde_json::to_value(numa).expect("NUMA strategies serialize"))?,
+        Kind::PathList | Kind::TextList => unreachable!("lists are edited item by item"),
+    })
+}
+
+fn missing_path(value: &str) -> Option<String> {
+    (!value.is_empty() && !Path::new(value).exists()).then(|| format!("{value} does not exist"))
+}
+
+fn valid_keys() -> String {
+    KEYS.iter()
+        .map(|&(key, kind)| {
+            if kind.is_list() {
+                format!("{key}.add, {key}.remove, {key}.clear")
+            } else {
+                key.to_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Replace secret values in `json` with a mask keeping their last four
+/// characters.
+fn mask_secrets(json: &mut Value) {
+    for &key in SECRETS {
+        if let Some(Value::String(secret)) = json.get_mut(key) {
+            let chars = secret.chars().count();
+            let shown: String = if chars > 8 {
+                secret.chars().skip(chars - 4).collect()
+            } else {
+                String::new()
+            };
+            *secret = format!("****{shown}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn set_ok(cfg: &AppConfig, key: &str, value: Option<&str>) -> AppConfig {
+        set(cfg, key, value).unwrap().0
+    }
+
+    #[test]
+    fn every_config_field_has_a_key() {
+        let json = serde_json::to_value(AppConfig::default()).unwrap();
+        let fields: Vec<&String> = json.as_object().unwrap().keys().collect();
+        for field in &fields {
+            assert!(
+                KEYS.iter().any(|(k, _)| k == field),
+                "no config set entry for {field}"
+            );
+        }
+        assert_eq!(KEYS.len(), fields.len());
+    }
+
+    #[test]
+    fn scalars_are_typed_and_validated() {
+        let cfg = AppConfig::default();
+        assert_eq!(set_ok(&cfg, "port", Some("9000")).port, 9000);
+        assert_eq!(set_ok(&cfg, "host", Some("0.0.0.0")).host, "0.0.0.0");
+        assert_eq!(
+            set_ok(&cfg, "default_n_gpu_layers", Some("-1")).default_n_gpu_layers,
+            -1
+        );
+        assert_eq!(
+            set_ok(&cfg, "default_ctx_size", Some("8192")).default_ctx_size,
+            8192
+        );
+        assert!(!set_ok(&cfg, "auto_load_models", Some("off")).auto_load_models);
+        assert_eq!(
+            set_ok(&cfg, "max_memory_bytes", Some("2G")).max_memory_bytes,
+            2 << 30
+        );
+        assert_eq!(
+            set_ok(&cfg, "log_stream_level", Some("DEBUG")).log_stream_level,
+            "debug"
+        );
+        assert_eq!(
+            set_ok(&cfg, "numa", Some("distribute")).numa,
+            llama_core::NumaStrategy::Distribute
+        );
+
+        let with_key = set_ok(&cfg, "api_key", Some("sk-secret"));
+        assert_eq!(with_key.api_key.as_deref(), Some("sk-secret"));
+        assert_eq!(set_ok(&with_key, "api_key", Some("")).api_key, None);
+        let redirect = set_ok(&cfg, "http_redirect_port", Some("80"));
+        assert_eq!(redirect.http_redirect_port, Some(80));
+        assert_eq!(
+            set_ok(&redirect, "http_redirect_port", Some("0")).http_redirect_port,
+            None
+        );
+
+        for (key, value) in [
+            ("port", "0"),
+            ("port", "70000"),
+            ("port", "http"),
+            ("auto_load_models", "maybe"),
+            ("default_ctx_size", "-1"),
+            ("max_models", "1.5"),
+            ("log_stream_level", "loud"),
+            ("numa", "everywhere"),
+        ] {
+            assert!(set(&cfg, key, Some(value)).is_err(), "{key}={value}");
+        }
+        assert_eq!(set(&cfg, "port", None).unwrap_err(), "port needs a value");
+    }
+
+    #[test]
+    fn lists_add_remove_and_clear() {
+        let cfg = AppConfig::default();
+        let (cfg, warnings) = set(&cfg, "model_dirs.add", Some("/nonexistent/models")).unwrap();
+        assert_eq!(warnings, ["/nonexistent/models does not exist"]);
+        let cfg = set_ok(&cfg, "model_dirs.add", Some("/srv/gguf"));
+        assert_eq!(cfg.model_dirs.len(), 2);
+
+        let (again, warnings) = set(&cfg, "model_dirs.add", Some("/srv/gguf")).unwrap();
+        assert_eq!(again.model_dirs.len(), 2);
+        assert_eq!(warnings[0], "/srv/gguf is already in model_dirs");
+
+        let removed = set_ok(&cfg, "model_dirs.remove", Some("/nonexistent/models"));
+        assert_eq!(removed.model_dirs, [std::path::PathBuf::from("/srv/gguf")]);
+        assert!(set(&removed, "model_dirs.remove", Some("/elsewhere")).is_err());
+        assert!(
+            set_ok(&removed, "model_dirs.clear", None)
+                .model_dirs
+                .is_empty()
+        );
+
+        let pinned = set_ok(&cfg, "pinned_models.add", Some("qwen"));
+        assert_eq!(pinned.pinned_models, ["qwen"]);
+
+        assert!(
+            set(&cfg, "model_dirs", Some("/srv"))
+                .unwrap_err()
+                .contains("is a list")
+        );
+        assert!(set(&cfg, "model_dirs.clear", Some("x")).is_err());
+        assert!(set(&cfg, "model_dirs.add", None).is_err());
+    }
+
+    #[test]
+    fn unknown_keys_list_the_valid_ones() {
+        let cfg = AppConfig::default();
+        for key in ["colour", "port.add", "model_dirs.sort"] {
+            let err = set(&cfg, key, Some("x")).unwrap_err();
+            assert!(
+                err.starts_with(&format!("Unknown config key: {key}")),
+                "{err}"
+            );
+            assert!(err.contains("host, port, model_dirs.add, model_dirs.remove"));
+        }
+    }
+
+    #[test]
+    fn set_saves_to_the_file_it_loaded() {
+        let tmp = TempDir::new("llama-config-set");
+        let file = tmp.0.join("b.json");
+        let cfg = AppConfig::load_or_default(Some(&file)).unwrap();
+        set_ok(&cfg, "port", Some("9000")).save().unwrap();
+        assert_eq!(AppConfig::load_or_default(Some(&file)).unwrap().port, 9000);
+    }
+
+    #[test]
+    fn secrets_are_masked() {
+        let mut json = serde_json::json!({ "api_key": "sk-1234567890abcd", "port": 8080 });
+        mask_secrets(&mut json);
+        assert_eq!(
+            json,
+            serde_json::json!({ "api_key": "****abcd", "port": 8080 })
+        );
+
+        let mut short = serde_json::json!({ "api_key": "short" });
+        mask_secrets(&mut short);
+        assert_eq!(short["api_key"], "****");
+
+        let mut unset = serde_json::json!({ "api_key": null });
+        mask_secrets(&mut unset);
+        assert_eq!(unset["api_key"], Value::Null);
+    }
+}
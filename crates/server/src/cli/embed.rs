@@ -0,0 +1,227 @@
+use std::io::{self, BufRead, BufWriter, IsTerminal, Write};
+
+use serde::Serialize;
+
+use crate::cli::EmbedArgs;
+use crate::cli::generation::{context_params, load_weights};
+use crate::services::inference::{
+    PoolError, PoolParams, l2_normalize, pooled_blocking, read_embedding,
+};
+
+/// How `embed` writes its vectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbedFormat {
+    /// One document with every vector.
+    Json,
+    /// One object per input and line.
+    Ndjson,
+    /// A header, then `index,source,n_tokens` and one column per dimension.
+    Csv,
+}
+
+/// One embedded input.
+#[derive(Debug, Serialize)]
+struct Embedding {
+    index: usize,
+    /// The file it came from; `None` for stdin lines.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    n_tokens: u32,
+    embedding: Vec<f32>,
+}
+
+pub async fn execute(args: EmbedArgs) -> anyhow::Result<()> {
+    let (sources, texts) = read_inputs(&args)?;
+    if texts.is_empty() {
+        anyhow::bail!("nothing to embed");
+    }
+
+    let model = load_weights(&args.model, &args.load)?;
+    let n_embd = model.n_embd() as usize;
+    let normalize = !args.no_normalize;
+    let params = PoolParams {
+        context: context_params(&args.load),
+        truncate: args.truncate,
+    };
+    let pooled = tokio::task::spawn_blocking(move || {
+        pooled_blocking(model, &texts, &params, |ctx| {
+            let embedding = read_embedding(ctx, n_embd)?;
+            Some(if normalize {
+                l2_normalize(embedding)
+            } else {
+                embedding
+            })
+        })
+    })
+    .await?
+    .map_err(|e| match e {
+        PoolError::NoOutput => {
+            anyhow::anyhow!("the model produced no embeddings; is it an embedding model?")
+        }
+        e @ PoolError::TooLong { .. } => anyhow::anyhow!("{e} (--truncate cuts it to fit)"),
+        PoolError::Llama(e) => e.into(),
+    })?;
+
+    let embeddings: Vec<Embedding> = pooled
+        .into_iter()
+        .zip(sources)
+        .enumerate()
+        .map(|(index, (p, source))| Embedding {
+            index,
+            source,
+            n_tokens: p.n_tokens,
+            embedding: p.values,
+        })
+        .collect();
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    write_embeddings(&mut out, args.format, n_embd, &embeddings)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Each `--input` file whole, or each non-empty stdin line; with the file
+/// each came from.
+fn read_inputs(args: &EmbedArgs) -> anyhow::Result<(Vec<Option<String>>, Vec<String>)> {
+    if args.inputs.is_empty() {
+        if io::stdin().is_terminal() {
+            anyhow::bail!("give --input files or pipe lines on stdin");
+        }
+        let lines = stdin_inputs(io::stdin().lock())?;
+        return Ok((vec![None; lines.len()], lines));
+    }
+    args.inputs
+        .iter()
+        .map(|path| {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("cannot read {}: {e}", path.display()))?;
+            Ok((Some(path.display().to_string()), text))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map(|inputs| inputs.into_iter().unzip())
+}
+
+fn stdin_inputs(input: impl BufRead) -> io::Result<Vec<String>> {
+    input
+        .lines()
+        .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+        .collect()
+}
+
+fn write_embeddings(
+    out: &mut impl Write,
+    format: EmbedFormat,
+    dimensions: usize,
+    embeddings: &[Embedding],
+) -> anyhow::Result<()> {
+    match format {
+        EmbedFormat::Json => {
+            let total_tokens: u32 = embeddings.iter().map(|e| e.n_tokens).sum();
+            serde_json::to_writer_pretty(
+                &mut *out,
+                &serde_json::json!({
+                    "dimensions": dimensions,
+                    "data": embeddings,
+                    "usage": { "total_tokens": total_tokens },
+                }),
+            )?;
+            writeln!(out)?;
+        }
+        EmbedFormat::Ndjson => {
+            for embedding in embeddings {
+                serde_json::to_writer(&mut *out, embedding)?;
+                writeln!(out)?;
+            }
+        }
+        EmbedFormat::Csv => {
+            write!(out, "index,source,n_tokens")?;
+            for i in 0..dimensions {
+                write!(out, ",d{i}")?;
+            }
+            writeln!(out)?;
+            for e in embeddings {
+                let source = e.source.as_deref().map(csv_field).unwrap_or_default();
+                write!(out, "{},{source},{}", e.index, e.n_tokens)?;
+                for x in &e.embedding {
+                    write!(out, ",{x}")?;
+                }
+                writeln!(out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `field` quoted if it holds a comma, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embeddings() -> Vec<Embedding> {
+        vec![
+            Embedding {
+                index: 0,
+                source: Some("notes, draft.txt".into()),
+                n_tokens: 4,
+                embedding: vec![0.6, 0.8],
+            },
+            Embedding {
+                index: 1,
+                source: None,
+                n_tokens: 2,
+                embedding: vec![1.0, 0.0],
+            },
+        ]
+    }
+
+    fn written(format: EmbedFormat) -> String {
+        let mut out = Vec::new();
+        write_embeddings(&mut out, format, 2, &embeddings()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn stdin_lines_skip_blanks() {
+        let lines = stdin_inputs("first\n\n  \nsecond line\n".as_bytes()).unwrap();
+        assert_eq!(lines, ["first", "second line"]);
+    }
+
+    #[test]
+    fn formats() {
+        let json: serde_json::Value = serde_json::from_str(&written(EmbedFormat::Json)).unwrap();
+        assert_eq!(json["dimensions"], 2);
+        assert_eq!(json["usage"]["total_tokens"], 6);
+        assert_eq!(json["data"][0]["source"], "notes, draft.txt");
+        assert_eq!(json["data"][1].get("source"), None);
+        assert_eq!(json["data"][1]["embedding"], serde_json::json!([1.0, 0.0]));
+
+        let ndjson = written(EmbedFormat::Ndjson);
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            r#"{"index":1,"n_tokens":2,"embedding":[1.0,0.0]}"#
+        );
+
+        assert_eq!(
+            written(EmbedFormat::Csv),
+            "index,source,n_tokens,d0,d1\n\
+             0,\"notes, draft.txt\",4,0.6,0.8\n\
+             1,,2,1,0\n"
+        );
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("a.txt"), "a.txt");
+        assert_eq!(csv_field("say \"hi\".txt"), "\"say \"\"hi\"\".txt\"");
+    }
+}
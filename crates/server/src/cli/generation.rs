@@ -0,0 +1,394 @@
+//! Model loading and generation shared by `run`, `complete` and `embed`.
+
+use std::fmt;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::mpsc;
+
+use crate::cli::{LoadArgs, SamplingArgs};
+
+/// Load the model at `path` and a context for it as the flags say.
+pub(crate) fn load_model(
+    path: &Path,
+    args: &LoadArgs,
+) -> anyhow::Result<(Arc<llama_core::LlamaModel>, llama_core::LlamaContext)> {
+    let model = load_weights(path, args)?;
+    let ctx = llama_core::LlamaContext::new(model.clone(), &context_params(args))?;
+    Ok((model, ctx))
+}
+
+/// Load just the model at `path`, offloaded as the flags say.
+pub(crate) fn load_weights(
+    path: &Path,
+    args: &LoadArgs,
+) -> anyhow::Result<Arc<llama_core::LlamaModel>> {
+    let backend = llama_core::LlamaBackend::init();
+    backend.numa_init(args.numa.unwrap_or_default());
+
+    if args.n_gpu_layers > 0 && !llama_core::LlamaBackend::supports_gpu_offload() {
+        eprintln!(
+            "Warning: --n-gpu-layers {} ignored; this build has no GPU offload support \
+             and will run on the CPU only.",
+            args.n_gpu_layers
+        );
+    }
+
+    let model_params = llama_core::ModelParams {
+        n_gpu_layers: args.n_gpu_layers,
+        split_mode: args.gpu_split.split_mode,
+        main_gpu: args.gpu_split.main_gpu,
+        tensor_split: args.gpu_split.tensor_split.clone(),
+        ..Default::default()
+    };
+    Ok(Arc::new(llama_core::LlamaModel::load_from_file(
+        path,
+        &model_params,
+    )?))
+}
+
+/// Context parameters from the flags.
+pub(crate) fn context_params(args: &LoadArgs) -> llama_core::ContextParams {
+    let n_threads = args.threads.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get() as i32)
+            .unwrap_or(4)
+    });
+
+    let mut ctx_params = llama_core::ContextParams {
+        n_ctx: args.ctx_size,
+        n_threads,
+        n_threads_batch: n_threads,
+        flash_attn: args.flash_attn,
+        type_k: args.cache_type_k,
+        type_v: args.cache_type_v,
+        ..Default::default()
+    };
+    args.rope.settings().apply(&mut ctx_params);
+    ctx_params
+}
+
+/// The model's chat template applied to `messages`, or a plain
+/// `role: content` transcript for models without one.
+pub(crate) fn render_prompt(
+    model: &llama_core::LlamaModel,
+    messages: &[llama_core::ChatMessage],
+    add_assistant: bool,
+) -> String {
+    llama_core::apply_model_template(model, messages, add_assistant).unwrap_or_else(|| {
+        let mut prompt = messages
+            .iter()
+            .map(|m| format!("{}: {}", m.role, m.content))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if add_assistant {
+            prompt.push_str("\nassistant:");
+        }
+        prompt
+    })
+}
+
+//  Streaming
+
+/// How a generation ended.
+#[derive(Debug)]
+pub(crate) struct Finish {
+    pub reason: llama_core::FinishReason,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+}
+
+/// What [`stream_completion`] produced.
+#[derive(Debug)]
+pub(crate) struct Completion {
+    /// The text streamed, also when generation failed part way.
+    pub text: String,
+    /// The error message when generation failed.
+    pub outcome: Result<Finish, String>,
+    /// Prompt tokens found in the KV cache rather than decoded.
+    pub n_past: usize,
+    /// Counters and timings of this generation alone.
+    pub perf: llama_core::PerfData,
+}
+
+/// Run `request` on sequence 0 of `ctx`, which holds `previous` from an
+/// earlier one (empty for a fresh context), passing each piece of text
+/// to `on_piece` as it arrives.  Only the part of the prompt past what
+/// `previous` shares with it is decoded.
+///
+/// An error from `on_piece` (e.g. a closed pipe) stops generation and is
+/// returned.
+pub(crate) async fn stream_completion(
+    ctx: &Arc<Mutex<llama_core::LlamaContext>>,
+    request: llama_core::GenerateRequest,
+    previous: Vec<i32>,
+    mut on_piece: impl FnMut(&str) -> io::Result<()>,
+) -> anyhow::Result<Completion> {
+    let (tx, mut rx) = mpsc::channel(64);
+    let ctx = ctx.clone();
+    let task = tokio::task::spawn_blocking(move || {
+        let mut ctx = ctx.lock().unwrap();
+        ctx.perf_reset();
+        let n_past = llama_core::generate::reuse_kv_prefix(&mut ctx, &previous, &request.tokens);
+        llama_core::generate::generate_blocking_from(
+            &mut ctx,
+            &request,
+            n_past,
+            tx,
+            &llama_core::CancelToken::new(),
+        );
+        (n_past, ctx.perf())
+    });
+
+    let mut text = String::new();
+    let mut outcome = Err("generation ended without a result".to_string());
+    while let Some(event) = rx.recv().await {
+        match event {
+            llama_core::GenerateEvent::Token(piece) => {
+                on_piece(&piece)?;
+                text.push_str(&piece);
+            }
+            llama_core::GenerateEvent::Done {
+                finish_reason,
+                prompt_tokens,
+                completion_tokens,
+            } => {
+                outcome = Ok(Finish {
+                    reason: finish_reason,
+                    prompt_tokens,
+                    completion_tokens,
+                });
+                break;
+            }
+            llama_core::GenerateEvent::Error(e) => {
+                outcome = Err(e.message);
+                break;
+            }
+        }
+    }
+
+    let (n_past, perf) = task.await?;
+    Ok(Completion {
+        text,
+        outcome,
+        n_past,
+        perf,
+    })
+}
+
+//  Settings
+
+/// Sampling and generation settings: the flags, changed in `run` with
+/// `/set`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct Settings {
+    /// `seed: None` draws a new seed for every reply, so `/retry` gets a
+    /// different one.
+    sampling: llama_core::SamplingParams,
+    max_tokens: u32,
+    stop: Vec<String>,
+    ignore_eos: bool,
+}
+
+impl Settings {
+    /// The flags given, over the defaults of [`llama_core::SamplingParams`].
+    pub(crate) fn from_args(args: &SamplingArgs) -> Self {
+        let defaults = llama_core::SamplingParams::default();
+        Self {
+            sampling: llama_core::SamplingParams {
+                temperature: args.temp,
+                top_p: args.top_p.unwrap_or(defaults.top_p),
+                top_k: args.top_k.unwrap_or(defaults.top_k),
+                min_p: args.min_p.unwrap_or(defaults.min_p),
+                repeat_penalty: args.repeat_penalty.unwrap_or(defaults.repeat_penalty),
+                seed: args.seed,
+                ..defaults
+            },
+            max_tokens: args.max_tokens,
+            stop: args.stop.clone(),
+            ignore_eos: args.ignore_eos,
+        }
+    }
+
+    /// Apply `/set <key> <value>`; on error nothing changes.
+    pub(crate) fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        fn number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+            value
+                .parse()
+                .map_err(|_| format!("{key}: '{value}' is not a valid number"))
+        }
+        fn in_range(key: &str, value: &str, range: RangeInclusive<f32>) -> Result<f32, String> {
+            let v = number(key, value)?;
+            if range.contains(&v) {
+                Ok(v)
+            } else {
+                Err(format!(
+                    "{key} must be between {} and {}",
+                    range.start(),
+                    range.end()
+                ))
+            }
+        }
+
+        let s = &mut self.sampling;
+        match key.replace('_', "-").as_str() {
+            "temp" | "temperature" => s.temperature = in_range(key, value, 0.0..=f32::MAX)?,
+            "top-p" => s.top_p = in_range(key, value, 0.0..=1.0)?,
+            "top-k" => s.top_k = number::<u32>(key, value)? as i32,
+            "min-p" => s.min_p = in_range(key, value, 0.0..=1.0)?,
+            "repeat-penalty" => s.repeat_penalty = in_range(key, value, 0.0..=f32::MAX)?,
+            "seed" if value == "random" => s.seed = None,
+            "seed" => s.seed = Some(number(key, value)?),
+            "max-tokens" => match number(key, value)? {
+                0 => return Err("max-tokens must be at least 1".into()),
+                n => self.max_tokens = n,
+            },
+            "stop" if value == "none" => self.stop.clear(),
+            "stop" => self.stop.push(unescape(value)),
+            "ignore-eos" => {
+                self.ignore_eos = match value {
+                    "on" | "true" | "1" => true,
+                    "off" | "false" | "0" => false,
+                    _ => return Err(format!("ignore-eos: '{value}' is not on or off")),
+                }
+            }
+            _ => return Err(format!("Unknown setting '{key}'; see /help")),
+        }
+        Ok(())
+    }
+
+    /// A request for `tokens` with these settings.
+    pub(crate) fn request(&self, tokens: Vec<i32>) -> llama_core::GenerateRequest {
+        let seed = self.sampling.seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.subsec_nanos())
+        });
+        llama_core::GenerateRequest {
+            tokens,
+            max_tokens: self.max_tokens,
+            stop_words: self.stop.clone(),
+            sampling_params: llama_core::SamplingParams {
+                seed: Some(seed),
+                ..self.sampling.clone()
+            },
+            return_special: false,
+            ignore_eos: self.ignore_eos,
+        }
+    }
+}
+
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = &self.sampling;
+        writeln!(f, "  temp            {}", s.temperature)?;
+        writeln!(f, "  top-p           {}", s.top_p)?;
+        writeln!(f, "  top-k           {}", s.top_k)?;
+        writeln!(f, "  min-p           {}", s.min_p)?;
+        writeln!(f, "  repeat-penalty  {}", s.repeat_penalty)?;
+        match s.seed {
+            Some(seed) => writeln!(f, "  seed            {seed}")?,
+            None => writeln!(f, "  seed            random")?,
+        }
+        writeln!(f, "  max-tokens      {}", self.max_tokens)?;
+        writeln!(f, "  stop            {:?}", self.stop)?;
+        writeln!(
+            f,
+            "  ignore-eos      {}",
+            if self.ignore_eos { "on" } else { "off" }
+        )
+    }
+}
+
+/// Turn `\n` and `\t` typed in a stop word into the characters.
+fn unescape(value: &str) -> String {
+    value.replace("\\n", "\n").replace("\\t", "\t")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sampling_args(flags: &[&str]) -> SamplingArgs {
+        use clap::Parser;
+
+        let argv = ["llama-dashboard", "run", "model.gguf"];
+        let cli = crate::cli::Cli::try_parse_from(argv.iter().chain(flags)).unwrap();
+        match cli.command {
+            Some(crate::cli::Commands::Run(args)) => args.sampling,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn flags_override_only_what_they_name() {
+        let defaults = llama_core::SamplingParams::default();
+        let settings = Settings::from_args(&sampling_args(&[]));
+        assert_eq!(settings.sampling.top_k, defaults.top_k);
+        assert_eq!(settings.sampling.top_p, defaults.top_p);
+        assert_eq!(settings.sampling.seed, None);
+        assert_eq!(settings.max_tokens, 2048);
+        assert!(settings.stop.is_empty() && !settings.ignore_eos);
+
+        let settings = Settings::from_args(&sampling_args(&[
+            "--temp=0",
+            "--top-k=20",
+            "--min-p=0.1",
+            "--seed=42",
+            "--max-tokens=64",
+            "--stop=</s>",
+            "--stop",
+            "User:",
+            "--ignore-eos",
+        ]));
+        assert_eq!(settings.sampling.temperature, 0.0);
+        assert_eq!(settings.sampling.top_k, 20);
+        assert_eq!(settings.sampling.min_p, 0.1);
+        assert_eq!(settings.sampling.top_p, defaults.top_p);
+        assert_eq!(settings.sampling.repeat_penalty, defaults.repeat_penalty);
+        assert_eq!(settings.stop, ["</s>", "User:"]);
+
+        let request = settings.request(vec![1, 2]);
+        assert_eq!(request.sampling_params.seed, Some(42));
+        assert_eq!(request.max_tokens, 64);
+        assert_eq!(request.stop_words, ["</s>", "User:"]);
+        assert!(request.ignore_eos);
+    }
+
+    #[test]
+    fn set_changes_one_setting_or_none() {
+        let mut settings = Settings::from_args(&sampling_args(&["--seed=7"]));
+        settings.set("temp", "0.2").unwrap();
+        settings.set("top_p", "0.5").unwrap();
+        settings.set("max-tokens", "10").unwrap();
+        settings.set("stop", "\\n\\n").unwrap();
+        settings.set("ignore-eos", "on").unwrap();
+        assert_eq!(settings.sampling.temperature, 0.2);
+        assert_eq!(settings.sampling.top_p, 0.5);
+        assert_eq!(settings.max_tokens, 10);
+        assert_eq!(settings.stop, ["\n\n"]);
+        assert!(settings.ignore_eos);
+
+        settings.set("seed", "random").unwrap();
+        assert_eq!(settings.sampling.seed, None);
+        // A random seed is drawn per request
+        assert!(settings.request(vec![]).sampling_params.seed.is_some());
+        settings.set("stop", "none").unwrap();
+        assert!(settings.stop.is_empty());
+
+        let before = format!("{settings}");
+        for (key, value) in [
+            ("temp", "hot"),
+            ("top-p", "1.5"),
+            ("top-k", "-1"),
+            ("max-tokens", "0"),
+            ("ignore-eos", "maybe"),
+            ("mirostat", "2"),
+        ] {
+            assert!(settings.set(key, value).is_err(), "{key} {value}");
+        }
+        assert_eq!(format!("{settings}"), before);
+    }
+}
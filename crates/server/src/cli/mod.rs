@@ -1,10 +1,18 @@
+pub mod bench;
+pub mod complete;
 pub mod config_cmd;
+pub mod embed;
+pub mod generation;
 pub mod models;
+pub mod pull;
 pub mod run;
 pub mod serve;
+pub mod tokenize;
 
 use clap::{Parser, Subcommand};
 
+use crate::services::model_manager::RopeSettings;
+
 #[derive(Parser)]
 #[command(
     name = "llama-dashboard",
@@ -33,9 +41,15 @@ pub struct GlobalArgs {
     #[arg(long = "models-dir", env = "LLAMA_MODELS_DIR")]
     pub models_dirs: Vec<std::path::PathBuf>,
 
-    /// Optional API key for bearer-token auth.
+    /// Optional API key for bearer-token auth (overrides `api_key` in
+    /// config.json).
     #[arg(long, env = "LLAMA_API_KEY")]
     pub api_key: Option<String>,
+
+    /// Configuration file to use instead of
+    /// `~/.config/llama-dashboard/config.json`.
+    #[arg(long, global = true, env = "LLAMA_CONFIG")]
+    pub config: Option<std::path::PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -46,15 +60,205 @@ pub enum Commands {
     /// Load a model and start an interactive chat.
     Run(RunArgs),
 
+    /// Generate one completion for a prompt and exit, for scripts.
+    ///
+    /// Exit codes: 2 = generation failed, 3 = the prompt doesn't fit the
+    /// context, 1 = any other error.
+    Complete(CompleteArgs),
+
     /// Manage discovered models.
     Models(ModelsArgs),
 
+    /// Measure prompt-processing and generation speed of a model.
+    Bench(BenchArgs),
+
+    /// Show the tokens a model's vocabulary makes of some text, or the
+    /// text of some token ids.
+    Tokenize(TokenizeArgs),
+
+    /// Embed text files or stdin lines with an embedding model, for
+    /// offline indexing.
+    Embed(EmbedArgs),
+
+    /// Download a GGUF model from Hugging Face.
+    ///
+    /// Exit codes: 2 = repository or file not found, 3 = network
+    /// failure, 4 = disk full, 1 = any other error.
+    Pull(PullArgs),
+
     /// View / edit configuration.
     Config(ConfigArgs),
 }
 
 //  Subcommand argument structs
 
+/// Parse a byte size with an optional binary suffix (`K`, `M`, `G`, `T`,
+/// optionally followed by `iB` or `B`).
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num: f64 = num
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid size '{s}'"))?;
+    let shift = match unit
+        .to_ascii_uppercase()
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+    {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(format!("unknown size unit in '{s}'")),
+    };
+    Ok((num * (1u64 << shift) as f64) as u64)
+}
+
+/// Parse a split mode: `none`, `layer` or `row`.
+fn parse_split_mode(s: &str) -> Result<llama_core::SplitMode, String> {
+    serde_json::from_value(s.to_ascii_lowercase().into())
+        .map_err(|_| format!("'{s}' is not a split mode (none, layer or row)"))
+}
+
+/// Multi-GPU placement flags shared by `serve` and `run`.
+#[derive(Debug, clap::Args, Clone)]
+pub struct GpuSplitArgs {
+    /// How to spread offloaded layers over GPUs: none, layer or row.
+    #[arg(long, default_value = "layer", value_parser = parse_split_mode)]
+    pub split_mode: llama_core::SplitMode,
+
+    /// GPU for the whole model with `--split-mode none`, or for
+    /// intermediate results with `row`.
+    #[arg(long, default_value_t = 0)]
+    pub main_gpu: i32,
+
+    /// Comma-separated share of the offloaded layers per GPU, e.g. `3,1`.
+    #[arg(long, value_delimiter = ',')]
+    pub tensor_split: Vec<f32>,
+}
+
+/// Parse a flash attention switch: `on` or `off`.
+fn parse_flash_attn(s: &str) -> Result<bool, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "on" | "true" | "1" => Ok(true),
+        "off" | "false" | "0" => Ok(false),
+        _ => Err(format!("'{s}' is not a flash attention mode (on or off)")),
+    }
+}
+
+/// Parse a KV cache type such as `f16` or `q8_0`.
+fn parse_cache_type(s: &str) -> Result<llama_core::KvCacheType, String> {
+    serde_json::from_value(s.to_ascii_lowercase().into()).map_err(|_| {
+        format!(
+            "'{s}' is not a cache type (f32, f16, bf16, q8_0, q5_1, q5_0, q4_1, q4_0 or iq4_nl)"
+        )
+    })
+}
+
+/// Parse a NUMA strategy: `disabled`, `distribute`, `isolate`, `numactl`
+/// or `mirror`.
+fn parse_numa(s: &str) -> Result<llama_core::NumaStrategy, String> {
+    serde_json::from_value(s.to_ascii_lowercase().into()).map_err(|_| {
+        format!("'{s}' is not a NUMA strategy (disabled, distribute, isolate, numactl or mirror)")
+    })
+}
+
+/// Parse a `models list --sort` key: `size`, `name`, `quant` or `ctx`.
+fn parse_model_sort(s: &str) -> Result<models::ModelSort, String> {
+    use models::ModelSort;
+    match s.to_ascii_lowercase().as_str() {
+        "size" => Ok(ModelSort::Size),
+        "name" => Ok(ModelSort::Name),
+        "quant" => Ok(ModelSort::Quant),
+        "ctx" => Ok(ModelSort::Ctx),
+        _ => Err(format!(
+            "'{s}' is not a sort key (size, name, quant or ctx)"
+        )),
+    }
+}
+
+/// Parse an `embed --format`: `json`, `ndjson` or `csv`.
+fn parse_embed_format(s: &str) -> Result<embed::EmbedFormat, String> {
+    use embed::EmbedFormat;
+    match s.to_ascii_lowercase().as_str() {
+        "json" => Ok(EmbedFormat::Json),
+        "ndjson" => Ok(EmbedFormat::Ndjson),
+        "csv" => Ok(EmbedFormat::Csv),
+        _ => Err(format!("'{s}' is not a format (json, ndjson or csv)")),
+    }
+}
+
+/// Parse a RoPE scaling type: `none`, `linear` or `yarn`.
+fn parse_rope_scaling(s: &str) -> Result<llama_core::RopeScalingType, String> {
+    serde_json::from_value(s.to_ascii_lowercase().into())
+        .map_err(|_| format!("'{s}' is not a RoPE scaling type (none, linear or yarn)"))
+}
+
+/// RoPE scaling flags shared by `serve` and `run`; unset flags keep the
+/// model's own values.
+#[derive(Debug, clap::Args, Clone, Default)]
+pub struct RopeArgs {
+    /// RoPE scaling for contexts past the training length: none, linear
+    /// or yarn.
+    #[arg(long = "rope-scaling", value_parser = parse_rope_scaling)]
+    pub rope_scaling_type: Option<llama_core::RopeScalingType>,
+
+    /// RoPE base frequency.
+    #[arg(long)]
+    pub rope_freq_base: Option<f32>,
+
+    /// RoPE frequency scale, e.g. 0.25 for four times the training context.
+    #[arg(long)]
+    pub rope_freq_scale: Option<f32>,
+
+    /// YaRN extrapolation mix factor.
+    #[arg(long)]
+    pub yarn_ext_factor: Option<f32>,
+
+    /// YaRN magnitude scaling factor.
+    #[arg(long)]
+    pub yarn_attn_factor: Option<f32>,
+
+    /// YaRN low correction dimension.
+    #[arg(long)]
+    pub yarn_beta_fast: Option<f32>,
+
+    /// YaRN high correction dimension.
+    #[arg(long)]
+    pub yarn_beta_slow: Option<f32>,
+
+    /// Context size the model was trained on, for YaRN.
+    #[arg(long)]
+    pub yarn_orig_ctx: Option<u32>,
+}
+
+impl RopeArgs {
+    pub fn settings(&self) -> RopeSettings {
+        RopeSettings {
+            rope_scaling_type: self.rope_scaling_type,
+            rope_freq_base: self.rope_freq_base,
+            rope_freq_scale: self.rope_freq_scale,
+            yarn_ext_factor: self.yarn_ext_factor,
+            yarn_attn_factor: self.yarn_attn_factor,
+            yarn_beta_fast: self.yarn_beta_fast,
+            yarn_beta_slow: self.yarn_beta_slow,
+            yarn_orig_ctx: self.yarn_orig_ctx,
+        }
+    }
+}
+
+/// Parse an octal permission mode such as `600` or `0o660`.
+fn parse_mode(s: &str) -> Result<u32, String> {
+    let digits = s.strip_prefix("0o").unwrap_or(s);
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o777 => Ok(mode),
+        _ => Err(format!("'{s}' is not an octal permission mode like 600")),
+    }
+}
+
 #[derive(Debug, clap::Args, Clone)]
 pub struct ServeArgs {
     /// Pre-load this model on startup.
@@ -69,13 +273,95 @@ pub struct ServeArgs {
     #[arg(long, default_value_t = -1)]
     pub n_gpu_layers: i32,
 
+    #[command(flatten)]
+    pub gpu_split: GpuSplitArgs,
+
+    #[command(flatten)]
+    pub rope: RopeArgs,
+
+    /// NUMA strategy: disabled, distribute, isolate, numactl or mirror.
+    #[arg(long, value_parser = parse_numa)]
+    pub numa: Option<llama_core::NumaStrategy>,
+
+    /// Flash attention: on or off; a bare `--flash-attn` means on.
+    /// Unset lets llama.cpp decide per backend.
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "on",
+        value_parser = parse_flash_attn
+    )]
+    pub flash_attn: Option<bool>,
+
+    /// K cache type, e.g. q8_0 (default: f16).
+    #[arg(long = "cache-type-k", value_parser = parse_cache_type)]
+    pub cache_type_k: Option<llama_core::KvCacheType>,
+
+    /// V cache type, e.g. q8_0 (default: f16); quantized types need
+    /// flash attention.
+    #[arg(long = "cache-type-v", value_parser = parse_cache_type)]
+    pub cache_type_v: Option<llama_core::KvCacheType>,
+
+    /// Sequences per model context, i.e. requests that can share a loaded
+    /// model.  Each sequence gets ctx-size positions, so the KV cache
+    /// grows with it.
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..),
+        env = "LLAMA_PARALLEL"
+    )]
+    pub parallel: u32,
+
+    /// Fraction of holes in the KV cache that triggers defragmentation
+    /// (negative = off).
+    #[arg(long = "defrag-thold", default_value_t = -1.0, allow_negative_numbers = true)]
+    pub defrag_thold: f32,
+
     /// Maximum number of concurrently loaded models (0 = unlimited).
     #[arg(long = "models-max", default_value_t = 4, env = "LLAMA_MODELS_MAX")]
     pub max_models: usize,
 
+    /// Memory budget for loaded models, e.g. `24G` or `512M` (0 = unlimited).
+    #[arg(long = "max-memory", value_parser = parse_size, env = "LLAMA_MAX_MEMORY")]
+    pub max_memory: Option<u64>,
+
     /// Idle timeout in seconds; unload models after this period (0 = disabled).
     #[arg(long = "idle-timeout", default_value_t = 0, env = "LLAMA_IDLE_TIMEOUT")]
     pub idle_timeout: u64,
+
+    /// Seconds to wait for in-flight requests on SIGINT/SIGTERM before
+    /// closing them.
+    #[arg(
+        long = "shutdown-timeout",
+        default_value_t = 30,
+        env = "LLAMA_SHUTDOWN_TIMEOUT"
+    )]
+    pub shutdown_timeout: u64,
+
+    /// PEM certificate chain; serve HTTPS (together with --tls-key).
+    #[arg(long = "tls-cert", env = "LLAMA_TLS_CERT", requires = "tls_key")]
+    pub tls_cert: Option<std::path::PathBuf>,
+
+    /// PEM private key for --tls-cert.
+    #[arg(long = "tls-key", env = "LLAMA_TLS_KEY", requires = "tls_cert")]
+    pub tls_key: Option<std::path::PathBuf>,
+
+    /// With TLS, also accept plain HTTP on this port and redirect to HTTPS.
+    #[arg(long = "http-redirect-port", env = "LLAMA_HTTP_REDIRECT_PORT")]
+    pub http_redirect_port: Option<u16>,
+
+    /// Also serve on this Unix domain socket (not supported on Windows).
+    #[arg(long, env = "LLAMA_UDS")]
+    pub uds: Option<std::path::PathBuf>,
+
+    /// Octal permissions of the --uds socket file.
+    #[arg(long = "uds-mode", default_value = "600", value_parser = parse_mode)]
+    pub uds_mode: u32,
+
+    /// Serve only on the --uds socket, without the TCP listener.
+    #[arg(long = "uds-only", requires = "uds")]
+    pub uds_only: bool,
 }
 
 #[derive(Debug, clap::Args, Clone)]
@@ -83,6 +369,67 @@ pub struct RunArgs {
     /// Path to a GGUF model file.
     pub model: std::path::PathBuf,
 
+    #[command(flatten)]
+    pub load: LoadArgs,
+
+    #[command(flatten)]
+    pub sampling: SamplingArgs,
+
+    /// System prompt.
+    #[arg(long)]
+    pub system: Option<String>,
+
+    /// Continue a conversation saved with /save or --save-to; its
+    /// settings replace the sampling flags.
+    #[arg(long)]
+    pub resume: Option<std::path::PathBuf>,
+
+    /// Save the conversation to this file on exit, Ctrl-C included.
+    #[arg(long)]
+    pub save_to: Option<std::path::PathBuf>,
+}
+
+#[derive(Debug, clap::Args, Clone)]
+pub struct CompleteArgs {
+    /// Path to a GGUF model file.
+    #[arg(long)]
+    pub model: std::path::PathBuf,
+
+    /// The prompt; without it (or --file) the prompt is read from stdin.
+    pub prompt: Option<String>,
+
+    /// Read the prompt from this file, `-` for stdin.
+    #[arg(long, conflicts_with = "prompt")]
+    pub file: Option<std::path::PathBuf>,
+
+    /// Send the prompt as a user message through the chat template,
+    /// even for a model without one.  The default for models with one.
+    #[arg(long)]
+    pub chat: bool,
+
+    /// Send the prompt as is, without the chat template.
+    #[arg(long, conflicts_with_all = ["chat", "system"])]
+    pub raw: bool,
+
+    /// System prompt for the chat template.
+    #[arg(long)]
+    pub system: Option<String>,
+
+    /// Print nothing while generating, then one JSON object with the
+    /// text, usage, finish reason and timings.
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub load: LoadArgs,
+
+    #[command(flatten)]
+    pub sampling: SamplingArgs,
+}
+
+/// Model and context flags shared by `run`, `complete` and `embed`.
+#[derive(Debug, clap::Args, Clone)]
+pub struct LoadArgs {
     /// Context size (0 = model default).
     #[arg(long, default_value_t = 0)]
     pub ctx_size: u32,
@@ -91,17 +438,182 @@ pub struct RunArgs {
     #[arg(long, default_value_t = -1)]
     pub n_gpu_layers: i32,
 
+    #[command(flatten)]
+    pub gpu_split: GpuSplitArgs,
+
+    #[command(flatten)]
+    pub rope: RopeArgs,
+
+    /// NUMA strategy: disabled, distribute, isolate, numactl or mirror.
+    #[arg(long, value_parser = parse_numa)]
+    pub numa: Option<llama_core::NumaStrategy>,
+
+    /// Flash attention: on or off; a bare `--flash-attn` means on.
+    /// Unset lets llama.cpp decide per backend.
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "on",
+        value_parser = parse_flash_attn
+    )]
+    pub flash_attn: Option<bool>,
+
+    /// K cache type, e.g. q8_0 (default: f16).
+    #[arg(long = "cache-type-k", value_parser = parse_cache_type)]
+    pub cache_type_k: Option<llama_core::KvCacheType>,
+
+    /// V cache type, e.g. q8_0 (default: f16); quantized types need
+    /// flash attention.
+    #[arg(long = "cache-type-v", value_parser = parse_cache_type)]
+    pub cache_type_v: Option<llama_core::KvCacheType>,
+
     /// Threads.
     #[arg(long)]
     pub threads: Option<i32>,
+}
 
-    /// Temperature.
+/// Sampling and generation flags shared by `run` and `complete`.
+#[derive(Debug, clap::Args, Clone)]
+pub struct SamplingArgs {
+    /// Temperature; 0 samples greedily.
     #[arg(long, default_value_t = 0.8)]
     pub temp: f32,
 
-    /// System prompt.
+    /// Top-p (nucleus) sampling; 1 disables it (default: 0.95).
     #[arg(long)]
-    pub system: Option<String>,
+    pub top_p: Option<f32>,
+
+    /// Top-k sampling; 0 disables it (default: 40).
+    #[arg(long)]
+    pub top_k: Option<i32>,
+
+    /// Min-p sampling; 0 disables it (default: 0.05).
+    #[arg(long)]
+    pub min_p: Option<f32>,
+
+    /// Repetition penalty; 1 disables it (default: 1.1).
+    #[arg(long)]
+    pub repeat_penalty: Option<f32>,
+
+    /// Sampling seed (default: a new one every reply).
+    #[arg(long)]
+    pub seed: Option<u32>,
+
+    /// Most tokens per reply.
+    #[arg(long, default_value_t = 2048)]
+    pub max_tokens: u32,
+
+    /// Stop a reply at this text (can be repeated).
+    #[arg(long = "stop")]
+    pub stop: Vec<String>,
+
+    /// Keep generating past end-of-generation tokens, up to
+    /// --max-tokens.
+    #[arg(long)]
+    pub ignore_eos: bool,
+}
+
+#[derive(Debug, clap::Args, Clone)]
+pub struct BenchArgs {
+    /// Path to a GGUF model file.
+    pub model: std::path::PathBuf,
+
+    /// Context size (must fit `--pp` + `--tg`).
+    #[arg(long, default_value_t = 4096)]
+    pub ctx_size: u32,
+
+    /// GPU layers (-1 = all); a comma-separated list sweeps several values.
+    #[arg(
+        long,
+        default_value = "-1",
+        value_delimiter = ',',
+        allow_hyphen_values = true
+    )]
+    pub n_gpu_layers: Vec<i32>,
+
+    /// Prompt tokens for the prompt-processing test.
+    #[arg(long, default_value_t = 512)]
+    pub pp: u32,
+
+    /// Tokens for the generation test.
+    #[arg(long, default_value_t = 128)]
+    pub tg: u32,
+
+    /// Repetitions per configuration.
+    #[arg(long, default_value_t = 3)]
+    pub repeat: u32,
+
+    /// Threads.
+    #[arg(long)]
+    pub threads: Option<i32>,
+
+    /// Print results as JSON instead of a table.
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, clap::Args, Clone)]
+pub struct EmbedArgs {
+    /// Path to a GGUF embedding model.
+    #[arg(long)]
+    pub model: std::path::PathBuf,
+
+    /// File to embed as one input (can be repeated).  Without any, each
+    /// non-empty line on stdin is an input.
+    #[arg(long = "input")]
+    pub inputs: Vec<std::path::PathBuf>,
+
+    /// Output format: json, ndjson (one object per input) or csv.
+    #[arg(long, default_value = "json", value_parser = parse_embed_format)]
+    pub format: embed::EmbedFormat,
+
+    /// Scale vectors to unit length, as /v1/embeddings does (the default).
+    #[arg(long, overrides_with = "no_normalize")]
+    pub normalize: bool,
+
+    /// Write the vectors as the model produced them.
+    #[arg(long, overrides_with = "normalize")]
+    pub no_normalize: bool,
+
+    /// Cut inputs longer than the context to fit instead of failing.
+    #[arg(long)]
+    pub truncate: bool,
+
+    #[command(flatten)]
+    pub load: LoadArgs,
+}
+
+#[derive(Debug, clap::Args, Clone)]
+pub struct TokenizeArgs {
+    /// Path to a GGUF model file; only its vocabulary is loaded.
+    #[arg(long)]
+    pub model: std::path::PathBuf,
+
+    /// Text to tokenize, or token ids with --detokenize.
+    #[arg(required_unless_present = "file")]
+    pub text: Option<String>,
+
+    /// Read the input from this file instead, `-` for stdin.
+    #[arg(long, conflicts_with = "text")]
+    pub file: Option<std::path::PathBuf>,
+
+    /// Turn token ids (separated by commas or spaces) back into text.
+    #[arg(long)]
+    pub detokenize: bool,
+
+    /// Print only the number of tokens.
+    #[arg(long, conflicts_with = "detokenize")]
+    pub count_only: bool,
+
+    /// Don't add BOS and other special tokens the model asks for, like
+    /// `add_special: false` on /tokenize.
+    #[arg(long)]
+    pub no_bos: bool,
+
+    /// Turn special-token text such as `<|im_start|>` into its token,
+    /// like `parse_special: true` on /tokenize.
+    #[arg(long)]
+    pub parse_special: bool,
 }
 
 #[derive(Debug, clap::Args)]
@@ -114,17 +626,76 @@ pub struct ModelsArgs {
 pub enum ModelsAction {
     /// List available models.
     List {
-        /// Directory to scan (overrides config).
+        /// Directory to scan, can be repeated (default: --models-dir,
+        /// then `model_dirs` from config.json).
+        #[arg(long)]
+        dir: Vec<std::path::PathBuf>,
+        /// Print the full entries as JSON instead of a table.
+        #[arg(long)]
+        json: bool,
+        /// Order by name, quant, size (largest first) or ctx (longest
+        /// first).
+        #[arg(long, default_value = "name", value_parser = parse_model_sort)]
+        sort: crate::cli::models::ModelSort,
+        /// Only models whose name, path, architecture or quantization
+        /// contains this text, ignoring case.
         #[arg(long)]
-        dir: Option<std::path::PathBuf>,
+        filter: Option<String>,
     },
     /// Show detailed info about a GGUF file.
     Info {
         /// Path to the GGUF file.
         path: std::path::PathBuf,
+        /// List the tensors (name, shape, type and size) and count them
+        /// by type instead; with --json, add them to the info.
+        #[arg(long)]
+        tensors: bool,
+        /// Context size for the memory estimate (default: trained length).
+        #[arg(long)]
+        ctx_size: Option<u32>,
+        /// Offloaded layers for the memory estimate (-1 for all).
+        #[arg(long, default_value_t = 0, allow_hyphen_values = true)]
+        n_gpu_layers: i32,
+        /// Print metadata values type-tagged (`{"Uint32": 32768}`) instead
+        /// of as plain JSON.
+        #[arg(long)]
+        raw: bool,
+        /// Print JSON even with --tensors or --key.
+        #[arg(long)]
+        json: bool,
+        /// Print just this metadata value, e.g. `general.name`; exits
+        /// with an error when the file doesn't have it.
+        #[arg(long, conflicts_with = "tensors")]
+        key: Option<String>,
+    },
+    /// Compute a GGUF file's SHA-256, optionally checking it.
+    Verify {
+        /// Path to the GGUF file.
+        path: std::path::PathBuf,
+        /// Expected SHA-256; exits with an error on a mismatch.
+        #[arg(long, value_name = "SHA256")]
+        expect: Option<String>,
     },
 }
 
+#[derive(Debug, clap::Args)]
+pub struct PullArgs {
+    /// Hugging Face repository, e.g. `Qwen/Qwen2.5-7B-Instruct-GGUF`.
+    pub repo: String,
+
+    /// Only consider files whose name contains this (e.g. `q4_k_m`).
+    #[arg(long)]
+    pub quant: Option<String>,
+
+    /// Target directory (default: first model directory).
+    #[arg(long)]
+    pub dir: Option<std::path::PathBuf>,
+
+    /// Branch, tag or commit to download from.
+    #[arg(long, default_value = "main")]
+    pub revision: String,
+}
+
 #[derive(Debug, clap::Args)]
 pub struct ConfigArgs {
     #[command(subcommand)]
@@ -134,7 +705,12 @@ pub struct ConfigArgs {
 #[derive(Debug, Subcommand)]
 pub enum ConfigAction {
     /// Display the current configuration.
-    Show,
-    /// Set a configuration value.
-    Set { key: String, value: String },
+    Show {
+        /// Print the API key instead of masking it.
+        #[arg(long)]
+        show_secrets: bool,
+    },
+    /// Set a configuration value.  List keys take `.add <value>`,
+    /// `.remove <value>` or `.clear`, e.g. `model_dirs.add ~/models`.
+    Set { key: String, value: Option<String> },
 }
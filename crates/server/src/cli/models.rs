@@ -1,43 +1,353 @@
-use crate::cli::ModelsArgs;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::path::PathBuf;
 
-pub async fn execute(args: ModelsArgs) -> anyhow::Result<()> {
-    match args.action {
-        crate::cli::ModelsAction::List { dir } => {
-            let search_dir = dir.unwrap_or_else(|| std::path::PathBuf::from("."));
-            if !search_dir.is_dir() {
-                anyhow::bail!("{} is not a directory", search_dir.display());
-            }
+use gguf_parser::ModelEntry;
 
-            let entries =
-                gguf_parser::scan_directory(&search_dir).map_err(|e| anyhow::anyhow!("{e}"))?;
+use crate::cli::{GlobalArgs, ModelsArgs};
+use crate::config::AppConfig;
 
-            if entries.is_empty() {
-                println!("No GGUF models found in {}", search_dir.display());
+/// How `models list` orders its rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelSort {
+    /// Largest first.
+    Size,
+    Name,
+    /// By quantization name, unknown last.
+    Quant,
+    /// Longest context first, unknown last.
+    Ctx,
+}
+
+pub async fn execute(global: GlobalArgs, args: ModelsArgs) -> anyhow::Result<()> {
+    match args.action {
+        crate::cli::ModelsAction::List {
+            dir,
+            json,
+            sort,
+            filter,
+        } => list(global, dir, json, sort, filter.as_deref())?,
+        crate::cli::ModelsAction::Info {
+            path,
+            tensors,
+            ctx_size,
+            n_gpu_layers,
+            raw,
+            json,
+            key,
+        } => {
+            let scan = gguf_parser::full_scan(&path).map_err(|e| anyhow::anyhow!("{e}"))?;
+            if let Some(key) = key {
+                let value = scan.get(&key).ok_or_else(|| {
+                    anyhow::anyhow!("{} has no metadata key '{key}'", path.display())
+                })?;
+                let value = if raw {
+                    serde_json::to_value(value)?
+                } else {
+                    serde_json::Value::from(value)
+                };
+                // Strings bare, for shell scripts
+                match value {
+                    serde_json::Value::String(s) if !json => println!("{s}"),
+                    value => println!("{value}"),
+                }
+                return Ok(());
+            }
+            if tensors && !json {
+                let tensors =
+                    gguf_parser::scan_tensors(&path).map_err(|e| anyhow::anyhow!("{e}"))?;
+                print!("{}", tensor_table(&tensors, terminal_width()));
+                let summary: Vec<_> = type_summary(&tensors)
+                    .iter()
+                    .map(|t| format!("{} × {}", t.count, t.ggml_type))
+                    .collect();
+                println!("\n{} tensors: {}", tensors.len(), summary.join(", "));
                 return Ok(());
             }
 
-            println!("{:<40} {:<12} {:<10} {:<8}", "Name", "Quant", "Size", "Ctx");
-            println!("{}", "-".repeat(74));
-            for entry in &entries {
-                let size = human_size(entry.file_size);
-                let quant = entry.quantization.as_deref().unwrap_or("-");
-                let ctx = entry
-                    .context_length
-                    .map(|c| format!("{c}"))
-                    .unwrap_or_else(|| "-".into());
-                println!("{:<40} {:<12} {:<10} {:<8}", entry.name, quant, size, ctx);
+            let mut info = serde_json::to_value(&scan)?;
+            if !raw {
+                info["metadata"] = scan.metadata_json().into();
+            }
+            let shape = gguf_parser::ContextShape {
+                n_ctx: ctx_size.unwrap_or(0),
+                n_gpu_layers,
+                ..Default::default()
+            };
+            info["memory_estimate"] =
+                serde_json::to_value(gguf_parser::estimate_memory(&scan, &shape))?;
+            if tensors {
+                let tensors =
+                    gguf_parser::scan_tensors(&path).map_err(|e| anyhow::anyhow!("{e}"))?;
+                info["tensors"] = tensors
+                    .iter()
+                    .map(|t| {
+                        serde_json::json!({
+                            "name": t.name,
+                            "shape": &t.dims[..t.n_dims as usize],
+                            "type": gguf_parser::ggml_type_name(t.ggml_type),
+                            "offset": t.offset,
+                            "size": gguf_parser::ggml_tensor_bytes(t),
+                        })
+                    })
+                    .collect();
+                info["tensor_types"] = serde_json::to_value(type_summary(&tensors))?;
             }
-            println!("\n{} model(s) found.", entries.len());
+            println!("{}", serde_json::to_string_pretty(&info)?);
         }
-        crate::cli::ModelsAction::Info { path } => {
-            let scan = gguf_parser::quick_scan(&path).map_err(|e| anyhow::anyhow!("{e}"))?;
-            println!("{}", serde_json::to_string_pretty(&scan)?);
+        crate::cli::ModelsAction::Verify { path, expect } => {
+            if let Some(hash) = &expect
+                && !gguf_parser::is_sha256_hex(hash)
+            {
+                anyhow::bail!("--expect must be a SHA-256: 64 hex digits");
+            }
+            let size = std::fs::metadata(&path)?.len();
+            let bar = indicatif::ProgressBar::new(size);
+            bar.set_style(
+                indicatif::ProgressStyle::with_template(
+                    "hashing [{bar:30}] {bytes}/{total_bytes} {bytes_per_sec} eta {eta}",
+                )
+                .expect("valid progress template")
+                .progress_chars("=> "),
+            );
+            let sha256 = gguf_parser::hash_file_with_progress(&path, |done| bar.set_position(done));
+            bar.finish_and_clear();
+            let sha256 = sha256.map_err(|e| anyhow::anyhow!("{e}"))?;
+
+            println!("{sha256}  {}", path.display());
+            if let Some(expected) = expect {
+                if !expected.eq_ignore_ascii_case(&sha256) {
+                    anyhow::bail!("SHA-256 mismatch: expected {}", expected.to_lowercase());
+                }
+                println!("OK");
+            }
         }
     }
     Ok(())
 }
 
-fn human_size(bytes: u64) -> String {
+fn list(
+    global: GlobalArgs,
+    dirs: Vec<PathBuf>,
+    json: bool,
+    sort: ModelSort,
+    filter: Option<&str>,
+) -> anyhow::Result<()> {
+    let cfg = AppConfig::load_or_default(global.config.as_deref())?;
+    let dirs = [dirs, global.models_dirs, cfg.model_dirs]
+        .into_iter()
+        .find(|dirs| !dirs.is_empty())
+        .ok_or_else(|| {
+            anyhow::anyhow!("no model directories configured; pass --dir or set model_dirs")
+        })?;
+    let walk = gguf_parser::WalkOptions {
+        follow_dir_symlinks: cfg.follow_dir_symlinks,
+    };
+
+    // Directories may overlap; list each model once, as the server does
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for dir in &dirs {
+        if !dir.is_dir() {
+            eprintln!("Warning: {} is not a directory", dir.display());
+            continue;
+        }
+        let found = gguf_parser::scan_directory_cached(dir, walk, &mut gguf_parser::NoScanCache)
+            .map_err(|e| anyhow::anyhow!("{}: {e}", dir.display()))?;
+        entries.extend(
+            found
+                .into_iter()
+                .filter(|e| seen.insert(e.canonical_path.clone())),
+        );
+    }
+    if let Some(filter) = filter {
+        entries.retain(|e| matches_filter(e, filter));
+    }
+    sort_entries(&mut entries, sort);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        let dirs: Vec<_> = dirs.iter().map(|d| d.display().to_string()).collect();
+        println!("No GGUF models found in {}", dirs.join(", "));
+        return Ok(());
+    }
+
+    print!("{}", render_table(&entries, terminal_width()));
+    println!("\n{} model(s) found.", entries.len());
+    Ok(())
+}
+
+/// Whether `filter` appears in the entry's name, path, architecture or
+/// quantization, ignoring case.
+fn matches_filter(entry: &ModelEntry, filter: &str) -> bool {
+    let filter = filter.to_lowercase();
+    let path = entry.path.to_string_lossy();
+    [
+        Some(entry.name.as_str()),
+        Some(path.as_ref()),
+        entry.architecture.as_deref(),
+        entry.quantization.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .any(|field| field.to_lowercase().contains(&filter))
+}
+
+/// Order `entries` by `sort`, ties by name.
+fn sort_entries(entries: &mut [ModelEntry], sort: ModelSort) {
+    let name = |e: &ModelEntry| (e.name.to_lowercase(), e.path.clone());
+    match sort {
+        ModelSort::Name => entries.sort_by_cached_key(name),
+        ModelSort::Size => entries.sort_by_cached_key(|e| (Reverse(e.file_size), name(e))),
+        ModelSort::Quant => entries.sort_by_cached_key(|e| {
+            let quant = e.quantization.as_ref().map(|q| q.to_ascii_uppercase());
+            (quant.is_none(), quant, name(e))
+        }),
+        ModelSort::Ctx => entries.sort_by_cached_key(|e| {
+            (
+                e.context_length.is_none(),
+                Reverse(e.context_length),
+                name(e),
+            )
+        }),
+    }
+}
+
+/// Size on disk; split models add their part count, and how many of the
+/// parts are present when some are missing.
+fn size_label(entry: &ModelEntry) -> String {
+    let size = human_size(entry.file_size);
+    if !entry.is_split {
+        return size;
+    }
+    let present = entry.split_parts.len();
+    let total = present + entry.missing_parts.len();
+    if present < total {
+        format!("{size} ({present}/{total} parts)")
+    } else {
+        format!("{size} ({total} parts)")
+    }
+}
+
+/// The `models list` table.
+fn render_table(entries: &[ModelEntry], width: Option<usize>) -> String {
+    let rows: Vec<[String; 5]> = entries
+        .iter()
+        .map(|e| {
+            [
+                e.name.clone(),
+                e.parameters.clone().unwrap_or_else(|| "-".into()),
+                e.quantization.clone().unwrap_or_else(|| "-".into()),
+                size_label(e),
+                e.context_length
+                    .map_or_else(|| "-".into(), |c| c.to_string()),
+            ]
+        })
+        .collect();
+    format_table(["Name", "Params", "Quant", "Size", "Ctx"], &rows, width)
+}
+
+/// Tensors of one ggml type in a file.
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+struct TypeCount {
+    #[serde(rename = "type")]
+    ggml_type: &'static str,
+    count: usize,
+    /// Summed data size; unknown types count 0.
+    bytes: u64,
+}
+
+/// Tensor counts by type, the most common first.
+fn type_summary(tensors: &[gguf_parser::TensorInfo]) -> Vec<TypeCount> {
+    let mut summary: Vec<TypeCount> = Vec::new();
+    for t in tensors {
+        let ggml_type = gguf_parser::ggml_type_name(t.ggml_type);
+        let bytes = gguf_parser::ggml_tensor_bytes(t).unwrap_or(0);
+        match summary.iter_mut().find(|c| c.ggml_type == ggml_type) {
+            Some(c) => {
+                c.count += 1;
+                c.bytes += bytes;
+            }
+            None => summary.push(TypeCount {
+                ggml_type,
+                count: 1,
+                bytes,
+            }),
+        }
+    }
+    summary.sort_by(|a, b| b.count.cmp(&a.count).then(a.ggml_type.cmp(b.ggml_type)));
+    summary
+}
+
+/// The `models info --tensors` table.
+fn tensor_table(tensors: &[gguf_parser::TensorInfo], width: Option<usize>) -> String {
+    let rows: Vec<[String; 4]> = tensors
+        .iter()
+        .map(|t| {
+            let shape: Vec<_> = t.dims[..t.n_dims as usize]
+                .iter()
+                .map(u64::to_string)
+                .collect();
+            [
+                t.name.clone(),
+                shape.join(" × "),
+                gguf_parser::ggml_type_name(t.ggml_type).into(),
+                gguf_parser::ggml_tensor_bytes(t).map_or_else(|| "-".into(), human_size),
+            ]
+        })
+        .collect();
+    format_table(["Name", "Shape", "Type", "Size"], &rows, width)
+}
+
+fn terminal_width() -> Option<usize> {
+    console::Term::stdout()
+        .size_checked()
+        .map(|(_, cols)| cols as usize)
+}
+
+/// Narrowest the first column gets before the table overflows instead.
+const MIN_NAME_WIDTH: usize = 16;
+
+/// Lay out `rows` under `headers`.  Every column is as wide as its
+/// widest cell; with a terminal `width`, the first column (names) is cut
+/// to make the table fit.
+fn format_table<const N: usize>(
+    headers: [&str; N],
+    rows: &[[String; N]],
+    width: Option<usize>,
+) -> String {
+    let mut widths = headers.map(console::measure_text_width);
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(console::measure_text_width(cell));
+        }
+    }
+    if let Some(width) = width {
+        let others: usize = widths[1..].iter().map(|w| w + 1).sum();
+        widths[0] = widths[0].min(width.saturating_sub(others).max(MIN_NAME_WIDTH));
+    }
+
+    let line = |cells: [&str; N]| {
+        let cells: Vec<_> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, w)| console::pad_str(cell, w, console::Alignment::Left, Some("…")))
+            .collect();
+        cells.join(" ").trim_end().to_string() + "\n"
+    };
+    let mut table = line(headers);
+    table += &"-".repeat(widths.iter().sum::<usize>() + widths.len() - 1);
+    table += "\n";
+    for row in rows {
+        table += &line(row.each_ref().map(String::as_str));
+    }
+    table
+}
+
+pub(crate) fn human_size(bytes: u64) -> String {
     const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
     let mut size = bytes as f64;
     for &unit in UNITS {
@@ -48,3 +358,160 @@ fn human_size(bytes: u64) -> String {
     }
     format!("{size:.1} PiB")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, quant: Option<&str>, size: u64, ctx: Option<u32>) -> ModelEntry {
+        serde_json::from_value(serde_json::json!({
+            "id": name.to_lowercase(),
+            "name": name,
+            "path": format!("/models/{name}.gguf"),
+            "file_size": size,
+            "architecture": "llama",
+            "quantization": quant,
+            "context_length": ctx,
+            "parameters": "7B",
+            "is_split": false,
+            "split_parts": [format!("/models/{name}.gguf")],
+            "mmproj_path": null,
+        }))
+        .unwrap()
+    }
+
+    fn names(entries: &[ModelEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    fn catalogue() -> Vec<ModelEntry> {
+        vec![
+            entry("mistral", Some("Q8_0"), 7 << 30, Some(32768)),
+            entry("Llama", Some("Q4_K_M"), 4 << 30, Some(8192)),
+            entry("broken", None, 1 << 20, None),
+            entry("gemma", Some("Q4_K_M"), 9 << 30, Some(8192)),
+        ]
+    }
+
+    #[test]
+    fn entries_sort_by_each_key_with_unknowns_last() {
+        let mut entries = catalogue();
+        sort_entries(&mut entries, ModelSort::Name);
+        assert_eq!(names(&entries), ["broken", "gemma", "Llama", "mistral"]);
+        sort_entries(&mut entries, ModelSort::Size);
+        assert_eq!(names(&entries), ["gemma", "mistral", "Llama", "broken"]);
+        sort_entries(&mut entries, ModelSort::Quant);
+        assert_eq!(names(&entries), ["gemma", "Llama", "mistral", "broken"]);
+        sort_entries(&mut entries, ModelSort::Ctx);
+        assert_eq!(names(&entries), ["mistral", "gemma", "Llama", "broken"]);
+    }
+
+    #[test]
+    fn filter_matches_any_field_ignoring_case() {
+        let entries = catalogue();
+        let matching = |filter| {
+            let kept: Vec<_> = entries
+                .iter()
+                .filter(|e| matches_filter(e, filter))
+                .cloned()
+                .collect();
+            names(&kept).join(",")
+        };
+        assert_eq!(matching("LLAMA"), "mistral,Llama,broken,gemma");
+        assert_eq!(matching("q4_k"), "Llama,gemma");
+        assert_eq!(matching("/models/gem"), "gemma");
+        assert_eq!(matching("phi"), "");
+    }
+
+    #[test]
+    fn split_models_show_their_parts() {
+        let mut e = entry("big", Some("Q4_K_M"), 3 << 30, None);
+        assert_eq!(size_label(&e), "3.0 GiB");
+        e.is_split = true;
+        e.split_parts = vec!["a".into(), "b".into(), "c".into()];
+        assert_eq!(size_label(&e), "3.0 GiB (3 parts)");
+        e.split_parts.pop();
+        e.missing_parts = vec![3];
+        assert_eq!(size_label(&e), "3.0 GiB (2/3 parts)");
+    }
+
+    fn tensor(name: &str, dims: &[u64], ggml_type: u32) -> gguf_parser::TensorInfo {
+        let mut shape = [1; 4];
+        shape[..dims.len()].copy_from_slice(dims);
+        gguf_parser::TensorInfo {
+            name: name.into(),
+            n_dims: dims.len() as u32,
+            dims: shape,
+            ggml_type,
+            offset: 0,
+        }
+    }
+
+    #[test]
+    fn tensors_are_listed_and_counted_by_type() {
+        let tensors = [
+            tensor("token_embd.weight", &[4096, 32000], 14),
+            tensor("blk.0.attn_q.weight", &[4096, 4096], 12),
+            tensor("blk.0.attn_k.weight", &[4096, 1024], 12),
+            tensor("blk.0.attn_norm.weight", &[4096], 0),
+            tensor("blk.0.odd", &[4096], 1000),
+        ];
+        let summary = type_summary(&tensors);
+        let counts: Vec<_> = summary.iter().map(|t| (t.ggml_type, t.count)).collect();
+        assert_eq!(
+            counts,
+            [("Q4_K", 2), ("F32", 1), ("Q6_K", 1), ("unknown", 1)]
+        );
+        assert_eq!(summary[0].bytes, (4096 * 4096 + 4096 * 1024) / 256 * 144);
+        assert_eq!(summary[3].bytes, 0);
+
+        let table = tensor_table(&tensors, None);
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines[0], "Name                   Shape        Type    Size");
+        assert_eq!(
+            lines[2],
+            "token_embd.weight      4096 × 32000 Q6_K    102.5 MiB"
+        );
+        assert_eq!(
+            lines[5],
+            "blk.0.attn_norm.weight 4096         F32     16.0 KiB"
+        );
+        assert_eq!(lines[6], "blk.0.odd              4096         unknown -");
+    }
+
+    #[test]
+    fn table_columns_fit_their_cells_and_the_terminal() {
+        let mut entries = catalogue();
+        entries[0].name = "Meta-Llama-3.1-8B-Instruct-abliterated".into();
+        entries.truncate(2);
+
+        let table = render_table(&entries, None);
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "Name                                   Params Quant  Size    Ctx",
+                "------------------------------------------------------------------",
+                "Meta-Llama-3.1-8B-Instruct-abliterated 7B     Q8_0   7.0 GiB 32768",
+                "Llama                                  7B     Q4_K_M 4.0 GiB 8192",
+            ]
+        );
+
+        // Names give way to a narrow terminal, down to a minimum
+        let table = render_table(&entries, Some(50));
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(
+            lines[2],
+            "Meta-Llama-3.1-8B-Ins… 7B     Q8_0   7.0 GiB 32768"
+        );
+        assert!(lines.iter().all(|l| console::measure_text_width(l) <= 50));
+        let table = render_table(&entries, Some(10));
+        assert!(
+            table
+                .lines()
+                .nth(2)
+                .unwrap()
+                .starts_with("Meta-Llama-3.1-… ")
+        );
+    }
+}
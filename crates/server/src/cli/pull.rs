@@ -0,0 +1,252 @@
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::cli::models::human_size;
+use crate::cli::{GlobalArgs, PullArgs};
+use crate::config::AppConfig;
+use crate::services::downloads::{self, DownloadError};
+
+/// Exit codes for scripts driving `pull`.
+mod exit {
+    pub const OTHER: i32 = 1;
+    pub const NOT_FOUND: i32 = 2;
+    pub const NETWORK: i32 = 3;
+    pub const DISK_FULL: i32 = 4;
+}
+
+#[derive(Debug, thiserror::Error)]
+enum PullError {
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error(transparent)]
+    Download(#[from] DownloadError),
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl PullError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Self::NotFound(_) => exit::NOT_FOUND,
+            Self::Download(e) if e.is_not_found() => exit::NOT_FOUND,
+            Self::Download(e) if e.is_disk_full() => exit::DISK_FULL,
+            Self::Download(DownloadError::Http(_) | DownloadError::Status(_)) => exit::NETWORK,
+            _ => exit::OTHER,
+        }
+    }
+}
+
+pub async fn execute(global: GlobalArgs, args: PullArgs) -> anyhow::Result<()> {
+    if let Err(e) = pull(global, args).await {
+        eprintln!("Error: {e}");
+        std::process::exit(e.exit_code());
+    }
+    Ok(())
+}
+
+async fn pull(global: GlobalArgs, args: PullArgs) -> Result<(), PullError> {
+    let dir = match args.dir {
+        Some(dir) => dir,
+        None => global
+            .models_dirs
+            .first()
+            .cloned()
+            .or_else(|| {
+                AppConfig::load_or_default(global.config.as_deref())
+                    .ok()
+                    .and_then(|cfg| cfg.model_dirs.first().cloned())
+            })
+            .unwrap_or_else(|| PathBuf::from(".")),
+    };
+    std::fs::create_dir_all(&dir).map_err(DownloadError::from)?;
+
+    let client = downloads::hf_client()?;
+    let files = downloads::hf_repo_files(&client, &args.repo, &args.revision).await?;
+    let candidates = gguf_candidates(&files, args.quant.as_deref());
+    let file = match candidates.as_slice() {
+        [] => {
+            return Err(PullError::NotFound(match &args.quant {
+                Some(q) => format!("No GGUF file matching '{q}' in {}", args.repo),
+                None => format!("No GGUF files in {}", args.repo),
+            }));
+        }
+        [file] => file.clone(),
+        _ => pick(&candidates)?,
+    };
+
+    // Split models are fetched part by part, in order.
+    let (subdir, name) = match file.rsplit_once('/') {
+        Some((subdir, name)) => (format!("{subdir}/"), name),
+        None => (String::new(), file.as_str()),
+    };
+    let names = gguf_parser::split_part_names(name).unwrap_or_else(|| vec![name.to_string()]);
+
+    let mut first = None;
+    for name in &names {
+        let dest = dir.join(name);
+        first.get_or_insert_with(|| dest.clone());
+        if dest.exists() {
+            println!("{name} already downloaded, skipping");
+            continue;
+        }
+        let url = downloads::hf_file_url(&args.repo, &args.revision, &format!("{subdir}{name}"));
+        fetch_with_progress(&client, &url, &dest, name).await?;
+    }
+
+    if let Some(path) = first {
+        print_summary(&path)?;
+    }
+    Ok(())
+}
+
+/// GGUF files in `files` whose name contains `quant` (case-insensitive).
+///
+/// Split models are represented by their first part only.
+fn gguf_candidates(files: &[String], quant: Option<&str>) -> Vec<String> {
+    let quant = quant.map(str::to_lowercase);
+    files
+        .iter()
+        .filter(|f| f.to_lowercase().ends_with(".gguf"))
+        .filter(|f| {
+            let name = f.rsplit('/').next().unwrap_or(f);
+            gguf_parser::split_part_names(name).is_none_or(|parts| parts[0] == name)
+        })
+        .filter(|f| quant.as_ref().is_none_or(|q| f.to_lowercase().contains(q)))
+        .cloned()
+        .collect()
+}
+
+/// Ask the user to choose one of `candidates`.
+fn pick(candidates: &[String]) -> Result<String, PullError> {
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!(
+            "{} files match; narrow it down with --quant:\n  {}",
+            candidates.len(),
+            candidates.join("\n  ")
+        )
+        .into());
+    }
+
+    for (i, file) in candidates.iter().enumerate() {
+        println!("{:>3}) {file}", i + 1);
+    }
+    let stdin = std::io::stdin();
+    loop {
+        print!("Select a file [1-{}]: ", candidates.len());
+        std::io::stdout().flush().map_err(anyhow::Error::from)?;
+        let mut line = String::new();
+        if stdin
+            .lock()
+            .read_line(&mut line)
+            .map_err(anyhow::Error::from)?
+            == 0
+        {
+            return Err(anyhow::anyhow!("No file selected").into());
+        }
+        match line.trim().parse::<usize>() {
+            Ok(n) if (1..=candidates.len()).contains(&n) => return Ok(candidates[n - 1].clone()),
+            _ => println!("Please enter a number between 1 and {}", candidates.len()),
+        }
+    }
+}
+
+async fn fetch_with_progress(
+    client: &reqwest::Client,
+    url: &str,
+    dest: &Path,
+    name: &str,
+) -> Result<(), DownloadError> {
+    let bar = ProgressBar::new(0).with_message(name.to_string());
+    bar.set_style(
+        ProgressStyle::with_template(
+            "{msg} [{bar:30}] {bytes}/{total_bytes} {bytes_per_sec} eta {eta}",
+        )
+        .expect("valid progress template")
+        .progress_chars("=> "),
+    );
+
+    let result = downloads::fetch(client, url, dest, |p| {
+        if let Some(total) = p.total_bytes {
+            bar.set_length(total);
+        }
+        bar.set_position(p.bytes_done);
+    })
+    .await;
+
+    match result {
+        Ok(_) => bar.finish(),
+        Err(_) => bar.abandon(),
+    }
+    result.map(|_| ())
+}
+
+fn print_summary(path: &Path) -> anyhow::Result<()> {
+    let scan = gguf_parser::quick_scan(path).map_err(|e| anyhow::anyhow!("{e}"))?;
+    println!();
+    println!("Saved to      {}", path.display());
+    println!("Name          {}", scan.name.as_deref().unwrap_or("-"));
+    println!(
+        "Architecture  {}",
+        scan.architecture.as_deref().unwrap_or("-")
+    );
+    println!(
+        "Quantization  {}",
+        scan.file_type_name.as_deref().unwrap_or("-")
+    );
+    println!(
+        "Context       {}",
+        scan.context_length
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| "-".into())
+    );
+    println!("Size          {}", human_size(scan.file_size));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn candidates_filter_by_quant() {
+        let files = files(&[
+            "README.md",
+            "model-Q4_K_M.gguf",
+            "model-Q8_0.gguf",
+            "mmproj-f16.gguf",
+        ]);
+        assert_eq!(
+            gguf_candidates(&files, Some("q4_k_m")),
+            vec!["model-Q4_K_M.gguf"]
+        );
+        assert_eq!(gguf_candidates(&files, None).len(), 3);
+        assert!(gguf_candidates(&files, Some("q2_k")).is_empty());
+    }
+
+    #[test]
+    fn candidates_collapse_split_parts() {
+        let files = files(&[
+            "Q8_0/model-q8_0-00001-of-00002.gguf",
+            "Q8_0/model-q8_0-00002-of-00002.gguf",
+        ]);
+        assert_eq!(
+            gguf_candidates(&files, Some("q8_0")),
+            vec!["Q8_0/model-q8_0-00001-of-00002.gguf"]
+        );
+        assert_eq!(
+            gguf_parser::split_part_names("model-q8_0-00002-of-00002.gguf").unwrap(),
+            vec![
+                "model-q8_0-00001-of-00002.gguf",
+                "model-q8_0-00002-of-00002.gguf"
+            ]
+        );
+    }
+}
@@ -1,57 +1,68 @@
 use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
-use tokio::sync::mpsc;
+use anyhow::Context as _;
 use tracing::info;
 
 use crate::cli::RunArgs;
+use crate::cli::generation::{Settings, load_model, render_prompt, stream_completion};
 
 pub async fn execute(args: RunArgs) -> anyhow::Result<()> {
-    let _backend = llama_core::LlamaBackend::init();
-
     info!(model = %args.model.display(), "Loading model for interactive chat…");
-
-    let model_params = llama_core::ModelParams {
-        n_gpu_layers: args.n_gpu_layers,
-        ..Default::default()
-    };
-    let model = Arc::new(llama_core::LlamaModel::load_from_file(
-        &args.model,
-        &model_params,
-    )?);
-
-    let n_threads = args.threads.unwrap_or_else(|| {
-        std::thread::available_parallelism()
-            .map(|n| n.get() as i32)
-            .unwrap_or(4)
-    });
-
-    let ctx_params = llama_core::ContextParams {
-        n_ctx: args.ctx_size,
-        n_threads,
-        n_threads_batch: n_threads,
-        ..Default::default()
-    };
-    let ctx = llama_core::LlamaContext::new(model.clone(), &ctx_params)?;
+    let (model, ctx) = load_model(&args.model, &args.load)?;
     let ctx = Arc::new(Mutex::new(ctx));
 
-    let template = model.chat_template();
     let system_msg = args
         .system
         .as_deref()
         .unwrap_or("You are a helpful assistant.");
+    let mut conversation = Conversation::new(system_msg);
+    let mut settings = Settings::from_args(&args.sampling);
 
-    let mut history: Vec<llama_core::ChatMessage> = vec![llama_core::ChatMessage {
-        role: "system".into(),
-        content: system_msg.into(),
-    }];
+    // Prompt tokens of the last turn, which the KV cache still holds
+    let mut cached: Vec<i32> = Vec::new();
 
-    println!("Model loaded. Type your message (Ctrl-D to quit).\n");
+    if let Some(path) = &args.resume {
+        let saved = SavedConversation::read(path)?;
+        resume(
+            &model,
+            &ctx,
+            &args.model,
+            saved,
+            &mut conversation,
+            &mut settings,
+            &mut cached,
+        )
+        .await?;
+    }
+
+    // What --save-to gets on exit, including Ctrl-C mid-turn: the
+    // conversation as of the last prompt
+    let snapshot = Arc::new(Mutex::new(SavedConversation::new(
+        &args.model,
+        &conversation,
+        &settings,
+    )));
+    if let Some(path) = args.save_to.clone() {
+        let snapshot = snapshot.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                println!();
+                save_or_warn(&snapshot.lock().unwrap(), &path);
+                std::process::exit(130);
+            }
+        });
+    }
+
+    println!("Model loaded. Type your message (/help for commands, Ctrl-D to quit).\n");
 
     let stdin = io::stdin();
     let mut stdout = io::stdout();
 
     loop {
+        *snapshot.lock().unwrap() = SavedConversation::new(&args.model, &conversation, &settings);
+
         print!("> ");
         stdout.flush()?;
 
@@ -65,81 +76,547 @@ pub async fn execute(args: RunArgs) -> anyhow::Result<()> {
             continue;
         }
 
-        history.push(llama_core::ChatMessage {
-            role: "user".into(),
-            content: line.to_string(),
-        });
-
-        // Apply chat template
-        let prompt = llama_core::apply_template(template.as_deref(), &history, true)
-            .unwrap_or_else(|| {
-                // Fallback: simple concatenation
-                history
-                    .iter()
-                    .map(|m| format!("{}: {}", m.role, m.content))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-                    + "\nassistant:"
-            });
-
-        let tokens = llama_core::tokenize(model.vocab(), &prompt, true, true)?;
-
-        let sampling = llama_core::SamplingParams {
-            temperature: args.temp,
-            ..Default::default()
-        };
-
-        let request = llama_core::GenerateRequest {
-            tokens,
-            max_tokens: 2048,
-            stop_words: vec![],
-            sampling_params: sampling,
-        };
-
-        let (tx, mut rx) = mpsc::channel(64);
-
-        // Move the Arc<Mutex<Context>> into the blocking task
-        let ctx_clone = ctx.clone();
-        tokio::task::spawn_blocking(move || {
-            let mut ctx_guard = ctx_clone.lock().unwrap();
-            ctx_guard.kv_cache_clear();
-            llama_core::generate::generate_blocking(&mut ctx_guard, &request, tx);
-        });
-
-        let mut assistant_reply = String::new();
-
-        while let Some(event) = rx.recv().await {
-            match event {
-                llama_core::GenerateEvent::Token(piece) => {
-                    print!("{piece}");
-                    stdout.flush()?;
-                    assistant_reply.push_str(&piece);
+        match parse_command(line) {
+            None => conversation.push("user", line),
+            Some(Err(e)) => {
+                eprintln!("{e}\n");
+                print_help();
+                continue;
+            }
+            Some(Ok(command)) => match command {
+                Command::Quit => break,
+                Command::Help => {
+                    print_help();
+                    continue;
                 }
-                llama_core::GenerateEvent::Done {
-                    finish_reason,
-                    prompt_tokens,
-                    completion_tokens,
-                } => {
-                    println!();
-                    eprintln!(
-                        "  [{finish_reason} | prompt: {prompt_tokens} tok, gen: {completion_tokens} tok]"
-                    );
-                    break;
+                Command::Clear => {
+                    conversation.clear();
+                    cached.clear();
+                    ctx.lock().unwrap().kv_cache_clear();
+                    println!("Conversation cleared.\n");
+                    continue;
                 }
-                llama_core::GenerateEvent::Error(e) => {
-                    eprintln!("\nError: {e}");
-                    break;
+                Command::System(text) => {
+                    conversation.set_system(&text);
+                    println!("System prompt set.\n");
+                    continue;
                 }
-            }
+                Command::Undo => {
+                    if conversation.undo() {
+                        println!("Removed the last exchange.\n");
+                    } else {
+                        println!("Nothing to undo.\n");
+                    }
+                    continue;
+                }
+                // The prompt is rebuilt from history on every turn, so the
+                // reply's tokens left in the KV cache past `cached` are
+                // dropped by `reuse_kv_prefix`; popping the message is
+                // enough
+                Command::Retry => {
+                    if !conversation.retract_reply() {
+                        println!("Nothing to retry.\n");
+                        continue;
+                    }
+                }
+                Command::Set(key, value) => {
+                    match settings.set(&key, &value) {
+                        Ok(()) => println!("{settings}"),
+                        Err(e) => eprintln!("{e}\n"),
+                    }
+                    continue;
+                }
+                Command::Settings => {
+                    println!("{settings}");
+                    continue;
+                }
+                Command::Stats => {
+                    print_stats(&ctx.lock().unwrap(), &conversation, cached.len());
+                    continue;
+                }
+                Command::Save(path) => {
+                    let saved = SavedConversation::new(&args.model, &conversation, &settings);
+                    match saved.write(&path) {
+                        Ok(()) => println!("Saved to {}.\n", path.display()),
+                        Err(e) => eprintln!("Error: {e:#}\n"),
+                    }
+                    continue;
+                }
+                Command::Load(path) => {
+                    match SavedConversation::read(&path) {
+                        Ok(saved) => {
+                            resume(
+                                &model,
+                                &ctx,
+                                &args.model,
+                                saved,
+                                &mut conversation,
+                                &mut settings,
+                                &mut cached,
+                            )
+                            .await?
+                        }
+                        Err(e) => eprintln!("Error: {e:#}\n"),
+                    }
+                    continue;
+                }
+            },
         }
 
-        history.push(llama_core::ChatMessage {
-            role: "assistant".into(),
-            content: assistant_reply,
-        });
+        let reply = generate_reply(&model, &ctx, &settings, &conversation, &mut cached).await?;
+        conversation.push("assistant", &reply);
 
         println!();
     }
 
+    if let Some(path) = &args.save_to {
+        save_or_warn(
+            &SavedConversation::new(&args.model, &conversation, &settings),
+            path,
+        );
+    }
+
     Ok(())
 }
+
+fn save_or_warn(saved: &SavedConversation, path: &Path) {
+    match saved.write(path) {
+        Ok(()) => eprintln!("Conversation saved to {}.", path.display()),
+        Err(e) => eprintln!("Error: {e:#}"),
+    }
+}
+
+/// Switch to a saved conversation and its settings, warning when it was
+/// held with another model, and decode its history into the KV cache so
+/// the next turn starts right away.
+async fn resume(
+    model: &Arc<llama_core::LlamaModel>,
+    ctx: &Arc<Mutex<llama_core::LlamaContext>>,
+    model_path: &Path,
+    saved: SavedConversation,
+    conversation: &mut Conversation,
+    settings: &mut Settings,
+    cached: &mut Vec<i32>,
+) -> anyhow::Result<()> {
+    if !same_file(&saved.model, model_path) {
+        eprintln!(
+            "Warning: this conversation was held with {}, not {}.",
+            saved.model.display(),
+            model_path.display()
+        );
+    }
+    *conversation = saved.conversation();
+    *settings = saved.settings;
+    println!("Restored {} messages.", conversation.messages.len() - 1);
+
+    let prompt = render_prompt(model, &conversation.messages, false);
+    let tokens = model.vocab().tokenize_prompt(&prompt)?;
+    cached.clear();
+    let n_ctx = ctx.lock().unwrap().n_ctx() as usize;
+    if tokens.len() >= n_ctx {
+        ctx.lock().unwrap().kv_cache_clear();
+        eprintln!(
+            "Warning: the history is {} tokens, more than the {n_ctx} token context; \
+             use /undo or /clear to make room.\n",
+            tokens.len()
+        );
+        return Ok(());
+    }
+
+    let ctx = ctx.clone();
+    *cached = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<i32>> {
+        prime_kv_cache(&mut ctx.lock().unwrap(), &tokens)?;
+        Ok(tokens)
+    })
+    .await??;
+    println!();
+    Ok(())
+}
+
+/// Decode `tokens` into an empty KV cache in `n_batch` sized chunks,
+/// showing progress when there is more than one.
+fn prime_kv_cache(ctx: &mut llama_core::LlamaContext, tokens: &[i32]) -> anyhow::Result<()> {
+    ctx.kv_cache_clear();
+    let n_batch = ctx.n_batch().max(1) as usize;
+    let show_progress = tokens.len() > n_batch;
+    let mut batch = llama_core::LlamaBatch::new(n_batch as i32, 0, 1);
+    for (i, chunk) in tokens.chunks(n_batch).enumerate() {
+        batch.clear();
+        batch.add_sequence(chunk, 0, (i * n_batch) as i32, true)?;
+        ctx.decode(&mut batch)?;
+        if show_progress {
+            eprint!(
+                "\rRestoring context: {} / {} tokens",
+                i * n_batch + chunk.len(),
+                tokens.len()
+            );
+        }
+    }
+    if show_progress {
+        eprintln!();
+    }
+    Ok(())
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Generate and print the assistant's reply to `conversation`, reusing the
+/// KV cache for the part of the prompt `cached` shares with it.
+async fn generate_reply(
+    model: &Arc<llama_core::LlamaModel>,
+    ctx: &Arc<Mutex<llama_core::LlamaContext>>,
+    settings: &Settings,
+    conversation: &Conversation,
+    cached: &mut Vec<i32>,
+) -> anyhow::Result<String> {
+    let prompt = render_prompt(model, &conversation.messages, true);
+    let tokens = model.vocab().tokenize_prompt(&prompt)?;
+
+    let mut stdout = io::stdout();
+    let previous = std::mem::take(cached);
+    let completion = stream_completion(ctx, settings.request(tokens.clone()), previous, |piece| {
+        print!("{piece}");
+        stdout.flush()
+    })
+    .await?;
+    println!();
+
+    match &completion.outcome {
+        Ok(finish) => {
+            *cached = tokens;
+            eprintln!(
+                "  [{} | prompt: {} tok ({} reused, {} new), gen: {} tok]",
+                finish.reason,
+                finish.prompt_tokens,
+                completion.n_past,
+                finish.prompt_tokens as usize - completion.n_past,
+                finish.completion_tokens
+            );
+        }
+        // The cache may hold anything now; `cached` stays empty so the
+        // next turn starts over
+        Err(e) => eprintln!("Error: {e}"),
+    }
+    Ok(completion.text)
+}
+
+fn print_help() {
+    println!(
+        "Commands:
+  /clear          start a new conversation (alias /reset)
+  /system <text>  replace the system prompt
+  /retry          regenerate the last reply
+  /undo           remove the last message and its reply
+  /stats          show context usage and the last turn's speed
+  /set <name> <v> change a setting: temp, top-p, top-k, min-p,
+                  repeat-penalty, seed (or random), max-tokens,
+                  stop (adds one; none clears), ignore-eos (on/off)
+  /settings       show the current settings
+  /save <file>    write the conversation and settings to a JSON file
+  /load <file>    continue a conversation saved with /save
+  /help           show this help
+  /quit           leave (also Ctrl-D)
+"
+    );
+}
+
+fn print_stats(ctx: &llama_core::LlamaContext, conversation: &Conversation, cached: usize) {
+    let used = ctx.kv_cache_used();
+    let n_ctx = ctx.n_ctx();
+    let perf = ctx.perf();
+    println!(
+        "Context: {used} / {n_ctx} tokens ({:.1}%), {cached} cached prompt tokens",
+        used as f64 * 100.0 / n_ctx.max(1) as f64
+    );
+    println!("Messages: {}", conversation.messages.len() - 1);
+    println!(
+        "Last turn: prompt {} tok at {:.1} tok/s, gen {} tok at {:.1} tok/s\n",
+        perf.n_p_eval,
+        perf.prompt_tokens_per_sec(),
+        perf.n_eval,
+        perf.generation_tokens_per_sec()
+    );
+}
+
+//  Commands
+
+/// A `/command` typed at the prompt.
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Clear,
+    System(String),
+    Retry,
+    Undo,
+    Stats,
+    Set(String, String),
+    Settings,
+    Save(PathBuf),
+    Load(PathBuf),
+    Help,
+    Quit,
+}
+
+/// Parse a line starting with `/`; `None` for ordinary messages.
+fn parse_command(line: &str) -> Option<Result<Command, String>> {
+    let rest = line.strip_prefix('/')?;
+    let (name, arg) = match rest.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (rest, ""),
+    };
+    let needs_arg = |what: &str| {
+        if arg.is_empty() {
+            Err(format!("/{name} needs {what}"))
+        } else {
+            Ok(arg)
+        }
+    };
+    let no_arg = |command: Command| {
+        if arg.is_empty() {
+            Ok(command)
+        } else {
+            Err(format!("/{name} takes no argument"))
+        }
+    };
+    Some(match name {
+        "clear" | "reset" => no_arg(Command::Clear),
+        "system" => needs_arg("the new system prompt").map(|a| Command::System(a.into())),
+        "retry" => no_arg(Command::Retry),
+        "undo" => no_arg(Command::Undo),
+        "stats" => no_arg(Command::Stats),
+        "set" => match arg.split_once(char::is_whitespace) {
+            Some((key, value)) => Ok(Command::Set(key.into(), value.trim_start().into())),
+            None => Err("/set needs a setting and a value, e.g. /set temp 0.2".into()),
+        },
+        "settings" => no_arg(Command::Settings),
+        "save" => needs_arg("a file name").map(|a| Command::Save(a.into())),
+        "load" => needs_arg("a file name").map(|a| Command::Load(a.into())),
+        "help" | "?" => no_arg(Command::Help),
+        "quit" | "exit" => no_arg(Command::Quit),
+        _ => Err(format!("Unknown command /{name}")),
+    })
+}
+
+//  Conversation
+
+/// Chat history; the first message is always the system prompt.
+#[derive(Debug)]
+struct Conversation {
+    messages: Vec<llama_core::ChatMessage>,
+}
+
+impl Conversation {
+    fn new(system: &str) -> Self {
+        Self {
+            messages: vec![message("system", system)],
+        }
+    }
+
+    fn push(&mut self, role: &str, content: &str) {
+        self.messages.push(message(role, content));
+    }
+
+    /// Drop everything but the system prompt.
+    fn clear(&mut self) {
+        self.messages.truncate(1);
+    }
+
+    fn set_system(&mut self, system: &str) {
+        self.messages[0].content = system.into();
+    }
+
+    /// Remove the last reply, leaving its user message to answer again.
+    fn retract_reply(&mut self) -> bool {
+        let n = self.messages.len();
+        if n < 3 || self.messages[n - 1].role != "assistant" || self.messages[n - 2].role != "user"
+        {
+            return false;
+        }
+        self.messages.pop();
+        true
+    }
+
+    /// Remove the last user message and whatever followed it.
+    fn undo(&mut self) -> bool {
+        match self.messages.iter().rposition(|m| m.role == "user") {
+            Some(i) if i > 0 => {
+                self.messages.truncate(i);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A conversation as `/save` and `--save-to` write it.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct SavedConversation {
+    /// The model it was held with, as given on the command line.
+    model: PathBuf,
+    system: String,
+    settings: Settings,
+    /// Everything after the system prompt.
+    messages: Vec<llama_core::ChatMessage>,
+}
+
+impl SavedConversation {
+    fn new(model: &Path, conversation: &Conversation, settings: &Settings) -> Self {
+        Self {
+            model: model.to_path_buf(),
+            system: conversation.messages[0].content.clone(),
+            settings: settings.clone(),
+            messages: conversation.messages[1..].to_vec(),
+        }
+    }
+
+    fn conversation(&self) -> Conversation {
+        let mut conversation = Conversation::new(&self.system);
+        conversation.messages.extend(self.messages.iter().cloned());
+        conversation
+    }
+
+    fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n")
+            .with_context(|| format!("cannot write {}", path.display()))
+    }
+
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("{} is not a saved conversation", path.display()))
+    }
+}
+
+fn message(role: &str, content: &str) -> llama_core::ChatMessage {
+    llama_core::ChatMessage {
+        role: role.into(),
+        content: content.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roles(conversation: &Conversation) -> Vec<&str> {
+        conversation
+            .messages
+            .iter()
+            .map(|m| m.role.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn commands_are_parsed() {
+        assert_eq!(parse_command("hello /clear"), None);
+        assert_eq!(parse_command("/clear"), Some(Ok(Command::Clear)));
+        assert_eq!(parse_command("/reset"), Some(Ok(Command::Clear)));
+        assert_eq!(
+            parse_command("/system  Be brief. "),
+            Some(Ok(Command::System("Be brief.".into())))
+        );
+        assert_eq!(
+            parse_command("/save chat one.json"),
+            Some(Ok(Command::Save("chat one.json".into())))
+        );
+        assert_eq!(parse_command("/quit"), Some(Ok(Command::Quit)));
+        assert_eq!(
+            parse_command("/set stop User: "),
+            Some(Ok(Command::Set("stop".into(), "User:".into())))
+        );
+        assert_eq!(parse_command("/settings"), Some(Ok(Command::Settings)));
+
+        assert!(matches!(parse_command("/system"), Some(Err(_))));
+        assert!(matches!(parse_command("/load "), Some(Err(_))));
+        assert!(matches!(parse_command("/undo 2"), Some(Err(_))));
+        assert!(matches!(parse_command("/set temp"), Some(Err(_))));
+        assert!(matches!(parse_command("/frobnicate"), Some(Err(_))));
+    }
+
+    fn run_args(flags: &[&str]) -> RunArgs {
+        use clap::Parser;
+
+        let argv = ["llama-dashboard", "run", "model.gguf"];
+        let cli = crate::cli::Cli::try_parse_from(argv.iter().chain(flags)).unwrap();
+        match cli.command {
+            Some(crate::cli::Commands::Run(args)) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn retry_and_undo_edit_the_history() {
+        let mut c = Conversation::new("sys");
+        assert!(!c.retract_reply());
+        assert!(!c.undo());
+
+        c.push("user", "a");
+        c.push("assistant", "A");
+        c.push("user", "b");
+        c.push("assistant", "B");
+
+        assert!(c.retract_reply());
+        assert_eq!(roles(&c), ["system", "user", "assistant", "user"]);
+        // Nothing to retract until the user message is answered again
+        assert!(!c.retract_reply());
+
+        assert!(c.undo());
+        assert_eq!(roles(&c), ["system", "user", "assistant"]);
+        assert!(c.undo());
+        assert_eq!(roles(&c), ["system"]);
+        assert!(!c.undo());
+
+        c.push("user", "c");
+        c.set_system("new");
+        c.clear();
+        assert_eq!(roles(&c), ["system"]);
+        assert_eq!(c.messages[0].content, "new");
+    }
+
+    #[test]
+    fn conversations_round_trip_through_files() {
+        let dir = std::env::temp_dir().join(format!("llama-run-chat-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("chat.json");
+
+        let mut c = Conversation::new("sys");
+        c.push("user", "hi");
+        c.push("assistant", "hello");
+        let mut settings = Settings::from_args(&run_args(&["--stop=User:"]).sampling);
+        settings.set("temp", "0.3").unwrap();
+        settings.set("seed", "9").unwrap();
+        SavedConversation::new(Path::new("models/a.gguf"), &c, &settings)
+            .write(&path)
+            .unwrap();
+
+        let saved = SavedConversation::read(&path).unwrap();
+        assert_eq!(saved.model, Path::new("models/a.gguf"));
+        assert_eq!(saved.system, "sys");
+        assert_eq!(saved.messages.len(), 2);
+        assert_eq!(format!("{}", saved.settings), format!("{settings}"));
+        let restored = saved.conversation();
+        assert_eq!(roles(&restored), ["system", "user", "assistant"]);
+        assert_eq!(restored.messages[0].content, "sys");
+        assert_eq!(restored.messages[2].content, "hello");
+
+        // The system prompt is kept apart from the history in the file
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["system"], "sys");
+        assert_eq!(json["messages"][0]["role"], "user");
+        assert_eq!(json["settings"]["stop"][0], "User:");
+
+        std::fs::write(&path, r#"[{"role":"user","content":"hi"}]"#).unwrap();
+        assert!(SavedConversation::read(&path).is_err());
+        std::fs::write(&path, "not json").unwrap();
+        assert!(SavedConversation::read(&path).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
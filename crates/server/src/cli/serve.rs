@@ -1,23 +1,66 @@
+use std::future::{Future, IntoFuture};
 use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::Duration;
 
-use axum::Router;
+use axum::http::{HeaderMap, Uri, header, uri::Authority};
+use axum::response::Redirect;
 use tower_http::cors::{Any, CorsLayer};
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::cli::{GlobalArgs, ServeArgs};
 use crate::config::AppConfig;
 use crate::db::Database;
 use crate::routes;
-use crate::services::model_manager::{ModelManager, ModelManagerConfig, spawn_idle_checker};
-use crate::state::AppState;
+use crate::services::chat_retention::spawn_chat_retention;
+use crate::services::model_manager::{
+    ModelManager, ModelManagerConfig, ModelSettings, format_bytes, spawn_idle_checker,
+    spawn_rescanner,
+};
+use crate::services::request_log::{RequestLog, spawn_request_log_writer};
+use crate::services::resources::spawn_metrics_sampler;
+use crate::services::stats::{StatsRegistry, spawn_stats_flusher};
+use crate::services::tls::{TlsPaths, TlsReloader, spawn_sighup_reloader};
+use crate::state::{AppState, ShutdownSignal, shutdown_requested};
 
 pub async fn execute(global: GlobalArgs, serve_args: ServeArgs) -> anyhow::Result<()> {
-    let _backend = llama_core::LlamaBackend::init();
+    #[cfg(not(unix))]
+    if serve_args.uds.is_some() {
+        anyhow::bail!("--uds is not supported on this platform (Unix domain sockets need Unix)");
+    }
+
+    let backend = llama_core::LlamaBackend::init();
+    // llama.cpp output goes through `tracing`, and so to the dashboard
+    backend.set_log_callback();
+
+    //  Devices
+    let devices = backend.gpu_devices();
+    if devices.is_empty() {
+        info!("No GPU devices found; running on CPU");
+    }
+    for dev in &devices {
+        info!(
+            name = dev.name,
+            backend = dev.backend,
+            total = %format_bytes(dev.total_bytes),
+            free = %format_bytes(dev.free_bytes),
+            "GPU device: {}",
+            dev.description
+        );
+    }
 
     //  Config / DB
-    let cfg = AppConfig::load_or_default()?;
+    let cfg = AppConfig::load_or_default(global.config.as_deref())?;
+    info!(file = %cfg.file.display(), "Configuration");
     let db = Database::open(&cfg.db_path())?;
 
+    // Must precede the first model load to have any effect
+    let numa = serve_args.numa.unwrap_or(cfg.numa);
+    backend.numa_init(numa);
+    if numa != llama_core::NumaStrategy::Disabled {
+        info!(?numa, "NUMA strategy");
+    }
+
     //  Model manager
     let model_dirs: Vec<std::path::PathBuf> = if global.models_dirs.is_empty() {
         cfg.model_dirs.clone()
@@ -27,22 +70,40 @@ pub async fn execute(global: GlobalArgs, serve_args: ServeArgs) -> anyhow::Resul
 
     let mm_config = ModelManagerConfig {
         max_models: serve_args.max_models,
+        max_memory_bytes: serve_args.max_memory.unwrap_or(cfg.max_memory_bytes),
         idle_timeout_secs: serve_args.idle_timeout,
         default_n_gpu_layers: serve_args.n_gpu_layers,
+        default_split_mode: serve_args.gpu_split.split_mode,
+        default_main_gpu: serve_args.gpu_split.main_gpu,
+        default_tensor_split: serve_args.gpu_split.tensor_split.clone(),
+        default_rope: serve_args.rope.settings(),
+        default_flash_attn: serve_args.flash_attn,
+        default_type_k: serve_args.cache_type_k,
+        default_type_v: serve_args.cache_type_v,
         default_ctx_size: serve_args.ctx_size,
+        parallel: serve_args.parallel,
+        defrag_thold: serve_args.defrag_thold,
+        allow_external_paths: cfg.allow_external_paths,
+        follow_dir_symlinks: cfg.follow_dir_symlinks,
+        pinned_models: cfg.pinned_models.clone(),
     };
     let model_manager = ModelManager::new(model_dirs, mm_config);
+    model_manager.set_aliases(db.list_aliases()?);
+
+    model_manager.set_display_names(db.list_display_names()?);
+    model_manager.set_all_settings(db.list_model_settings()?);
+
+    let stats = StatsRegistry::new();
+    stats.set_all(db.list_model_stats()?);
+
+    let (request_log, request_log_rx) = RequestLog::new(cfg.request_log_enabled, cfg.log_prompts);
 
     //  Pre-load model if specified
     if let Some(model_path) = &serve_args.model {
-        let model_params = llama_core::ModelParams {
-            n_gpu_layers: serve_args.n_gpu_layers,
-            ..Default::default()
-        };
-        let ctx_params = llama_core::ContextParams {
-            n_ctx: serve_args.ctx_size,
-            ..Default::default()
-        };
+        let id = model_manager.model_id_for(model_path);
+        let (model_params, ctx_params) = model_manager
+            .effective_settings(&id, ModelSettings::default())
+            .load_params();
         model_manager
             .load(model_path, &model_params, &ctx_params)
             .map_err(|e| anyhow::anyhow!("Failed to pre-load model: {e}"))?;
@@ -53,12 +114,114 @@ pub async fn execute(global: GlobalArgs, serve_args: ServeArgs) -> anyhow::Resul
         cfg.clone(),
         db,
         model_manager.clone(),
-        global.api_key.clone(),
+        stats,
+        request_log,
+        global.api_key.clone().or_else(|| cfg.api_key.clone()),
     );
 
+    state.api_keys().set_all(state.db().active_api_keys()?);
+
+    //  Log lines → WebSocket events
+    let log_level = cfg.log_stream_level.parse().unwrap_or_else(|_| {
+        warn!(
+            "Invalid log_stream_level '{}'; streaming info and above",
+            cfg.log_stream_level
+        );
+        tracing::level_filters::LevelFilter::INFO
+    });
+    crate::services::logs::capture().attach(state.events().clone(), log_level);
+
+    //  Load progress → WebSocket events
+    let mut progress_rx = model_manager.subscribe_progress();
+    let progress_state = state.clone();
+    tokio::spawn(async move {
+        use tokio::sync::broadcast::error::RecvError;
+        loop {
+            match progress_rx.recv().await {
+                Ok(p) => progress_state.broadcast_event(
+                    "model.loading.progress",
+                    serde_json::json!({ "id": p.id, "progress": p.progress }),
+                ),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+
     //  Idle checker background task
-    let shutdown_rx = state.event_tx().subscribe();
-    spawn_idle_checker(model_manager, serve_args.idle_timeout, shutdown_rx);
+    let shutdown_rx = state.shutdown_signal();
+    spawn_idle_checker(model_manager.clone(), serve_args.idle_timeout, shutdown_rx);
+
+    //  Host resource samples → WebSocket events
+    if cfg.system_metrics_enabled {
+        let shutdown_rx = state.shutdown_signal();
+        let metrics_state = state.clone();
+        spawn_metrics_sampler(
+            state.resources().clone(),
+            model_manager.clone(),
+            cfg.system_metrics_interval_secs,
+            shutdown_rx,
+            move |snapshot| {
+                metrics_state.broadcast_event("system.metrics", serde_json::json!(snapshot));
+            },
+        );
+    }
+
+    //  Usage stats → SQLite
+    let shutdown_rx = state.shutdown_signal();
+    let stats_state = state.clone();
+    spawn_stats_flusher(
+        state.stats().clone(),
+        cfg.stats_flush_interval_secs,
+        shutdown_rx,
+        move |rows| stats_state.db().upsert_model_stats(rows),
+    );
+
+    //  Request log → SQLite
+    // Stopped only after the drain, so cancelled requests still get logged.
+    let (stop_writers, writers_stop_rx) = tokio::sync::watch::channel(false);
+    let request_log_writer = request_log_rx.map(|rx| {
+        let (persist_state, prune_state) = (state.clone(), state.clone());
+        spawn_request_log_writer(
+            rx,
+            cfg.request_log_retention_days,
+            writers_stop_rx,
+            move |entries| persist_state.db().insert_request_log(entries),
+            move |before| prune_state.db().prune_request_log(before),
+        )
+    });
+
+    //  Chat retention
+    let shutdown_rx = state.shutdown_signal();
+    let (prune_state, event_state) = (state.clone(), state.clone());
+    spawn_chat_retention(
+        cfg.chat_retention_days,
+        shutdown_rx,
+        move |before| prune_state.db().prune_chat_sessions(Some(before), None),
+        move |deleted| {
+            event_state.broadcast_event(
+                "chat.sessions_changed",
+                serde_json::json!({ "reason": "retention", "deleted": deleted }),
+            );
+        },
+    );
+
+    //  Model directory rescans → WebSocket events
+    let shutdown_rx = state.shutdown_signal();
+    let rescan_state = state.clone();
+    spawn_rescanner(
+        model_manager.clone(),
+        cfg.rescan_interval_secs,
+        shutdown_rx,
+        move |diff| {
+            for entry in diff.removed {
+                rescan_state.broadcast_event("model.removed", serde_json::json!(entry));
+            }
+            for entry in diff.discovered {
+                rescan_state.broadcast_event("model.discovered", serde_json::json!(entry));
+            }
+        },
+    );
 
     //  Router
     let cors = CorsLayer::new()
@@ -66,21 +229,201 @@ pub async fn execute(global: GlobalArgs, serve_args: ServeArgs) -> anyhow::Resul
         .allow_methods(Any)
         .allow_headers(Any);
 
-    let app = Router::new()
-        .merge(routes::health::router())
-        .merge(routes::openai::router())
-        .merge(routes::native::router())
-        .merge(routes::management::router())
-        .merge(routes::ws::router())
-        .merge(routes::spa::router())
-        .layer(cors)
-        .with_state(state);
+    let router = routes::app(state.clone()).layer(cors);
+    let app = router
+        .clone()
+        .into_make_service_with_connect_info::<SocketAddr>();
 
     let addr: SocketAddr = format!("{}:{}", global.host, global.port).parse()?;
-    info!(%addr, "Starting server");
+    let drain_timeout = Duration::from_secs(serve_args.shutdown_timeout);
+    let signal_state = state.clone();
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        info!(
+            timeout_secs = drain_timeout.as_secs(),
+            "Shutting down; draining in-flight requests"
+        );
+        signal_state.begin_shutdown(drain_timeout);
+    });
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    //  Unix socket; the guard removes the file once the server is done
+    #[cfg(unix)]
+    let mut uds_server = None;
+    #[cfg(unix)]
+    let _uds_file = match &serve_args.uds {
+        Some(path) => {
+            let (listener, file) = crate::services::uds::bind(path, serve_args.uds_mode)
+                .map_err(|e| anyhow::anyhow!("Cannot bind {}: {e}", path.display()))?;
+            info!(path = %path.display(), "Listening on Unix socket");
+            let mut shutdown_rx = state.shutdown_signal();
+            let server = axum::serve(listener, router.into_make_service())
+                .with_graceful_shutdown(async move {
+                    shutdown_requested(&mut shutdown_rx).await;
+                })
+                .into_future();
+            uds_server = Some(server);
+            Some(file)
+        }
+        None => None,
+    };
+
+    //  TLS
+    let tls_paths = TlsPaths::from_options(
+        serve_args.tls_cert.or_else(|| cfg.tls_cert.clone()),
+        serve_args.tls_key.or_else(|| cfg.tls_key.clone()),
+    )?;
+    let server: Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>> = match tls_paths {
+        #[cfg(unix)]
+        _ if serve_args.uds_only => {
+            if tls_paths.is_some() {
+                warn!("--uds-only serves plain HTTP on the socket; ignoring the TLS certificate");
+            }
+            Box::pin(uds_server.take().expect("--uds-only requires --uds"))
+        }
+        Some(paths) => {
+            let reloader = TlsReloader::new(paths)?;
+            state.set_tls(reloader.clone());
+            spawn_sighup_reloader(reloader.clone(), state.shutdown_signal());
+            if let Some(port) = serve_args.http_redirect_port.or(cfg.http_redirect_port) {
+                spawn_https_redirect(addr, port, state.shutdown_signal()).await?;
+            }
+
+            info!(%addr, "Starting server (HTTPS)");
+            let handle = axum_server::Handle::new();
+            let stop = handle.clone();
+            let mut shutdown_rx = state.shutdown_signal();
+            tokio::spawn(async move {
+                shutdown_requested(&mut shutdown_rx).await;
+                stop.graceful_shutdown(None);
+            });
+            Box::pin(
+                axum_server::bind_rustls(addr, reloader.rustls_config())
+                    .handle(handle)
+                    .serve(app),
+            )
+        }
+        None => {
+            if serve_args.http_redirect_port.is_some() {
+                warn!("--http-redirect-port needs --tls-cert and --tls-key; ignoring it");
+            }
+            info!(%addr, "Starting server");
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            let mut shutdown_rx = state.shutdown_signal();
+            Box::pin(
+                axum::serve(listener, app)
+                    .with_graceful_shutdown(async move {
+                        shutdown_requested(&mut shutdown_rx).await;
+                    })
+                    .into_future(),
+            )
+        }
+    };
+    #[cfg(unix)]
+    if let Some(uds_server) = uds_server {
+        tokio::spawn(async move {
+            if let Err(e) = uds_server.await {
+                warn!("Unix socket server failed: {e}");
+            }
+        });
+    }
+
+    //  Drain, bounded by --shutdown-timeout once the signal arrives
+    let mut shutdown_rx = state.shutdown_signal();
+    tokio::select! {
+        result = server => result?,
+        _ = async {
+            shutdown_requested(&mut shutdown_rx).await;
+            tokio::time::sleep(drain_timeout).await;
+        } => {
+            warn!("In-flight requests did not finish in time; closing them");
+        }
+    }
+
+    //  Persist state
+    let dirty = state.stats().take_dirty();
+    if !dirty.is_empty()
+        && let Err(e) = state.db().upsert_model_stats(&dirty)
+    {
+        warn!("Failed to persist model stats: {e}");
+    }
+    stop_writers.send_replace(true);
+    if let Some(writer) = request_log_writer
+        && tokio::time::timeout(drain_timeout, writer).await.is_err()
+    {
+        warn!("Timed out flushing the request log");
+    }
+
+    model_manager.unload_all();
+    info!("Shutdown complete");
 
     Ok(())
 }
+
+/// Listen for plain HTTP on `port` (same host as `https_addr`) and
+/// redirect every request to the HTTPS listener.
+async fn spawn_https_redirect(
+    https_addr: SocketAddr,
+    port: u16,
+    mut shutdown: ShutdownSignal,
+) -> anyhow::Result<()> {
+    let addr = SocketAddr::new(https_addr.ip(), port);
+    let https_port = https_addr.port();
+    let redirect = move |headers: HeaderMap, uri: Uri| async move {
+        let host = headers
+            .get(header::HOST)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.parse::<Authority>().ok())
+            .map(|a| a.host().to_string())
+            .unwrap_or_else(|| https_addr.ip().to_string());
+        let path = uri.path_and_query().map_or("/", |p| p.as_str());
+        let target = if https_port == 443 {
+            format!("https://{host}{path}")
+        } else {
+            format!("https://{host}:{https_port}{path}")
+        };
+        Redirect::permanent(&target)
+    };
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!(%addr, "Redirecting HTTP to HTTPS");
+    let app = axum::Router::new().fallback(redirect);
+    tokio::spawn(async move {
+        let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+            shutdown_requested(&mut shutdown).await;
+        });
+        if let Err(e) = server.await {
+            warn!("HTTP redirect listener failed: {e}");
+        }
+    });
+    Ok(())
+}
+
+/// Resolve on Ctrl-C, or SIGTERM on Unix.
+async fn wait_for_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Failed to listen for Ctrl-C: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
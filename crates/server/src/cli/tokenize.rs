@@ -0,0 +1,108 @@
+use std::io::Read;
+
+use crate::cli::TokenizeArgs;
+
+pub async fn execute(args: TokenizeArgs) -> anyhow::Result<()> {
+    let input = match &args.file {
+        Some(path) if path.as_os_str() == "-" => {
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input)?;
+            input
+        }
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("cannot read {}: {e}", path.display()))?,
+        None => args.text.clone().unwrap_or_default(),
+    };
+    // Parse ids before the model loads, so typos fail fast
+    let ids = if args.detokenize {
+        Some(parse_token_ids(&input)?)
+    } else {
+        None
+    };
+
+    let _backend = llama_core::LlamaBackend::init();
+    let model_params = llama_core::ModelParams {
+        n_gpu_layers: 0,
+        vocab_only: true,
+        ..Default::default()
+    };
+    let model = tokio::task::spawn_blocking({
+        let path = args.model.clone();
+        move || llama_core::LlamaModel::load_from_file(&path, &model_params)
+    })
+    .await??;
+    let vocab = model.vocab();
+
+    if let Some(ids) = ids {
+        let n_tokens = vocab.n_tokens();
+        if let Some(bad) = ids.iter().find(|&&id| id < 0 || id >= n_tokens) {
+            anyhow::bail!(
+                "token {bad} is not in the vocabulary (0 to {})",
+                n_tokens - 1
+            );
+        }
+        println!("{}", vocab.detokenize(&ids)?);
+        return Ok(());
+    }
+
+    // Same switches as POST /tokenize: add_special, parse_special
+    let tokens = vocab.tokenize(&input, !args.no_bos, args.parse_special)?;
+    if args.count_only {
+        println!("{}", tokens.len());
+        return Ok(());
+    }
+    for &token in &tokens {
+        println!(
+            "{}",
+            token_line(token, &vocab.token_to_piece_with(token, true))
+        );
+    }
+    Ok(())
+}
+
+/// Token ids separated by commas and/or whitespace; a JSON array such as
+/// `[1, 2, 3]` (the `tokens` of a /tokenize response) works too.
+fn parse_token_ids(input: &str) -> anyhow::Result<Vec<i32>> {
+    let input = input.trim();
+    let input = input
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .unwrap_or(input);
+    input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse()
+                .map_err(|_| anyhow::anyhow!("'{s}' is not a token id"))
+        })
+        .collect()
+}
+
+/// `id<TAB>"piece"`, the piece escaped so whitespace and control
+/// characters show.
+fn token_line(token: i32, piece: &str) -> String {
+    format!("{token}\t{piece:?}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_ids_parse_from_lists_and_arrays() {
+        assert_eq!(parse_token_ids("1,2,3").unwrap(), [1, 2, 3]);
+        assert_eq!(parse_token_ids(" 1, 2\n3 ").unwrap(), [1, 2, 3]);
+        assert_eq!(parse_token_ids("[128000, 9906]\n").unwrap(), [128000, 9906]);
+        assert!(parse_token_ids("").unwrap().is_empty());
+        assert!(parse_token_ids("1,two").is_err());
+        assert!(parse_token_ids("1.5").is_err());
+    }
+
+    #[test]
+    fn pieces_are_escaped() {
+        assert_eq!(token_line(9906, "Hello"), "9906\t\"Hello\"");
+        assert_eq!(token_line(198, "\n"), "198\t\"\\n\"");
+        assert_eq!(token_line(220, " \t"), "220\t\" \\t\"");
+        assert_eq!(token_line(5, "\"é\""), "5\t\"\\\"é\\\"\"");
+    }
+}
@@ -1,6 +1,6 @@
 //! Application configuration — persisted as JSON.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
@@ -24,9 +24,94 @@ pub struct AppConfig {
     /// Maximum concurrently loaded models (0 = unlimited).
     #[serde(default = "default_max_models")]
     pub max_models: usize,
+    /// Memory budget for loaded models in bytes (0 = unlimited).
+    #[serde(default)]
+    pub max_memory_bytes: u64,
     /// Idle timeout in seconds (0 = disabled).
     #[serde(default)]
     pub idle_timeout_secs: u64,
+    /// Load scanned models on demand when an API request names them.
+    #[serde(default = "default_true")]
+    pub auto_load_models: bool,
+    /// Seconds a request waits for a model that is still loading.
+    #[serde(default = "default_load_wait_timeout")]
+    pub load_wait_timeout_secs: u64,
+    /// Model ids kept resident regardless of LRU eviction and idle timeout.
+    #[serde(default)]
+    pub pinned_models: Vec<String>,
+    /// Allow loading model files outside `model_dirs` through the API.
+    #[serde(default)]
+    pub allow_external_paths: bool,
+    /// Descend into symlinked directories when scanning `model_dirs`.
+    #[serde(default)]
+    pub follow_dir_symlinks: bool,
+    /// Seconds between background rescans of `model_dirs` (0 = disabled).
+    #[serde(default = "default_rescan_interval")]
+    pub rescan_interval_secs: u64,
+    /// Broadcast `system.metrics` WebSocket events.
+    #[serde(default = "default_true")]
+    pub system_metrics_enabled: bool,
+    /// Seconds between `system.metrics` samples.
+    #[serde(default = "default_metrics_interval")]
+    pub system_metrics_interval_secs: u64,
+    /// Seconds between writes of per-model usage stats (0 = never persist).
+    #[serde(default = "default_stats_flush_interval")]
+    pub stats_flush_interval_secs: u64,
+    /// Record every generation request in the `request_log` table.
+    #[serde(default)]
+    pub request_log_enabled: bool,
+    /// Also store prompt text in the request log.
+    #[serde(default)]
+    pub log_prompts: bool,
+    /// Days of request log kept (0 = keep forever).
+    #[serde(default = "default_request_log_retention")]
+    pub request_log_retention_days: u64,
+    /// Delete chat sessions inactive for this many days (0 = keep forever).
+    #[serde(default)]
+    pub chat_retention_days: u64,
+    /// Requests per minute per API key, or per IP without one (0 = unlimited).
+    #[serde(default)]
+    pub rate_limit_per_minute: u32,
+    /// Model load/unload requests per minute per client (0 = unlimited).
+    #[serde(default)]
+    pub model_ops_rate_limit_per_minute: u32,
+    /// Largest accepted request body in bytes.  Chat imports get a
+    /// larger allowance of their own.
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    /// Longest a generation may take, queueing included (0 = unlimited).
+    /// Requests may ask for a shorter limit with `timeout`.
+    #[serde(default = "default_generation_timeout")]
+    pub generation_timeout_secs: u64,
+    /// gzip/brotli-compress responses for clients that accept it.  Event
+    /// streams and WebSocket upgrades are never compressed.
+    #[serde(default = "default_true")]
+    pub compression_enabled: bool,
+    /// PEM certificate chain; serve HTTPS when set together with `tls_key`.
+    #[serde(default)]
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key of `tls_cert`.
+    #[serde(default)]
+    pub tls_key: Option<PathBuf>,
+    /// With TLS, also listen for plain HTTP on this port and redirect it
+    /// to HTTPS.
+    #[serde(default)]
+    pub http_redirect_port: Option<u16>,
+    /// Lowest level (`error` … `trace`) of log lines streamed as
+    /// `logs.line` events and kept for `GET /api/logs`.  Lines the
+    /// `RUST_LOG` filter drops never get that far.
+    #[serde(default = "default_log_stream_level")]
+    pub log_stream_level: String,
+    /// NUMA placement, applied once at startup before any model loads.
+    #[serde(default)]
+    pub numa: llama_core::NumaStrategy,
+    /// SQLite database file; relative paths and the default `data.db`
+    /// are next to the configuration file.
+    #[serde(default)]
+    pub db_path: Option<PathBuf>,
+    /// File this configuration was loaded from and saves to.
+    #[serde(skip, default = "AppConfig::default_file")]
+    pub file: PathBuf,
 }
 
 fn default_host() -> String {
@@ -41,6 +126,33 @@ fn default_gpu_layers() -> i32 {
 fn default_max_models() -> usize {
     4
 }
+fn default_load_wait_timeout() -> u64 {
+    300
+}
+fn default_rescan_interval() -> u64 {
+    60
+}
+fn default_metrics_interval() -> u64 {
+    5
+}
+fn default_stats_flush_interval() -> u64 {
+    30
+}
+fn default_request_log_retention() -> u64 {
+    30
+}
+fn default_max_body_bytes() -> usize {
+    32 * 1024 * 1024
+}
+fn default_generation_timeout() -> u64 {
+    600
+}
+fn default_log_stream_level() -> String {
+    "info".into()
+}
+fn default_true() -> bool {
+    true
+}
 
 impl Default for AppConfig {
     fn default() -> Self {
@@ -52,7 +164,33 @@ impl Default for AppConfig {
             default_ctx_size: 0,
             default_n_gpu_layers: default_gpu_layers(),
             max_models: default_max_models(),
+            max_memory_bytes: 0,
             idle_timeout_secs: 0,
+            auto_load_models: true,
+            load_wait_timeout_secs: default_load_wait_timeout(),
+            pinned_models: Vec::new(),
+            allow_external_paths: false,
+            follow_dir_symlinks: false,
+            rescan_interval_secs: default_rescan_interval(),
+            system_metrics_enabled: true,
+            system_metrics_interval_secs: default_metrics_interval(),
+            stats_flush_interval_secs: default_stats_flush_interval(),
+            request_log_enabled: false,
+            log_prompts: false,
+            request_log_retention_days: default_request_log_retention(),
+            chat_retention_days: 0,
+            rate_limit_per_minute: 0,
+            model_ops_rate_limit_per_minute: 0,
+            max_body_bytes: default_max_body_bytes(),
+            generation_timeout_secs: default_generation_timeout(),
+            compression_enabled: true,
+            tls_cert: None,
+            tls_key: None,
+            http_redirect_port: None,
+            log_stream_level: default_log_stream_level(),
+            numa: llama_core::NumaStrategy::Disabled,
+            db_path: None,
+            file: Self::default_file(),
         }
     }
 }
@@ -65,31 +203,72 @@ impl AppConfig {
             .join("llama-dashboard")
     }
 
-    fn config_file() -> PathBuf {
+    /// `config.json` in [`Self::config_dir`], unless `--config` or
+    /// `LLAMA_CONFIG` names another.
+    pub fn default_file() -> PathBuf {
         Self::config_dir().join("config.json")
     }
 
     pub fn db_path(&self) -> PathBuf {
-        Self::config_dir().join("data.db")
+        let dir = self.file.parent().unwrap_or(Path::new("."));
+        dir.join(self.db_path.as_deref().unwrap_or(Path::new("data.db")))
     }
 
-    /// Load from disk, or return defaults if the file doesn't exist.
-    pub fn load_or_default() -> anyhow::Result<Self> {
-        let path = Self::config_file();
-        if path.exists() {
+    /// Load `file` (the default file when `None`) from disk, or return
+    /// defaults if it doesn't exist.  Either way the result saves there.
+    pub fn load_or_default(file: Option<&Path>) -> anyhow::Result<Self> {
+        let path = file.map_or_else(Self::default_file, Path::to_path_buf);
+        let mut cfg = if path.exists() {
             let data = std::fs::read_to_string(&path)?;
-            Ok(serde_json::from_str(&data)?)
+            serde_json::from_str(&data)
+                .map_err(|e| anyhow::anyhow!("invalid config {}: {e}", path.display()))?
         } else {
-            Ok(Self::default())
-        }
+            Self::default()
+        };
+        cfg.file = path;
+        Ok(cfg)
     }
 
     /// Persist to disk.
     pub fn save(&self) -> anyhow::Result<()> {
-        let dir = Self::config_dir();
-        std::fs::create_dir_all(&dir)?;
+        if let Some(dir) = self.file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
         let data = serde_json::to_string_pretty(self)?;
-        std::fs::write(Self::config_file(), data)?;
+        std::fs::write(&self.file, data)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn configs_side_by_side() {
+        let tmp = TempDir::new("llama-config");
+        let root = &tmp.0;
+        let (a, b) = (root.join("a/config.json"), root.join("etc/b.json"));
+
+        let mut first = AppConfig::load_or_default(Some(&a)).unwrap();
+        assert_eq!(first.port, 8080);
+        first.port = 8081;
+        first.save().unwrap();
+        let mut second = AppConfig::load_or_default(Some(&b)).unwrap();
+        second.port = 8082;
+        second.db_path = Some("b.db".into());
+        second.save().unwrap();
+
+        let first = AppConfig::load_or_default(Some(&a)).unwrap();
+        let second = AppConfig::load_or_default(Some(&b)).unwrap();
+        let saved = std::fs::read_to_string(&a).unwrap();
+
+        assert_eq!((first.port, second.port), (8081, 8082));
+        assert_eq!(first.file, a);
+        assert_eq!(first.db_path(), root.join("a/data.db"));
+        assert_eq!(second.db_path(), root.join("etc/b.db"));
+        // The file location is not itself part of the configuration
+        assert!(!saved.contains("\"file\""));
+    }
+}
@@ -1,11 +1,185 @@
 //! SQLite persistence layer.
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
-use rusqlite::Connection;
+use chrono::{DateTime, Utc};
+use llama_core::{BenchReport, Stats};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
 use tracing::info;
 
+use crate::services::api_keys::{ActiveKey, Scope};
+use crate::services::chat_export::ExportedChat;
+use crate::services::model_manager::ModelSettings;
+use crate::services::request_log::RequestLogEntry;
+use crate::services::stats::ModelStats;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DbError {
+    #[error("Alias '{alias}' is already assigned to model '{model_id}'")]
+    AliasTaken { alias: String, model_id: String },
+
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+/// A file's SHA-256 as recorded after a download or verification, with
+/// the size and modification time the file had then.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHashRecord {
+    pub sha256: String,
+    pub size: u64,
+    /// Milliseconds since the Unix epoch.
+    pub mtime_ms: i64,
+}
+
+/// A file's directory-scan result, as JSON, with the size and
+/// modification time the file had when it was scanned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanCacheRecord {
+    pub size: u64,
+    /// Milliseconds since the Unix epoch.
+    pub mtime_ms: i64,
+    /// A serialized [`gguf_parser::FileScan`].
+    pub scan: String,
+}
+
+/// A stored benchmark run of one model.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BenchRecord {
+    pub id: i64,
+    pub model_id: String,
+    pub n_prompt: u32,
+    pub n_gen: u32,
+    pub repetitions: u32,
+    pub pp: Stats,
+    pub tg: Stats,
+    pub ttft_ms: Stats,
+    pub created_at: String,
+}
+
+/// A stored chat conversation.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChatSession {
+    pub id: i64,
+    pub title: String,
+    /// Model of the last reply, or the one picked at creation.
+    pub model_id: Option<String>,
+    pub message_count: u64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// One message of a [`ChatSession`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChatMessageRecord {
+    pub id: i64,
+    pub session_id: i64,
+    pub model_id: Option<String>,
+    pub role: String,
+    pub content: String,
+    /// Tokens of the message itself (completion tokens for replies).
+    pub tokens: Option<u32>,
+    /// Why a reply ended; `error` or `cancelled` marks a partial reply.
+    pub finish_reason: Option<String>,
+    pub created_at: String,
+}
+
+/// A stored API key; the key itself is only known by its hash.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ApiKeyRecord {
+    pub id: String,
+    pub label: String,
+    /// First characters of the key, for telling keys apart.
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub revoked: bool,
+}
+
+/// Rows removed by a chat deletion.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ChatDeletion {
+    pub sessions: u64,
+    pub messages: u64,
+}
+
+/// How [`Database::usage`] buckets the request log.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageGroupBy {
+    #[default]
+    Model,
+    /// Calendar day (UTC), `YYYY-MM-DD`.
+    Day,
+    /// Id of the API key; `none` for requests without one.
+    #[serde(rename = "api_key")]
+    ApiKey,
+}
+
+/// Aggregated request log rows of one bucket.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageRow {
+    /// Model id, day or API key id, depending on [`UsageGroupBy`].
+    pub key: String,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub duration_ms: u64,
+    /// Requests that did not finish with status 200.
+    pub errors: u64,
+}
+
+/// SQLite's `datetime()` format, used for timestamps written from Rust
+/// so that they sort and compare as text with `datetime('now')` ones.
+pub const SQLITE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+const API_KEY_SELECT: &str =
+    "SELECT id, label, key_prefix, scopes, created_at, last_used_at, revoked FROM api_keys";
+
+fn api_key_row(r: &rusqlite::Row<'_>) -> rusqlite::Result<ApiKeyRecord> {
+    let scopes: String = r.get(3)?;
+    Ok(ApiKeyRecord {
+        id: r.get(0)?,
+        label: r.get(1)?,
+        prefix: r.get(2)?,
+        scopes: parse_scopes(&scopes),
+        created_at: r.get(4)?,
+        last_used_at: r.get(5)?,
+        revoked: r.get(6)?,
+    })
+}
+
+/// Scopes are stored comma-separated; unknown names are ignored.
+fn join_scopes(scopes: &[Scope]) -> String {
+    scopes
+        .iter()
+        .map(|s| s.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn parse_scopes(s: &str) -> Vec<Scope> {
+    s.split(',').filter_map(Scope::parse).collect()
+}
+
+const CHAT_SESSION_SELECT: &str = "SELECT s.id, s.title, s.model_id, s.created_at, s.updated_at,
+            (SELECT COUNT(*) FROM chat_history h WHERE h.session_id = s.id)
+     FROM chat_sessions s";
+
+fn chat_session_row(r: &rusqlite::Row<'_>) -> rusqlite::Result<ChatSession> {
+    Ok(ChatSession {
+        id: r.get(0)?,
+        title: r.get(1)?,
+        model_id: r.get(2)?,
+        created_at: r.get(3)?,
+        updated_at: r.get(4)?,
+        message_count: r.get(5)?,
+    })
+}
+
 pub struct Database {
     conn: Mutex<Connection>,
 }
@@ -16,12 +190,23 @@ impl Database {
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
-        let conn = Connection::open(path)?;
+        let db = Self::from_connection(Connection::open(path)?)?;
+        info!(path = %path.display(), "Database ready");
+        Ok(db)
+    }
+
+    /// A fresh in-memory database (tests only).
+    #[cfg(test)]
+    pub(crate) fn open_in_memory() -> Self {
+        Self::from_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn from_connection(conn: Connection) -> anyhow::Result<Self> {
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
         let db = Self {
             conn: Mutex::new(conn),
         };
         db.migrate()?;
-        info!(path = %path.display(), "Database ready");
         Ok(db)
     }
 
@@ -51,9 +236,1107 @@ impl Database {
                 PRAGMA user_version = 1;",
             )?;
         }
+
+        if version < 2 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS aliases (
+                    alias       TEXT PRIMARY KEY COLLATE NOCASE,
+                    model_id    TEXT NOT NULL UNIQUE
+                                REFERENCES model_meta(id) ON DELETE CASCADE,
+                    created_at  TEXT DEFAULT (datetime('now'))
+                );
+                PRAGMA user_version = 2;",
+            )?;
+        }
+
+        // `mmproj_path` and `draft_model` are no longer read or written.
+        if version < 3 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS model_settings (
+                    model_id     TEXT PRIMARY KEY COLLATE NOCASE,
+                    ctx_size     INTEGER,
+                    n_gpu_layers INTEGER,
+                    n_threads    INTEGER,
+                    flash_attn   INTEGER,
+                    cache_type   TEXT,
+                    mmproj_path  TEXT,
+                    draft_model  TEXT,
+                    updated_at   TEXT DEFAULT (datetime('now'))
+                );
+                PRAGMA user_version = 3;",
+            )?;
+        }
+
+        if version < 4 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS favorites (
+                    model_id    TEXT PRIMARY KEY COLLATE NOCASE,
+                    created_at  TEXT DEFAULT (datetime('now'))
+                );
+                PRAGMA user_version = 4;",
+            )?;
+        }
+
+        if version < 5 {
+            conn.execute_batch(
+                "ALTER TABLE model_meta ADD COLUMN display_name TEXT;
+                PRAGMA user_version = 5;",
+            )?;
+        }
+
+        if version < 6 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS bench_results (
+                    id             INTEGER PRIMARY KEY AUTOINCREMENT,
+                    model_id       TEXT NOT NULL COLLATE NOCASE,
+                    n_prompt       INTEGER NOT NULL,
+                    n_gen          INTEGER NOT NULL,
+                    repetitions    INTEGER NOT NULL,
+                    pp_mean        REAL NOT NULL,
+                    pp_stddev      REAL NOT NULL,
+                    tg_mean        REAL NOT NULL,
+                    tg_stddev      REAL NOT NULL,
+                    ttft_ms_mean   REAL NOT NULL,
+                    ttft_ms_stddev REAL NOT NULL,
+                    created_at     TEXT DEFAULT (datetime('now'))
+                );
+                CREATE INDEX IF NOT EXISTS bench_results_model
+                    ON bench_results (model_id, created_at);
+                PRAGMA user_version = 6;",
+            )?;
+        }
+
+        if version < 7 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS model_stats (
+                    model_id          TEXT PRIMARY KEY COLLATE NOCASE,
+                    requests          INTEGER NOT NULL,
+                    prompt_tokens     INTEGER NOT NULL,
+                    completion_tokens INTEGER NOT NULL,
+                    generation_ms     INTEGER NOT NULL,
+                    last_request_at   TEXT,
+                    updated_at        TEXT DEFAULT (datetime('now'))
+                );
+                PRAGMA user_version = 7;",
+            )?;
+        }
+
+        if version < 8 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS request_log (
+                    id                INTEGER PRIMARY KEY AUTOINCREMENT,
+                    timestamp         TEXT NOT NULL,
+                    endpoint          TEXT NOT NULL,
+                    model_id          TEXT NOT NULL COLLATE NOCASE,
+                    prompt_tokens     INTEGER NOT NULL,
+                    completion_tokens INTEGER NOT NULL,
+                    duration_ms       INTEGER NOT NULL,
+                    finish_reason     TEXT NOT NULL,
+                    status            INTEGER NOT NULL,
+                    user_hash         TEXT,
+                    prompt            TEXT
+                );
+                CREATE INDEX IF NOT EXISTS request_log_timestamp
+                    ON request_log (timestamp);
+                PRAGMA user_version = 8;",
+            )?;
+        }
+
+        if version < 9 {
+            // Rows written before sessions existed keep a NULL session_id.
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS chat_sessions (
+                    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                    title       TEXT NOT NULL,
+                    model_id    TEXT,
+                    created_at  TEXT DEFAULT (datetime('now')),
+                    updated_at  TEXT DEFAULT (datetime('now'))
+                );
+                ALTER TABLE chat_history ADD COLUMN session_id INTEGER
+                    REFERENCES chat_sessions(id) ON DELETE CASCADE;
+                ALTER TABLE chat_history ADD COLUMN tokens INTEGER;
+                ALTER TABLE chat_history ADD COLUMN finish_reason TEXT;
+                CREATE INDEX IF NOT EXISTS chat_history_session
+                    ON chat_history (session_id, id);
+                PRAGMA user_version = 9;",
+            )?;
+        }
+
+        if version < 10 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS api_keys (
+                    id           TEXT PRIMARY KEY,
+                    label        TEXT NOT NULL,
+                    key_hash     TEXT NOT NULL UNIQUE,
+                    key_prefix   TEXT NOT NULL,
+                    scopes       TEXT NOT NULL,
+                    created_at   TEXT DEFAULT (datetime('now')),
+                    last_used_at TEXT,
+                    revoked      INTEGER NOT NULL DEFAULT 0
+                );
+                ALTER TABLE request_log ADD COLUMN api_key_id TEXT;
+                PRAGMA user_version = 10;",
+            )?;
+        }
+
+        if version < 11 {
+            conn.execute_batch(
+                "ALTER TABLE model_settings ADD COLUMN split_mode TEXT;
+                ALTER TABLE model_settings ADD COLUMN main_gpu INTEGER;
+                ALTER TABLE model_settings ADD COLUMN tensor_split TEXT;
+                PRAGMA user_version = 11;",
+            )?;
+        }
+
+        if version < 12 {
+            conn.execute_batch(
+                "ALTER TABLE model_settings ADD COLUMN kv_overrides TEXT;
+                PRAGMA user_version = 12;",
+            )?;
+        }
+
+        if version < 13 {
+            conn.execute_batch(
+                "ALTER TABLE model_settings ADD COLUMN rope TEXT;
+                PRAGMA user_version = 13;",
+            )?;
+        }
+
+        if version < 14 {
+            conn.execute_batch(
+                "ALTER TABLE model_settings ADD COLUMN type_k TEXT;
+                ALTER TABLE model_settings ADD COLUMN type_v TEXT;
+                PRAGMA user_version = 14;",
+            )?;
+        }
+
+        if version < 15 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS file_hashes (
+                    path        TEXT PRIMARY KEY,
+                    sha256      TEXT NOT NULL,
+                    size        INTEGER NOT NULL,
+                    mtime_ms    INTEGER NOT NULL,
+                    recorded_at TEXT DEFAULT (datetime('now'))
+                );
+                PRAGMA user_version = 15;",
+            )?;
+        }
+
+        if version < 16 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS scan_cache (
+                    path        TEXT PRIMARY KEY,
+                    size        INTEGER NOT NULL,
+                    mtime_ms    INTEGER NOT NULL,
+                    scan        TEXT NOT NULL,
+                    scanned_at  TEXT DEFAULT (datetime('now'))
+                );
+                PRAGMA user_version = 16;",
+            )?;
+        }
+        Ok(())
+    }
+
+    //  Aliases
+
+    /// All aliases as `(alias, model_id)` pairs.
+    pub fn list_aliases(&self) -> Result<Vec<(String, String)>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT alias, model_id FROM aliases ORDER BY alias")?;
+        let rows = stmt
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Set (or with `None`, clear) the alias of `model_id`.
+    ///
+    /// Aliases are unique case-insensitively.  The model's `model_meta`
+    /// row is created if missing; deleting that row drops its alias.
+    pub fn set_alias(
+        &self,
+        model_id: &str,
+        path: &Path,
+        alias: Option<&str>,
+    ) -> Result<(), DbError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        if let Some(alias) = alias {
+            let owner: Option<String> = tx
+                .query_row(
+                    "SELECT model_id FROM aliases WHERE alias = ?1",
+                    params![alias],
+                    |r| r.get(0),
+                )
+                .optional()?;
+            if let Some(owner) = owner
+                && owner != model_id
+            {
+                return Err(DbError::AliasTaken {
+                    alias: alias.to_string(),
+                    model_id: owner,
+                });
+            }
+        }
+
+        tx.execute(
+            "INSERT INTO model_meta (id, path) VALUES (?1, ?2)
+             ON CONFLICT(id) DO UPDATE SET path = excluded.path",
+            params![model_id, path.display().to_string()],
+        )?;
+        tx.execute("DELETE FROM aliases WHERE model_id = ?1", params![model_id])?;
+        if let Some(alias) = alias {
+            tx.execute(
+                "INSERT INTO aliases (alias, model_id) VALUES (?1, ?2)",
+                params![alias, model_id],
+            )?;
+        }
+        tx.commit()?;
         Ok(())
     }
 
+    //  Model metadata
+
+    /// Drop everything stored about `model_id`: its `model_meta` row
+    /// (and with it the alias), favorite flag and load settings.
+    pub fn delete_model_meta(&self, model_id: &str) -> Result<(), DbError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM model_meta WHERE id = ?1", params![model_id])?;
+        tx.execute(
+            "DELETE FROM favorites WHERE model_id = ?1",
+            params![model_id],
+        )?;
+        tx.execute(
+            "DELETE FROM model_settings WHERE model_id = ?1",
+            params![model_id],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    //  Display names
+
+    /// All display names as `(model_id, display_name)` pairs.
+    pub fn list_display_names(&self) -> Result<Vec<(String, String)>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, display_name FROM model_meta
+             WHERE display_name IS NOT NULL ORDER BY id",
+        )?;
+        let rows = stmt
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Set (or with `None`, clear) the display name of `model_id`.
+    ///
+    /// The `model_meta` row is kept when the file disappears, so the
+    /// name reattaches if a file with the same id shows up again.
+    pub fn set_display_name(
+        &self,
+        model_id: &str,
+        path: &Path,
+        display_name: Option<&str>,
+    ) -> Result<(), DbError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO model_meta (id, path, display_name) VALUES (?1, ?2, ?3)
+             ON CONFLICT(id) DO UPDATE SET
+                path = excluded.path,
+                display_name = excluded.display_name,
+                updated_at = datetime('now')",
+            params![model_id, path.display().to_string(), display_name],
+        )?;
+        Ok(())
+    }
+
+    //  Favorites
+
+    /// Ids of all favorite models.
+    pub fn list_favorites(&self) -> Result<Vec<String>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT model_id FROM favorites ORDER BY model_id")?;
+        let rows = stmt
+            .query_map([], |r| r.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    pub fn is_favorite(&self, model_id: &str) -> Result<bool, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let found = conn
+            .query_row(
+                "SELECT 1 FROM favorites WHERE model_id = ?1",
+                params![model_id],
+                |_| Ok(()),
+            )
+            .optional()?;
+        Ok(found.is_some())
+    }
+
+    /// Flip the favorite flag of `model_id` and return the new state.
+    pub fn toggle_favorite(&self, model_id: &str) -> Result<bool, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute(
+            "DELETE FROM favorites WHERE model_id = ?1",
+            params![model_id],
+        )?;
+        if removed > 0 {
+            return Ok(false);
+        }
+        conn.execute(
+            "INSERT INTO favorites (model_id) VALUES (?1)",
+            params![model_id],
+        )?;
+        Ok(true)
+    }
+
+    //  Model settings
+
+    /// Stored load settings of every model as `(model_id, settings)`.
+    pub fn list_model_settings(&self) -> Result<Vec<(String, ModelSettings)>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT model_id, ctx_size, n_gpu_layers, n_threads, flash_attn,
+                    cache_type, split_mode, main_gpu, tensor_split, kv_overrides,
+                    rope, type_k, type_v
+             FROM model_settings ORDER BY model_id",
+        )?;
+        let rows = stmt
+            .query_map([], |r| {
+                let cache_type: Option<String> = r.get(5)?;
+                let split_mode: Option<String> = r.get(6)?;
+                let tensor_split: Option<String> = r.get(8)?;
+                let kv_overrides: Option<String> = r.get(9)?;
+                let rope: Option<String> = r.get(10)?;
+                let type_k: Option<String> = r.get(11)?;
+                let type_v: Option<String> = r.get(12)?;
+                Ok((
+                    r.get(0)?,
+                    ModelSettings {
+                        ctx_size: r.get(1)?,
+                        n_gpu_layers: r.get(2)?,
+                        split_mode: split_mode.and_then(|m| serde_json::from_value(m.into()).ok()),
+                        main_gpu: r.get(7)?,
+                        tensor_split: tensor_split.and_then(|s| serde_json::from_str(&s).ok()),
+                        kv_overrides: kv_overrides.and_then(|s| serde_json::from_str(&s).ok()),
+                        rope: rope
+                            .and_then(|s| serde_json::from_str(&s).ok())
+                            .unwrap_or_default(),
+                        n_threads: r.get(3)?,
+                        flash_attn: r.get(4)?,
+                        cache_type: cache_type.and_then(|t| serde_json::from_value(t.into()).ok()),
+                        type_k: type_k.and_then(|t| serde_json::from_value(t.into()).ok()),
+                        type_v: type_v.and_then(|t| serde_json::from_value(t.into()).ok()),
+                    },
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Store `model_id`'s load settings; empty settings delete the row.
+    pub fn set_model_settings(
+        &self,
+        model_id: &str,
+        settings: &ModelSettings,
+    ) -> Result<(), DbError> {
+        let conn = self.conn.lock().unwrap();
+        if settings.is_empty() {
+            conn.execute(
+                "DELETE FROM model_settings WHERE model_id = ?1",
+                params![model_id],
+            )?;
+            return Ok(());
+        }
+        let cache_name = |t: Option<llama_core::KvCacheType>| t.map(|t| t.name());
+        let split_mode = settings
+            .split_mode
+            .and_then(|m| serde_json::to_value(m).ok())
+            .and_then(|v| v.as_str().map(str::to_string));
+        let tensor_split = settings
+            .tensor_split
+            .as_ref()
+            .and_then(|s| serde_json::to_string(s).ok());
+        let kv_overrides = settings
+            .kv_overrides
+            .as_ref()
+            .and_then(|o| serde_json::to_string(o).ok());
+        let rope = Some(settings.rope)
+            .filter(|r| !r.is_empty())
+            .and_then(|r| serde_json::to_string(&r).ok());
+        conn.execute(
+            "INSERT INTO model_settings (model_id, ctx_size, n_gpu_layers, n_threads,
+                                         flash_attn, cache_type, split_mode, main_gpu,
+                                         tensor_split, kv_overrides, rope, type_k, type_v)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+             ON CONFLICT(model_id) DO UPDATE SET
+                ctx_size = excluded.ctx_size,
+                n_gpu_layers = excluded.n_gpu_layers,
+                n_threads = excluded.n_threads,
+                flash_attn = excluded.flash_attn,
+                cache_type = excluded.cache_type,
+                split_mode = excluded.split_mode,
+                main_gpu = excluded.main_gpu,
+                tensor_split = excluded.tensor_split,
+                kv_overrides = excluded.kv_overrides,
+                rope = excluded.rope,
+                type_k = excluded.type_k,
+                type_v = excluded.type_v,
+                updated_at = datetime('now')",
+            params![
+                model_id,
+                settings.ctx_size,
+                settings.n_gpu_layers,
+                settings.n_threads,
+                settings.flash_attn,
+                cache_name(settings.cache_type),
+                split_mode,
+                settings.main_gpu,
+                tensor_split,
+                kv_overrides,
+                rope,
+                cache_name(settings.type_k),
+                cache_name(settings.type_v),
+            ],
+        )?;
+        Ok(())
+    }
+
+    //  Benchmarks
+
+    /// Store a benchmark result and return its id.
+    pub fn insert_bench_result(
+        &self,
+        model_id: &str,
+        report: &BenchReport,
+    ) -> Result<i64, DbError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO bench_results (model_id, n_prompt, n_gen, repetitions,
+                                        pp_mean, pp_stddev, tg_mean, tg_stddev,
+                                        ttft_ms_mean, ttft_ms_stddev)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                model_id,
+                report.n_prompt,
+                report.n_gen,
+                report.repetitions,
+                report.pp.mean,
+                report.pp.stddev,
+                report.tg.mean,
+                report.tg.stddev,
+                report.ttft_ms.mean,
+                report.ttft_ms.stddev,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Benchmark history of `model_id`, oldest first.
+    pub fn list_bench_results(&self, model_id: &str) -> Result<Vec<BenchRecord>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, model_id, n_prompt, n_gen, repetitions, pp_mean, pp_stddev,
+                    tg_mean, tg_stddev, ttft_ms_mean, ttft_ms_stddev, created_at
+             FROM bench_results WHERE model_id = ?1 ORDER BY created_at, id",
+        )?;
+        let rows = stmt
+            .query_map(params![model_id], |r| {
+                Ok(BenchRecord {
+                    id: r.get(0)?,
+                    model_id: r.get(1)?,
+                    n_prompt: r.get(2)?,
+                    n_gen: r.get(3)?,
+                    repetitions: r.get(4)?,
+                    pp: Stats {
+                        mean: r.get(5)?,
+                        stddev: r.get(6)?,
+                    },
+                    tg: Stats {
+                        mean: r.get(7)?,
+                        stddev: r.get(8)?,
+                    },
+                    ttft_ms: Stats {
+                        mean: r.get(9)?,
+                        stddev: r.get(10)?,
+                    },
+                    created_at: r.get(11)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    //  Model stats
+
+    /// All stored usage counters as `(model_id, stats)` pairs.
+    pub fn list_model_stats(&self) -> Result<Vec<(String, ModelStats)>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT model_id, requests, prompt_tokens, completion_tokens,
+                    generation_ms, last_request_at
+             FROM model_stats ORDER BY model_id",
+        )?;
+        let rows = stmt
+            .query_map([], |r| {
+                let last_request_at: Option<String> = r.get(5)?;
+                Ok((
+                    r.get(0)?,
+                    ModelStats {
+                        requests: r.get(1)?,
+                        prompt_tokens: r.get(2)?,
+                        completion_tokens: r.get(3)?,
+                        generation_ms: r.get(4)?,
+                        last_request_at: last_request_at
+                            .and_then(|t| chrono::DateTime::parse_from_rfc3339(&t).ok())
+                            .map(|t| t.with_timezone(&chrono::Utc)),
+                    },
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Insert or overwrite the counters of each model in `rows`.
+    pub fn upsert_model_stats(&self, rows: &[(String, ModelStats)]) -> Result<(), DbError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO model_stats (model_id, requests, prompt_tokens,
+                                          completion_tokens, generation_ms, last_request_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT(model_id) DO UPDATE SET
+                    requests = excluded.requests,
+                    prompt_tokens = excluded.prompt_tokens,
+                    completion_tokens = excluded.completion_tokens,
+                    generation_ms = excluded.generation_ms,
+                    last_request_at = excluded.last_request_at,
+                    updated_at = datetime('now')",
+            )?;
+            for (model_id, stats) in rows {
+                stmt.execute(params![
+                    model_id,
+                    stats.requests,
+                    stats.prompt_tokens,
+                    stats.completion_tokens,
+                    stats.generation_ms,
+                    stats.last_request_at.map(|t| t.to_rfc3339()),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Forget `model_id`'s stored counters.
+    pub fn delete_model_stats(&self, model_id: &str) -> Result<(), DbError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM model_stats WHERE model_id = ?1",
+            params![model_id],
+        )?;
+        Ok(())
+    }
+
+    //  Chat sessions
+
+    pub fn create_chat_session(
+        &self,
+        title: &str,
+        model_id: Option<&str>,
+    ) -> Result<ChatSession, DbError> {
+        let id = {
+            let conn = self.conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO chat_sessions (title, model_id) VALUES (?1, ?2)",
+                params![title, model_id],
+            )?;
+            conn.last_insert_rowid()
+        };
+        Ok(self
+            .get_chat_session(id)?
+            .expect("session row was just inserted"))
+    }
+
+    /// All sessions, most recently active first.
+    pub fn list_chat_sessions(&self) -> Result<Vec<ChatSession>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "{CHAT_SESSION_SELECT} ORDER BY s.updated_at DESC, s.id DESC"
+        ))?;
+        let rows = stmt
+            .query_map([], chat_session_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    pub fn get_chat_session(&self, id: i64) -> Result<Option<ChatSession>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn
+            .query_row(
+                &format!("{CHAT_SESSION_SELECT} WHERE s.id = ?1"),
+                params![id],
+                chat_session_row,
+            )
+            .optional()?)
+    }
+
+    /// Delete a session and its messages.
+    pub fn delete_chat_session(&self, id: i64) -> Result<ChatDeletion, DbError> {
+        self.delete_chat_sessions_where("id = ?1", params![id])
+    }
+
+    /// Delete sessions last active before `before` and/or whose model is
+    /// `model_id`, with their messages.  No filter deletes nothing.
+    pub fn prune_chat_sessions(
+        &self,
+        before: Option<DateTime<Utc>>,
+        model_id: Option<&str>,
+    ) -> Result<ChatDeletion, DbError> {
+        if before.is_none() && model_id.is_none() {
+            return Ok(ChatDeletion::default());
+        }
+        let before = before.map(|t| t.format(SQLITE_TIME_FORMAT).to_string());
+        self.delete_chat_sessions_where(
+            "(?1 IS NULL OR updated_at < ?1) AND (?2 IS NULL OR model_id = ?2 COLLATE NOCASE)",
+            params![before, model_id],
+        )
+    }
+
+    /// Delete the sessions matching `filter` (a `WHERE` clause over
+    /// `chat_sessions`) in one transaction, counting the messages that
+    /// go with them.
+    fn delete_chat_sessions_where(
+        &self,
+        filter: &str,
+        args: &[&dyn rusqlite::ToSql],
+    ) -> Result<ChatDeletion, DbError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let messages: u64 = tx.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM chat_history
+                 WHERE session_id IN (SELECT id FROM chat_sessions WHERE {filter})"
+            ),
+            args,
+            |r| r.get(0),
+        )?;
+        let sessions = tx.execute(&format!("DELETE FROM chat_sessions WHERE {filter}"), args)?;
+        tx.commit()?;
+        Ok(ChatDeletion {
+            sessions: sessions as u64,
+            messages,
+        })
+    }
+
+    /// Messages of a session in the order they were written.
+    pub fn list_chat_messages(&self, session_id: i64) -> Result<Vec<ChatMessageRecord>, DbError> {
+        self.chat_messages_after(session_id, 0, i64::MAX)
+    }
+
+    /// Up to `limit` messages of a session with ids above `after_id`, in
+    /// order; used to page through long sessions.
+    pub fn chat_messages_after(
+        &self,
+        session_id: i64,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<ChatMessageRecord>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, session_id, model_id, role, content, tokens, finish_reason, created_at
+             FROM chat_history WHERE session_id = ?1 AND id > ?2 ORDER BY id LIMIT ?3",
+        )?;
+        let rows = stmt
+            .query_map(params![session_id, after_id, limit], |r| {
+                Ok(ChatMessageRecord {
+                    id: r.get(0)?,
+                    session_id: r.get(1)?,
+                    model_id: r.get(2)?,
+                    role: r.get(3)?,
+                    content: r.get(4)?,
+                    tokens: r.get(5)?,
+                    finish_reason: r.get(6)?,
+                    created_at: r.get(7)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Recreate an exported session with its original timestamps.
+    pub fn import_chat_session(&self, chat: &ExportedChat) -> Result<ChatSession, DbError> {
+        let id = {
+            let mut conn = self.conn.lock().unwrap();
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO chat_sessions (title, model_id, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    chat.session.title,
+                    chat.session.model_id,
+                    chat.session.created_at,
+                    chat.session.updated_at,
+                ],
+            )?;
+            let id = tx.last_insert_rowid();
+            {
+                let mut stmt = tx.prepare(
+                    "INSERT INTO chat_history (session_id, model_id, role, content, tokens,
+                                               finish_reason, created_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                )?;
+                for m in &chat.messages {
+                    stmt.execute(params![
+                        id,
+                        m.model_id,
+                        m.role,
+                        m.content,
+                        m.tokens,
+                        m.finish_reason,
+                        m.created_at,
+                    ])?;
+                }
+            }
+            tx.commit()?;
+            id
+        };
+        Ok(self
+            .get_chat_session(id)?
+            .expect("session row was just inserted"))
+    }
+
+    /// Append a message to a session, making `model_id` the session's
+    /// model and bumping its `updated_at`.
+    pub fn insert_chat_message(
+        &self,
+        session_id: i64,
+        model_id: &str,
+        role: &str,
+        content: &str,
+        tokens: Option<u32>,
+        finish_reason: Option<&str>,
+    ) -> Result<ChatMessageRecord, DbError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO chat_history (session_id, model_id, role, content, tokens, finish_reason)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![session_id, model_id, role, content, tokens, finish_reason],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.execute(
+            "UPDATE chat_sessions SET model_id = ?2, updated_at = datetime('now') WHERE id = ?1",
+            params![session_id, model_id],
+        )?;
+        let created_at = tx.query_row(
+            "SELECT created_at FROM chat_history WHERE id = ?1",
+            params![id],
+            |r| r.get(0),
+        )?;
+        tx.commit()?;
+        Ok(ChatMessageRecord {
+            id,
+            session_id,
+            model_id: Some(model_id.to_string()),
+            role: role.to_string(),
+            content: content.to_string(),
+            tokens,
+            finish_reason: finish_reason.map(str::to_string),
+            created_at,
+        })
+    }
+
+    //  Request log
+
+    /// Append request log entries in one transaction.
+    pub fn insert_request_log(&self, entries: &[RequestLogEntry]) -> Result<(), DbError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO request_log (timestamp, endpoint, model_id, prompt_tokens,
+                                          completion_tokens, duration_ms, finish_reason,
+                                          status, user_hash, prompt, api_key_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            )?;
+            for e in entries {
+                stmt.execute(params![
+                    e.timestamp.format(SQLITE_TIME_FORMAT).to_string(),
+                    e.endpoint,
+                    e.model_id,
+                    e.prompt_tokens,
+                    e.completion_tokens,
+                    e.duration_ms,
+                    e.finish_reason,
+                    e.status,
+                    e.user_hash,
+                    e.prompt,
+                    e.api_key_id,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Delete request log rows older than `before`; returns how many.
+    pub fn prune_request_log(&self, before: DateTime<Utc>) -> Result<usize, DbError> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.execute(
+            "DELETE FROM request_log WHERE timestamp < ?1",
+            params![before.format(SQLITE_TIME_FORMAT).to_string()],
+        )?)
+    }
+
+    /// Request log totals in `[from, to)`, bucketed by `group_by`.
+    pub fn usage(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        group_by: UsageGroupBy,
+    ) -> Result<Vec<UsageRow>, DbError> {
+        let key = match group_by {
+            UsageGroupBy::Model => "model_id",
+            UsageGroupBy::Day => "substr(timestamp, 1, 10)",
+            UsageGroupBy::ApiKey => "COALESCE(api_key_id, 'none')",
+        };
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {key} AS k, COUNT(*), SUM(prompt_tokens), SUM(completion_tokens),
+                    SUM(duration_ms), SUM(status != 200)
+             FROM request_log
+             WHERE (?1 IS NULL OR timestamp >= ?1) AND (?2 IS NULL OR timestamp < ?2)
+             GROUP BY k ORDER BY k"
+        ))?;
+        let bound = |t: Option<DateTime<Utc>>| t.map(|t| t.format(SQLITE_TIME_FORMAT).to_string());
+        let rows = stmt
+            .query_map(params![bound(from), bound(to)], |r| {
+                Ok(UsageRow {
+                    key: r.get(0)?,
+                    requests: r.get(1)?,
+                    prompt_tokens: r.get(2)?,
+                    completion_tokens: r.get(3)?,
+                    duration_ms: r.get(4)?,
+                    errors: r.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    //  API keys
+
+    /// Store a new key under `hash`; the plaintext is never stored.
+    pub fn insert_api_key(
+        &self,
+        id: &str,
+        label: &str,
+        hash: &str,
+        prefix: &str,
+        scopes: &[Scope],
+    ) -> Result<ApiKeyRecord, DbError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO api_keys (id, label, key_hash, key_prefix, scopes)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![id, label, hash, prefix, join_scopes(scopes)],
+        )?;
+        Ok(conn.query_row(
+            &format!("{API_KEY_SELECT} WHERE id = ?1"),
+            params![id],
+            api_key_row,
+        )?)
+    }
+
+    /// All keys, revoked ones included, newest first.
+    pub fn list_api_keys(&self) -> Result<Vec<ApiKeyRecord>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "{API_KEY_SELECT} ORDER BY created_at DESC, rowid DESC"
+        ))?;
+        let rows = stmt
+            .query_map([], api_key_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Non-revoked keys as `(hash, key)` pairs for the auth registry.
+    pub fn active_api_keys(&self) -> Result<Vec<(String, ActiveKey)>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT key_hash, id, scopes FROM api_keys WHERE revoked = 0")?;
+        let rows = stmt
+            .query_map([], |r| {
+                let scopes: String = r.get(2)?;
+                Ok((
+                    r.get(0)?,
+                    ActiveKey {
+                        id: r.get(1)?,
+                        scopes: parse_scopes(&scopes),
+                    },
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Revoke a key.  Returns `false` if it doesn't exist or was already
+    /// revoked.
+    pub fn revoke_api_key(&self, id: &str) -> Result<bool, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let n = conn.execute(
+            "UPDATE api_keys SET revoked = 1 WHERE id = ?1 AND revoked = 0",
+            params![id],
+        )?;
+        Ok(n > 0)
+    }
+
+    pub fn touch_api_key(&self, id: &str) -> Result<(), DbError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE api_keys SET last_used_at = datetime('now') WHERE id = ?1",
+            params![id],
+        )?;
+        Ok(())
+    }
+
+    //  File hashes
+
+    /// Recorded hashes of all files, keyed by path.
+    pub fn list_file_hashes(&self) -> Result<Vec<(PathBuf, FileHashRecord)>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT path, sha256, size, mtime_ms FROM file_hashes")?;
+        let rows = stmt
+            .query_map([], |r| {
+                Ok((
+                    PathBuf::from(r.get::<_, String>(0)?),
+                    FileHashRecord {
+                        sha256: r.get(1)?,
+                        size: r.get::<_, i64>(2)? as u64,
+                        mtime_ms: r.get(3)?,
+                    },
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    pub fn get_file_hash(&self, path: &Path) -> Result<Option<FileHashRecord>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let record = conn
+            .query_row(
+                "SELECT sha256, size, mtime_ms FROM file_hashes WHERE path = ?1",
+                params![path.display().to_string()],
+                |r| {
+                    Ok(FileHashRecord {
+                        sha256: r.get(0)?,
+                        size: r.get::<_, i64>(1)? as u64,
+                        mtime_ms: r.get(2)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(record)
+    }
+
+    /// Record (or replace) the hash of the file at `path`.
+    pub fn set_file_hash(&self, path: &Path, record: &FileHashRecord) -> Result<(), DbError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO file_hashes (path, sha256, size, mtime_ms) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(path) DO UPDATE SET
+                sha256 = excluded.sha256,
+                size = excluded.size,
+                mtime_ms = excluded.mtime_ms,
+                recorded_at = datetime('now')",
+            params![
+                path.display().to_string(),
+                record.sha256,
+                record.size as i64,
+                record.mtime_ms
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_file_hash(&self, path: &Path) -> Result<(), DbError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM file_hashes WHERE path = ?1",
+            params![path.display().to_string()],
+        )?;
+        Ok(())
+    }
+
+    //  Scan cache
+
+    pub fn get_scan_cache(&self, path: &Path) -> Result<Option<ScanCacheRecord>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let record = conn
+            .query_row(
+                "SELECT size, mtime_ms, scan FROM scan_cache WHERE path = ?1",
+                params![path.display().to_string()],
+                |r| {
+                    Ok(ScanCacheRecord {
+                        size: r.get::<_, i64>(0)? as u64,
+                        mtime_ms: r.get(1)?,
+                        scan: r.get(2)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(record)
+    }
+
+    /// Store (or replace) the scan result of the file at `path`.
+    pub fn set_scan_cache(&self, path: &Path, record: &ScanCacheRecord) -> Result<(), DbError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO scan_cache (path, size, mtime_ms, scan) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(path) DO UPDATE SET
+                size = excluded.size,
+                mtime_ms = excluded.mtime_ms,
+                scan = excluded.scan,
+                scanned_at = datetime('now')",
+            params![
+                path.display().to_string(),
+                record.size as i64,
+                record.mtime_ms,
+                record.scan
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Drop the scan results of every file not in `keep`; returns how many
+    /// were dropped.
+    pub fn prune_scan_cache(&self, keep: &[PathBuf]) -> Result<usize, DbError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute_batch("CREATE TEMP TABLE IF NOT EXISTS scan_keep (path TEXT PRIMARY KEY)")?;
+        tx.execute("DELETE FROM scan_keep", [])?;
+        {
+            let mut insert = tx.prepare("INSERT OR IGNORE INTO scan_keep (path) VALUES (?1)")?;
+            for path in keep {
+                insert.execute(params![path.display().to_string()])?;
+            }
+        }
+        let dropped = tx.execute(
+            "DELETE FROM scan_cache WHERE path NOT IN (SELECT path FROM scan_keep)",
+            [],
+        )?;
+        tx.execute("DELETE FROM scan_keep", [])?;
+        tx.commit()?;
+        Ok(dropped)
+    }
+
     #[allow(dead_code)]
     pub fn with_conn<F, T>(&self, f: F) -> T
     where
@@ -63,3 +1346,401 @@ impl Database {
         f(&conn)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_db() -> Database {
+        Database::open_in_memory()
+    }
+
+    #[test]
+    fn model_settings_round_trip_load_params() {
+        let db = memory_db();
+        let settings = ModelSettings {
+            n_gpu_layers: Some(-1),
+            split_mode: Some(llama_core::SplitMode::Row),
+            main_gpu: Some(1),
+            tensor_split: Some(vec![0.75, 0.25]),
+            kv_overrides: Some(vec![llama_core::KvOverride {
+                key: "tokenizer.ggml.add_bos_token".into(),
+                value: llama_core::KvOverrideValue::Bool(false),
+            }]),
+            cache_type: Some(llama_core::KvCacheType::Q8_0),
+            type_v: Some(llama_core::KvCacheType::F16),
+            rope: crate::services::model_manager::RopeSettings {
+                rope_scaling_type: Some(llama_core::RopeScalingType::Linear),
+                rope_freq_scale: Some(0.5),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        db.set_model_settings("m", &settings).unwrap();
+        assert_eq!(
+            db.list_model_settings().unwrap(),
+            vec![("m".to_string(), settings)]
+        );
+    }
+
+    fn favorite_rows(db: &Database) -> Vec<String> {
+        db.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT model_id FROM favorites ORDER BY model_id")
+                .unwrap();
+            stmt.query_map([], |r| r.get(0))
+                .unwrap()
+                .collect::<Result<Vec<String>, _>>()
+                .unwrap()
+        })
+    }
+
+    #[test]
+    fn migrations_reach_latest_version() {
+        let db = memory_db();
+        let version: i32 = db
+            .with_conn(|conn| conn.pragma_query_value(None, "user_version", |r| r.get(0)))
+            .unwrap();
+        assert_eq!(version, 16);
+    }
+
+    #[test]
+    fn toggle_favorite_flips_and_persists() {
+        let db = memory_db();
+        assert!(db.toggle_favorite("qwen2.5-7b").unwrap());
+        assert_eq!(favorite_rows(&db), ["qwen2.5-7b"]);
+        assert!(db.is_favorite("Qwen2.5-7B").unwrap());
+
+        assert!(!db.toggle_favorite("Qwen2.5-7B").unwrap());
+        assert!(favorite_rows(&db).is_empty());
+        assert!(!db.is_favorite("qwen2.5-7b").unwrap());
+    }
+
+    #[test]
+    fn list_favorites_returns_all() {
+        let db = memory_db();
+        db.toggle_favorite("b").unwrap();
+        db.toggle_favorite("a").unwrap();
+        assert_eq!(db.list_favorites().unwrap(), ["a", "b"]);
+    }
+
+    #[test]
+    fn display_name_set_and_clear() {
+        let db = memory_db();
+        let path = Path::new("/models/llama.gguf");
+        db.set_display_name("llama", path, Some("Llama 3.1 8B"))
+            .unwrap();
+        assert_eq!(
+            db.list_display_names().unwrap(),
+            [("llama".to_string(), "Llama 3.1 8B".to_string())]
+        );
+
+        // Clearing the name keeps the row (and any alias on it).
+        db.set_alias("llama", path, Some("l3")).unwrap();
+        db.set_display_name("llama", path, None).unwrap();
+        assert!(db.list_display_names().unwrap().is_empty());
+        assert_eq!(db.list_aliases().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn delete_model_meta_drops_related_rows() {
+        let db = memory_db();
+        let path = Path::new("/models/llama.gguf");
+        db.set_alias("llama", path, Some("l3")).unwrap();
+        db.set_display_name("llama", path, Some("Llama")).unwrap();
+        db.toggle_favorite("llama").unwrap();
+        db.set_model_settings(
+            "llama",
+            &ModelSettings {
+                ctx_size: Some(8192),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        db.delete_model_meta("llama").unwrap();
+        assert!(db.list_aliases().unwrap().is_empty());
+        assert!(db.list_display_names().unwrap().is_empty());
+        assert!(db.list_favorites().unwrap().is_empty());
+        assert!(db.list_model_settings().unwrap().is_empty());
+    }
+
+    #[test]
+    fn bench_results_round_trip() {
+        let db = memory_db();
+        let report = BenchReport {
+            n_prompt: 512,
+            n_gen: 128,
+            repetitions: 3,
+            pp: Stats {
+                mean: 1500.0,
+                stddev: 12.5,
+            },
+            tg: Stats {
+                mean: 42.0,
+                stddev: 0.5,
+            },
+            ttft_ms: Stats {
+                mean: 350.0,
+                stddev: 4.0,
+            },
+            runs: Vec::new(),
+        };
+        let id = db.insert_bench_result("qwen-q4", &report).unwrap();
+        db.insert_bench_result("other", &report).unwrap();
+
+        let rows = db.list_bench_results("Qwen-Q4").unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].id, id);
+        assert_eq!(rows[0].n_prompt, 512);
+        assert_eq!(rows[0].pp, report.pp);
+        assert_eq!(rows[0].tg, report.tg);
+        assert_eq!(rows[0].ttft_ms, report.ttft_ms);
+    }
+
+    #[test]
+    fn model_stats_upsert_and_delete() {
+        let db = memory_db();
+        let mut stats = ModelStats {
+            requests: 3,
+            prompt_tokens: 120,
+            completion_tokens: 300,
+            generation_ms: 6000,
+            last_request_at: Some(chrono::Utc::now()),
+        };
+        db.upsert_model_stats(&[("qwen".into(), stats.clone())])
+            .unwrap();
+        stats.requests = 4;
+        db.upsert_model_stats(&[("Qwen".into(), stats.clone())])
+            .unwrap();
+
+        let rows = db.list_model_stats().unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].1, stats);
+
+        db.delete_model_stats("QWEN").unwrap();
+        assert!(db.list_model_stats().unwrap().is_empty());
+    }
+
+    fn log_entry(model_id: &str, timestamp: &str, status: u16) -> RequestLogEntry {
+        RequestLogEntry {
+            timestamp: DateTime::parse_from_rfc3339(timestamp)
+                .unwrap()
+                .with_timezone(&Utc),
+            endpoint: "/v1/chat/completions".into(),
+            model_id: model_id.into(),
+            prompt_tokens: 10,
+            completion_tokens: 20,
+            duration_ms: 100,
+            finish_reason: "stop".into(),
+            status,
+            user_hash: None,
+            prompt: None,
+            api_key_id: None,
+        }
+    }
+
+    #[test]
+    fn api_keys_revoke_and_attribute_usage() {
+        let db = memory_db();
+        let rec = db
+            .insert_api_key("k1", "ci", "hash1", "sk-lld-abcd", &[Scope::Inference])
+            .unwrap();
+        assert_eq!(rec.scopes, vec![Scope::Inference]);
+        assert!(!rec.revoked);
+        db.insert_api_key("k2", "admin", "hash2", "sk-lld-efgh", &[Scope::Admin])
+            .unwrap();
+        db.touch_api_key("k1").unwrap();
+
+        let active = db.active_api_keys().unwrap();
+        assert_eq!(active.len(), 2);
+        assert!(db.revoke_api_key("k1").unwrap());
+        assert!(!db.revoke_api_key("k1").unwrap());
+        let active = db.active_api_keys().unwrap();
+        assert_eq!(
+            active,
+            vec![(
+                "hash2".to_string(),
+                ActiveKey {
+                    id: "k2".into(),
+                    scopes: vec![Scope::Admin]
+                }
+            )]
+        );
+
+        let listed = db.list_api_keys().unwrap();
+        let k1 = listed.iter().find(|k| k.id == "k1").unwrap();
+        assert!(k1.revoked && k1.last_used_at.is_some());
+
+        let mut keyed = log_entry("a", "2025-03-01T10:00:00Z", 200);
+        keyed.api_key_id = Some("k1".into());
+        db.insert_request_log(&[keyed, log_entry("a", "2025-03-01T11:00:00Z", 200)])
+            .unwrap();
+        let by_key = db.usage(None, None, UsageGroupBy::ApiKey).unwrap();
+        let keys: Vec<_> = by_key
+            .iter()
+            .map(|r| (r.key.as_str(), r.requests))
+            .collect();
+        assert_eq!(keys, vec![("k1", 1), ("none", 1)]);
+    }
+
+    #[test]
+    fn usage_groups_and_filters_request_log() {
+        let db = memory_db();
+        db.insert_request_log(&[
+            log_entry("a", "2025-03-01T10:00:00Z", 200),
+            log_entry("a", "2025-03-02T10:00:00Z", 500),
+            log_entry("b", "2025-03-02T11:00:00Z", 200),
+        ])
+        .unwrap();
+
+        let by_model = db.usage(None, None, UsageGroupBy::Model).unwrap();
+        assert_eq!(by_model.len(), 2);
+        assert_eq!(by_model[0].key, "a");
+        assert_eq!(by_model[0].requests, 2);
+        assert_eq!(by_model[0].completion_tokens, 40);
+        assert_eq!(by_model[0].errors, 1);
+
+        let from = DateTime::parse_from_rfc3339("2025-03-02T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let by_day = db.usage(Some(from), None, UsageGroupBy::Day).unwrap();
+        assert_eq!(by_day.len(), 1);
+        assert_eq!(by_day[0].key, "2025-03-02");
+        assert_eq!(by_day[0].requests, 2);
+
+        assert_eq!(db.prune_request_log(from).unwrap(), 1);
+        assert_eq!(db.usage(None, None, UsageGroupBy::Day).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn chat_session_messages_cascade_on_delete() {
+        let db = memory_db();
+        let session = db.create_chat_session("Test", None).unwrap();
+        assert_eq!(session.message_count, 0);
+
+        db.insert_chat_message(session.id, "qwen", "user", "Hi", Some(2), None)
+            .unwrap();
+        let reply = db
+            .insert_chat_message(
+                session.id,
+                "qwen",
+                "assistant",
+                "Hello!",
+                Some(3),
+                Some("stop"),
+            )
+            .unwrap();
+
+        let messages = db.list_chat_messages(session.id).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].role, "user");
+        assert_eq!(messages[1], reply);
+
+        let listed = db.list_chat_sessions().unwrap();
+        assert_eq!(listed[0].message_count, 2);
+        assert_eq!(listed[0].model_id.as_deref(), Some("qwen"));
+
+        let deleted = db.delete_chat_session(session.id).unwrap();
+        assert_eq!(
+            deleted,
+            ChatDeletion {
+                sessions: 1,
+                messages: 2
+            }
+        );
+        assert_eq!(db.delete_chat_session(session.id).unwrap().sessions, 0);
+        assert!(db.list_chat_messages(session.id).unwrap().is_empty());
+        let orphans: i64 = db
+            .with_conn(|c| c.query_row("SELECT COUNT(*) FROM chat_history", [], |r| r.get(0)))
+            .unwrap();
+        assert_eq!(orphans, 0);
+    }
+
+    fn session_at(db: &Database, title: &str, model_id: &str, updated_at: &str) -> ChatSession {
+        let message = |content: &str| crate::services::chat_export::ExportedMessage {
+            role: "user".into(),
+            content: content.into(),
+            model_id: Some(model_id.into()),
+            tokens: None,
+            finish_reason: None,
+            created_at: updated_at.into(),
+        };
+        db.import_chat_session(&ExportedChat {
+            format: crate::services::chat_export::EXPORT_FORMAT.into(),
+            version: crate::services::chat_export::EXPORT_VERSION,
+            session: crate::services::chat_export::ExportedSession {
+                title: title.into(),
+                model_id: Some(model_id.into()),
+                created_at: updated_at.into(),
+                updated_at: updated_at.into(),
+            },
+            messages: vec![message("a"), message("b")],
+        })
+        .unwrap()
+    }
+
+    fn session_titles(db: &Database) -> Vec<String> {
+        let mut titles: Vec<_> = db
+            .list_chat_sessions()
+            .unwrap()
+            .into_iter()
+            .map(|s| s.title)
+            .collect();
+        titles.sort();
+        titles
+    }
+
+    #[test]
+    fn prune_chat_sessions_respects_date_boundary() {
+        let db = memory_db();
+        session_at(&db, "old", "qwen", "2025-02-28 23:59:59");
+        session_at(&db, "edge", "qwen", "2025-03-01 00:00:00");
+        session_at(&db, "new", "qwen", "2025-03-02 12:00:00");
+
+        let before = DateTime::parse_from_rfc3339("2025-03-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let deleted = db.prune_chat_sessions(Some(before), None).unwrap();
+        assert_eq!(
+            deleted,
+            ChatDeletion {
+                sessions: 1,
+                messages: 2
+            }
+        );
+        // `before` is exclusive: a session last active exactly then stays.
+        assert_eq!(session_titles(&db), ["edge", "new"]);
+    }
+
+    #[test]
+    fn prune_chat_sessions_filters_by_model() {
+        let db = memory_db();
+        session_at(&db, "q1", "qwen", "2025-01-01 00:00:00");
+        session_at(&db, "l1", "llama", "2025-01-01 00:00:00");
+        session_at(&db, "q2", "qwen", "2025-06-01 00:00:00");
+
+        let before = DateTime::parse_from_rfc3339("2025-03-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let deleted = db.prune_chat_sessions(Some(before), Some("QWEN")).unwrap();
+        assert_eq!(deleted.sessions, 1);
+        assert_eq!(session_titles(&db), ["l1", "q2"]);
+
+        assert_eq!(
+            db.prune_chat_sessions(None, None).unwrap(),
+            ChatDeletion::default()
+        );
+        assert_eq!(
+            db.prune_chat_sessions(None, Some("qwen")).unwrap().sessions,
+            1
+        );
+        assert_eq!(session_titles(&db), ["l1"]);
+        assert_eq!(
+            db.list_chat_messages(db.list_chat_sessions().unwrap()[0].id)
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
@@ -5,9 +5,11 @@ mod middleware;
 mod routes;
 mod services;
 mod state;
+#[cfg(test)]
+mod test_util;
 
 use clap::Parser;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{EnvFilter, fmt::format::FmtSpan, prelude::*};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -17,14 +19,24 @@ async fn main() -> anyhow::Result<()> {
             EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| EnvFilter::new("info,llama_dashboard=debug")),
         )
+        // One line per request, with the fields recorded on its span
+        .with_span_events(FmtSpan::CLOSE)
+        .finish()
+        // Recent lines for the dashboard
+        .with(services::logs::capture().layer())
         .init();
 
     let args = cli::Cli::parse();
 
     match args.command {
         Some(cli::Commands::Run(run_args)) => cli::run::execute(run_args).await,
-        Some(cli::Commands::Models(m)) => cli::models::execute(m).await,
-        Some(cli::Commands::Config(c)) => cli::config_cmd::execute(c).await,
+        Some(cli::Commands::Complete(c)) => cli::complete::execute(c).await,
+        Some(cli::Commands::Models(m)) => cli::models::execute(args.global, m).await,
+        Some(cli::Commands::Bench(b)) => cli::bench::execute(b).await,
+        Some(cli::Commands::Tokenize(t)) => cli::tokenize::execute(t).await,
+        Some(cli::Commands::Embed(e)) => cli::embed::execute(e).await,
+        Some(cli::Commands::Pull(p)) => cli::pull::execute(args.global, p).await,
+        Some(cli::Commands::Config(c)) => cli::config_cmd::execute(args.global, c).await,
         // Default: start HTTP server
         Some(cli::Commands::Serve(serve_args)) => {
             cli::serve::execute(args.global, serve_args).await
@@ -36,8 +48,28 @@ async fn main() -> anyhow::Result<()> {
                     model: None,
                     ctx_size: 4096,
                     n_gpu_layers: -1,
+                    gpu_split: cli::GpuSplitArgs {
+                        split_mode: llama_core::SplitMode::default(),
+                        main_gpu: 0,
+                        tensor_split: Vec::new(),
+                    },
+                    rope: cli::RopeArgs::default(),
+                    numa: None,
+                    flash_attn: None,
+                    cache_type_k: None,
+                    cache_type_v: None,
+                    parallel: 1,
+                    defrag_thold: -1.0,
                     max_models: 4,
+                    max_memory: None,
                     idle_timeout: 0,
+                    shutdown_timeout: 30,
+                    tls_cert: None,
+                    tls_key: None,
+                    http_redirect_port: None,
+                    uds: None,
+                    uds_mode: 0o600,
+                    uds_only: false,
                 },
             )
             .await
@@ -0,0 +1,456 @@
+//! API key authentication.
+//!
+//! Auth is on when the legacy `api_key` is configured or any key in the
+//! `api_keys` table is active.  Requests then need `Authorization:
+//! Bearer <key>`; `X-Api-Key: <key>` is accepted as well so the dashboard
+//! can authenticate without building the header.  The legacy key acts as
+//! an admin key.  Missing or unknown keys get a 401 and keys without the
+//! route's scope a 403, both in the OpenAI error envelope.
+//!
+//! WebSocket upgrades may pass the key as `?api_key=` instead; an
+//! `/ws/events` upgrade without one authenticates in its first message.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Query, Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::Response,
+};
+use sha1::{Digest, Sha1};
+
+use super::error_response;
+use crate::services::api_keys::{Scope, allows};
+use crate::state::AppState;
+
+/// Id of the `api_keys` row that authenticated a request, added to the
+/// request extensions.  Absent for the legacy key or when auth is off.
+#[derive(Debug, Clone)]
+pub struct ApiKeyId(pub String);
+
+/// Guard for inference routes.
+pub async fn require_inference(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    authorize(&state, Scope::Inference, req, next).await
+}
+
+/// Guard for management and dashboard routes.
+pub async fn require_admin(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    authorize(&state, Scope::Admin, req, next).await
+}
+
+/// Guard for `/ws/events`, which needs the `inference` scope.  Browsers
+/// can't set headers on a WebSocket, so the key may also come as
+/// `?api_key=`; an upgrade without any key is let through marked
+/// [`AuthPending`] and must authenticate with its first message.  Keys
+/// with the `admin` scope are marked [`AdminScope`].
+pub async fn require_events(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let key = presented_key(&req);
+    if auth_enabled(&state) && key.is_none() && is_upgrade(req.headers()) {
+        req.extensions_mut().insert(AuthPending);
+        return next.run(req).await;
+    }
+    if is_admin(&state, key.as_deref()) {
+        req.extensions_mut().insert(AdminScope);
+    }
+    authorize(&state, Scope::Inference, req, next).await
+}
+
+/// Marks a WebSocket upgrade that presented no key.
+#[derive(Debug, Clone, Copy)]
+pub struct AuthPending;
+
+/// Marks an `/ws/events` upgrade whose key has the `admin` scope, or
+/// any upgrade while auth is off.
+#[derive(Debug, Clone, Copy)]
+pub struct AdminScope;
+
+/// Why a key was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denied {
+    Missing,
+    Incorrect,
+    Scope(Scope),
+}
+
+impl Denied {
+    pub fn message(self) -> String {
+        match self {
+            Self::Missing => "Missing API key; send it as 'Authorization: Bearer <key>'".into(),
+            Self::Incorrect => "Incorrect API key provided".into(),
+            Self::Scope(scope) => format!("This API key lacks the '{}' scope", scope.as_str()),
+        }
+    }
+
+    fn into_response(self) -> Response {
+        if let Self::Scope(_) = self {
+            return error_response(
+                StatusCode::FORBIDDEN,
+                &self.message(),
+                "invalid_request_error",
+                "insufficient_scope",
+            );
+        }
+        let mut response = error_response(
+            StatusCode::UNAUTHORIZED,
+            &self.message(),
+            "invalid_request_error",
+            "invalid_api_key",
+        );
+        response.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            header::HeaderValue::from_static("Bearer"),
+        );
+        response
+    }
+}
+
+async fn authorize(state: &AppState, required: Scope, mut req: Request, next: Next) -> Response {
+    if !auth_enabled(state) {
+        return next.run(req).await;
+    }
+    match check_key(state, presented_key(&req).as_deref(), required) {
+        Ok(Some(id)) => {
+            req.extensions_mut().insert(id);
+        }
+        Ok(None) => {}
+        Err(denied) => return denied.into_response(),
+    }
+    next.run(req).await
+}
+
+fn auth_enabled(state: &AppState) -> bool {
+    state.api_key().is_some() || state.api_keys().has_keys()
+}
+
+/// Whether `key` may use admin routes: auth is off or it has the scope.
+pub fn is_admin(state: &AppState, key: Option<&str>) -> bool {
+    !auth_enabled(state) || check_key(state, key, Scope::Admin).is_ok()
+}
+
+/// Check `key` for the `required` scope while auth is on.  On success
+/// returns the table key's id, or `None` for the legacy key.
+pub fn check_key(
+    state: &AppState,
+    key: Option<&str>,
+    required: Scope,
+) -> Result<Option<ApiKeyId>, Denied> {
+    let key = key.ok_or(Denied::Missing)?;
+    if state
+        .api_key()
+        .is_some_and(|expected| keys_match(key, expected))
+    {
+        return Ok(None);
+    }
+    let active = state
+        .api_keys()
+        .authenticate(key)
+        .ok_or(Denied::Incorrect)?;
+    if !allows(&active.scopes, required) {
+        return Err(Denied::Scope(required));
+    }
+    if state.api_keys().should_touch(&active.id)
+        && let Err(e) = state.db().touch_api_key(&active.id)
+    {
+        tracing::warn!(id = active.id, "Failed to record API key use: {e}");
+    }
+    Ok(Some(ApiKeyId(active.id)))
+}
+
+/// The key from a `Bearer` authorization header, else from `X-Api-Key`,
+/// else, on WebSocket upgrades only, from the `api_key` query parameter.
+/// Query strings end up in logs, so plain requests don't get that option.
+fn presented_key(req: &Request) -> Option<String> {
+    let headers = req.headers();
+    let header_str = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let from_header = header_str(header::AUTHORIZATION.as_str())
+        .and_then(|value| value.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        .map(|(_, key)| key.trim())
+        .or_else(|| header_str("x-api-key").map(str::trim));
+    if let Some(key) = from_header {
+        return Some(key.to_string());
+    }
+    if !is_upgrade(headers) {
+        return None;
+    }
+    let Query(mut query) = Query::<HashMap<String, String>>::try_from_uri(req.uri()).ok()?;
+    query.remove("api_key")
+}
+
+fn is_upgrade(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("websocket"))
+}
+
+/// Compare keys without leaking where they differ: both are hashed to a
+/// fixed length first, then every byte is compared.
+fn keys_match(presented: &str, expected: &str) -> bool {
+    let (a, b) = (Sha1::digest(presented), Sha1::digest(expected));
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::IntoFuture;
+
+    use axum::{Router, body::Body};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::AppConfig;
+
+    const KEY: &str = "s3cret";
+
+    fn state(api_key: Option<&str>) -> AppState {
+        AppState::for_tests(AppConfig::default(), api_key)
+    }
+
+    fn app(api_key: Option<&str>) -> Router {
+        crate::routes::app(state(api_key))
+    }
+
+    async fn send(
+        app: &Router,
+        method: &str,
+        path: &str,
+        auth: Option<(&str, &str)>,
+        body: Option<serde_json::Value>,
+    ) -> Response {
+        let mut req = Request::builder().method(method).uri(path);
+        if let Some((name, value)) = auth {
+            req = req.header(name, value);
+        }
+        let body = match body {
+            Some(json) => {
+                req = req.header(header::CONTENT_TYPE, "application/json");
+                Body::from(json.to_string())
+            }
+            None => Body::empty(),
+        };
+        app.clone().oneshot(req.body(body).unwrap()).await.unwrap()
+    }
+
+    async fn get(app: &Router, path: &str, auth: Option<(&str, &str)>) -> Response {
+        send(app, "GET", path, auth, None).await
+    }
+
+    async fn json_body(response: Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    /// Status of a real WebSocket handshake against `path`.
+    async fn ws_handshake(path: &str, auth: Option<(&str, &str)>) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, app(Some(KEY))).into_future());
+
+        let mut req = format!(
+            "GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: Upgrade\r\n\
+             Upgrade: websocket\r\nSec-WebSocket-Version: 13\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n"
+        );
+        if let Some((name, value)) = auth {
+            req += &format!("{name}: {value}\r\n");
+        }
+        req += "\r\n";
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream.write_all(req.as_bytes()).await.unwrap();
+        let mut buf = [0u8; 64];
+        let n = stream.read(&mut buf).await.unwrap();
+        // "HTTP/1.1 101 Switching Protocols"
+        std::str::from_utf8(&buf[..n]).unwrap()[9..12]
+            .parse()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn protected_routes_require_the_key() {
+        let app = app(Some(KEY));
+        for path in ["/v1/models", "/api/models/loaded"] {
+            let missing = get(&app, path, None).await;
+            assert_eq!(missing.status(), StatusCode::UNAUTHORIZED, "{path}");
+            assert_eq!(json_body(missing).await["error"]["code"], "invalid_api_key");
+
+            let wrong = get(&app, path, Some(("authorization", "Bearer nope"))).await;
+            assert_eq!(wrong.status(), StatusCode::UNAUTHORIZED, "{path}");
+            let ok = get(&app, path, Some(("authorization", "Bearer s3cret"))).await;
+            assert_eq!(ok.status(), StatusCode::OK, "{path}");
+            let ok = get(&app, path, Some(("x-api-key", KEY))).await;
+            assert_eq!(ok.status(), StatusCode::OK, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn health_and_unconfigured_servers_stay_open() {
+        let app_with_key = app(Some(KEY));
+        assert_eq!(
+            get(&app_with_key, "/health", None).await.status(),
+            StatusCode::OK
+        );
+        let open = app(None);
+        assert_eq!(
+            get(&open, "/api/models/loaded", None).await.status(),
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn websocket_upgrade_requires_the_key() {
+        let bearer = |key| ("authorization", format!("Bearer {key}"));
+        for path in ["/ws/events", "/ws/generate"] {
+            let (name, nope) = bearer("nope");
+            assert_eq!(ws_handshake(path, Some((name, &nope))).await, 401, "{path}");
+            let (name, key) = bearer(KEY);
+            assert_eq!(ws_handshake(path, Some((name, &key))).await, 101, "{path}");
+            // Browsers can't set headers, so upgrades take the key as a query parameter
+            let query = format!("{path}?api_key={KEY}");
+            assert_eq!(ws_handshake(&query, None).await, 101, "{path}");
+            let query = format!("{path}?api_key=nope");
+            assert_eq!(ws_handshake(&query, None).await, 401, "{path}");
+        }
+        assert_eq!(ws_handshake("/ws/generate", None).await, 401);
+        // Accepted, then authenticated by the first message
+        assert_eq!(ws_handshake("/ws/events", None).await, 101);
+        // The query parameter is for upgrades only
+        let plain = get(&app(Some(KEY)), &format!("/v1/models?api_key={KEY}"), None).await;
+        assert_eq!(plain.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn table_keys_are_scoped_and_revocable() {
+        let app = app(None);
+        let create =
+            |label: &str, scope: &str| serde_json::json!({ "label": label, "scopes": [scope] });
+
+        // The first key is created while auth is still off
+        let created = send(
+            &app,
+            "POST",
+            "/api/keys",
+            None,
+            Some(create("ops", "admin")),
+        )
+        .await;
+        assert_eq!(created.status(), StatusCode::CREATED);
+        let created = json_body(created).await;
+        let admin_id = created["id"].as_str().unwrap().to_string();
+        let admin_key = created["key"].as_str().unwrap().to_string();
+        let admin = ("authorization", format!("Bearer {admin_key}"));
+        let admin = Some((admin.0, admin.1.as_str()));
+        assert_eq!(
+            get(&app, "/api/models/loaded", None).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let created = send(
+            &app,
+            "POST",
+            "/api/keys",
+            admin,
+            Some(create("ci", "inference")),
+        )
+        .await;
+        let created = json_body(created).await;
+        let (id, key) = (
+            created["id"].as_str().unwrap(),
+            created["key"].as_str().unwrap(),
+        );
+        let inference = Some(("x-api-key", key));
+        assert_eq!(
+            get(&app, "/v1/models", inference).await.status(),
+            StatusCode::OK
+        );
+        let forbidden = get(&app, "/api/models/loaded", inference).await;
+        assert_eq!(forbidden.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            json_body(forbidden).await["error"]["code"],
+            "insufficient_scope"
+        );
+        assert_eq!(
+            get(&app, "/v1/models", admin).await.status(),
+            StatusCode::OK
+        );
+
+        let listed = json_body(get(&app, "/api/keys", admin).await).await;
+        assert_eq!(listed.as_array().unwrap().len(), 2);
+        assert!(listed[0].get("key").is_none());
+
+        let path = format!("/api/keys/{id}");
+        let revoked = send(&app, "DELETE", &path, admin, None).await;
+        assert_eq!(revoked.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            get(&app, "/v1/models", inference).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+        let again = send(&app, "DELETE", &path, admin, None).await;
+        assert_eq!(again.status(), StatusCode::NOT_FOUND);
+
+        // Revoking the last key would turn auth back off
+        let last = send(
+            &app,
+            "DELETE",
+            &format!("/api/keys/{admin_id}"),
+            admin,
+            None,
+        )
+        .await;
+        assert_eq!(last.status(), StatusCode::CONFLICT);
+        assert_eq!(
+            get(&app, "/api/models/loaded", None).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get(&app, "/api/models/loaded", admin).await.status(),
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn legacy_key_acts_as_admin_next_to_table_keys() {
+        let state = state(Some(KEY));
+        let app = crate::routes::app(state.clone());
+        state.api_keys().insert(
+            crate::services::api_keys::hash_key("sk-lld-table"),
+            crate::services::api_keys::ActiveKey {
+                id: "k1".into(),
+                scopes: vec![Scope::Inference],
+            },
+        );
+        let legacy = Some(("authorization", "Bearer s3cret"));
+        assert_eq!(
+            get(&app, "/api/models/loaded", legacy).await.status(),
+            StatusCode::OK
+        );
+        let table = Some(("authorization", "Bearer sk-lld-table"));
+        assert_eq!(
+            get(&app, "/v1/models", table).await.status(),
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn key_comparison() {
+        assert!(keys_match(KEY, KEY));
+        assert!(!keys_match("s3cre", KEY));
+        assert!(!keys_match("", KEY));
+    }
+}
@@ -1,3 +1,28 @@
-//! HTTP middleware (Phase 1: placeholder).
-//!
-//! Phase 2 will add bearer-token auth and request logging middleware.
+//! HTTP middleware.
+
+pub mod auth;
+pub mod rate_limit;
+pub mod request_id;
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+
+/// An error in the OpenAI envelope, so SDK clients can parse rejections
+/// from middleware like any other API error.
+fn error_response(status: StatusCode, message: &str, error_type: &str, code: &str) -> Response {
+    (
+        status,
+        Json(serde_json::json!({
+            "error": {
+                "message": message,
+                "type": error_type,
+                "param": null,
+                "code": code,
+            }
+        })),
+    )
+        .into_response()
+}
@@ -0,0 +1,125 @@
+//! Per-client rate limiting.
+//!
+//! Runs after authentication: requests are counted against their API
+//! key id, or the client IP when there is none.  Model load/unload
+//! endpoints draw from a separate bucket.  Each request is counted once
+//! at admission, however long its response streams.
+
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::Response,
+};
+
+use super::auth::ApiKeyId;
+use super::error_response;
+use crate::services::rate_limit::BucketKind;
+use crate::state::AppState;
+
+/// Routes limited by the model-ops bucket.
+const MODEL_OPS_ROUTES: &[&str] = &[
+    "/api/models/{id}/load",
+    "/api/models/{id}/unload",
+    "/api/models/load-by-path",
+    "/api/models/unload-all",
+];
+
+pub async fn rate_limit(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let kind = match req.extensions().get::<MatchedPath>() {
+        Some(path) if MODEL_OPS_ROUTES.contains(&path.as_str()) => BucketKind::ModelOps,
+        _ => BucketKind::General,
+    };
+    let client = if let Some(ApiKeyId(id)) = req.extensions().get::<ApiKeyId>() {
+        format!("key:{id}")
+    } else if let Some(ConnectInfo(addr)) = req.extensions().get::<ConnectInfo<SocketAddr>>() {
+        format!("ip:{}", addr.ip())
+    } else {
+        "unknown".to_string()
+    };
+
+    match state.rate_limiter().check(kind, &client) {
+        Ok(()) => next.run(req).await,
+        Err(retry_after) => {
+            let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            let mut response = error_response(
+                StatusCode::TOO_MANY_REQUESTS,
+                &format!("Rate limit exceeded; retry in {secs}s"),
+                "rate_limit_error",
+                "rate_limit_exceeded",
+            );
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, body::Body, extract::connect_info::MockConnectInfo};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::AppConfig;
+
+    fn app(general: u32, model_ops: u32) -> Router {
+        let config = AppConfig {
+            rate_limit_per_minute: general,
+            model_ops_rate_limit_per_minute: model_ops,
+            ..AppConfig::default()
+        };
+        crate::routes::app(AppState::for_tests(config, None))
+            .layer(MockConnectInfo(SocketAddr::from(([192, 168, 1, 7], 50000))))
+    }
+
+    async fn send(app: &Router, method: &str, path: &str) -> Response {
+        let req = Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn exhausted_bucket_returns_429_with_retry_after() {
+        let app = app(2, 0);
+        for _ in 0..2 {
+            assert_eq!(
+                send(&app, "GET", "/v1/models").await.status(),
+                StatusCode::OK
+            );
+        }
+        let limited = send(&app, "GET", "/v1/models").await;
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(limited.headers()[header::RETRY_AFTER], "30");
+        let body = axum::body::to_bytes(limited.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], "rate_limit_exceeded");
+
+        // The general bucket is shared across routes; /health is exempt
+        let limited = send(&app, "GET", "/api/models/loaded").await;
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(send(&app, "GET", "/health").await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn model_ops_have_their_own_bucket() {
+        let app = app(0, 1);
+        let unload = "/api/models/foo/unload";
+        assert_eq!(send(&app, "POST", unload).await.status(), StatusCode::OK);
+        let limited = send(&app, "POST", unload).await;
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(limited.headers()[header::RETRY_AFTER], "60");
+        assert_eq!(
+            send(&app, "GET", "/v1/models").await.status(),
+            StatusCode::OK
+        );
+    }
+}
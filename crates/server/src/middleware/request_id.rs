@@ -0,0 +1,217 @@
+//! Request ids and per-request tracing spans.
+//!
+//! Every request gets an id, taken from an incoming `X-Request-Id` header
+//! or generated, that is echoed in the response header, added to error
+//! bodies and to the WebSocket events broadcast while handling the
+//! request.  The request runs inside a `request` span; handlers record
+//! the model and token counts on it, and with span-close logging enabled
+//! its close line summarises the request.
+
+use std::future::Future;
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::Request,
+    http::{HeaderName, HeaderValue, header},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{Instrument, field::Empty};
+
+pub static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest incoming id that is honoured.
+const MAX_ID_LEN: usize = 128;
+/// Largest error body rewritten to include the id.
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+/// Id of the request being handled by this task, if any.
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|id| id.clone()).ok()
+}
+
+/// Run `fut` as part of request `id`, e.g. in a task spawned by a handler.
+pub async fn with_request_id<F: Future>(id: Option<String>, fut: F) -> F::Output {
+    match id {
+        Some(id) => CURRENT.scope(id, fut).await,
+        None => fut.await,
+    }
+}
+
+pub async fn request_id(req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(&X_REQUEST_ID)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| is_valid_id(v))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+
+    let span = tracing::info_span!(
+        "request",
+        id = %id,
+        method = %req.method(),
+        endpoint = %req.uri().path(),
+        status = Empty,
+        model = Empty,
+        response_id = Empty,
+        prompt_tokens = Empty,
+        completion_tokens = Empty,
+    );
+    let response = CURRENT
+        .scope(id.clone(), next.run(req))
+        .instrument(span.clone())
+        .await;
+    span.record("status", response.status().as_u16());
+
+    let mut response = if response.status().is_client_error() || response.status().is_server_error()
+    {
+        add_id_to_error(response, &id).await
+    } else {
+        response
+    };
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(X_REQUEST_ID.clone(), value);
+    }
+    response
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Put `id` into a JSON error body (inside `error` when present) or
+/// append it to a plain-text one.  Bodies of unknown or too large a size
+/// are passed through untouched; the id is still in the header.
+async fn add_id_to_error(response: Response, id: &str) -> Response {
+    let length = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<u64>().ok())
+        .or_else(|| response.body().size_hint().exact());
+    if length.is_none_or(|len| len > MAX_ERROR_BODY as u64) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, MAX_ERROR_BODY).await else {
+        tracing::warn!(request_id = id, "Failed to read the error body");
+        return Response::from_parts(parts, Body::empty());
+    };
+    let is_json = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+
+    let bytes = if is_json && let Ok(mut json) = serde_json::from_slice::<serde_json::Value>(&bytes)
+    {
+        let target = match json.get_mut("error") {
+            Some(error) if error.is_object() => error,
+            _ => &mut json,
+        };
+        if let Some(obj) = target.as_object_mut() {
+            obj.insert("request_id".into(), id.into());
+        }
+        serde_json::to_vec(&json).unwrap_or_default().into()
+    } else if !bytes.is_empty() && std::str::from_utf8(&bytes).is_ok() {
+        format!("{} (request id: {id})", String::from_utf8_lossy(&bytes)).into()
+    } else {
+        bytes
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, http::StatusCode, routing::get};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/json",
+                get(|| async {
+                    (
+                        StatusCode::NOT_FOUND,
+                        axum::Json(serde_json::json!({ "error": { "message": "nope" } })),
+                    )
+                }),
+            )
+            .route(
+                "/text",
+                get(|| async { (StatusCode::BAD_REQUEST, "bad input".to_string()) }),
+            )
+            .route(
+                "/large",
+                get(|| async { (StatusCode::BAD_GATEWAY, "x".repeat(MAX_ERROR_BODY + 1)) }),
+            )
+            .route(
+                "/stream",
+                get(|| async {
+                    let chunks = futures_util::stream::iter([Ok::<_, std::io::Error>("partial")]);
+                    (StatusCode::BAD_GATEWAY, Body::from_stream(chunks))
+                }),
+            )
+            .route("/ok", get(|| async { current().unwrap_or_default() }))
+            .layer(axum::middleware::from_fn(request_id))
+    }
+
+    async fn call(path: &str, incoming: Option<&str>) -> (String, String) {
+        let mut req = Request::get(path);
+        if let Some(id) = incoming {
+            req = req.header(&X_REQUEST_ID, id);
+        }
+        let response = app()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let id = response.headers()[&X_REQUEST_ID]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (id, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn incoming_id_is_honoured_and_visible_to_handlers() {
+        let (id, body) = call("/ok", Some("trace-42")).await;
+        assert_eq!(id, "trace-42");
+        assert_eq!(body, "trace-42");
+
+        let (generated, _) = call("/ok", Some("has space")).await;
+        assert_eq!(generated.len(), 32);
+    }
+
+    #[tokio::test]
+    async fn error_bodies_carry_the_id() {
+        let (id, body) = call("/json", None).await;
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["error"]["request_id"], id.as_str());
+        assert_eq!(json["error"]["message"], "nope");
+
+        let (id, body) = call("/text", Some("abc")).await;
+        assert_eq!(id, "abc");
+        assert_eq!(body, "bad input (request id: abc)");
+    }
+
+    #[tokio::test]
+    async fn large_or_unsized_error_bodies_pass_through() {
+        let (id, body) = call("/large", Some("abc")).await;
+        assert_eq!(id, "abc");
+        assert_eq!(body.len(), MAX_ERROR_BODY + 1);
+
+        let (id, body) = call("/stream", Some("abc")).await;
+        assert_eq!(id, "abc");
+        assert_eq!(body, "partial");
+    }
+}
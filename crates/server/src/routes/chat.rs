@@ -0,0 +1,579 @@
+//! Chat session API: /api/chat/sessions
+//!
+//! Conversations are stored in `chat_sessions` / `chat_history`.  Posting
+//! a message saves it before generation starts, so a failed or crashed
+//! generation never loses the user's side; the reply is saved when
+//! generation ends, even if the client disconnected meanwhile.
+
+use std::convert::Infallible;
+
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::{DefaultBodyLimit, Extension, Path, Query, State},
+    http::StatusCode,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{delete, get, post},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{error, info};
+
+use crate::db::{ChatMessageRecord, ChatSession};
+use crate::middleware::auth::ApiKeyId;
+use crate::routes::openai::{render_chat_prompt, require_capability, resolve_model};
+use crate::services::chat_export::{ExportFormat, export_stream, parse_import};
+use crate::services::inference::{generation_timeout, spawn_generation};
+use crate::services::request_log::{RequestMeta, finish_reason_name};
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/chat/sessions",
+            get(list_sessions).post(create_session),
+        )
+        .route(
+            "/api/chat/sessions/{id}",
+            get(get_session).delete(delete_session),
+        )
+        .route(
+            "/api/chat/sessions/{id}/messages",
+            post(post_message).get(list_messages),
+        )
+        .route("/api/chat/sessions/{id}/export", get(export_session))
+        .route(
+            "/api/chat/import",
+            post(import_sessions).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+        )
+        .route("/api/chat/history", delete(prune_history))
+}
+
+type ApiError = (StatusCode, String);
+
+/// Body limit of `/api/chat/import`, above the general `max_body_bytes`:
+/// a ChatGPT `conversations.json` easily runs to hundreds of megabytes.
+const IMPORT_BODY_LIMIT: usize = 512 * 1024 * 1024;
+
+fn db_error(e: crate::db::DbError) -> ApiError {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+/// Event telling open dashboards to reload their session lists; `data`
+/// carries a `reason` plus details.
+const SESSIONS_CHANGED: &str = "chat.sessions_changed";
+
+fn session_not_found(id: i64) -> ApiError {
+    (
+        StatusCode::NOT_FOUND,
+        format!("Chat session {id} not found"),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateSessionRequest {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    model: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PruneQuery {
+    /// RFC 3339 timestamp or `YYYY-MM-DD`; sessions last active before it.
+    before: Option<String>,
+    /// Only sessions whose model is this id.
+    model: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+#[derive(Deserialize)]
+struct PostMessageRequest {
+    content: String,
+    /// Overrides the session's model for this and later replies.
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default = "default_true")]
+    stream: bool,
+    #[serde(default = "default_max_tokens")]
+    max_tokens: u32,
+    #[serde(flatten)]
+    sampling: llama_core::SamplingParams,
+}
+
+fn default_true() -> bool {
+    true
+}
+fn default_max_tokens() -> u32 {
+    2048
+}
+
+/// Progress of a reply, sent as SSE `data:` payloads.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ReplyEvent {
+    Token {
+        content: String,
+    },
+    /// The saved reply; the last event of a successful stream.
+    Done {
+        message: ChatMessageRecord,
+    },
+    /// Generation failed; a partial reply is still saved.
+    Error {
+        message: String,
+        partial: Option<ChatMessageRecord>,
+    },
+}
+
+//  Handlers
+
+/// GET /api/chat/sessions — most recently active first
+async fn list_sessions(State(state): State<AppState>) -> Result<Json<Vec<ChatSession>>, ApiError> {
+    state.db().list_chat_sessions().map(Json).map_err(db_error)
+}
+
+/// POST /api/chat/sessions
+async fn create_session(
+    State(state): State<AppState>,
+    Json(req): Json<CreateSessionRequest>,
+) -> Result<(StatusCode, Json<ChatSession>), ApiError> {
+    let title = req
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .unwrap_or("New chat");
+    let session = state
+        .db()
+        .create_chat_session(title, req.model.as_deref())
+        .map_err(db_error)?;
+    state.broadcast_event(
+        SESSIONS_CHANGED,
+        serde_json::json!({ "reason": "created", "id": session.id }),
+    );
+    Ok((StatusCode::CREATED, Json(session)))
+}
+
+/// GET /api/chat/sessions/:id
+async fn get_session(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<ChatSession>, ApiError> {
+    state
+        .db()
+        .get_chat_session(id)
+        .map_err(db_error)?
+        .map(Json)
+        .ok_or_else(|| session_not_found(id))
+}
+
+/// DELETE /api/chat/sessions/:id — removes its messages too
+async fn delete_session(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let deleted = state.db().delete_chat_session(id).map_err(db_error)?;
+    if deleted.sessions == 0 {
+        return Err(session_not_found(id));
+    }
+    state.broadcast_event(
+        SESSIONS_CHANGED,
+        serde_json::json!({ "reason": "deleted", "id": id, "deleted": deleted }),
+    );
+    Ok(Json(serde_json::json!({ "id": id, "deleted": deleted })))
+}
+
+/// DELETE /api/chat/history?before=&model= — bulk-delete sessions last
+/// active before a date and/or using a model
+async fn prune_history(
+    State(state): State<AppState>,
+    Query(query): Query<PruneQuery>,
+) -> Result<Json<crate::db::ChatDeletion>, ApiError> {
+    if query.before.is_none() && query.model.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Specify `before` and/or `model`".to_string(),
+        ));
+    }
+    let before = match &query.before {
+        None => None,
+        Some(v) => Some(crate::routes::usage::parse_bound(v, false).ok_or((
+            StatusCode::BAD_REQUEST,
+            format!("Invalid date '{v}': expected RFC 3339 or YYYY-MM-DD"),
+        ))?),
+    };
+    let deleted = state
+        .db()
+        .prune_chat_sessions(before, query.model.as_deref())
+        .map_err(db_error)?;
+    info!(
+        sessions = deleted.sessions,
+        messages = deleted.messages,
+        "Pruned chat history"
+    );
+    if deleted.sessions > 0 {
+        state.broadcast_event(
+            SESSIONS_CHANGED,
+            serde_json::json!({ "reason": "pruned", "deleted": deleted }),
+        );
+    }
+    Ok(Json(deleted))
+}
+
+/// GET /api/chat/sessions/:id/messages
+async fn list_messages(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<ChatMessageRecord>>, ApiError> {
+    if state.db().get_chat_session(id).map_err(db_error)?.is_none() {
+        return Err(session_not_found(id));
+    }
+    state
+        .db()
+        .list_chat_messages(id)
+        .map(Json)
+        .map_err(db_error)
+}
+
+/// GET /api/chat/sessions/:id/export?format=json|markdown
+///
+/// Streamed, so long sessions are never held in memory at once.
+async fn export_session(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, ApiError> {
+    let session = state
+        .db()
+        .get_chat_session(id)
+        .map_err(db_error)?
+        .ok_or_else(|| session_not_found(id))?;
+    let format = query.format;
+    let body = Body::from_stream(export_stream(session, format, move |after_id, limit| {
+        state.db().chat_messages_after(id, after_id, limit)
+    }));
+    let disposition = format!("attachment; filename=\"chat-{id}.{}\"", format.extension());
+    Ok((
+        [
+            (
+                axum::http::header::CONTENT_TYPE,
+                format.content_type().to_string(),
+            ),
+            (axum::http::header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// POST /api/chat/import — recreate sessions from an export (ours, or a
+/// ChatGPT `conversations.json`)
+async fn import_sessions(
+    State(state): State<AppState>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<(StatusCode, Json<Vec<ChatSession>>), ApiError> {
+    let chats = parse_import(body).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let mut sessions = Vec::with_capacity(chats.len());
+    for chat in &chats {
+        sessions.push(state.db().import_chat_session(chat).map_err(db_error)?);
+    }
+    info!(count = sessions.len(), "Imported chat sessions");
+    let ids: Vec<i64> = sessions.iter().map(|s| s.id).collect();
+    state.broadcast_event(
+        SESSIONS_CHANGED,
+        serde_json::json!({ "reason": "imported", "ids": ids }),
+    );
+    Ok((StatusCode::CREATED, Json(sessions)))
+}
+
+/// POST /api/chat/sessions/:id/messages — append a user message and
+/// generate the reply (SSE unless `stream: false`).
+async fn post_message(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    api_key: Option<Extension<ApiKeyId>>,
+    Json(req): Json<PostMessageRequest>,
+) -> Response {
+    let api_key_id = api_key.map(|Extension(ApiKeyId(id))| id);
+    match start_reply(&state, id, req, api_key_id).await {
+        Ok(reply) => reply,
+        Err(e) => e,
+    }
+}
+
+async fn start_reply(
+    state: &AppState,
+    id: i64,
+    req: PostMessageRequest,
+    api_key_id: Option<String>,
+) -> Result<Response, Response> {
+    let session = state
+        .db()
+        .get_chat_session(id)
+        .map_err(|e| db_error(e).into_response())?
+        .ok_or_else(|| session_not_found(id).into_response())?;
+    if req.content.trim().is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Message content is empty".to_string(),
+        )
+            .into_response());
+    }
+
+    let model_name = req.model.as_deref().or(session.model_id.as_deref());
+    let loaded = resolve_model(state, model_name).await?;
+    require_capability(&loaded, gguf_parser::Capability::Chat)
+        .map_err(IntoResponse::into_response)?;
+    let model_id = loaded.id.clone();
+
+    // Saved before generation so that it survives a failed reply.
+    let user_tokens = loaded
+        .model
+        .vocab()
+        .tokenize(&req.content, false, true)
+        .ok()
+        .map(|t| t.len() as u32);
+    let user_message = state
+        .db()
+        .insert_chat_message(id, &model_id, "user", &req.content, user_tokens, None)
+        .map_err(|e| db_error(e).into_response())?;
+
+    let history: Vec<llama_core::ChatMessage> = state
+        .db()
+        .list_chat_messages(id)
+        .map_err(|e| db_error(e).into_response())?
+        .into_iter()
+        .map(|m| llama_core::ChatMessage {
+            role: m.role,
+            content: m.content,
+        })
+        .collect();
+    let prompt = render_chat_prompt(&loaded.model, &history);
+    let tokens = loaded.model.vocab().tokenize_prompt(&prompt).map_err(|e| {
+        (StatusCode::BAD_REQUEST, format!("Tokenization failed: {e}")).into_response()
+    })?;
+
+    let gen_req = llama_core::GenerateRequest {
+        tokens,
+        max_tokens: req.max_tokens,
+        stop_words: Vec::new(),
+        sampling_params: req.sampling,
+        return_special: false,
+        ignore_eos: false,
+    };
+    let meta = RequestMeta {
+        endpoint: "/api/chat/sessions/{id}/messages",
+        user: None,
+        prompt: state.request_log().logs_prompts().then_some(prompt),
+        api_key_id,
+        timeout: generation_timeout(state.config().generation_timeout_secs, None),
+        cancel: None,
+    };
+    let rx = spawn_generation(state, loaded, gen_req, meta);
+
+    let (events_tx, mut events_rx) = mpsc::channel(64);
+    tokio::spawn(save_reply(state.clone(), id, model_id, rx, events_tx));
+
+    if req.stream {
+        let stream = ReceiverStream::new(events_rx).map(|event| {
+            Ok::<_, Infallible>(
+                Event::default().data(serde_json::to_string(&event).unwrap_or_default()),
+            )
+        });
+        return Ok(Sse::new(stream)
+            .keep_alive(KeepAlive::default())
+            .into_response());
+    }
+
+    while let Some(event) = events_rx.recv().await {
+        match event {
+            ReplyEvent::Token { .. } => {}
+            ReplyEvent::Done { message } => {
+                return Ok(Json(serde_json::json!({
+                    "user_message": user_message,
+                    "assistant_message": message,
+                }))
+                .into_response());
+            }
+            ReplyEvent::Error { message, partial } => {
+                return Ok((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({
+                        "error": message,
+                        "user_message": user_message,
+                        "assistant_message": partial,
+                    })),
+                )
+                    .into_response());
+            }
+        }
+    }
+    Err((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Generation ended unexpectedly".to_string(),
+    )
+        .into_response())
+}
+
+/// Relay generation events to the client and save the reply when
+/// generation ends.  Keeps draining after the client goes away, so the
+/// full reply is still stored.
+async fn save_reply(
+    state: AppState,
+    session_id: i64,
+    model_id: String,
+    mut rx: mpsc::Receiver<llama_core::GenerateEvent>,
+    events: mpsc::Sender<ReplyEvent>,
+) {
+    let mut text = String::new();
+    let mut pieces = 0;
+    let mut outcome: Option<Result<(&str, u32), String>> = None;
+    while let Some(event) = rx.recv().await {
+        match event {
+            llama_core::GenerateEvent::Token(piece) => {
+                text.push_str(&piece);
+                pieces += 1;
+                let _ = events.send(ReplyEvent::Token { content: piece }).await;
+            }
+            llama_core::GenerateEvent::Done {
+                finish_reason,
+                completion_tokens,
+                ..
+            } => {
+                outcome = Some(Ok((finish_reason_name(&finish_reason), completion_tokens)));
+            }
+            llama_core::GenerateEvent::Error(e) => {
+                error!(session_id, "Chat generation error: {e}");
+                outcome = Some(Err(e.message));
+                break;
+            }
+        }
+    }
+
+    let (finish_reason, tokens) = match &outcome {
+        Some(Ok((reason, tokens))) => (*reason, *tokens),
+        Some(Err(_)) => ("error", pieces),
+        None => ("cancelled", pieces),
+    };
+    let saved = if outcome.as_ref().is_some_and(|o| o.is_ok()) || !text.is_empty() {
+        match state.db().insert_chat_message(
+            session_id,
+            &model_id,
+            "assistant",
+            &text,
+            Some(tokens),
+            Some(finish_reason),
+        ) {
+            Ok(message) => Some(message),
+            Err(e) => {
+                error!(session_id, "Failed to save chat reply: {e}");
+                let _ = events
+                    .send(ReplyEvent::Error {
+                        message: format!("Failed to save reply: {e}"),
+                        partial: None,
+                    })
+                    .await;
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
+    let event = match (outcome, saved) {
+        (Some(Ok(_)), Some(message)) => ReplyEvent::Done { message },
+        (Some(Err(e)), partial) => ReplyEvent::Error {
+            message: e,
+            partial,
+        },
+        (_, partial) => ReplyEvent::Error {
+            message: "Generation ended without finishing".to_string(),
+            partial,
+        },
+    };
+    let _ = events.send(event).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+    use axum::http::{Request, header};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn export(app: Router, id: i64, format: &str) -> (StatusCode, String, String, String) {
+        let uri = format!("/api/chat/sessions/{id}/export?format={format}");
+        let resp = app
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .map(|v| v.to_str().unwrap().to_string())
+                .unwrap_or_default()
+        };
+        let (content_type, disposition) = (
+            header(header::CONTENT_TYPE),
+            header(header::CONTENT_DISPOSITION),
+        );
+        let status = resp.status();
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (
+            status,
+            content_type,
+            disposition,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn export_sets_type_file_name_and_body_per_format() {
+        let state = AppState::for_tests(Default::default(), None);
+        let session = state.db().create_chat_session("Export", None).unwrap();
+        state
+            .db()
+            .insert_chat_message(session.id, "qwen", "user", "Hello", Some(1), None)
+            .unwrap();
+        let app = crate::routes::app(state);
+        let id = session.id;
+
+        let (status, content_type, disposition, body) = export(app.clone(), id, "json").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, "application/json");
+        assert_eq!(
+            disposition,
+            format!("attachment; filename=\"chat-{id}.json\"")
+        );
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["session"]["title"], "Export");
+        assert_eq!(json["messages"][0]["role"], "user");
+        assert_eq!(json["messages"][0]["content"], "Hello");
+
+        let (status, content_type, disposition, body) = export(app.clone(), id, "markdown").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, "text/markdown; charset=utf-8");
+        assert_eq!(
+            disposition,
+            format!("attachment; filename=\"chat-{id}.md\"")
+        );
+        assert!(body.starts_with("# Export\n"));
+        assert!(body.contains("## user · "));
+        assert!(body.contains("Hello"));
+
+        let (status, ..) = export(app, id + 1, "json").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
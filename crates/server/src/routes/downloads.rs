@@ -0,0 +1,106 @@
+//! Download API routes: /api/downloads
+
+use std::path::PathBuf;
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::get,
+};
+use serde::Deserialize;
+
+use crate::services::downloads::DownloadInfo;
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/downloads", get(list_downloads).post(start_download))
+        .route("/api/downloads/{id}", get(get_download))
+}
+
+#[derive(Debug, Deserialize)]
+struct StartDownloadRequest {
+    url: String,
+    /// Target file name (defaults to the last URL path segment).
+    filename: Option<String>,
+    /// Target model directory (defaults to the first configured one).
+    dir: Option<PathBuf>,
+}
+
+//  Handlers
+
+async fn list_downloads(State(state): State<AppState>) -> Json<Vec<DownloadInfo>> {
+    Json(state.downloads().list())
+}
+
+async fn get_download(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<DownloadInfo>, axum::http::StatusCode> {
+    state
+        .downloads()
+        .get(&id)
+        .map(Json)
+        .ok_or(axum::http::StatusCode::NOT_FOUND)
+}
+
+async fn start_download(
+    State(state): State<AppState>,
+    Json(req): Json<StartDownloadRequest>,
+) -> Result<(axum::http::StatusCode, Json<DownloadInfo>), (axum::http::StatusCode, String)> {
+    let bad_request = |msg: String| (axum::http::StatusCode::BAD_REQUEST, msg);
+
+    if !(req.url.starts_with("http://") || req.url.starts_with("https://")) {
+        return Err(bad_request("URL must be http:// or https://".into()));
+    }
+    let filename = match req.filename {
+        Some(name) => name,
+        None => req
+            .url
+            .split(['?', '#'])
+            .next()
+            .and_then(|u| u.rsplit('/').next())
+            .unwrap_or_default()
+            .to_string(),
+    };
+    if filename.is_empty()
+        || filename.contains(['/', '\\'])
+        || filename.starts_with('.')
+        || !filename.to_lowercase().ends_with(".gguf")
+    {
+        return Err(bad_request(format!(
+            "Invalid file name '{filename}': expected a plain *.gguf name"
+        )));
+    }
+
+    let dirs = state.model_manager().model_dirs();
+    let dir = match req.dir {
+        Some(dir) if dirs.contains(&dir) => dir,
+        Some(dir) => {
+            return Err((
+                axum::http::StatusCode::FORBIDDEN,
+                format!("'{}' is not a configured model directory", dir.display()),
+            ));
+        }
+        None => dirs
+            .into_iter()
+            .next()
+            .ok_or_else(|| bad_request("No model directory configured to download into".into()))?,
+    };
+
+    let dest = dir.join(&filename);
+    if dest.exists() {
+        return Err((
+            axum::http::StatusCode::CONFLICT,
+            format!("'{}' already exists", dest.display()),
+        ));
+    }
+
+    let info = state.downloads().start(req.url, dest).map_err(|id| {
+        (
+            axum::http::StatusCode::CONFLICT,
+            format!("Download {id} is already writing to this file"),
+        )
+    })?;
+    Ok((axum::http::StatusCode::ACCEPTED, Json(info)))
+}
@@ -0,0 +1,195 @@
+//! Errors in the OpenAI envelope:
+//! `{"error": {"message", "type", "param", "code"}}`.
+//!
+//! [`ApiError`] converts from [`LlamaError`] and [`GenerateError`],
+//! taking its status and machine-readable `code` from the error, and from
+//! the `(StatusCode, String)` pairs the management helpers return, so
+//! handlers can mix them with `?`.
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use llama_core::{GenerateError, LlamaError};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub(crate) struct ErrorBody {
+    pub error: ErrorDetail,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ErrorDetail {
+    pub message: String,
+    pub r#type: String,
+    pub param: Option<String>,
+    pub code: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+    pub error_type: &'static str,
+    pub code: Option<&'static str>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>, error_type: &'static str) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            error_type,
+            code: None,
+        }
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// `err`'s status and code with a message giving more context.
+    pub fn llama(err: &LlamaError, message: impl Into<String>) -> Self {
+        let status =
+            StatusCode::from_u16(err.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        Self::new(status, message, error_type(status)).with_code(err.error_code())
+    }
+
+    /// The `error` object of the envelope.
+    pub(crate) fn detail(self) -> ErrorDetail {
+        ErrorDetail {
+            message: self.message,
+            r#type: self.error_type.to_string(),
+            param: None,
+            code: self.code.map(String::from),
+        }
+    }
+}
+
+/// `invalid_request_error` for 4xx, `server_error` otherwise.
+fn error_type(status: StatusCode) -> &'static str {
+    if status.is_client_error() {
+        "invalid_request_error"
+    } else {
+        "server_error"
+    }
+}
+
+impl From<LlamaError> for ApiError {
+    fn from(err: LlamaError) -> Self {
+        Self::llama(&err, err.to_string())
+    }
+}
+
+impl From<&GenerateError> for ApiError {
+    fn from(err: &GenerateError) -> Self {
+        let status = StatusCode::from_u16(err.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        Self {
+            code: err.code,
+            ..Self::new(status, err.message.clone(), error_type(status))
+        }
+    }
+}
+
+impl From<(StatusCode, String)> for ApiError {
+    fn from((status, message): (StatusCode, String)) -> Self {
+        Self::new(status, message, error_type(status))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status;
+        let body = ErrorBody {
+            error: self.detail(),
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+
+    use super::*;
+
+    async fn body(err: ApiError) -> (StatusCode, serde_json::Value) {
+        let resp = err.into_response();
+        let status = resp.status();
+        let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn llama_errors_carry_status_and_code() {
+        let full = LlamaError::KvCacheFull {
+            used: 4096,
+            capacity: 4096,
+        };
+        let (status, json) = body(full.into()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json["error"]["code"], "kv_cache_full");
+        assert_eq!(json["error"]["type"], "server_error");
+        assert!(
+            json["error"]["message"]
+                .as_str()
+                .unwrap()
+                .starts_with("KV cache is full: 4096 of 4096 tokens in use")
+        );
+        assert!(
+            json["error"]["message"]
+                .as_str()
+                .unwrap()
+                .ends_with("or enable context shifting")
+        );
+
+        let (status, json) = body(LlamaError::DecodeFailed(-3).into()).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(json["error"]["code"], "decode_failed");
+
+        let err = LlamaError::ModelLoadFailed {
+            path: "m.gguf".into(),
+            reason: "bad magic".into(),
+        };
+        let (status, json) = body(ApiError::llama(
+            &err,
+            format!("Failed to load model: {err}"),
+        ))
+        .await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert_eq!(json["error"]["code"], "invalid_model");
+        assert_eq!(
+            json["error"]["message"],
+            "Failed to load model: Failed to load model from 'm.gguf': bad magic"
+        );
+
+        let (status, json) = body(LlamaError::TokenizationFailed("bad utf-8".into()).into()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["error"]["code"], "tokenization_failed");
+        assert_eq!(json["error"]["type"], "invalid_request_error");
+
+        let (status, json) = body(LlamaError::InsufficientMemory("too big".into()).into()).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(json["error"]["code"], "insufficient_memory");
+
+        let (status, json) = body(LlamaError::LoadingPaused("upgrade".into()).into()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json["error"]["code"], "loading_paused");
+
+        let (status, json) = body(LlamaError::Other("oops".into()).into()).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(json["error"]["code"], "internal_error");
+    }
+
+    #[tokio::test]
+    async fn plain_errors_get_the_envelope() {
+        let err = ApiError::from((StatusCode::NOT_FOUND, "Model 'x' is not loaded".to_string()));
+        let (status, json) = body(err).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(json["error"]["message"], "Model 'x' is not loaded");
+        assert_eq!(json["error"]["type"], "invalid_request_error");
+        assert!(json["error"]["code"].is_null());
+    }
+}
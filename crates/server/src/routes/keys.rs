@@ -0,0 +1,102 @@
+//! API key management: /api/keys
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get},
+};
+use serde::Deserialize;
+
+use crate::db::ApiKeyRecord;
+use crate::services::api_keys::{self, ActiveKey, Scope};
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/keys", get(list_keys).post(create_key))
+        .route("/api/keys/{id}", delete(revoke_key))
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateKeyRequest {
+    label: String,
+    #[serde(default = "default_scopes")]
+    scopes: Vec<Scope>,
+}
+
+fn default_scopes() -> Vec<Scope> {
+    vec![Scope::Inference]
+}
+
+/// GET /api/keys — all keys, without their secrets
+async fn list_keys(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ApiKeyRecord>>, (StatusCode, String)> {
+    state
+        .db()
+        .list_api_keys()
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// POST /api/keys — create a key; the plaintext is only returned here
+async fn create_key(
+    State(state): State<AppState>,
+    Json(req): Json<CreateKeyRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, String)> {
+    let label = req.label.trim();
+    if label.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Label must not be empty".into()));
+    }
+    if req.scopes.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "At least one scope is required".into(),
+        ));
+    }
+    let mut scopes = req.scopes;
+    scopes.sort_by_key(|s| s.as_str());
+    scopes.dedup();
+
+    let key = api_keys::generate_key();
+    let hash = api_keys::hash_key(&key);
+    let id = uuid::Uuid::new_v4().to_string();
+    let record = state
+        .db()
+        .insert_api_key(&id, label, &hash, &api_keys::key_prefix(&key), &scopes)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    state.api_keys().insert(hash, ActiveKey { id, scopes });
+    tracing::info!(id = record.id, label, "Created API key");
+
+    let mut body = serde_json::json!(record);
+    body["key"] = key.into();
+    Ok((StatusCode::CREATED, Json(body)))
+}
+
+/// DELETE /api/keys/:id — revoke a key; the row is kept for usage history.
+/// The last key can't be revoked without a legacy `--api-key`, as that
+/// would turn auth off.
+async fn revoke_key(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if state.api_key().is_none() && state.api_keys().is_last(&id) {
+        return Err((
+            StatusCode::CONFLICT,
+            "Cannot revoke the last API key; it would turn authentication off. \
+             Create another key first"
+                .into(),
+        ));
+    }
+    let revoked = state
+        .db()
+        .revoke_api_key(&id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    state.api_keys().remove(&id);
+    if !revoked {
+        return Err((StatusCode::NOT_FOUND, format!("No active API key '{id}'")));
+    }
+    tracing::info!(id, "Revoked API key");
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,64 @@
+//! Log API: GET /api/logs — recent server and llama.cpp log lines, for
+//! the dashboard to show before `logs.line` events arrive.
+
+use axum::{Json, Router, extract::Query, routing::get};
+use serde::Deserialize;
+
+use crate::services::logs::{BUFFER_CAPACITY, LogLine, capture};
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/api/logs", get(recent_logs))
+}
+
+#[derive(Debug, Deserialize)]
+struct LogsQuery {
+    /// Number of lines, newest last.
+    #[serde(default = "default_tail")]
+    tail: usize,
+}
+
+fn default_tail() -> usize {
+    200
+}
+
+/// GET /api/logs?tail=200
+async fn recent_logs(Query(query): Query<LogsQuery>) -> Json<Vec<LogLine>> {
+    Json(capture().tail(query.tail.min(BUFFER_CAPACITY)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{Body, to_bytes};
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn returns_the_tail() {
+        let subscriber = tracing_subscriber::registry().with(capture().layer());
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..3 {
+                tracing::warn!("logs route test {i}");
+            }
+        });
+
+        let app = crate::routes::app(AppState::for_tests(Default::default(), None));
+        let req = Request::get("/api/logs?tail=2")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let lines: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let messages: Vec<&str> = lines
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|l| l["message"].as_str())
+            .collect();
+        assert_eq!(messages, ["logs route test 1", "logs route test 2"]);
+    }
+}
@@ -1,13 +1,21 @@
 //! Management API routes: /api/models, /api/config, /api/system
 
+use std::time::Duration;
+
 use axum::{
     Json, Router,
-    extract::{Path, State},
-    routing::{get, post, put},
+    extract::{Path, Query, State},
+    routing::{delete, get, post, put},
 };
 use serde::{Deserialize, Serialize};
-use tracing::{error, info};
+use serde_json::{Value, json};
+use tracing::{Instrument, error, info, warn};
 
+use crate::middleware::request_id::{current as current_request_id, with_request_id};
+use crate::routes::error::ApiError;
+use crate::routes::openapi::{Spec, json_body, json_response, param, schema_ref};
+use crate::services::model_manager::{ModelSettings, TokenizerInfo};
+use crate::services::tls::TlsPaths;
 use crate::state::AppState;
 
 pub fn router() -> Router<AppState> {
@@ -15,15 +23,43 @@ pub fn router() -> Router<AppState> {
         // Model management
         .route("/api/models", get(list_models))
         .route("/api/models/scan", post(scan_models))
+        .route("/api/adapters", get(list_adapters))
         .route("/api/models/loaded", get(list_loaded_models))
+        .route("/api/models/load-by-path", post(load_model_by_path))
         .route("/api/models/{id}/details", get(model_details))
         .route("/api/models/{id}/load", post(load_model))
         .route("/api/models/{id}/unload", post(unload_model))
+        .route("/api/models/unload-all", post(unload_all_models))
+        .route("/api/models/{id}/file", delete(delete_model_file))
+        .route("/api/models/{id}/status", get(model_status))
+        .route("/api/models/{id}/clear-error", post(clear_model_error))
+        .route("/api/models/{id}/estimate", get(estimate_model))
+        .route("/api/models/{id}/metadata", get(model_metadata))
+        .route("/api/models/{id}/classify", post(classify))
+        .route("/api/models/{id}/verify", post(verify_model))
+        .route(
+            "/api/models/{id}/bench",
+            get(list_bench_results).post(bench_model),
+        )
+        .route(
+            "/api/models/{id}/stats",
+            get(model_stats).delete(reset_model_stats),
+        )
         .route("/api/models/{id}/favorite", put(toggle_favorite))
+        .route("/api/models/{id}/pin", put(pin_model))
+        .route("/api/models/{id}/alias", put(set_alias))
+        .route("/api/models/{id}/name", put(set_display_name))
+        .route(
+            "/api/models/{id}/settings",
+            get(get_model_settings).put(update_model_settings),
+        )
+        .route("/api/aliases", get(list_aliases))
         // Config
         .route("/api/config", get(get_config).put(update_config))
         // System
         .route("/api/system/info", get(system_info))
+        .route("/api/system/resources", get(system_resources))
+        .route("/api/maintenance/resume", post(resume_maintenance))
 }
 
 //  Types
@@ -33,9 +69,23 @@ struct ModelEntry {
     id: String,
     filename: String,
     path: String,
+    /// `path` with symlinks resolved.
+    canonical_path: String,
     size: u64,
     architecture: Option<String>,
     parameters: Option<String>,
+    param_count: Option<u64>,
+    /// Average weight storage per parameter, from the tensor types.
+    bytes_per_param: Option<f64>,
+    /// SHA-256 of the model file, if recorded since it last changed.
+    hash: Option<String>,
+    /// What the model can serve: `chat`, `embeddings` or
+    /// `vision-projector`.
+    capabilities: Vec<gguf_parser::Capability>,
+    /// `base`, or `mmproj` for a vision projector no model was found for.
+    kind: gguf_parser::ModelKind,
+    /// Badges: `moe`, `vision`, `embedding`, `instruct`, `reasoning`.
+    features: Vec<gguf_parser::ModelFeature>,
     context_length: Option<u64>,
     file_type: Option<String>,
     quantization: Option<String>,
@@ -43,21 +93,236 @@ struct ModelEntry {
     status: &'static str,
     favorite: bool,
     alias: Option<String>,
+    display_name: Option<String>,
+    /// Another model shares this file name; `id` may carry a path hash.
+    collision: bool,
+    /// Part numbers of a split model that are missing on disk.
+    missing_parts: Vec<u16>,
+    /// `filename` when the metadata couldn't be read and the details are
+    /// guesses from the file name.
+    metadata_source: gguf_parser::MetadataSource,
+    /// Why the metadata couldn't be read.
+    scan_error: Option<String>,
+    /// Effective load settings (details endpoint only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    settings: Option<ModelSettings>,
+    /// Special tokens and template format (details endpoint only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tokenizer: Option<TokenizerInfo>,
+    /// Layer and head counts, RoPE and vocab type (details endpoint only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model_info: Option<llama_core::ModelInfo>,
+}
+
+/// A LoRA adapter found in the model directories.
+#[derive(Debug, Serialize)]
+struct AdapterEntry {
+    id: String,
+    filename: String,
+    path: String,
+    size: u64,
+    architecture: Option<String>,
+    /// The model the adapter was trained against, if recorded.
+    base_model: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct LoadModelRequest {
-    #[serde(default = "default_ctx_size")]
-    ctx_size: u32,
-    #[serde(default = "default_gpu_layers")]
-    n_gpu_layers: i32,
+    /// Overrides the model's stored settings and the global defaults.
+    #[serde(flatten)]
+    settings: ModelSettings,
+    /// Skip the pre-load memory check.
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct EstimateQuery {
+    #[serde(default)]
+    ctx_size: Option<u32>,
+    #[serde(default)]
+    n_gpu_layers: Option<i32>,
+    #[serde(default)]
+    flash_attn: Option<bool>,
+    #[serde(default)]
+    type_k: Option<llama_core::KvCacheType>,
+    #[serde(default)]
+    type_v: Option<llama_core::KvCacheType>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ListModelsQuery {
+    #[serde(default)]
+    sort: Option<ModelSort>,
+    /// Only models with this capability.
+    #[serde(default)]
+    capability: Option<gguf_parser::Capability>,
+    /// Only models with this feature.
+    #[serde(default)]
+    feature: Option<gguf_parser::ModelFeature>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ModelSort {
+    Favorite,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct MetadataQuery {
+    /// Include arrays longer than [`MAX_INLINE_ARRAY_LEN`] (e.g. the
+    /// tokenizer vocabulary) instead of eliding them.
+    #[serde(default)]
+    include_arrays: bool,
+    /// Also list the tensor infos and count tensors per type.
+    #[serde(default)]
+    tensors: bool,
+    /// Values in the type-tagged form (`{"Uint32": 32768}`) instead of
+    /// plain JSON.
+    #[serde(default)]
+    raw: bool,
+}
+
+/// Longest array returned by the metadata endpoint by default.
+const MAX_INLINE_ARRAY_LEN: usize = 64;
+
+#[derive(Debug, Deserialize)]
+struct BenchRequest {
+    /// Synthetic prompt length for the prompt-processing phase.
+    #[serde(default = "default_bench_pp")]
+    pp: u32,
+    /// Tokens generated in the token-generation phase.
+    #[serde(default = "default_bench_tg")]
+    tg: u32,
+    #[serde(default = "default_bench_repetitions")]
+    repetitions: u32,
+    /// Wait for an in-flight generation instead of failing with 409.
+    #[serde(default)]
+    force: bool,
+}
+
+fn default_bench_pp() -> u32 {
+    512
+}
+fn default_bench_tg() -> u32 {
+    128
+}
+fn default_bench_repetitions() -> u32 {
+    3
+}
+
+/// Upper bound on `repetitions`, to keep a run from hogging the model.
+const MAX_BENCH_REPETITIONS: u32 = 20;
+
+#[derive(Debug, Deserialize)]
+struct ClassifyRequest {
+    /// Texts to score, each on its own.
+    texts: Vec<String>,
+}
+
+/// Upper bound on `texts` per classify request.
+const MAX_CLASSIFY_TEXTS: usize = 256;
+
+#[derive(Debug, Default, Deserialize)]
+struct VerifyRequest {
+    /// Expected SHA-256 (single-file models only); defaults to the
+    /// recorded hash.
+    #[serde(default)]
+    sha256: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct LabelScore {
+    label: String,
+    /// Raw classifier output.
+    score: f32,
+    /// Softmax over the labels, or the sigmoid of a single output.
+    probability: f32,
 }
 
-fn default_ctx_size() -> u32 {
-    4096
+#[derive(Debug, Serialize)]
+struct Classification {
+    index: usize,
+    /// The highest-scoring label.
+    label: String,
+    scores: Vec<LabelScore>,
+    n_tokens: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetAliasRequest {
+    alias: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ScanQuery {
+    /// Read every file again instead of reusing stored scan results.
+    #[serde(default)]
+    force: bool,
 }
-fn default_gpu_layers() -> i32 {
-    -1
+
+#[derive(Debug, Default, Deserialize)]
+struct DeleteFileQuery {
+    /// Also delete the model's mmproj projector.
+    #[serde(default)]
+    include_companions: bool,
+    /// Only report what would be deleted.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct FileDeletion {
+    path: String,
+    bytes: u64,
+    deleted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetDisplayNameRequest {
+    display_name: Option<String>,
+}
+
+/// Longest accepted display name, in characters.
+const MAX_DISPLAY_NAME_LEN: usize = 128;
+
+#[derive(Debug, Serialize)]
+struct AliasEntry {
+    alias: String,
+    model_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PinRequest {
+    pinned: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct LoadQuery {
+    /// Return 202 immediately and load in the background.
+    #[serde(default, rename = "async")]
+    background: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct UnloadAllQuery {
+    /// Seconds to wait for in-flight requests before giving up on a model.
+    #[serde(default = "default_drain_timeout")]
+    timeout: u64,
+    reason: Option<String>,
+}
+
+fn default_drain_timeout() -> u64 {
+    30
+}
+
+#[derive(Debug, Deserialize)]
+struct LoadByPathRequest {
+    path: std::path::PathBuf,
+    #[serde(flatten)]
+    params: LoadModelRequest,
 }
 
 #[derive(Debug, Serialize)]
@@ -67,6 +332,18 @@ struct ConfigResponse {
     default_n_gpu_layers: i32,
     default_temperature: f64,
     api_key: Option<String>,
+    auto_load_models: bool,
+    allow_external_paths: bool,
+    rate_limit_per_minute: u32,
+    model_ops_rate_limit_per_minute: u32,
+    max_body_bytes: usize,
+    generation_timeout_secs: u64,
+    compression_enabled: bool,
+    tls_cert: Option<String>,
+    tls_key: Option<String>,
+    http_redirect_port: Option<u16>,
+    /// Saved strategy; see `SystemInfoResponse::numa` for the one in effect.
+    numa: llama_core::NumaStrategy,
 }
 
 #[derive(Debug, Deserialize)]
@@ -77,6 +354,26 @@ struct ConfigUpdate {
     default_n_gpu_layers: Option<i32>,
     default_temperature: Option<f64>,
     api_key: Option<String>,
+    auto_load_models: Option<bool>,
+    rate_limit_per_minute: Option<u32>,
+    model_ops_rate_limit_per_minute: Option<u32>,
+    max_body_bytes: Option<usize>,
+    generation_timeout_secs: Option<u64>,
+    compression_enabled: Option<bool>,
+    /// Empty string clears the path.
+    tls_cert: Option<String>,
+    tls_key: Option<String>,
+    http_redirect_port: Option<u16>,
+    /// Takes effect after a restart.
+    numa: Option<llama_core::NumaStrategy>,
+}
+
+/// llama.cpp capabilities of this build.
+#[derive(Debug, Serialize)]
+struct SupportFlags {
+    mmap: bool,
+    mlock: bool,
+    gpu_offload: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -84,18 +381,45 @@ struct SystemInfoResponse {
     version: String,
     models_loaded: usize,
     models_available: usize,
+    memory_used_bytes: u64,
+    max_memory_bytes: u64,
+    last_scan: crate::services::model_manager::ScanSummary,
+    /// GPU backends compiled into this binary.
+    compiled_backends: Vec<&'static str>,
+    /// GPU devices visible to ggml (empty without a usable GPU).
+    devices: Vec<llama_core::DeviceInfo>,
+    supports: SupportFlags,
+    /// NUMA strategy in effect, fixed at startup.
+    numa: llama_core::NumaStrategy,
     loaded_models: Vec<crate::services::model_manager::SlotInfo>,
+    /// Generations running or queued for a model's context.
+    generations_in_flight: usize,
 }
 
 //  Handlers
 
 /// GET /api/models — list all discovered models with status
-async fn list_models(State(state): State<AppState>) -> Json<Vec<ModelEntry>> {
+///
+/// With `?sort=favorite`, favorites come first.  LoRA adapters are listed
+/// under `/api/adapters` instead.
+async fn list_models(
+    State(state): State<AppState>,
+    Query(query): Query<ListModelsQuery>,
+) -> Result<Json<Vec<ModelEntry>>, ApiError> {
     let available = state.model_manager().scan_available();
     let loaded_ids = state.model_manager().loaded_model_ids();
+    let favorites = state
+        .db()
+        .list_favorites()
+        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let hashes = crate::services::integrity::recorded_hashes(state.db())
+        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    let entries: Vec<ModelEntry> = available
+    let mut entries: Vec<ModelEntry> = available
         .into_iter()
+        .filter(|m| m.kind != gguf_parser::ModelKind::LoraAdapter)
+        .filter(|m| query.capability.is_none_or(|c| m.capabilities.contains(&c)))
+        .filter(|m| query.feature.is_none_or(|f| m.features.contains(&f)))
         .map(|m| {
             let status = if loaded_ids.iter().any(|lid| lid.eq_ignore_ascii_case(&m.id)) {
                 "loaded"
@@ -106,27 +430,75 @@ async fn list_models(State(state): State<AppState>) -> Json<Vec<ModelEntry>> {
                 id: m.id.clone(),
                 filename: m.name.clone(),
                 path: m.path.display().to_string(),
+                canonical_path: m.canonical_path.display().to_string(),
                 size: m.file_size,
                 architecture: m.architecture.clone(),
-                parameters: None,
+                parameters: state.model_manager().parameters_label(&m),
+                param_count: state.model_manager().param_count(&m),
+                bytes_per_param: m.bytes_per_param,
+                hash: crate::services::integrity::lookup(&hashes, &m.path),
+                capabilities: m.capabilities.clone(),
+                kind: m.kind,
+                features: m.features.clone(),
                 context_length: m.context_length.map(|v| v as u64),
                 file_type: m.quantization.clone(),
                 quantization: m.quantization.clone(),
                 chat_template: None,
                 status,
-                favorite: false,
-                alias: None,
+                favorite: favorites.iter().any(|f| f.eq_ignore_ascii_case(&m.id)),
+                alias: state.model_manager().alias_of(&m.id),
+                display_name: state.model_manager().display_name_of(&m.id),
+                collision: m.collision,
+                missing_parts: m.missing_parts.clone(),
+                metadata_source: m.metadata_source,
+                scan_error: m.scan_error.clone(),
+                settings: None,
+                tokenizer: None,
+                model_info: None,
             }
         })
         .collect();
 
-    Json(entries)
+    if let Some(ModelSort::Favorite) = query.sort {
+        entries.sort_by_key(|e| !e.favorite);
+    }
+    Ok(Json(entries))
+}
+
+/// GET /api/adapters — LoRA adapters in the model directories
+async fn list_adapters(State(state): State<AppState>) -> Json<Vec<AdapterEntry>> {
+    let adapters = state
+        .model_manager()
+        .scan_available()
+        .into_iter()
+        .filter(|m| m.kind == gguf_parser::ModelKind::LoraAdapter)
+        .map(|m| AdapterEntry {
+            path: m.path.display().to_string(),
+            id: m.id,
+            filename: m.name,
+            size: m.file_size,
+            architecture: m.architecture,
+            base_model: m.base_model,
+        })
+        .collect();
+    Json(adapters)
 }
 
 /// POST /api/models/scan — trigger directory rescan
-async fn scan_models(State(state): State<AppState>) -> Json<serde_json::Value> {
-    let entries = state.model_manager().scan_available();
-    info!(count = entries.len(), "Model scan complete");
+async fn scan_models(
+    State(state): State<AppState>,
+    Query(query): Query<ScanQuery>,
+) -> Json<serde_json::Value> {
+    let entries = if query.force {
+        state.model_manager().scan_available_uncached()
+    } else {
+        state.model_manager().scan_available()
+    };
+    info!(
+        count = entries.len(),
+        force = query.force,
+        "Model scan complete"
+    );
     Json(serde_json::json!({
         "scanned": entries.len()
     }))
@@ -136,14 +508,18 @@ async fn scan_models(State(state): State<AppState>) -> Json<serde_json::Value> {
 async fn model_details(
     State(state): State<AppState>,
     Path(id): Path<String>,
-) -> Result<Json<ModelEntry>, axum::http::StatusCode> {
+) -> Result<Json<ModelEntry>, ApiError> {
     let available = state.model_manager().scan_available();
     let loaded_ids = state.model_manager().loaded_model_ids();
 
-    let m = available
-        .into_iter()
-        .find(|m| m.id == id)
-        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+    let m = available.into_iter().find(|m| m.id == id).ok_or((
+        axum::http::StatusCode::NOT_FOUND,
+        format!("Model '{id}' not found in configured directories"),
+    ))?;
+    let favorite = state
+        .db()
+        .is_favorite(&m.id)
+        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     let status = if loaded_ids.iter().any(|lid| lid.eq_ignore_ascii_case(&m.id)) {
         "loaded"
@@ -152,47 +528,410 @@ async fn model_details(
     };
 
     Ok(Json(ModelEntry {
-        id: m.id,
+        parameters: state.model_manager().parameters_label(&m),
+        param_count: state.model_manager().param_count(&m),
+        bytes_per_param: m.bytes_per_param,
+        hash: crate::services::integrity::recorded_hashes(state.db())
+            .ok()
+            .and_then(|hashes| crate::services::integrity::lookup(&hashes, &m.path)),
+        capabilities: m.capabilities.clone(),
+        kind: m.kind,
+        features: m.features.clone(),
         filename: m.name,
         path: m.path.display().to_string(),
+        canonical_path: m.canonical_path.display().to_string(),
         size: m.file_size,
         architecture: m.architecture,
-        parameters: None,
         context_length: m.context_length.map(|v| v as u64),
         file_type: m.quantization.clone(),
         quantization: m.quantization,
         chat_template: None,
         status,
-        favorite: false,
-        alias: None,
+        favorite,
+        alias: state.model_manager().alias_of(&m.id),
+        display_name: state.model_manager().display_name_of(&m.id),
+        collision: m.collision,
+        missing_parts: m.missing_parts,
+        metadata_source: m.metadata_source,
+        scan_error: m.scan_error,
+        tokenizer: state.model_manager().tokenizer_info(&m.id, &m.path),
+        model_info: state.model_manager().model_info(&m.id, &m.path),
+        settings: Some(
+            state
+                .model_manager()
+                .effective_settings(&m.id, ModelSettings::default()),
+        ),
+        id: m.id,
     }))
 }
 
 /// POST /api/models/:id/load — load a model by id
+///
+/// With `?async=true` the load runs in the background and 202 is returned
+/// with a job id; poll `GET /api/models/:id/status` for the outcome.
 async fn load_model(
     State(state): State<AppState>,
     Path(id): Path<String>,
+    Query(query): Query<LoadQuery>,
     Json(req): Json<LoadModelRequest>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
+) -> Result<(axum::http::StatusCode, Json<serde_json::Value>), ApiError> {
     // If already loaded, just return
     if state.model_manager().is_loaded(&id) {
-        return Ok(Json(serde_json::json!({ "status": "loaded", "id": id })));
+        return Ok((
+            axum::http::StatusCode::OK,
+            Json(serde_json::json!({ "status": "loaded", "id": id })),
+        ));
     }
 
+    check_not_draining(&state)?;
+
     // Find model path by scanning
     let model_path = state.model_manager().find_model_path(&id).ok_or((
         axum::http::StatusCode::NOT_FOUND,
         format!("Model '{}' not found in configured directories", id),
     ))?;
 
-    let model_params = llama_core::ModelParams {
-        n_gpu_layers: req.n_gpu_layers,
-        ..Default::default()
+    check_split_parts(&model_path)?;
+    let settings = state.model_manager().effective_settings(
+        &state.model_manager().model_id_for(&model_path),
+        req.settings,
+    );
+    if !req.force {
+        check_memory(&model_path, &settings)?;
+    }
+
+    if !query.background {
+        let body = load_from_path(&state, id, model_path, settings).await?;
+        return Ok((axum::http::StatusCode::OK, body));
+    }
+
+    let (job, started) = state.model_manager().begin_load_job(&id);
+    if started {
+        let state = state.clone();
+        let id = id.clone();
+        let request_id = current_request_id();
+        tokio::spawn(with_request_id(
+            request_id,
+            async move {
+                let result = load_from_path(&state, id.clone(), model_path, settings).await;
+                state
+                    .model_manager()
+                    .finish_load_job(&id, result.map(|_| ()).map_err(|e| e.message));
+            }
+            .in_current_span(),
+        ));
+    }
+
+    Ok((
+        axum::http::StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "status": "loading", "id": id, "job_id": job.job_id })),
+    ))
+}
+
+/// GET /api/models/:id/status — load state of a model
+async fn model_status(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    use crate::services::model_manager::LoadJobState;
+
+    let mm = state.model_manager();
+    let job = mm.load_job(&id);
+    let failure = mm.load_failure(&id);
+    let status = match job.as_ref().map(|j| j.state) {
+        Some(LoadJobState::Loading) => "loading",
+        _ if mm.is_loading(&id) => "loading",
+        _ if failure.is_some() => "failed",
+        Some(LoadJobState::Failed) => "failed",
+        _ if mm.is_loaded(&id) => "ready",
+        _ => "unloaded",
+    };
+    let error = failure
+        .as_ref()
+        .map(|(e, _)| e.clone())
+        .or_else(|| job.as_ref().and_then(|j| j.error.clone()));
+
+    Json(serde_json::json!({
+        "id": id,
+        "status": status,
+        "job_id": job.as_ref().map(|j| &j.job_id),
+        "progress": mm.load_progress(&id),
+        "error": error,
+        "failed_at": failure.map(|(_, at)| at.to_rfc3339()),
+    }))
+}
+
+/// POST /api/models/:id/clear-error — forget a failed load
+async fn clear_model_error(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    if !state.model_manager().clear_error(&id) {
+        return Err(ApiError::from((
+            axum::http::StatusCode::NOT_FOUND,
+            format!("Model '{}' has no failed load", id),
+        )));
+    }
+    Ok(Json(serde_json::json!({ "id": id, "status": "unloaded" })))
+}
+
+/// POST /api/models/load-by-path — load a model file by path
+///
+/// The canonicalized path must lie inside a model directory unless
+/// `allow_external_paths` is enabled.
+async fn load_model_by_path(
+    State(state): State<AppState>,
+    Json(req): Json<LoadByPathRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    use crate::services::model_manager::ModelPathError;
+
+    let model_path = state
+        .model_manager()
+        .check_model_path(&req.path)
+        .map_err(|e| match e {
+            ModelPathError::Inaccessible { .. } => {
+                (axum::http::StatusCode::NOT_FOUND, e.to_string())
+            }
+            ModelPathError::OutsideModelDirs(_) => (
+                axum::http::StatusCode::FORBIDDEN,
+                format!("{e}; enable 'allow_external_paths' to load it"),
+            ),
+        })?;
+    if !model_path.is_file() {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("'{}' is not a file", model_path.display()),
+        )
+            .into());
+    }
+
+    let id = state.model_manager().model_id_for(&model_path);
+    if state.model_manager().is_loaded(&id) {
+        return Ok(Json(serde_json::json!({ "status": "loaded", "id": id })));
+    }
+    check_not_draining(&state)?;
+    check_split_parts(&model_path)?;
+
+    let settings = state
+        .model_manager()
+        .effective_settings(&id, req.params.settings);
+    if !req.params.force {
+        check_memory(&model_path, &settings)?;
+    }
+
+    load_from_path(&state, id, model_path, settings).await
+}
+
+/// GET /api/models/:id/estimate — dry-run memory check for a load
+async fn estimate_model(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<EstimateQuery>,
+) -> Result<Json<crate::services::memory::MemoryCheck>, ApiError> {
+    let model_path = state.model_manager().find_model_path(&id).ok_or((
+        axum::http::StatusCode::NOT_FOUND,
+        format!("Model '{}' not found in configured directories", id),
+    ))?;
+    let settings = state.model_manager().effective_settings(
+        &state.model_manager().model_id_for(&model_path),
+        ModelSettings {
+            ctx_size: query.ctx_size,
+            n_gpu_layers: query.n_gpu_layers,
+            flash_attn: query.flash_attn,
+            type_k: query.type_k,
+            type_v: query.type_v,
+            ..Default::default()
+        },
+    );
+    let (model_params, ctx_params) = settings.load_params();
+    let check = crate::services::memory::check_model(&model_path, &model_params, &ctx_params)
+        .map_err(|e| (axum::http::StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+    Ok(Json(check))
+}
+
+/// GET /api/models/:id/metadata — every GGUF metadata key/value
+///
+/// Values are plain JSON.  For loaded models, scalar values come from
+/// llama.cpp's parsed metadata; arrays always come from the file.
+/// Arrays longer than [`MAX_INLINE_ARRAY_LEN`] are listed under
+/// `elided` with their length unless `?include_arrays=true`.
+/// `?tensors=true` adds the tensor infos and a per-type tensor count.
+async fn model_metadata(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<MetadataQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let mm = state.model_manager().clone();
+    let loaded = mm.get_loaded(&id);
+    let path = match &loaded {
+        Some(loaded) => loaded.path.clone(),
+        None => mm.find_model_path(&id).ok_or((
+            axum::http::StatusCode::NOT_FOUND,
+            format!("Model '{}' not found in configured directories", id),
+        ))?,
+    };
+
+    let with_tensors = query.tensors;
+    let (scan, tensors) = tokio::task::spawn_blocking(move || {
+        let tensors = if with_tensors {
+            Some(gguf_parser::scan_tensors(&path)?)
+        } else {
+            None
+        };
+        Ok::<_, gguf_parser::types::GGUFError>((mm.scan_metadata(&path)?, tensors))
+    })
+    .await
+    .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e| (axum::http::StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+
+    let render = |value: &gguf_parser::GGUFValue| {
+        if query.raw {
+            serde_json::to_value(value).unwrap_or_default()
+        } else {
+            serde_json::Value::from(value)
+        }
     };
-    let ctx_params = llama_core::ContextParams {
-        n_ctx: req.ctx_size,
-        ..Default::default()
+    let mut metadata = serde_json::Map::new();
+    let mut elided = serde_json::Map::new();
+    for kv in &scan.metadata {
+        match &kv.value {
+            gguf_parser::GGUFValue::Array(items)
+                if !query.include_arrays && items.len() > MAX_INLINE_ARRAY_LEN =>
+            {
+                elided.insert(kv.key.clone(), items.len().into());
+            }
+            gguf_parser::GGUFValue::SkippedArray { len, .. } => {
+                elided.insert(kv.key.clone(), (*len).into());
+            }
+            value => {
+                metadata.insert(kv.key.clone(), render(value));
+            }
+        }
+    }
+
+    // Prefer what llama.cpp actually parsed for scalars.
+    if let Some(loaded) = &loaded {
+        let file_values: std::collections::HashMap<&str, &gguf_parser::GGUFValue> = scan
+            .metadata
+            .iter()
+            .map(|kv| (kv.key.as_str(), &kv.value))
+            .collect();
+        for (key, text) in loaded.model.metadata() {
+            match file_values.get(key.as_str()) {
+                Some(
+                    gguf_parser::GGUFValue::Array(_) | gguf_parser::GGUFValue::SkippedArray { .. },
+                ) => {}
+                Some(value) => {
+                    metadata.insert(key, render(&parse_like(value, &text)));
+                }
+                None => {
+                    metadata.insert(key, render(&gguf_parser::GGUFValue::String(text)));
+                }
+            }
+        }
+    }
+
+    let mut body = serde_json::json!({
+        "id": loaded.as_ref().map_or(id, |l| l.id.clone()),
+        "path": scan.file_path.display().to_string(),
+        "source": if loaded.is_some() { "llama.cpp" } else { "gguf" },
+        "version": scan.header.version,
+        "tensor_count": scan.header.tensor_count,
+        "kv_count": scan.header.metadata_kv_count,
+        "metadata": metadata,
+        "elided": elided,
+    });
+    if let Some(tensors) = tensors {
+        let mut types = std::collections::BTreeMap::<&str, usize>::new();
+        for t in &tensors {
+            *types
+                .entry(gguf_parser::ggml_type_name(t.ggml_type))
+                .or_default() += 1;
+        }
+        body["tensor_types"] = json!(types);
+        body["tensors"] = json!(tensors);
+    }
+    Ok(Json(body))
+}
+
+/// Parse llama.cpp's string rendering of a scalar as the type the file
+/// declares, falling back to the file's own value.
+fn parse_like(file_value: &gguf_parser::GGUFValue, text: &str) -> gguf_parser::GGUFValue {
+    use gguf_parser::GGUFValue as V;
+    let parsed: Option<serde_json::Value> = match file_value {
+        V::Float32(_) | V::Float64(_) => text.parse::<f64>().ok().map(Into::into),
+        V::Bool(_) => text.parse::<bool>().ok().map(Into::into),
+        V::Array(_) | V::SkippedArray { .. } => None,
+        // `from_json` reads integers of any width from decimal strings
+        _ => Some(text.into()),
     };
+    parsed
+        .and_then(|json| V::from_json(&json, file_value))
+        .unwrap_or_else(|| file_value.clone())
+}
+
+/// Refuse a load that would not fit in free memory (409).
+///
+/// Files whose metadata cannot be read are let through; the load itself
+/// will report the problem.
+fn check_memory(
+    model_path: &std::path::Path,
+    settings: &ModelSettings,
+) -> Result<(), (axum::http::StatusCode, String)> {
+    let (model_params, ctx_params) = settings.load_params();
+    match crate::services::memory::check_model(model_path, &model_params, &ctx_params) {
+        Ok(check) if !check.fits => Err((
+            axum::http::StatusCode::CONFLICT,
+            format!(
+                "Not enough memory to load model: {}. Pass \"force\": true to load anyway.",
+                check.summary
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// 422 for a split model with parts missing, which llama.cpp would only
+/// notice part-way through the load.
+fn check_split_parts(model_path: &std::path::Path) -> Result<(), (axum::http::StatusCode, String)> {
+    let missing = gguf_parser::missing_split_parts(model_path);
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let parts: Vec<String> = missing.iter().map(u16::to_string).collect();
+    Err((
+        axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+        format!(
+            "Split model is incomplete: part(s) {} missing",
+            parts.join(", ")
+        ),
+    ))
+}
+
+/// 503 while the model manager is in maintenance drain mode.
+fn check_not_draining(state: &AppState) -> Result<(), (axum::http::StatusCode, String)> {
+    match state.model_manager().drain_reason() {
+        Some(reason) => Err((
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            format!("Model loading is paused for maintenance: {reason}"),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Load `model_path` with `settings` on a blocking thread and announce
+/// it as `id`.
+async fn load_from_path(
+    state: &AppState,
+    id: String,
+    model_path: std::path::PathBuf,
+    settings: ModelSettings,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let (model_params, ctx_params) = settings.load_params();
+    let flash_attn = ctx_params.flash_attn_mode();
+    let gpu_offload_ignored = state
+        .model_manager()
+        .gpu_offload_ignored(model_params.n_gpu_layers);
 
     // Load in blocking task to avoid blocking the async runtime
     let mm = state.model_manager().clone();
@@ -205,12 +944,24 @@ async fn load_model(
         Ok(_) => {
             info!(id, "Model loaded via API");
             // Broadcast event
-            state.broadcast_event("model.loaded", serde_json::json!({ "id": id }));
-            Ok(Json(serde_json::json!({ "status": "loaded", "id": id })))
+            // The requested mode: llama.cpp does not report what it chose
+            let mut event = serde_json::json!({ "id": id, "flash_attn": flash_attn });
+            let mut body =
+                serde_json::json!({ "status": "loaded", "id": id, "flash_attn": flash_attn });
+            if gpu_offload_ignored {
+                event["gpu_offload_ignored"] = true.into();
+                body["gpu_offload_ignored"] = true.into();
+            }
+            state.broadcast_event("model.loaded", event);
+            Ok(Json(body))
         }
         Err(e) => {
             error!(id, error = %e, "Failed to load model");
-            Err((axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+            state.broadcast_event(
+                "model.load_failed",
+                serde_json::json!({ "id": id, "error": e.to_string() }),
+            );
+            Err(e.into())
         }
     }
 }
@@ -228,22 +979,679 @@ async fn unload_model(
     Json(serde_json::json!({ "status": "unloaded", "id": id }))
 }
 
+/// POST /api/models/unload-all?timeout=&reason= — enter maintenance
+/// drain mode and unload every model
+///
+/// New loads are refused with 503 until `POST /api/maintenance/resume`.
+/// Models still serving requests after `timeout` seconds stay loaded and
+/// are reported as skipped.
+async fn unload_all_models(
+    State(state): State<AppState>,
+    Query(query): Query<UnloadAllQuery>,
+) -> Json<serde_json::Value> {
+    let reason = query.reason.unwrap_or_else(|| "unload-all".into());
+    state.broadcast_event(
+        "maintenance.draining",
+        serde_json::json!({ "reason": reason }),
+    );
+    let report = state
+        .model_manager()
+        .drain_and_unload(reason.clone(), Duration::from_secs(query.timeout))
+        .await;
+    for id in &report.unloaded {
+        state.broadcast_event("model.unloaded", serde_json::json!({ "id": id }));
+    }
+    info!(
+        unloaded = report.unloaded.len(),
+        skipped = report.skipped.len(),
+        "Unloaded models for maintenance"
+    );
+    Json(serde_json::json!({
+        "draining": true,
+        "reason": reason,
+        "unloaded": report.unloaded,
+        "skipped": report.skipped,
+    }))
+}
+
+/// POST /api/maintenance/resume — leave drain mode and allow loads again
+async fn resume_maintenance(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let was_draining = state.model_manager().end_drain();
+    if was_draining {
+        state.broadcast_event("maintenance.resumed", serde_json::json!({}));
+    }
+    Json(serde_json::json!({ "draining": false, "was_draining": was_draining }))
+}
+
+/// DELETE /api/models/:id/file — delete a model's files from disk
+///
+/// All split parts are deleted together; the mmproj projector only with
+/// `?include_companions=true`.  `?dry_run=true` lists the files and bytes
+/// that would be freed.  If some files cannot be deleted the response is
+/// 207 with the outcome of each file.  Files must lie in a configured
+/// model directory, and a model that requests are using is not touched
+/// (409).
+async fn delete_model_file(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<DeleteFileQuery>,
+) -> Result<(axum::http::StatusCode, Json<serde_json::Value>), ApiError> {
+    let model = state
+        .model_manager()
+        .scan_available()
+        .into_iter()
+        .find(|m| m.id.eq_ignore_ascii_case(&id))
+        .ok_or((
+            axum::http::StatusCode::NOT_FOUND,
+            format!("Model '{}' not found in configured directories", id),
+        ))?;
+    let id = model.id;
+
+    let mut paths = if model.split_parts.is_empty() {
+        vec![model.path]
+    } else {
+        model.split_parts
+    };
+    if query.include_companions
+        && let Some(mmproj) = model.mmproj_path
+    {
+        paths.push(mmproj);
+    }
+
+    let mut files = Vec::with_capacity(paths.len());
+    for path in &paths {
+        let canonical = state
+            .model_manager()
+            .check_inside_model_dirs(path)
+            .map_err(|e| (axum::http::StatusCode::FORBIDDEN, e.to_string()))?;
+        files.push(FileDeletion {
+            bytes: std::fs::metadata(&canonical).map_or(0, |m| m.len()),
+            path: canonical.display().to_string(),
+            deleted: false,
+            error: None,
+        });
+    }
+    let total_bytes: u64 = files.iter().map(|f| f.bytes).sum();
+
+    if query.dry_run {
+        return Ok((
+            axum::http::StatusCode::OK,
+            Json(serde_json::json!({
+                "id": id,
+                "dry_run": true,
+                "files": files,
+                "total_bytes": total_bytes,
+            })),
+        ));
+    }
+
+    match state.model_manager().unload_if_idle(&id) {
+        None => {
+            return Err(ApiError::from((
+                axum::http::StatusCode::CONFLICT,
+                format!("Model '{id}' is in use; retry when its requests finish"),
+            )));
+        }
+        Some(true) => {
+            state.broadcast_event("model.unloaded", serde_json::json!({ "id": id }));
+        }
+        Some(false) => {}
+    }
+
+    for file in &mut files {
+        match std::fs::remove_file(&file.path) {
+            Ok(()) => {
+                file.deleted = true;
+                let path = std::path::Path::new(&file.path);
+                if let Err(e) = crate::services::integrity::forget(state.db(), path) {
+                    warn!(id, path = file.path, "Failed to drop recorded hash: {e}");
+                }
+            }
+            Err(e) => {
+                warn!(id, path = file.path, "Failed to delete model file: {e}");
+                file.error = Some(e.to_string());
+            }
+        }
+    }
+    let freed_bytes: u64 = files.iter().filter(|f| f.deleted).map(|f| f.bytes).sum();
+    let complete = files.iter().all(|f| f.deleted);
+
+    // Keep the metadata while any file is left, so a retry still finds it.
+    if complete && let Err(e) = forget_model(&state, &id) {
+        error!(id, "Failed to remove model metadata: {e}");
+    }
+
+    info!(id, freed_bytes, complete, "Model files deleted via API");
+    let body = serde_json::json!({
+        "id": id,
+        "dry_run": false,
+        "files": files,
+        "total_bytes": total_bytes,
+        "freed_bytes": freed_bytes,
+    });
+    state.broadcast_event("model.deleted", body.clone());
+
+    let status = if complete {
+        axum::http::StatusCode::OK
+    } else {
+        axum::http::StatusCode::MULTI_STATUS
+    };
+    Ok((status, Json(body)))
+}
+
+/// Remove `id`'s database metadata and refresh the manager's mirrors.
+fn forget_model(state: &AppState, id: &str) -> Result<(), crate::db::DbError> {
+    let db = state.db();
+    db.delete_model_meta(id)?;
+    let mm = state.model_manager();
+    mm.set_aliases(db.list_aliases()?);
+    mm.set_display_names(db.list_display_names()?);
+    mm.set_all_settings(db.list_model_settings()?);
+    Ok(())
+}
+
 /// PUT /api/models/:id/favorite — toggle favorite
-async fn toggle_favorite(Path(id): Path<String>) -> Json<serde_json::Value> {
-    // TODO: persist favorite state in DB
-    Json(serde_json::json!({ "id": id, "favorite": true }))
+async fn toggle_favorite(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let id = catalogue_id(&state, &id)?;
+    let favorite = state
+        .db()
+        .toggle_favorite(&id)
+        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(serde_json::json!({ "id": id, "favorite": favorite })))
+}
+
+/// PUT /api/models/:id/alias — set or clear a model's alias
+async fn set_alias(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<SetAliasRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let available = state.model_manager().scan_available();
+    let model = available
+        .iter()
+        .find(|m| m.id.eq_ignore_ascii_case(&id))
+        .ok_or((
+            axum::http::StatusCode::NOT_FOUND,
+            format!("Model '{}' not found in configured directories", id),
+        ))?;
+    let id = model.id.clone();
+
+    let alias = req
+        .alias
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty());
+
+    if let Some(alias) = &alias {
+        let clashes = available
+            .iter()
+            .any(|m| m.id.eq_ignore_ascii_case(alias) && m.id != id);
+        if clashes {
+            return Err(ApiError::from((
+                axum::http::StatusCode::CONFLICT,
+                format!("Alias '{}' collides with an existing model id", alias),
+            )));
+        }
+    }
+
+    state
+        .db()
+        .set_alias(&id, &model.path, alias.as_deref())
+        .map_err(|e| match e {
+            crate::db::DbError::AliasTaken { .. } => {
+                (axum::http::StatusCode::CONFLICT, e.to_string())
+            }
+            _ => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        })?;
+
+    let aliases = state
+        .db()
+        .list_aliases()
+        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    state.model_manager().set_aliases(aliases);
+
+    info!(id, alias = ?alias, "Model alias updated");
+    state.broadcast_event(
+        "model.alias",
+        serde_json::json!({ "id": id, "alias": alias }),
+    );
+    Ok(Json(serde_json::json!({ "id": id, "alias": alias })))
+}
+
+/// PUT /api/models/:id/name — set or clear a model's display name
+async fn set_display_name(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<SetDisplayNameRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let model = state
+        .model_manager()
+        .scan_available()
+        .into_iter()
+        .find(|m| m.id.eq_ignore_ascii_case(&id))
+        .ok_or((
+            axum::http::StatusCode::NOT_FOUND,
+            format!("Model '{}' not found in configured directories", id),
+        ))?;
+    let id = model.id;
+
+    let display_name = req
+        .display_name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty());
+    if let Some(name) = &display_name
+        && name.chars().count() > MAX_DISPLAY_NAME_LEN
+    {
+        return Err(ApiError::from((
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("Display name is longer than {MAX_DISPLAY_NAME_LEN} characters"),
+        )));
+    }
+
+    let db = state.db();
+    db.set_display_name(&id, &model.path, display_name.as_deref())
+        .and_then(|_| db.list_display_names())
+        .map(|names| state.model_manager().set_display_names(names))
+        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    info!(id, display_name = ?display_name, "Model display name updated");
+    state.broadcast_event(
+        "model.name",
+        serde_json::json!({ "id": id, "display_name": display_name }),
+    );
+    Ok(Json(
+        serde_json::json!({ "id": id, "display_name": display_name }),
+    ))
+}
+
+/// POST /api/models/:id/bench — benchmark a loaded model
+///
+/// Blocks the model for the duration of the run; the result is stored
+/// in the benchmark history.
+async fn bench_model(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<BenchRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    use crate::services::inference::{BenchError, bench_blocking};
+
+    if req.repetitions == 0 || req.repetitions > MAX_BENCH_REPETITIONS {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("repetitions must be between 1 and {MAX_BENCH_REPETITIONS}"),
+        )
+            .into());
+    }
+    let loaded = state.model_manager().get_loaded(&id).ok_or((
+        axum::http::StatusCode::NOT_FOUND,
+        format!("Model '{}' is not loaded", id),
+    ))?;
+    let id = loaded.id.clone();
+
+    let params = llama_core::BenchParams {
+        n_prompt: req.pp,
+        n_gen: req.tg,
+        repetitions: req.repetitions,
+    };
+    info!(id, ?params, "Benchmark started");
+    let report = tokio::task::spawn_blocking(move || bench_blocking(&loaded, &params, req.force))
+        .await
+        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| match e {
+            BenchError::Busy => ApiError::from((
+                axum::http::StatusCode::CONFLICT,
+                format!("{e}. Pass \"force\": true to wait for it."),
+            )),
+            BenchError::Llama(e) => e.into(),
+        })?;
+    state.model_manager().touch(&id);
+
+    let result_id = state
+        .db()
+        .insert_bench_result(&id, &report)
+        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    info!(
+        id,
+        pp = report.pp.mean,
+        tg = report.tg.mean,
+        "Benchmark finished"
+    );
+    Ok(Json(serde_json::json!({
+        "id": id,
+        "result_id": result_id,
+        "report": report,
+    })))
+}
+
+/// POST /api/models/:id/classify — score texts with a classifier model
+///
+/// For sequence-classification and reward models (`rank` pooling); each
+/// text gets one score per `classifier.output_labels` entry.
+async fn classify(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<ClassifyRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    use crate::services::inference::{PoolError, PoolParams, pooled_blocking};
+
+    if req.texts.is_empty() || req.texts.len() > MAX_CLASSIFY_TEXTS {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("texts must hold between 1 and {MAX_CLASSIFY_TEXTS} strings"),
+        )
+            .into());
+    }
+    let loaded = state.model_manager().get_loaded(&id).ok_or((
+        axum::http::StatusCode::NOT_FOUND,
+        format!("Model '{}' is not loaded", id),
+    ))?;
+    let id = loaded.id.clone();
+    let model = loaded.model.clone();
+    let labels = model.cls_labels();
+
+    let texts = req.texts;
+    let pooled = tokio::task::spawn_blocking(move || {
+        pooled_blocking(model, &texts, &PoolParams::default(), |ctx| {
+            ctx.get_classification(0).map(<[f32]>::to_vec)
+        })
+    })
+    .await
+    .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e| match e {
+        PoolError::NoOutput => ApiError::from((
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("Model '{id}' is not a classifier (its pooling type is not `rank`)"),
+        )),
+        e @ PoolError::TooLong { .. } => {
+            ApiError::from((axum::http::StatusCode::BAD_REQUEST, e.to_string()))
+        }
+        PoolError::Llama(e) => e.into(),
+    })?;
+    state.model_manager().touch(&id);
+
+    let prompt_tokens: u32 = pooled.iter().map(|p| p.n_tokens).sum();
+    let data: Vec<Classification> = pooled
+        .into_iter()
+        .enumerate()
+        .map(|(index, p)| {
+            let scores = label_scores(&labels, &p.values);
+            Classification {
+                index,
+                label: scores
+                    .iter()
+                    .max_by(|a, b| a.score.total_cmp(&b.score))
+                    .map(|s| s.label.clone())
+                    .unwrap_or_default(),
+                scores,
+                n_tokens: p.n_tokens,
+            }
+        })
+        .collect();
+    Ok(Json(serde_json::json!({
+        "id": id,
+        "data": data,
+        "usage": { "prompt_tokens": prompt_tokens },
+    })))
+}
+
+/// Pair classifier outputs with `labels` (`LABEL_<i>` where missing, as
+/// Hugging Face names them) and turn them into probabilities.
+fn label_scores(labels: &[String], scores: &[f32]) -> Vec<LabelScore> {
+    let probabilities: Vec<f32> = match scores {
+        [single] => vec![1.0 / (1.0 + (-single).exp())],
+        _ => {
+            let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            let exp: Vec<f32> = scores.iter().map(|s| (s - max).exp()).collect();
+            let sum: f32 = exp.iter().sum();
+            exp.iter().map(|e| e / sum).collect()
+        }
+    };
+    scores
+        .iter()
+        .zip(probabilities)
+        .enumerate()
+        .map(|(i, (&score, probability))| LabelScore {
+            label: labels
+                .get(i)
+                .cloned()
+                .unwrap_or_else(|| format!("LABEL_{i}")),
+            score,
+            probability,
+        })
+        .collect()
+}
+
+/// POST /api/models/:id/verify — re-hash a model's files
+///
+/// Each file is compared with `sha256` from the body if given, otherwise
+/// with its recorded hash; files without one get their hash recorded.
+/// Progress is broadcast as `model.verify.progress` events, the outcome
+/// as `model.verify.completed`.
+async fn verify_model(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    req: Option<Json<VerifyRequest>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    use crate::services::downloads::ProgressMeter;
+    use crate::services::integrity::{VerifyStatus, verify_file};
+
+    let expected = req.and_then(|Json(req)| req.sha256);
+    if let Some(hash) = &expected
+        && !gguf_parser::is_sha256_hex(hash)
+    {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "sha256 must be 64 hex digits".to_string(),
+        )
+            .into());
+    }
+    let model = state
+        .model_manager()
+        .scan_available()
+        .into_iter()
+        .find(|m| m.id.eq_ignore_ascii_case(&id))
+        .ok_or((
+            axum::http::StatusCode::NOT_FOUND,
+            format!("Model '{}' not found in configured directories", id),
+        ))?;
+    let id = model.id;
+    let paths = if model.split_parts.is_empty() {
+        vec![model.path]
+    } else {
+        model.split_parts
+    };
+    if expected.is_some() && paths.len() > 1 {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            format!(
+                "Model '{id}' is split into {} files; verify against the recorded hashes instead",
+                paths.len()
+            ),
+        )
+            .into());
+    }
+
+    info!(id, files = paths.len(), "Verifying model files");
+    let task_state = state.clone();
+    let task_id = id.clone();
+    let files = tokio::task::spawn_blocking(move || {
+        paths
+            .iter()
+            .map(|path| {
+                let total = std::fs::metadata(path).map(|m| m.len()).ok();
+                let mut meter = ProgressMeter::new(total, 0, std::time::Instant::now());
+                verify_file(task_state.db(), path, expected.as_deref(), |done| {
+                    if let Some(snapshot) = meter.update(done, std::time::Instant::now()) {
+                        task_state.broadcast_event(
+                            "model.verify.progress",
+                            json!({
+                                "id": task_id,
+                                "path": path,
+                                "bytes_done": snapshot.bytes_done,
+                                "total_bytes": total,
+                                "speed_bps": snapshot.speed_bps,
+                                "eta_secs": snapshot.eta_secs,
+                            }),
+                        );
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await
+    .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let ok = files.iter().all(|f| f.status != VerifyStatus::Mismatch);
+    if ok {
+        info!(id, "Model files verified");
+    } else {
+        warn!(id, "Model files do not match their expected hashes");
+    }
+    let body = json!({ "id": id, "ok": ok, "files": files });
+    state.broadcast_event("model.verify.completed", body.clone());
+    Ok(Json(body))
+}
+
+/// GET /api/models/:id/bench — benchmark history, oldest first
+async fn list_bench_results(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<crate::db::BenchRecord>>, ApiError> {
+    state
+        .db()
+        .list_bench_results(&id)
+        .map(Json)
+        .map_err(|e| ApiError::from((axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string())))
+}
+
+/// GET /api/models/:id/stats — usage counters, kept across unloads
+///
+/// Models that never served a request report zeros.
+async fn model_stats(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    let stats = state.stats().get(&id).unwrap_or_default();
+    Json(serde_json::json!({
+        "id": id,
+        "loaded": state.model_manager().get_loaded(&id).is_some(),
+        "requests": stats.requests,
+        "prompt_tokens": stats.prompt_tokens,
+        "completion_tokens": stats.completion_tokens,
+        "generation_ms": stats.generation_ms,
+        "tokens_per_sec": stats.tokens_per_sec(),
+        "last_request_at": stats.last_request_at,
+    }))
+}
+
+/// DELETE /api/models/:id/stats — reset usage counters
+async fn reset_model_stats(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let had_stats = state.stats().reset(&id);
+    state
+        .db()
+        .delete_model_stats(&id)
+        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    info!(id, "Model stats reset");
+    Ok(Json(serde_json::json!({ "id": id, "reset": had_stats })))
+}
+
+/// Exact (case-insensitive) catalogue id for `id`, or 404.
+fn catalogue_id(state: &AppState, id: &str) -> Result<String, (axum::http::StatusCode, String)> {
+    state
+        .model_manager()
+        .scan_available()
+        .into_iter()
+        .find(|m| m.id.eq_ignore_ascii_case(id))
+        .map(|m| m.id)
+        .ok_or((
+            axum::http::StatusCode::NOT_FOUND,
+            format!("Model '{}' not found in configured directories", id),
+        ))
+}
+
+/// GET /api/models/:id/settings — stored and effective load settings
+async fn get_model_settings(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let id = catalogue_id(&state, &id)?;
+    let mm = state.model_manager();
+    Ok(Json(serde_json::json!({
+        "id": id,
+        "settings": mm.settings(&id),
+        "effective": mm.effective_settings(&id, ModelSettings::default()),
+    })))
+}
+
+/// PUT /api/models/:id/settings — replace a model's stored load settings
+async fn update_model_settings(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(settings): Json<ModelSettings>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let id = catalogue_id(&state, &id)?;
+    state
+        .db()
+        .set_model_settings(&id, &settings)
+        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let mm = state.model_manager();
+    mm.set_settings(&id, settings);
+
+    info!(id, "Model settings updated");
+    Ok(Json(serde_json::json!({
+        "id": id,
+        "settings": mm.settings(&id),
+        "effective": mm.effective_settings(&id, ModelSettings::default()),
+    })))
+}
+
+/// GET /api/aliases — list all model aliases
+async fn list_aliases(State(state): State<AppState>) -> Json<Vec<AliasEntry>> {
+    let entries = state
+        .model_manager()
+        .aliases()
+        .into_iter()
+        .map(|(alias, model_id)| AliasEntry { alias, model_id })
+        .collect();
+    Json(entries)
+}
+
+/// PUT /api/models/:id/pin — pin or unpin a loaded model
+async fn pin_model(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<PinRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    if !state.model_manager().set_pinned(&id, req.pinned) {
+        return Err(ApiError::from((
+            axum::http::StatusCode::NOT_FOUND,
+            format!("Model '{}' is not loaded", id),
+        )));
+    }
+    state.broadcast_event(
+        "model.pinned",
+        serde_json::json!({ "id": id, "pinned": req.pinned }),
+    );
+    Ok(Json(serde_json::json!({ "id": id, "pinned": req.pinned })))
 }
 
 /// GET /api/models/loaded — list all currently loaded models
 async fn list_loaded_models(
     State(state): State<AppState>,
 ) -> Json<Vec<crate::services::model_manager::SlotInfo>> {
-    Json(state.model_manager().slot_info())
+    Json(state.slot_info())
 }
 
 /// GET /api/config — get current configuration
 async fn get_config(State(state): State<AppState>) -> Json<ConfigResponse> {
     let cfg = state.config();
+    let (rate_limit_per_minute, model_ops_rate_limit_per_minute) = state.rate_limiter().limits();
     Json(ConfigResponse {
         model_dirs: cfg
             .model_dirs
@@ -254,6 +1662,17 @@ async fn get_config(State(state): State<AppState>) -> Json<ConfigResponse> {
         default_n_gpu_layers: cfg.default_n_gpu_layers,
         default_temperature: 0.7,
         api_key: cfg.api_key.clone(),
+        auto_load_models: cfg.auto_load_models,
+        allow_external_paths: cfg.allow_external_paths,
+        rate_limit_per_minute,
+        model_ops_rate_limit_per_minute,
+        max_body_bytes: cfg.max_body_bytes,
+        generation_timeout_secs: cfg.generation_timeout_secs,
+        compression_enabled: cfg.compression_enabled,
+        tls_cert: cfg.tls_cert.as_ref().map(|p| p.display().to_string()),
+        tls_key: cfg.tls_key.as_ref().map(|p| p.display().to_string()),
+        http_redirect_port: cfg.http_redirect_port,
+        numa: cfg.numa,
     })
 }
 
@@ -261,7 +1680,7 @@ async fn get_config(State(state): State<AppState>) -> Json<ConfigResponse> {
 async fn update_config(
     State(state): State<AppState>,
     Json(update): Json<ConfigUpdate>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
+) -> Result<Json<serde_json::Value>, ApiError> {
     let mut cfg = state.config().clone();
 
     if let Some(dirs) = update.model_dirs {
@@ -276,23 +1695,1158 @@ async fn update_config(
     if let Some(key) = update.api_key {
         cfg.api_key = if key.is_empty() { None } else { Some(key) };
     }
+    if let Some(auto_load) = update.auto_load_models {
+        cfg.auto_load_models = auto_load;
+    }
+    if let Some(max) = update.max_body_bytes {
+        cfg.max_body_bytes = max;
+    }
+    if let Some(secs) = update.generation_timeout_secs {
+        cfg.generation_timeout_secs = secs;
+    }
+    if let Some(enabled) = update.compression_enabled {
+        cfg.compression_enabled = enabled;
+    }
+    if let Some(port) = update.http_redirect_port {
+        cfg.http_redirect_port = Some(port).filter(|&p| p != 0);
+    }
+    if let Some(numa) = update.numa {
+        let current = llama_core::LlamaBackend::numa_strategy();
+        if numa != current {
+            warn!(
+                ?numa,
+                ?current,
+                "NUMA strategy saved; it takes effect after a restart, since models \
+                 loaded in this process keep their placement"
+            );
+        }
+        cfg.numa = numa;
+    }
+    let path = |s: String| Some(std::path::PathBuf::from(s)).filter(|p| !p.as_os_str().is_empty());
+    if let Some(cert) = update.tls_cert {
+        cfg.tls_cert = path(cert);
+    }
+    if let Some(key) = update.tls_key {
+        cfg.tls_key = path(key);
+    }
+    // A running HTTPS listener picks up new certificate paths right away;
+    // bad files are rejected before anything is saved.
+    let tls_paths = TlsPaths::from_options(cfg.tls_cert.clone(), cfg.tls_key.clone())
+        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e.to_string()))?;
+    let tls_reload = match (state.tls(), tls_paths) {
+        (Some(reloader), Some(paths)) if paths != reloader.paths() => {
+            crate::services::tls::server_config(&paths)
+                .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e.to_string()))?;
+            Some((reloader, paths))
+        }
+        _ => None,
+    };
+    // Rate limits, like the TLS paths above, apply without a restart
+    let (mut general, mut model_ops) = state.rate_limiter().limits();
+    if let Some(limit) = update.rate_limit_per_minute {
+        general = limit;
+    }
+    if let Some(limit) = update.model_ops_rate_limit_per_minute {
+        model_ops = limit;
+    }
+    cfg.rate_limit_per_minute = general;
+    cfg.model_ops_rate_limit_per_minute = model_ops;
 
     cfg.save()
         .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    state.rate_limiter().set_limits(general, model_ops);
+    if let Some((reloader, paths)) = tls_reload
+        && let Err(e) = reloader.reload(Some(paths))
+    {
+        warn!("Saved new TLS paths but could not load them: {e}");
+    }
 
     Ok(Json(serde_json::json!({ "status": "ok" })))
 }
 
+/// GET /api/system/resources — CPU, RAM, swap, GPU and per-model usage
+async fn system_resources(
+    State(state): State<AppState>,
+) -> Result<Json<crate::services::resources::ResourceSnapshot>, ApiError> {
+    let (monitor, mm) = (state.resources().clone(), state.model_manager().clone());
+    tokio::task::spawn_blocking(move || monitor.sample(&mm))
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::from((axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string())))
+}
+
 /// GET /api/system/info
 async fn system_info(State(state): State<AppState>) -> Json<SystemInfoResponse> {
     let models_loaded = state.model_manager().loaded_count();
     let models_available = state.model_manager().scan_available().len();
-    let loaded_models = state.model_manager().slot_info();
+    let loaded_models = state.slot_info();
 
     Json(SystemInfoResponse {
         version: env!("CARGO_PKG_VERSION").to_string(),
         models_loaded,
         models_available,
+        memory_used_bytes: state.model_manager().memory_used(),
+        max_memory_bytes: state.model_manager().max_memory_bytes(),
+        last_scan: state.model_manager().scan_summary(),
+        compiled_backends: crate::services::resources::compiled_backends(),
+        devices: llama_core::LlamaBackend::init().gpu_devices(),
+        supports: SupportFlags {
+            mmap: llama_core::LlamaBackend::supports_mmap(),
+            mlock: llama_core::LlamaBackend::supports_mlock(),
+            gpu_offload: llama_core::LlamaBackend::supports_gpu_offload(),
+        },
+        numa: llama_core::LlamaBackend::numa_strategy(),
         loaded_models,
+        generations_in_flight: state.stats().in_flight(),
     })
 }
+
+//  OpenAPI
+
+/// Describe the routes above in `spec`.
+pub fn openapi(spec: &mut Spec) {
+    let nullable = |t: &str| json!({ "type": [t, "null"] });
+    let cache_types = json!([
+        "f32", "f16", "bf16", "q8_0", "q4_0", "q4_1", "iq4_nl", "q5_0", "q5_1", null,
+    ]);
+    let settings = json!({
+        "ctx_size": nullable("integer"),
+        "n_gpu_layers": nullable("integer"),
+        "n_threads": nullable("integer"),
+        "flash_attn": nullable("boolean"),
+        "cache_type": {
+            "enum": cache_types.clone(),
+            "description": "KV cache type for both K and V.",
+        },
+        "type_k": {
+            "enum": cache_types.clone(),
+            "description": "K cache type; overrides cache_type.",
+        },
+        "type_v": {
+            "enum": cache_types.clone(),
+            "description": "V cache type; overrides cache_type.  Quantized types need \
+                flash attention.",
+        },
+        "split_mode": {
+            "enum": ["none", "layer", "row", null],
+            "description": "How offloaded layers are spread over GPUs.",
+        },
+        "main_gpu": {
+            "type": ["integer", "null"],
+            "description": "GPU for the whole model with split_mode none, or for \
+                intermediate results with row.",
+        },
+        "tensor_split": {
+            "type": ["array", "null"],
+            "items": { "type": "number" },
+            "description": "Relative share of the offloaded layers per GPU; must not \
+                list more GPUs than are available.",
+        },
+        "kv_overrides": {
+            "type": ["array", "null"],
+            "description": "GGUF metadata to override when loading, like llama.cpp's \
+                --override-kv. String values are cut at 127 bytes.",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "key": { "type": "string", "maxLength": 127 },
+                    "type": { "enum": ["int", "float", "bool", "str"] },
+                    "value": { "type": ["integer", "number", "boolean", "string"] },
+                },
+                "required": ["key", "type", "value"],
+            },
+        },
+        "rope_scaling_type": {
+            "enum": ["none", "linear", "yarn", null],
+            "description": "RoPE scaling for contexts past the training length. \
+                This and the rope_/yarn_ fields below keep the model's values when null.",
+        },
+        "rope_freq_base": nullable("number"),
+        "rope_freq_scale": {
+            "type": ["number", "null"],
+            "description": "e.g. 0.25 for four times the training context.",
+        },
+        "yarn_ext_factor": nullable("number"),
+        "yarn_attn_factor": nullable("number"),
+        "yarn_beta_fast": nullable("number"),
+        "yarn_beta_slow": nullable("number"),
+        "yarn_orig_ctx": nullable("integer"),
+    });
+    spec.component(
+        "ModelSettings",
+        json!({
+            "type": "object",
+            "description": "Load parameters; unset fields fall back to the stored \
+                settings, then the global defaults.",
+            "properties": settings,
+        }),
+    );
+    let mut load = settings.clone();
+    load["force"] = json!({
+        "type": "boolean",
+        "default": false,
+        "description": "Skip the pre-load memory check.",
+    });
+    spec.component(
+        "LoadModelRequest",
+        json!({ "type": "object", "properties": load }),
+    );
+    let mut load_by_path = load.clone();
+    load_by_path["path"] = json!({ "type": "string" });
+    spec.component(
+        "LoadByPathRequest",
+        json!({ "type": "object", "properties": load_by_path, "required": ["path"] }),
+    );
+    let special_token = json!({
+        "type": ["object", "null"],
+        "properties": {
+            "id": { "type": "integer" },
+            "text": nullable("string"),
+        },
+        "required": ["id", "text"],
+    });
+    spec.component(
+        "TokenizerInfo",
+        json!({
+            "type": "object",
+            "properties": {
+                "source": {
+                    "enum": ["model", "gguf"],
+                    "description": "`model` for loaded models; `gguf` reads file metadata, \
+                        where fields past the scan window are null.",
+                },
+                "n_vocab": nullable("integer"),
+                "bos": special_token,
+                "eos": special_token,
+                "eot": special_token,
+                "add_bos": nullable("boolean"),
+                "chat_template_name": {
+                    "type": ["string", "null"],
+                    "description": "Well-known format of the embedded chat template, \
+                        e.g. chatml or llama3.",
+                },
+            },
+            "required": [
+                "source", "n_vocab", "bos", "eos", "eot", "add_bos", "chat_template_name",
+            ],
+        }),
+    );
+    spec.component(
+        "ModelInfo",
+        json!({
+            "type": "object",
+            "properties": {
+                "n_ctx_train": { "type": "integer" },
+                "n_embd": { "type": "integer" },
+                "n_layer": { "type": "integer" },
+                "n_head": { "type": "integer" },
+                "n_head_kv": {
+                    "type": "integer",
+                    "description": "Fewer than n_head with grouped-query attention.",
+                },
+                "rope_freq_base": nullable("number"),
+                "rope_freq_scale_train": { "type": "number" },
+                "vocab_type": { "enum": ["none", "spm", "bpe", "wpm", "ugm", "rwkv", "other"] },
+            },
+            "required": [
+                "n_ctx_train", "n_embd", "n_layer", "n_head", "n_head_kv", "rope_freq_base",
+                "rope_freq_scale_train", "vocab_type",
+            ],
+        }),
+    );
+    spec.component(
+        "Capability",
+        json!({ "enum": ["chat", "embeddings", "vision-projector"] }),
+    );
+    spec.component(
+        "ModelFeature",
+        json!({ "enum": ["moe", "vision", "embedding", "instruct", "reasoning"] }),
+    );
+    spec.component(
+        "AdapterEntry",
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "filename": { "type": "string" },
+                "path": { "type": "string" },
+                "size": { "type": "integer" },
+                "architecture": nullable("string"),
+                "base_model": {
+                    "type": ["string", "null"],
+                    "description": "The model the adapter was trained against, \
+                        from `adapter.lora.base_model` or `general.base_model.0.name`.",
+                },
+            },
+            "required": ["id", "filename", "path", "size", "architecture", "base_model"],
+        }),
+    );
+    spec.component(
+        "ModelEntry",
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "filename": { "type": "string" },
+                "path": { "type": "string" },
+                "size": { "type": "integer" },
+                "architecture": nullable("string"),
+                "parameters": nullable("string"),
+                "param_count": nullable("integer"),
+                "canonical_path": {
+                    "type": "string",
+                    "description": "`path` with symlinks resolved.",
+                },
+                "hash": {
+                    "type": ["string", "null"],
+                    "description": "SHA-256 of the model file, if recorded since it last changed.",
+                },
+                "bytes_per_param": {
+                    "type": ["number", "null"],
+                    "description": "Average weight storage per parameter, from the tensor types.",
+                },
+                "capabilities": {
+                    "type": "array",
+                    "items": schema_ref("Capability"),
+                    "description": "What the model can serve.",
+                },
+                "kind": {
+                    "enum": ["base", "mmproj"],
+                    "description": "`mmproj` for a vision projector no model was found for.",
+                },
+                "features": {
+                    "type": "array",
+                    "items": schema_ref("ModelFeature"),
+                    "description": "Badges guessed from the metadata.",
+                },
+                "context_length": nullable("integer"),
+                "file_type": nullable("string"),
+                "quantization": nullable("string"),
+                "chat_template": nullable("string"),
+                "status": { "enum": ["loaded", "unloaded"] },
+                "favorite": { "type": "boolean" },
+                "alias": nullable("string"),
+                "display_name": nullable("string"),
+                "collision": {
+                    "type": "boolean",
+                    "description": "Another model shares this file name; \
+                        `id` may carry a path hash.",
+                },
+                "missing_parts": {
+                    "type": "array",
+                    "items": { "type": "integer" },
+                    "description": "Part numbers of a split model missing on disk; \
+                        such a model can't be loaded.",
+                },
+                "metadata_source": {
+                    "enum": ["scanned", "filename"],
+                    "description": "`filename` when the metadata couldn't be read; size, \
+                        quantization and features are then guesses from the file name.",
+                },
+                "scan_error": {
+                    "type": ["string", "null"],
+                    "description": "Why the metadata couldn't be read.",
+                },
+                "settings": {
+                    "$ref": "#/components/schemas/ModelSettings",
+                    "description": "Effective load settings (details endpoint only).",
+                },
+                "tokenizer": {
+                    "$ref": "#/components/schemas/TokenizerInfo",
+                    "description": "Special tokens and template format (details endpoint only).",
+                },
+                "model_info": {
+                    "$ref": "#/components/schemas/ModelInfo",
+                    "description": "Model shape, from the loaded model or file metadata \
+                        (details endpoint only).",
+                },
+            },
+            "required": [
+                "id", "filename", "path", "canonical_path", "size", "architecture", "parameters",
+                "param_count", "bytes_per_param", "hash", "capabilities", "kind", "features", "context_length", "file_type", "quantization", "chat_template",
+                "status", "favorite", "alias", "display_name", "collision", "missing_parts",
+                "metadata_source", "scan_error",
+            ],
+        }),
+    );
+    let config = json!({
+        "model_dirs": { "type": "array", "items": { "type": "string" } },
+        "default_ctx_size": { "type": "integer" },
+        "default_n_gpu_layers": { "type": "integer" },
+        "default_temperature": { "type": "number" },
+        "api_key": nullable("string"),
+        "auto_load_models": { "type": "boolean" },
+        "allow_external_paths": {
+            "type": "boolean",
+            "description": "Read-only; it lifts the model directory allowlist, so it is \
+                only set in the configuration file or with `config set`.",
+        },
+        "rate_limit_per_minute": { "type": "integer" },
+        "model_ops_rate_limit_per_minute": { "type": "integer" },
+        "max_body_bytes": { "type": "integer" },
+        "generation_timeout_secs": { "type": "integer" },
+        "compression_enabled": { "type": "boolean" },
+        "tls_cert": nullable("string"),
+        "tls_key": nullable("string"),
+        "http_redirect_port": nullable("integer"),
+        "numa": {
+            "enum": ["disabled", "distribute", "isolate", "numactl", "mirror"],
+            "description": "NUMA strategy; takes effect after a restart.",
+        },
+    });
+    let required: Vec<&String> = config
+        .as_object()
+        .into_iter()
+        .flat_map(|m| m.keys())
+        .collect();
+    spec.component(
+        "Config",
+        json!({ "type": "object", "properties": config, "required": required }),
+    );
+    // Everything but the read-only allowlist switch can be updated
+    let mut update = config.clone();
+    if let Some(fields) = update.as_object_mut() {
+        fields.remove("allow_external_paths");
+    }
+    spec.component(
+        "ConfigUpdate",
+        json!({
+            "type": "object",
+            "description": "Fields to change; omitted fields are kept.  An empty \
+                `api_key`, `tls_cert` or `tls_key` clears it, as does \
+                `http_redirect_port` 0.",
+            "properties": update,
+        }),
+    );
+    spec.component(
+        "SystemInfo",
+        json!({
+            "type": "object",
+            "properties": {
+                "version": { "type": "string" },
+                "models_loaded": { "type": "integer" },
+                "models_available": { "type": "integer" },
+                "memory_used_bytes": { "type": "integer" },
+                "max_memory_bytes": { "type": "integer" },
+                "last_scan": { "type": "object" },
+                "compiled_backends": { "type": "array", "items": { "type": "string" } },
+                "devices": { "type": "array", "items": { "type": "object" } },
+                "supports": {
+                    "type": "object",
+                    "properties": {
+                        "mmap": { "type": "boolean" },
+                        "mlock": { "type": "boolean" },
+                        "gpu_offload": { "type": "boolean" },
+                    },
+                },
+                "numa": {
+                    "enum": ["disabled", "distribute", "isolate", "numactl", "mirror"],
+                    "description": "NUMA strategy in effect.",
+                },
+                "loaded_models": { "type": "array", "items": { "type": "object" } },
+                "generations_in_flight": { "type": "integer" },
+            },
+        }),
+    );
+
+    let id = param("path", "id", json!({ "type": "string" }), "Model id");
+    let object = json!({ "type": "object" });
+    let ok = |description: &str| json_response(description, json!({ "type": "object" }));
+    let body = |schema: Value| {
+        json!({
+            "required": true,
+            "content": { "application/json": { "schema": schema } },
+        })
+    };
+    let error = |description: &str| json_response(description, schema_ref("Error"));
+    let not_found = error("Unknown model");
+    let failed = error("Internal error");
+    let mut add = |method: &str, path: &str, summary: &str, mut op: Value| {
+        let tag = if path.starts_with("/api/config") || path.starts_with("/api/system") {
+            "System"
+        } else {
+            "Models"
+        };
+        op["tags"] = json!([tag]);
+        op["summary"] = summary.into();
+        if path.contains("{id}") {
+            let mut params = vec![id.clone()];
+            params.extend(op["parameters"].as_array().into_iter().flatten().cloned());
+            op["parameters"] = params.into();
+        }
+        spec.operation(method, path, op);
+    };
+
+    add(
+        "get",
+        "/api/models",
+        "List discovered models",
+        json!({
+            "parameters": [
+                param(
+                    "query",
+                    "sort",
+                    json!({ "enum": ["favorite"] }),
+                    "`favorite` lists favorites first",
+                ),
+                param(
+                    "query",
+                    "capability",
+                    schema_ref("Capability"),
+                    "Only models with this capability",
+                ),
+                param(
+                    "query",
+                    "feature",
+                    schema_ref("ModelFeature"),
+                    "Only models with this feature",
+                ),
+            ],
+            "responses": {
+                "200": json_response("Models", json!({
+                    "type": "array",
+                    "items": schema_ref("ModelEntry"),
+                })),
+                "500": failed,
+            },
+        }),
+    );
+    add(
+        "get",
+        "/api/adapters",
+        "List LoRA adapters",
+        json!({
+            "responses": {
+                "200": json_response("Adapters", json!({
+                    "type": "array",
+                    "items": schema_ref("AdapterEntry"),
+                })),
+            },
+        }),
+    );
+    add(
+        "post",
+        "/api/models/scan",
+        "Rescan the model directories",
+        json!({
+            "parameters": [
+                param(
+                    "query",
+                    "force",
+                    json!({ "type": "boolean", "default": false }),
+                    "Read every file again instead of reusing results for unchanged files",
+                ),
+            ],
+            "responses": { "200": ok("Number of models found") },
+        }),
+    );
+    add(
+        "get",
+        "/api/models/loaded",
+        "Loaded models and their slots",
+        json!({
+            "responses": {
+                "200": json_response("Slots", json!({ "type": "array", "items": object })),
+            },
+        }),
+    );
+    add(
+        "post",
+        "/api/models/load-by-path",
+        "Load a model file by path",
+        json!({
+            "requestBody": json_body("LoadByPathRequest"),
+            "responses": {
+                "200": ok("Loaded"),
+                "400": error("Not a model file"),
+                "403": error("Path outside the model directories"),
+                "404": error("No such file"),
+                "409": error("Not enough memory"),
+                "422": error("Split model with parts missing"),
+                "500": error("Load failed"),
+                "502": error("Not a usable model (code `invalid_model`)"),
+                "503": error("Loading is paused for maintenance"),
+            },
+        }),
+    );
+    add(
+        "get",
+        "/api/models/{id}/details",
+        "Model details with effective settings",
+        json!({
+            "responses": {
+                "200": json_response("Model", schema_ref("ModelEntry")),
+                "404": not_found,
+            },
+        }),
+    );
+    add(
+        "post",
+        "/api/models/{id}/load",
+        "Load a model",
+        json!({
+            "parameters": [param(
+                "query", "async", json!({ "type": "boolean" }),
+                "Return 202 with a job id and load in the background",
+            )],
+            "requestBody": json_body("LoadModelRequest"),
+            "responses": {
+                "200": ok("Loaded; `flash_attn` is the requested mode (enabled, disabled or auto)"),
+                "202": ok("Loading in the background"),
+                "404": error("Unknown model"),
+                "409": error("Not enough memory"),
+                "422": error("Split model with parts missing"),
+                "500": error("Load failed"),
+                "502": error("Not a usable model (code `invalid_model`)"),
+                "503": error("Loading is paused for maintenance"),
+            },
+        }),
+    );
+    add(
+        "post",
+        "/api/models/{id}/unload",
+        "Unload a model",
+        json!({
+            "responses": { "200": ok("Unloaded") },
+        }),
+    );
+    add(
+        "post",
+        "/api/models/unload-all",
+        "Drain and unload every model, then pause loading",
+        json!({
+            "parameters": [
+                param("query", "timeout", json!({ "type": "integer", "default": 30 }),
+                    "Seconds to wait for in-flight requests per model"),
+                param(
+                    "query",
+                    "reason",
+                    json!({ "type": "string" }),
+                    "Shown while loading is paused",
+                ),
+            ],
+            "responses": { "200": ok("Unloaded models") },
+        }),
+    );
+    add(
+        "post",
+        "/api/maintenance/resume",
+        "Allow model loading again",
+        json!({
+            "responses": { "200": ok("Resumed") },
+        }),
+    );
+    add(
+        "delete",
+        "/api/models/{id}/file",
+        "Delete a model file from disk",
+        json!({
+            "parameters": [
+                param(
+                    "query",
+                    "include_companions",
+                    json!({ "type": "boolean" }),
+                    "Also delete the mmproj projector",
+                ),
+                param(
+                    "query",
+                    "dry_run",
+                    json!({ "type": "boolean" }),
+                    "Only report what would be deleted",
+                ),
+            ],
+            "responses": {
+                "200": ok("Deleted files"),
+                "207": ok("Some files could not be deleted"),
+                "403": error("File outside the configured model directories"),
+                "404": not_found,
+                "409": error("The model is in use"),
+            },
+        }),
+    );
+    add(
+        "get",
+        "/api/models/{id}/status",
+        "Load state and background job of a model",
+        json!({
+            "responses": { "200": ok("Status") },
+        }),
+    );
+    add(
+        "post",
+        "/api/models/{id}/clear-error",
+        "Forget a failed background load",
+        json!({
+            "responses": { "200": ok("Cleared"), "404": not_found },
+        }),
+    );
+    add(
+        "get",
+        "/api/models/{id}/estimate",
+        "Estimate memory needed to load a model",
+        json!({
+            "parameters": [
+                param(
+                    "query",
+                    "ctx_size",
+                    json!({ "type": "integer" }),
+                    "Context size to estimate for",
+                ),
+                param(
+                    "query",
+                    "n_gpu_layers",
+                    json!({ "type": "integer" }),
+                    "Layers to offload (-1 for all); splits the estimate into RAM and VRAM",
+                ),
+                param(
+                    "query",
+                    "flash_attn",
+                    json!({ "type": "boolean" }),
+                    "Estimate with flash attention on (drops the attention score buffer)",
+                ),
+                param(
+                    "query",
+                    "type_k",
+                    json!({ "type": "string" }),
+                    "K cache type, e.g. q8_0",
+                ),
+                param(
+                    "query",
+                    "type_v",
+                    json!({ "type": "string" }),
+                    "V cache type, e.g. q8_0",
+                ),
+            ],
+            "responses": {
+                "200": ok("Estimate and available memory"),
+                "404": not_found,
+                "422": error("Unreadable model file"),
+            },
+        }),
+    );
+    add(
+        "get",
+        "/api/models/{id}/metadata",
+        "GGUF metadata of a model",
+        json!({
+            "parameters": [
+                param(
+                    "query", "include_arrays", json!({ "type": "boolean" }),
+                    "Include long arrays such as the vocabulary",
+                ),
+                param(
+                    "query", "tensors", json!({ "type": "boolean" }),
+                    "Add `tensors` (name, dims, ggml type, offset) and `tensor_types` \
+                     (tensor count per type)",
+                ),
+                param(
+                    "query", "raw", json!({ "type": "boolean" }),
+                    "Type-tagged values (`{\"Uint32\": 32768}`) instead of plain JSON, \
+                     where 64-bit integers past 2^53 and non-finite floats are strings",
+                ),
+            ],
+            "responses": {
+                "200": ok("Metadata"),
+                "404": not_found,
+                "422": error("Unreadable model file"),
+            },
+        }),
+    );
+    add(
+        "post",
+        "/api/models/{id}/classify",
+        "Score texts with a loaded classifier or reward model",
+        json!({
+            "requestBody": body(json!({
+                "type": "object",
+                "properties": {
+                    "texts": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "minItems": 1,
+                        "maxItems": MAX_CLASSIFY_TEXTS,
+                    },
+                },
+                "required": ["texts"],
+            })),
+            "responses": {
+                "200": json_response("Per-text label scores", json!({
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "data": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "index": { "type": "integer" },
+                                    "label": { "type": "string" },
+                                    "scores": {
+                                        "type": "array",
+                                        "items": {
+                                            "type": "object",
+                                            "properties": {
+                                                "label": { "type": "string" },
+                                                "score": { "type": "number" },
+                                                "probability": { "type": "number" },
+                                            },
+                                        },
+                                    },
+                                    "n_tokens": { "type": "integer" },
+                                },
+                            },
+                        },
+                        "usage": {
+                            "type": "object",
+                            "properties": { "prompt_tokens": { "type": "integer" } },
+                        },
+                    },
+                })),
+                "400": error("Invalid texts, or the model is not a classifier"),
+                "404": error("Model not loaded"),
+            },
+        }),
+    );
+    add(
+        "post",
+        "/api/models/{id}/verify",
+        "Re-hash a model's files and compare with the expected SHA-256",
+        json!({
+            "requestBody": {
+                "required": false,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "properties": {
+                        "sha256": {
+                            "type": "string",
+                            "pattern": "^[0-9a-fA-F]{64}$",
+                            "description": "Expected hash (single-file models); \
+                                defaults to the recorded one.",
+                        },
+                    },
+                } } },
+            },
+            "responses": {
+                "200": json_response("Per-file outcome", json!({
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "ok": { "type": "boolean" },
+                        "files": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "path": { "type": "string" },
+                                    "sha256": { "type": "string" },
+                                    "expected": nullable("string"),
+                                    "status": { "enum": ["ok", "mismatch", "recorded"] },
+                                },
+                            },
+                        },
+                    },
+                })),
+                "400": error("Malformed hash, or a hash given for a split model"),
+                "404": error("Unknown model"),
+                "500": error("The files could not be read"),
+            },
+        }),
+    );
+    add(
+        "get",
+        "/api/models/{id}/bench",
+        "Stored benchmark results",
+        json!({
+            "responses": {
+                "200": json_response("Results", json!({ "type": "array", "items": object })),
+                "500": failed,
+            },
+        }),
+    );
+    add(
+        "post",
+        "/api/models/{id}/bench",
+        "Benchmark a loaded model",
+        json!({
+            "requestBody": body(json!({
+                "type": "object",
+                "properties": {
+                    "pp": { "type": "integer", "default": 512 },
+                    "tg": { "type": "integer", "default": 128 },
+                    "repetitions": {
+                        "type": "integer",
+                        "default": 3,
+                        "maximum": MAX_BENCH_REPETITIONS,
+                    },
+                    "force": { "type": "boolean", "default": false },
+                },
+            })),
+            "responses": {
+                "200": ok("Result"),
+                "400": error("Invalid parameters"),
+                "404": error("Model not loaded"),
+                "409": error("The model is busy"),
+                "503": error("The KV cache is full (code `kv_cache_full`)"),
+            },
+        }),
+    );
+    add(
+        "get",
+        "/api/models/{id}/stats",
+        "Usage statistics of a model",
+        json!({
+            "responses": { "200": ok("Statistics") },
+        }),
+    );
+    add(
+        "delete",
+        "/api/models/{id}/stats",
+        "Reset usage statistics",
+        json!({
+            "responses": { "200": ok("Reset"), "404": not_found },
+        }),
+    );
+    add(
+        "put",
+        "/api/models/{id}/favorite",
+        "Toggle favorite",
+        json!({
+            "responses": { "200": ok("New state"), "500": failed },
+        }),
+    );
+    add(
+        "put",
+        "/api/models/{id}/pin",
+        "Pin a model against eviction",
+        json!({
+            "requestBody": body(json!({
+                "type": "object",
+                "properties": { "pinned": { "type": "boolean" } },
+                "required": ["pinned"],
+            })),
+            "responses": { "200": ok("Pinned"), "404": error("Model not loaded") },
+        }),
+    );
+    add(
+        "put",
+        "/api/models/{id}/alias",
+        "Set or clear an alias",
+        json!({
+            "requestBody": body(json!({
+                "type": "object",
+                "properties": { "alias": nullable("string") },
+            })),
+            "responses": {
+                "200": ok("Alias set"),
+                "404": not_found,
+                "409": error("Alias taken"),
+            },
+        }),
+    );
+    add(
+        "put",
+        "/api/models/{id}/name",
+        "Set or clear the display name",
+        json!({
+            "requestBody": body(json!({
+                "type": "object",
+                "properties": {
+                    "display_name": {
+                        "type": ["string", "null"],
+                        "maxLength": MAX_DISPLAY_NAME_LEN,
+                    },
+                },
+            })),
+            "responses": {
+                "200": ok("Name set"),
+                "400": error("Name too long"),
+                "404": not_found,
+            },
+        }),
+    );
+    add(
+        "get",
+        "/api/models/{id}/settings",
+        "Stored and effective load settings",
+        json!({
+            "responses": { "200": ok("Settings") },
+        }),
+    );
+    add(
+        "put",
+        "/api/models/{id}/settings",
+        "Store load settings",
+        json!({
+            "requestBody": json_body("ModelSettings"),
+            "responses": { "200": ok("Stored"), "500": failed },
+        }),
+    );
+    add(
+        "get",
+        "/api/aliases",
+        "List aliases",
+        json!({
+            "responses": {
+                "200": json_response("Aliases", json!({
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "alias": { "type": "string" },
+                            "model_id": { "type": "string" },
+                        },
+                        "required": ["alias", "model_id"],
+                    },
+                })),
+            },
+        }),
+    );
+    add(
+        "get",
+        "/api/config",
+        "Current configuration",
+        json!({
+            "responses": { "200": json_response("Configuration", schema_ref("Config")) },
+        }),
+    );
+    add(
+        "put",
+        "/api/config",
+        "Change configuration",
+        json!({
+            "requestBody": json_body("ConfigUpdate"),
+            "responses": {
+                "200": ok("Saved"),
+                "400": error("Invalid TLS certificate or key"),
+                "500": failed,
+            },
+        }),
+    );
+    add(
+        "get",
+        "/api/system/info",
+        "Version, backends, devices and loaded models",
+        json!({
+            "responses": { "200": json_response("System information", schema_ref("SystemInfo")) },
+        }),
+    );
+    add(
+        "get",
+        "/api/system/resources",
+        "CPU, RAM, swap, GPU and per-model usage",
+        json!({
+            "responses": { "200": ok("Resource snapshot"), "500": failed },
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{Body, to_bytes};
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::routes::openapi;
+    use crate::test_util::TempDir;
+
+    async fn get_json(app: Router, path: &str) -> Value {
+        let req = Request::get(path).body(Body::empty()).unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        serde_json::from_slice(&to_bytes(resp.into_body(), usize::MAX).await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn responses_match_their_schemas() {
+        let dir = TempDir::new("llama-dashboard-openapi");
+        std::fs::write(dir.0.join("tiny-q4_k_m.gguf"), b"").unwrap();
+        let config = AppConfig {
+            model_dirs: vec![dir.0.clone()],
+            tls_cert: Some("cert.pem".into()),
+            ..Default::default()
+        };
+        let app = crate::routes::app(AppState::for_tests(config, None));
+        let models = get_json(app.clone(), "/api/models").await;
+        let config = get_json(app, "/api/config").await;
+
+        let spec = openapi::spec();
+        let list = json!({ "type": "array", "items": openapi::schema_ref("ModelEntry") });
+        assert_eq!(models.as_array().unwrap().len(), 1);
+        openapi::validate(&spec, &list, &models).unwrap();
+        openapi::validate(&spec, &openapi::schema_ref("Config"), &config).unwrap();
+    }
+
+    #[test]
+    fn request_schemas_describe_request_types() {
+        let spec = openapi::spec();
+        let update = json!({
+            "model_dirs": ["/models"], "default_ctx_size": 8192, "default_n_gpu_layers": -1,
+            "default_temperature": 0.7, "api_key": "", "auto_load_models": true,
+            "rate_limit_per_minute": 60,
+            "model_ops_rate_limit_per_minute": 10, "max_body_bytes": 1024,
+            "generation_timeout_secs": 60, "compression_enabled": false, "tls_cert": "",
+            "tls_key": "", "http_redirect_port": 0, "numa": "numactl",
+        });
+        openapi::validate(&spec, &openapi::schema_ref("ConfigUpdate"), &update).unwrap();
+        serde_json::from_value::<ConfigUpdate>(update).unwrap();
+
+        let load = json!({
+            "path": "/models/m.gguf", "ctx_size": 4096, "n_gpu_layers": 0, "n_threads": null,
+            "flash_attn": true, "cache_type": "q8_0",
+            "split_mode": "row", "main_gpu": 1, "tensor_split": [3, 1],
+            "kv_overrides": [{ "key": "llama.rope.freq_base", "type": "float", "value": 1e6 }],
+            "rope_scaling_type": "yarn", "rope_freq_scale": 0.25, "yarn_orig_ctx": 8192,
+            "force": true,
+        });
+        let schema = openapi::schema_ref("LoadByPathRequest");
+        openapi::validate(&spec, &schema, &load).unwrap();
+        let req: LoadByPathRequest = serde_json::from_value(load).unwrap();
+        assert_eq!(
+            req.params.settings.cache_type,
+            Some(llama_core::KvCacheType::Q8_0)
+        );
+        assert_eq!(
+            req.params.settings.split_mode,
+            Some(llama_core::SplitMode::Row)
+        );
+        assert_eq!(req.params.settings.tensor_split, Some(vec![3.0, 1.0]));
+        assert_eq!(
+            req.params.settings.kv_overrides.unwrap()[0].value,
+            llama_core::KvOverrideValue::Float(1e6)
+        );
+        assert_eq!(req.params.settings.rope.yarn_orig_ctx, Some(8192));
+        assert_eq!(req.params.settings.rope.rope_freq_scale, Some(0.25));
+    }
+
+    #[test]
+    fn classifier_outputs_become_labelled_probabilities() {
+        let labels = vec!["negative".to_string(), "positive".to_string()];
+        let scores = label_scores(&labels, &[0.0, 2.0_f32.ln()]);
+        assert_eq!(scores[1].label, "positive");
+        assert!((scores[0].probability - 1.0 / 3.0).abs() < 1e-6);
+        assert!((scores[1].probability - 2.0 / 3.0).abs() < 1e-6);
+
+        // Reward models have one unlabelled output
+        let reward = label_scores(&[], &[0.0]);
+        assert_eq!(reward[0].label, "LABEL_0");
+        assert_eq!(reward[0].probability, 0.5);
+    }
+
+    #[test]
+    fn llama_cpp_metadata_text_keeps_the_file_type() {
+        use gguf_parser::GGUFValue as V;
+        let parsed = |value: V, text: &str| Value::from(&parse_like(&value, text));
+
+        assert_eq!(
+            parsed(V::Uint64(0), "18446744073709551615"),
+            json!("18446744073709551615")
+        );
+        assert_eq!(parsed(V::Uint32(0), "32768"), json!(32768));
+        assert_eq!(parsed(V::Float32(0.0), "10000.000000"), json!(10000.0));
+        assert_eq!(parsed(V::Bool(false), "true"), json!(true));
+        // Unparseable text falls back to the file's value
+        assert_eq!(parsed(V::Int8(-3), "n/a"), json!(-3));
+    }
+}
@@ -1,6 +1,155 @@
+pub mod chat;
+pub mod downloads;
+pub mod error;
 pub mod health;
+pub mod keys;
+pub mod logs;
 pub mod management;
 pub mod native;
 pub mod openai;
+pub mod openapi;
 pub mod spa;
+pub mod usage;
+pub mod validation;
 pub mod ws;
+
+use axum::{
+    Router,
+    extract::DefaultBodyLimit,
+    http::{Extensions, HeaderMap, StatusCode},
+};
+use tower_http::compression::{CompressionLayer, DefaultPredicate, Predicate};
+
+use crate::middleware::auth::{require_admin, require_events, require_inference};
+use crate::middleware::rate_limit::rate_limit;
+use crate::middleware::request_id::request_id;
+use crate::state::AppState;
+
+/// All routes.  Inference routes and `/ws/events` need a key with the
+/// `inference` scope, everything else but `/health`, `/openapi.json` and
+/// the SPA fallback an `admin` key.
+/// Authenticated requests are then rate limited.  Every request, rejected
+/// or not, gets a request id and tracing span.  Bodies are capped at
+/// `max_body_bytes` unless a route sets its own limit, and responses are
+/// compressed when `compression_enabled` is set.
+pub fn app(state: AppState) -> Router {
+    // The last layer added runs first, so auth precedes rate limiting.
+    let limit = axum::middleware::from_fn_with_state(state.clone(), rate_limit);
+    let inference = Router::new()
+        .merge(openai::router())
+        .merge(native::router())
+        .merge(ws::generate_router())
+        .route_layer(limit.clone())
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            require_inference,
+        ));
+    let admin = Router::new()
+        .merge(management::router())
+        .merge(keys::router())
+        .merge(chat::router())
+        .merge(downloads::router())
+        .merge(usage::router())
+        .merge(logs::router())
+        .route_layer(limit.clone())
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            require_admin,
+        ));
+    let events = ws::router()
+        .route_layer(limit)
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            require_events,
+        ));
+
+    Router::new()
+        .merge(health::router())
+        .merge(openapi::router())
+        .merge(inference)
+        .merge(admin)
+        .merge(events)
+        .merge(spa::router())
+        .layer(DefaultBodyLimit::max(state.config().max_body_bytes))
+        .layer(axum::middleware::from_fn(request_id))
+        .layer(compression(state.config().compression_enabled))
+        .with_state(state)
+}
+
+/// gzip/brotli for responses worth compressing.  [`DefaultPredicate`]
+/// already skips tiny bodies and `text/event-stream`, whose tokens must
+/// not sit in an encoder buffer; protocol upgrades are excluded as well.
+fn compression(enabled: bool) -> CompressionLayer<impl Predicate> {
+    let predicate = DefaultPredicate::new().and(
+        move |status: StatusCode, _, _: &HeaderMap, _: &Extensions| {
+            enabled && status != StatusCode::SWITCHING_PROTOCOLS
+        },
+    );
+    CompressionLayer::new()
+        .gzip(true)
+        .br(true)
+        .compress_when(predicate)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use axum::{
+        body::Body,
+        http::{Request, header},
+        response::sse::{Event, Sse},
+        routing::get,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::test_util::TempDir;
+
+    async fn content_encoding(app: Router, path: &str) -> Option<String> {
+        let req = Request::get(path)
+            .header(header::ACCEPT_ENCODING, "gzip, br")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert!(resp.status().is_success());
+        resp.headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|v| v.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn model_list_is_compressed_unless_disabled() {
+        let dir = TempDir::new("llama-dashboard-gzip");
+        for i in 0..20 {
+            std::fs::write(dir.0.join(format!("model-{i}-q4_k_m.gguf")), b"").unwrap();
+        }
+        let config = |compression_enabled| AppConfig {
+            model_dirs: vec![dir.0.clone()],
+            compression_enabled,
+            ..Default::default()
+        };
+
+        let enabled = app(AppState::for_tests(config(true), None));
+        let encoding = content_encoding(enabled, "/api/models").await;
+        let disabled = app(AppState::for_tests(config(false), None));
+        let plain = content_encoding(disabled, "/api/models").await;
+
+        assert_eq!(encoding.as_deref(), Some("br"));
+        assert_eq!(plain, None);
+    }
+
+    #[tokio::test]
+    async fn event_streams_are_not_compressed() {
+        let sse = || async {
+            let events =
+                (0..100).map(|i| Ok::<_, Infallible>(Event::default().data(format!("token {i}"))));
+            Sse::new(futures_util::stream::iter(events))
+        };
+        let app = Router::new()
+            .route("/stream", get(sse))
+            .layer(compression(true));
+        assert_eq!(content_encoding(app, "/stream").await, None);
+    }
+}
@@ -1,14 +1,36 @@
-//! Native llama.cpp API routes: /tokenize, /detokenize
+//! Native llama.cpp API routes: /tokenize, /detokenize, /infill
 
-use axum::{Json, Router, extract::State, routing::post};
+use std::convert::Infallible;
+
+use axum::{
+    Json, Router,
+    extract::{Extension, State},
+    http::StatusCode,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::post,
+};
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::error;
 
+use crate::middleware::auth::ApiKeyId;
+use crate::routes::error::ApiError;
+use crate::routes::openapi::{Spec, json_body, json_or_sse_response, json_response, text_response};
+use crate::services::inference::{generation_timeout, spawn_generation};
+use crate::services::request_log::RequestMeta;
 use crate::state::AppState;
 
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/tokenize", post(tokenize))
         .route("/detokenize", post(detokenize))
+        .route("/infill", post(infill))
 }
 
 #[derive(Deserialize)]
@@ -56,8 +78,10 @@ async fn tokenize(
 
     state.model_manager().touch(&loaded.id);
 
-    let vocab = loaded.model.vocab();
-    let tokens = llama_core::tokenize(vocab, &req.content, req.add_special, req.parse_special)
+    let tokens = loaded
+        .model
+        .vocab()
+        .tokenize(&req.content, req.add_special, req.parse_special)
         .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
 
     Ok(Json(TokenizeResponse { tokens }))
@@ -74,9 +98,497 @@ async fn detokenize(
 
     state.model_manager().touch(&loaded.id);
 
-    let vocab = loaded.model.vocab();
-    let content = llama_core::detokenize(vocab, &req.tokens)
+    let content = loaded
+        .model
+        .vocab()
+        .detokenize(&req.tokens)
         .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
 
     Ok(Json(DetokenizeResponse { content }))
 }
+
+//  /infill
+
+#[derive(Deserialize)]
+struct InfillRequest {
+    #[serde(default)]
+    input_prefix: String,
+    #[serde(default)]
+    input_suffix: String,
+    /// Extra context files, e.g. other open buffers in the editor.
+    #[serde(default)]
+    input_extra: Vec<InfillExtra>,
+    /// Text placed after `<MID>` as the start of the completion.
+    #[serde(default)]
+    prompt: String,
+    /// Tokens to generate; -1 = until the context is full.
+    #[serde(default = "default_n_predict")]
+    n_predict: i32,
+    #[serde(default)]
+    stop: Vec<String>,
+    #[serde(default)]
+    stream: bool,
+    /// Accepted for compatibility; the KV cache is cleared per request.
+    #[serde(default = "default_true")]
+    #[allow(dead_code)]
+    cache_prompt: bool,
+    #[serde(flatten)]
+    sampling: llama_core::SamplingParams,
+    /// Optional model name. If omitted, uses the most recently used model.
+    #[serde(default)]
+    model: Option<String>,
+    /// Generation time limit in seconds; can only lower the server's.
+    #[serde(default)]
+    timeout: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct InfillExtra {
+    #[serde(default)]
+    filename: String,
+    text: String,
+}
+
+fn default_n_predict() -> i32 {
+    -1
+}
+
+/// Final `/completion`-style response; also the last event of a stream.
+#[derive(Serialize)]
+struct CompletionResult {
+    content: String,
+    model: String,
+    stop: bool,
+    /// `eos`, `limit` or `word`.
+    stop_type: &'static str,
+    stopping_word: String,
+    tokens_predicted: u32,
+    tokens_evaluated: u32,
+    /// Set when the time limit cut generation short.
+    truncated: bool,
+}
+
+/// Streamed text piece.
+#[derive(Serialize)]
+struct CompletionPiece {
+    content: String,
+    stop: bool,
+}
+
+fn stop_type(reason: &llama_core::FinishReason) -> (&'static str, String) {
+    match reason {
+        llama_core::FinishReason::Stop => ("eos", String::new()),
+        llama_core::FinishReason::Length | llama_core::FinishReason::Timeout => {
+            ("limit", String::new())
+        }
+        llama_core::FinishReason::StopWord(w) => ("word", w.clone()),
+    }
+}
+
+fn error_payload(err: &llama_core::GenerateError) -> String {
+    let error_type = ApiError::from(err).error_type;
+    serde_json::json!({
+        "error": { "code": err.status, "message": err.message, "type": error_type }
+    })
+    .to_string()
+}
+
+/// POST /infill — fill-in-the-middle completion for code editors.
+async fn infill(
+    State(state): State<AppState>,
+    api_key: Option<Extension<ApiKeyId>>,
+    Json(req): Json<InfillRequest>,
+) -> Result<Response, (StatusCode, String)> {
+    let loaded = state.model_manager().resolve(req.model.as_deref()).ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "No model loaded".to_string(),
+    ))?;
+
+    let fim = loaded.model.fim_tokens().ok_or((
+        StatusCode::BAD_REQUEST,
+        format!(
+            "Model '{}' does not support infill (no FIM tokens)",
+            loaded.id
+        ),
+    ))?;
+    let extra: Vec<llama_core::InfillChunk> = req
+        .input_extra
+        .into_iter()
+        .map(|c| llama_core::InfillChunk {
+            filename: c.filename,
+            text: c.text,
+        })
+        .collect();
+    let tokens = llama_core::infill_tokens(
+        &loaded.model,
+        &fim,
+        &req.input_prefix,
+        &req.input_suffix,
+        &req.prompt,
+        &extra,
+    )
+    .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let gen_req = llama_core::GenerateRequest {
+        tokens,
+        max_tokens: u32::try_from(req.n_predict).unwrap_or(u32::MAX),
+        stop_words: req.stop,
+        sampling_params: req.sampling,
+        return_special: false,
+        ignore_eos: false,
+    };
+
+    let meta = RequestMeta {
+        endpoint: "/infill",
+        user: None,
+        prompt: state.request_log().logs_prompts().then(|| {
+            serde_json::json!({
+                "input_prefix": req.input_prefix,
+                "input_suffix": req.input_suffix,
+                "prompt": req.prompt,
+            })
+            .to_string()
+        }),
+        api_key_id: api_key.map(|Extension(ApiKeyId(id))| id),
+        timeout: generation_timeout(state.config().generation_timeout_secs, req.timeout),
+        cancel: None,
+    };
+    let model_id = loaded.id.clone();
+    let rx = spawn_generation(&state, loaded, gen_req, meta);
+    if req.stream {
+        let stream = completion_stream_payloads(rx, model_id)
+            .map(|data| Ok::<_, Infallible>(Event::default().data(data)));
+        Ok(Sse::new(stream)
+            .keep_alive(KeepAlive::default())
+            .into_response())
+    } else {
+        completion_non_stream(rx, model_id).await
+    }
+}
+
+/// Map generation events onto `/completion`-style SSE payloads.
+fn completion_stream_payloads(
+    rx: mpsc::Receiver<llama_core::GenerateEvent>,
+    model_id: String,
+) -> impl tokio_stream::Stream<Item = String> {
+    ReceiverStream::new(rx).map(move |event| match event {
+        llama_core::GenerateEvent::Token(content) => serde_json::to_string(&CompletionPiece {
+            content,
+            stop: false,
+        })
+        .unwrap_or_default(),
+        llama_core::GenerateEvent::Done {
+            finish_reason,
+            prompt_tokens,
+            completion_tokens,
+        } => {
+            let (stop_type, stopping_word) = stop_type(&finish_reason);
+            serde_json::to_string(&CompletionResult {
+                content: String::new(),
+                model: model_id.clone(),
+                stop: true,
+                stop_type,
+                stopping_word,
+                tokens_predicted: completion_tokens,
+                tokens_evaluated: prompt_tokens,
+                truncated: finish_reason == llama_core::FinishReason::Timeout,
+            })
+            .unwrap_or_default()
+        }
+        llama_core::GenerateEvent::Error(e) => {
+            error!("Generation error: {e}");
+            error_payload(&e)
+        }
+    })
+}
+
+async fn completion_non_stream(
+    mut rx: mpsc::Receiver<llama_core::GenerateEvent>,
+    model_id: String,
+) -> Result<Response, (StatusCode, String)> {
+    let mut result = CompletionResult {
+        content: String::new(),
+        model: model_id,
+        stop: true,
+        stop_type: "limit",
+        stopping_word: String::new(),
+        tokens_predicted: 0,
+        tokens_evaluated: 0,
+        truncated: false,
+    };
+    let mut done = false;
+    while let Some(event) = rx.recv().await {
+        match event {
+            llama_core::GenerateEvent::Token(piece) => result.content.push_str(&piece),
+            llama_core::GenerateEvent::Done {
+                finish_reason,
+                prompt_tokens,
+                completion_tokens,
+            } => {
+                if finish_reason == llama_core::FinishReason::Timeout {
+                    return Err((
+                        StatusCode::GATEWAY_TIMEOUT,
+                        "Generation timed out".to_string(),
+                    ));
+                }
+                (result.stop_type, result.stopping_word) = stop_type(&finish_reason);
+                result.tokens_predicted = completion_tokens;
+                result.tokens_evaluated = prompt_tokens;
+                done = true;
+            }
+            llama_core::GenerateEvent::Error(e) => {
+                error!("Generation error: {e}");
+                let status =
+                    StatusCode::from_u16(e.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                return Err((status, e.message));
+            }
+        }
+    }
+    // The generation task died without saying why
+    if !done {
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Generation ended unexpectedly".to_string(),
+        ));
+    }
+    Ok(Json(result).into_response())
+}
+
+//  OpenAPI
+
+/// Describe the routes above in `spec`.
+pub fn openapi(spec: &mut Spec) {
+    let model = json!({
+        "type": "string",
+        "description": "Model name; defaults to the most recently used model.",
+    });
+    spec.component(
+        "TokenizeRequest",
+        json!({
+            "type": "object",
+            "properties": {
+                "content": { "type": "string" },
+                "add_special": { "type": "boolean", "default": true },
+                "parse_special": { "type": "boolean", "default": false },
+                "model": model,
+            },
+            "required": ["content"],
+        }),
+    );
+    spec.component(
+        "DetokenizeRequest",
+        json!({
+            "type": "object",
+            "properties": {
+                "tokens": { "type": "array", "items": { "type": "integer" } },
+                "model": model,
+            },
+            "required": ["tokens"],
+        }),
+    );
+    spec.component(
+        "InfillRequest",
+        json!({
+            "type": "object",
+            "properties": {
+                "input_prefix": { "type": "string" },
+                "input_suffix": { "type": "string" },
+                "input_extra": {
+                    "type": "array",
+                    "description": "Extra context files, e.g. other open buffers.",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "filename": { "type": "string" },
+                            "text": { "type": "string" },
+                        },
+                        "required": ["text"],
+                    },
+                },
+                "prompt": {
+                    "type": "string",
+                    "description": "Start of the completion, placed after the middle token.",
+                },
+                "n_predict": {
+                    "type": "integer",
+                    "default": -1,
+                    "description": "Tokens to generate; -1 = until the context is full.",
+                },
+                "stop": { "type": "array", "items": { "type": "string" } },
+                "stream": { "type": "boolean", "default": false },
+                "cache_prompt": {
+                    "type": "boolean",
+                    "description": "Accepted for compatibility; the KV cache is \
+                        cleared per request.",
+                },
+                "temperature": { "type": "number", "default": 0.8 },
+                "top_k": { "type": "integer", "default": 40 },
+                "top_p": { "type": "number", "default": 0.95 },
+                "min_p": { "type": "number", "default": 0.05 },
+                "repeat_penalty": { "type": "number", "default": 1.1 },
+                "frequency_penalty": { "type": "number", "default": 0 },
+                "presence_penalty": { "type": "number", "default": 0 },
+                "repeat_last_n": { "type": "integer", "default": 64 },
+                "seed": { "type": "integer" },
+                "model": model,
+                "timeout": {
+                    "type": "integer",
+                    "minimum": 0,
+                    "description": "Generation time limit in seconds; can only lower the server's.",
+                },
+            },
+        }),
+    );
+    spec.component(
+        "InfillResult",
+        json!({
+            "type": "object",
+            "description": "The response, and the last event of a stream.",
+            "properties": {
+                "content": { "type": "string" },
+                "model": { "type": "string" },
+                "stop": { "const": true },
+                "stop_type": { "enum": ["eos", "limit", "word"] },
+                "stopping_word": { "type": "string" },
+                "tokens_predicted": { "type": "integer" },
+                "tokens_evaluated": { "type": "integer" },
+                "truncated": {
+                    "type": "boolean",
+                    "description": "Set when the time limit cut generation short.",
+                },
+            },
+            "required": [
+                "content", "model", "stop", "stop_type", "stopping_word",
+                "tokens_predicted", "tokens_evaluated", "truncated",
+            ],
+        }),
+    );
+    spec.component(
+        "InfillPiece",
+        json!({
+            "type": "object",
+            "properties": {
+                "content": { "type": "string" },
+                "stop": { "const": false },
+            },
+            "required": ["content", "stop"],
+        }),
+    );
+
+    let status_only = |description: &str| json!({ "description": description });
+    spec.operation(
+        "post",
+        "/tokenize",
+        json!({
+            "tags": ["Native"],
+            "summary": "Tokenize text",
+            "requestBody": json_body("TokenizeRequest"),
+            "responses": {
+                "200": json_response("Token ids", json!({
+                    "type": "object",
+                    "properties": {
+                        "tokens": { "type": "array", "items": { "type": "integer" } },
+                    },
+                    "required": ["tokens"],
+                })),
+                "400": status_only("Tokenization failed"),
+                "503": status_only("No model loaded"),
+            },
+        }),
+    );
+    spec.operation(
+        "post",
+        "/detokenize",
+        json!({
+            "tags": ["Native"],
+            "summary": "Turn token ids back into text",
+            "requestBody": json_body("DetokenizeRequest"),
+            "responses": {
+                "200": json_response("Text", json!({
+                    "type": "object",
+                    "properties": { "content": { "type": "string" } },
+                    "required": ["content"],
+                })),
+                "400": status_only("Invalid token ids"),
+                "503": status_only("No model loaded"),
+            },
+        }),
+    );
+    spec.operation(
+        "post",
+        "/infill",
+        json!({
+            "tags": ["Native"],
+            "summary": "Fill-in-the-middle completion for code editors",
+            "requestBody": json_body("InfillRequest"),
+            "responses": {
+                "200": json_or_sse_response("InfillResult", "InfillPiece"),
+                "400": text_response("The model has no FIM tokens, or the input is invalid"),
+                "500": text_response("Generation failed"),
+                "503": text_response("No model loaded"),
+                "504": text_response("The generation time limit ran out"),
+            },
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn documented(name: &str, value: &serde_json::Value) {
+        let spec = crate::routes::openapi::spec();
+        let schema = crate::routes::openapi::schema_ref(name);
+        crate::routes::openapi::validate(&spec, &schema, value).unwrap();
+    }
+
+    #[test]
+    fn infill_request_defaults() {
+        let body = serde_json::json!({
+            "input_prefix": "fn main() {",
+            "input_suffix": "}",
+            "input_extra": [{ "filename": "lib.rs", "text": "pub fn f() {}" }],
+            "top_k": 20,
+        });
+        documented("InfillRequest", &body);
+        let req: InfillRequest = serde_json::from_value(body).unwrap();
+        assert_eq!(req.n_predict, -1);
+        assert!(!req.stream);
+        assert!(req.cache_prompt);
+        assert_eq!(req.sampling.top_k, 20);
+        assert_eq!(
+            req.sampling.top_p,
+            llama_core::SamplingParams::default().top_p
+        );
+        assert_eq!(req.input_extra[0].filename, "lib.rs");
+    }
+
+    #[tokio::test]
+    async fn stream_ends_with_stop_result() {
+        let (tx, rx) = mpsc::channel(4);
+        tx.try_send(llama_core::GenerateEvent::Token("x".into()))
+            .unwrap();
+        tx.try_send(llama_core::GenerateEvent::Done {
+            finish_reason: llama_core::FinishReason::StopWord("\n".into()),
+            prompt_tokens: 7,
+            completion_tokens: 2,
+        })
+        .unwrap();
+        drop(tx);
+
+        let payloads: Vec<serde_json::Value> = completion_stream_payloads(rx, "m".into())
+            .map(|p| serde_json::from_str(&p).unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(payloads[0]["content"], "x");
+        assert_eq!(payloads[0]["stop"], false);
+        assert_eq!(payloads[1]["stop"], true);
+        assert_eq!(payloads[1]["stop_type"], "word");
+        assert_eq!(payloads[1]["stopping_word"], "\n");
+        assert_eq!(payloads[1]["tokens_evaluated"], 7);
+        documented("InfillPiece", &payloads[0]);
+        documented("InfillResult", &payloads[1]);
+    }
+}
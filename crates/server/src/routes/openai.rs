@@ -7,10 +7,11 @@
 //!   POST   /v1/embeddings
 
 use std::convert::Infallible;
+use std::time::Duration;
 
 use axum::{
     Json, Router,
-    extract::{Path, State},
+    extract::{Extension, FromRequest, Path, Request, State, rejection::JsonRejection},
     http::StatusCode,
     response::{
         IntoResponse, Response,
@@ -18,11 +19,25 @@ use axum::{
     },
     routing::{get, post},
 };
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use tokio::sync::mpsc;
-use tokio_stream::{StreamExt, wrappers::ReceiverStream};
-use tracing::error;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{error, info, warn};
 
+use crate::middleware::auth::ApiKeyId;
+use crate::routes::error::{ApiError, ErrorBody, ErrorDetail};
+use crate::routes::openapi::{
+    Spec, json_body, json_or_sse_response, json_response, param, schema_ref,
+};
+use crate::routes::validation::{self, ValidationError};
+use crate::services::inference::{
+    PoolError, PoolParams, generation_timeout, l2_normalize, pooled_blocking, read_embedding,
+    spawn_generation,
+};
+use crate::services::model_manager::{IdMatch, TokenizerInfo, WaitError};
+use crate::services::request_log::RequestMeta;
 use crate::state::AppState;
 
 pub fn router() -> Router<AppState> {
@@ -39,34 +54,57 @@ pub fn router() -> Router<AppState> {
 
 //  Error response (OpenAI format)
 
-#[derive(Serialize)]
-struct ErrorBody {
-    error: ErrorDetail,
-}
-
-#[derive(Serialize)]
-struct ErrorDetail {
-    message: String,
-    r#type: String,
-    param: Option<String>,
-    code: Option<String>,
+fn api_error(status: StatusCode, message: impl Into<String>, error_type: &'static str) -> Response {
+    ApiError::new(status, message, error_type).into_response()
 }
 
-fn api_error(status: StatusCode, message: impl Into<String>, error_type: &str) -> Response {
+/// 400 response for a request that failed validation.
+fn validation_error(err: ValidationError) -> Response {
     (
-        status,
+        StatusCode::BAD_REQUEST,
         Json(ErrorBody {
             error: ErrorDetail {
-                message: message.into(),
-                r#type: error_type.to_string(),
-                param: None,
-                code: None,
+                message: err.message,
+                r#type: "invalid_request_error".to_string(),
+                param: Some(err.param),
+                code: err.code.map(String::from),
             },
         }),
     )
         .into_response()
 }
 
+/// JSON body extractor that reports malformed input in the OpenAI error
+/// envelope instead of axum's plain-text rejection.
+struct OpenAiJson<T>(T);
+
+impl<S, T> FromRequest<S> for OpenAiJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(Self(value)),
+            Err(rejection) => {
+                let status = match rejection {
+                    JsonRejection::JsonDataError(_) | JsonRejection::JsonSyntaxError(_) => {
+                        StatusCode::BAD_REQUEST
+                    }
+                    ref other => other.status(),
+                };
+                Err(api_error(
+                    status,
+                    rejection.body_text(),
+                    "invalid_request_error",
+                ))
+            }
+        }
+    }
+}
+
 //  Shared types
 
 #[derive(Serialize)]
@@ -76,6 +114,111 @@ struct Usage {
     total_tokens: u32,
 }
 
+fn finish_reason_str(reason: &llama_core::FinishReason) -> &'static str {
+    match reason {
+        llama_core::FinishReason::Stop | llama_core::FinishReason::StopWord(_) => "stop",
+        // Reported like `max_tokens`, with `finish_details` saying why
+        llama_core::FinishReason::Length | llama_core::FinishReason::Timeout => "length",
+    }
+}
+
+/// Extension explaining a `length` finish that wasn't `max_tokens`.
+fn finish_details(reason: &llama_core::FinishReason) -> Option<&'static str> {
+    match reason {
+        llama_core::FinishReason::Timeout => Some("timeout"),
+        _ => None,
+    }
+}
+
+/// The `error` object for a failed generation, with its status's type
+/// and the code of the error behind it.
+fn generation_error_detail(err: &llama_core::GenerateError) -> ErrorDetail {
+    ApiError::from(err).detail()
+}
+
+//  Generation plumbing
+
+/// Final SSE payload of every stream.
+const STREAM_DONE: &str = "[DONE]";
+
+/// Payloads ending a stream after a mid-generation failure: the OpenAI
+/// error envelope followed by the `[DONE]` terminator.
+fn stream_error_payloads(err: &llama_core::GenerateError) -> Vec<String> {
+    let body = ErrorBody {
+        error: generation_error_detail(err),
+    };
+    vec![
+        serde_json::to_string(&body).unwrap_or_default(),
+        STREAM_DONE.to_string(),
+    ]
+}
+
+/// Everything a non-streaming handler needs from a finished generation.
+#[derive(Default)]
+struct Collected {
+    text: String,
+    finish_reason: Option<String>,
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    /// Number of text pieces received before generation ended.
+    pieces: usize,
+    error: Option<llama_core::GenerateError>,
+    /// Generation ran out of time.
+    timed_out: bool,
+}
+
+async fn collect_generation(mut rx: mpsc::Receiver<llama_core::GenerateEvent>) -> Collected {
+    let mut out = Collected::default();
+    while let Some(event) = rx.recv().await {
+        match event {
+            llama_core::GenerateEvent::Token(piece) => {
+                out.text.push_str(&piece);
+                out.pieces += 1;
+            }
+            llama_core::GenerateEvent::Done {
+                finish_reason,
+                prompt_tokens,
+                completion_tokens,
+            } => {
+                out.finish_reason = Some(finish_reason_str(&finish_reason).to_string());
+                out.timed_out = finish_reason == llama_core::FinishReason::Timeout;
+                out.prompt_tokens = prompt_tokens;
+                out.completion_tokens = completion_tokens;
+            }
+            llama_core::GenerateEvent::Error(e) => {
+                error!("Generation error: {e}");
+                out.error = Some(e);
+                break;
+            }
+        }
+    }
+    // The generation task died without saying why
+    if out.finish_reason.is_none() && out.error.is_none() {
+        out.error = Some(llama_core::GenerateError::other(
+            "Generation ended unexpectedly",
+        ));
+    }
+    out
+}
+
+/// An error response when generation failed before producing any text,
+/// with the status and code of what failed.  Partial results are
+/// returned normally with the error attached.  A generation that timed
+/// out is a 504 regardless.
+fn generation_failed(out: &Collected) -> Option<Response> {
+    if out.timed_out {
+        return Some(api_error(
+            StatusCode::GATEWAY_TIMEOUT,
+            "Generation timed out".to_string(),
+            "server_error",
+        ));
+    }
+    match &out.error {
+        Some(e) if out.pieces == 0 => Some(ApiError::from(e).into_response()),
+        _ => None,
+    }
+}
+
 //  /v1/models
 
 #[derive(Serialize)]
@@ -84,6 +227,58 @@ struct ModelObject {
     object: &'static str,
     created: i64,
     owned_by: &'static str,
+    /// Extension: set on alias entries to the model id they resolve to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alias_for: Option<String>,
+    /// Extension: user-assigned label of the (target) model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    display_name: Option<String>,
+    /// Extension: what the model can serve (`chat`, `embeddings`,
+    /// `vision-projector`); omitted on alias entries.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    capabilities: Vec<gguf_parser::Capability>,
+    /// Extension: badges such as `moe` or `reasoning`, guessed from the
+    /// metadata; omitted on alias entries.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    features: Vec<gguf_parser::ModelFeature>,
+    /// Extension: special tokens and template format (single-model
+    /// lookups only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tokenizer: Option<TokenizerInfo>,
+}
+
+impl ModelObject {
+    fn new(state: &AppState, id: String, alias_for: Option<String>) -> Self {
+        let display_name = state
+            .model_manager()
+            .display_name_of(alias_for.as_deref().unwrap_or(&id));
+        Self {
+            id,
+            object: "model",
+            created: 0,
+            owned_by: "local",
+            alias_for,
+            display_name,
+            capabilities: Vec::new(),
+            features: Vec::new(),
+            tokenizer: None,
+        }
+    }
+
+    fn with_capabilities(mut self, capabilities: &[gguf_parser::Capability]) -> Self {
+        self.capabilities = capabilities.to_vec();
+        self
+    }
+
+    fn with_features(mut self, features: &[gguf_parser::ModelFeature]) -> Self {
+        self.features = features.to_vec();
+        self
+    }
+
+    fn with_tokenizer(mut self, state: &AppState, path: &std::path::Path) -> Self {
+        self.tokenizer = state.model_manager().tokenizer_info(&self.id, path);
+        self
+    }
 }
 
 #[derive(Serialize)]
@@ -95,30 +290,49 @@ struct ModelsListResponse {
 /// GET /v1/models — List available models.
 async fn list_models(State(state): State<AppState>) -> Json<ModelsListResponse> {
     let mut data = Vec::new();
+    let available = state.model_manager().scan_available();
+    let features_of = |id: &str| {
+        available
+            .iter()
+            .find(|m| m.id.eq_ignore_ascii_case(id))
+            .map(|m| m.features.clone())
+            .unwrap_or_default()
+    };
 
     // Include all loaded models
     let loaded_ids = state.model_manager().loaded_model_ids();
     for id in &loaded_ids {
-        data.push(ModelObject {
-            id: id.clone(),
-            object: "model",
-            created: 0,
-            owned_by: "local",
-        });
+        let capabilities = state
+            .model_manager()
+            .get_loaded(id)
+            .map(|l| l.capabilities.clone())
+            .unwrap_or_default();
+        data.push(
+            ModelObject::new(&state, id.clone(), None)
+                .with_capabilities(&capabilities)
+                .with_features(&features_of(id)),
+        );
     }
 
-    // Also include scanned but not loaded models
-    let available = state.model_manager().scan_available();
-    for m in available {
+    // Also include scanned but not loaded models; LoRA adapters can't
+    // serve requests and are listed under /api/adapters
+    for m in &available {
+        if m.kind == gguf_parser::ModelKind::LoraAdapter {
+            continue;
+        }
         if loaded_ids.iter().any(|lid| lid.eq_ignore_ascii_case(&m.id)) {
             continue; // already listed
         }
-        data.push(ModelObject {
-            id: m.id,
-            object: "model",
-            created: 0,
-            owned_by: "local",
-        });
+        data.push(
+            ModelObject::new(&state, m.id.clone(), None)
+                .with_capabilities(&m.capabilities)
+                .with_features(&m.features),
+        );
+    }
+
+    // Aliases are listed as extra model objects pointing at their target
+    for (alias, id) in state.model_manager().aliases() {
+        data.push(ModelObject::new(&state, alias, Some(id)));
     }
 
     Json(ModelsListResponse {
@@ -129,14 +343,17 @@ async fn list_models(State(state): State<AppState>) -> Json<ModelsListResponse>
 
 /// GET /v1/models/{model} — Retrieve a single model.
 async fn retrieve_model(State(state): State<AppState>, Path(model_id): Path<String>) -> Response {
+    if let Some(target) = state.model_manager().alias_target(&model_id) {
+        return Json(ModelObject::new(&state, model_id, Some(target))).into_response();
+    }
+
     // Check loaded models
     if let Some(loaded) = state.model_manager().get_loaded(&model_id) {
-        return Json(ModelObject {
-            id: loaded.id.clone(),
-            object: "model",
-            created: 0,
-            owned_by: "local",
-        })
+        return Json(
+            ModelObject::new(&state, loaded.id.clone(), None)
+                .with_capabilities(&loaded.capabilities)
+                .with_tokenizer(&state, &loaded.path),
+        )
         .into_response();
     }
 
@@ -146,12 +363,14 @@ async fn retrieve_model(State(state): State<AppState>, Path(model_id): Path<Stri
         .into_iter()
         .find(|m| m.id.eq_ignore_ascii_case(&model_id))
     {
-        return Json(ModelObject {
-            id: m.id,
-            object: "model",
-            created: 0,
-            owned_by: "local",
-        })
+        let capabilities = m.capabilities.clone();
+        let features = m.features.clone();
+        return Json(
+            ModelObject::new(&state, m.id, None)
+                .with_capabilities(&capabilities)
+                .with_features(&features)
+                .with_tokenizer(&state, &m.path),
+        )
         .into_response();
     }
 
@@ -193,7 +412,7 @@ async fn delete_model(State(state): State<AppState>, Path(model_id): Path<String
 
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
-struct ChatCompletionRequest {
+pub(crate) struct ChatCompletionRequest {
     #[serde(default)]
     model: Option<String>,
     messages: Vec<ChatMessageReq>,
@@ -225,6 +444,16 @@ struct ChatCompletionRequest {
     user: Option<String>,
     #[serde(default)]
     response_format: Option<ResponseFormat>,
+    /// Extension: how long to keep the model loaded after this request.
+    #[serde(default)]
+    keep_alive: Option<crate::services::model_manager::KeepAlive>,
+    /// Extension: generation time limit in seconds; can only lower the
+    /// server's `generation_timeout_secs`.
+    #[serde(default)]
+    timeout: Option<u64>,
+    /// Extension: stream control tokens such as `<|im_end|>` as text.
+    #[serde(default)]
+    return_special: bool,
 }
 
 /// OpenAI `stop` can be a string or an array of strings.
@@ -308,6 +537,9 @@ struct ChatCompletionResponse {
     choices: Vec<ChatChoice>,
     usage: Usage,
     system_fingerprint: Option<String>,
+    /// Set when generation failed after producing partial output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorDetail>,
 }
 
 #[derive(Serialize)]
@@ -346,6 +578,9 @@ struct ChatChunkChoice {
     finish_reason: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     logprobs: Option<serde_json::Value>,
+    /// Extension: `"timeout"` when the time limit ended generation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finish_details: Option<&'static str>,
 }
 
 #[derive(Serialize)]
@@ -357,73 +592,256 @@ struct ChatDelta {
 }
 
 /// Resolve the model for a request: try by name, fall back to any loaded.
-#[allow(clippy::result_large_err)]
-fn resolve_model(
+///
+/// Names match case-insensitively, and a unique prefix or substring of a
+/// model id is accepted; ambiguous names are rejected with the candidates.
+/// With `auto_load_models` enabled, a named model that is not loaded but
+/// was found by scanning is loaded on demand.
+pub(crate) async fn resolve_model(
     state: &AppState,
     model_name: Option<&str>,
 ) -> Result<std::sync::Arc<crate::services::model_manager::LoadedModel>, Response> {
     let mm = state.model_manager();
-    match mm.resolve(model_name) {
-        Some(loaded) => {
-            mm.touch(&loaded.id);
+    let Some(name) = model_name else {
+        return match mm.get_any_loaded() {
+            Some(loaded) => {
+                mm.touch(&loaded.id);
+                Ok(loaded)
+            }
+            None => Err(not_loaded("No model loaded".to_string())),
+        };
+    };
+
+    let auto_load = state.config().auto_load_models;
+    let matched = if auto_load {
+        mm.match_model(name)
+    } else {
+        mm.match_loaded(name)
+    };
+    let id = match matched {
+        IdMatch::Unique(id) => id,
+        IdMatch::Ambiguous(candidates) => {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "The model '{}' is ambiguous; candidates: {}",
+                    name,
+                    candidates.join(", ")
+                ),
+                "invalid_request_error",
+            ));
+        }
+        IdMatch::NotFound if auto_load => {
+            return Err(api_error(
+                StatusCode::NOT_FOUND,
+                format!("The model '{}' does not exist", name),
+                "invalid_request_error",
+            ));
+        }
+        IdMatch::NotFound => {
+            return Err(not_loaded(format!("Model '{}' is not loaded", name)));
+        }
+    };
+
+    if let Some(loaded) = mm.get_loaded(&id) {
+        mm.touch(&loaded.id);
+        return Ok(loaded);
+    }
+
+    // Another request (or the dashboard) is loading it: wait for that
+    if mm.is_loading(&id) {
+        let timeout = Duration::from_secs(state.config().load_wait_timeout_secs);
+        return match mm.wait_ready(&id, timeout).await {
+            Ok(Some(loaded)) => {
+                mm.touch(&loaded.id);
+                Ok(loaded)
+            }
+            Ok(None) | Err(WaitError::NotLoading(_)) => {
+                Err(not_loaded(format!("Model '{}' is not loaded", name)))
+            }
+            Err(e @ WaitError::Timeout(_)) => Err(api_error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                e.to_string(),
+                "server_error",
+            )),
+            Err(WaitError::Failed(e)) => Err(ApiError::new(
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to load model '{}': {e}", name),
+                "server_error",
+            )
+            .with_code("invalid_model")
+            .into_response()),
+        };
+    }
+
+    if !auto_load {
+        return Err(not_loaded(format!("Model '{}' is not loaded", name)));
+    }
+
+    if let Some(reason) = mm.drain_reason() {
+        return Err(ApiError::from(llama_core::LlamaError::LoadingPaused(reason)).into_response());
+    }
+
+    // Loading is blocking; concurrent requests queue on the manager's
+    // load lock and share the first load's result.
+    let mm = mm.clone();
+    let result = tokio::task::spawn_blocking(move || mm.ensure_loaded(Some(&id))).await;
+
+    match result {
+        Ok(Ok((loaded, loaded_now))) => {
+            if loaded_now {
+                info!(id = %loaded.id, "Model auto-loaded for request");
+                state.broadcast_event("model.loaded", serde_json::json!({ "id": loaded.id }));
+            }
             Ok(loaded)
         }
-        None => Err(api_error(
-            StatusCode::SERVICE_UNAVAILABLE,
-            model_name
-                .map(|n| format!("Model '{}' is not loaded", n))
-                .unwrap_or_else(|| "No model loaded".to_string()),
+        Ok(Err(e)) => {
+            state.broadcast_event(
+                "model.load_failed",
+                serde_json::json!({ "id": name, "error": e.to_string() }),
+            );
+            Err(
+                ApiError::llama(&e, format!("Failed to load model '{}': {e}", name))
+                    .into_response(),
+            )
+        }
+        Err(e) => Err(api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            e.to_string(),
             "server_error",
         )),
     }
 }
 
+/// 503 `model_not_loaded`.
+fn not_loaded(message: String) -> Response {
+    ApiError::llama(&llama_core::LlamaError::ModelNotLoaded, message).into_response()
+}
+
+/// 400 `model_capability_mismatch` unless `loaded` can serve `capability`,
+/// e.g. a chat request sent to an embedding-only model.
+pub(crate) fn require_capability(
+    loaded: &crate::services::model_manager::LoadedModel,
+    capability: gguf_parser::Capability,
+) -> Result<(), ApiError> {
+    if loaded.supports(capability) {
+        return Ok(());
+    }
+    let supported = loaded
+        .capabilities
+        .iter()
+        .map(|c| c.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Err(ApiError::new(
+        StatusCode::BAD_REQUEST,
+        format!(
+            "The model '{}' only supports {supported}, not {capability}",
+            loaded.id
+        ),
+        "invalid_request_error",
+    )
+    .with_code("model_capability_mismatch"))
+}
+
+/// Render a conversation with the model's chat template, falling back to
+/// plain `role: content` lines for models without a usable template.
+pub(crate) fn render_chat_prompt(
+    model: &llama_core::LlamaModel,
+    messages: &[llama_core::ChatMessage],
+) -> String {
+    llama_core::apply_model_template(model, messages, true).unwrap_or_else(|| {
+        messages
+            .iter()
+            .map(|m| format!("{}: {}", m.role, m.content))
+            .collect::<Vec<_>>()
+            .join("\n")
+            + "\nassistant:"
+    })
+}
+
 /// POST /v1/chat/completions — Chat completion (stream + non-stream).
 async fn chat_completions(
     State(state): State<AppState>,
-    Json(req): Json<ChatCompletionRequest>,
+    api_key: Option<Extension<ApiKeyId>>,
+    OpenAiJson(req): OpenAiJson<ChatCompletionRequest>,
 ) -> Response {
     let stream = req.stream.unwrap_or(false);
-
-    let loaded = match resolve_model(&state, req.model.as_deref()) {
-        Ok(l) => l,
+    let api_key_id = api_key.map(|Extension(ApiKeyId(id))| id);
+    let chat = match prepare_chat(&state, req, "/v1/chat/completions", api_key_id).await {
+        Ok(chat) => chat,
         Err(e) => return e,
     };
 
-    let model_id = loaded.id.clone();
-    let model = loaded.model.clone();
+    let model_id = chat.loaded.id.clone();
+    let request_id = format!("chatcmpl-{}", uuid::Uuid::new_v4());
+    tracing::Span::current().record("response_id", request_id.as_str());
+    let created = chrono::Utc::now().timestamp();
+    let fingerprint = format!("fp_{}", &model_id[..model_id.len().min(8)]);
 
-    // Build chat messages
-    let messages: Vec<llama_core::ChatMessage> = req
-        .messages
-        .iter()
-        .map(|m| llama_core::ChatMessage {
-            role: m.role.clone(),
-            content: m.content.as_ref().map(|c| c.as_text()).unwrap_or_default(),
-        })
-        .collect();
+    let rx = spawn_generation(&state, chat.loaded, chat.request, chat.meta);
+    if stream {
+        chat_stream(rx, request_id, created, model_id, fingerprint).into_response()
+    } else {
+        chat_non_stream(rx, request_id, created, model_id, fingerprint)
+            .await
+            .into_response()
+    }
+}
 
-    let template = model.chat_template();
-    let prompt =
-        llama_core::apply_template(template.as_deref(), &messages, true).unwrap_or_else(|| {
-            messages
-                .iter()
-                .map(|m| format!("{}: {}", m.role, m.content))
-                .collect::<Vec<_>>()
-                .join("\n")
-                + "\nassistant:"
-        });
+/// A validated chat request, ready for [`spawn_generation`].
+pub(crate) struct ChatGeneration {
+    pub loaded: std::sync::Arc<crate::services::model_manager::LoadedModel>,
+    pub request: llama_core::GenerateRequest,
+    pub meta: RequestMeta,
+}
 
-    let tokens = match llama_core::tokenize(model.vocab(), &prompt, true, true) {
-        Ok(t) => t,
-        Err(e) => {
-            return api_error(
-                StatusCode::BAD_REQUEST,
-                format!("Tokenization failed: {e}"),
-                "invalid_request_error",
-            );
-        }
-    };
+/// Validate `req`, resolve (and if need be load) its model and render
+/// the prompt.  Failures come back as OpenAI error responses.
+pub(crate) async fn prepare_chat(
+    state: &AppState,
+    req: ChatCompletionRequest,
+    endpoint: &'static str,
+    api_key_id: Option<String>,
+) -> Result<ChatGeneration, Response> {
+    validation::validate_generation_params(&validation::GenerationParams {
+        temperature: req.temperature,
+        top_p: req.top_p,
+        presence_penalty: req.presence_penalty,
+        frequency_penalty: req.frequency_penalty,
+        n: req.n,
+        max_tokens: req.max_tokens,
+        max_completion_tokens: req.max_completion_tokens,
+    })
+    .map_err(validation_error)?;
+
+    // Validate roles / content and map them for the chat template
+    let messages = validation::validate_messages(
+        req.messages
+            .iter()
+            .map(|m| validation::RawMessage {
+                role: &m.role,
+                content: m.content.as_ref().map(|c| c.as_text()),
+                has_tool_calls: m.tool_calls.is_some(),
+            })
+            .collect(),
+    )
+    .map_err(validation_error)?;
+
+    let loaded = resolve_model(state, req.model.as_deref()).await?;
+    require_capability(&loaded, gguf_parser::Capability::Chat)
+        .map_err(IntoResponse::into_response)?;
+    state
+        .model_manager()
+        .set_keep_alive(&loaded.id, req.keep_alive);
+
+    let prompt = render_chat_prompt(&loaded.model, &messages);
+
+    let tokens = loaded
+        .model
+        .vocab()
+        .tokenize_prompt(&prompt)
+        .map_err(|e| ApiError::from(e).into_response())?;
 
     let max_tokens = req.max_completion_tokens.or(req.max_tokens).unwrap_or(2048);
 
@@ -436,49 +854,70 @@ async fn chat_completions(
         ..Default::default()
     };
 
-    let gen_req = llama_core::GenerateRequest {
+    let request = llama_core::GenerateRequest {
         tokens,
         max_tokens,
         stop_words: req.stop.map(|s| s.into_vec()).unwrap_or_default(),
         sampling_params: sampling,
+        return_special: req.return_special,
+        ignore_eos: false,
     };
 
-    let request_id = format!("chatcmpl-{}", uuid::Uuid::new_v4());
-    let created = chrono::Utc::now().timestamp();
-    let fingerprint = format!("fp_{}", &model_id[..model_id.len().min(8)]);
-
-    if stream {
-        chat_stream(loaded, gen_req, request_id, created, model_id, fingerprint).into_response()
-    } else {
-        chat_non_stream(loaded, gen_req, request_id, created, model_id, fingerprint)
-            .await
-            .into_response()
-    }
+    let meta = RequestMeta {
+        endpoint,
+        user: req.user,
+        prompt: state.request_log().logs_prompts().then_some(prompt),
+        api_key_id,
+        timeout: generation_timeout(state.config().generation_timeout_secs, req.timeout),
+        cancel: None,
+    };
+    Ok(ChatGeneration {
+        loaded,
+        request,
+        meta,
+    })
 }
 
 fn chat_stream(
-    loaded: std::sync::Arc<crate::services::model_manager::LoadedModel>,
-    gen_req: llama_core::GenerateRequest,
+    rx: mpsc::Receiver<llama_core::GenerateEvent>,
     request_id: String,
     created: i64,
     model_id: String,
     fingerprint: String,
 ) -> Sse<impl tokio_stream::Stream<Item = Result<Event, Infallible>>> {
-    let (tx, rx) = mpsc::channel(64);
+    let stream = chat_stream_payloads(rx, request_id, created, model_id, fingerprint)
+        .map(|data| Ok(Event::default().data(data)));
 
-    tokio::task::spawn_blocking(move || {
-        let mut ctx = loaded.context.lock().unwrap();
-        ctx.kv_cache_clear();
-        llama_core::generate::generate_blocking(&mut ctx, &gen_req, tx);
-    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
 
-    let rid = request_id.clone();
-    let mid = model_id.clone();
-    let fp = fingerprint.clone();
+/// Map generation events onto the SSE `data:` payloads of a chat stream.
+fn chat_stream_payloads(
+    rx: mpsc::Receiver<llama_core::GenerateEvent>,
+    request_id: String,
+    created: i64,
+    model_id: String,
+    fingerprint: String,
+) -> impl tokio_stream::Stream<Item = String> {
     let mut sent_role = false;
 
-    let stream = ReceiverStream::new(rx).map(move |event| {
-        let chunk = match event {
+    ReceiverStream::new(rx).flat_map(move |event| {
+        let chunk = |delta: ChatDelta, finish_reason: Option<String>| ChatCompletionChunk {
+            id: request_id.clone(),
+            object: "chat.completion.chunk",
+            created,
+            model: model_id.clone(),
+            choices: vec![ChatChunkChoice {
+                index: 0,
+                delta,
+                finish_reason,
+                logprobs: None,
+                finish_details: None,
+            }],
+            system_fingerprint: Some(fingerprint.clone()),
+        };
+
+        let payloads = match event {
             llama_core::GenerateEvent::Token(piece) => {
                 let role = if !sent_role {
                     sent_role = true;
@@ -486,106 +925,58 @@ fn chat_stream(
                 } else {
                     None
                 };
-                ChatCompletionChunk {
-                    id: rid.clone(),
-                    object: "chat.completion.chunk",
-                    created,
-                    model: mid.clone(),
-                    choices: vec![ChatChunkChoice {
-                        index: 0,
-                        delta: ChatDelta {
-                            role,
-                            content: Some(piece),
-                        },
-                        finish_reason: None,
-                        logprobs: None,
-                    }],
-                    system_fingerprint: Some(fp.clone()),
-                }
+                let c = chunk(
+                    ChatDelta {
+                        role,
+                        content: Some(piece),
+                    },
+                    None,
+                );
+                vec![serde_json::to_string(&c).unwrap_or_default()]
             }
             llama_core::GenerateEvent::Done { finish_reason, .. } => {
-                let reason = match finish_reason {
-                    llama_core::FinishReason::Stop => "stop",
-                    llama_core::FinishReason::Length => "length",
-                    llama_core::FinishReason::StopWord(_) => "stop",
-                };
-                ChatCompletionChunk {
-                    id: rid.clone(),
-                    object: "chat.completion.chunk",
-                    created,
-                    model: mid.clone(),
-                    choices: vec![ChatChunkChoice {
-                        index: 0,
-                        delta: ChatDelta {
-                            role: None,
-                            content: None,
-                        },
-                        finish_reason: Some(reason.to_string()),
-                        logprobs: None,
-                    }],
-                    system_fingerprint: Some(fp.clone()),
-                }
+                let mut c = chunk(
+                    ChatDelta {
+                        role: None,
+                        content: None,
+                    },
+                    Some(finish_reason_str(&finish_reason).to_string()),
+                );
+                c.choices[0].finish_details = finish_details(&finish_reason);
+                vec![
+                    serde_json::to_string(&c).unwrap_or_default(),
+                    STREAM_DONE.to_string(),
+                ]
             }
             llama_core::GenerateEvent::Error(e) => {
                 error!("Generation error: {e}");
-                ChatCompletionChunk {
-                    id: rid.clone(),
-                    object: "chat.completion.chunk",
-                    created,
-                    model: mid.clone(),
-                    choices: vec![],
-                    system_fingerprint: Some(fp.clone()),
-                }
+                stream_error_payloads(&e)
             }
         };
-        Ok(Event::default().data(serde_json::to_string(&chunk).unwrap_or_default()))
-    });
-
-    Sse::new(stream).keep_alive(KeepAlive::default())
+        futures_util::stream::iter(payloads)
+    })
 }
 
 async fn chat_non_stream(
-    loaded: std::sync::Arc<crate::services::model_manager::LoadedModel>,
-    gen_req: llama_core::GenerateRequest,
+    rx: mpsc::Receiver<llama_core::GenerateEvent>,
     request_id: String,
     created: i64,
     model_id: String,
     fingerprint: String,
-) -> Json<ChatCompletionResponse> {
-    let (tx, mut rx) = mpsc::channel(64);
-
-    tokio::task::spawn_blocking(move || {
-        let mut ctx = loaded.context.lock().unwrap();
-        ctx.kv_cache_clear();
-        llama_core::generate::generate_blocking(&mut ctx, &gen_req, tx);
-    });
-
-    let mut content = String::new();
-    let mut finish_reason = None;
-    let mut prompt_tokens = 0u32;
-    let mut completion_tokens = 0u32;
+) -> Response {
+    let out = collect_generation(rx).await;
+    chat_completion_response(out, request_id, created, model_id, fingerprint)
+}
 
-    while let Some(event) = rx.recv().await {
-        match event {
-            llama_core::GenerateEvent::Token(piece) => content.push_str(&piece),
-            llama_core::GenerateEvent::Done {
-                finish_reason: fr,
-                prompt_tokens: pt,
-                completion_tokens: ct,
-            } => {
-                finish_reason = Some(match fr {
-                    llama_core::FinishReason::Stop => "stop".to_string(),
-                    llama_core::FinishReason::Length => "length".to_string(),
-                    llama_core::FinishReason::StopWord(_) => "stop".to_string(),
-                });
-                prompt_tokens = pt;
-                completion_tokens = ct;
-            }
-            llama_core::GenerateEvent::Error(e) => {
-                error!("Generation error: {e}");
-                break;
-            }
-        }
+fn chat_completion_response(
+    out: Collected,
+    request_id: String,
+    created: i64,
+    model_id: String,
+    fingerprint: String,
+) -> Response {
+    if let Some(resp) = generation_failed(&out) {
+        return resp;
     }
 
     Json(ChatCompletionResponse {
@@ -597,19 +988,21 @@ async fn chat_non_stream(
             index: 0,
             message: ChatMessageResp {
                 role: "assistant",
-                content: Some(content),
+                content: Some(out.text),
                 tool_calls: None,
             },
-            finish_reason,
+            finish_reason: out.finish_reason,
             logprobs: None,
         }],
         usage: Usage {
-            prompt_tokens,
-            completion_tokens,
-            total_tokens: prompt_tokens + completion_tokens,
+            prompt_tokens: out.prompt_tokens,
+            completion_tokens: out.completion_tokens,
+            total_tokens: out.prompt_tokens + out.completion_tokens,
         },
         system_fingerprint: Some(fingerprint),
+        error: out.error.as_ref().map(generation_error_detail),
     })
+    .into_response()
 }
 
 //  /v1/completions (legacy text completions)
@@ -648,6 +1041,16 @@ struct CompletionRequest {
     user: Option<String>,
     #[serde(default)]
     best_of: Option<u32>,
+    /// Extension: how long to keep the model loaded after this request.
+    #[serde(default)]
+    keep_alive: Option<crate::services::model_manager::KeepAlive>,
+    /// Extension: generation time limit in seconds; can only lower the
+    /// server's `generation_timeout_secs`.
+    #[serde(default)]
+    timeout: Option<u64>,
+    /// Extension: stream control tokens such as `<|im_end|>` as text.
+    #[serde(default)]
+    return_special: bool,
 }
 
 /// OpenAI `prompt` can be a string, array of strings, or token array.
@@ -685,6 +1088,9 @@ struct CompletionResponse {
     choices: Vec<CompletionChoice>,
     usage: Usage,
     system_fingerprint: Option<String>,
+    /// Set when generation failed after producing partial output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorDetail>,
 }
 
 #[derive(Serialize)]
@@ -713,20 +1119,42 @@ struct CompletionChunkChoice {
     text: String,
     finish_reason: Option<String>,
     logprobs: Option<serde_json::Value>,
+    /// Extension: `"timeout"` when the time limit ended generation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finish_details: Option<&'static str>,
 }
 
 /// POST /v1/completions — Text completion (legacy).
 async fn completions(
     State(state): State<AppState>,
-    Json(req): Json<CompletionRequest>,
+    api_key: Option<Extension<ApiKeyId>>,
+    OpenAiJson(req): OpenAiJson<CompletionRequest>,
 ) -> Response {
     let stream = req.stream.unwrap_or(false);
     let echo = req.echo.unwrap_or(false);
 
-    let loaded = match resolve_model(&state, req.model.as_deref()) {
+    if let Err(e) = validation::validate_generation_params(&validation::GenerationParams {
+        temperature: req.temperature,
+        top_p: req.top_p,
+        presence_penalty: req.presence_penalty,
+        frequency_penalty: req.frequency_penalty,
+        n: req.n,
+        max_tokens: req.max_tokens,
+        max_completion_tokens: None,
+    }) {
+        return validation_error(e);
+    }
+
+    let loaded = match resolve_model(&state, req.model.as_deref()).await {
         Ok(l) => l,
         Err(e) => return e,
     };
+    if let Err(e) = require_capability(&loaded, gguf_parser::Capability::Chat) {
+        return e.into_response();
+    }
+    state
+        .model_manager()
+        .set_keep_alive(&loaded.id, req.keep_alive);
 
     let model_id = loaded.id.clone();
     let model = loaded.model.clone();
@@ -736,15 +1164,9 @@ async fn completions(
         tok.to_vec()
     } else {
         let prompt_text = req.prompt.as_text();
-        match llama_core::tokenize(model.vocab(), &prompt_text, true, true) {
+        match model.vocab().tokenize(&prompt_text, true, true) {
             Ok(t) => t,
-            Err(e) => {
-                return api_error(
-                    StatusCode::BAD_REQUEST,
-                    format!("Tokenization failed: {e}"),
-                    "invalid_request_error",
-                );
-            }
+            Err(e) => return ApiError::from(e).into_response(),
         }
     };
 
@@ -768,164 +1190,117 @@ async fn completions(
         max_tokens: req.max_tokens.unwrap_or(16),
         stop_words: req.stop.map(|s| s.into_vec()).unwrap_or_default(),
         sampling_params: sampling,
+        return_special: req.return_special,
+        ignore_eos: false,
     };
 
     let request_id = format!("cmpl-{}", uuid::Uuid::new_v4());
+    tracing::Span::current().record("response_id", request_id.as_str());
     let created = chrono::Utc::now().timestamp();
     let fingerprint = format!("fp_{}", &model_id[..model_id.len().min(8)]);
 
+    let meta = RequestMeta {
+        endpoint: "/v1/completions",
+        user: req.user,
+        prompt: state
+            .request_log()
+            .logs_prompts()
+            .then(|| req.prompt.as_text()),
+        api_key_id: api_key.map(|Extension(ApiKeyId(id))| id),
+        timeout: generation_timeout(state.config().generation_timeout_secs, req.timeout),
+        cancel: None,
+    };
+    let rx = spawn_generation(&state, loaded, gen_req, meta);
     if stream {
-        completion_stream(
-            loaded,
-            gen_req,
-            request_id,
-            created,
-            model_id,
-            fingerprint,
-            prompt_text,
-        )
-        .into_response()
+        completion_stream(rx, request_id, created, model_id, fingerprint, prompt_text)
+            .into_response()
     } else {
-        completion_non_stream(
-            loaded,
-            gen_req,
-            request_id,
-            created,
-            model_id,
-            fingerprint,
-            prompt_text,
-        )
-        .await
-        .into_response()
+        completion_non_stream(rx, request_id, created, model_id, fingerprint, prompt_text)
+            .await
+            .into_response()
     }
 }
 
 fn completion_stream(
-    loaded: std::sync::Arc<crate::services::model_manager::LoadedModel>,
-    gen_req: llama_core::GenerateRequest,
+    rx: mpsc::Receiver<llama_core::GenerateEvent>,
     request_id: String,
     created: i64,
     model_id: String,
     fingerprint: String,
     echo_prefix: String,
 ) -> Sse<impl tokio_stream::Stream<Item = Result<Event, Infallible>>> {
-    let (tx, rx) = mpsc::channel(64);
+    let stream =
+        completion_stream_payloads(rx, request_id, created, model_id, fingerprint, echo_prefix)
+            .map(|data| Ok(Event::default().data(data)));
 
-    tokio::task::spawn_blocking(move || {
-        let mut ctx = loaded.context.lock().unwrap();
-        ctx.kv_cache_clear();
-        llama_core::generate::generate_blocking(&mut ctx, &gen_req, tx);
-    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
 
-    let rid = request_id.clone();
-    let mid = model_id.clone();
-    let fp = fingerprint.clone();
-    let mut sent_echo = echo_prefix.is_empty();
-
-    let stream = ReceiverStream::new(rx).map(move |event| {
-        // If echo, first emit the prompt as a chunk
-        let prefix_text = if !sent_echo {
-            sent_echo = true;
-            echo_prefix.clone()
-        } else {
-            String::new()
+/// Map generation events onto the SSE `data:` payloads of a text
+/// completion stream.  With `echo`, the prompt is prepended to the first
+/// emitted text.
+fn completion_stream_payloads(
+    rx: mpsc::Receiver<llama_core::GenerateEvent>,
+    request_id: String,
+    created: i64,
+    model_id: String,
+    fingerprint: String,
+    echo_prefix: String,
+) -> impl tokio_stream::Stream<Item = String> {
+    let mut echo_prefix = Some(echo_prefix).filter(|p| !p.is_empty());
+
+    ReceiverStream::new(rx).flat_map(move |event| {
+        let chunk = |text: String, finish_reason: Option<String>| CompletionChunk {
+            id: request_id.clone(),
+            object: "text_completion",
+            created,
+            model: model_id.clone(),
+            choices: vec![CompletionChunkChoice {
+                index: 0,
+                text,
+                finish_reason,
+                logprobs: None,
+                finish_details: None,
+            }],
+            system_fingerprint: Some(fingerprint.clone()),
         };
 
-        let chunk = match event {
-            llama_core::GenerateEvent::Token(piece) => CompletionChunk {
-                id: rid.clone(),
-                object: "text_completion",
-                created,
-                model: mid.clone(),
-                choices: vec![CompletionChunkChoice {
-                    index: 0,
-                    text: format!("{}{}", prefix_text, piece),
-                    finish_reason: None,
-                    logprobs: None,
-                }],
-                system_fingerprint: Some(fp.clone()),
-            },
+        let payloads = match event {
+            llama_core::GenerateEvent::Token(piece) => {
+                let text = echo_prefix.take().unwrap_or_default() + &piece;
+                vec![serde_json::to_string(&chunk(text, None)).unwrap_or_default()]
+            }
             llama_core::GenerateEvent::Done { finish_reason, .. } => {
-                let reason = match finish_reason {
-                    llama_core::FinishReason::Stop => "stop",
-                    llama_core::FinishReason::Length => "length",
-                    llama_core::FinishReason::StopWord(_) => "stop",
-                };
-                CompletionChunk {
-                    id: rid.clone(),
-                    object: "text_completion",
-                    created,
-                    model: mid.clone(),
-                    choices: vec![CompletionChunkChoice {
-                        index: 0,
-                        text: String::new(),
-                        finish_reason: Some(reason.to_string()),
-                        logprobs: None,
-                    }],
-                    system_fingerprint: Some(fp.clone()),
-                }
+                let mut c = chunk(
+                    echo_prefix.take().unwrap_or_default(),
+                    Some(finish_reason_str(&finish_reason).to_string()),
+                );
+                c.choices[0].finish_details = finish_details(&finish_reason);
+                vec![
+                    serde_json::to_string(&c).unwrap_or_default(),
+                    STREAM_DONE.to_string(),
+                ]
             }
             llama_core::GenerateEvent::Error(e) => {
                 error!("Generation error: {e}");
-                CompletionChunk {
-                    id: rid.clone(),
-                    object: "text_completion",
-                    created,
-                    model: mid.clone(),
-                    choices: vec![],
-                    system_fingerprint: Some(fp.clone()),
-                }
+                stream_error_payloads(&e)
             }
         };
-        Ok(Event::default().data(serde_json::to_string(&chunk).unwrap_or_default()))
-    });
-
-    Sse::new(stream).keep_alive(KeepAlive::default())
+        futures_util::stream::iter(payloads)
+    })
 }
 
 async fn completion_non_stream(
-    loaded: std::sync::Arc<crate::services::model_manager::LoadedModel>,
-    gen_req: llama_core::GenerateRequest,
+    rx: mpsc::Receiver<llama_core::GenerateEvent>,
     request_id: String,
     created: i64,
     model_id: String,
     fingerprint: String,
     echo_prefix: String,
-) -> Json<CompletionResponse> {
-    let (tx, mut rx) = mpsc::channel(64);
-
-    tokio::task::spawn_blocking(move || {
-        let mut ctx = loaded.context.lock().unwrap();
-        ctx.kv_cache_clear();
-        llama_core::generate::generate_blocking(&mut ctx, &gen_req, tx);
-    });
-
-    let mut content = echo_prefix;
-    let mut finish_reason = None;
-    let mut prompt_tokens = 0u32;
-    let mut completion_tokens = 0u32;
-
-    while let Some(event) = rx.recv().await {
-        match event {
-            llama_core::GenerateEvent::Token(piece) => content.push_str(&piece),
-            llama_core::GenerateEvent::Done {
-                finish_reason: fr,
-                prompt_tokens: pt,
-                completion_tokens: ct,
-            } => {
-                finish_reason = Some(match fr {
-                    llama_core::FinishReason::Stop => "stop".to_string(),
-                    llama_core::FinishReason::Length => "length".to_string(),
-                    llama_core::FinishReason::StopWord(_) => "stop".to_string(),
-                });
-                prompt_tokens = pt;
-                completion_tokens = ct;
-            }
-            llama_core::GenerateEvent::Error(e) => {
-                error!("Generation error: {e}");
-                break;
-            }
-        }
+) -> Response {
+    let out = collect_generation(rx).await;
+    if let Some(resp) = generation_failed(&out) {
+        return resp;
     }
 
     Json(CompletionResponse {
@@ -935,17 +1310,19 @@ async fn completion_non_stream(
         model: model_id,
         choices: vec![CompletionChoice {
             index: 0,
-            text: content,
-            finish_reason,
+            text: echo_prefix + &out.text,
+            finish_reason: out.finish_reason,
             logprobs: None,
         }],
         usage: Usage {
-            prompt_tokens,
-            completion_tokens,
-            total_tokens: prompt_tokens + completion_tokens,
+            prompt_tokens: out.prompt_tokens,
+            completion_tokens: out.completion_tokens,
+            total_tokens: out.prompt_tokens + out.completion_tokens,
         },
         system_fingerprint: Some(fingerprint),
+        error: out.error.as_ref().map(generation_error_detail),
     })
+    .into_response()
 }
 
 //  /v1/embeddings
@@ -1007,13 +1384,23 @@ struct EmbeddingUsage {
 /// POST /v1/embeddings — Get embeddings for text.
 ///
 /// Note: embedding support depends on the model. Standard chat models
-/// may not produce meaningful embeddings. A dedicated embedding model
-/// (e.g. nomic-embed) is recommended.
-async fn embeddings(State(state): State<AppState>, Json(req): Json<EmbeddingRequest>) -> Response {
-    let loaded = match resolve_model(&state, req.model.as_deref()) {
+/// may not produce meaningful embeddings (a warning is logged). A
+/// dedicated embedding model (e.g. nomic-embed) is recommended.
+async fn embeddings(
+    State(state): State<AppState>,
+    OpenAiJson(req): OpenAiJson<EmbeddingRequest>,
+) -> Response {
+    let loaded = match resolve_model(&state, req.model.as_deref()).await {
         Ok(l) => l,
         Err(e) => return e,
     };
+    if !loaded.supports(gguf_parser::Capability::Embeddings) {
+        warn!(
+            id = %loaded.id,
+            "Embeddings requested from a model that is not an embedding model; \
+             results may be poor"
+        );
+    }
 
     let model_id = loaded.id.clone();
     let model = loaded.model.clone();
@@ -1029,62 +1416,30 @@ async fn embeddings(State(state): State<AppState>, Json(req): Json<EmbeddingRequ
 
     let n_embd = model.n_embd() as usize;
 
-    // Create a temporary context with embeddings enabled.
-    // This is separate from the inference context so that normal chat/completions
-    // are not affected by the embeddings flag.
-    let model_clone = model.clone();
     let results = tokio::task::spawn_blocking(move || {
-        let emb_ctx_params = llama_core::ContextParams {
-            n_ctx: 4096,
-            embeddings: true,
-            ..Default::default()
-        };
-        let mut emb_ctx = llama_core::LlamaContext::new(model_clone.clone(), &emb_ctx_params)
-            .map_err(|e| format!("Failed to create embeddings context: {e}"))?;
-
-        let vocab = model_clone.vocab();
-        let mut all_embeddings: Vec<(Vec<f32>, u32)> = Vec::new();
-
-        for text in &texts {
-            emb_ctx.kv_cache_clear();
-
-            let tokens = match llama_core::tokenize(vocab, text, true, true) {
-                Ok(t) => t,
-                Err(e) => return Err(format!("Tokenization failed: {e}")),
-            };
-            let n_tokens = tokens.len() as u32;
-
-            // Create a batch with the tokens
-            let mut batch = llama_core::LlamaBatch::new(tokens.len() as i32, 0, 1);
-            for (i, &token) in tokens.iter().enumerate() {
-                batch.add(token, i as i32, &[0], i == tokens.len() - 1);
-            }
-
-            // Decode
-            if let Err(e) = emb_ctx.decode(&mut batch) {
-                return Err(format!("Decode failed: {e}"));
-            }
-
-            // Get embeddings
-            match emb_ctx.get_embeddings() {
-                Some(emb) => {
-                    let embedding = emb[..n_embd].to_vec();
-                    // L2 normalize
-                    let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
-                    let normalized = if norm > 0.0 {
-                        embedding.iter().map(|x| x / norm).collect()
-                    } else {
-                        embedding
-                    };
-                    all_embeddings.push((normalized, n_tokens));
-                }
-                None => {
-                    return Err("Model does not support embeddings extraction.".to_string());
-                }
-            }
-        }
-
-        Ok(all_embeddings)
+        let read = |ctx: &llama_core::LlamaContext| read_embedding(ctx, n_embd).map(l2_normalize);
+        pooled_blocking(model, &texts, &PoolParams::default(), read)
+            .map(|pooled| {
+                pooled
+                    .into_iter()
+                    .map(|p| (p.values, p.n_tokens))
+                    .collect::<Vec<_>>()
+            })
+            .map_err(|e| match e {
+                PoolError::NoOutput => ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "Model does not support embeddings extraction.",
+                    "invalid_request_error",
+                )
+                .with_code("embeddings_unsupported"),
+                e @ PoolError::TooLong { .. } => ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    e.to_string(),
+                    "invalid_request_error",
+                )
+                .with_code("context_length_exceeded"),
+                PoolError::Llama(e) => ApiError::llama(&e, format!("Embeddings failed: {e}")),
+            })
     })
     .await;
 
@@ -1115,7 +1470,7 @@ async fn embeddings(State(state): State<AppState>, Json(req): Json<EmbeddingRequ
             })
             .into_response()
         }
-        Ok(Err(e)) => api_error(StatusCode::BAD_REQUEST, e, "invalid_request_error"),
+        Ok(Err(e)) => e.into_response(),
         Err(e) => api_error(
             StatusCode::INTERNAL_SERVER_ERROR,
             e.to_string(),
@@ -1123,3 +1478,996 @@ async fn embeddings(State(state): State<AppState>, Json(req): Json<EmbeddingRequ
         ),
     }
 }
+
+//  OpenAPI
+
+/// Describe the routes above in `spec`.
+pub fn openapi(spec: &mut Spec) {
+    let nullable_string = json!({ "type": ["string", "null"] });
+    let finish_reason = json!({
+        "type": ["string", "null"],
+        "enum": ["stop", "length", null],
+    });
+    let finish_details = json!({
+        "const": "timeout",
+        "description": "Extension: set when the time limit ended generation.",
+    });
+    let keep_alive = json!({
+        "type": ["number", "string"],
+        "description": "Extension: keep the model loaded this long after the request, \
+            as seconds or a duration like \"5m\"; 0 unloads it, negative keeps it loaded.",
+    });
+    let timeout = json!({
+        "type": "integer",
+        "minimum": 0,
+        "description": "Extension: generation time limit in seconds; can only lower \
+            the server's generation_timeout_secs.",
+    });
+    let return_special = json!({
+        "type": "boolean",
+        "description": "Extension: stream control tokens such as <|im_end|> as text \
+            instead of dropping them.",
+    });
+
+    //  Shared
+    spec.component(
+        "ErrorDetail",
+        json!({
+            "type": "object",
+            "properties": {
+                "message": { "type": "string" },
+                "type": { "type": "string" },
+                "param": nullable_string,
+                "code": nullable_string,
+            },
+            "required": ["message", "type", "param", "code"],
+        }),
+    );
+    spec.component(
+        "Error",
+        json!({
+            "type": "object",
+            "properties": { "error": schema_ref("ErrorDetail") },
+            "required": ["error"],
+        }),
+    );
+    spec.component(
+        "Usage",
+        json!({
+            "type": "object",
+            "properties": {
+                "prompt_tokens": { "type": "integer" },
+                "completion_tokens": { "type": "integer" },
+                "total_tokens": { "type": "integer" },
+            },
+            "required": ["prompt_tokens", "completion_tokens", "total_tokens"],
+        }),
+    );
+    spec.component(
+        "StopSequence",
+        json!({
+            "description": "Up to 4 sequences where generation stops.",
+            "oneOf": [
+                { "type": "string" },
+                { "type": "array", "items": { "type": "string" } },
+            ],
+        }),
+    );
+
+    //  Models
+    spec.component(
+        "Model",
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "object": { "const": "model" },
+                "created": { "type": "integer" },
+                "owned_by": { "type": "string" },
+                "alias_for": {
+                    "type": "string",
+                    "description": "Extension: on alias entries, the model id they resolve to.",
+                },
+                "display_name": {
+                    "type": "string",
+                    "description": "Extension: user-assigned label of the (target) model.",
+                },
+                "capabilities": {
+                    "type": "array",
+                    "items": { "enum": ["chat", "embeddings", "vision-projector"] },
+                    "description": "Extension: what the model can serve; omitted on alias \
+                        entries.",
+                },
+                "features": {
+                    "type": "array",
+                    "items": { "enum": ["moe", "vision", "embedding", "instruct", "reasoning"] },
+                    "description": "Extension: badges guessed from the metadata; omitted on \
+                        alias entries.",
+                },
+                "tokenizer": {
+                    "$ref": "#/components/schemas/TokenizerInfo",
+                    "description": "Extension: special tokens and template format \
+                        (single-model lookups only).",
+                },
+            },
+            "required": ["id", "object", "created", "owned_by"],
+        }),
+    );
+    spec.component(
+        "ModelList",
+        json!({
+            "type": "object",
+            "properties": {
+                "object": { "const": "list" },
+                "data": { "type": "array", "items": schema_ref("Model") },
+            },
+            "required": ["object", "data"],
+        }),
+    );
+
+    //  Chat completions
+    spec.component(
+        "ContentPart",
+        json!({
+            "type": "object",
+            "properties": {
+                "type": { "type": "string", "description": "Only `text` parts are used." },
+                "text": { "type": "string" },
+                "image_url": { "type": "object", "description": "Accepted and ignored." },
+            },
+            "required": ["type"],
+        }),
+    );
+    spec.component(
+        "ChatContent",
+        json!({
+            "oneOf": [
+                { "type": "string" },
+                { "type": "array", "items": schema_ref("ContentPart") },
+            ],
+        }),
+    );
+    spec.component(
+        "ChatMessage",
+        json!({
+            "type": "object",
+            "properties": {
+                "role": { "type": "string" },
+                "content": {
+                    "oneOf": [schema_ref("ChatContent"), { "type": "null" }],
+                },
+                "name": { "type": "string" },
+                "tool_calls": {},
+                "tool_call_id": { "type": "string" },
+            },
+            "required": ["role"],
+        }),
+    );
+    spec.component(
+        "ChatCompletionRequest",
+        json!({
+            "type": "object",
+            "properties": {
+                "model": {
+                    "type": "string",
+                    "description": "Model id, alias, or unique part of an id; \
+                        defaults to a loaded model.",
+                },
+                "messages": { "type": "array", "items": schema_ref("ChatMessage") },
+                "max_tokens": { "type": "integer" },
+                "max_completion_tokens": { "type": "integer" },
+                "temperature": { "type": "number" },
+                "top_p": { "type": "number" },
+                "n": { "type": "integer" },
+                "stream": { "type": "boolean" },
+                "stop": schema_ref("StopSequence"),
+                "frequency_penalty": { "type": "number" },
+                "presence_penalty": { "type": "number" },
+                "logprobs": { "type": "boolean" },
+                "top_logprobs": { "type": "integer" },
+                "seed": { "type": "integer" },
+                "user": { "type": "string" },
+                "response_format": {
+                    "type": "object",
+                    "properties": { "type": { "type": "string" } },
+                    "required": ["type"],
+                },
+                "keep_alive": keep_alive,
+                "timeout": timeout,
+                "return_special": return_special,
+            },
+            "required": ["messages"],
+        }),
+    );
+    spec.component(
+        "ChatCompletion",
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "object": { "const": "chat.completion" },
+                "created": { "type": "integer" },
+                "model": { "type": "string" },
+                "choices": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "index": { "type": "integer" },
+                            "message": {
+                                "type": "object",
+                                "properties": {
+                                    "role": { "const": "assistant" },
+                                    "content": nullable_string,
+                                    "tool_calls": {},
+                                },
+                                "required": ["role", "content"],
+                            },
+                            "finish_reason": finish_reason,
+                            "logprobs": {},
+                        },
+                        "required": ["index", "message", "finish_reason"],
+                    },
+                },
+                "usage": schema_ref("Usage"),
+                "system_fingerprint": nullable_string,
+                "error": {
+                    "$ref": "#/components/schemas/ErrorDetail",
+                    "description": "Set when generation failed after producing partial output.",
+                },
+            },
+            "required": [
+                "id", "object", "created", "model", "choices", "usage", "system_fingerprint",
+            ],
+        }),
+    );
+    spec.component(
+        "ChatCompletionChunk",
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "object": { "const": "chat.completion.chunk" },
+                "created": { "type": "integer" },
+                "model": { "type": "string" },
+                "choices": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "index": { "type": "integer" },
+                            "delta": {
+                                "type": "object",
+                                "properties": {
+                                    "role": { "type": "string" },
+                                    "content": { "type": "string" },
+                                },
+                            },
+                            "finish_reason": finish_reason,
+                            "logprobs": {},
+                            "finish_details": finish_details,
+                        },
+                        "required": ["index", "delta", "finish_reason"],
+                    },
+                },
+                "system_fingerprint": nullable_string,
+            },
+            "required": ["id", "object", "created", "model", "choices", "system_fingerprint"],
+        }),
+    );
+
+    //  Text completions
+    spec.component(
+        "PromptInput",
+        json!({
+            "oneOf": [
+                { "type": "string" },
+                { "type": "array", "items": { "type": "string" } },
+                {
+                    "type": "array",
+                    "items": { "type": "integer" },
+                    "minItems": 1,
+                    "description": "Token ids.",
+                },
+            ],
+        }),
+    );
+    spec.component(
+        "CompletionRequest",
+        json!({
+            "type": "object",
+            "properties": {
+                "model": { "type": "string" },
+                "prompt": schema_ref("PromptInput"),
+                "max_tokens": { "type": "integer" },
+                "temperature": { "type": "number" },
+                "top_p": { "type": "number" },
+                "n": { "type": "integer" },
+                "stream": { "type": "boolean" },
+                "stop": schema_ref("StopSequence"),
+                "frequency_penalty": { "type": "number" },
+                "presence_penalty": { "type": "number" },
+                "logprobs": { "type": "integer" },
+                "echo": { "type": "boolean" },
+                "suffix": { "type": "string" },
+                "seed": { "type": "integer" },
+                "user": { "type": "string" },
+                "best_of": { "type": "integer" },
+                "keep_alive": keep_alive,
+                "timeout": timeout,
+                "return_special": return_special,
+            },
+            "required": ["prompt"],
+        }),
+    );
+    spec.component(
+        "Completion",
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "object": { "const": "text_completion" },
+                "created": { "type": "integer" },
+                "model": { "type": "string" },
+                "choices": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "index": { "type": "integer" },
+                            "text": { "type": "string" },
+                            "finish_reason": finish_reason,
+                            "logprobs": {},
+                        },
+                        "required": ["index", "text", "finish_reason", "logprobs"],
+                    },
+                },
+                "usage": schema_ref("Usage"),
+                "system_fingerprint": nullable_string,
+                "error": {
+                    "$ref": "#/components/schemas/ErrorDetail",
+                    "description": "Set when generation failed after producing partial output.",
+                },
+            },
+            "required": [
+                "id", "object", "created", "model", "choices", "usage", "system_fingerprint",
+            ],
+        }),
+    );
+    spec.component(
+        "CompletionChunk",
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "object": { "const": "text_completion" },
+                "created": { "type": "integer" },
+                "model": { "type": "string" },
+                "choices": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "index": { "type": "integer" },
+                            "text": { "type": "string" },
+                            "finish_reason": finish_reason,
+                            "logprobs": {},
+                            "finish_details": finish_details,
+                        },
+                        "required": ["index", "text", "finish_reason", "logprobs"],
+                    },
+                },
+                "system_fingerprint": nullable_string,
+            },
+            "required": ["id", "object", "created", "model", "choices", "system_fingerprint"],
+        }),
+    );
+
+    //  Embeddings
+    spec.component(
+        "EmbeddingInput",
+        json!({
+            "oneOf": [
+                { "type": "string" },
+                { "type": "array", "items": { "type": "string" } },
+                {
+                    "type": "array",
+                    "items": { "type": "array", "items": { "type": "integer" } },
+                    "minItems": 1,
+                },
+                { "type": "array", "items": { "type": "integer" }, "minItems": 1 },
+            ],
+        }),
+    );
+    spec.component(
+        "EmbeddingRequest",
+        json!({
+            "type": "object",
+            "properties": {
+                "model": { "type": "string" },
+                "input": schema_ref("EmbeddingInput"),
+                "encoding_format": { "type": "string" },
+                "user": { "type": "string" },
+            },
+            "required": ["input"],
+        }),
+    );
+    spec.component(
+        "EmbeddingList",
+        json!({
+            "type": "object",
+            "properties": {
+                "object": { "const": "list" },
+                "data": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "object": { "const": "embedding" },
+                            "index": { "type": "integer" },
+                            "embedding": { "type": "array", "items": { "type": "number" } },
+                        },
+                        "required": ["object", "index", "embedding"],
+                    },
+                },
+                "model": { "type": "string" },
+                "usage": {
+                    "type": "object",
+                    "properties": {
+                        "prompt_tokens": { "type": "integer" },
+                        "total_tokens": { "type": "integer" },
+                    },
+                    "required": ["prompt_tokens", "total_tokens"],
+                },
+            },
+            "required": ["object", "data", "model", "usage"],
+        }),
+    );
+
+    //  Operations
+    let error = |description: &str| json_response(description, schema_ref("Error"));
+    let model_param = param(
+        "path",
+        "model",
+        json!({ "type": "string" }),
+        "Model id or alias",
+    );
+    spec.operation(
+        "get",
+        "/v1/models",
+        json!({
+            "tags": ["OpenAI"],
+            "summary": "List loaded and available models, and aliases",
+            "responses": { "200": json_response("Models", schema_ref("ModelList")) },
+        }),
+    );
+    spec.operation(
+        "get",
+        "/v1/models/{model}",
+        json!({
+            "tags": ["OpenAI"],
+            "summary": "Retrieve a model",
+            "parameters": [model_param],
+            "responses": {
+                "200": json_response("The model", schema_ref("Model")),
+                "404": error("No such model"),
+            },
+        }),
+    );
+    spec.operation(
+        "delete",
+        "/v1/models/{model}",
+        json!({
+            "tags": ["OpenAI"],
+            "summary": "Unload a model",
+            "parameters": [model_param],
+            "responses": {
+                "200": json_response("Unloaded", json!({
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "object": { "const": "model" },
+                        "deleted": { "type": "boolean" },
+                    },
+                    "required": ["id", "object", "deleted"],
+                })),
+                "404": error("Model not loaded"),
+            },
+        }),
+    );
+    let generation_errors = json!({
+        "400": error("Invalid request"),
+        "404": error("No such model"),
+        "413": error("Request body too large"),
+        "500": error("Generation failed"),
+        "503": error("No model loaded, or the server is shutting down"),
+        "504": error("The generation time limit ran out"),
+    });
+    for (path, summary, body, response, chunk) in [
+        (
+            "/v1/chat/completions",
+            "Chat completion",
+            "ChatCompletionRequest",
+            "ChatCompletion",
+            "ChatCompletionChunk",
+        ),
+        (
+            "/v1/completions",
+            "Text completion (legacy)",
+            "CompletionRequest",
+            "Completion",
+            "CompletionChunk",
+        ),
+    ] {
+        let mut responses = generation_errors.clone();
+        responses["200"] = json_or_sse_response(response, chunk);
+        spec.operation(
+            "post",
+            path,
+            json!({
+                "tags": ["OpenAI"],
+                "summary": summary,
+                "requestBody": json_body(body),
+                "responses": responses,
+            }),
+        );
+    }
+    spec.operation(
+        "post",
+        "/v1/embeddings",
+        json!({
+            "tags": ["OpenAI"],
+            "summary": "Embed text",
+            "requestBody": json_body("EmbeddingRequest"),
+            "responses": {
+                "200": json_response("Embeddings", schema_ref("EmbeddingList")),
+                "400": error("Invalid request"),
+                "404": error("No such model"),
+                "500": error("Embedding failed"),
+                "503": error("No model loaded"),
+            },
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, to_bytes};
+
+    async fn extract(body: &str) -> Result<CompletionRequest, Response> {
+        let req = Request::builder()
+            .method("POST")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        OpenAiJson::<CompletionRequest>::from_request(req, &())
+            .await
+            .map(|OpenAiJson(r)| r)
+    }
+
+    async fn error_json(resp: Response) -> serde_json::Value {
+        let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn json_extractor_accepts_valid_body() {
+        let req = extract(r#"{"prompt": "hi", "max_tokens": 4}"#)
+            .await
+            .unwrap();
+        assert_eq!(req.max_tokens, Some(4));
+    }
+
+    #[tokio::test]
+    async fn json_extractor_wraps_type_errors() {
+        let resp = extract(r#"{"prompt": "hi", "max_tokens": "four"}"#)
+            .await
+            .unwrap_err();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body = error_json(resp).await;
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert!(
+            body["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("max_tokens")
+        );
+    }
+
+    #[tokio::test]
+    async fn json_extractor_wraps_syntax_errors() {
+        let resp = extract("{not json").await.unwrap_err();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body = error_json(resp).await;
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+    }
+
+    #[tokio::test]
+    async fn validation_error_populates_param_and_code() {
+        let err = validation::validate_generation_params(&validation::GenerationParams {
+            top_p: Some(7.0),
+            ..Default::default()
+        })
+        .unwrap_err();
+        let resp = validation_error(err);
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body = error_json(resp).await;
+        assert_eq!(body["error"]["param"], "top_p");
+        assert_eq!(body["error"]["code"], "decimal_above_max_value");
+    }
+
+    /// The event sequence `generate_blocking` emits when a decode step
+    /// fails after `tokens` pieces were produced.
+    fn failing_decode(tokens: &[&str]) -> mpsc::Receiver<llama_core::GenerateEvent> {
+        let (tx, rx) = mpsc::channel(16);
+        for t in tokens {
+            tx.try_send(llama_core::GenerateEvent::Token(t.to_string()))
+                .unwrap();
+        }
+        let err = llama_core::LlamaError::DecodeFailed(-1);
+        tx.try_send(llama_core::GenerateEvent::Error(
+            llama_core::GenerateError::llama("decode", &err),
+        ))
+        .unwrap();
+        rx
+    }
+
+    #[tokio::test]
+    async fn stream_emits_error_envelope_then_done() {
+        let payloads: Vec<String> = chat_stream_payloads(
+            failing_decode(&["Hel"]),
+            "chatcmpl-1".into(),
+            0,
+            "m".into(),
+            "fp".into(),
+        )
+        .collect()
+        .await;
+
+        assert_eq!(payloads.len(), 3);
+        let first: serde_json::Value = serde_json::from_str(&payloads[0]).unwrap();
+        assert_eq!(first["choices"][0]["delta"]["content"], "Hel");
+        let err: serde_json::Value = serde_json::from_str(&payloads[1]).unwrap();
+        assert_eq!(err["error"]["type"], "server_error");
+        assert_eq!(err["error"]["code"], "decode_failed");
+        assert!(
+            err["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("Decode failed")
+        );
+        assert_eq!(payloads[2], STREAM_DONE);
+    }
+
+    #[tokio::test]
+    async fn completion_stream_ends_with_done() {
+        let (tx, rx) = mpsc::channel(4);
+        tx.try_send(llama_core::GenerateEvent::Token("a".into()))
+            .unwrap();
+        tx.try_send(llama_core::GenerateEvent::Done {
+            finish_reason: llama_core::FinishReason::Stop,
+            prompt_tokens: 1,
+            completion_tokens: 1,
+        })
+        .unwrap();
+        drop(tx);
+
+        let payloads: Vec<String> =
+            completion_stream_payloads(rx, "c".into(), 0, "m".into(), "fp".into(), "> ".into())
+                .collect()
+                .await;
+
+        assert_eq!(payloads.len(), 3);
+        let first: serde_json::Value = serde_json::from_str(&payloads[0]).unwrap();
+        assert_eq!(first["choices"][0]["text"], "> a");
+        assert_eq!(payloads[2], STREAM_DONE);
+    }
+
+    #[tokio::test]
+    async fn non_stream_failure_without_output_is_500() {
+        let out = collect_generation(failing_decode(&[])).await;
+        let resp = chat_completion_response(out, "c".into(), 0, "m".into(), "fp".into());
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = error_json(resp).await;
+        assert_eq!(body["error"]["type"], "server_error");
+        assert_eq!(body["error"]["code"], "decode_failed");
+    }
+
+    #[tokio::test]
+    async fn full_kv_cache_is_reported_as_such() {
+        let kv_full = || {
+            let (tx, rx) = mpsc::channel(1);
+            let err = llama_core::LlamaError::KvCacheFull {
+                used: 4096,
+                capacity: 4096,
+            };
+            tx.try_send(llama_core::GenerateEvent::Error(
+                llama_core::GenerateError::llama("decode", &err),
+            ))
+            .unwrap();
+            rx
+        };
+
+        let out = collect_generation(kv_full()).await;
+        let resp = chat_completion_response(out, "c".into(), 0, "m".into(), "fp".into());
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = error_json(resp).await;
+        assert_eq!(body["error"]["code"], "kv_cache_full");
+        assert_eq!(body["error"]["type"], "server_error");
+
+        let resp =
+            completion_non_stream(kv_full(), "c".into(), 0, "m".into(), "fp".into(), "".into())
+                .await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(error_json(resp).await["error"]["code"], "kv_cache_full");
+
+        let payloads: Vec<String> =
+            chat_stream_payloads(kv_full(), "c".into(), 0, "m".into(), "fp".into())
+                .collect()
+                .await;
+        let err: serde_json::Value = serde_json::from_str(&payloads[0]).unwrap();
+        assert_eq!(err["error"]["code"], "kv_cache_full");
+    }
+
+    #[tokio::test]
+    async fn non_stream_failure_with_output_attaches_error() {
+        let out = collect_generation(failing_decode(&["partial", " text"])).await;
+        let resp = chat_completion_response(out, "c".into(), 0, "m".into(), "fp".into());
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = error_json(resp).await;
+        assert_eq!(body["choices"][0]["message"]["content"], "partial text");
+        assert_eq!(body["error"]["type"], "server_error");
+    }
+
+    fn timed_out(tokens: &[&str]) -> mpsc::Receiver<llama_core::GenerateEvent> {
+        let (tx, rx) = mpsc::channel(tokens.len() + 1);
+        for t in tokens {
+            tx.try_send(llama_core::GenerateEvent::Token(t.to_string()))
+                .unwrap();
+        }
+        tx.try_send(llama_core::GenerateEvent::Done {
+            finish_reason: llama_core::FinishReason::Timeout,
+            prompt_tokens: 3,
+            completion_tokens: tokens.len() as u32,
+        })
+        .unwrap();
+        rx
+    }
+
+    #[tokio::test]
+    async fn timeout_truncates_stream_and_fails_non_stream() {
+        let payloads: Vec<String> =
+            chat_stream_payloads(timed_out(&["a"]), "c".into(), 0, "m".into(), "fp".into())
+                .collect()
+                .await;
+        assert_eq!(payloads.len(), 3);
+        let last: serde_json::Value = serde_json::from_str(&payloads[1]).unwrap();
+        assert_eq!(last["choices"][0]["finish_reason"], "length");
+        assert_eq!(last["choices"][0]["finish_details"], "timeout");
+
+        let out = collect_generation(timed_out(&["a", "b"])).await;
+        let resp = chat_completion_response(out, "c".into(), 0, "m".into(), "fp".into());
+        assert_eq!(resp.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn oversized_body_is_rejected() {
+        let config = crate::config::AppConfig {
+            max_body_bytes: 64,
+            ..Default::default()
+        };
+        let app = crate::routes::app(AppState::for_tests(config, None));
+        let body = serde_json::json!({
+            "messages": [{ "role": "user", "content": "x".repeat(100) }],
+        });
+        let req = Request::builder()
+            .method("POST")
+            .uri("/v1/chat/completions")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let resp = tower::ServiceExt::oneshot(app, req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body = error_json(resp).await;
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+    }
+
+    /// Whether `value` passes component `name` of the OpenAPI document.
+    fn documented(name: &str, value: &serde_json::Value) -> Result<(), String> {
+        let spec = crate::routes::openapi::spec();
+        crate::routes::openapi::validate(&spec, &schema_ref(name), value)
+    }
+
+    /// The schema of `name` accepts exactly the `cases` serde accepts as `T`.
+    fn agrees<T: serde::de::DeserializeOwned>(name: &str, cases: serde_json::Value) {
+        for case in cases.as_array().unwrap() {
+            let schema = documented(name, case);
+            let serde = serde_json::from_value::<T>(case.clone());
+            assert_eq!(
+                schema.is_ok(),
+                serde.is_ok(),
+                "{name} on {case}: schema {schema:?}, serde {:?}",
+                serde.err()
+            );
+        }
+    }
+
+    #[test]
+    fn union_schemas_agree_with_serde() {
+        let cases = json!(["x", ["a", "b"], [], 5, [1], null]);
+        agrees::<StopSequence>("StopSequence", cases);
+        let cases = json!(["x", ["a"], [1, 2], [], [-1], 1.5, [true], ["a", 1]]);
+        agrees::<PromptInput>("PromptInput", cases);
+        let cases = json!([
+            "hi",
+            [{ "type": "text", "text": "a" }],
+            [{ "type": "image_url", "image_url": { "url": "data:," } }],
+            [],
+            [{ "text": "no type" }],
+            3,
+        ]);
+        agrees::<ChatContent>("ChatContent", cases);
+        let cases = json!(["x", ["a"], [[1, 2]], [[]], [1, 2], [], [[1], ["a"]], {}]);
+        agrees::<EmbeddingInput>("EmbeddingInput", cases);
+    }
+
+    #[test]
+    fn request_schemas_describe_request_types() {
+        let chat = json!({
+            "model": "m",
+            "messages": [
+                { "role": "system", "content": "Be brief.", "name": "s" },
+                { "role": "user", "content": [{ "type": "text", "text": "hi" }] },
+                { "role": "assistant", "content": null, "tool_calls": [] },
+                { "role": "tool", "content": "42", "tool_call_id": "t1" },
+            ],
+            "max_tokens": 8, "max_completion_tokens": 8, "temperature": 0.5, "top_p": 0.9,
+            "n": 1, "stream": true, "stop": ["\n"], "frequency_penalty": 0.1,
+            "presence_penalty": 0.1, "logprobs": false, "top_logprobs": 0, "seed": 1,
+            "user": "u", "response_format": { "type": "text" }, "keep_alive": "5m",
+            "timeout": 30, "return_special": true,
+        });
+        documented("ChatCompletionRequest", &chat).unwrap();
+        serde_json::from_value::<ChatCompletionRequest>(chat).unwrap();
+
+        let completion = json!({
+            "model": "m", "prompt": [1, 2, 3], "max_tokens": 8, "temperature": 0.5,
+            "top_p": 0.9, "n": 1, "stream": false, "stop": "\n", "frequency_penalty": 0.1,
+            "presence_penalty": 0.1, "logprobs": 0, "echo": true, "suffix": "", "seed": 1,
+            "user": "u", "best_of": 1, "keep_alive": -1, "timeout": 30, "return_special": true,
+        });
+        documented("CompletionRequest", &completion).unwrap();
+        serde_json::from_value::<CompletionRequest>(completion).unwrap();
+
+        let embedding = json!({
+            "model": "m", "input": ["a", "b"], "encoding_format": "float", "user": "u",
+        });
+        documented("EmbeddingRequest", &embedding).unwrap();
+        serde_json::from_value::<EmbeddingRequest>(embedding).unwrap();
+
+        let missing = json!({ "model": "m" });
+        assert!(documented("ChatCompletionRequest", &missing).is_err());
+        assert!(serde_json::from_value::<ChatCompletionRequest>(missing).is_err());
+    }
+
+    #[tokio::test]
+    async fn responses_match_their_schemas() {
+        let (tx, rx) = mpsc::channel(4);
+        tx.try_send(llama_core::GenerateEvent::Token("a".into()))
+            .unwrap();
+        tx.try_send(llama_core::GenerateEvent::Done {
+            finish_reason: llama_core::FinishReason::Timeout,
+            prompt_tokens: 1,
+            completion_tokens: 1,
+        })
+        .unwrap();
+        drop(tx);
+        let payloads: Vec<String> =
+            chat_stream_payloads(rx, "c".into(), 0, "m".into(), "fp".into())
+                .collect()
+                .await;
+        for chunk in &payloads[..payloads.len() - 1] {
+            documented("ChatCompletionChunk", &serde_json::from_str(chunk).unwrap()).unwrap();
+        }
+
+        let payloads: Vec<String> = chat_stream_payloads(
+            failing_decode(&["a"]),
+            "c".into(),
+            0,
+            "m".into(),
+            "fp".into(),
+        )
+        .collect()
+        .await;
+        documented("Error", &serde_json::from_str(&payloads[1]).unwrap()).unwrap();
+
+        let out = collect_generation(failing_decode(&["partial"])).await;
+        let resp = chat_completion_response(out, "c".into(), 0, "m".into(), "fp".into());
+        documented("ChatCompletion", &error_json(resp).await).unwrap();
+
+        let (tx, rx) = mpsc::channel(4);
+        tx.try_send(llama_core::GenerateEvent::Token("a".into()))
+            .unwrap();
+        tx.try_send(llama_core::GenerateEvent::Done {
+            finish_reason: llama_core::FinishReason::Stop,
+            prompt_tokens: 1,
+            completion_tokens: 1,
+        })
+        .unwrap();
+        drop(tx);
+        let payloads: Vec<String> =
+            completion_stream_payloads(rx, "c".into(), 0, "m".into(), "fp".into(), "".into())
+                .collect()
+                .await;
+        for chunk in &payloads[..payloads.len() - 1] {
+            documented("CompletionChunk", &serde_json::from_str(chunk).unwrap()).unwrap();
+        }
+
+        let resp = completion_non_stream(
+            failing_decode(&["partial"]),
+            "c".into(),
+            0,
+            "m".into(),
+            "fp".into(),
+            "".into(),
+        )
+        .await;
+        documented("Completion", &error_json(resp).await).unwrap();
+
+        let models = ModelsListResponse {
+            object: "list",
+            data: vec![ModelObject {
+                id: "alias".into(),
+                object: "model",
+                created: 0,
+                owned_by: "local",
+                alias_for: Some("m".into()),
+                display_name: Some("M".into()),
+                capabilities: Vec::new(),
+                features: Vec::new(),
+                tokenizer: None,
+            }],
+        };
+        documented("ModelList", &serde_json::to_value(models).unwrap()).unwrap();
+
+        let model = ModelObject {
+            id: "m".into(),
+            object: "model",
+            created: 0,
+            owned_by: "local",
+            alias_for: None,
+            display_name: None,
+            capabilities: vec![gguf_parser::Capability::Chat],
+            features: vec![gguf_parser::ModelFeature::Instruct],
+            tokenizer: Some(TokenizerInfo {
+                source: "model",
+                n_vocab: Some(32000),
+                bos: Some(crate::services::model_manager::SpecialToken {
+                    id: 1,
+                    text: Some("<s>".into()),
+                }),
+                eos: None,
+                eot: None,
+                add_bos: Some(true),
+                chat_template_name: None,
+            }),
+        };
+        documented("Model", &serde_json::to_value(model).unwrap()).unwrap();
+
+        let embeddings = EmbeddingResponse {
+            object: "list",
+            data: vec![EmbeddingData {
+                object: "embedding",
+                index: 0,
+                embedding: vec![0.5, -1.0],
+            }],
+            model: "m".into(),
+            usage: EmbeddingUsage {
+                prompt_tokens: 2,
+                total_tokens: 2,
+            },
+        };
+        documented("EmbeddingList", &serde_json::to_value(embeddings).unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,325 @@
+//! OpenAPI 3.1 description of the API: GET /openapi.json
+//!
+//! The document is written by hand.  Each route module describes its
+//! operations and the schemas of its request/response types in an
+//! `openapi(&mut Spec)` function beside its `router()`.  The tests of
+//! those modules run real bodies through [`validate`] against the
+//! schemas, so a field added to a type but not to its schema is caught.
+//! Covered are the OpenAI-compatible, native and management routes.
+
+use std::sync::OnceLock;
+
+use axum::{Json, Router, routing::get};
+use serde_json::{Map, Value, json};
+
+use crate::routes::{management, native, openai};
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/openapi.json", get(openapi_json))
+}
+
+/// GET /openapi.json — the API description; needs no key.
+async fn openapi_json() -> Json<&'static Value> {
+    static SPEC: OnceLock<Value> = OnceLock::new();
+    Json(SPEC.get_or_init(spec))
+}
+
+/// The complete document.
+pub fn spec() -> Value {
+    let mut spec = Spec::default();
+    spec.operation(
+        "get",
+        "/health",
+        json!({
+            "tags": ["System"],
+            "summary": "Liveness check",
+            "security": [],
+            "responses": {
+                "200": json_response("Server is up", json!({
+                    "type": "object",
+                    "properties": { "status": { "const": "ok" } },
+                    "required": ["status"],
+                })),
+            },
+        }),
+    );
+    spec.operation(
+        "get",
+        "/openapi.json",
+        json!({
+            "tags": ["System"],
+            "summary": "This document",
+            "security": [],
+            "responses": {
+                "200": json_response("OpenAPI 3.1 document", json!({ "type": "object" })),
+            },
+        }),
+    );
+    openai::openapi(&mut spec);
+    native::openapi(&mut spec);
+    management::openapi(&mut spec);
+    spec.into_json()
+}
+
+/// Paths and component schemas collected from the route modules.
+#[derive(Default)]
+pub struct Spec {
+    paths: Map<String, Value>,
+    schemas: Map<String, Value>,
+}
+
+impl Spec {
+    /// Add `schema` to the components as `name`.
+    pub fn component(&mut self, name: &str, schema: Value) {
+        let previous = self.schemas.insert(name.to_string(), schema);
+        debug_assert!(previous.is_none(), "schema {name} described twice");
+    }
+
+    /// Add the operation object `op` for `method` on `path`.
+    pub fn operation(&mut self, method: &str, path: &str, op: Value) {
+        let item = self
+            .paths
+            .entry(path.to_string())
+            .or_insert_with(|| json!({}));
+        item[method] = op;
+    }
+
+    fn into_json(self) -> Value {
+        json!({
+            "openapi": "3.1.0",
+            "info": {
+                "title": "llama-dashboard",
+                "version": env!("CARGO_PKG_VERSION"),
+                "description": "Local LLM server with an OpenAI-compatible API.",
+            },
+            "security": [{ "bearerAuth": [] }],
+            "paths": self.paths,
+            "components": {
+                "schemas": self.schemas,
+                "securitySchemes": {
+                    "bearerAuth": {
+                        "type": "http",
+                        "scheme": "bearer",
+                        "description": "API key; `X-Api-Key: <key>` is accepted as well.",
+                    },
+                },
+            },
+        })
+    }
+}
+
+//  Helpers for the route modules
+
+/// `$ref` to the component schema `name`.
+pub fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{name}") })
+}
+
+/// Required JSON request body of component `name`.
+pub fn json_body(name: &str) -> Value {
+    json!({
+        "required": true,
+        "content": { "application/json": { "schema": schema_ref(name) } },
+    })
+}
+
+pub fn json_response(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema } },
+    })
+}
+
+/// Response that is either JSON of component `name` or, with
+/// `"stream": true`, server-sent events carrying `chunk` objects.
+pub fn json_or_sse_response(name: &str, chunk: &str) -> Value {
+    json!({
+        "description": format!(
+            "{name}, or with `stream: true` an event stream of {chunk} objects"
+        ),
+        "content": {
+            "application/json": { "schema": schema_ref(name) },
+            "text/event-stream": {
+                "schema": {
+                    "type": "string",
+                    "description": format!("`data:` lines, each a JSON {chunk}"),
+                },
+            },
+        },
+    })
+}
+
+/// Error response with a plain-text body.
+pub fn text_response(description: &str) -> Value {
+    json!({
+        "description": description,
+        "content": { "text/plain": { "schema": { "type": "string" } } },
+    })
+}
+
+/// Path or query parameter.
+pub fn param(location: &str, name: &str, schema: Value, description: &str) -> Value {
+    json!({
+        "in": location,
+        "name": name,
+        "required": location == "path",
+        "schema": schema,
+        "description": description,
+    })
+}
+
+/// Check `value` against `schema`, resolving `$ref`s in `spec`.
+///
+/// Supports the subset of JSON Schema the document uses.  Objects must
+/// not have properties their schema leaves out, so a schema that lags
+/// behind its serde type fails.
+#[cfg(test)]
+pub fn validate(spec: &Value, schema: &Value, value: &Value) -> Result<(), String> {
+    validate_at(spec, schema, value, "$")
+}
+
+#[cfg(test)]
+fn validate_at(spec: &Value, schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let name = reference.trim_start_matches("#/components/schemas/");
+        let target = &spec["components"]["schemas"][name];
+        if target.is_null() {
+            return Err(format!("{at}: unknown schema {reference}"));
+        }
+        return validate_at(spec, target, value, at);
+    }
+    if let Some(branches) = schema["oneOf"].as_array() {
+        let matching = branches
+            .iter()
+            .filter(|b| validate_at(spec, b, value, at).is_ok())
+            .count();
+        if matching != 1 {
+            return Err(format!("{at}: {value} matches {matching} oneOf branches"));
+        }
+    }
+    if let Some(constant) = schema.get("const")
+        && constant != value
+    {
+        return Err(format!("{at}: expected {constant}, got {value}"));
+    }
+    if let Some(allowed) = schema["enum"].as_array()
+        && !allowed.contains(value)
+    {
+        return Err(format!("{at}: {value} is not one of {allowed:?}"));
+    }
+    let types: Vec<&str> = match &schema["type"] {
+        Value::String(t) => vec![t.as_str()],
+        Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+        _ => vec![],
+    };
+    if !types.is_empty() && !types.iter().any(|t| has_type(value, t)) {
+        return Err(format!("{at}: {value} is not {types:?}"));
+    }
+    match value {
+        Value::Object(fields) => {
+            if let Some(properties) = schema["properties"].as_object() {
+                for (key, field) in fields {
+                    let Some(property) = properties.get(key) else {
+                        return Err(format!("{at}: undocumented property {key}"));
+                    };
+                    validate_at(spec, property, field, &format!("{at}.{key}"))?;
+                }
+            }
+            for key in schema["required"].as_array().into_iter().flatten() {
+                let key = key.as_str().unwrap_or_default();
+                if !fields.contains_key(key) {
+                    return Err(format!("{at}: missing required property {key}"));
+                }
+            }
+            if let Some(values) = schema.get("additionalProperties")
+                && values.is_object()
+            {
+                for (key, field) in fields {
+                    validate_at(spec, values, field, &format!("{at}.{key}"))?;
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema["minItems"].as_u64()
+                && (items.len() as u64) < min
+            {
+                return Err(format!("{at}: fewer than {min} items"));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(spec, item_schema, item, &format!("{at}[{i}]"))?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+fn has_type(value: &Value, t: &str) -> bool {
+    match t {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{Body, to_bytes};
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    /// Every `$ref` anywhere under `value`.
+    fn refs<'a>(value: &'a Value, out: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(r) = map.get("$ref").and_then(Value::as_str) {
+                    out.push(r);
+                }
+                map.values().for_each(|v| refs(v, out));
+            }
+            Value::Array(items) => items.iter().for_each(|v| refs(v, out)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn references_resolve() {
+        let spec = spec();
+        let mut found = Vec::new();
+        refs(&spec, &mut found);
+        assert!(!found.is_empty());
+        for r in found {
+            let name = r.trim_start_matches("#/components/schemas/");
+            assert!(
+                spec["components"]["schemas"].get(name).is_some(),
+                "dangling {r}"
+            );
+        }
+        for path in ["/v1/chat/completions", "/infill", "/api/config"] {
+            assert!(spec["paths"].get(path).is_some(), "{path} not described");
+        }
+    }
+
+    #[tokio::test]
+    async fn served_without_a_key() {
+        let state = AppState::for_tests(Default::default(), Some("s3cret"));
+        let app = crate::routes::app(state);
+        let req = Request::get("/openapi.json").body(Body::empty()).unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let served: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(served, spec());
+    }
+}
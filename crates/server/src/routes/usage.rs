@@ -0,0 +1,85 @@
+//! Usage API: GET /api/usage — aggregates of the request log.
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    routing::get,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+
+use crate::db::UsageGroupBy;
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/api/usage", get(usage))
+}
+
+#[derive(Debug, Deserialize)]
+struct UsageQuery {
+    /// RFC 3339 timestamp or `YYYY-MM-DD` (start of day), inclusive.
+    from: Option<String>,
+    /// RFC 3339 timestamp (exclusive) or `YYYY-MM-DD` (that whole day).
+    to: Option<String>,
+    #[serde(default)]
+    group_by: UsageGroupBy,
+}
+
+/// Parse a query bound; a bare date means midnight UTC, moved one day
+/// forward when `end_of_day` so that `to=2025-03-31` covers the 31st.
+pub(crate) fn parse_bound(value: &str, end_of_day: bool) -> Option<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Some(t.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let date = if end_of_day { date.succ_opt()? } else { date };
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+/// GET /api/usage?from=&to=&group_by=model|day
+async fn usage(
+    State(state): State<AppState>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
+    let bound = |value: &Option<String>, end_of_day| match value {
+        None => Ok(None),
+        Some(v) => parse_bound(v, end_of_day).map(Some).ok_or((
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("Invalid date '{v}': expected RFC 3339 or YYYY-MM-DD"),
+        )),
+    };
+    let from = bound(&query.from, false)?;
+    let to = bound(&query.to, true)?;
+
+    let rows = state
+        .db()
+        .usage(from, to, query.group_by)
+        .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(serde_json::json!({
+        "enabled": state.request_log().is_enabled(),
+        "group_by": query.group_by,
+        "from": from,
+        "to": to,
+        "rows": rows,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_dates_cover_whole_days() {
+        let from = parse_bound("2025-03-31", false).unwrap();
+        let to = parse_bound("2025-03-31", true).unwrap();
+        assert_eq!(from.to_rfc3339(), "2025-03-31T00:00:00+00:00");
+        assert_eq!(to.to_rfc3339(), "2025-04-01T00:00:00+00:00");
+        assert_eq!(
+            parse_bound("2025-03-31T12:00:00+02:00", true)
+                .unwrap()
+                .to_rfc3339(),
+            "2025-03-31T10:00:00+00:00"
+        );
+        assert!(parse_bound("yesterday", false).is_none());
+    }
+}
@@ -0,0 +1,307 @@
+//! Request validation shared by the OpenAI-compatible routes.
+//!
+//! Failures carry the offending parameter path (e.g. `messages[2].role`)
+//! so handlers can surface it in the OpenAI error envelope.
+
+/// A rejected request parameter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub message: String,
+    pub param: String,
+    pub code: Option<&'static str>,
+}
+
+impl ValidationError {
+    fn new(param: impl Into<String>, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            param: param.into(),
+            code: Some(code),
+        }
+    }
+}
+
+//  Chat messages
+
+/// A chat message as received from the client, before validation.
+#[derive(Debug, Clone)]
+pub struct RawMessage<'a> {
+    pub role: &'a str,
+    pub content: Option<String>,
+    pub has_tool_calls: bool,
+}
+
+const SUPPORTED_ROLES: &str = "'system', 'developer', 'user', 'assistant', 'tool', and 'function'";
+
+/// Map a client role onto the role name passed to chat templates.
+///
+/// `developer` is the newer OpenAI spelling of `system`; the legacy
+/// `function` role is folded into `tool`.
+pub fn normalize_role(role: &str) -> Option<&'static str> {
+    match role {
+        "system" | "developer" => Some("system"),
+        "user" => Some("user"),
+        "assistant" => Some("assistant"),
+        "tool" | "function" => Some("tool"),
+        _ => None,
+    }
+}
+
+/// Validate a chat conversation and convert it for templating.
+///
+/// Rejects empty conversations, unknown roles, and messages missing
+/// content.  Assistant messages may omit content when they carry tool
+/// calls.
+pub fn validate_messages(
+    messages: Vec<RawMessage<'_>>,
+) -> Result<Vec<llama_core::ChatMessage>, ValidationError> {
+    if messages.is_empty() {
+        return Err(ValidationError::new(
+            "messages",
+            "empty_array",
+            "'messages' must contain at least one message",
+        ));
+    }
+
+    messages
+        .into_iter()
+        .enumerate()
+        .map(|(i, m)| {
+            let role = normalize_role(m.role).ok_or_else(|| {
+                ValidationError::new(
+                    format!("messages[{i}].role"),
+                    "invalid_value",
+                    format!(
+                        "Invalid value: '{}'. Supported values are: {SUPPORTED_ROLES}.",
+                        m.role
+                    ),
+                )
+            })?;
+
+            let content = match m.content {
+                Some(c) => c,
+                None if role == "assistant" && m.has_tool_calls => String::new(),
+                None => {
+                    return Err(ValidationError::new(
+                        format!("messages[{i}].content"),
+                        "missing_required_parameter",
+                        format!("Missing required parameter: 'messages[{i}].content'."),
+                    ));
+                }
+            };
+
+            Ok(llama_core::ChatMessage {
+                role: role.to_string(),
+                content,
+            })
+        })
+        .collect()
+}
+
+//  Generation parameters
+
+/// Numeric parameters shared by chat and legacy completions.
+#[derive(Debug, Clone, Default)]
+pub struct GenerationParams {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub presence_penalty: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    pub n: Option<u32>,
+    pub max_tokens: Option<u32>,
+    pub max_completion_tokens: Option<u32>,
+}
+
+/// Check every supplied parameter against the ranges OpenAI accepts.
+pub fn validate_generation_params(p: &GenerationParams) -> Result<(), ValidationError> {
+    check_float("temperature", p.temperature, 0.0, 2.0)?;
+    check_float("top_p", p.top_p, 0.0, 1.0)?;
+    check_float("presence_penalty", p.presence_penalty, -2.0, 2.0)?;
+    check_float("frequency_penalty", p.frequency_penalty, -2.0, 2.0)?;
+    check_min_int("n", p.n, 1)?;
+    check_min_int("max_tokens", p.max_tokens, 1)?;
+    check_min_int("max_completion_tokens", p.max_completion_tokens, 1)?;
+    Ok(())
+}
+
+fn check_float(
+    param: &'static str,
+    value: Option<f32>,
+    min: f32,
+    max: f32,
+) -> Result<(), ValidationError> {
+    match value {
+        Some(v) if v.is_nan() || v < min => Err(ValidationError::new(
+            param,
+            "decimal_below_min_value",
+            format!("{v} is less than the minimum of {min} - '{param}'"),
+        )),
+        Some(v) if v > max => Err(ValidationError::new(
+            param,
+            "decimal_above_max_value",
+            format!("{v} is greater than the maximum of {max} - '{param}'"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn check_min_int(param: &'static str, value: Option<u32>, min: u32) -> Result<(), ValidationError> {
+    match value {
+        Some(v) if v < min => Err(ValidationError::new(
+            param,
+            "integer_below_min_value",
+            format!("{v} is less than the minimum of {min} - '{param}'"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg<'a>(role: &'a str, content: Option<&str>) -> RawMessage<'a> {
+        RawMessage {
+            role,
+            content: content.map(String::from),
+            has_tool_calls: false,
+        }
+    }
+
+    #[test]
+    fn maps_developer_and_function_roles() {
+        let out = validate_messages(vec![
+            msg("developer", Some("be brief")),
+            msg("user", Some("hi")),
+            msg("function", Some("{}")),
+        ])
+        .unwrap();
+        let roles: Vec<_> = out.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "user", "tool"]);
+    }
+
+    #[test]
+    fn rejects_empty_conversation() {
+        let err = validate_messages(vec![]).unwrap_err();
+        assert_eq!(err.param, "messages");
+        assert_eq!(err.code, Some("empty_array"));
+    }
+
+    #[test]
+    fn rejects_unknown_role_with_index() {
+        let err = validate_messages(vec![
+            msg("system", Some("x")),
+            msg("user", Some("y")),
+            msg("narrator", Some("z")),
+        ])
+        .unwrap_err();
+        assert_eq!(err.param, "messages[2].role");
+        assert_eq!(err.code, Some("invalid_value"));
+        assert!(err.message.contains("'narrator'"));
+    }
+
+    #[test]
+    fn requires_content_except_for_assistant_tool_calls() {
+        let err = validate_messages(vec![msg("user", None)]).unwrap_err();
+        assert_eq!(err.param, "messages[0].content");
+
+        let err =
+            validate_messages(vec![msg("user", Some("q")), msg("assistant", None)]).unwrap_err();
+        assert_eq!(err.param, "messages[1].content");
+
+        let out = validate_messages(vec![
+            msg("user", Some("q")),
+            RawMessage {
+                role: "assistant",
+                content: None,
+                has_tool_calls: true,
+            },
+        ])
+        .unwrap();
+        assert_eq!(out[1].content, "");
+    }
+
+    #[test]
+    fn empty_string_content_is_allowed() {
+        assert!(validate_messages(vec![msg("user", Some(""))]).is_ok());
+    }
+
+    #[test]
+    fn float_range_boundaries() {
+        let cases: &[(&str, f32, Option<&str>)] = &[
+            ("temperature", 0.0, None),
+            ("temperature", 2.0, None),
+            ("temperature", -0.01, Some("decimal_below_min_value")),
+            ("temperature", 2.01, Some("decimal_above_max_value")),
+            ("top_p", 0.0, None),
+            ("top_p", 1.0, None),
+            ("top_p", -0.1, Some("decimal_below_min_value")),
+            ("top_p", 7.0, Some("decimal_above_max_value")),
+            ("presence_penalty", -2.0, None),
+            ("presence_penalty", 2.0, None),
+            ("presence_penalty", -2.5, Some("decimal_below_min_value")),
+            ("presence_penalty", 2.5, Some("decimal_above_max_value")),
+            ("frequency_penalty", -2.0, None),
+            ("frequency_penalty", 2.0, None),
+            ("frequency_penalty", -3.0, Some("decimal_below_min_value")),
+            ("frequency_penalty", 3.0, Some("decimal_above_max_value")),
+            ("temperature", f32::NAN, Some("decimal_below_min_value")),
+        ];
+
+        for &(param, value, expected) in cases {
+            let mut p = GenerationParams::default();
+            match param {
+                "temperature" => p.temperature = Some(value),
+                "top_p" => p.top_p = Some(value),
+                "presence_penalty" => p.presence_penalty = Some(value),
+                "frequency_penalty" => p.frequency_penalty = Some(value),
+                _ => unreachable!(),
+            }
+            let result = validate_generation_params(&p);
+            match expected {
+                None => assert!(result.is_ok(), "{param}={value} should pass"),
+                Some(code) => {
+                    let err = result.unwrap_err();
+                    assert_eq!(err.param, param, "{param}={value}");
+                    assert_eq!(err.code, Some(code), "{param}={value}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn integer_minimum_boundaries() {
+        let cases: &[(&str, u32, bool)] = &[
+            ("n", 0, false),
+            ("n", 1, true),
+            ("max_tokens", 0, false),
+            ("max_tokens", 1, true),
+            ("max_tokens", 10_000_000, true),
+            ("max_completion_tokens", 0, false),
+            ("max_completion_tokens", 1, true),
+        ];
+
+        for &(param, value, ok) in cases {
+            let mut p = GenerationParams::default();
+            match param {
+                "n" => p.n = Some(value),
+                "max_tokens" => p.max_tokens = Some(value),
+                "max_completion_tokens" => p.max_completion_tokens = Some(value),
+                _ => unreachable!(),
+            }
+            let result = validate_generation_params(&p);
+            if ok {
+                assert!(result.is_ok(), "{param}={value} should pass");
+            } else {
+                let err = result.unwrap_err();
+                assert_eq!(err.param, param);
+                assert_eq!(err.code, Some("integer_below_min_value"));
+            }
+        }
+    }
+
+    #[test]
+    fn unset_params_pass() {
+        assert!(validate_generation_params(&GenerationParams::default()).is_ok());
+    }
+}
@@ -1,59 +1,933 @@
-//! WebSocket events: /ws/events
+//! WebSocket routes:
+//!   /ws/events    — real-time events (model state changes, system events)
+//!   /ws/generate  — chat generation with token streaming and cancellation
 //!
-//! Broadcasts real-time events (model state changes, system events) to
-//! connected WebSocket clients.
+//! Both need an `inference` key when auth is on, sent in a header or as
+//! `?api_key=`.  A `/ws/events` client without either is accepted but
+//! must first send `{"type":"auth","api_key":"..."}`, answered with an
+//! `auth.ok` event; a wrong or missing key closes the socket with 1008
+//! (policy violation).  `logs.line` events only go to `admin` keys.
+//!
+//! `/ws/events` clients get every event type unless they narrow it with
+//! `{"subscribe": ["model.*", "download.progress"]}`; `{"unsubscribe":
+//! [...]}` drops patterns again.  Each such message is answered with a
+//! `subscription` event listing the current filter.
+//!
+//! Broadcast events carry a `seq`.  A client that reconnects sends
+//! `{"since": <last seq seen>}` to have the events it missed replayed
+//! before live ones; if they are no longer kept it gets a
+//! `resync_required` event and should reload state over REST.
+//!
+//! On `/ws/generate` the client sends a chat completion request as JSON,
+//! optionally with an `id` that is echoed on every reply, and gets
+//! `{"type":"token"}` messages followed by `{"type":"done"}` with the
+//! usage and finish reason, or `{"type":"error"}`.  `{"type":"cancel"}`
+//! stops the running generation.  One generation runs at a time; more
+//! can follow on the same socket.
+
+use std::collections::BTreeSet;
+use std::time::Duration;
 
 use axum::{
-    Router,
+    Extension, Router,
+    body::to_bytes,
     extract::{
         State, WebSocketUpgrade,
-        ws::{Message, WebSocket},
+        ws::{CloseFrame, Message, WebSocket, close_code},
     },
-    response::IntoResponse,
+    http::StatusCode,
+    response::{IntoResponse, Response},
     routing::get,
 };
-use futures_util::{SinkExt, StreamExt};
-use tracing::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::{Instrument, info, warn};
 
-use crate::state::AppState;
+use crate::middleware::auth::{AdminScope, ApiKeyId, AuthPending, Denied, check_key, is_admin};
+use crate::middleware::request_id::{current as current_request_id, with_request_id};
+use crate::routes::error::ApiError;
+use crate::routes::openai::{ChatCompletionRequest, prepare_chat};
+use crate::services::api_keys::Scope;
+use crate::services::events::{Event, EventBus, event_message};
+use crate::services::inference::spawn_generation;
+use crate::state::{AppState, shutdown_requested};
 
 pub fn router() -> Router<AppState> {
     Router::new().route("/ws/events", get(ws_handler))
 }
 
-async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+/// Routes that need the `inference` scope.
+pub fn generate_router() -> Router<AppState> {
+    Router::new().route("/ws/generate", get(generate_handler))
 }
 
-async fn handle_socket(socket: WebSocket, state: AppState) {
-    let (mut sender, mut receiver) = socket.split();
-    let mut rx = state.subscribe_events();
+/// How long an `/ws/events` client that connected without a key has to
+/// send `{"type":"auth","api_key":"..."}`.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    pending: Option<Extension<AuthPending>>,
+    admin: Option<Extension<AdminScope>>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |mut socket| async move {
+        let mut admin = admin.is_some();
+        if pending.is_some() {
+            match first_message_auth(&mut socket, &state).await {
+                Ok(is_admin) => admin = is_admin,
+                Err(denied) => {
+                    warn!(
+                        "WebSocket client failed to authenticate: {}",
+                        denied.message()
+                    );
+                    let frame = CloseFrame {
+                        code: close_code::POLICY,
+                        reason: denied.message().into(),
+                    };
+                    let _ = socket.send(Message::Close(Some(frame))).await;
+                    return;
+                }
+            }
+            let ok = event_message("auth.ok", serde_json::json!({}));
+            if socket.send(Message::Text(ok.into())).await.is_err() {
+                return;
+            }
+        }
+        handle_socket(socket, state, admin).await
+    })
+}
+
+/// Wait for the `auth` message of a client that upgraded without a key;
+/// returns whether the key has the `admin` scope.
+async fn first_message_auth(socket: &mut WebSocket, state: &AppState) -> Result<bool, Denied> {
+    #[derive(Deserialize)]
+    struct Auth {
+        r#type: String,
+        api_key: String,
+    }
+    let text = match tokio::time::timeout(AUTH_TIMEOUT, socket.recv()).await {
+        Ok(Some(Ok(Message::Text(text)))) => text,
+        _ => return Err(Denied::Missing),
+    };
+    match serde_json::from_str::<Auth>(&text) {
+        Ok(auth) if auth.r#type == "auth" => {
+            check_key(state, Some(&auth.api_key), Scope::Inference)?;
+            Ok(is_admin(state, Some(&auth.api_key)))
+        }
+        _ => Err(Denied::Missing),
+    }
+}
+
+async fn handle_socket(mut socket: WebSocket, state: AppState, admin: bool) {
+    let (mut rx, subscribed_after) = state.events().subscribe();
+    let mut shutdown = state.shutdown_signal();
+    let mut client = EventClient {
+        admin,
+        subscribed_after,
+        ..Default::default()
+    };
 
     info!("WebSocket client connected");
 
-    // Forward broadcast events to WebSocket
-    let send_task = tokio::spawn(async move {
-        while let Ok(event) = rx.recv().await {
-            if sender.send(Message::Text(event.into())).await.is_err() {
+    loop {
+        tokio::select! {
+            event = rx.recv() => {
+                let Ok(event) = event else { break };
+                if let Some(message) = client.forward(event)
+                    && socket.send(Message::Text(message.into())).await.is_err()
+                {
+                    break;
+                }
+            }
+            msg = socket.recv() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    // Pings are answered by axum
+                    Some(Ok(_)) => continue,
+                };
+                for reply in client.handle(state.events(), &text) {
+                    if socket.send(Message::Text(reply.into())).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            _ = shutdown_requested(&mut shutdown) => {
+                // Deliver what's queued (e.g. `server.shutting_down`),
+                // then close so the connection doesn't hold up the drain.
+                while let Ok(event) = rx.try_recv() {
+                    if let Some(message) = client.forward(event)
+                        && socket.send(Message::Text(message.into())).await.is_err()
+                    {
+                        break;
+                    }
+                }
+                let _ = socket.send(Message::Close(None)).await;
                 break;
             }
         }
-    });
+    }
 
-    // Read messages from client (pings, close frames)
-    let recv_task = tokio::spawn(async move {
-        while let Some(Ok(msg)) = receiver.next().await {
-            if let Message::Close(_) = msg {
+    warn!("WebSocket client disconnected");
+}
+
+/// Per-connection state of an `/ws/events` client.
+#[derive(Debug, Default)]
+struct EventClient {
+    filter: EventFilter,
+    /// Highest `seq` forwarded, so live events that were also replayed
+    /// aren't sent twice.
+    last_seq: u64,
+    /// `seq` of the last event sent before the client subscribed; those
+    /// up to it only come by replay, even after later live ones.  Reset
+    /// once replayed.
+    subscribed_after: u64,
+    /// Whether admin-only events are forwarded.
+    admin: bool,
+}
+
+/// A message from an `/ws/events` client; all fields are optional.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClientMessage {
+    #[serde(default)]
+    subscribe: Vec<String>,
+    #[serde(default)]
+    unsubscribe: Vec<String>,
+    /// Replay the events after this `seq`.
+    since: Option<u64>,
+}
+
+impl EventClient {
+    /// The message to send for a broadcast `event`, if any.
+    fn forward(&mut self, event: Event) -> Option<String> {
+        if event.admin_only && !self.admin {
+            return None;
+        }
+        if let Some(seq) = event.seq {
+            if seq <= self.last_seq {
+                return None;
+            }
+            self.last_seq = seq;
+        }
+        self.filter
+            .wants(&event.event_type)
+            .then_some(event.message)
+    }
+
+    /// Act on a client message; returns the replies to send.
+    fn handle(&mut self, bus: &EventBus, text: &str) -> Vec<String> {
+        let message: ClientMessage = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(e) => {
+                let message =
+                    format!("Expected {{\"subscribe\"|\"unsubscribe\"|\"since\": ...}}: {e}");
+                return vec![event_message(
+                    "error",
+                    serde_json::json!({ "message": message }),
+                )];
+            }
+        };
+        let mut replies = Vec::new();
+        let changes_filter = !message.subscribe.is_empty() || !message.unsubscribe.is_empty();
+        if changes_filter || message.since.is_none() {
+            replies.push(self.filter.update(message.subscribe, message.unsubscribe));
+        }
+        if let Some(since) = message.since {
+            match bus.since(since) {
+                Some(missed) => {
+                    for event in missed {
+                        let seq = event.seq.unwrap_or_default();
+                        if seq > self.subscribed_after {
+                            replies.extend(self.forward(event));
+                        } else if self.filter.wants(&event.event_type) {
+                            self.last_seq = self.last_seq.max(seq);
+                            replies.push(event.message);
+                        }
+                    }
+                    self.subscribed_after = 0;
+                }
+                None => {
+                    // Missed events were dropped; the client must reload
+                    let data = serde_json::json!({ "seq": bus.latest_seq() });
+                    replies.push(event_message("resync_required", data));
+                }
+            }
+        }
+        replies
+    }
+}
+
+/// Which event types an `/ws/events` client receives.  Without any
+/// `subscribe` message that is every type; patterns may use `*`, e.g.
+/// `model.*`.
+#[derive(Debug, Default)]
+struct EventFilter {
+    /// `None` until the first `subscribe`: everything.
+    include: Option<BTreeSet<String>>,
+    exclude: BTreeSet<String>,
+}
+
+impl EventFilter {
+    fn wants(&self, event_type: &str) -> bool {
+        let matches =
+            |patterns: &BTreeSet<String>| patterns.iter().any(|p| glob_match(p, event_type));
+        self.include.as_ref().is_none_or(matches) && !matches(&self.exclude)
+    }
+
+    /// Add and drop patterns; returns a `subscription` event with the
+    /// resulting filter.
+    fn update(&mut self, subscribe: Vec<String>, unsubscribe: Vec<String>) -> String {
+        for pattern in subscribe {
+            self.exclude.remove(&pattern);
+            self.include.get_or_insert_default().insert(pattern);
+        }
+        for pattern in unsubscribe {
+            if let Some(include) = &mut self.include {
+                include.remove(&pattern);
+            }
+            self.exclude.insert(pattern);
+        }
+        let data = serde_json::json!({ "subscribe": self.include, "unsubscribe": self.exclude });
+        event_message("subscription", data)
+    }
+}
+
+/// Match `text` against `pattern`, where `*` stands for any run of
+/// characters.  On a mismatch only the last `*` is retried, one byte
+/// further along, so the cost is at most pattern × text length.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text) = (pattern.as_bytes(), text.as_bytes());
+    let (mut p, mut t) = (0, 0);
+    // Pattern position after the last `*`, and where in `text` it resumes.
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(&c) if c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, t));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+//  /ws/generate
+
+/// A generation request: a chat completion body plus a correlation id.
+#[derive(Deserialize)]
+struct GenerateMessage {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(flatten)]
+    request: ChatCompletionRequest,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Reply {
+    Token {
+        id: Option<String>,
+        content: String,
+    },
+    Done {
+        id: Option<String>,
+        /// `stop`, `length`, `timeout` or `cancelled`.
+        finish_reason: &'static str,
+        usage: Usage,
+    },
+    Error {
+        id: Option<String>,
+        /// The HTTP status the same failure gets on `/v1/chat/completions`.
+        status: u16,
+        /// OpenAI error object (`message`, `type`, `param`, `code`).
+        error: serde_json::Value,
+    },
+}
+
+#[derive(Serialize)]
+struct Usage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    total_tokens: u32,
+}
+
+impl Usage {
+    fn new(prompt_tokens: u32, completion_tokens: u32) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+}
+
+/// The generation a socket is currently streaming.
+struct Running {
+    id: Option<String>,
+    rx: mpsc::Receiver<llama_core::GenerateEvent>,
+    cancel: llama_core::CancelToken,
+    prompt_tokens: u32,
+    pieces: u32,
+}
+
+async fn generate_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    api_key: Option<Extension<ApiKeyId>>,
+) -> impl IntoResponse {
+    let api_key_id = api_key.map(|Extension(ApiKeyId(id))| id);
+    let socket = move |socket| {
+        with_request_id(
+            current_request_id(),
+            handle_generate_socket(socket, state, api_key_id).in_current_span(),
+        )
+    };
+    ws.on_upgrade(socket)
+}
+
+async fn handle_generate_socket(
+    mut socket: WebSocket,
+    state: AppState,
+    api_key_id: Option<String>,
+) {
+    let mut running: Option<Running> = None;
+    let mut shutdown = state.shutdown_signal();
+
+    loop {
+        let reply = tokio::select! {
+            msg = socket.recv() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+                match client_message(&state, &text, &mut running, api_key_id.clone()).await {
+                    Some(reply) => reply,
+                    None => continue,
+                }
+            }
+            event = next_event(&mut running) => match event {
+                Some(reply) => reply,
+                None => continue,
+            },
+            _ = shutdown_requested(&mut shutdown), if running.is_none() => {
+                let _ = socket.send(Message::Close(None)).await;
                 break;
             }
+        };
+        let text = serde_json::to_string(&reply).unwrap_or_default();
+        if socket.send(Message::Text(text.into())).await.is_err() {
+            break;
+        }
+    }
+
+    // The client is gone; don't leave its generation running.
+    if let Some(running) = running {
+        running.cancel.cancel();
+    }
+}
+
+/// Act on a message from the client; returns the reply to send, if any.
+async fn client_message(
+    state: &AppState,
+    text: &str,
+    running: &mut Option<Running>,
+    api_key_id: Option<String>,
+) -> Option<Reply> {
+    let value: serde_json::Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(e) => return Some(invalid_request(None, e.to_string(), None)),
+    };
+    let id = value["id"].as_str().map(String::from);
+
+    match value.get("type") {
+        None => {}
+        Some(t) if t == "generate" => {}
+        Some(t) if t == "cancel" => {
+            // A cancel naming another generation is stale; ignore it.
+            let current = running.take_if(|r| id.is_none() || id == r.id)?;
+            current.cancel.cancel();
+            return Some(Reply::Done {
+                id: current.id,
+                finish_reason: "cancelled",
+                usage: Usage::new(current.prompt_tokens, current.pieces),
+            });
         }
+        Some(_) => {
+            let message = "Unknown message type; expected 'generate' or 'cancel'";
+            return Some(invalid_request(id, message.into(), Some("type")));
+        }
+    }
+    if running.is_some() {
+        return Some(Reply::Error {
+            id,
+            status: StatusCode::CONFLICT.as_u16(),
+            error: serde_json::json!({
+                "message": "A generation is already running on this socket",
+                "type": "invalid_request_error",
+                "param": null,
+                "code": "generation_in_progress",
+            }),
+        });
+    }
+    let message: GenerateMessage = match serde_json::from_value(value) {
+        Ok(message) => message,
+        Err(e) => return Some(invalid_request(id, e.to_string(), None)),
+    };
+
+    let mut chat = match prepare_chat(state, message.request, "/ws/generate", api_key_id).await {
+        Ok(chat) => chat,
+        Err(response) => return Some(error_reply(message.id, response).await),
+    };
+    let cancel = state.generation_cancel().child();
+    chat.meta.cancel = Some(cancel.clone());
+    let prompt_tokens = chat.request.tokens.len() as u32;
+    let rx = spawn_generation(state, chat.loaded, chat.request, chat.meta);
+    *running = Some(Running {
+        id: message.id,
+        rx,
+        cancel,
+        prompt_tokens,
+        pieces: 0,
     });
+    None
+}
+
+/// The reply for the next event of the running generation.  Pending
+/// while nothing is running; `None` for events that need no reply.
+async fn next_event(running: &mut Option<Running>) -> Option<Reply> {
+    let Some(current) = running else {
+        return std::future::pending().await;
+    };
+    let id = current.id.clone();
+    let reply = match current.rx.recv().await {
+        Some(llama_core::GenerateEvent::Token(content)) => {
+            current.pieces += 1;
+            return Some(Reply::Token { id, content });
+        }
+        Some(llama_core::GenerateEvent::Done {
+            finish_reason,
+            prompt_tokens,
+            completion_tokens,
+        }) => Reply::Done {
+            id,
+            finish_reason: match finish_reason {
+                llama_core::FinishReason::Stop | llama_core::FinishReason::StopWord(_) => "stop",
+                llama_core::FinishReason::Length => "length",
+                llama_core::FinishReason::Timeout => "timeout",
+            },
+            usage: Usage::new(prompt_tokens, completion_tokens),
+        },
+        Some(llama_core::GenerateEvent::Error(e)) => Reply::Error {
+            id,
+            status: e.status,
+            error: serde_json::to_value(ApiError::from(&e).detail()).unwrap_or_default(),
+        },
+        None => {
+            *running = None;
+            return None;
+        }
+    };
+    *running = None;
+    Some(reply)
+}
+
+fn invalid_request(id: Option<String>, message: String, param: Option<&str>) -> Reply {
+    Reply::Error {
+        id,
+        status: StatusCode::BAD_REQUEST.as_u16(),
+        error: serde_json::json!({
+            "message": message,
+            "type": "invalid_request_error",
+            "param": param,
+            "code": null,
+        }),
+    }
+}
+
+/// Turn an OpenAI error response from [`prepare_chat`] into a reply.
+async fn error_reply(id: Option<String>, response: Response) -> Reply {
+    let status = response.status().as_u16();
+    let body = to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap_or_default();
+    let error = serde_json::from_slice::<serde_json::Value>(&body)
+        .map(|mut body| body["error"].take())
+        .unwrap_or_default();
+    Reply::Error { id, status, error }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::IntoFuture;
 
-    // Wait for either task to finish
-    tokio::select! {
-        _ = send_task => {},
-        _ = recv_task => {},
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::{Value, json};
+    use tokio_tungstenite::tungstenite;
+
+    use super::*;
+    use crate::services::events::REPLAY_CAPACITY;
+
+    async fn send(state: &AppState, running: &mut Option<Running>, msg: Value) -> Option<Value> {
+        let reply = client_message(state, &msg.to_string(), running, None).await?;
+        Some(serde_json::to_value(reply).unwrap())
     }
 
-    warn!("WebSocket client disconnected");
+    /// A generation fed by the returned sender instead of a model.
+    fn fake_running(id: &str) -> (Running, mpsc::Sender<llama_core::GenerateEvent>) {
+        let (tx, rx) = mpsc::channel(8);
+        let running = Running {
+            id: Some(id.into()),
+            rx,
+            cancel: llama_core::CancelToken::new(),
+            prompt_tokens: 5,
+            pieces: 0,
+        };
+        (running, tx)
+    }
+
+    type Client = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    /// Serve `state` and connect to its `/ws/events` without a key.
+    async fn connect_events(state: AppState) -> Client {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, crate::routes::app(state)).into_future());
+        let url = format!("ws://{addr}/ws/events");
+        tokio_tungstenite::connect_async(url).await.unwrap().0
+    }
+
+    async fn next_json(socket: &mut Client) -> Value {
+        let message = socket.next().await.unwrap().unwrap();
+        serde_json::from_str(message.to_text().unwrap()).unwrap()
+    }
+
+    /// Connect to `/ws/events` without a key, send `first` and return
+    /// the server's first frame.
+    async fn events_reply(first: Option<Value>) -> tungstenite::Message {
+        let state = AppState::for_tests(Default::default(), Some("s3cret"));
+        let mut socket = connect_events(state).await;
+        if let Some(first) = first {
+            let text = first.to_string();
+            socket
+                .send(tungstenite::Message::Text(text.into()))
+                .await
+                .unwrap();
+        }
+        socket.next().await.unwrap().unwrap()
+    }
+
+    fn close_code(message: tungstenite::Message) -> Option<u16> {
+        match message {
+            tungstenite::Message::Close(frame) => frame.map(|f| f.code.into()),
+            other => panic!("expected a close frame, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn events_authenticate_with_the_first_message() {
+        let auth = |key| json!({ "type": "auth", "api_key": key });
+        let reply = events_reply(Some(auth("s3cret"))).await;
+        let reply: Value = serde_json::from_str(reply.to_text().unwrap()).unwrap();
+        assert_eq!(reply["type"], "auth.ok");
+
+        assert_eq!(
+            close_code(events_reply(Some(auth("nope"))).await),
+            Some(1008)
+        );
+        let other = json!({ "type": "subscribe" });
+        assert_eq!(close_code(events_reply(Some(other)).await), Some(1008));
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_match("model.*", "model.loaded"));
+        assert!(glob_match("*", "system.metrics"));
+        assert!(glob_match("*.progress", "download.progress"));
+        assert!(glob_match("download.progress", "download.progress"));
+        assert!(!glob_match("model.*", "download.progress"));
+        assert!(!glob_match("model", "model.loaded"));
+        assert!(glob_match("m*.*ed", "model.unloaded"));
+        assert!(glob_match("model.**", "model."));
+        assert!(!glob_match("*.loaded", "model.unloaded."));
+
+        // Many stars against a long near-miss stay fast.
+        let pattern = "*a".repeat(64) + "b";
+        assert!(!glob_match(&pattern, &"a".repeat(4096)));
+    }
+
+    fn parse(message: &str) -> Value {
+        serde_json::from_str(message).unwrap()
+    }
+
+    #[test]
+    fn filter_subscriptions() {
+        let bus = EventBus::new();
+        let mut client = EventClient::default();
+        assert!(client.filter.wants("system.metrics"));
+
+        client.handle(&bus, r#"{"unsubscribe": ["system.*"]}"#);
+        assert!(!client.filter.wants("system.metrics"));
+        assert!(client.filter.wants("model.loaded"));
+
+        let replies = client.handle(&bus, r#"{"subscribe": ["model.*", "download.progress"]}"#);
+        let reply = parse(&replies[0]);
+        assert_eq!(reply["type"], "subscription");
+        assert_eq!(
+            reply["data"]["subscribe"],
+            json!(["download.progress", "model.*"])
+        );
+        assert!(client.filter.wants("model.loaded"));
+        assert!(client.filter.wants("download.progress"));
+        assert!(!client.filter.wants("download.completed"));
+        assert!(!client.filter.wants("system.metrics"));
+
+        client.handle(&bus, r#"{"unsubscribe": ["model.*"]}"#);
+        assert!(!client.filter.wants("model.loaded"));
+        client.handle(&bus, r#"{"subscribe": ["model.*"]}"#);
+        assert!(client.filter.wants("model.loaded"));
+
+        let replies = client.handle(&bus, r#"{"subscribe": "model.*"}"#);
+        assert_eq!(parse(&replies[0])["type"], "error");
+    }
+
+    #[test]
+    fn replays_missed_events_once() {
+        let bus = EventBus::new();
+        let (mut rx, _) = bus.subscribe();
+        for event_type in ["model.loaded", "system.metrics", "model.unloaded"] {
+            bus.send(event_type, json!({}));
+        }
+
+        // A client that last saw seq 1 reconnects while 2 and 3 are also
+        // queued live for it
+        let mut client = EventClient::default();
+        let replies = client.handle(&bus, r#"{"since": 1, "unsubscribe": ["system.*"]}"#);
+        let replies: Vec<Value> = replies.iter().map(|r| parse(r)).collect();
+        assert_eq!(replies[0]["type"], "subscription");
+        assert_eq!(replies[1]["type"], "model.unloaded");
+        assert_eq!(replies[1]["seq"], 3);
+        assert_eq!(replies.len(), 2);
+        while let Ok(event) = rx.try_recv() {
+            assert_eq!(client.forward(event), None);
+        }
+        let seq = bus.send("model.loaded", json!({}));
+        let live = client.forward(rx.try_recv().unwrap()).unwrap();
+        assert_eq!(parse(&live)["seq"], seq);
+
+        for _ in 0..REPLAY_CAPACITY {
+            bus.send("system.metrics", json!({}));
+        }
+        let replies = EventClient::default().handle(&bus, r#"{"since": 1}"#);
+        let reply = parse(&replies[0]);
+        assert_eq!(reply["type"], "resync_required");
+        assert_eq!(reply["data"]["seq"], bus.latest_seq());
+        assert_eq!(replies.len(), 1);
+    }
+
+    #[test]
+    fn replay_covers_events_before_live_ones() {
+        let bus = EventBus::new();
+        for event_type in ["model.loaded", "model.unloaded"] {
+            bus.send(event_type, json!({}));
+        }
+        // Reconnected after seq 1, then sent live 3 before `since`
+        let (mut rx, subscribed_after) = bus.subscribe();
+        let mut client = EventClient {
+            subscribed_after,
+            ..Default::default()
+        };
+        bus.send("download.progress", json!({}));
+        bus.send("download.completed", json!({}));
+        let live = client.forward(rx.try_recv().unwrap()).unwrap();
+        assert_eq!(parse(&live)["seq"], 3);
+
+        let replies = client.handle(&bus, r#"{"since": 1}"#);
+        let seqs: Vec<Value> = replies.iter().map(|r| parse(r)["seq"].clone()).collect();
+        assert_eq!(seqs, [json!(2), json!(4)]);
+        assert_eq!(client.forward(rx.try_recv().unwrap()), None);
+        assert!(client.handle(&bus, r#"{"since": 1}"#).is_empty());
+    }
+
+    #[tokio::test]
+    async fn subscribed_clients_only_get_matching_events() {
+        let state = AppState::for_tests(Default::default(), None);
+        let mut socket = connect_events(state.clone()).await;
+        let subscribe = json!({ "subscribe": ["download.*"] }).to_string();
+        socket
+            .send(tungstenite::Message::Text(subscribe.into()))
+            .await
+            .unwrap();
+        assert_eq!(next_json(&mut socket).await["type"], "subscription");
+
+        state.broadcast_event("system.metrics", json!({}));
+        state.broadcast_event("download.progress", json!({ "id": "d1" }));
+        let event = next_json(&mut socket).await;
+        assert_eq!(event["type"], "download.progress");
+        assert_eq!(event["data"]["id"], "d1");
+    }
+
+    #[tokio::test]
+    async fn log_lines_only_reach_admin_clients() {
+        let state = AppState::for_tests(Default::default(), Some("s3cret"));
+        state.api_keys().insert(
+            crate::services::api_keys::hash_key("sk-lld-ci"),
+            crate::services::api_keys::ActiveKey {
+                id: "ci".into(),
+                scopes: vec![Scope::Inference],
+            },
+        );
+        let mut sockets = Vec::new();
+        for key in ["s3cret", "sk-lld-ci"] {
+            let mut socket = connect_events(state.clone()).await;
+            for message in [json!({ "type": "auth", "api_key": key }), json!({})] {
+                socket
+                    .send(tungstenite::Message::Text(message.to_string().into()))
+                    .await
+                    .unwrap();
+                next_json(&mut socket).await;
+            }
+            sockets.push(socket);
+        }
+
+        state
+            .events()
+            .send_admin_only("logs.line", json!({ "message": "hi" }));
+        state.broadcast_event("model.loaded", json!({}));
+        let [admin, inference] = &mut sockets[..] else {
+            unreachable!()
+        };
+        let line = next_json(admin).await;
+        assert_eq!(line["type"], "logs.line");
+        assert!(line.get("seq").is_none());
+        assert_eq!(next_json(admin).await["type"], "model.loaded");
+        assert_eq!(next_json(inference).await["type"], "model.loaded");
+    }
+
+    #[tokio::test]
+    async fn rejected_messages_get_error_replies() {
+        let state = AppState::for_tests(Default::default(), None);
+        let mut running = None;
+        let hi = json!([{ "role": "user", "content": "hi" }]);
+
+        let reply = client_message(&state, "{", &mut running, None).await;
+        let reply = serde_json::to_value(reply.unwrap()).unwrap();
+        assert_eq!(reply["type"], "error");
+        assert_eq!(reply["id"], Value::Null);
+        assert_eq!(reply["status"], 400);
+
+        let reply = send(&state, &mut running, json!({ "id": "b", "type": "bogus" })).await;
+        let reply = reply.unwrap();
+        assert_eq!(reply["id"], "b");
+        assert_eq!(reply["status"], 400);
+        assert_eq!(reply["error"]["param"], "type");
+
+        let reply = send(&state, &mut running, json!({ "id": "c", "messages": [] })).await;
+        assert_eq!(reply.unwrap()["status"], 400);
+
+        // Same status and error object as /v1/chat/completions
+        let reply = send(&state, &mut running, json!({ "id": "a", "messages": hi })).await;
+        let reply = reply.unwrap();
+        assert_eq!(reply["id"], "a");
+        assert_eq!(reply["status"], 503);
+        assert!(reply["error"]["message"].is_string());
+        assert!(running.is_none());
+
+        // Nothing is running, so there is nothing to cancel
+        let reply = send(&state, &mut running, json!({ "type": "cancel", "id": "a" })).await;
+        assert!(reply.is_none());
+    }
+
+    #[tokio::test]
+    async fn streams_tokens_then_done() {
+        let (current, tx) = fake_running("a");
+        let mut running = Some(current);
+        tx.send(llama_core::GenerateEvent::Token("Hel".into()))
+            .await
+            .unwrap();
+        tx.send(llama_core::GenerateEvent::Done {
+            finish_reason: llama_core::FinishReason::StopWord("\n".into()),
+            prompt_tokens: 5,
+            completion_tokens: 1,
+        })
+        .await
+        .unwrap();
+
+        let token = serde_json::to_value(next_event(&mut running).await).unwrap();
+        assert_eq!(
+            token,
+            json!({ "type": "token", "id": "a", "content": "Hel" })
+        );
+        let done = serde_json::to_value(next_event(&mut running).await).unwrap();
+        assert_eq!(done["type"], "done");
+        assert_eq!(done["finish_reason"], "stop");
+        assert_eq!(done["usage"]["total_tokens"], 6);
+        assert!(running.is_none());
+    }
+
+    #[tokio::test]
+    async fn generation_errors_keep_their_status_and_code() {
+        let (current, tx) = fake_running("a");
+        let mut running = Some(current);
+        let err = llama_core::LlamaError::KvCacheFull {
+            used: 512,
+            capacity: 512,
+        };
+        tx.send(llama_core::GenerateEvent::Error(
+            llama_core::GenerateError::llama("decode", &err),
+        ))
+        .await
+        .unwrap();
+
+        let reply = serde_json::to_value(next_event(&mut running).await).unwrap();
+        assert_eq!(reply["type"], "error");
+        assert_eq!(reply["status"], 503);
+        assert_eq!(reply["error"]["code"], "kv_cache_full");
+        assert!(running.is_none());
+    }
+
+    #[tokio::test]
+    async fn cancel_stops_the_matching_generation() {
+        let state = AppState::for_tests(Default::default(), None);
+        let (mut current, tx) = fake_running("a");
+        current.pieces = 2;
+        let cancel = current.cancel.clone();
+        let mut running = Some(current);
+
+        // Busy: a second request is refused, a stale cancel ignored
+        let hi = json!([{ "role": "user", "content": "hi" }]);
+        let reply = send(&state, &mut running, json!({ "id": "b", "messages": hi })).await;
+        let reply = reply.unwrap();
+        assert_eq!(reply["status"], 409);
+        assert_eq!(reply["error"]["code"], "generation_in_progress");
+        let reply = send(&state, &mut running, json!({ "type": "cancel", "id": "z" })).await;
+        assert!(reply.is_none());
+        assert!(!cancel.is_cancelled());
+
+        let reply = send(&state, &mut running, json!({ "type": "cancel" })).await;
+        let reply = reply.unwrap();
+        assert_eq!(reply["type"], "done");
+        assert_eq!(reply["id"], "a");
+        assert_eq!(reply["finish_reason"], "cancelled");
+        assert_eq!(reply["usage"]["completion_tokens"], 2);
+        assert!(cancel.is_cancelled());
+        assert!(running.is_none());
+        drop(tx);
+    }
 }
@@ -0,0 +1,177 @@
+//! API keys with scopes.
+//!
+//! Keys live in the `api_keys` table as SHA-1 hashes; the plaintext is
+//! only returned once, when the key is created.  The registry keeps the
+//! active (non-revoked) keys in memory so authentication doesn't hit
+//! SQLite, and throttles `last_used_at` updates.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+/// Minimum time between two `last_used_at` writes for the same key.
+const TOUCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What a key may access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// OpenAI-compatible and native inference routes.
+    Inference,
+    /// Management, config and dashboard routes; implies `inference`.
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Inference => "inference",
+            Self::Admin => "admin",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "inference" => Some(Self::Inference),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `scopes` grant access to routes requiring `required`.
+pub fn allows(scopes: &[Scope], required: Scope) -> bool {
+    scopes.contains(&Scope::Admin) || scopes.contains(&required)
+}
+
+/// A new random key, e.g. `sk-lld-3f2a…` (64 hex digits).
+pub fn generate_key() -> String {
+    format!(
+        "sk-lld-{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// Hex SHA-1 of `key`, as stored in `api_keys.key_hash`.
+pub fn hash_key(key: &str) -> String {
+    Sha1::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// The start of `key` shown in listings so users can tell keys apart.
+pub fn key_prefix(key: &str) -> String {
+    key.chars().take(12).collect()
+}
+
+/// An active key as seen by the auth middleware.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveKey {
+    pub id: String,
+    pub scopes: Vec<Scope>,
+}
+
+/// In-memory index of active keys by hash.
+#[derive(Clone, Default)]
+pub struct ApiKeyRegistry {
+    by_hash: Arc<RwLock<HashMap<String, ActiveKey>>>,
+    last_touch: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl ApiKeyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace all keys with `(hash, key)` pairs (used at startup).
+    pub fn set_all(&self, keys: impl IntoIterator<Item = (String, ActiveKey)>) {
+        *self.by_hash.write().unwrap() = keys.into_iter().collect();
+    }
+
+    pub fn insert(&self, hash: String, key: ActiveKey) {
+        self.by_hash.write().unwrap().insert(hash, key);
+    }
+
+    /// Forget the key with `id`.  Returns whether it was active.
+    pub fn remove(&self, id: &str) -> bool {
+        let mut by_hash = self.by_hash.write().unwrap();
+        let before = by_hash.len();
+        by_hash.retain(|_, k| k.id != id);
+        self.last_touch.lock().unwrap().remove(id);
+        by_hash.len() != before
+    }
+
+    /// Whether any key is active, i.e. auth is required.
+    pub fn has_keys(&self) -> bool {
+        !self.by_hash.read().unwrap().is_empty()
+    }
+
+    /// Whether `id` is the only active key.
+    pub fn is_last(&self, id: &str) -> bool {
+        let by_hash = self.by_hash.read().unwrap();
+        by_hash.len() == 1 && by_hash.values().all(|k| k.id == id)
+    }
+
+    /// The active key whose plaintext is `key`.
+    pub fn authenticate(&self, key: &str) -> Option<ActiveKey> {
+        self.by_hash.read().unwrap().get(&hash_key(key)).cloned()
+    }
+
+    /// Whether `id`'s `last_used_at` is due for an update; at most once
+    /// per [`TOUCH_INTERVAL`].
+    pub fn should_touch(&self, id: &str) -> bool {
+        let now = Instant::now();
+        let mut last = self.last_touch.lock().unwrap();
+        match last.get(id) {
+            Some(t) if now.duration_since(*t) < TOUCH_INTERVAL => false,
+            _ => {
+                last.insert(id.to_string(), now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_authenticates_by_hash_until_removed() {
+        let reg = ApiKeyRegistry::new();
+        assert!(!reg.has_keys());
+
+        let key = generate_key();
+        assert!(key.starts_with("sk-lld-") && key.len() == 71);
+        let active = ActiveKey {
+            id: "k1".into(),
+            scopes: vec![Scope::Inference],
+        };
+        reg.insert(hash_key(&key), active.clone());
+        assert!(reg.has_keys());
+        assert_eq!(reg.authenticate(&key), Some(active));
+        assert_eq!(reg.authenticate("sk-lld-guess"), None);
+
+        assert!(reg.is_last("k1") && !reg.is_last("k2"));
+
+        assert!(reg.should_touch("k1"));
+        assert!(!reg.should_touch("k1"));
+
+        assert!(reg.remove("k1"));
+        assert!(!reg.remove("k1"));
+        assert_eq!(reg.authenticate(&key), None);
+    }
+
+    #[test]
+    fn admin_implies_inference() {
+        assert!(allows(&[Scope::Admin], Scope::Inference));
+        assert!(allows(&[Scope::Inference], Scope::Inference));
+        assert!(!allows(&[Scope::Inference], Scope::Admin));
+        assert!(!allows(&[], Scope::Inference));
+    }
+}
@@ -0,0 +1,524 @@
+//! Chat session export and import.
+//!
+//! The JSON format ([`ExportedChat`]) carries everything stored for a
+//! session except row ids, so export → import is lossless.  Exports are
+//! produced as a stream of chunks, reading messages a page at a time.
+//! Imports also accept ChatGPT's `conversations.json` shape.
+
+use std::collections::HashMap;
+
+use futures_util::Stream;
+use serde::{Deserialize, Serialize};
+
+use crate::db::{ChatMessageRecord, ChatSession, DbError, SQLITE_TIME_FORMAT};
+
+/// `format` tag of [`ExportedChat`].
+pub const EXPORT_FORMAT: &str = "llama-dashboard.chat";
+pub const EXPORT_VERSION: u32 = 1;
+/// Messages read per database query while exporting.
+const PAGE_SIZE: i64 = 256;
+
+/// A portable chat session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedChat {
+    pub format: String,
+    pub version: u32,
+    pub session: ExportedSession,
+    pub messages: Vec<ExportedMessage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedSession {
+    pub title: String,
+    pub model_id: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedMessage {
+    pub role: String,
+    pub content: String,
+    pub model_id: Option<String>,
+    #[serde(default)]
+    pub tokens: Option<u32>,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+    pub created_at: String,
+}
+
+impl From<&ChatSession> for ExportedSession {
+    fn from(s: &ChatSession) -> Self {
+        Self {
+            title: s.title.clone(),
+            model_id: s.model_id.clone(),
+            created_at: s.created_at.clone(),
+            updated_at: s.updated_at.clone(),
+        }
+    }
+}
+
+impl From<ChatMessageRecord> for ExportedMessage {
+    fn from(m: ChatMessageRecord) -> Self {
+        Self {
+            role: m.role,
+            content: m.content,
+            model_id: m.model_id,
+            tokens: m.tokens,
+            finish_reason: m.finish_reason,
+            created_at: m.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Markdown,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Markdown => "text/markdown; charset=utf-8",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Markdown => "md",
+        }
+    }
+}
+
+enum Stage {
+    Header,
+    Messages { after_id: i64, first: bool },
+    Footer,
+    End,
+}
+
+/// Stream `session` in `format`.  `fetch_page(after_id, limit)` returns
+/// the next messages in id order (see
+/// [`Database::chat_messages_after`](crate::db::Database::chat_messages_after)).
+pub fn export_stream<F>(
+    session: ChatSession,
+    format: ExportFormat,
+    fetch_page: F,
+) -> impl Stream<Item = Result<String, DbError>> + Send + 'static
+where
+    F: FnMut(i64, i64) -> Result<Vec<ChatMessageRecord>, DbError> + Send + 'static,
+{
+    futures_util::stream::unfold(
+        (Stage::Header, fetch_page),
+        move |(stage, mut fetch_page)| {
+            let session = session.clone();
+            async move {
+                let (chunk, next) = match stage {
+                    Stage::Header => (
+                        Ok(header(&session, format)),
+                        Stage::Messages {
+                            after_id: 0,
+                            first: true,
+                        },
+                    ),
+                    Stage::Messages { after_id, first } => match fetch_page(after_id, PAGE_SIZE) {
+                        Ok(page) if page.is_empty() => (Ok(String::new()), Stage::Footer),
+                        Ok(page) => {
+                            let last_id = page.last().map_or(after_id, |m| m.id);
+                            let chunk = render_page(page, format, first);
+                            let next = Stage::Messages {
+                                after_id: last_id,
+                                first: false,
+                            };
+                            (Ok(chunk), next)
+                        }
+                        Err(e) => (Err(e), Stage::End),
+                    },
+                    Stage::Footer => (Ok(footer(format).to_string()), Stage::End),
+                    Stage::End => return None,
+                };
+                Some((chunk, (next, fetch_page)))
+            }
+        },
+    )
+}
+
+fn header(session: &ChatSession, format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Json => {
+            let session =
+                serde_json::to_string(&ExportedSession::from(session)).unwrap_or_default();
+            format!(
+                "{{\"format\":\"{EXPORT_FORMAT}\",\"version\":{EXPORT_VERSION},\
+                 \"session\":{session},\"messages\":["
+            )
+        }
+        ExportFormat::Markdown => {
+            let mut out = format!("# {}\n\n", session.title);
+            if let Some(model) = &session.model_id {
+                out.push_str(&format!("- Model: `{model}`\n"));
+            }
+            out.push_str(&format!(
+                "- Created: {}\n- Updated: {}\n\n",
+                session.created_at, session.updated_at
+            ));
+            out
+        }
+    }
+}
+
+fn render_page(page: Vec<ChatMessageRecord>, format: ExportFormat, first: bool) -> String {
+    let mut out = String::new();
+    for (i, m) in page.into_iter().enumerate() {
+        match format {
+            ExportFormat::Json => {
+                if !(first && i == 0) {
+                    out.push(',');
+                }
+                let m = ExportedMessage::from(m);
+                out.push_str(&serde_json::to_string(&m).unwrap_or_default());
+            }
+            ExportFormat::Markdown => {
+                out.push_str(&format!("## {} · {}", m.role, m.created_at));
+                if m.role == "assistant"
+                    && let Some(model) = &m.model_id
+                {
+                    out.push_str(&format!(" · `{model}`"));
+                }
+                out.push_str(&format!("\n\n{}\n\n", m.content.trim_end()));
+            }
+        }
+    }
+    out
+}
+
+fn footer(format: ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::Json => "]}",
+        ExportFormat::Markdown => "",
+    }
+}
+
+//  Import
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error(
+        "Unrecognised import format: expected a {EXPORT_FORMAT} export or a ChatGPT conversations export"
+    )]
+    UnknownFormat,
+
+    #[error("Unsupported {EXPORT_FORMAT} version {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("Nothing to import")]
+    Empty,
+}
+
+/// One conversation of a ChatGPT `conversations.json` export.
+#[derive(Debug, Deserialize)]
+struct OpenAiConversation {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    create_time: Option<f64>,
+    #[serde(default)]
+    update_time: Option<f64>,
+    mapping: HashMap<String, OpenAiNode>,
+    #[serde(default)]
+    current_node: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiNode {
+    #[serde(default)]
+    message: Option<OpenAiMessage>,
+    #[serde(default)]
+    parent: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiMessage {
+    author: OpenAiAuthor,
+    #[serde(default)]
+    content: Option<OpenAiContent>,
+    #[serde(default)]
+    create_time: Option<f64>,
+    #[serde(default)]
+    metadata: Option<OpenAiMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiAuthor {
+    role: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiContent {
+    #[serde(default)]
+    parts: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiMetadata {
+    #[serde(default)]
+    model_slug: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ImportPayload {
+    Native(ExportedChat),
+    NativeMany(Vec<ExportedChat>),
+    OpenAi(OpenAiConversation),
+    OpenAiMany(Vec<OpenAiConversation>),
+}
+
+/// Parse an import body into sessions to create.
+pub fn parse_import(body: serde_json::Value) -> Result<Vec<ExportedChat>, ImportError> {
+    let payload: ImportPayload =
+        serde_json::from_value(body).map_err(|_| ImportError::UnknownFormat)?;
+    let chats = match payload {
+        ImportPayload::Native(chat) => vec![chat],
+        ImportPayload::NativeMany(chats) => chats,
+        ImportPayload::OpenAi(conv) => vec![from_openai(conv)],
+        ImportPayload::OpenAiMany(convs) => convs.into_iter().map(from_openai).collect(),
+    };
+    if chats.is_empty() {
+        return Err(ImportError::Empty);
+    }
+    for chat in &chats {
+        if chat.format != EXPORT_FORMAT {
+            return Err(ImportError::UnknownFormat);
+        }
+        if chat.version > EXPORT_VERSION {
+            return Err(ImportError::UnsupportedVersion(chat.version));
+        }
+    }
+    Ok(chats)
+}
+
+/// SQLite `datetime()` text for a Unix timestamp (now when absent).
+fn sqlite_time(unix: Option<f64>) -> String {
+    unix.and_then(|t| chrono::DateTime::from_timestamp(t as i64, 0))
+        .unwrap_or_else(chrono::Utc::now)
+        .format(SQLITE_TIME_FORMAT)
+        .to_string()
+}
+
+/// Follow the conversation's active branch from `current_node` back to
+/// the root, keeping user, assistant and system text messages.
+fn from_openai(conv: OpenAiConversation) -> ExportedChat {
+    let mut chain = Vec::new();
+    let mut node_id = conv.current_node.clone();
+    while let Some(id) = node_id {
+        let Some(node) = conv.mapping.get(&id) else {
+            break;
+        };
+        if let Some(msg) = &node.message {
+            chain.push(msg);
+        }
+        node_id = node.parent.clone();
+        if chain.len() > conv.mapping.len() {
+            break; // cycle guard
+        }
+    }
+    chain.reverse();
+
+    let messages: Vec<ExportedMessage> = chain
+        .into_iter()
+        .filter(|m| matches!(m.author.role.as_str(), "user" | "assistant" | "system"))
+        .filter_map(|m| {
+            let text = m
+                .content
+                .as_ref()?
+                .parts
+                .iter()
+                .filter_map(|p| p.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            if text.trim().is_empty() {
+                return None;
+            }
+            Some(ExportedMessage {
+                role: m.author.role.clone(),
+                content: text,
+                model_id: m.metadata.as_ref().and_then(|md| md.model_slug.clone()),
+                tokens: None,
+                finish_reason: None,
+                created_at: sqlite_time(m.create_time.or(conv.create_time)),
+            })
+        })
+        .collect();
+
+    let model_id = messages.iter().rev().find_map(|m| m.model_id.clone());
+    ExportedChat {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        session: ExportedSession {
+            title: conv
+                .title
+                .filter(|t| !t.trim().is_empty())
+                .unwrap_or_else(|| "Imported chat".to_string()),
+            model_id,
+            created_at: sqlite_time(conv.create_time),
+            updated_at: sqlite_time(conv.update_time.or(conv.create_time)),
+        },
+        messages,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures_util::TryStreamExt;
+
+    use super::*;
+    use crate::db::Database;
+
+    async fn export(db: &Arc<Database>, session: &ChatSession, format: ExportFormat) -> String {
+        let fetch_db = db.clone();
+        let id = session.id;
+        export_stream(session.clone(), format, move |after, limit| {
+            fetch_db.chat_messages_after(id, after, limit)
+        })
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap()
+        .concat()
+    }
+
+    fn seed(db: &Database, n: usize) -> ChatSession {
+        let session = db.create_chat_session("Round trip", Some("qwen")).unwrap();
+        for i in 0..n {
+            let (role, reason) = if i % 2 == 0 {
+                ("user", None)
+            } else {
+                ("assistant", Some("stop"))
+            };
+            db.insert_chat_message(
+                session.id,
+                "qwen",
+                role,
+                &format!("message {i}\n\"quoted\""),
+                Some(i as u32),
+                reason,
+            )
+            .unwrap();
+        }
+        db.get_chat_session(session.id).unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn json_export_import_round_trip_is_lossless() {
+        let db = Arc::new(Database::open_in_memory());
+        // More than one page, to exercise the paging.
+        let session = seed(&db, PAGE_SIZE as usize + 3);
+        let before = db.list_chat_messages(session.id).unwrap();
+
+        let exported = export(&db, &session, ExportFormat::Json).await;
+        db.delete_chat_session(session.id).unwrap();
+        assert!(db.list_chat_sessions().unwrap().is_empty());
+
+        let chats = parse_import(serde_json::from_str(&exported).unwrap()).unwrap();
+        let imported = db.import_chat_session(&chats[0]).unwrap();
+        let after = db.list_chat_messages(imported.id).unwrap();
+
+        assert_eq!(
+            ExportedSession::from(&imported),
+            ExportedSession::from(&session)
+        );
+        assert_eq!(imported.message_count, session.message_count);
+        let strip = |ms: Vec<ChatMessageRecord>| -> Vec<ExportedMessage> {
+            ms.into_iter().map(Into::into).collect()
+        };
+        assert_eq!(strip(after), strip(before));
+
+        // Exporting the import again yields the same document.
+        let reexported = export(&db, &imported, ExportFormat::Json).await;
+        assert_eq!(reexported, exported);
+    }
+
+    #[tokio::test]
+    async fn empty_session_exports_valid_json() {
+        let db = Arc::new(Database::open_in_memory());
+        let session = db.create_chat_session("Empty", None).unwrap();
+        let exported = export(&db, &session, ExportFormat::Json).await;
+        let chat: ExportedChat = serde_json::from_str(&exported).unwrap();
+        assert!(chat.messages.is_empty());
+        assert_eq!(chat.session.title, "Empty");
+    }
+
+    #[tokio::test]
+    async fn markdown_export_lists_messages() {
+        let db = Arc::new(Database::open_in_memory());
+        let session = seed(&db, 2);
+        let md = export(&db, &session, ExportFormat::Markdown).await;
+        assert!(md.starts_with("# Round trip\n"));
+        assert!(md.contains("## user · "));
+        assert!(md.contains("## assistant · "));
+        assert!(md.contains("message 1"));
+    }
+
+    #[test]
+    fn imports_chatgpt_conversation_active_branch() {
+        let body = serde_json::json!([{
+            "title": "From ChatGPT",
+            "create_time": 1_700_000_000.0,
+            "update_time": 1_700_000_100.0,
+            "current_node": "c",
+            "mapping": {
+                "root": { "message": null, "parent": null },
+                "a": {
+                    "parent": "root",
+                    "message": {
+                        "author": { "role": "user" },
+                        "content": { "content_type": "text", "parts": ["Hi"] },
+                        "create_time": 1_700_000_010.0
+                    }
+                },
+                "b-old": {
+                    "parent": "a",
+                    "message": {
+                        "author": { "role": "assistant" },
+                        "content": { "parts": ["discarded branch"] }
+                    }
+                },
+                "c": {
+                    "parent": "a",
+                    "message": {
+                        "author": { "role": "assistant" },
+                        "content": { "parts": ["Hello!"] },
+                        "metadata": { "model_slug": "gpt-4o" }
+                    }
+                }
+            }
+        }]);
+        let chats = parse_import(body).unwrap();
+        assert_eq!(chats.len(), 1);
+        let chat = &chats[0];
+        assert_eq!(chat.session.title, "From ChatGPT");
+        assert_eq!(chat.session.model_id.as_deref(), Some("gpt-4o"));
+        assert_eq!(chat.session.created_at, "2023-11-14 22:13:20");
+        let texts: Vec<_> = chat.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(texts, ["Hi", "Hello!"]);
+    }
+
+    #[test]
+    fn rejects_unknown_shapes() {
+        assert!(matches!(
+            parse_import(serde_json::json!({ "foo": 1 })),
+            Err(ImportError::UnknownFormat)
+        ));
+        assert!(matches!(
+            parse_import(serde_json::json!([])),
+            Err(ImportError::Empty)
+        ));
+    }
+}
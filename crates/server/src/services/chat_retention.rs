@@ -0,0 +1,50 @@
+//! Automatic deletion of old chat sessions.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::db::ChatDeletion;
+use crate::state::{ShutdownSignal, shutdown_requested};
+
+/// How often the retention window is enforced.
+const CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Spawn a background task that, every hour and once at startup, calls
+/// `prune` with the cutoff `retention_days` ago and reports non-empty
+/// deletions to `on_pruned`.  `retention_days == 0` keeps everything.
+pub fn spawn_chat_retention<E: std::fmt::Display>(
+    retention_days: u64,
+    mut shutdown: ShutdownSignal,
+    prune: impl Fn(DateTime<Utc>) -> Result<ChatDeletion, E> + Send + 'static,
+    on_pruned: impl Fn(ChatDeletion) + Send + 'static,
+) {
+    if retention_days == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let cutoff = Utc::now() - chrono::Duration::days(retention_days as i64);
+                    match prune(cutoff) {
+                        Ok(deleted) if deleted.sessions > 0 => {
+                            tracing::info!(
+                                sessions = deleted.sessions,
+                                messages = deleted.messages,
+                                "Deleted chat sessions past retention"
+                            );
+                            on_pruned(deleted);
+                        }
+                        Ok(_) => {}
+                        Err(e) => tracing::warn!("Chat retention failed: {e}"),
+                    }
+                }
+                _ = shutdown_requested(&mut shutdown) => {
+                    break;
+                }
+            }
+        }
+    });
+}
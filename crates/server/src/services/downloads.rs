@@ -0,0 +1,606 @@
+//! Model downloads over HTTP with progress tracking.
+//!
+//! Every download is kept in a registry so the UI can render progress
+//! after a page refresh.  Progress is broadcast as `download.progress`
+//! events (at most [`PROGRESS_INTERVAL`] apart), followed by a terminal
+//! `download.completed` or `download.failed`.  Completed files are hashed
+//! and the SHA-256 recorded for later verification (see
+//! [`integrity`](crate::services::integrity)).
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use crate::db::Database;
+use crate::services::events::EventBus;
+use crate::services::integrity;
+
+/// Minimum spacing between `download.progress` events of one download.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Bytes of a `.gguf` download checked by [`check_gguf_head`] before
+/// anything is written.
+const GGUF_HEAD_LEN: usize = 64 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DownloadError {
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Server responded with {0}")]
+    Status(reqwest::StatusCode),
+
+    #[error("Unexpected response: {0}")]
+    InvalidResponse(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl DownloadError {
+    /// The remote file (or repository) doesn't exist.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Self::Status(s) if *s == reqwest::StatusCode::NOT_FOUND)
+    }
+
+    /// A write failed because the target disk is full.
+    pub fn is_disk_full(&self) -> bool {
+        const ENOSPC: i32 = 28;
+        match self {
+            Self::Io(e) => {
+                e.kind() == std::io::ErrorKind::StorageFull || e.raw_os_error() == Some(ENOSPC)
+            }
+            _ => false,
+        }
+    }
+}
+
+//  Registry types
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadStatus {
+    Downloading,
+    Completed,
+    Failed,
+}
+
+/// Current state of a download, as returned by `GET /api/downloads`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadInfo {
+    pub id: String,
+    pub url: String,
+    pub path: PathBuf,
+    pub status: DownloadStatus,
+    pub bytes_done: u64,
+    pub total_bytes: Option<u64>,
+    /// Average bytes per second since the download started.
+    pub speed_bps: u64,
+    pub eta_secs: Option<u64>,
+    pub error: Option<String>,
+    /// SHA-256 of the completed file.
+    pub sha256: Option<String>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+//  Progress metering
+
+/// Turns byte counts into throttled speed/ETA snapshots.
+#[derive(Debug)]
+pub struct ProgressMeter {
+    started: Instant,
+    last_report: Option<Instant>,
+    total: Option<u64>,
+    /// Bytes already on disk when the transfer (re)started.
+    resumed_from: u64,
+}
+
+/// Speed and ETA at one point of a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgressSnapshot {
+    pub bytes_done: u64,
+    pub speed_bps: u64,
+    pub eta_secs: Option<u64>,
+}
+
+impl ProgressMeter {
+    pub fn new(total: Option<u64>, resumed_from: u64, now: Instant) -> Self {
+        Self {
+            started: now,
+            last_report: None,
+            total,
+            resumed_from,
+        }
+    }
+
+    /// Snapshot at `bytes_done`.  Resumed bytes don't count towards speed.
+    pub fn snapshot(&self, bytes_done: u64, now: Instant) -> ProgressSnapshot {
+        let elapsed = now.duration_since(self.started).as_secs_f64();
+        let transferred = bytes_done.saturating_sub(self.resumed_from);
+        let speed_bps = if elapsed > 0.0 {
+            (transferred as f64 / elapsed) as u64
+        } else {
+            0
+        };
+        let eta_secs = match self.total {
+            Some(total) if speed_bps > 0 => Some(total.saturating_sub(bytes_done) / speed_bps),
+            _ => None,
+        };
+        ProgressSnapshot {
+            bytes_done,
+            speed_bps,
+            eta_secs,
+        }
+    }
+
+    /// Like [`snapshot`](Self::snapshot), but `None` until
+    /// [`PROGRESS_INTERVAL`] has passed since the last reported one.
+    pub fn update(&mut self, bytes_done: u64, now: Instant) -> Option<ProgressSnapshot> {
+        if let Some(last) = self.last_report
+            && now.duration_since(last) < PROGRESS_INTERVAL
+        {
+            return None;
+        }
+        self.last_report = Some(now);
+        Some(self.snapshot(bytes_done, now))
+    }
+}
+
+//  Fetching
+
+/// Progress of a running [`fetch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FetchProgress {
+    /// Bytes on disk, including resumed ones.
+    pub bytes_done: u64,
+    pub total_bytes: Option<u64>,
+    /// Bytes already on disk when the transfer started.
+    pub resumed_from: u64,
+}
+
+/// Stream `url` into `dest`, calling `on_progress` after every chunk.
+///
+/// Data goes to `dest` with a `.part` suffix and is renamed into place
+/// once complete.  An existing `.part` file is resumed with a `Range`
+/// request; servers that ignore the range restart the file from scratch,
+/// and so does a `.part` file whose size doesn't match the remote one.
+/// A fresh `.gguf` download is refused unless its first bytes parse as a
+/// GGUF header (see [`check_gguf_head`]).  Returns the final file size.
+pub async fn fetch(
+    client: &reqwest::Client,
+    url: &str,
+    dest: &Path,
+    mut on_progress: impl FnMut(FetchProgress),
+) -> Result<u64, DownloadError> {
+    let part = part_path(dest);
+    let mut existing = match tokio::fs::metadata(&part).await {
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    };
+
+    let mut req = client.get(url);
+    if existing > 0 {
+        req = req.header(reqwest::header::RANGE, format!("bytes={existing}-"));
+    }
+    let mut resp = req.send().await?;
+
+    if resp.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE && existing > 0 {
+        let total = unsatisfied_range_total(resp.headers());
+        if total == Some(existing) {
+            // The partial file is already complete.
+            tokio::fs::rename(&part, dest).await?;
+            return Ok(existing);
+        }
+        warn!(
+            part = %part.display(),
+            existing,
+            ?total,
+            "Partial download doesn't match the remote file, restarting"
+        );
+        tokio::fs::remove_file(&part).await?;
+        existing = 0;
+        resp = client.get(url).send().await?;
+    }
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(DownloadError::Status(status));
+    }
+
+    let resumed_from = if status == reqwest::StatusCode::PARTIAL_CONTENT {
+        existing
+    } else {
+        0
+    };
+    let total = resp.content_length().map(|len| len + resumed_from);
+
+    let mut body = resp.bytes_stream();
+    let mut head = Vec::new();
+    if resumed_from == 0 && dest.extension().is_some_and(|e| e == "gguf") {
+        while head.len() < GGUF_HEAD_LEN
+            && let Some(chunk) = body.next().await
+        {
+            head.extend_from_slice(&chunk?);
+        }
+        check_gguf_head(&head, total)?;
+    }
+
+    let mut file = if resumed_from > 0 {
+        tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&part)
+            .await?
+    } else {
+        tokio::fs::File::create(&part).await?
+    };
+    file.write_all(&head).await?;
+    let mut done = resumed_from + head.len() as u64;
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk).await?;
+        done += chunk.len() as u64;
+        on_progress(FetchProgress {
+            bytes_done: done,
+            total_bytes: total,
+            resumed_from,
+        });
+    }
+    file.flush().await?;
+    drop(file);
+
+    tokio::fs::rename(&part, dest).await?;
+    Ok(done)
+}
+
+/// Refuse a download whose first bytes `head` (of `total` in all) aren't
+/// the start of a GGUF file, e.g. an HTML error page served with 200.
+pub fn check_gguf_head(head: &[u8], total: Option<u64>) -> Result<(), DownloadError> {
+    let total = total.unwrap_or(head.len() as u64);
+    let scan = gguf_parser::quick_scan_from(std::io::Cursor::new(head), total)
+        .map_err(|e| DownloadError::InvalidResponse(format!("not a GGUF file: {e}")))?;
+    info!(
+        version = scan.header.version,
+        architecture = scan.architecture.as_deref().unwrap_or("unknown"),
+        "GGUF header checked"
+    );
+    Ok(())
+}
+
+/// Complete length from the `Content-Range: bytes */N` header of a 416
+/// response.
+fn unsatisfied_range_total(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    headers
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes */")?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// `dest` with `.part` appended to the file name.
+pub fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dest.with_file_name(name)
+}
+
+//  Hugging Face
+
+const HF_ENDPOINT: &str = "https://huggingface.co";
+
+/// HTTP client for Hugging Face, authenticated with `HF_TOKEN` if set.
+///
+/// Only used by the CLI; the server never attaches credentials to
+/// user-supplied URLs.
+pub fn hf_client() -> Result<reqwest::Client, DownloadError> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Ok(token) = std::env::var("HF_TOKEN")
+        && let Ok(value) = format!("Bearer {token}").parse()
+    {
+        headers.insert(reqwest::header::AUTHORIZATION, value);
+    }
+    Ok(reqwest::Client::builder()
+        .user_agent(concat!("llama-dashboard/", env!("CARGO_PKG_VERSION")))
+        .default_headers(headers)
+        .build()?)
+}
+
+/// File names in a Hugging Face model repository at `revision`.
+pub async fn hf_repo_files(
+    client: &reqwest::Client,
+    repo: &str,
+    revision: &str,
+) -> Result<Vec<String>, DownloadError> {
+    #[derive(serde::Deserialize)]
+    struct Sibling {
+        rfilename: String,
+    }
+    #[derive(serde::Deserialize)]
+    struct RepoInfo {
+        #[serde(default)]
+        siblings: Vec<Sibling>,
+    }
+
+    let url = format!("{HF_ENDPOINT}/api/models/{repo}/revision/{revision}");
+    let resp = client.get(&url).send().await?;
+    if !resp.status().is_success() {
+        return Err(DownloadError::Status(resp.status()));
+    }
+    let body = resp.bytes().await?;
+    let info: RepoInfo =
+        serde_json::from_slice(&body).map_err(|e| DownloadError::InvalidResponse(e.to_string()))?;
+    Ok(info.siblings.into_iter().map(|s| s.rfilename).collect())
+}
+
+/// Download URL of `file` in a Hugging Face repository.
+pub fn hf_file_url(repo: &str, revision: &str, file: &str) -> String {
+    format!("{HF_ENDPOINT}/{repo}/resolve/{revision}/{file}")
+}
+
+//  DownloadManager
+
+#[derive(Clone)]
+pub struct DownloadManager {
+    downloads: Arc<RwLock<HashMap<String, DownloadInfo>>>,
+    client: reqwest::Client,
+    events: EventBus,
+    db: Arc<Database>,
+}
+
+impl DownloadManager {
+    pub fn new(events: EventBus, db: Arc<Database>) -> Self {
+        Self {
+            downloads: Arc::new(RwLock::new(HashMap::new())),
+            client: reqwest::Client::new(),
+            events,
+            db,
+        }
+    }
+
+    /// All downloads, most recent first.
+    pub fn list(&self) -> Vec<DownloadInfo> {
+        let downloads = self.downloads.read().unwrap();
+        let mut list: Vec<_> = downloads.values().cloned().collect();
+        list.sort_by_key(|d| std::cmp::Reverse(d.started_at));
+        list
+    }
+
+    pub fn get(&self, id: &str) -> Option<DownloadInfo> {
+        self.downloads.read().unwrap().get(id).cloned()
+    }
+
+    /// Start downloading `url` to `dest` in the background.
+    ///
+    /// Downloads to the same `dest` would share its `.part` file, so while
+    /// one is running another is refused with the running one's id.
+    pub fn start(&self, url: String, dest: PathBuf) -> Result<DownloadInfo, String> {
+        let info = DownloadInfo {
+            id: uuid::Uuid::new_v4().to_string(),
+            url,
+            path: dest,
+            status: DownloadStatus::Downloading,
+            bytes_done: 0,
+            total_bytes: None,
+            speed_bps: 0,
+            eta_secs: None,
+            error: None,
+            sha256: None,
+            started_at: chrono::Utc::now(),
+            finished_at: None,
+        };
+        {
+            let mut downloads = self.downloads.write().unwrap();
+            if let Some(running) = downloads
+                .values()
+                .find(|d| d.status == DownloadStatus::Downloading && d.path == info.path)
+            {
+                return Err(running.id.clone());
+            }
+            downloads.insert(info.id.clone(), info.clone());
+        }
+        info!(id = info.id, url = info.url, "Download started");
+
+        let manager = self.clone();
+        let (id, url, dest) = (info.id.clone(), info.url.clone(), info.path.clone());
+        tokio::spawn(async move {
+            let mut meter: Option<ProgressMeter> = None;
+            let result = fetch(&manager.client, &url, &dest, |p| {
+                let meter = meter.get_or_insert_with(|| {
+                    ProgressMeter::new(p.total_bytes, p.resumed_from, Instant::now())
+                });
+                if let Some(snapshot) = meter.update(p.bytes_done, Instant::now()) {
+                    manager.report_progress(&id, p.total_bytes, snapshot);
+                }
+            })
+            .await;
+            let sha256 = match result {
+                Ok(_) => manager.record_hash(&dest).await,
+                Err(_) => None,
+            };
+            manager.finish(&id, result, sha256);
+        });
+        Ok(info)
+    }
+
+    /// Hash a completed download and record it; failures are only logged,
+    /// the download itself succeeded.
+    async fn record_hash(&self, path: &Path) -> Option<String> {
+        let db = self.db.clone();
+        let path = path.to_path_buf();
+        let result = tokio::task::spawn_blocking(move || {
+            let sha256 = gguf_parser::hash_file(&path)?;
+            integrity::record(&db, &path, &sha256)?;
+            Ok::<_, integrity::IntegrityError>(sha256)
+        })
+        .await;
+        match result {
+            Ok(Ok(sha256)) => Some(sha256),
+            Ok(Err(e)) => {
+                warn!("Failed to hash download: {e}");
+                None
+            }
+            Err(e) => {
+                warn!("Failed to hash download: {e}");
+                None
+            }
+        }
+    }
+
+    fn report_progress(&self, id: &str, total: Option<u64>, snapshot: ProgressSnapshot) {
+        let info = {
+            let mut downloads = self.downloads.write().unwrap();
+            let Some(info) = downloads.get_mut(id) else {
+                return;
+            };
+            info.bytes_done = snapshot.bytes_done;
+            info.total_bytes = total;
+            info.speed_bps = snapshot.speed_bps;
+            info.eta_secs = snapshot.eta_secs;
+            info.clone()
+        };
+        self.emit("download.progress", &info);
+    }
+
+    fn finish(&self, id: &str, result: Result<u64, DownloadError>, sha256: Option<String>) {
+        let info = {
+            let mut downloads = self.downloads.write().unwrap();
+            let Some(info) = downloads.get_mut(id) else {
+                return;
+            };
+            info.finished_at = Some(chrono::Utc::now());
+            info.eta_secs = None;
+            match result {
+                Ok(bytes) => {
+                    info.status = DownloadStatus::Completed;
+                    info.bytes_done = bytes;
+                    info.total_bytes = Some(bytes);
+                    info.sha256 = sha256;
+                }
+                Err(e) => {
+                    info.status = DownloadStatus::Failed;
+                    info.error = Some(e.to_string());
+                }
+            }
+            info.clone()
+        };
+
+        match info.status {
+            DownloadStatus::Completed => {
+                info!(id, path = %info.path.display(), "Download completed");
+                self.emit("download.completed", &info);
+            }
+            _ => {
+                warn!(id, error = ?info.error, "Download failed");
+                self.emit("download.failed", &info);
+            }
+        }
+    }
+
+    fn emit(&self, event_type: &str, info: &DownloadInfo) {
+        let data = serde_json::to_value(info).unwrap_or_default();
+        self.events.send(event_type, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meter_throttles_updates() {
+        let t0 = Instant::now();
+        let mut meter = ProgressMeter::new(Some(1000), 0, t0);
+        assert!(meter.update(10, t0).is_some());
+        assert!(meter.update(20, t0 + Duration::from_millis(200)).is_none());
+        assert!(meter.update(30, t0 + Duration::from_millis(499)).is_none());
+        assert!(meter.update(40, t0 + Duration::from_millis(500)).is_some());
+    }
+
+    #[test]
+    fn meter_computes_speed_and_eta() {
+        let t0 = Instant::now();
+        let meter = ProgressMeter::new(Some(1000), 0, t0);
+        let snapshot = meter.snapshot(200, t0 + Duration::from_secs(2));
+        assert_eq!(snapshot.speed_bps, 100);
+        assert_eq!(snapshot.eta_secs, Some(8));
+
+        let resumed = ProgressMeter::new(Some(1000), 600, t0);
+        let snapshot = resumed.snapshot(800, t0 + Duration::from_secs(2));
+        assert_eq!(snapshot.speed_bps, 100);
+        assert_eq!(snapshot.eta_secs, Some(2));
+
+        let unknown_total = ProgressMeter::new(None, 0, t0);
+        assert_eq!(
+            unknown_total
+                .snapshot(200, t0 + Duration::from_secs(2))
+                .eta_secs,
+            None
+        );
+    }
+
+    #[test]
+    fn gguf_head_check_refuses_other_content() {
+        let key = "general.architecture";
+        let mut head = Vec::new();
+        head.extend_from_slice(b"GGUF");
+        head.extend_from_slice(&3u32.to_le_bytes());
+        head.extend_from_slice(&0u64.to_le_bytes()); // tensors
+        head.extend_from_slice(&2u64.to_le_bytes()); // KVs, one past the head
+        head.extend_from_slice(&(key.len() as u64).to_le_bytes());
+        head.extend_from_slice(key.as_bytes());
+        head.extend_from_slice(&8u32.to_le_bytes()); // string
+        head.extend_from_slice(&5u64.to_le_bytes());
+        head.extend_from_slice(b"llama");
+        assert!(check_gguf_head(&head, Some(4 << 30)).is_ok());
+
+        let page = b"<!DOCTYPE html><html><body>Sign in</body></html>";
+        assert!(matches!(
+            check_gguf_head(page, Some(page.len() as u64)),
+            Err(DownloadError::InvalidResponse(msg)) if msg.starts_with("not a GGUF file")
+        ));
+        assert!(check_gguf_head(b"", None).is_err());
+    }
+
+    #[test]
+    fn unsatisfied_range_total_reads_content_range() {
+        use reqwest::header::{CONTENT_RANGE, HeaderMap, HeaderValue};
+
+        let with = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_RANGE, HeaderValue::from_static(value));
+            headers
+        };
+        assert_eq!(unsatisfied_range_total(&with("bytes */1234")), Some(1234));
+        assert_eq!(unsatisfied_range_total(&with("bytes */*")), None);
+        assert_eq!(unsatisfied_range_total(&with("bytes 0-9/1234")), None);
+        assert_eq!(unsatisfied_range_total(&HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn concurrent_downloads_to_one_file_are_refused() {
+        let manager = DownloadManager::new(EventBus::new(), Arc::new(Database::open_in_memory()));
+        let url = "http://127.0.0.1:9/a.gguf".to_string();
+        let first = manager
+            .start(url.clone(), PathBuf::from("/models/a.gguf"))
+            .unwrap();
+        let second = manager.start(url.clone(), PathBuf::from("/models/a.gguf"));
+        assert_eq!(second.unwrap_err(), first.id);
+        assert!(manager.start(url, PathBuf::from("/models/b.gguf")).is_ok());
+    }
+
+    #[test]
+    fn part_path_appends_suffix() {
+        assert_eq!(
+            part_path(Path::new("/models/a.gguf")),
+            PathBuf::from("/models/a.gguf.part")
+        );
+    }
+}
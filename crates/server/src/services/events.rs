@@ -0,0 +1,178 @@
+//! Event bus behind `/ws/events`.
+//!
+//! Every broadcast event gets a sequence number, one higher than the
+//! previous, and the last [`REPLAY_CAPACITY`] events are kept so that a
+//! dashboard reconnecting after a dropped socket can be sent what it
+//! missed instead of refetching everything.  Numbers start at 1 again
+//! when the server restarts.
+//!
+//! Admin-only events (log lines) are neither numbered nor kept: they go
+//! to admin clients that are connected at the time and nowhere else.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::broadcast;
+
+/// Events kept for replay.
+pub const REPLAY_CAPACITY: usize = 500;
+
+/// A broadcast event.
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// `None` for admin-only events, which aren't kept for replay.
+    pub seq: Option<u64>,
+    pub event_type: String,
+    /// Only for clients with the `admin` scope.
+    pub admin_only: bool,
+    /// The serialized `{type, seq, timestamp, data}` envelope.
+    pub message: String,
+}
+
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<Event>,
+    recent: Arc<Mutex<Recent>>,
+}
+
+struct Recent {
+    next_seq: u64,
+    events: VecDeque<Event>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self {
+            tx: broadcast::channel(256).0,
+            recent: Arc::new(Mutex::new(Recent {
+                next_seq: 1,
+                events: VecDeque::with_capacity(REPLAY_CAPACITY),
+            })),
+        }
+    }
+
+    /// Number and broadcast an event; returns its sequence number.
+    pub fn send(&self, event_type: &str, data: serde_json::Value) -> u64 {
+        let mut recent = self.recent.lock().unwrap();
+        let seq = recent.next_seq;
+        recent.next_seq += 1;
+        let event = Event {
+            seq: Some(seq),
+            event_type: event_type.to_string(),
+            admin_only: false,
+            message: envelope(event_type, Some(seq), data),
+        };
+        if recent.events.len() == REPLAY_CAPACITY {
+            recent.events.pop_front();
+        }
+        recent.events.push_back(event.clone());
+        // Sent under the lock so subscribers see events in `seq` order.
+        // Ignore send errors (no subscribers)
+        let _ = self.tx.send(event);
+        seq
+    }
+
+    /// Broadcast an event to the admin clients connected now, without a
+    /// `seq` and without keeping it for replay.
+    pub fn send_admin_only(&self, event_type: &str, data: serde_json::Value) {
+        let _ = self.tx.send(Event {
+            seq: None,
+            event_type: event_type.to_string(),
+            admin_only: true,
+            message: envelope(event_type, None, data),
+        });
+    }
+
+    /// Subscribe, along with the `seq` of the last event sent before: the
+    /// receiver gets exactly the events after it.
+    pub fn subscribe(&self) -> (broadcast::Receiver<Event>, u64) {
+        let recent = self.recent.lock().unwrap();
+        (self.tx.subscribe(), recent.next_seq - 1)
+    }
+
+    /// Sequence number of the last event sent, 0 before the first.
+    pub fn latest_seq(&self) -> u64 {
+        self.recent.lock().unwrap().next_seq - 1
+    }
+
+    /// The events after `seq`, or `None` when some of them are no longer
+    /// kept or `seq` was never sent (it's from before a restart).
+    pub fn since(&self, seq: u64) -> Option<Vec<Event>> {
+        let recent = self.recent.lock().unwrap();
+        let oldest = recent
+            .events
+            .front()
+            .and_then(|e| e.seq)
+            .unwrap_or(recent.next_seq);
+        if seq >= recent.next_seq || seq + 1 < oldest {
+            return None;
+        }
+        let skip = (seq + 1 - oldest) as usize;
+        Some(recent.events.iter().skip(skip).cloned().collect())
+    }
+}
+
+/// Serialize a message in the `{type, timestamp, data}` WebSocket format
+/// that is not a broadcast event, such as a reply to one client.
+pub fn event_message(event_type: &str, data: serde_json::Value) -> String {
+    envelope(event_type, None, data)
+}
+
+fn envelope(event_type: &str, seq: Option<u64>, data: serde_json::Value) -> String {
+    let mut message = serde_json::json!({
+        "type": event_type,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "data": data,
+    });
+    if let Some(seq) = seq {
+        message["seq"] = seq.into();
+    }
+    message.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_events_and_replays_the_tail() {
+        let bus = EventBus::new();
+        let (mut rx, _) = bus.subscribe();
+        assert_eq!(bus.latest_seq(), 0);
+        assert_eq!(bus.since(0).unwrap().len(), 0);
+
+        bus.send("model.loaded", serde_json::json!({}));
+        let first = rx.try_recv().unwrap();
+        assert_eq!(first.seq, Some(1));
+        let message: serde_json::Value = serde_json::from_str(&first.message).unwrap();
+        assert_eq!(message["seq"], 1);
+        assert_eq!(message["type"], "model.loaded");
+        for i in 1..REPLAY_CAPACITY + 10 {
+            bus.send("system.metrics", serde_json::json!({ "i": i }));
+        }
+
+        let latest = bus.latest_seq();
+        assert_eq!(latest, REPLAY_CAPACITY as u64 + 10);
+        let missed = bus.since(latest - 3).unwrap();
+        let seqs: Vec<u64> = missed.iter().filter_map(|e| e.seq).collect();
+        assert_eq!(seqs, [latest - 2, latest - 1, latest]);
+        assert!(bus.since(latest).unwrap().is_empty());
+
+        // The oldest kept event is 11, so 10 can still be resumed from
+        assert_eq!(bus.since(10).unwrap().len(), REPLAY_CAPACITY);
+        assert!(bus.since(9).is_none());
+        // From a previous run of the server
+        assert!(bus.since(latest + 1).is_none());
+
+        // Admin-only events are live only
+        bus.send_admin_only("logs.line", serde_json::json!({}));
+        assert_eq!(bus.latest_seq(), latest);
+        assert!(bus.since(latest).unwrap().is_empty());
+    }
+}
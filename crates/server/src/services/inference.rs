@@ -2,3 +2,548 @@
 //!
 //! Phase 1: thin wrapper. Phase 2 will add request queuing,
 //! slot management, and multi-model routing.
+
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex, TryLockError};
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+use crate::middleware::request_id::with_request_id;
+use crate::services::model_manager::LoadedModel;
+use crate::services::request_log::{RequestLogEntry, RequestMeta, finish_reason_name, user_hash};
+use crate::state::AppState;
+
+/// Run generation on the model's context in a blocking task.
+///
+/// When it finishes the model's keep-alive clock restarts, and a model
+/// whose keep-alive has already run out (e.g. `keep_alive: 0`) is
+/// unloaded right away.  Completed generations are accounted in the
+/// model's stats, and every request is added to the request log.
+/// Generations stop early once the state's cancel token or `meta.cancel`
+/// fires.  When `meta.timeout` runs out first, generation is cancelled
+/// and the stream ends with [`FinishReason::Timeout`](llama_core::FinishReason::Timeout).
+///
+/// `generation.started` and `generation.finished` events are broadcast,
+/// and the current request span stays open until generation ends, with
+/// the model and token counts recorded on it.  All of this accounting
+/// goes through [`generation_finished`], so it is the same for every
+/// endpoint.
+pub fn spawn_generation(
+    state: &AppState,
+    loaded: Arc<LoadedModel>,
+    gen_req: llama_core::GenerateRequest,
+    meta: RequestMeta,
+) -> mpsc::Receiver<llama_core::GenerateEvent> {
+    let (tx, rx) = mpsc::channel(64);
+    let (gen_tx, mut gen_rx) = mpsc::channel(64);
+    let id = loaded.id.clone();
+    let n_prompt = gen_req.tokens.len() as u32;
+    let dispatched = Instant::now();
+    // Set once the context is ours; until then the request is queued.
+    let started = Arc::new(Mutex::new(None));
+
+    let mm = state.model_manager().clone();
+    // Cancelled on shutdown, by the caller, or by the forwarder when time
+    // runs out.
+    let cancel = meta
+        .cancel
+        .clone()
+        .unwrap_or_else(|| state.generation_cancel().child());
+    let gen_cancel = cancel.clone();
+    let gen_started = started.clone();
+    tokio::task::spawn_blocking(move || {
+        let error_tx = gen_tx.clone();
+        let ran = run_guarded(&loaded.context, &error_tx, |ctx| {
+            *gen_started.lock().unwrap() = Some(Instant::now());
+            ctx.kv_cache_clear();
+            llama_core::generate::generate_blocking(ctx, &gen_req, gen_tx, &gen_cancel);
+        });
+
+        let id = loaded.id.clone();
+        drop(loaded);
+        match ran {
+            Ok(()) => mm.touch(&id),
+            Err(e) => {
+                tracing::error!(model = %id, error = %e, "Generation failed; reloading the model");
+                mm.mark_failed(&id, e.to_string());
+            }
+        }
+        mm.sweep_expired();
+    });
+
+    // The forwarder outlives the handler when streaming; it keeps the
+    // request's span open until generation ends and tags its events.
+    let span = tracing::Span::current();
+    span.record("model", id.as_str());
+    span.record("prompt_tokens", n_prompt);
+    generation_started(state, &id, &meta, n_prompt);
+
+    let request_id = crate::middleware::request_id::current();
+    let state = state.clone();
+    let forward = async move {
+        let timer = async {
+            match meta.timeout {
+                Some(limit) => tokio::time::sleep(limit).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(timer);
+
+        let mut pieces = 0;
+        let mut outcome = None;
+        loop {
+            let event = tokio::select! {
+                event = gen_rx.recv() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+                // End the response now rather than when the blocking task
+                // notices the cancellation.
+                _ = &mut timer => {
+                    cancel.cancel();
+                    tracing::warn!(model = %id, "Generation timed out");
+                    outcome = Some((n_prompt, pieces, "timeout", 504));
+                    let _ = tx
+                        .send(llama_core::GenerateEvent::Done {
+                            finish_reason: llama_core::FinishReason::Timeout,
+                            prompt_tokens: n_prompt,
+                            completion_tokens: pieces,
+                        })
+                        .await;
+                    break;
+                }
+            };
+            match &event {
+                llama_core::GenerateEvent::Token(_) => pieces += 1,
+                llama_core::GenerateEvent::Done {
+                    finish_reason,
+                    prompt_tokens,
+                    completion_tokens,
+                } => {
+                    let reason = finish_reason_name(finish_reason);
+                    outcome = Some((*prompt_tokens, *completion_tokens, reason, 200));
+                }
+                // Stopped by shutdown or the client rather than a failure.
+                llama_core::GenerateEvent::Error(e) if e.is_cancelled() => {
+                    let status = if state.generation_cancel().is_cancelled() {
+                        503
+                    } else {
+                        499
+                    };
+                    outcome = Some((n_prompt, pieces, "cancelled", status));
+                }
+                llama_core::GenerateEvent::Error(e) => {
+                    outcome = Some((n_prompt, pieces, "error", e.status));
+                }
+            }
+            // A dropped receiver (client gone) also stops generation.
+            if tx.send(event).await.is_err() {
+                break;
+            }
+        }
+        let (prompt_tokens, completion_tokens, finish_reason, status) =
+            outcome.unwrap_or((n_prompt, pieces, "cancelled", 499));
+        let ended = Instant::now();
+        let started = started.lock().unwrap().unwrap_or(ended);
+        let finished = Finished {
+            prompt_tokens,
+            completion_tokens,
+            finish_reason,
+            status,
+            queued: started - dispatched,
+            generating: ended - started,
+        };
+        generation_finished(&state, &id, &meta, &finished);
+    };
+    tokio::spawn(with_request_id(request_id, forward.instrument(span)));
+    rx
+}
+
+/// Error for a context whose mutex a panic has poisoned.
+fn poisoned_context() -> llama_core::LlamaError {
+    llama_core::LlamaError::FfiPanic("the context was poisoned by an earlier panic".into())
+}
+
+/// Run `generate` on the context in `context`, sending a panic on `tx` as
+/// a [`GenerateEvent::Error`](llama_core::GenerateEvent::Error) instead
+/// of letting it end the blocking task without a terminal event.
+///
+/// A panic poisons the mutex, so an error here means the context can't be
+/// used again.
+fn run_guarded<C>(
+    context: &Mutex<C>,
+    tx: &mpsc::Sender<llama_core::GenerateEvent>,
+    generate: impl FnOnce(&mut C),
+) -> Result<(), llama_core::LlamaError> {
+    let outcome = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut ctx = context.lock().map_err(|_| poisoned_context())?;
+        generate(&mut ctx);
+        Ok(())
+    }));
+    let err = match outcome {
+        Ok(Ok(())) => return Ok(()),
+        Ok(Err(e)) => e,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            llama_core::LlamaError::FfiPanic(message)
+        }
+    };
+    let _ = tx.blocking_send(llama_core::GenerateEvent::Error(
+        llama_core::GenerateError::llama("generation", &err),
+    ));
+    Err(err)
+}
+
+/// How a generation ended.
+struct Finished {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    finish_reason: &'static str,
+    /// HTTP status the request is logged with; 200 when it completed.
+    status: u16,
+    /// Waiting for the model's context, behind other generations.
+    queued: Duration,
+    /// From taking the context to the end, prompt processing included.
+    generating: Duration,
+}
+
+/// Announce an admitted generation and count it as in flight.
+fn generation_started(state: &AppState, id: &str, meta: &RequestMeta, prompt_tokens: u32) {
+    let in_flight = state.stats().generation_started();
+    state.broadcast_event(
+        "generation.started",
+        serde_json::json!({
+            "model": id,
+            "endpoint": meta.endpoint,
+            "prompt_tokens": prompt_tokens,
+            "in_flight": in_flight,
+        }),
+    );
+}
+
+/// The one place a generation's end is accounted: the model's stats
+/// (completed generations only), the request log, the request span and
+/// the `generation.finished` event.
+fn generation_finished(state: &AppState, id: &str, meta: &RequestMeta, finished: &Finished) {
+    let in_flight = state.stats().generation_finished();
+    if finished.status == 200 {
+        state.stats().record(
+            id,
+            finished.prompt_tokens,
+            finished.completion_tokens,
+            finished.generating,
+        );
+    }
+    let duration = finished.queued + finished.generating;
+    state.request_log().record(RequestLogEntry {
+        timestamp: chrono::Utc::now(),
+        endpoint: meta.endpoint.to_string(),
+        model_id: id.to_string(),
+        prompt_tokens: finished.prompt_tokens,
+        completion_tokens: finished.completion_tokens,
+        duration_ms: duration.as_millis() as u64,
+        finish_reason: finished.finish_reason.to_string(),
+        status: finished.status,
+        user_hash: meta.user.as_deref().map(user_hash),
+        prompt: meta.prompt.clone(),
+        api_key_id: meta.api_key_id.clone(),
+    });
+
+    let span = tracing::Span::current();
+    span.record("prompt_tokens", finished.prompt_tokens);
+    span.record("completion_tokens", finished.completion_tokens);
+
+    let secs = finished.generating.as_secs_f64();
+    let tokens_per_sec = if secs > 0.0 {
+        f64::from(finished.completion_tokens) / secs
+    } else {
+        0.0
+    };
+    state.broadcast_event(
+        "generation.finished",
+        serde_json::json!({
+            "model": id,
+            "endpoint": meta.endpoint,
+            "prompt_tokens": finished.prompt_tokens,
+            "completion_tokens": finished.completion_tokens,
+            "finish_reason": finished.finish_reason,
+            "status": finished.status,
+            "duration_ms": duration.as_millis() as u64,
+            "queue_ms": finished.queued.as_millis() as u64,
+            "tokens_per_sec": tokens_per_sec,
+            "in_flight": in_flight,
+        }),
+    );
+}
+
+/// The time limit of a generation: the configured one (0 = unlimited),
+/// which a request's own `timeout` may lower but not raise.
+pub fn generation_timeout(configured_secs: u64, requested_secs: Option<u64>) -> Option<Duration> {
+    let requested = requested_secs.filter(|&s| s > 0);
+    let secs = match (configured_secs, requested) {
+        (0, requested) => requested,
+        (configured, Some(requested)) => Some(requested.min(configured)),
+        (configured, None) => Some(configured),
+    };
+    secs.map(Duration::from_secs)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BenchError {
+    #[error("A generation is in progress on this model")]
+    Busy,
+
+    #[error(transparent)]
+    Llama(#[from] llama_core::LlamaError),
+}
+
+/// Benchmark a loaded model on its own context (blocking).
+///
+/// Like a generation request it holds the context mutex for the whole
+/// run.  Unless `wait` is set, a context that's already in use is
+/// reported as [`BenchError::Busy`] instead of queueing behind it.  A
+/// poisoned context is refused, as in generation.
+pub fn bench_blocking(
+    loaded: &LoadedModel,
+    params: &llama_core::BenchParams,
+    wait: bool,
+) -> Result<llama_core::BenchReport, BenchError> {
+    let mut ctx = match loaded.context.try_lock() {
+        Ok(ctx) => ctx,
+        Err(TryLockError::WouldBlock) if !wait => return Err(BenchError::Busy),
+        Err(TryLockError::WouldBlock) => loaded.context.lock().map_err(|_| poisoned_context())?,
+        Err(TryLockError::Poisoned(_)) => return Err(poisoned_context().into()),
+    };
+    Ok(llama_core::run_bench(&mut ctx, params)?)
+}
+
+//  Pooled outputs (embeddings, classification)
+
+/// What [`pooled_blocking`] read for one input.
+#[derive(Debug)]
+pub struct Pooled {
+    pub values: Vec<f32>,
+    pub n_tokens: u32,
+}
+
+/// How [`pooled_blocking`] sets up its context and treats long inputs.
+#[derive(Debug, Clone)]
+pub struct PoolParams {
+    /// `embeddings` is always turned on; `n_ctx` 0 means the model's
+    /// training context.
+    pub context: llama_core::ContextParams,
+    /// Cut inputs longer than the context to fit instead of failing with
+    /// [`PoolError::TooLong`].
+    pub truncate: bool,
+}
+
+impl Default for PoolParams {
+    fn default() -> Self {
+        Self {
+            context: llama_core::ContextParams {
+                n_ctx: 4096,
+                ..Default::default()
+            },
+            truncate: false,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PoolError {
+    #[error("The model produced no pooled output")]
+    NoOutput,
+
+    #[error("Input {index} is {n_tokens} tokens, more than the context's {n_ctx}")]
+    TooLong {
+        index: usize,
+        n_tokens: usize,
+        n_ctx: u32,
+    },
+
+    #[error(transparent)]
+    Llama(#[from] llama_core::LlamaError),
+}
+
+/// Run each of `texts` through a temporary embeddings context on `model`
+/// and collect what `read` takes from it (blocking).
+///
+/// The context is separate from the model's inference context so that
+/// chat and completions are not affected by the embeddings flag.  Each
+/// text is decoded alone as sequence 0; `read` returning `None` ends the
+/// run with [`PoolError::NoOutput`].
+pub fn pooled_blocking(
+    model: Arc<llama_core::LlamaModel>,
+    texts: &[String],
+    params: &PoolParams,
+    read: impl Fn(&llama_core::LlamaContext) -> Option<Vec<f32>>,
+) -> Result<Vec<Pooled>, PoolError> {
+    let mut context = params.context.clone();
+    context.embeddings = true;
+    if context.n_ctx == 0 {
+        context.n_ctx = model.n_ctx_train().max(0) as u32;
+    }
+    // Non-causal models must see a whole input in one micro-batch
+    context.n_batch = context.n_ctx;
+    context.n_ubatch = context.n_ctx;
+    let mut ctx = llama_core::LlamaContext::new(model.clone(), &context)?;
+    let n_ctx = ctx.n_ctx();
+    let vocab = model.vocab();
+
+    let mut pooled = Vec::with_capacity(texts.len());
+    for (index, text) in texts.iter().enumerate() {
+        ctx.kv_cache_clear();
+        let mut tokens = vocab.tokenize(text, true, true)?;
+        if tokens.len() > n_ctx as usize {
+            if !params.truncate {
+                return Err(PoolError::TooLong {
+                    index,
+                    n_tokens: tokens.len(),
+                    n_ctx,
+                });
+            }
+            tokens.truncate(n_ctx as usize);
+        }
+        let mut batch = llama_core::LlamaBatch::new(tokens.len() as i32, 0, 1);
+        batch
+            .add_sequence(&tokens, 0, 0, true)
+            .map_err(llama_core::LlamaError::from)?;
+        ctx.decode(&mut batch)?;
+        pooled.push(Pooled {
+            values: read(&ctx).ok_or(PoolError::NoOutput)?,
+            n_tokens: tokens.len() as u32,
+        });
+    }
+    Ok(pooled)
+}
+
+/// The pooled embedding of sequence 0, or the last token's for models
+/// that don't pool, as a [`pooled_blocking`] reader.
+pub fn read_embedding(ctx: &llama_core::LlamaContext, n_embd: usize) -> Option<Vec<f32>> {
+    let emb = ctx.get_embeddings_seq(0).or_else(|| ctx.get_embeddings())?;
+    Some(emb[..n_embd].to_vec())
+}
+
+/// Scale `embedding` to unit length; an all-zero vector stays as is.
+pub fn l2_normalize(embedding: Vec<f32>) -> Vec<f32> {
+    let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        embedding.iter().map(|x| x / norm).collect()
+    } else {
+        embedding
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embeddings_normalize_to_unit_length() {
+        assert_eq!(l2_normalize(vec![3.0, 4.0]), [0.6, 0.8]);
+        assert_eq!(l2_normalize(vec![0.0, 0.0]), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn requests_may_only_lower_the_timeout() {
+        let secs = |s| Some(Duration::from_secs(s));
+        assert_eq!(generation_timeout(600, None), secs(600));
+        assert_eq!(generation_timeout(600, Some(30)), secs(30));
+        assert_eq!(generation_timeout(600, Some(3600)), secs(600));
+        assert_eq!(generation_timeout(600, Some(0)), secs(600));
+        assert_eq!(generation_timeout(0, None), None);
+        assert_eq!(generation_timeout(0, Some(30)), secs(30));
+    }
+
+    #[test]
+    fn panics_become_error_events() {
+        let context = Mutex::new(0u32);
+        let (tx, mut rx) = mpsc::channel(4);
+
+        run_guarded(&context, &tx, |n| *n += 1).unwrap();
+        assert!(rx.try_recv().is_err());
+
+        let err = run_guarded(&context, &tx, |_| panic!("index out of bounds")).unwrap_err();
+        assert!(
+            matches!(err, llama_core::LlamaError::FfiPanic(ref m) if m == "index out of bounds")
+        );
+        let event = rx.try_recv().unwrap();
+        assert!(
+            matches!(event, llama_core::GenerateEvent::Error(ref e) if e.message == "generation: FFI panic: index out of bounds")
+        );
+
+        // The poisoned context is refused rather than reused
+        let mut ran = false;
+        let err = run_guarded(&context, &tx, |_| ran = true).unwrap_err();
+        assert!(!ran);
+        assert!(err.to_string().contains("poisoned"));
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(llama_core::GenerateEvent::Error(_))
+        ));
+    }
+
+    #[test]
+    fn finished_generations_feed_stats_and_events() {
+        let state = AppState::for_tests(Default::default(), None);
+        let (mut rx, _) = state.events().subscribe();
+        let meta = RequestMeta {
+            endpoint: "/v1/chat/completions",
+            user: None,
+            prompt: None,
+            api_key_id: None,
+            timeout: None,
+            cancel: None,
+        };
+        let finished = |status, completion_tokens| Finished {
+            prompt_tokens: 10,
+            completion_tokens,
+            finish_reason: if status == 200 { "stop" } else { "cancelled" },
+            status,
+            queued: Duration::from_millis(250),
+            generating: Duration::from_secs(2),
+        };
+
+        generation_started(&state, "qwen", &meta, 10);
+        generation_started(&state, "qwen", &meta, 10);
+        assert_eq!(state.stats().in_flight(), 2);
+        generation_finished(&state, "qwen", &meta, &finished(200, 40));
+        generation_finished(&state, "qwen", &meta, &finished(499, 3));
+        assert_eq!(state.stats().in_flight(), 0);
+
+        // Only the completed generation counts towards the model's stats
+        let stats = state.stats().get("qwen").unwrap();
+        assert_eq!((stats.requests, stats.completion_tokens), (1, 40));
+        assert_eq!(stats.generation_ms, 2000);
+
+        let events: Vec<serde_json::Value> = std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|e| serde_json::from_str(&e.message).unwrap())
+            .collect();
+        let types: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+        assert_eq!(
+            types,
+            [
+                "generation.started",
+                "generation.started",
+                "generation.finished",
+                "generation.finished"
+            ]
+        );
+        assert_eq!(events[1]["data"]["in_flight"], 2);
+        let done = &events[2]["data"];
+        assert_eq!(done["model"], "qwen");
+        assert_eq!(done["endpoint"], "/v1/chat/completions");
+        assert_eq!(done["finish_reason"], "stop");
+        assert_eq!(done["duration_ms"], 2250);
+        assert_eq!(done["queue_ms"], 250);
+        assert_eq!(done["tokens_per_sec"], 20.0);
+        assert_eq!(done["in_flight"], 1);
+        assert_eq!(events[3]["data"]["status"], 499);
+    }
+}
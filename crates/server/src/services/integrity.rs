@@ -0,0 +1,187 @@
+//! SHA-256 integrity checks of model files.
+//!
+//! A file's hash is recorded, with its size and modification time, when a
+//! download completes or the file is first verified.  It is reported as
+//! current while size and mtime are unchanged; verifying re-reads the
+//! file and compares it with the recorded hash, or one the user supplies.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::Serialize;
+
+use crate::db::{Database, DbError, FileHashRecord};
+
+#[derive(Debug, thiserror::Error)]
+pub enum IntegrityError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Hash(#[from] gguf_parser::types::GGUFError),
+
+    #[error(transparent)]
+    Db(#[from] DbError),
+}
+
+/// Size and modification time of the file at `path`.
+pub(crate) fn file_stamp(path: &Path) -> std::io::Result<(u64, i64)> {
+    let meta = std::fs::metadata(path)?;
+    let mtime_ms = meta
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64);
+    Ok((meta.len(), mtime_ms))
+}
+
+/// Hashes are keyed by canonical path, so the same file is found whether
+/// it was reached through a download target or a directory scan.
+fn hash_key(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// `record`'s hash, if the file at `path` hasn't changed since it was
+/// taken.
+fn current_hash(record: Option<&FileHashRecord>, path: &Path) -> Option<String> {
+    let record = record?;
+    let (size, mtime_ms) = file_stamp(path).ok()?;
+    (record.size == size && record.mtime_ms == mtime_ms).then(|| record.sha256.clone())
+}
+
+/// Every recorded hash, for [`lookup`].
+pub fn recorded_hashes(db: &Database) -> Result<HashMap<PathBuf, FileHashRecord>, DbError> {
+    Ok(db.list_file_hashes()?.into_iter().collect())
+}
+
+/// The recorded hash of the file at `path`, if it is still current.
+pub fn lookup(hashes: &HashMap<PathBuf, FileHashRecord>, path: &Path) -> Option<String> {
+    current_hash(hashes.get(&hash_key(path)), path)
+}
+
+/// Record `sha256` as the hash of the file at `path` as it is now.
+pub fn record(db: &Database, path: &Path, sha256: &str) -> Result<(), IntegrityError> {
+    let (size, mtime_ms) = file_stamp(path)?;
+    db.set_file_hash(
+        &hash_key(path),
+        &FileHashRecord {
+            sha256: sha256.to_ascii_lowercase(),
+            size,
+            mtime_ms,
+        },
+    )?;
+    Ok(())
+}
+
+/// Forget the recorded hash of `path`, e.g. once the file is deleted.
+pub fn forget(db: &Database, path: &Path) -> Result<(), DbError> {
+    db.delete_file_hash(&hash_key(path))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyStatus {
+    /// The file matches the expected hash.
+    Ok,
+    /// The file no longer matches the expected hash.
+    Mismatch,
+    /// Nothing to compare against; the hash was recorded for next time.
+    Recorded,
+}
+
+/// Outcome of verifying one file.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileVerification {
+    pub path: PathBuf,
+    pub sha256: String,
+    /// The user-supplied hash, or else the recorded one.
+    pub expected: Option<String>,
+    pub status: VerifyStatus,
+}
+
+/// Re-hash `path` and compare it with `expected`, or else the recorded
+/// hash.  A match (or a first hash) is recorded; a mismatch leaves the
+/// record alone so it keeps failing until the file is replaced.
+///
+/// `on_progress` gets the bytes hashed so far; see
+/// [`gguf_parser::hash_file_with_progress`].
+pub fn verify_file(
+    db: &Database,
+    path: &Path,
+    expected: Option<&str>,
+    on_progress: impl FnMut(u64),
+) -> Result<FileVerification, IntegrityError> {
+    let key = hash_key(path);
+    let stamp = file_stamp(path)?;
+    let sha256 = gguf_parser::hash_file_with_progress(path, on_progress)?;
+
+    let expected = match expected {
+        Some(hash) => Some(hash.to_ascii_lowercase()),
+        None => db.get_file_hash(&key)?.map(|r| r.sha256),
+    };
+    let status = match &expected {
+        Some(hash) if *hash != sha256 => VerifyStatus::Mismatch,
+        Some(_) => VerifyStatus::Ok,
+        None => VerifyStatus::Recorded,
+    };
+    if status != VerifyStatus::Mismatch {
+        db.set_file_hash(
+            &key,
+            &FileHashRecord {
+                sha256: sha256.clone(),
+                size: stamp.0,
+                mtime_ms: stamp.1,
+            },
+        )?;
+    }
+    Ok(FileVerification {
+        path: path.to_path_buf(),
+        sha256,
+        expected,
+        status,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn verification_records_then_compares() {
+        let db = Database::open_in_memory();
+        let dir = std::env::temp_dir().join(format!("llama-integrity-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("model.gguf");
+        std::fs::write(&path, b"").unwrap();
+
+        let hashes = recorded_hashes(&db).unwrap();
+        assert_eq!(lookup(&hashes, &path), None);
+
+        let first = verify_file(&db, &path, None, |_| {}).unwrap();
+        assert_eq!(first.status, VerifyStatus::Recorded);
+        assert_eq!(first.sha256, EMPTY_SHA256);
+        let hashes = recorded_hashes(&db).unwrap();
+        assert_eq!(lookup(&hashes, &path).as_deref(), Some(EMPTY_SHA256));
+
+        let again = verify_file(&db, &path, None, |_| {}).unwrap();
+        assert_eq!(again.status, VerifyStatus::Ok);
+        let upper = verify_file(&db, &path, Some(&EMPTY_SHA256.to_uppercase()), |_| {}).unwrap();
+        assert_eq!(upper.status, VerifyStatus::Ok);
+
+        // A modified file is no longer current and fails against the record
+        std::fs::write(&path, b"tampered").unwrap();
+        assert_eq!(lookup(&recorded_hashes(&db).unwrap(), &path), None);
+        let modified = verify_file(&db, &path, None, |_| {}).unwrap();
+        assert_eq!(modified.status, VerifyStatus::Mismatch);
+        assert_eq!(modified.expected.as_deref(), Some(EMPTY_SHA256));
+        // The mismatch isn't recorded, so it keeps failing
+        let still = verify_file(&db, &path, None, |_| {}).unwrap();
+        assert_eq!(still.status, VerifyStatus::Mismatch);
+
+        forget(&db, &path).unwrap();
+        assert!(recorded_hashes(&db).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
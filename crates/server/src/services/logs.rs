@@ -0,0 +1,226 @@
+//! Server and llama.cpp logs for the dashboard.
+//!
+//! A `tracing` layer keeps the last [`BUFFER_CAPACITY`] lines at or above
+//! the `log_stream_level` for `GET /api/logs`, and once the server has
+//! attached the event bus broadcasts them to admin clients as `logs.line`
+//! events, at most [`LINES_PER_SECOND`]; lines over the limit are only
+//! counted, in the `dropped` field of the next event.  llama.cpp's own
+//! output reaches the layer through the log callback under the
+//! `llama.cpp` target.
+//!
+//! Logging from the WebSocket routes is not broadcast, so sending a line
+//! can't produce another one.
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::layer::{Context, Layer};
+
+use crate::services::events::EventBus;
+
+/// Lines kept for `GET /api/logs`.
+pub const BUFFER_CAPACITY: usize = 1000;
+
+/// Most `logs.line` events broadcast per second.
+pub const LINES_PER_SECOND: u32 = 20;
+
+/// Targets whose lines are kept but never broadcast.
+const QUIET_TARGETS: &[&str] = &["llama_dashboard::routes::ws", "tungstenite"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    pub timestamp: String,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// The process-wide capture that `main` installs as a layer.
+pub fn capture() -> &'static LogCapture {
+    static CAPTURE: OnceLock<LogCapture> = OnceLock::new();
+    CAPTURE.get_or_init(LogCapture::default)
+}
+
+#[derive(Clone)]
+pub struct LogCapture {
+    inner: Arc<Mutex<Inner>>,
+    events: Arc<OnceLock<EventBus>>,
+}
+
+struct Inner {
+    level: LevelFilter,
+    lines: VecDeque<LogLine>,
+    window_start: Instant,
+    sent_in_window: u32,
+    dropped: u64,
+}
+
+impl Default for LogCapture {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                level: LevelFilter::INFO,
+                lines: VecDeque::with_capacity(BUFFER_CAPACITY),
+                window_start: Instant::now(),
+                sent_in_window: 0,
+                dropped: 0,
+            })),
+            events: Arc::new(OnceLock::new()),
+        }
+    }
+}
+
+impl LogCapture {
+    /// Layer feeding this capture.
+    pub fn layer(&self) -> LogLayer {
+        LogLayer(self.clone())
+    }
+
+    /// Start broadcasting lines at or above `level` on `events`.
+    pub fn attach(&self, events: EventBus, level: LevelFilter) {
+        self.inner.lock().unwrap().level = level;
+        let _ = self.events.set(events);
+    }
+
+    /// The last `n` lines, oldest first.
+    pub fn tail(&self, n: usize) -> Vec<LogLine> {
+        let inner = self.inner.lock().unwrap();
+        let skip = inner.lines.len().saturating_sub(n);
+        inner.lines.iter().skip(skip).cloned().collect()
+    }
+
+    fn record(&self, line: LogLine, broadcast: bool) {
+        let dropped = {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.lines.len() == BUFFER_CAPACITY {
+                inner.lines.pop_front();
+            }
+            inner.lines.push_back(line.clone());
+            if !broadcast || self.events.get().is_none() {
+                return;
+            }
+            if inner.window_start.elapsed() >= Duration::from_secs(1) {
+                inner.window_start = Instant::now();
+                inner.sent_in_window = 0;
+            }
+            if inner.sent_in_window == LINES_PER_SECOND {
+                inner.dropped += 1;
+                return;
+            }
+            inner.sent_in_window += 1;
+            std::mem::take(&mut inner.dropped)
+        };
+        if let Some(events) = self.events.get() {
+            let mut data = serde_json::to_value(&line).unwrap_or_default();
+            if dropped > 0 {
+                data["dropped"] = dropped.into();
+            }
+            events.send_admin_only("logs.line", data);
+        }
+    }
+}
+
+pub struct LogLayer(LogCapture);
+
+thread_local! {
+    /// Set while a line is being recorded on this thread.
+    static RECORDING: Cell<bool> = const { Cell::new(false) };
+}
+
+impl<S: tracing::Subscriber> Layer<S> for LogLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let meta = event.metadata();
+        if self.0.inner.lock().unwrap().level < *meta.level() || RECORDING.get() {
+            return;
+        }
+        RECORDING.set(true);
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let line = LogLine {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            level: meta.level().to_string(),
+            target: meta.target().to_string(),
+            message: visitor.0,
+            request_id: crate::middleware::request_id::current(),
+        };
+        let quiet = QUIET_TARGETS.iter().any(|t| meta.target().starts_with(t));
+        self.0.record(line, !quiet);
+        RECORDING.set(false);
+    }
+}
+
+/// `message key=value …`, like the console output.
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0.insert_str(0, value);
+        } else {
+            let _ = write!(self.0, " {}={value}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0.insert_str(0, &format!("{value:?}"));
+        } else {
+            let _ = write!(self.0, " {}={value:?}", field.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[test]
+    fn keeps_and_broadcasts_lines_at_the_level() {
+        let capture = LogCapture::default();
+        let bus = EventBus::new();
+        let (mut rx, _) = bus.subscribe();
+        capture.attach(bus, LevelFilter::INFO);
+
+        let subscriber = tracing_subscriber::registry().with(capture.layer());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("too verbose");
+            tracing::info!(model = "qwen", "Model loaded");
+            tracing::warn!(target: "llama_dashboard::routes::ws", "WebSocket client disconnected");
+            for i in 0..LINES_PER_SECOND + 5 {
+                tracing::error!("burst {i}");
+            }
+        });
+
+        let lines = capture.tail(3);
+        assert_eq!(lines[2].message, format!("burst {}", LINES_PER_SECOND + 4));
+        let all = capture.tail(BUFFER_CAPACITY);
+        assert_eq!(all.len(), 2 + LINES_PER_SECOND as usize + 5);
+        assert_eq!(all[0].message, "Model loaded model=qwen");
+        assert_eq!(all[0].level, "INFO");
+        assert_eq!(all[1].target, "llama_dashboard::routes::ws");
+
+        // The ws line is kept but not sent; the burst is cut at the limit
+        let sent: Vec<serde_json::Value> = std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|e| serde_json::from_str(&e.message).unwrap())
+            .collect();
+        assert_eq!(sent.len(), LINES_PER_SECOND as usize);
+        assert_eq!(sent[0]["type"], "logs.line");
+        assert!(sent[0].get("seq").is_none());
+        assert_eq!(sent[0]["data"]["message"], "Model loaded model=qwen");
+        assert!(
+            sent.iter()
+                .all(|e| e["data"]["target"] != "llama_dashboard::routes::ws")
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Pre-load memory checks.
+//!
+//! Compares a model's estimated footprint (see
+//! [`gguf_parser::estimate_memory`]) against free system RAM and, in GPU
+//! builds, free VRAM for the offloaded part, so a load that cannot fit is
+//! refused up front instead of thrashing swap.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::services::model_manager::format_bytes;
+
+/// Whether this binary was built with a GPU backend.
+const GPU_BUILD: bool = cfg!(any(feature = "cuda", feature = "vulkan", feature = "rocm"));
+
+/// Outcome of a memory check for one model.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryCheck {
+    pub estimate: gguf_parser::MemoryEstimate,
+    pub ram_available_bytes: u64,
+    /// Free memory across GPU devices; `None` in CPU-only builds.
+    pub vram_free_bytes: Option<u64>,
+    pub available_bytes: u64,
+    pub fits: bool,
+    /// e.g. "needs ~42.0 GiB, 14.0 GiB available"
+    pub summary: String,
+}
+
+/// The footprint-relevant part of the load parameters.
+///
+/// Only an explicit `flash_attn = Some(true)` drops the attention score
+/// buffer from the estimate; `auto` may still resolve to off.  CPU-only
+/// builds keep every layer on the host whatever `n_gpu_layers` says.
+pub fn context_shape(
+    model_params: &llama_core::ModelParams,
+    params: &llama_core::ContextParams,
+) -> gguf_parser::ContextShape {
+    gguf_parser::ContextShape {
+        n_ctx: params.n_ctx,
+        flash_attn: params.flash_attn == Some(true),
+        type_k: params
+            .type_k
+            .map_or(gguf_parser::KvElemSize::F16, kv_elem_size),
+        type_v: params
+            .type_v
+            .map_or(gguf_parser::KvElemSize::F16, kv_elem_size),
+        n_gpu_layers: if GPU_BUILD {
+            model_params.n_gpu_layers
+        } else {
+            0
+        },
+    }
+}
+
+fn kv_elem_size(t: llama_core::KvCacheType) -> gguf_parser::KvElemSize {
+    use gguf_parser::KvElemSize as S;
+    use llama_core::KvCacheType as T;
+    match t {
+        T::F32 => S::F32,
+        T::F16 => S::F16,
+        T::Bf16 => S::BF16,
+        T::Q8_0 => S::Q8_0,
+        T::Q4_0 => S::Q4_0,
+        T::Q4_1 => S::Q4_1,
+        T::Iq4Nl => S::IQ4_NL,
+        T::Q5_0 => S::Q5_0,
+        T::Q5_1 => S::Q5_1,
+    }
+}
+
+/// Estimate what loading `path` with `params` needs and whether it fits
+/// in the memory currently free.
+pub fn check_model(
+    path: &Path,
+    model_params: &llama_core::ModelParams,
+    params: &llama_core::ContextParams,
+) -> Result<MemoryCheck, gguf_parser::types::GGUFError> {
+    let scan = gguf_parser::quick_scan(path)?;
+    let estimate = gguf_parser::estimate_memory(&scan, &context_shape(model_params, params));
+
+    let mut sys = sysinfo::System::new();
+    sys.refresh_memory();
+    let ram_available_bytes = sys.available_memory();
+
+    let vram_free_bytes = GPU_BUILD.then(|| {
+        llama_core::LlamaBackend::init()
+            .gpu_devices()
+            .iter()
+            .map(|d| d.free_bytes)
+            .sum()
+    });
+
+    // Offloaded layers live in VRAM, the rest in RAM
+    let available_bytes = ram_available_bytes + vram_free_bytes.unwrap_or(0);
+    let fits = estimate.host_bytes <= ram_available_bytes
+        && estimate.device_bytes <= vram_free_bytes.unwrap_or(0);
+
+    let summary = match vram_free_bytes {
+        Some(vram) => format!(
+            "needs ~{} RAM + {} VRAM, {} RAM + {} VRAM available",
+            format_bytes(estimate.host_bytes),
+            format_bytes(estimate.device_bytes),
+            format_bytes(ram_available_bytes),
+            format_bytes(vram)
+        ),
+        None => format!(
+            "needs ~{}, {} available",
+            format_bytes(estimate.total_bytes),
+            format_bytes(available_bytes)
+        ),
+    };
+
+    Ok(MemoryCheck {
+        estimate,
+        ram_available_bytes,
+        vram_free_bytes,
+        available_bytes,
+        fits,
+        summary,
+    })
+}
@@ -1,2 +1,17 @@
+pub mod api_keys;
+pub mod chat_export;
+pub mod chat_retention;
+pub mod downloads;
+pub mod events;
 pub mod inference;
+pub mod integrity;
+pub mod logs;
+pub mod memory;
 pub mod model_manager;
+pub mod rate_limit;
+pub mod request_log;
+pub mod resources;
+pub mod stats;
+pub mod tls;
+#[cfg(unix)]
+pub mod uds;
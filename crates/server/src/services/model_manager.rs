@@ -11,13 +11,17 @@
 //! - llama.cpp Router Mode (server-models.h / server-context.cpp)
 //! - Ollama scheduler (sched.go)
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex, RwLock};
-use std::time::Instant;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 
 use tracing::{info, warn};
 
+use crate::db::{Database, ScanCacheRecord};
+use crate::services::integrity::file_stamp;
+use crate::state::{ShutdownSignal, shutdown_requested};
+
 //  Types
 
 /// Status of a model slot.
@@ -27,6 +31,8 @@ pub enum ModelStatus {
     Loading,
     Ready,
     Unloading,
+    /// The last load attempt failed; the slot keeps the error.
+    Failed,
 }
 
 /// A loaded model together with its inference context.
@@ -35,6 +41,17 @@ pub struct LoadedModel {
     pub path: PathBuf,
     pub model: Arc<llama_core::LlamaModel>,
     pub context: Mutex<llama_core::LlamaContext>,
+    /// Sequences `context` holds, readable without taking its lock.
+    pub n_seq_max: u32,
+    /// What the model can serve, from its GGUF metadata.
+    pub capabilities: Vec<gguf_parser::Capability>,
+}
+
+impl LoadedModel {
+    /// Whether the model can serve `capability`.
+    pub fn supports(&self, capability: gguf_parser::Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
 }
 
 /// Metadata for one model slot visible from the outside.
@@ -44,13 +61,205 @@ pub struct SlotInfo {
     pub path: String,
     pub status: ModelStatus,
     pub last_used: u64, // millis since manager creation
+    /// Last reported weight-loading fraction (0.0–1.0).
+    pub progress: f32,
+    /// Exempt from LRU eviction and idle sweeping.
+    pub pinned: bool,
+    /// Pre-load footprint estimate (weights + KV cache).
+    pub estimated_bytes: u64,
+    /// The KV cache share of `estimated_bytes`, at the configured cache
+    /// types.
+    pub kv_cache_bytes: u64,
+    /// Weights as reported by llama.cpp plus the KV cache estimate;
+    /// `None` until loaded.
+    pub resident_bytes: Option<u64>,
+    /// Sequences the context holds, i.e. how many requests can share it;
+    /// `None` until loaded.
+    pub n_seq_max: Option<u32>,
+    /// Why the last load failed (status `failed` only).
+    pub error: Option<String>,
+    pub failed_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Usage summary; filled in by [`AppState::slot_info`].
+    ///
+    /// [`AppState::slot_info`]: crate::state::AppState::slot_info
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<crate::services::stats::StatsSummary>,
+}
+
+/// A model loading progress update.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LoadProgress {
+    pub id: String,
+    pub progress: f32,
+}
+
+/// Minimum spacing between published progress updates.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// State of a background load job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadJobState {
+    Loading,
+    Ready,
+    Failed,
+}
+
+/// A background load started through the management API.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LoadJob {
+    pub job_id: String,
+    pub id: String,
+    pub state: LoadJobState,
+    /// Failure message, retained until the next load attempt.
+    pub error: Option<String>,
+}
+
+/// How long a model stays loaded after its last request, as in Ollama's
+/// `keep_alive`: a duration string (`"5m"`, `"1h30m"`), integer seconds,
+/// `0` to unload once the request finishes, or a negative value to keep
+/// it loaded indefinitely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(try_from = "RawKeepAlive")]
+pub enum KeepAlive {
+    For(Duration),
+    Forever,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum RawKeepAlive {
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl TryFrom<RawKeepAlive> for KeepAlive {
+    type Error = String;
+
+    fn try_from(raw: RawKeepAlive) -> Result<Self, Self::Error> {
+        match raw {
+            RawKeepAlive::Int(n) if n < 0 => Ok(Self::Forever),
+            RawKeepAlive::Int(n) => Ok(Self::For(Duration::from_secs(n as u64))),
+            RawKeepAlive::Float(f) if f < 0.0 => Ok(Self::Forever),
+            RawKeepAlive::Float(f) => Duration::try_from_secs_f64(f)
+                .map(Self::For)
+                .map_err(|_| format!("invalid keep_alive: {f}")),
+            RawKeepAlive::Str(s) => s.parse(),
+        }
+    }
+}
+
+impl std::str::FromStr for KeepAlive {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || format!("invalid keep_alive '{s}'; use e.g. \"5m\", \"30s\", 0 or -1");
+        if let Ok(secs) = s.parse::<f64>() {
+            return RawKeepAlive::Float(secs).try_into();
+        }
+        match s.strip_prefix('-') {
+            Some(negated) => match parse_go_duration(negated).ok_or_else(invalid)? {
+                d if d.is_zero() => Ok(Self::For(d)),
+                _ => Ok(Self::Forever),
+            },
+            None => parse_go_duration(s).map(Self::For).ok_or_else(invalid),
+        }
+    }
+}
+
+/// Go-style duration: one or more `<number><unit>` groups, e.g. `1h30m`.
+fn parse_go_duration(s: &str) -> Option<Duration> {
+    if s.is_empty() {
+        return None;
+    }
+    let mut total = Duration::ZERO;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let num_len = rest.find(|c: char| !(c.is_ascii_digit() || c == '.'))?;
+        let value: f64 = rest[..num_len].parse().ok()?;
+        rest = &rest[num_len..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let scale = match &rest[..unit_len] {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return None,
+        };
+        rest = &rest[unit_len..];
+        total = total.checked_add(Duration::try_from_secs_f64(value * scale).ok()?)?;
+    }
+    Some(total)
+}
+
+/// Load outcome published to [`ModelManager::wait_ready`] callers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LoadSignal {
+    Loading,
+    Ready,
+    Failed(String),
+}
+
+/// Outcome of [`ModelManager::drain_and_unload`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct UnloadReport {
+    pub unloaded: Vec<String>,
+    /// Models still in use or loading when the timeout ran out.
+    pub skipped: Vec<String>,
+}
+
+/// Why [`ModelManager::wait_ready`] gave up.
+#[derive(Debug, thiserror::Error)]
+pub enum WaitError {
+    #[error("Model '{0}' is not loading")]
+    NotLoading(String),
+
+    #[error("Timed out waiting for model '{0}' to load")]
+    Timeout(String),
+
+    #[error("{0}")]
+    Failed(String),
 }
 
 /// Internal slot tracked by the manager.
 struct ModelSlot {
+    /// Model id as first seen (slot keys are lower-cased).
+    id: String,
     status: ModelStatus,
     loaded: Option<Arc<LoadedModel>>,
     last_used: Instant,
+    progress: f32,
+    pinned: bool,
+    /// Per-request override of the global idle timeout.
+    keep_alive: Option<KeepAlive>,
+    estimate: gguf_parser::MemoryEstimate,
+    resident_bytes: Option<u64>,
+    /// Load failure kept for the dashboard until cleared or retried.
+    failure: Option<(String, chrono::DateTime<chrono::Utc>)>,
+    /// Signals waiters when a `Loading` slot settles.
+    signal: tokio::sync::watch::Sender<LoadSignal>,
+    /// Stands in for a request's reference in tests, which can't build a
+    /// [`LoadedModel`].
+    #[cfg(test)]
+    held: bool,
+}
+
+/// llama.cpp build capabilities, behind a trait so tests can fake them.
+pub trait Capabilities: Send + Sync {
+    fn supports_gpu_offload(&self) -> bool;
+}
+
+/// The capabilities of the linked llama.cpp.
+struct NativeCapabilities;
+
+impl Capabilities for NativeCapabilities {
+    fn supports_gpu_offload(&self) -> bool {
+        llama_core::LlamaBackend::supports_gpu_offload()
+    }
 }
 
 /// Configuration for the model manager.
@@ -58,39 +267,601 @@ struct ModelSlot {
 pub struct ModelManagerConfig {
     /// Maximum number of concurrently loaded models (0 = unlimited).
     pub max_models: usize,
+    /// Cap on the summed memory footprint of loaded models (0 = unlimited).
+    pub max_memory_bytes: u64,
     /// Idle timeout in seconds (0 = disabled).
-    #[allow(dead_code)]
     pub idle_timeout_secs: u64,
     /// Default model params for auto-loading.
-    #[allow(dead_code)]
     pub default_n_gpu_layers: i32,
+    /// Default multi-GPU placement; see [`llama_core::ModelParams`].
+    pub default_split_mode: llama_core::SplitMode,
+    pub default_main_gpu: i32,
+    /// Empty leaves the split to llama.cpp.
+    pub default_tensor_split: Vec<f32>,
+    /// Default RoPE scaling; empty keeps each model's own.
+    pub default_rope: RopeSettings,
+    /// Default flash attention; `None` lets llama.cpp decide.
+    pub default_flash_attn: Option<bool>,
+    /// Default KV cache types; `None` is f16.
+    pub default_type_k: Option<llama_core::KvCacheType>,
+    pub default_type_v: Option<llama_core::KvCacheType>,
     /// Default context size for auto-loading.
-    #[allow(dead_code)]
     pub default_ctx_size: u32,
+    /// Sequences each context is created with, so that many requests can
+    /// share a loaded model.
+    pub parallel: u32,
+    /// KV cache defragmentation threshold for contexts that leave it
+    /// unset; negative disables it.
+    pub defrag_thold: f32,
+    /// Accept model paths outside the configured directories.
+    pub allow_external_paths: bool,
+    /// Descend into symlinked directories when scanning.
+    pub follow_dir_symlinks: bool,
+    /// Model ids pinned as soon as they load.
+    pub pinned_models: Vec<String>,
 }
 
 impl Default for ModelManagerConfig {
     fn default() -> Self {
         Self {
             max_models: 4,
+            max_memory_bytes: 0,
             idle_timeout_secs: 0,
             default_n_gpu_layers: -1,
+            default_split_mode: llama_core::SplitMode::default(),
+            default_main_gpu: 0,
+            default_tensor_split: Vec::new(),
+            default_rope: RopeSettings::default(),
+            default_flash_attn: None,
+            default_type_k: None,
+            default_type_v: None,
             default_ctx_size: 4096,
+            parallel: 1,
+            defrag_thold: -1.0,
+            allow_external_paths: false,
+            follow_dir_symlinks: false,
+            pinned_models: Vec::new(),
+        }
+    }
+}
+
+/// Per-model load parameters.  Unset fields fall back to the next layer:
+/// request → stored settings → global defaults.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ModelSettings {
+    #[serde(default)]
+    pub ctx_size: Option<u32>,
+    #[serde(default)]
+    pub n_gpu_layers: Option<i32>,
+    #[serde(default)]
+    pub split_mode: Option<llama_core::SplitMode>,
+    #[serde(default)]
+    pub main_gpu: Option<i32>,
+    /// Relative share of the offloaded layers per GPU.
+    #[serde(default)]
+    pub tensor_split: Option<Vec<f32>>,
+    /// GGUF metadata to override when loading.
+    #[serde(default)]
+    pub kv_overrides: Option<Vec<llama_core::KvOverride>>,
+    #[serde(default)]
+    pub n_threads: Option<i32>,
+    #[serde(default)]
+    pub flash_attn: Option<bool>,
+    /// KV cache type for both K and V; `type_k` / `type_v` override it.
+    #[serde(default)]
+    pub cache_type: Option<llama_core::KvCacheType>,
+    #[serde(default)]
+    pub type_k: Option<llama_core::KvCacheType>,
+    /// Quantized types need flash attention.
+    #[serde(default)]
+    pub type_v: Option<llama_core::KvCacheType>,
+    #[serde(flatten)]
+    pub rope: RopeSettings,
+}
+
+/// RoPE scaling for contexts past the training length; unset fields keep
+/// the model's own values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RopeSettings {
+    #[serde(default)]
+    pub rope_scaling_type: Option<llama_core::RopeScalingType>,
+    #[serde(default)]
+    pub rope_freq_base: Option<f32>,
+    #[serde(default)]
+    pub rope_freq_scale: Option<f32>,
+    #[serde(default)]
+    pub yarn_ext_factor: Option<f32>,
+    #[serde(default)]
+    pub yarn_attn_factor: Option<f32>,
+    #[serde(default)]
+    pub yarn_beta_fast: Option<f32>,
+    #[serde(default)]
+    pub yarn_beta_slow: Option<f32>,
+    #[serde(default)]
+    pub yarn_orig_ctx: Option<u32>,
+}
+
+impl RopeSettings {
+    /// `self`, with unset fields taken from `base`.
+    pub fn or(self, base: &RopeSettings) -> RopeSettings {
+        RopeSettings {
+            rope_scaling_type: self.rope_scaling_type.or(base.rope_scaling_type),
+            rope_freq_base: self.rope_freq_base.or(base.rope_freq_base),
+            rope_freq_scale: self.rope_freq_scale.or(base.rope_freq_scale),
+            yarn_ext_factor: self.yarn_ext_factor.or(base.yarn_ext_factor),
+            yarn_attn_factor: self.yarn_attn_factor.or(base.yarn_attn_factor),
+            yarn_beta_fast: self.yarn_beta_fast.or(base.yarn_beta_fast),
+            yarn_beta_slow: self.yarn_beta_slow.or(base.yarn_beta_slow),
+            yarn_orig_ctx: self.yarn_orig_ctx.or(base.yarn_orig_ctx),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == RopeSettings::default()
+    }
+
+    pub fn apply(&self, params: &mut llama_core::ContextParams) {
+        params.rope_scaling_type = self.rope_scaling_type;
+        params.rope_freq_base = self.rope_freq_base;
+        params.rope_freq_scale = self.rope_freq_scale;
+        params.yarn_ext_factor = self.yarn_ext_factor;
+        params.yarn_attn_factor = self.yarn_attn_factor;
+        params.yarn_beta_fast = self.yarn_beta_fast;
+        params.yarn_beta_slow = self.yarn_beta_slow;
+        params.yarn_orig_ctx = self.yarn_orig_ctx;
+    }
+}
+
+impl ModelSettings {
+    /// `self`, with unset fields taken from `base`.
+    pub fn or(self, base: &ModelSettings) -> ModelSettings {
+        ModelSettings {
+            ctx_size: self.ctx_size.or(base.ctx_size),
+            n_gpu_layers: self.n_gpu_layers.or(base.n_gpu_layers),
+            split_mode: self.split_mode.or(base.split_mode),
+            main_gpu: self.main_gpu.or(base.main_gpu),
+            tensor_split: self.tensor_split.or_else(|| base.tensor_split.clone()),
+            kv_overrides: self.kv_overrides.or_else(|| base.kv_overrides.clone()),
+            n_threads: self.n_threads.or(base.n_threads),
+            flash_attn: self.flash_attn.or(base.flash_attn),
+            cache_type: self.cache_type.or(base.cache_type),
+            type_k: self.type_k.or(base.type_k),
+            type_v: self.type_v.or(base.type_v),
+            rope: self.rope.or(&base.rope),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == ModelSettings::default()
+    }
+
+    /// llama.cpp parameters for these settings; unset fields use the
+    /// llama-core defaults.
+    pub fn load_params(&self) -> (llama_core::ModelParams, llama_core::ContextParams) {
+        let mut model_params = llama_core::ModelParams::default();
+        if let Some(n) = self.n_gpu_layers {
+            model_params.n_gpu_layers = n;
+        }
+        if let Some(mode) = self.split_mode {
+            model_params.split_mode = mode;
+        }
+        if let Some(gpu) = self.main_gpu {
+            model_params.main_gpu = gpu;
+        }
+        if let Some(split) = &self.tensor_split {
+            model_params.tensor_split = split.clone();
+        }
+        if let Some(overrides) = &self.kv_overrides {
+            model_params.kv_overrides = overrides.clone();
+        }
+        let mut ctx_params = llama_core::ContextParams {
+            flash_attn: self.flash_attn,
+            type_k: self.type_k.or(self.cache_type),
+            type_v: self.type_v.or(self.cache_type),
+            ..Default::default()
+        };
+        if let Some(n) = self.ctx_size {
+            ctx_params.n_ctx = n;
+        }
+        if let Some(n) = self.n_threads {
+            ctx_params.n_threads = n;
+            ctx_params.n_threads_batch = n;
+        }
+        self.rope.apply(&mut ctx_params);
+        (model_params, ctx_params)
+    }
+}
+
+/// A special token's id and raw text.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct SpecialToken {
+    pub id: i32,
+    pub text: Option<String>,
+}
+
+/// Vocabulary details for debugging chat templates.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct TokenizerInfo {
+    /// `model` when read from the loaded model, `gguf` when read from
+    /// file metadata (fields past the scan window are then `null`).
+    pub source: &'static str,
+    pub n_vocab: Option<i32>,
+    pub bos: Option<SpecialToken>,
+    pub eos: Option<SpecialToken>,
+    pub eot: Option<SpecialToken>,
+    /// Whether tokenization prepends BOS.
+    pub add_bos: Option<bool>,
+    /// Well-known format of the embedded chat template, e.g. `chatml`.
+    pub chat_template_name: Option<&'static str>,
+}
+
+impl TokenizerInfo {
+    pub fn from_model(model: &llama_core::LlamaModel) -> Self {
+        let vocab = model.vocab();
+        // LLAMA_TOKEN_NULL (-1) means the vocab has no such token
+        let special = |id: i32| {
+            (id >= 0).then(|| SpecialToken {
+                id,
+                text: vocab.token_get_text(id),
+            })
+        };
+        Self {
+            source: "model",
+            n_vocab: Some(vocab.n_tokens()),
+            bos: special(vocab.bos()),
+            eos: special(vocab.eos()),
+            eot: special(vocab.eot()),
+            add_bos: Some(vocab.add_bos()),
+            chat_template_name: model
+                .chat_template()
+                .and_then(|t| llama_core::template_name(&t)),
+        }
+    }
+
+    pub fn from_scan(scan: &gguf_parser::QuickScanResult) -> Self {
+        let meta = scan.tokenizer();
+        let special = |id: Option<u32>| {
+            id.map(|id| SpecialToken {
+                id: id as i32,
+                text: scan.token_text(id).map(String::from),
+            })
+        };
+        Self {
+            source: "gguf",
+            n_vocab: meta.n_vocab.map(|n| n as i32),
+            bos: special(meta.bos_token_id),
+            eos: special(meta.eos_token_id),
+            eot: special(meta.eot_token_id),
+            add_bos: meta.add_bos_token,
+            chat_template_name: scan
+                .chat_template
+                .as_deref()
+                .and_then(llama_core::template_name),
+        }
+    }
+}
+
+/// Model shape from file metadata, for a model that is not loaded;
+/// `None` when the scan window lacks the attention keys.
+pub fn model_info_from_scan(scan: &gguf_parser::QuickScanResult) -> Option<llama_core::ModelInfo> {
+    let dims = gguf_parser::KvDims::from_scan(scan)?;
+    let arch = scan.architecture.as_deref().unwrap_or("llama");
+    let arch_f32 = |suffix: &str| {
+        scan.get(&format!("{arch}.{suffix}"))
+            .and_then(gguf_parser::GGUFValue::as_f32)
+    };
+    Some(llama_core::ModelInfo {
+        n_ctx_train: scan.context_length? as i32,
+        n_embd: scan.embedding_length? as i32,
+        n_layer: dims.n_layer as i32,
+        n_head: dims.n_head as i32,
+        n_head_kv: dims.n_head_kv as i32,
+        rope_freq_base: arch_f32("rope.freq_base"),
+        // llama.cpp trains-in the inverse of the linear scaling factor
+        rope_freq_scale_train: arch_f32("rope.scaling.factor")
+            .filter(|&f| f > 0.0)
+            .map_or(1.0, |f| 1.0 / f),
+        vocab_type: scan
+            .get("tokenizer.ggml.model")
+            .and_then(gguf_parser::GGUFValue::as_str)
+            .map_or(
+                llama_core::VocabType::Other,
+                llama_core::VocabType::from_gguf_model,
+            ),
+    })
+}
+
+//  Id matching
+
+/// Outcome of matching a requested model name against known ids.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdMatch {
+    /// Exactly one id matched.
+    Unique(String),
+    /// Several ids matched a prefix or substring.
+    Ambiguous(Vec<String>),
+    NotFound,
+}
+
+/// Match `query` against `ids`, case-insensitively.
+///
+/// An exact match always wins.  Otherwise ids starting with the query
+/// are considered, then ids containing it; the first tier with any
+/// candidates decides, and it must contain exactly one to resolve.
+pub fn match_model_id<'a>(query: &str, ids: impl IntoIterator<Item = &'a str>) -> IdMatch {
+    let query = query.to_lowercase();
+    let ids: Vec<&str> = ids.into_iter().collect();
+
+    if let Some(id) = ids.iter().find(|id| id.to_lowercase() == query) {
+        return IdMatch::Unique(id.to_string());
+    }
+    if query.is_empty() {
+        return IdMatch::NotFound;
+    }
+
+    let tiers: [fn(&str, &str) -> bool; 2] = [|id, q| id.starts_with(q), |id, q| id.contains(q)];
+    for tier in tiers {
+        let mut hits: Vec<String> = ids
+            .iter()
+            .filter(|id| tier(&id.to_lowercase(), &query))
+            .map(|id| id.to_string())
+            .collect();
+        match hits.len() {
+            0 => continue,
+            1 => return IdMatch::Unique(hits.remove(0)),
+            _ => {
+                hits.sort();
+                hits.dedup();
+                return IdMatch::Ambiguous(hits);
+            }
+        }
+    }
+    IdMatch::NotFound
+}
+
+/// Why a model path was rejected.
+#[derive(Debug, thiserror::Error)]
+pub enum ModelPathError {
+    #[error("Model file '{path}' is not accessible: {source}")]
+    Inaccessible {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Model path '{0}' is outside the configured model directories")]
+    OutsideModelDirs(PathBuf),
+}
+
+impl ModelSlot {
+    /// Whether a request holds the model.  The slot itself holds one
+    /// reference; any more means someone is actively using it.
+    fn in_use(&self) -> bool {
+        #[cfg(test)]
+        if self.held {
+            return true;
+        }
+        self.loaded
+            .as_ref()
+            .is_some_and(|l| Arc::strong_count(l) > 1)
+    }
+
+    /// Bytes this slot counts against `max_memory_bytes`.
+    fn footprint(&self) -> u64 {
+        match self.status {
+            ModelStatus::Failed => 0,
+            _ => self.resident_bytes.unwrap_or(self.estimate.total_bytes),
+        }
+    }
+}
+
+/// Human-readable binary size, e.g. `42.0 GiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+//  Catalogue
+
+/// Identity of a scanned model file.  A file rewritten in place (e.g.
+/// re-quantized under the same name) gets a new key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CatalogueKey {
+    path: PathBuf,
+    size: u64,
+    mtime: Option<SystemTime>,
+}
+
+impl CatalogueKey {
+    fn of_path(path: &Path) -> std::io::Result<Self> {
+        let path = std::fs::canonicalize(path)?;
+        let meta = std::fs::metadata(&path)?;
+        Ok(Self {
+            path,
+            size: meta.len(),
+            mtime: meta.modified().ok(),
+        })
+    }
+
+    fn of(entry: &gguf_parser::ModelEntry) -> Self {
+        let path = std::fs::canonicalize(&entry.path).unwrap_or_else(|_| entry.path.clone());
+        let mtime = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        Self {
+            path,
+            size: entry.file_size,
+            mtime,
+        }
+    }
+}
+
+/// A cached quick scan and the file identity it was taken from.
+type CachedScan = (CatalogueKey, Arc<gguf_parser::QuickScanResult>);
+
+/// A directory-scan result and the (size, mtime ms) it was taken at.
+type StoredScan = ((u64, i64), gguf_parser::FileScan);
+
+/// Per-file directory scans, keyed by canonical path.  A stored scan is
+/// used while the file's size and mtime (ms) still match; misses are read
+/// from disk and written back to memory and, if set, the database.
+struct FileScanCache<'a> {
+    memory: &'a Mutex<HashMap<PathBuf, StoredScan>>,
+    db: Option<&'a Database>,
+    /// Ignore stored scans, but still record fresh ones.
+    force: bool,
+    /// Canonical paths of every file looked up.
+    seen: Vec<PathBuf>,
+}
+
+impl FileScanCache<'_> {
+    fn lookup(&self, path: &Path, stamp: (u64, i64)) -> Option<gguf_parser::FileScan> {
+        if let Some((cached, scan)) = self.memory.lock().unwrap().get(path)
+            && *cached == stamp
+        {
+            return Some(scan.clone());
+        }
+        let record = self.db?.get_scan_cache(path).ok()??;
+        if (record.size, record.mtime_ms) != stamp {
+            return None;
+        }
+        let scan: gguf_parser::FileScan = serde_json::from_str(&record.scan).ok()?;
+        self.memory
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), (stamp, scan.clone()));
+        Some(scan)
+    }
+}
+
+impl gguf_parser::ScanCache for FileScanCache<'_> {
+    fn get(&mut self, path: &Path) -> Option<gguf_parser::FileScan> {
+        let path = std::fs::canonicalize(path).ok()?;
+        let stamp = file_stamp(&path).ok()?;
+        let hit = (!self.force).then(|| self.lookup(&path, stamp)).flatten();
+        self.seen.push(path);
+        hit
+    }
+
+    fn put(&mut self, path: &Path, scan: &gguf_parser::FileScan) {
+        let Ok(path) = std::fs::canonicalize(path) else {
+            return;
+        };
+        let Ok(stamp) = file_stamp(&path) else {
+            return;
+        };
+        if let Some(db) = self.db {
+            let stored = serde_json::to_string(scan)
+                .map_err(|e| e.to_string())
+                .and_then(|scan| {
+                    let record = ScanCacheRecord {
+                        size: stamp.0,
+                        mtime_ms: stamp.1,
+                        scan,
+                    };
+                    db.set_scan_cache(&path, &record).map_err(|e| e.to_string())
+                });
+            if let Err(e) = stored {
+                warn!(path = %path.display(), "Failed to store scan result: {e}");
+            }
         }
+        self.memory
+            .lock()
+            .unwrap()
+            .insert(path, (stamp, scan.clone()));
+    }
+}
+
+/// Models added and removed between two directory scans.
+#[derive(Debug, Default)]
+pub struct CatalogueDiff {
+    pub discovered: Vec<gguf_parser::ModelEntry>,
+    pub removed: Vec<gguf_parser::ModelEntry>,
+}
+
+impl CatalogueDiff {
+    pub fn is_empty(&self) -> bool {
+        self.discovered.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Result of the most recent [`ModelManager::rescan`].
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ScanSummary {
+    pub last_scan_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub models: usize,
+    pub discovered: usize,
+    pub removed: usize,
+}
+
+fn diff_catalogue(
+    prev: &HashMap<CatalogueKey, gguf_parser::ModelEntry>,
+    next: &HashMap<CatalogueKey, gguf_parser::ModelEntry>,
+) -> CatalogueDiff {
+    let only_in = |a: &HashMap<CatalogueKey, gguf_parser::ModelEntry>,
+                   b: &HashMap<CatalogueKey, gguf_parser::ModelEntry>| {
+        let mut entries: Vec<_> = a
+            .iter()
+            .filter(|(key, _)| !b.contains_key(*key))
+            .map(|(_, entry)| entry.clone())
+            .collect();
+        entries.sort_by(|x, y| x.id.cmp(&y.id));
+        entries
+    };
+    CatalogueDiff {
+        discovered: only_in(next, prev),
+        removed: only_in(prev, next),
     }
 }
 
+/// Slot map key for a model id.
+fn slot_key(id: &str) -> String {
+    id.to_lowercase()
+}
+
 //  ModelManager
 
 #[derive(Clone)]
 pub struct ModelManager {
-    /// id → slot
+    /// lower-cased id → slot
     slots: Arc<RwLock<HashMap<String, ModelSlot>>>,
     /// Serialises loading (only one model loads at a time).
     load_lock: Arc<Mutex<()>>,
     model_dirs: Arc<RwLock<Vec<PathBuf>>>,
+    /// The directories given to [`new`](Self::new), without the ones
+    /// loads add later.
+    configured_dirs: Arc<Vec<PathBuf>>,
+    /// Throttled load progress updates, see [`subscribe_progress`](Self::subscribe_progress).
+    progress_tx: tokio::sync::broadcast::Sender<LoadProgress>,
+    /// lower-cased id → most recent background load job
+    load_jobs: Arc<Mutex<HashMap<String, LoadJob>>>,
+    /// Lower-cased alias → (alias, model id); mirrors the `aliases` table.
+    aliases: Arc<RwLock<HashMap<String, (String, String)>>>,
+    /// Lower-cased id → display name; mirrors `model_meta.display_name`.
+    display_names: Arc<RwLock<HashMap<String, String>>>,
+    /// Lower-cased id → stored load settings; mirrors `model_settings`.
+    settings: Arc<RwLock<HashMap<String, ModelSettings>>>,
+    /// Catalogue from the last [`rescan`](Self::rescan); `None` before the first.
+    catalogue: Arc<Mutex<Option<HashMap<CatalogueKey, gguf_parser::ModelEntry>>>>,
+    scan_summary: Arc<RwLock<ScanSummary>>,
+    /// Canonical path → quick-scan result, valid while the key matches.
+    metadata_cache: Arc<Mutex<HashMap<PathBuf, CachedScan>>>,
+    /// Canonical path → (size, mtime ms) and directory-scan result.
+    scan_cache: Arc<Mutex<HashMap<PathBuf, StoredScan>>>,
+    /// Persists `scan_cache` across restarts, see [`set_scan_store`](Self::set_scan_store).
+    scan_store: Arc<OnceLock<Arc<Database>>>,
     config: Arc<ModelManagerConfig>,
+    capabilities: Arc<dyn Capabilities>,
     epoch: Instant,
+    /// Reason for maintenance drain mode; new loads are refused while set.
+    drain: Arc<RwLock<Option<String>>>,
 }
 
 impl ModelManager {
@@ -98,9 +869,22 @@ impl ModelManager {
         Self {
             slots: Arc::new(RwLock::new(HashMap::new())),
             load_lock: Arc::new(Mutex::new(())),
+            configured_dirs: Arc::new(model_dirs.clone()),
             model_dirs: Arc::new(RwLock::new(model_dirs)),
+            progress_tx: tokio::sync::broadcast::channel(64).0,
+            load_jobs: Arc::new(Mutex::new(HashMap::new())),
+            aliases: Arc::new(RwLock::new(HashMap::new())),
+            display_names: Arc::new(RwLock::new(HashMap::new())),
+            settings: Arc::new(RwLock::new(HashMap::new())),
+            catalogue: Arc::new(Mutex::new(None)),
+            scan_summary: Arc::new(RwLock::new(ScanSummary::default())),
+            metadata_cache: Arc::new(Mutex::new(HashMap::new())),
+            scan_cache: Arc::new(Mutex::new(HashMap::new())),
+            scan_store: Arc::new(OnceLock::new()),
             config: Arc::new(config),
+            capabilities: Arc::new(NativeCapabilities),
             epoch: Instant::now(),
+            drain: Arc::new(RwLock::new(None)),
         }
     }
 
@@ -115,122 +899,653 @@ impl ModelManager {
         }
     }
 
+    /// Configured model directories.
+    pub fn model_dirs(&self) -> Vec<PathBuf> {
+        self.model_dirs.read().unwrap().clone()
+    }
+
+    /// Keep directory-scan results in `db`, so unchanged files aren't
+    /// read again after a restart.  Only the first call has an effect.
+    pub fn set_scan_store(&self, db: Arc<Database>) {
+        let _ = self.scan_store.set(db);
+    }
+
     /// Scan configured directories for available models.
+    ///
+    /// Files whose size and mtime match a previous scan aren't read again.
+    /// A model found under several directories is listed once.
     pub fn scan_available(&self) -> Vec<gguf_parser::ModelEntry> {
+        self.scan_dirs(false)
+    }
+
+    /// Like [`scan_available`](Self::scan_available), but reads every file
+    /// again and drops stored results of files that are gone.
+    pub fn scan_available_uncached(&self) -> Vec<gguf_parser::ModelEntry> {
+        self.scan_dirs(true)
+    }
+
+    fn scan_dirs(&self, force: bool) -> Vec<gguf_parser::ModelEntry> {
+        let db = self.scan_store.get().map(|db| &**db);
+        let mut cache = FileScanCache {
+            memory: &self.scan_cache,
+            db,
+            force,
+            seen: Vec::new(),
+        };
+        let walk = gguf_parser::WalkOptions {
+            follow_dir_symlinks: self.config.follow_dir_symlinks,
+        };
         let dirs = self.model_dirs.read().unwrap();
         let mut all = Vec::new();
+        let mut listed = HashSet::new();
+        let mut complete = true;
         for dir in dirs.iter() {
-            match gguf_parser::scan_directory(dir) {
-                Ok(entries) => all.extend(entries),
-                Err(e) => warn!(dir = %dir.display(), "Scan failed: {e}"),
+            match gguf_parser::scan_directory_cached(dir, walk, &mut cache) {
+                Ok(entries) => all.extend(
+                    entries
+                        .into_iter()
+                        .filter(|e| listed.insert(e.canonical_path.clone())),
+                ),
+                Err(e) => {
+                    complete = false;
+                    warn!(dir = %dir.display(), "Scan failed: {e}");
+                }
+            }
+        }
+        gguf_parser::disambiguate_ids(&mut all);
+
+        if force && complete {
+            let seen = cache.seen;
+            let keep: HashSet<&PathBuf> = seen.iter().collect();
+            self.scan_cache
+                .lock()
+                .unwrap()
+                .retain(|path, _| keep.contains(path));
+            if let Some(db) = db {
+                match db.prune_scan_cache(&seen) {
+                    Ok(0) => {}
+                    Ok(n) => info!(dropped = n, "Pruned stale scan results"),
+                    Err(e) => warn!("Failed to prune scan results: {e}"),
+                }
             }
         }
         all
     }
 
-    //  Loading / Unloading
-
-    /// Load a model from `path`, returns an `Arc<LoadedModel>`.
-    ///
-    /// If `max_models` would be exceeded, the least-recently-used model
-    /// (with no active references) is evicted first.
-    pub fn load(
+    /// Read all metadata of `path`, arrays included, reusing the last
+    /// result until the file's size or modification time changes.
+    pub fn scan_metadata(
         &self,
         path: &Path,
-        model_params: &llama_core::ModelParams,
-        ctx_params: &llama_core::ContextParams,
-    ) -> Result<Arc<LoadedModel>, llama_core::LlamaError> {
+    ) -> Result<Arc<gguf_parser::QuickScanResult>, gguf_parser::types::GGUFError> {
+        let key = CatalogueKey::of_path(path)?;
+        if let Some((cached_key, scan)) = self.metadata_cache.lock().unwrap().get(&key.path)
+            && *cached_key == key
+        {
+            return Ok(scan.clone());
+        }
+
+        let scan = Arc::new(gguf_parser::full_scan(&key.path)?);
+        self.metadata_cache
+            .lock()
+            .unwrap()
+            .insert(key.path.clone(), (key, scan.clone()));
+        Ok(scan)
+    }
+
+    /// Slot id for the model file at `path`.
+    ///
+    /// Scanned models use their (disambiguated) catalogue id.  Other files
+    /// use the file stem, suffixed if that would clash with a catalogue id.
+    pub fn model_id_for(&self, path: &Path) -> String {
+        let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let available = self.scan_available();
+        let same_file = |p: &Path| std::fs::canonicalize(p).is_ok_and(|p| p == canonical);
+        if let Some(entry) = available.iter().find(|m| same_file(&m.path)) {
+            return entry.id.clone();
+        }
+
         let id = path
             .file_stem()
             .unwrap_or_default()
             .to_string_lossy()
             .to_string();
+        if available.iter().any(|m| m.id.eq_ignore_ascii_case(&id)) {
+            gguf_parser::disambiguated_id(&id, &canonical)
+        } else {
+            id
+        }
+    }
 
-        // Serialise loading
-        let _guard = self.load_lock.lock().unwrap();
+    /// Scan the model directories and diff against the previous catalogue.
+    ///
+    /// The first call only records a baseline and reports no changes.
+    pub fn rescan(&self) -> CatalogueDiff {
+        let next: HashMap<_, _> = self
+            .scan_available()
+            .into_iter()
+            .map(|entry| (CatalogueKey::of(&entry), entry))
+            .collect();
 
-        // If already loaded, just touch + return
-        {
-            let slots = self.slots.read().unwrap();
-            if let Some(slot) = slots.get(&id)
-                && slot.status == ModelStatus::Ready
-                && slot.loaded.is_some()
-            {
-                let loaded = slot.loaded.clone().unwrap();
-                drop(slots);
-                self.touch(&id);
-                info!(id, "Model already loaded, returning existing");
-                return Ok(loaded);
-            }
-        }
+        let mut catalogue = self.catalogue.lock().unwrap();
+        let diff = match catalogue.as_ref() {
+            Some(prev) => diff_catalogue(prev, &next),
+            None => CatalogueDiff::default(),
+        };
+        *self.scan_summary.write().unwrap() = ScanSummary {
+            last_scan_at: Some(chrono::Utc::now()),
+            models: next.len(),
+            discovered: diff.discovered.len(),
+            removed: diff.removed.len(),
+        };
+        *catalogue = Some(next);
+        diff
+    }
 
-        // Evict if needed
-        self.maybe_evict();
+    /// Summary of the most recent [`rescan`](Self::rescan).
+    pub fn scan_summary(&self) -> ScanSummary {
+        self.scan_summary.read().unwrap().clone()
+    }
 
-        // Mark loading
-        {
-            let mut slots = self.slots.write().unwrap();
-            slots.insert(
-                id.clone(),
-                ModelSlot {
-                    status: ModelStatus::Loading,
-                    loaded: None,
-                    last_used: Instant::now(),
-                },
-            );
+    /// Canonicalize `path` and check it lies inside one of the model
+    /// directories, unless `allow_external_paths` is set.
+    pub fn check_model_path(&self, path: &Path) -> Result<PathBuf, ModelPathError> {
+        if self.config.allow_external_paths {
+            return std::fs::canonicalize(path).map_err(|source| ModelPathError::Inaccessible {
+                path: path.to_path_buf(),
+                source,
+            });
         }
+        self.check_inside_model_dirs(path)
+    }
 
-        // Actually load
-        let result = (|| {
-            let model = Arc::new(llama_core::LlamaModel::load_from_file(path, model_params)?);
-            let ctx = llama_core::LlamaContext::new(model.clone(), ctx_params)?;
-            Ok(Arc::new(LoadedModel {
-                id: id.clone(),
+    /// Canonicalize `path` and check it lies inside one of the configured
+    /// model directories, regardless of `allow_external_paths`.  The
+    /// parent directories of loaded models that [`load_or_get`](Self::load_or_get)
+    /// adds to the scan list don't count.
+    pub fn check_inside_model_dirs(&self, path: &Path) -> Result<PathBuf, ModelPathError> {
+        let canonical =
+            std::fs::canonicalize(path).map_err(|source| ModelPathError::Inaccessible {
                 path: path.to_path_buf(),
-                model,
-                context: Mutex::new(ctx),
-            }))
-        })();
+                source,
+            })?;
 
-        match result {
-            Ok(loaded) => {
-                let mut slots = self.slots.write().unwrap();
-                slots.insert(
-                    id.clone(),
-                    ModelSlot {
-                        status: ModelStatus::Ready,
-                        loaded: Some(loaded.clone()),
-                        last_used: Instant::now(),
-                    },
-                );
+        let inside = self.configured_dirs.iter().any(|dir| {
+            std::fs::canonicalize(dir)
+                .map(|dir| canonical.starts_with(dir))
+                .unwrap_or(false)
+        });
+        if inside {
+            Ok(canonical)
+        } else {
+            Err(ModelPathError::OutsideModelDirs(canonical))
+        }
+    }
 
-                // Auto-register the model's parent directory
-                if let Some(parent) = path.parent() {
-                    let canonical =
-                        std::fs::canonicalize(parent).unwrap_or_else(|_| parent.to_path_buf());
-                    drop(slots);
-                    self.add_model_dir(canonical);
+    //  Aliases
+
+    /// Replace the alias table with `(alias, model_id)` pairs.
+    pub fn set_aliases(&self, pairs: impl IntoIterator<Item = (String, String)>) {
+        let mut aliases = self.aliases.write().unwrap();
+        *aliases = pairs
+            .into_iter()
+            .map(|(alias, id)| (alias.to_lowercase(), (alias, id)))
+            .collect();
+    }
+
+    /// Model id an alias points at (case-insensitive).
+    pub fn alias_target(&self, alias: &str) -> Option<String> {
+        let aliases = self.aliases.read().unwrap();
+        aliases.get(&alias.to_lowercase()).map(|(_, id)| id.clone())
+    }
+
+    /// Alias assigned to `model_id`, if any.
+    pub fn alias_of(&self, model_id: &str) -> Option<String> {
+        let aliases = self.aliases.read().unwrap();
+        aliases
+            .values()
+            .find(|(_, id)| id.eq_ignore_ascii_case(model_id))
+            .map(|(alias, _)| alias.clone())
+    }
+
+    /// All `(alias, model_id)` pairs, sorted by alias.
+    pub fn aliases(&self) -> Vec<(String, String)> {
+        let aliases = self.aliases.read().unwrap();
+        let mut pairs: Vec<_> = aliases.values().cloned().collect();
+        pairs.sort();
+        pairs
+    }
+
+    //  Display names
+
+    /// Replace all display names with `(model_id, name)` pairs.
+    pub fn set_display_names(&self, pairs: impl IntoIterator<Item = (String, String)>) {
+        let mut names = self.display_names.write().unwrap();
+        *names = pairs
+            .into_iter()
+            .map(|(id, name)| (slot_key(&id), name))
+            .collect();
+    }
+
+    /// Display name assigned to `model_id`, if any.
+    pub fn display_name_of(&self, model_id: &str) -> Option<String> {
+        let names = self.display_names.read().unwrap();
+        names.get(&slot_key(model_id)).cloned()
+    }
+
+    //  Per-model settings
+
+    /// Replace all stored settings with `(model_id, settings)` pairs.
+    pub fn set_all_settings(&self, pairs: impl IntoIterator<Item = (String, ModelSettings)>) {
+        let mut settings = self.settings.write().unwrap();
+        *settings = pairs
+            .into_iter()
+            .map(|(id, s)| (slot_key(&id), s))
+            .collect();
+    }
+
+    /// Store (or with empty settings, clear) `model_id`'s settings.
+    pub fn set_settings(&self, model_id: &str, model_settings: ModelSettings) {
+        let mut settings = self.settings.write().unwrap();
+        if model_settings.is_empty() {
+            settings.remove(&slot_key(model_id));
+        } else {
+            settings.insert(slot_key(model_id), model_settings);
+        }
+    }
+
+    /// Stored settings of `model_id` (empty if none).
+    pub fn settings(&self, model_id: &str) -> ModelSettings {
+        let settings = self.settings.read().unwrap();
+        settings
+            .get(&slot_key(model_id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// `overrides` merged over `model_id`'s stored settings and the
+    /// global defaults.
+    pub fn effective_settings(&self, model_id: &str, overrides: ModelSettings) -> ModelSettings {
+        let defaults = ModelSettings {
+            ctx_size: Some(self.config.default_ctx_size),
+            n_gpu_layers: Some(self.config.default_n_gpu_layers),
+            split_mode: Some(self.config.default_split_mode),
+            main_gpu: Some(self.config.default_main_gpu),
+            tensor_split: Some(self.config.default_tensor_split.clone())
+                .filter(|split| !split.is_empty()),
+            flash_attn: self.config.default_flash_attn,
+            type_k: self.config.default_type_k,
+            type_v: self.config.default_type_v,
+            rope: self.config.default_rope,
+            ..Default::default()
+        };
+        overrides.or(&self.settings(model_id)).or(&defaults)
+    }
+
+    //  Loading / Unloading
+
+    /// Load a model from `path`, returns an `Arc<LoadedModel>`.
+    ///
+    /// If `max_models` would be exceeded, the least-recently-used model
+    /// (with no active references) is evicted first.
+    pub fn load(
+        &self,
+        path: &Path,
+        model_params: &llama_core::ModelParams,
+        ctx_params: &llama_core::ContextParams,
+    ) -> Result<Arc<LoadedModel>, llama_core::LlamaError> {
+        self.load_or_get(path, model_params, ctx_params)
+            .map(|(loaded, _)| loaded)
+    }
+
+    /// Like [`load`](Self::load), but also reports whether this call
+    /// performed the load (`true`) or found the model already loaded.
+    ///
+    /// Concurrent callers queue on the load lock, so only the first one
+    /// loads; the rest receive the same `Arc`.
+    fn load_or_get(
+        &self,
+        path: &Path,
+        model_params: &llama_core::ModelParams,
+        ctx_params: &llama_core::ContextParams,
+    ) -> Result<(Arc<LoadedModel>, bool), llama_core::LlamaError> {
+        let id = self.model_id_for(path);
+        let key = slot_key(&id);
+
+        // Serialise loading
+        let _guard = self.load_lock.lock().unwrap();
+
+        // If already loaded, just touch + return
+        {
+            let slots = self.slots.read().unwrap();
+            if let Some(slot) = slots.get(&key)
+                && slot.status == ModelStatus::Ready
+                && slot.loaded.is_some()
+            {
+                let loaded = slot.loaded.clone().unwrap();
+                drop(slots);
+                self.touch(&id);
+                info!(id, "Model already loaded, returning existing");
+                return Ok((loaded, false));
+            }
+        }
+
+        // Checked under the load lock so queued loads are refused too
+        if let Some(reason) = self.drain_reason() {
+            return Err(llama_core::LlamaError::LoadingPaused(reason));
+        }
+
+        let scan = gguf_parser::quick_scan(path).ok();
+        let ctx_params = &self.server_context_params(
+            ctx_params,
+            scan.as_ref().and_then(|scan| scan.context_length),
+        );
+        let estimate = scan
+            .as_ref()
+            .map(|scan| {
+                gguf_parser::estimate_memory(
+                    scan,
+                    &crate::services::memory::context_shape(model_params, ctx_params),
+                )
+            })
+            .unwrap_or_default();
+        let refusal = match scan.as_ref().map(gguf_parser::QuickScanResult::kind) {
+            Some(gguf_parser::ModelKind::Mmproj) => Some(
+                "this is a vision projector (mmproj) file; it is used \
+                 alongside a chat model and cannot be loaded on its own",
+            ),
+            Some(gguf_parser::ModelKind::LoraAdapter) => Some(
+                "this is a LoRA adapter; it is applied on top of its base \
+                 model (see GET /api/adapters) and cannot be loaded on its own",
+            ),
+            _ => None,
+        };
+        if let Some(reason) = refusal {
+            return Err(llama_core::LlamaError::ModelLoadFailed {
+                path: path.display().to_string(),
+                reason: reason.into(),
+            });
+        }
+        let capabilities = scan
+            .as_ref()
+            .map_or_else(|| vec![gguf_parser::Capability::Chat], |s| s.capabilities());
+
+        if self.gpu_offload_ignored(model_params.n_gpu_layers) {
+            warn!(
+                id,
+                n_gpu_layers = model_params.n_gpu_layers,
+                "GPU offload requested, but this build cannot offload; \
+                 the model will run on the CPU only"
+            );
+        }
+
+        // Evict if needed
+        self.maybe_evict(estimate.total_bytes)?;
+
+        self.begin_loading(&id, estimate);
+
+        // Actually load
+        let mut model_params = model_params.clone();
+        model_params.progress = Some(self.progress_callback(&id));
+        let model_params = &model_params;
+        let result: Result<_, llama_core::LlamaError> = (|| {
+            let model = Arc::new(llama_core::LlamaModel::load_from_file(path, model_params)?);
+            let ctx = llama_core::LlamaContext::new(model.clone(), ctx_params)?;
+            Ok(Arc::new(LoadedModel {
+                id: id.clone(),
+                path: path.to_path_buf(),
+                model,
+                n_seq_max: ctx.n_seq_max(),
+                context: Mutex::new(ctx),
+                capabilities,
+            }))
+        })();
+
+        match result {
+            Ok(loaded) => {
+                self.finish_loading(&id, Ok(Some(loaded.clone())));
+
+                // Auto-register the model's parent directory
+                if let Some(parent) = path.parent() {
+                    let canonical =
+                        std::fs::canonicalize(parent).unwrap_or_else(|_| parent.to_path_buf());
+                    self.add_model_dir(canonical);
                 }
 
                 info!(id, "Model loaded and ready");
-                Ok(loaded)
+                Ok((loaded, true))
             }
             Err(e) => {
-                // Remove the Loading slot
-                let mut slots = self.slots.write().unwrap();
-                slots.remove(&id);
+                self.finish_loading(&id, Err(e.to_string()));
                 Err(e)
             }
         }
     }
 
+    /// `params` with at least [`parallel`](ModelManagerConfig::parallel)
+    /// sequences, and the configured defrag threshold if it has none.
+    ///
+    /// llama.cpp splits `n_ctx` between the sequences, so it is scaled by
+    /// their number to leave each the context it was configured with;
+    /// `n_ctx_train` stands in for an `n_ctx` of 0 (the model's own).
+    pub fn server_context_params(
+        &self,
+        params: &llama_core::ContextParams,
+        n_ctx_train: Option<u32>,
+    ) -> llama_core::ContextParams {
+        let mut params = params.clone();
+        params.n_seq_max = params.n_seq_max.max(self.config.parallel);
+        if params.n_seq_max > 1 {
+            let n_ctx = match params.n_ctx {
+                0 => n_ctx_train.unwrap_or(0),
+                n => n,
+            };
+            params.n_ctx = n_ctx.saturating_mul(params.n_seq_max);
+        }
+        if params.defrag_thold < 0.0 {
+            params.defrag_thold = self.config.defrag_thold;
+        }
+        params
+    }
+
+    /// Whether a load explicitly asking for `n_gpu_layers` would silently
+    /// run on the CPU because the build lacks GPU offload support.  The
+    /// default of -1 ("as many as possible") is not a request for a GPU.
+    pub fn gpu_offload_ignored(&self, n_gpu_layers: i32) -> bool {
+        n_gpu_layers > 0 && !self.capabilities.supports_gpu_offload()
+    }
+
+    /// Insert a `Loading` slot for `id` with a fresh completion signal.
+    fn begin_loading(&self, id: &str, estimate: gguf_parser::MemoryEstimate) {
+        let mut slots = self.slots.write().unwrap();
+        slots.insert(
+            slot_key(id),
+            ModelSlot {
+                id: id.to_string(),
+                status: ModelStatus::Loading,
+                loaded: None,
+                last_used: Instant::now(),
+                progress: 0.0,
+                pinned: self
+                    .config
+                    .pinned_models
+                    .iter()
+                    .any(|p| p.eq_ignore_ascii_case(id)),
+                keep_alive: None,
+                estimate,
+                resident_bytes: None,
+                failure: None,
+                signal: tokio::sync::watch::channel(LoadSignal::Loading).0,
+                #[cfg(test)]
+                held: false,
+            },
+        );
+    }
+
+    /// Settle the `Loading` slot for `id`: mark it ready, or failed with
+    /// the error retained.  Either way, waiters are woken with the outcome.
+    fn finish_loading(&self, id: &str, result: Result<Option<Arc<LoadedModel>>, String>) {
+        let key = slot_key(id);
+        let mut slots = self.slots.write().unwrap();
+        match result {
+            Ok(loaded) => {
+                if let Some(slot) = slots.get_mut(&key) {
+                    slot.status = ModelStatus::Ready;
+                    slot.resident_bytes = loaded.as_ref().map(|l| {
+                        l.model.size() + slot.estimate.kv_cache_bytes + slot.estimate.compute_bytes
+                    });
+                    slot.loaded = loaded;
+                    slot.last_used = Instant::now();
+                    slot.progress = 1.0;
+                    slot.signal.send_replace(LoadSignal::Ready);
+                }
+            }
+            Err(e) => {
+                if let Some(slot) = slots.get_mut(&key) {
+                    slot.status = ModelStatus::Failed;
+                    slot.loaded = None;
+                    slot.resident_bytes = None;
+                    slot.failure = Some((e.clone(), chrono::Utc::now()));
+                    slot.signal.send_replace(LoadSignal::Failed(e));
+                }
+            }
+        }
+    }
+
+    /// Wait until the `Loading` slot for `id` becomes ready or fails.
+    ///
+    /// Returns immediately for a model that is already loaded; returns
+    /// [`WaitError::NotLoading`] if there is no slot for `id` at all.
+    pub async fn wait_ready(
+        &self,
+        id: &str,
+        timeout: Duration,
+    ) -> Result<Option<Arc<LoadedModel>>, WaitError> {
+        let mut rx = {
+            let slots = self.slots.read().unwrap();
+            let slot = slots
+                .get(&slot_key(id))
+                .ok_or_else(|| WaitError::NotLoading(id.to_string()))?;
+            if slot.status == ModelStatus::Ready {
+                return Ok(slot.loaded.clone());
+            }
+            slot.signal.subscribe()
+        };
+
+        let settled = tokio::time::timeout(timeout, rx.wait_for(|s| *s != LoadSignal::Loading))
+            .await
+            .map_err(|_| WaitError::Timeout(id.to_string()))?
+            .map(|s| s.clone());
+
+        match settled {
+            Ok(LoadSignal::Ready) => Ok(self.get_loaded(id)),
+            Ok(LoadSignal::Failed(e)) => Err(WaitError::Failed(e)),
+            // Slot dropped without settling (unloaded mid-load)
+            Ok(LoadSignal::Loading) | Err(_) => Err(WaitError::Failed(format!(
+                "Loading of model '{id}' was cancelled"
+            ))),
+        }
+    }
+
+    /// Ids of all slots, loading or ready.
+    fn slot_ids(&self) -> Vec<String> {
+        let slots = self.slots.read().unwrap();
+        slots.values().map(|s| s.id.clone()).collect()
+    }
+
+    /// Whether `id` has a slot that is still loading.
+    pub fn is_loading(&self, id: &str) -> bool {
+        let slots = self.slots.read().unwrap();
+        slots
+            .get(&slot_key(id))
+            .is_some_and(|s| s.status == ModelStatus::Loading)
+    }
+
+    /// Build a load progress callback for `id` that records the fraction
+    /// on its slot and publishes updates at most every
+    /// [`PROGRESS_INTERVAL`].
+    fn progress_callback(&self, id: &str) -> llama_core::ProgressCallback {
+        let slots = self.slots.clone();
+        let tx = self.progress_tx.clone();
+        let id = id.to_string();
+        let key = slot_key(&id);
+        let last = Mutex::new(None::<Instant>);
+        Arc::new(move |progress| {
+            let mut last = last.lock().unwrap();
+            let due = progress >= 1.0 || last.is_none_or(|t| t.elapsed() >= PROGRESS_INTERVAL);
+            if !due {
+                return;
+            }
+            *last = Some(Instant::now());
+            if let Some(slot) = slots.write().unwrap().get_mut(&key) {
+                slot.progress = progress;
+            }
+            // No subscribers is fine
+            let _ = tx.send(LoadProgress {
+                id: id.clone(),
+                progress,
+            });
+        })
+    }
+
+    /// Subscribe to throttled load progress updates.
+    pub fn subscribe_progress(&self) -> tokio::sync::broadcast::Receiver<LoadProgress> {
+        self.progress_tx.subscribe()
+    }
+
+    //  Background load jobs
+
+    /// Register a background load for `id`.
+    ///
+    /// If one is already running, it is returned with `false` and the
+    /// caller must not start another; otherwise a fresh job is recorded
+    /// and returned with `true`.
+    pub fn begin_load_job(&self, id: &str) -> (LoadJob, bool) {
+        let mut jobs = self.load_jobs.lock().unwrap();
+        let key = slot_key(id);
+        if let Some(job) = jobs.get(&key)
+            && job.state == LoadJobState::Loading
+        {
+            return (job.clone(), false);
+        }
+        let job = LoadJob {
+            job_id: format!("load-{}", uuid::Uuid::new_v4()),
+            id: id.to_string(),
+            state: LoadJobState::Loading,
+            error: None,
+        };
+        jobs.insert(key, job.clone());
+        (job, true)
+    }
+
+    /// Record the outcome of the running load job for `id`.
+    pub fn finish_load_job(&self, id: &str, result: Result<(), String>) {
+        let mut jobs = self.load_jobs.lock().unwrap();
+        if let Some(job) = jobs.get_mut(&slot_key(id)) {
+            match result {
+                Ok(()) => job.state = LoadJobState::Ready,
+                Err(e) => {
+                    job.state = LoadJobState::Failed;
+                    job.error = Some(e);
+                }
+            }
+        }
+    }
+
+    /// Most recent background load job for `id`.
+    pub fn load_job(&self, id: &str) -> Option<LoadJob> {
+        let jobs = self.load_jobs.lock().unwrap();
+        jobs.get(&slot_key(id)).cloned()
+    }
+
     /// Unload a specific model by id.
     pub fn unload(&self, id: &str) -> bool {
+        let key = slot_key(id);
         let mut slots = self.slots.write().unwrap();
-        if let Some(slot) = slots.get_mut(id) {
+        if let Some(slot) = slots.get_mut(&key) {
             slot.status = ModelStatus::Unloading;
             slot.loaded.take();
-            slots.remove(id);
+            slots.remove(&key);
             info!(id, "Model unloaded");
             true
         } else {
@@ -238,24 +1553,139 @@ impl ModelManager {
         }
     }
 
+    /// [`unload`](Self::unload) `id` unless a request holds it, in which
+    /// case it stays loaded and `None` is returned.
+    pub fn unload_if_idle(&self, id: &str) -> Option<bool> {
+        let key = slot_key(id);
+        let mut slots = self.slots.write().unwrap();
+        match slots.get(&key) {
+            Some(slot) if slot.in_use() => None,
+            Some(_) => {
+                slots.remove(&key);
+                info!(id, "Model unloaded");
+                Some(true)
+            }
+            None => Some(false),
+        }
+    }
+
     /// Unload all models.
-    #[allow(dead_code)]
     pub fn unload_all(&self) {
         let mut slots = self.slots.write().unwrap();
-        let ids: Vec<String> = slots.keys().cloned().collect();
+        let ids: Vec<String> = slots.values().map(|s| s.id.clone()).collect();
         for id in &ids {
             info!(id, "Unloading model");
         }
         slots.clear();
     }
 
+    //  Maintenance drain
+
+    /// Enter drain mode: loads fail with `reason` until [`end_drain`](Self::end_drain).
+    pub fn begin_drain(&self, reason: String) {
+        info!(reason, "Entering maintenance drain mode");
+        *self.drain.write().unwrap() = Some(reason);
+    }
+
+    /// Leave drain mode; `false` if the manager wasn't draining.
+    pub fn end_drain(&self) -> bool {
+        let was_draining = self.drain.write().unwrap().take().is_some();
+        if was_draining {
+            info!("Leaving maintenance drain mode");
+        }
+        was_draining
+    }
+
+    /// Why loads are refused, if the manager is draining.
+    pub fn drain_reason(&self) -> Option<String> {
+        self.drain.read().unwrap().clone()
+    }
+
+    /// Enter drain mode, wait up to `timeout` for in-flight requests and
+    /// loads to finish, then unload every ready model, pinned ones
+    /// included.  Models that are still busy at the deadline are left
+    /// loaded and reported as skipped.
+    pub async fn drain_and_unload(&self, reason: String, timeout: Duration) -> UnloadReport {
+        const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+        self.begin_drain(reason);
+        let busy = |s: &ModelSlot| s.in_use() || s.status == ModelStatus::Loading;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let any_busy = self.slots.read().unwrap().values().any(busy);
+            let now = Instant::now();
+            if !any_busy || now >= deadline {
+                break;
+            }
+            tokio::time::sleep(POLL_INTERVAL.min(deadline - now)).await;
+        }
+
+        let mut report = UnloadReport::default();
+        let mut slots = self.slots.write().unwrap();
+        slots.retain(|_, s| {
+            if busy(s) {
+                report.skipped.push(s.id.clone());
+                return true;
+            }
+            if s.status != ModelStatus::Ready {
+                return true;
+            }
+            info!(id = s.id, "Unloading model for maintenance");
+            report.unloaded.push(s.id.clone());
+            false
+        });
+        report.unloaded.sort();
+        report.skipped.sort();
+        if !report.skipped.is_empty() {
+            warn!(skipped = ?report.skipped, "Models still in use after drain timeout");
+        }
+        report
+    }
+
     //  Queries
 
-    /// Get a reference to a loaded model by id.
+    /// Tokenizer details of model `id`: from the loaded model if there is
+    /// one, otherwise from the metadata of the file at `path`.
+    pub fn tokenizer_info(&self, id: &str, path: &Path) -> Option<TokenizerInfo> {
+        if let Some(loaded) = self.get_loaded(id) {
+            return Some(TokenizerInfo::from_model(&loaded.model));
+        }
+        let scan = self.scan_metadata(path).ok()?;
+        Some(TokenizerInfo::from_scan(&scan))
+    }
+
+    /// Shape of model `id`: from the loaded model if there is one,
+    /// otherwise from the metadata of the file at `path`.
+    pub fn model_info(&self, id: &str, path: &Path) -> Option<llama_core::ModelInfo> {
+        if let Some(loaded) = self.get_loaded(id) {
+            return Some(loaded.model.info());
+        }
+        let scan = self.scan_metadata(path).ok()?;
+        model_info_from_scan(&scan)
+    }
+
+    /// Parameter count of catalogue entry `m`: exact from the loaded model
+    /// if there is one, otherwise as found by the directory scan.
+    pub fn param_count(&self, m: &gguf_parser::ModelEntry) -> Option<u64> {
+        match self.get_loaded(&m.id) {
+            Some(loaded) => Some(loaded.model.n_params()),
+            None => m.param_count,
+        }
+    }
+
+    /// Human form of [`Self::param_count`], or the file's
+    /// `general.size_label` when the count is unknown.
+    pub fn parameters_label(&self, m: &gguf_parser::ModelEntry) -> Option<String> {
+        self.param_count(m)
+            .map(gguf_parser::param_count_label)
+            .or_else(|| m.parameters.clone())
+    }
+
+    /// Get a reference to a loaded model by id (case-insensitive).
     pub fn get_loaded(&self, id: &str) -> Option<Arc<LoadedModel>> {
         let slots = self.slots.read().unwrap();
         slots
-            .get(id)
+            .get(&slot_key(id))
             .filter(|s| s.status == ModelStatus::Ready)
             .and_then(|s| s.loaded.clone())
     }
@@ -274,9 +1704,9 @@ impl ModelManager {
     pub fn loaded_model_ids(&self) -> Vec<String> {
         let slots = self.slots.read().unwrap();
         slots
-            .iter()
-            .filter(|(_, s)| s.status == ModelStatus::Ready)
-            .map(|(id, _)| id.clone())
+            .values()
+            .filter(|s| s.status == ModelStatus::Ready)
+            .map(|s| s.id.clone())
             .collect()
     }
 
@@ -284,7 +1714,7 @@ impl ModelManager {
     pub fn is_loaded(&self, id: &str) -> bool {
         let slots = self.slots.read().unwrap();
         slots
-            .get(id)
+            .get(&slot_key(id))
             .map(|s| s.status == ModelStatus::Ready)
             .unwrap_or(false)
     }
@@ -302,9 +1732,9 @@ impl ModelManager {
     pub fn slot_info(&self) -> Vec<SlotInfo> {
         let slots = self.slots.read().unwrap();
         slots
-            .iter()
-            .map(|(id, s)| SlotInfo {
-                id: id.clone(),
+            .values()
+            .map(|s| SlotInfo {
+                id: s.id.clone(),
                 path: s
                     .loaded
                     .as_ref()
@@ -312,64 +1742,199 @@ impl ModelManager {
                     .unwrap_or_default(),
                 status: s.status,
                 last_used: s.last_used.duration_since(self.epoch).as_millis() as u64,
+                progress: s.progress,
+                pinned: s.pinned,
+                estimated_bytes: s.estimate.total_bytes,
+                kv_cache_bytes: s.estimate.kv_cache_bytes,
+                resident_bytes: s.resident_bytes,
+                n_seq_max: s.loaded.as_ref().map(|l| l.n_seq_max),
+                error: s.failure.as_ref().map(|(e, _)| e.clone()),
+                failed_at: s.failure.as_ref().map(|(_, at)| *at),
+                stats: None,
             })
             .collect()
     }
 
+    /// Last reported load progress of a model's slot.
+    pub fn load_progress(&self, id: &str) -> Option<f32> {
+        let slots = self.slots.read().unwrap();
+        slots.get(&slot_key(id)).map(|s| s.progress)
+    }
+
+    /// Summed footprint of loading and loaded models, preferring actual
+    /// sizes over estimates.
+    pub fn memory_used(&self) -> u64 {
+        let slots = self.slots.read().unwrap();
+        slots.values().map(ModelSlot::footprint).sum()
+    }
+
+    /// Configured memory cap (0 = unlimited).
+    pub fn max_memory_bytes(&self) -> u64 {
+        self.config.max_memory_bytes
+    }
+
+    /// Error and time of the last failed load of `id`, if it failed.
+    pub fn load_failure(&self, id: &str) -> Option<(String, chrono::DateTime<chrono::Utc>)> {
+        let slots = self.slots.read().unwrap();
+        slots.get(&slot_key(id)).and_then(|s| s.failure.clone())
+    }
+
+    /// Take the loaded model `id` out of service after its context was
+    /// left unusable.  The slot is marked failed with `error`, and the next
+    /// request for the model loads it again.
+    pub fn mark_failed(&self, id: &str, error: String) {
+        let mut slots = self.slots.write().unwrap();
+        if let Some(slot) = slots.get_mut(&slot_key(id))
+            && slot.status == ModelStatus::Ready
+        {
+            slot.status = ModelStatus::Failed;
+            slot.loaded = None;
+            slot.resident_bytes = None;
+            slot.failure = Some((error, chrono::Utc::now()));
+        }
+    }
+
+    /// Forget a failed load of `id`.  Returns `false` if it has none.
+    pub fn clear_error(&self, id: &str) -> bool {
+        let key = slot_key(id);
+        let cleared = {
+            let mut slots = self.slots.write().unwrap();
+            let failed = slots
+                .get(&key)
+                .is_some_and(|s| s.status == ModelStatus::Failed);
+            if failed {
+                slots.remove(&key);
+            }
+            failed
+        };
+        let mut jobs = self.load_jobs.lock().unwrap();
+        if let Some(job) = jobs.get(&key)
+            && job.state == LoadJobState::Failed
+        {
+            jobs.remove(&key);
+        }
+        cleared
+    }
+
+    /// Override how long `id` stays loaded after its last use; `None`
+    /// goes back to the configured idle timeout.
+    pub fn set_keep_alive(&self, id: &str, keep_alive: Option<KeepAlive>) {
+        let mut slots = self.slots.write().unwrap();
+        if let Some(slot) = slots.get_mut(&slot_key(id)) {
+            slot.keep_alive = keep_alive;
+        }
+    }
+
+    /// Pin or unpin a loaded model.  Returns `false` if it has no slot.
+    pub fn set_pinned(&self, id: &str, pinned: bool) -> bool {
+        let mut slots = self.slots.write().unwrap();
+        match slots.get_mut(&slot_key(id)) {
+            Some(slot) => {
+                slot.pinned = pinned;
+                info!(id, pinned, "Model pin updated");
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Update LRU timestamp for a model.
     pub fn touch(&self, id: &str) {
         let mut slots = self.slots.write().unwrap();
-        if let Some(slot) = slots.get_mut(id) {
+        if let Some(slot) = slots.get_mut(&slot_key(id)) {
             slot.last_used = Instant::now();
         }
     }
 
+    /// Match `name` (or the model its alias points at) against the ids
+    /// of loaded and loading models only.
+    pub fn match_loaded(&self, name: &str) -> IdMatch {
+        let target = self.alias_target(name);
+        let name = target.as_deref().unwrap_or(name);
+        let ids = self.slot_ids();
+        match_model_id(name, ids.iter().map(String::as_str))
+    }
+
+    /// Match `name` (or the model its alias points at) against loaded
+    /// and scanned model ids.  An exact hit on a loaded model skips the
+    /// directory scan.
+    pub fn match_model(&self, name: &str) -> IdMatch {
+        let target = self.alias_target(name);
+        let name = target.as_deref().unwrap_or(name);
+        if let Some(loaded) = self.get_loaded(name) {
+            return IdMatch::Unique(loaded.id.clone());
+        }
+
+        let mut ids = self.slot_ids();
+        for m in self.scan_available() {
+            if !ids.iter().any(|id| id.eq_ignore_ascii_case(&m.id)) {
+                ids.push(m.id);
+            }
+        }
+        match_model_id(name, ids.iter().map(String::as_str))
+    }
+
     /// Find a model path by scanning directories for a matching model id.
+    /// Aliases are consulted first, then exact, unique-prefix and
+    /// unique-substring matches.  The result is canonicalized and must
+    /// pass [`check_model_path`](Self::check_model_path), so symlinks
+    /// cannot point a scanned id outside the model directories.
     pub fn find_model_path(&self, model_id: &str) -> Option<PathBuf> {
+        let target = self.alias_target(model_id);
+        let model_id = target.as_deref().unwrap_or(model_id);
         let available = self.scan_available();
-        available
-            .into_iter()
-            .find(|m| m.id.eq_ignore_ascii_case(model_id))
-            .map(|m| m.path)
+        let IdMatch::Unique(id) = match_model_id(model_id, available.iter().map(|m| m.id.as_str()))
+        else {
+            return None;
+        };
+        let path = available.into_iter().find(|m| m.id == id)?.path;
+        match self.check_model_path(&path) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                warn!(id, "Ignoring model: {e}");
+                None
+            }
+        }
     }
 
     //  Resolve (for route handlers)
 
-    /// Resolve a model: if a model name is given, try to get it from
-    /// loaded models. Otherwise return the most recently used model.
+    /// Resolve a model: if a model name is given, match it against loaded
+    /// models (see [`match_loaded`](Self::match_loaded)). Otherwise return
+    /// the most recently used model.
     pub fn resolve(&self, model_name: Option<&str>) -> Option<Arc<LoadedModel>> {
         match model_name {
-            Some(name) => self.get_loaded(name),
+            Some(name) => match self.match_loaded(name) {
+                IdMatch::Unique(id) => self.get_loaded(&id),
+                _ => None,
+            },
             None => self.get_any_loaded(),
         }
     }
 
     /// Same as `resolve` but also tries auto-loading if the model is
     /// not currently loaded.  This is a **blocking** call.
-    #[allow(dead_code)]
+    ///
+    /// The flag is `true` when this call loaded the model.
     pub fn ensure_loaded(
         &self,
         model_name: Option<&str>,
-    ) -> Result<Arc<LoadedModel>, llama_core::LlamaError> {
+    ) -> Result<(Arc<LoadedModel>, bool), llama_core::LlamaError> {
         // Try loaded first
         if let Some(loaded) = self.resolve(model_name) {
             self.touch(&loaded.id);
-            return Ok(loaded);
+            return Ok((loaded, false));
         }
 
         // Auto-load if model name is provided
         if let Some(name) = model_name
             && let Some(path) = self.find_model_path(name)
         {
-            let model_params = llama_core::ModelParams {
-                n_gpu_layers: self.config.default_n_gpu_layers,
-                ..Default::default()
-            };
-            let ctx_params = llama_core::ContextParams {
-                n_ctx: self.config.default_ctx_size,
-                ..Default::default()
-            };
-            return self.load(&path, &model_params, &ctx_params);
+            let id = self.model_id_for(&path);
+            let (model_params, ctx_params) = self
+                .effective_settings(&id, ModelSettings::default())
+                .load_params();
+            return self.load_or_get(&path, &model_params, &ctx_params);
         }
 
         Err(llama_core::LlamaError::ContextCreationFailed(
@@ -379,33 +1944,48 @@ impl ModelManager {
 
     //  LRU eviction
 
-    /// Evict the least-recently-used model if we're at capacity.
-    fn maybe_evict(&self) {
+    /// Evict least-recently-used models until a new model needing
+    /// `incoming_bytes` fits both the `max_memory_bytes` budget and the
+    /// `max_models` count.
+    ///
+    /// Pinned models are never evicted; if they alone fill the capacity
+    /// the load is refused.
+    fn maybe_evict(&self, incoming_bytes: u64) -> Result<(), llama_core::LlamaError> {
         let max = self.config.max_models;
-        if max == 0 {
-            return; // unlimited
+        let max_memory = self.config.max_memory_bytes;
+        if max_memory > 0 && incoming_bytes > max_memory {
+            return Err(llama_core::LlamaError::InsufficientMemory(format!(
+                "Model needs ~{} but max-memory is {}",
+                format_bytes(incoming_bytes),
+                format_bytes(max_memory)
+            )));
         }
 
         let mut slots = self.slots.write().unwrap();
-        while slots
-            .values()
-            .filter(|s| s.status == ModelStatus::Ready || s.status == ModelStatus::Loading)
-            .count()
-            >= max
-        {
+        loop {
+            let active = slots
+                .values()
+                .filter(|s| s.status == ModelStatus::Ready || s.status == ModelStatus::Loading);
+            let over_count = max > 0 && active.clone().count() >= max;
+            let used: u64 = active.map(ModelSlot::footprint).sum();
+            let over_memory = max_memory > 0 && used + incoming_bytes > max_memory;
+            if !over_count && !over_memory {
+                break;
+            }
+
             // Find the LRU model with no active external refs
+            let all_pinned = {
+                let mut ready = slots
+                    .values()
+                    .filter(|s| s.status == ModelStatus::Ready)
+                    .peekable();
+                ready.peek().is_some() && ready.all(|s| s.pinned)
+            };
             let victim = slots
                 .iter()
-                .filter(|(_, s)| s.status == ModelStatus::Ready)
-                .filter(|(_, s)| {
-                    // Only evict if nobody else holds a reference.
-                    // The slot itself holds 1 ref; if strong_count > 1
-                    // someone is actively using it.
-                    s.loaded
-                        .as_ref()
-                        .map(|l| Arc::strong_count(l) <= 1)
-                        .unwrap_or(true)
-                })
+                .filter(|(_, s)| s.status == ModelStatus::Ready && !s.pinned)
+                // Only evict if nobody else holds a reference.
+                .filter(|(_, s)| !s.in_use())
                 .min_by_key(|(_, s)| s.last_used)
                 .map(|(id, _)| id.clone());
 
@@ -414,36 +1994,64 @@ impl ModelManager {
                     info!(id, "Evicting LRU model to make room");
                     slots.remove(&id);
                 }
+                None if all_pinned => {
+                    return Err(llama_core::LlamaError::InsufficientMemory(
+                        "Capacity is held by pinned models; \
+                         increase models-max/max-memory or unpin a model"
+                            .into(),
+                    ));
+                }
+                // Loading past the cap would break it; the caller can retry
+                // once a request lets go of its model
                 None => {
-                    warn!("Cannot evict: all loaded models have active references");
-                    break;
+                    let cap = if over_memory {
+                        format!(
+                            "max-memory ({} in use, {} more needed, limit {})",
+                            format_bytes(used),
+                            format_bytes(incoming_bytes),
+                            format_bytes(max_memory)
+                        )
+                    } else {
+                        format!("models-max ({max})")
+                    };
+                    return Err(llama_core::LlamaError::InsufficientMemory(format!(
+                        "Capacity under {cap} is held by models that are pinned, \
+                         loading or in use; retry when a request finishes"
+                    )));
                 }
             }
         }
+        Ok(())
+    }
+
+    /// Sweep idle models using the configured idle timeout.
+    pub fn sweep_expired(&self) {
+        self.sweep_idle(self.config.idle_timeout_secs);
     }
 
     /// Sweep idle models (called from background task).
+    ///
+    /// A slot's own `keep_alive` takes precedence over `timeout_secs`.
     pub fn sweep_idle(&self, timeout_secs: u64) {
-        if timeout_secs == 0 {
-            return;
-        }
-        let cutoff = Instant::now() - std::time::Duration::from_secs(timeout_secs);
+        let now = Instant::now();
         let mut slots = self.slots.write().unwrap();
         let idle: Vec<String> = slots
             .iter()
-            .filter(|(_, s)| s.status == ModelStatus::Ready)
-            .filter(|(_, s)| s.last_used < cutoff)
-            .filter(|(_, s)| {
-                s.loaded
-                    .as_ref()
-                    .map(|l| Arc::strong_count(l) <= 1)
-                    .unwrap_or(true)
+            .filter(|(_, s)| s.status == ModelStatus::Ready && !s.pinned)
+            .filter(|(_, s)| match s.keep_alive {
+                Some(KeepAlive::Forever) => false,
+                Some(KeepAlive::For(d)) => now.duration_since(s.last_used) >= d,
+                None => {
+                    timeout_secs > 0
+                        && now.duration_since(s.last_used) >= Duration::from_secs(timeout_secs)
+                }
             })
+            .filter(|(_, s)| !s.in_use())
             .map(|(id, _)| id.clone())
             .collect();
 
         for id in idle {
-            info!(id, "Unloading idle model (timeout={}s)", timeout_secs);
+            info!(id, "Unloading idle model");
             slots.remove(&id);
         }
     }
@@ -453,13 +2061,14 @@ impl ModelManager {
 pub fn spawn_idle_checker(
     manager: ModelManager,
     idle_timeout_secs: u64,
-    mut shutdown: tokio::sync::broadcast::Receiver<String>,
+    mut shutdown: ShutdownSignal,
 ) {
-    if idle_timeout_secs == 0 {
-        return;
-    }
-
-    let interval = std::time::Duration::from_secs(idle_timeout_secs.max(30).min(idle_timeout_secs));
+    // Keep sweeping even without a global timeout: requests may set
+    // their own `keep_alive`.
+    let interval = Duration::from_secs(match idle_timeout_secs {
+        0 => 30,
+        t => t.min(30),
+    });
     tokio::spawn(async move {
         let mut ticker = tokio::time::interval(interval);
         ticker.tick().await; // skip first immediate tick
@@ -468,10 +2077,1078 @@ pub fn spawn_idle_checker(
                 _ = ticker.tick() => {
                     manager.sweep_idle(idle_timeout_secs);
                 }
-                _ = shutdown.recv() => {
+                _ = shutdown_requested(&mut shutdown) => {
                     break;
                 }
             }
         }
     });
 }
+
+/// Spawn a background task that rescans the model directories every
+/// `interval_secs` (0 = disabled) and reports changes to `on_change`.
+pub fn spawn_rescanner(
+    manager: ModelManager,
+    interval_secs: u64,
+    mut shutdown: ShutdownSignal,
+    on_change: impl Fn(CatalogueDiff) + Send + 'static,
+) {
+    if interval_secs == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        // The first tick fires immediately and records the baseline.
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let mm = manager.clone();
+                    match tokio::task::spawn_blocking(move || mm.rescan()).await {
+                        Ok(diff) if !diff.is_empty() => on_change(diff),
+                        Ok(_) => {}
+                        Err(e) => warn!("Model rescan failed: {e}"),
+                    }
+                }
+                _ = shutdown_requested(&mut shutdown) => {
+                    break;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    /// A manager whose slot map holds `ids` as ready (model-less) slots.
+    fn manager_with_slots(ids: &[&str]) -> ModelManager {
+        let mm = ModelManager::new(Vec::new(), ModelManagerConfig::default());
+        {
+            let mut slots = mm.slots.write().unwrap();
+            for id in ids {
+                slots.insert(
+                    slot_key(id),
+                    ModelSlot {
+                        id: id.to_string(),
+                        status: ModelStatus::Ready,
+                        loaded: None,
+                        last_used: Instant::now(),
+                        progress: 1.0,
+                        pinned: false,
+                        keep_alive: None,
+                        estimate: Default::default(),
+                        resident_bytes: None,
+                        failure: None,
+                        signal: tokio::sync::watch::channel(LoadSignal::Ready).0,
+                        held: false,
+                    },
+                );
+            }
+        }
+        mm
+    }
+
+    const IDS: &[&str] = &[
+        "Qwen2.5-7B-Instruct-Q4_K_M",
+        "Qwen2.5-14B-Instruct-Q4_K_M",
+        "Llama-3.1-8B-Instruct-Q8_0",
+    ];
+
+    #[test]
+    fn exact_match() {
+        let mm = manager_with_slots(IDS);
+        assert_eq!(
+            mm.match_loaded("Llama-3.1-8B-Instruct-Q8_0"),
+            IdMatch::Unique("Llama-3.1-8B-Instruct-Q8_0".into())
+        );
+    }
+
+    #[test]
+    fn case_insensitive_match() {
+        let mm = manager_with_slots(IDS);
+        assert_eq!(
+            mm.match_loaded("qwen2.5-7b-instruct-q4_k_m"),
+            IdMatch::Unique("Qwen2.5-7B-Instruct-Q4_K_M".into())
+        );
+        assert!(mm.is_loaded("QWEN2.5-14B-INSTRUCT-Q4_K_M"));
+    }
+
+    #[test]
+    fn unique_prefix_and_substring() {
+        let mm = manager_with_slots(IDS);
+        assert_eq!(
+            mm.match_loaded("qwen2.5-7b"),
+            IdMatch::Unique("Qwen2.5-7B-Instruct-Q4_K_M".into())
+        );
+        assert_eq!(
+            mm.match_loaded("8b-instruct"),
+            IdMatch::Unique("Llama-3.1-8B-Instruct-Q8_0".into())
+        );
+    }
+
+    #[test]
+    fn ambiguous_prefix_lists_candidates() {
+        let mm = manager_with_slots(IDS);
+        assert_eq!(
+            mm.match_loaded("qwen2.5"),
+            IdMatch::Ambiguous(vec![
+                "Qwen2.5-14B-Instruct-Q4_K_M".into(),
+                "Qwen2.5-7B-Instruct-Q4_K_M".into(),
+            ])
+        );
+        assert!(matches!(mm.match_loaded("instruct"), IdMatch::Ambiguous(c) if c.len() == 3));
+    }
+
+    #[test]
+    fn prefix_tier_wins_over_substring() {
+        assert_eq!(
+            match_model_id("llama", ["llama-7b", "tinyllama-1b"]),
+            IdMatch::Unique("llama-7b".into())
+        );
+    }
+
+    #[test]
+    fn exact_match_wins_over_longer_prefix() {
+        assert_eq!(
+            match_model_id("phi-3", ["phi-3", "phi-3-mini"]),
+            IdMatch::Unique("phi-3".into())
+        );
+    }
+
+    #[test]
+    fn no_match() {
+        let mm = manager_with_slots(IDS);
+        assert_eq!(mm.match_loaded("mistral"), IdMatch::NotFound);
+        assert_eq!(match_model_id("", IDS.iter().copied()), IdMatch::NotFound);
+    }
+
+    #[test]
+    fn aliases_are_consulted_first() {
+        let mm = manager_with_slots(IDS);
+        mm.set_aliases([(
+            "gpt-4o".to_string(),
+            "Llama-3.1-8B-Instruct-Q8_0".to_string(),
+        )]);
+        assert_eq!(
+            mm.match_loaded("GPT-4o"),
+            IdMatch::Unique("Llama-3.1-8B-Instruct-Q8_0".into())
+        );
+    }
+
+    /// Simulate a slow load: settle `id` with `result` after `delay`.
+    fn finish_later(
+        mm: &ModelManager,
+        id: &'static str,
+        delay: Duration,
+        result: Result<Option<Arc<LoadedModel>>, String>,
+    ) {
+        let mm = mm.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(delay);
+            mm.finish_loading(id, result);
+        });
+    }
+
+    #[tokio::test]
+    async fn wait_ready_returns_once_slow_load_completes() {
+        let mm = manager_with_slots(&[]);
+        mm.begin_loading("Slow-Model", Default::default());
+        assert!(mm.is_loading("slow-model"));
+
+        finish_later(&mm, "Slow-Model", Duration::from_millis(50), Ok(None));
+        let result = mm.wait_ready("slow-model", Duration::from_secs(5)).await;
+        assert!(matches!(result, Ok(None)));
+        assert!(mm.is_loaded("Slow-Model"));
+    }
+
+    #[tokio::test]
+    async fn wait_ready_propagates_load_error() {
+        let mm = manager_with_slots(&[]);
+        mm.begin_loading("broken", Default::default());
+        finish_later(
+            &mm,
+            "broken",
+            Duration::from_millis(50),
+            Err("out of memory".into()),
+        );
+
+        let err = mm
+            .wait_ready("broken", Duration::from_secs(5))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, WaitError::Failed(ref e) if e == "out of memory"));
+        assert!(!mm.is_loading("broken"));
+
+        // The failure stays visible on the slot until cleared
+        let info = mm.slot_info();
+        assert_eq!(info[0].status, ModelStatus::Failed);
+        assert_eq!(info[0].error.as_deref(), Some("out of memory"));
+        assert!(info[0].failed_at.is_some());
+        assert_eq!(mm.memory_used(), 0);
+
+        assert!(mm.clear_error("broken"));
+        assert!(mm.load_failure("broken").is_none());
+        assert!(!mm.clear_error("broken"));
+    }
+
+    #[test]
+    fn failed_contexts_take_the_model_out_of_service() {
+        let mm = manager_with_slots(&["qwen"]);
+        mm.mark_failed("Qwen", "FFI panic: index out of bounds".into());
+
+        assert!(!mm.is_loaded("qwen"));
+        assert!(mm.get_any_loaded().is_none());
+        let (error, _) = mm.load_failure("qwen").unwrap();
+        assert_eq!(error, "FFI panic: index out of bounds");
+        assert_eq!(mm.slot_info()[0].status, ModelStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn wait_ready_times_out() {
+        let mm = manager_with_slots(&[]);
+        mm.begin_loading("glacial", Default::default());
+        let err = mm
+            .wait_ready("glacial", Duration::from_millis(20))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, WaitError::Timeout(_)));
+        assert!(mm.is_loading("glacial"));
+    }
+
+    #[tokio::test]
+    async fn wait_ready_without_slot_or_after_cancel() {
+        let mm = manager_with_slots(&[]);
+        let err = mm
+            .wait_ready("absent", Duration::from_secs(1))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, WaitError::NotLoading(_)));
+
+        mm.begin_loading("cancelled", Default::default());
+        let waiter = {
+            let mm = mm.clone();
+            tokio::spawn(async move { mm.wait_ready("cancelled", Duration::from_secs(5)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        mm.unload("cancelled");
+        let err = waiter.await.unwrap().err().unwrap();
+        assert!(matches!(err, WaitError::Failed(_)));
+    }
+
+    #[tokio::test]
+    async fn drain_waits_for_loads_then_unloads_everything() {
+        let mm = manager_with_slots(IDS);
+        pin(&mm, IDS[0]);
+        mm.begin_loading("late", Default::default());
+        finish_later(&mm, "late", Duration::from_millis(50), Ok(None));
+
+        let report = mm
+            .drain_and_unload("driver update".into(), Duration::from_secs(5))
+            .await;
+        assert_eq!(report.unloaded.len(), 4);
+        assert!(report.skipped.is_empty());
+        assert_eq!(mm.loaded_count(), 0);
+        assert_eq!(mm.drain_reason().as_deref(), Some("driver update"));
+
+        assert!(mm.end_drain());
+        assert!(mm.drain_reason().is_none());
+        assert!(!mm.end_drain());
+    }
+
+    #[tokio::test]
+    async fn drain_skips_models_still_busy_at_timeout() {
+        let mm = manager_with_slots(&["idle"]);
+        mm.begin_loading("glacial", Default::default());
+
+        let report = mm
+            .drain_and_unload("reboot".into(), Duration::from_millis(20))
+            .await;
+        assert_eq!(report.unloaded, vec!["idle".to_string()]);
+        assert_eq!(report.skipped, vec!["glacial".to_string()]);
+        assert!(mm.is_loading("glacial"));
+    }
+
+    fn pin(mm: &ModelManager, id: &str) {
+        assert!(mm.set_pinned(id, true));
+    }
+
+    fn capped_manager(max_models: usize, ids: &[&str]) -> ModelManager {
+        let mm = ModelManager::new(
+            Vec::new(),
+            ModelManagerConfig {
+                max_models,
+                ..Default::default()
+            },
+        );
+        for id in ids {
+            mm.begin_loading(id, Default::default());
+            mm.finish_loading(id, Ok(None));
+        }
+        mm
+    }
+
+    #[test]
+    fn eviction_skips_pinned_models() {
+        let mm = capped_manager(2, &["main", "scratch"]);
+        pin(&mm, "main");
+        mm.touch("scratch"); // "main" is now least recently used
+
+        mm.maybe_evict(0).unwrap();
+        assert!(mm.is_loaded("main"));
+        assert!(!mm.is_loaded("scratch"));
+        assert!(mm.slot_info().iter().all(|s| s.pinned));
+    }
+
+    #[test]
+    fn eviction_fails_when_all_models_pinned() {
+        let mm = capped_manager(2, &["a", "b"]);
+        pin(&mm, "a");
+        pin(&mm, "b");
+
+        let err = mm.maybe_evict(0).unwrap_err();
+        assert!(err.to_string().contains("unpin a model"));
+        assert_eq!(mm.loaded_count(), 2);
+
+        // Unpinning takes effect immediately
+        mm.set_pinned("b", false);
+        mm.maybe_evict(0).unwrap();
+        assert!(!mm.is_loaded("b"));
+    }
+
+    fn hold(mm: &ModelManager, id: &str) {
+        mm.slots.write().unwrap().get_mut(id).unwrap().held = true;
+    }
+
+    #[test]
+    fn busy_models_are_not_unloaded_when_idle_is_required() {
+        let mm = capped_manager(2, &["idle", "busy"]);
+        hold(&mm, "busy");
+        assert_eq!(mm.unload_if_idle("busy"), None);
+        assert!(mm.slots.read().unwrap().contains_key("busy"));
+        assert_eq!(mm.unload_if_idle("idle"), Some(true));
+        assert_eq!(mm.unload_if_idle("idle"), Some(false));
+    }
+
+    #[test]
+    fn eviction_fails_when_the_rest_are_in_use() {
+        let mm = capped_manager(2, &["pinned", "busy"]);
+        pin(&mm, "pinned");
+        hold(&mm, "busy");
+
+        let err = mm.maybe_evict(0).unwrap_err();
+        assert!(matches!(err, llama_core::LlamaError::InsufficientMemory(_)));
+        assert!(err.to_string().contains("in use"));
+        assert_eq!(mm.loaded_count(), 2);
+
+        // Free again once the request lets go
+        mm.slots.write().unwrap().get_mut("busy").unwrap().held = false;
+        mm.maybe_evict(0).unwrap();
+        assert!(!mm.is_loaded("busy"));
+    }
+
+    #[test]
+    fn idle_sweep_skips_pinned_models() {
+        let mm = capped_manager(0, &["keep", "drop"]);
+        pin(&mm, "keep");
+        {
+            let mut slots = mm.slots.write().unwrap();
+            for slot in slots.values_mut() {
+                slot.last_used = Instant::now() - Duration::from_secs(120);
+            }
+        }
+
+        mm.sweep_idle(60);
+        assert!(mm.is_loaded("keep"));
+        assert!(!mm.is_loaded("drop"));
+    }
+
+    #[test]
+    fn configured_pins_apply_at_load_time() {
+        let mm = ModelManager::new(
+            Vec::new(),
+            ModelManagerConfig {
+                pinned_models: vec!["Main".into()],
+                ..Default::default()
+            },
+        );
+        mm.begin_loading("main", Default::default());
+        mm.finish_loading("main", Ok(None));
+        mm.begin_loading("other", Default::default());
+        mm.finish_loading("other", Ok(None));
+
+        let pinned: Vec<_> = mm
+            .slot_info()
+            .into_iter()
+            .filter(|s| s.pinned)
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(pinned, ["main"]);
+    }
+
+    fn estimate(total_bytes: u64) -> gguf_parser::MemoryEstimate {
+        gguf_parser::MemoryEstimate {
+            weights_bytes: total_bytes,
+            kv_cache_bytes: 0,
+            compute_bytes: 0,
+            total_bytes,
+            host_bytes: total_bytes,
+            device_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn memory_budget_evicts_until_model_fits() {
+        const GIB: u64 = 1 << 30;
+        let mm = ModelManager::new(
+            Vec::new(),
+            ModelManagerConfig {
+                max_models: 0,
+                max_memory_bytes: 24 * GIB,
+                ..Default::default()
+            },
+        );
+        for (id, size) in [("old", 8 * GIB), ("mid", 8 * GIB), ("new", 4 * GIB)] {
+            mm.begin_loading(id, estimate(size));
+            mm.finish_loading(id, Ok(None));
+        }
+        mm.touch("mid");
+        mm.touch("new");
+        assert_eq!(mm.memory_used(), 20 * GIB);
+
+        // 20 + 4 fits exactly
+        mm.maybe_evict(4 * GIB).unwrap();
+        assert_eq!(mm.loaded_count(), 3);
+
+        // 20 + 13 needs both 8 GiB models gone, LRU first
+        mm.maybe_evict(13 * GIB).unwrap();
+        assert!(!mm.is_loaded("old"));
+        assert!(!mm.is_loaded("mid"));
+        assert!(mm.is_loaded("new"));
+
+        let err = mm.maybe_evict(32 * GIB).unwrap_err();
+        assert!(err.to_string().contains("max-memory"));
+    }
+
+    #[test]
+    fn memory_cap_holds_while_models_are_in_use() {
+        const GIB: u64 = 1 << 30;
+        let mm = ModelManager::new(
+            Vec::new(),
+            ModelManagerConfig {
+                max_models: 0,
+                max_memory_bytes: 16 * GIB,
+                ..Default::default()
+            },
+        );
+        for id in ["a", "b"] {
+            mm.begin_loading(id, estimate(8 * GIB));
+            mm.finish_loading(id, Ok(None));
+            hold(&mm, id);
+        }
+
+        let err = mm.maybe_evict(4 * GIB).unwrap_err();
+        assert!(matches!(err, llama_core::LlamaError::InsufficientMemory(_)));
+        assert!(err.to_string().contains("max-memory (16.0 GiB in use"));
+        assert_eq!(mm.memory_used(), 16 * GIB);
+    }
+
+    #[test]
+    fn model_count_still_caps_alongside_memory() {
+        let mm = ModelManager::new(
+            Vec::new(),
+            ModelManagerConfig {
+                max_models: 1,
+                max_memory_bytes: u64::MAX,
+                ..Default::default()
+            },
+        );
+        mm.begin_loading("a", estimate(1));
+        mm.finish_loading("a", Ok(None));
+        mm.maybe_evict(1).unwrap();
+        assert_eq!(mm.loaded_count(), 0);
+    }
+
+    #[test]
+    fn formats_binary_sizes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(42 * (1 << 30)), "42.0 GiB");
+        assert_eq!(format_bytes(1536 * (1 << 20)), "1.5 GiB");
+    }
+
+    #[test]
+    fn parses_keep_alive_values() {
+        let parse = |v: serde_json::Value| serde_json::from_value::<KeepAlive>(v);
+        let secs = |n| KeepAlive::For(Duration::from_secs(n));
+
+        assert_eq!(parse(serde_json::json!("5m")).unwrap(), secs(300));
+        assert_eq!(parse(serde_json::json!("1h30m")).unwrap(), secs(5400));
+        assert_eq!(parse(serde_json::json!("45s")).unwrap(), secs(45));
+        assert_eq!(parse(serde_json::json!("0")).unwrap(), secs(0));
+        assert_eq!(parse(serde_json::json!("-1")).unwrap(), KeepAlive::Forever);
+        assert_eq!(parse(serde_json::json!("-5m")).unwrap(), KeepAlive::Forever);
+        assert_eq!(parse(serde_json::json!(0)).unwrap(), secs(0));
+        assert_eq!(parse(serde_json::json!(600)).unwrap(), secs(600));
+        assert_eq!(parse(serde_json::json!(-1)).unwrap(), KeepAlive::Forever);
+        assert!(parse(serde_json::json!("5 minutes")).is_err());
+        assert!(parse(serde_json::json!("")).is_err());
+        assert!(parse(serde_json::json!("m")).is_err());
+        assert!(parse(serde_json::json!("-")).is_err());
+        assert!(parse(serde_json::json!("-forever")).is_err());
+        assert!(parse(serde_json::json!("--5m")).is_err());
+
+        // Too long for a Duration
+        assert!(parse(serde_json::json!(1e30)).is_err());
+        assert!(parse(serde_json::json!("1e300")).is_err());
+        assert!(parse(serde_json::json!("99999999999999999999h")).is_err());
+        let near_max = format!("{}s{}s", u64::MAX / 2 + 1, u64::MAX / 2 + 1);
+        assert!(parse(serde_json::json!(near_max)).is_err());
+    }
+
+    #[test]
+    fn keep_alive_overrides_idle_timeout() {
+        let mm = capped_manager(0, &["zero", "forever", "long", "default"]);
+        mm.set_keep_alive("zero", Some(KeepAlive::For(Duration::ZERO)));
+        mm.set_keep_alive("forever", Some(KeepAlive::Forever));
+        mm.set_keep_alive("long", Some(KeepAlive::For(Duration::from_secs(3600))));
+        // A later request without keep_alive drops an earlier override
+        mm.set_keep_alive("default", Some(KeepAlive::Forever));
+        mm.set_keep_alive("default", None);
+        {
+            let mut slots = mm.slots.write().unwrap();
+            for slot in slots.values_mut() {
+                slot.last_used = Instant::now() - Duration::from_secs(120);
+            }
+        }
+
+        mm.sweep_idle(60);
+        assert!(!mm.is_loaded("zero"));
+        assert!(mm.is_loaded("forever"));
+        assert!(mm.is_loaded("long"));
+        assert!(!mm.is_loaded("default"));
+    }
+
+    #[test]
+    fn keep_alive_zero_applies_without_global_timeout() {
+        let mm = capped_manager(0, &["a", "b"]);
+        mm.set_keep_alive("a", Some(KeepAlive::For(Duration::ZERO)));
+        mm.sweep_expired();
+        assert!(!mm.is_loaded("a"));
+        assert!(mm.is_loaded("b"));
+    }
+
+    fn entry(id: &str, size: u64) -> (CatalogueKey, gguf_parser::ModelEntry) {
+        let path = PathBuf::from(format!("/models/{id}.gguf"));
+        let key = CatalogueKey {
+            path: path.clone(),
+            size,
+            mtime: None,
+        };
+        let entry = gguf_parser::ModelEntry {
+            id: id.to_string(),
+            name: format!("{id}.gguf"),
+            canonical_path: path.clone(),
+            path,
+            file_size: size,
+            architecture: None,
+            quantization: None,
+            context_length: None,
+            parameters: None,
+            param_count: None,
+            bytes_per_param: None,
+            capabilities: Vec::new(),
+            kind: gguf_parser::ModelKind::Base,
+            features: Vec::new(),
+            base_model: None,
+            is_split: false,
+            split_parts: Vec::new(),
+            missing_parts: Vec::new(),
+            mmproj_path: None,
+            collision: false,
+            metadata_source: gguf_parser::MetadataSource::Scanned,
+            scan_error: None,
+        };
+        (key, entry)
+    }
+
+    #[test]
+    fn catalogue_diff_reports_added_and_removed() {
+        let prev: HashMap<_, _> = [entry("a", 1), entry("b", 1)].into_iter().collect();
+        let next: HashMap<_, _> = [entry("b", 1), entry("c", 1)].into_iter().collect();
+        let diff = diff_catalogue(&prev, &next);
+        let ids =
+            |v: &[gguf_parser::ModelEntry]| v.iter().map(|e| e.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&diff.discovered), ["c"]);
+        assert_eq!(ids(&diff.removed), ["a"]);
+        assert!(diff_catalogue(&next, &next).is_empty());
+    }
+
+    #[test]
+    fn catalogue_diff_detects_rewritten_file() {
+        let prev: HashMap<_, _> = [entry("a", 1)].into_iter().collect();
+        let next: HashMap<_, _> = [entry("a", 2)].into_iter().collect();
+        let diff = diff_catalogue(&prev, &next);
+        assert_eq!(diff.discovered[0].file_size, 2);
+        assert_eq!(diff.removed[0].file_size, 1);
+    }
+
+    #[test]
+    fn first_rescan_is_baseline() {
+        let mm = ModelManager::new(Vec::new(), ModelManagerConfig::default());
+        assert!(mm.scan_summary().last_scan_at.is_none());
+        assert!(mm.rescan().is_empty());
+        let summary = mm.scan_summary();
+        assert!(summary.last_scan_at.is_some());
+        assert_eq!(summary.models, 0);
+    }
+
+    #[test]
+    fn same_named_models_in_sibling_dirs_get_distinct_ids() {
+        let tmp = TempDir::new("llama-dashboard-collision");
+        for sub in ["a", "b"] {
+            std::fs::create_dir_all(tmp.0.join(sub)).unwrap();
+            std::fs::write(tmp.0.join(sub).join("model-q4_k_m.gguf"), b"").unwrap();
+        }
+        let mm = ModelManager::new(vec![tmp.0.clone()], ModelManagerConfig::default());
+
+        let available = mm.scan_available();
+        assert_eq!(available.len(), 2);
+        assert!(available.iter().all(|m| m.collision));
+        let (first, second) = (&available[0], &available[1]);
+        assert_eq!(first.id, "model-q4_k_m");
+        assert!(second.id.starts_with("model-q4_k_m-"));
+        assert_ne!(first.id, second.id);
+
+        // Each id resolves to its own file, and slots use the same ids.
+        let canonical = |p: &Path| std::fs::canonicalize(p).unwrap();
+        assert_eq!(
+            mm.find_model_path(&second.id).unwrap(),
+            canonical(&second.path)
+        );
+        assert_eq!(
+            mm.find_model_path(&first.id).unwrap(),
+            canonical(&first.path)
+        );
+        assert_eq!(mm.model_id_for(&second.path), second.id);
+        assert_eq!(mm.model_id_for(&first.path), first.id);
+    }
+
+    #[test]
+    fn external_file_does_not_take_catalogue_id() {
+        let models = TempDir::new("llama-dashboard-models");
+        let external = TempDir::new("llama-dashboard-external");
+        std::fs::write(models.0.join("model.gguf"), b"").unwrap();
+        std::fs::write(external.0.join("model.gguf"), b"").unwrap();
+        let mm = ModelManager::new(vec![models.0.clone()], ModelManagerConfig::default());
+
+        assert_eq!(mm.model_id_for(&models.0.join("model.gguf")), "model");
+        assert_ne!(mm.model_id_for(&external.0.join("model.gguf")), "model");
+    }
+
+    /// Minimal GGUF v3 file with one `general.name` string KV.
+    fn write_gguf(path: &Path, name: &str) {
+        write_gguf_kv(path, "general.name", name);
+    }
+
+    /// Minimal GGUF v3 file with one string KV.
+    fn write_gguf_kv(path: &Path, key: &str, value: &str) {
+        let mut data = Vec::new();
+        data.extend_from_slice(&0x4655_4747u32.to_le_bytes()); // "GGUF"
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes()); // tensors
+        data.extend_from_slice(&1u64.to_le_bytes()); // KVs
+        data.extend_from_slice(&(key.len() as u64).to_le_bytes());
+        data.extend_from_slice(key.as_bytes());
+        data.extend_from_slice(&8u32.to_le_bytes()); // string
+        data.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        data.extend_from_slice(value.as_bytes());
+        std::fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn lora_adapters_are_refused_as_models() {
+        let tmp = TempDir::new("llama-dashboard-lora");
+        let path = tmp.0.join("adapter.gguf");
+        write_gguf_kv(&path, "general.type", "adapter");
+        let mm = ModelManager::new(vec![tmp.0.clone()], ModelManagerConfig::default());
+
+        let err = mm
+            .load(
+                &path,
+                &llama_core::ModelParams::default(),
+                &llama_core::ContextParams::default(),
+            )
+            .err()
+            .unwrap();
+        assert!(
+            matches!(&err, llama_core::LlamaError::ModelLoadFailed { reason, .. }
+                if reason.contains("LoRA adapter")),
+            "{err}"
+        );
+        assert!(!mm.is_loaded("adapter"));
+    }
+
+    #[test]
+    fn directory_scans_are_stored_until_file_changes() {
+        let tmp = TempDir::new("llama-dashboard-scan-cache");
+        let path = tmp.0.join("model.gguf");
+        write_gguf(&path, "first");
+        let canonical = std::fs::canonicalize(&path).unwrap();
+        let db = Arc::new(Database::open_in_memory());
+        let mm = ModelManager::new(vec![tmp.0.clone()], ModelManagerConfig::default());
+        mm.set_scan_store(db.clone());
+        assert_eq!(mm.scan_available()[0].name, "first");
+
+        // Doctor the stored scan: a new manager must use it without
+        // reading the file.
+        let mut record = db.get_scan_cache(&canonical).unwrap().unwrap();
+        let mut scan: gguf_parser::FileScan = serde_json::from_str(&record.scan).unwrap();
+        scan.scan.as_mut().unwrap().name = Some("stored".into());
+        record.scan = serde_json::to_string(&scan).unwrap();
+        db.set_scan_cache(&canonical, &record).unwrap();
+
+        let mm = ModelManager::new(vec![tmp.0.clone()], ModelManagerConfig::default());
+        mm.set_scan_store(db.clone());
+        assert_eq!(mm.scan_available()[0].name, "stored");
+        assert_eq!(mm.scan_available_uncached()[0].name, "first");
+
+        write_gguf(&path, "second, longer");
+        assert_eq!(mm.scan_available()[0].name, "second, longer");
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(mm.scan_available_uncached().is_empty());
+        assert!(db.get_scan_cache(&canonical).unwrap().is_none());
+    }
+
+    #[test]
+    fn scan_metadata_is_cached_until_file_changes() {
+        let tmp = TempDir::new("llama-dashboard-metadata");
+        let path = tmp.0.join("model.gguf");
+        write_gguf(&path, "first");
+        let mm = ModelManager::new(vec![tmp.0.clone()], ModelManagerConfig::default());
+
+        let a = mm.scan_metadata(&path).unwrap();
+        let b = mm.scan_metadata(&path).unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(a.name.as_deref(), Some("first"));
+
+        write_gguf(&path, "second, longer");
+        let c = mm.scan_metadata(&path).unwrap();
+        assert!(!Arc::ptr_eq(&a, &c));
+        assert_eq!(c.name.as_deref(), Some("second, longer"));
+    }
+
+    #[test]
+    fn directory_scan_counts_parameters_from_tensor_shapes() {
+        let tmp = TempDir::new("llama-dashboard-params");
+        let mut data = Vec::new();
+        data.extend_from_slice(&0x4655_4747u32.to_le_bytes()); // "GGUF"
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&2u64.to_le_bytes()); // tensors
+        data.extend_from_slice(&0u64.to_le_bytes()); // KVs
+        for (name, dims) in [
+            ("token_embd.weight", &[4096u64, 151_936][..]),
+            ("output_norm.weight", &[4096]),
+        ] {
+            data.extend_from_slice(&(name.len() as u64).to_le_bytes());
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(&(dims.len() as u32).to_le_bytes());
+            for d in dims {
+                data.extend_from_slice(&d.to_le_bytes());
+            }
+            data.extend_from_slice(&1u32.to_le_bytes()); // F16
+            data.extend_from_slice(&0u64.to_le_bytes()); // offset
+        }
+        std::fs::write(tmp.0.join("tiny.gguf"), data).unwrap();
+        write_gguf(&tmp.0.join("empty.gguf"), "empty");
+
+        let entries = gguf_parser::scan_directory(&tmp.0).unwrap();
+        let params = |id: &str| {
+            entries
+                .iter()
+                .find(|e| e.id == id)
+                .and_then(|e| e.parameters.clone())
+        };
+        assert_eq!(params("tiny").as_deref(), Some("622.3M"));
+        assert_eq!(params("empty"), None);
+
+        assert_eq!(gguf_parser::param_count_label(7_615_616_512), "7.6B");
+        assert_eq!(gguf_parser::param_count_label(70_553_706_496), "70.6B");
+        assert_eq!(gguf_parser::param_count_label(1_100_048_384), "1.1B");
+        assert_eq!(gguf_parser::param_count_label(512), "512");
+    }
+
+    #[test]
+    fn tokenizer_info_reads_gguf_metadata() {
+        use gguf_parser::{GGUFMetadataKV, GGUFValue, GGUFValueType};
+
+        let kv = |key: &str, value_type, value| GGUFMetadataKV {
+            key: key.into(),
+            value_type,
+            value,
+        };
+        let tokens = ["<unk>", "<s>", "</s>", "hi"]
+            .map(|t| GGUFValue::String(t.into()))
+            .to_vec();
+        let scan = gguf_parser::QuickScanResult {
+            file_path: "m.gguf".into(),
+            file_size: 0,
+            header: gguf_parser::GGUFHeader {
+                version: 3,
+                tensor_count: 0,
+                metadata_kv_count: 4,
+            },
+            architecture: None,
+            name: None,
+            file_type: None,
+            file_type_name: None,
+            context_length: None,
+            embedding_length: None,
+            chat_template: Some("{{ '<|im_start|>' + m.role }}".into()),
+            param_count: None,
+            truncated: false,
+            metadata: vec![
+                kv(
+                    "tokenizer.ggml.tokens",
+                    GGUFValueType::Array,
+                    GGUFValue::Array(tokens),
+                ),
+                kv(
+                    "tokenizer.ggml.bos_token_id",
+                    GGUFValueType::Uint32,
+                    GGUFValue::Uint32(1),
+                ),
+                kv(
+                    "tokenizer.ggml.eos_token_id",
+                    GGUFValueType::Uint32,
+                    GGUFValue::Uint32(2),
+                ),
+                kv(
+                    "tokenizer.ggml.add_bos_token",
+                    GGUFValueType::Bool,
+                    GGUFValue::Bool(true),
+                ),
+            ],
+        };
+
+        let info = TokenizerInfo::from_scan(&scan);
+        assert_eq!(info.source, "gguf");
+        assert_eq!(info.n_vocab, Some(4));
+        assert_eq!(
+            info.bos,
+            Some(SpecialToken {
+                id: 1,
+                text: Some("<s>".into())
+            })
+        );
+        assert_eq!(info.eos.unwrap().text.as_deref(), Some("</s>"));
+        assert_eq!(info.eot, None);
+        assert_eq!(info.add_bos, Some(true));
+        assert_eq!(info.chat_template_name, Some("chatml"));
+        // No attention keys in the window
+        assert_eq!(model_info_from_scan(&scan), None);
+    }
+
+    #[test]
+    fn model_info_reads_gguf_metadata() {
+        use gguf_parser::{GGUFMetadataKV, GGUFValue, GGUFValueType};
+
+        let kv = |key: &str, value| GGUFMetadataKV {
+            key: key.into(),
+            value_type: GGUFValueType::Uint32,
+            value,
+        };
+        let scan = gguf_parser::QuickScanResult {
+            file_path: "m.gguf".into(),
+            file_size: 0,
+            header: gguf_parser::GGUFHeader {
+                version: 3,
+                tensor_count: 0,
+                metadata_kv_count: 5,
+            },
+            architecture: Some("qwen2".into()),
+            name: None,
+            file_type: None,
+            file_type_name: None,
+            context_length: Some(32768),
+            embedding_length: Some(3584),
+            chat_template: None,
+            param_count: None,
+            truncated: false,
+            metadata: vec![
+                kv("qwen2.block_count", GGUFValue::Uint32(28)),
+                kv("qwen2.attention.head_count", GGUFValue::Uint32(28)),
+                kv("qwen2.attention.head_count_kv", GGUFValue::Uint32(4)),
+                kv("qwen2.rope.freq_base", GGUFValue::Float32(1e6)),
+                kv("tokenizer.ggml.model", GGUFValue::String("gpt2".into())),
+            ],
+        };
+
+        let info = model_info_from_scan(&scan).unwrap();
+        assert_eq!(info.n_ctx_train, 32768);
+        assert_eq!(info.n_layer, 28);
+        assert_eq!((info.n_head, info.n_head_kv), (28, 4));
+        assert_eq!(info.rope_freq_base, Some(1e6));
+        assert_eq!(info.rope_freq_scale_train, 1.0);
+        assert_eq!(info.vocab_type, llama_core::VocabType::Bpe);
+
+        use gguf_parser::Capability;
+        assert_eq!(scan.capabilities(), [Capability::Chat]);
+        let with_arch = |arch: &str, metadata| gguf_parser::QuickScanResult {
+            architecture: Some(arch.into()),
+            metadata,
+            ..scan.clone()
+        };
+        assert_eq!(
+            with_arch("nomic-bert", Vec::new()).capabilities(),
+            [Capability::Embeddings]
+        );
+        assert_eq!(
+            with_arch("clip", Vec::new()).capabilities(),
+            [Capability::VisionProjector]
+        );
+        // Decoder architectures converted for embeddings declare pooling
+        let pooled = vec![kv("qwen3.pooling_type", GGUFValue::Uint32(3))];
+        assert_eq!(
+            with_arch("qwen3", pooled).capabilities(),
+            [Capability::Embeddings]
+        );
+    }
+
+    struct FakeCapabilities {
+        gpu_offload: bool,
+    }
+
+    impl Capabilities for FakeCapabilities {
+        fn supports_gpu_offload(&self) -> bool {
+            self.gpu_offload
+        }
+    }
+
+    #[test]
+    fn gpu_offload_ignored_without_support() {
+        let mut mm = ModelManager::new(Vec::new(), ModelManagerConfig::default());
+        mm.capabilities = Arc::new(FakeCapabilities { gpu_offload: false });
+        assert!(mm.gpu_offload_ignored(20));
+        assert!(!mm.gpu_offload_ignored(-1));
+        assert!(!mm.gpu_offload_ignored(0));
+
+        mm.capabilities = Arc::new(FakeCapabilities { gpu_offload: true });
+        assert!(!mm.gpu_offload_ignored(20));
+    }
+
+    #[test]
+    fn settings_merge_request_over_stored_over_defaults() {
+        let mm = ModelManager::new(Vec::new(), ModelManagerConfig::default());
+        mm.set_all_settings([(
+            "Big-32B".to_string(),
+            ModelSettings {
+                ctx_size: Some(16384),
+                n_gpu_layers: Some(48),
+                tensor_split: Some(vec![3.0, 1.0]),
+                flash_attn: Some(false),
+                cache_type: Some(llama_core::KvCacheType::Q8_0),
+                ..Default::default()
+            },
+        )]);
+
+        let stored = mm.effective_settings("big-32b", ModelSettings::default());
+        assert_eq!(stored.ctx_size, Some(16384));
+        assert_eq!(stored.n_gpu_layers, Some(48));
+
+        let request = ModelSettings {
+            ctx_size: Some(8192),
+            split_mode: Some(llama_core::SplitMode::Row),
+            type_v: Some(llama_core::KvCacheType::F16),
+            rope: RopeSettings {
+                rope_scaling_type: Some(llama_core::RopeScalingType::Yarn),
+                yarn_orig_ctx: Some(4096),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let merged = mm.effective_settings("big-32b", request);
+        assert_eq!(merged.ctx_size, Some(8192));
+        assert_eq!(merged.n_gpu_layers, Some(48));
+
+        let other = mm.effective_settings("other", ModelSettings::default());
+        assert_eq!(other.ctx_size, Some(4096));
+        assert_eq!(other.n_gpu_layers, Some(-1));
+        assert_eq!(other.split_mode, Some(llama_core::SplitMode::Layer));
+        assert_eq!(other.tensor_split, None);
+        assert_eq!(other.flash_attn, None);
+
+        let (model_params, mut ctx_params) = merged.load_params();
+        assert_eq!(model_params.n_gpu_layers, 48);
+        assert_eq!(model_params.split_mode, llama_core::SplitMode::Row);
+        assert_eq!(model_params.main_gpu, 0);
+        assert_eq!(model_params.tensor_split, [3.0, 1.0]);
+        assert_eq!(
+            ctx_params.rope_scaling_type,
+            Some(llama_core::RopeScalingType::Yarn)
+        );
+        assert_eq!(ctx_params.yarn_orig_ctx, Some(4096));
+        assert_eq!(ctx_params.rope_freq_base, None);
+        assert_eq!(ctx_params.n_ctx, 8192);
+        assert_eq!(ctx_params.flash_attn_mode(), "disabled");
+        assert_eq!(ctx_params.type_k, Some(llama_core::KvCacheType::Q8_0));
+        assert_eq!(ctx_params.type_v, Some(llama_core::KvCacheType::F16));
+
+        let server = ModelManager::new(
+            Vec::new(),
+            ModelManagerConfig {
+                parallel: 4,
+                defrag_thold: 0.1,
+                ..Default::default()
+            },
+        )
+        .server_context_params(&ctx_params, Some(32768));
+        assert_eq!(server.n_seq_max, 4);
+        assert_eq!(server.n_ctx, 4 * 8192);
+        assert_eq!(server.defrag_thold, 0.1);
+        ctx_params.n_ctx = 0;
+        let server = ModelManager::new(
+            Vec::new(),
+            ModelManagerConfig {
+                parallel: 2,
+                ..Default::default()
+            },
+        )
+        .server_context_params(&ctx_params, Some(32768));
+        assert_eq!(server.n_ctx, 2 * 32768);
+
+        mm.set_settings("BIG-32B", ModelSettings::default());
+        assert!(mm.settings("big-32b").is_empty());
+    }
+
+    #[test]
+    fn inside_model_dirs_check_ignores_allow_external() {
+        let models = TempDir::new("llama-dashboard-inside");
+        let external = TempDir::new("llama-dashboard-outside");
+        let file = external.0.join("model.gguf");
+        std::fs::write(&file, b"").unwrap();
+        let mm = ModelManager::new(
+            vec![models.0.clone()],
+            ModelManagerConfig {
+                allow_external_paths: true,
+                ..Default::default()
+            },
+        );
+
+        assert!(mm.check_model_path(&file).is_ok());
+        assert!(matches!(
+            mm.check_inside_model_dirs(&file),
+            Err(ModelPathError::OutsideModelDirs(_))
+        ));
+        // As after loading it, which adds its directory to the scan list
+        mm.add_model_dir(external.0.clone());
+        assert!(matches!(
+            mm.check_inside_model_dirs(&file),
+            Err(ModelPathError::OutsideModelDirs(_))
+        ));
+    }
+}
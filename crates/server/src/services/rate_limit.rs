@@ -0,0 +1,158 @@
+//! Token-bucket rate limiting.
+//!
+//! Each client (API key id, or IP address) gets one bucket per
+//! [`BucketKind`].  A bucket holds up to a minute's worth of requests and
+//! refills continuously, so bursts up to the limit are allowed and the
+//! long-run rate is the configured requests per minute.  Limits live in
+//! atomics so the config endpoint can change them without a restart.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Buckets kept before idle (full) ones are dropped.
+const MAX_BUCKETS: usize = 10_000;
+
+/// Which limit a request counts against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BucketKind {
+    General,
+    /// Model load/unload endpoints, usually limited more strictly.
+    ModelOps,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    /// Refill for the time since the last update, capped at `capacity`.
+    fn refill(&mut self, capacity: f64, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * capacity / 60.0).min(capacity);
+        self.updated = now;
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    general: Arc<AtomicU32>,
+    model_ops: Arc<AtomicU32>,
+    buckets: Arc<Mutex<HashMap<(BucketKind, String), Bucket>>>,
+}
+
+impl RateLimiter {
+    /// A limiter allowing `general` and `model_ops` requests per minute
+    /// (0 = unlimited).
+    pub fn new(general: u32, model_ops: u32) -> Self {
+        let limiter = Self::default();
+        limiter.set_limits(general, model_ops);
+        limiter
+    }
+
+    pub fn set_limits(&self, general: u32, model_ops: u32) {
+        self.general.store(general, Ordering::Relaxed);
+        self.model_ops.store(model_ops, Ordering::Relaxed);
+    }
+
+    /// Current `(general, model_ops)` limits.
+    pub fn limits(&self) -> (u32, u32) {
+        (
+            self.general.load(Ordering::Relaxed),
+            self.model_ops.load(Ordering::Relaxed),
+        )
+    }
+
+    fn limit(&self, kind: BucketKind) -> u32 {
+        let (general, model_ops) = self.limits();
+        match kind {
+            BucketKind::General => general,
+            BucketKind::ModelOps => model_ops,
+        }
+    }
+
+    /// Take one token from `client`'s bucket, or return how long until
+    /// one is available.
+    pub fn check(&self, kind: BucketKind, client: &str) -> Result<(), Duration> {
+        self.check_at(kind, client, Instant::now())
+    }
+
+    fn check_at(&self, kind: BucketKind, client: &str, now: Instant) -> Result<(), Duration> {
+        let limit = self.limit(kind);
+        if limit == 0 {
+            return Ok(());
+        }
+        let capacity = f64::from(limit);
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_BUCKETS {
+            buckets.retain(|(kind, _), b| {
+                let capacity = f64::from(self.limit(*kind));
+                let mut b = *b;
+                b.refill(capacity, now);
+                b.tokens < capacity
+            });
+        }
+        let bucket = buckets.entry((kind, client.to_string())).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        bucket.refill(capacity, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - bucket.tokens;
+            Err(Duration::from_secs_f64(missing * 60.0 / capacity))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exhausted_bucket_recovers_over_the_window() {
+        let limiter = RateLimiter::new(3, 0);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.check_at(BucketKind::General, "a", start).is_ok());
+        }
+        let retry = limiter
+            .check_at(BucketKind::General, "a", start)
+            .unwrap_err();
+        assert_eq!(retry, Duration::from_secs(20));
+
+        // Other clients and the unlimited bucket are unaffected
+        assert!(limiter.check_at(BucketKind::General, "b", start).is_ok());
+        assert!(limiter.check_at(BucketKind::ModelOps, "a", start).is_ok());
+
+        // One token back after 20 s, the full bucket after a minute
+        let later = start + Duration::from_secs(20);
+        assert!(limiter.check_at(BucketKind::General, "a", later).is_ok());
+        assert!(limiter.check_at(BucketKind::General, "a", later).is_err());
+        let refilled = start + Duration::from_secs(80);
+        for _ in 0..3 {
+            assert!(limiter.check_at(BucketKind::General, "a", refilled).is_ok());
+        }
+        assert!(
+            limiter
+                .check_at(BucketKind::General, "a", refilled)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn limits_change_at_runtime() {
+        let limiter = RateLimiter::new(1, 1);
+        let now = Instant::now();
+        assert!(limiter.check_at(BucketKind::ModelOps, "a", now).is_ok());
+        assert!(limiter.check_at(BucketKind::ModelOps, "a", now).is_err());
+        limiter.set_limits(1, 0);
+        assert!(limiter.check_at(BucketKind::ModelOps, "a", now).is_ok());
+    }
+}
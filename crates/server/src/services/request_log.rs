@@ -0,0 +1,258 @@
+//! Opt-in request log for usage accounting.
+//!
+//! Every generation request produces one [`RequestLogEntry`].  Entries
+//! are handed to a bounded channel and written to SQLite in batches by
+//! [`spawn_request_log_writer`], so a slow disk never holds up a request;
+//! when the channel is full the entry is dropped.  Prompt text is only
+//! kept when `log_prompts` is enabled, and the OpenAI `user` field is
+//! stored as a truncated hash.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::state::{ShutdownSignal, shutdown_requested};
+
+/// Entries buffered between request handlers and the writer.
+const CHANNEL_CAPACITY: usize = 1024;
+/// Most entries written in one transaction.
+const MAX_BATCH: usize = 256;
+/// How often rows older than the retention window are deleted.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// One logged request.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RequestLogEntry {
+    pub timestamp: DateTime<Utc>,
+    pub endpoint: String,
+    pub model_id: String,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    /// Wall time from dispatch to the end of generation.
+    pub duration_ms: u64,
+    /// `stop`, `length`, `stop_word`, `timeout`, `error` or `cancelled`.
+    pub finish_reason: String,
+    /// 200 on completion, 500 when generation failed, 499 when the
+    /// client went away first, 503 when shutdown cancelled it, 504 when
+    /// it ran out of time.
+    pub status: u16,
+    pub user_hash: Option<String>,
+    pub prompt: Option<String>,
+    /// The `api_keys` row that authenticated the request.
+    pub api_key_id: Option<String>,
+}
+
+/// Where a generation request came from.
+#[derive(Debug, Clone, Default)]
+pub struct RequestMeta {
+    pub endpoint: &'static str,
+    /// The OpenAI `user` field, hashed before it is stored.
+    pub user: Option<String>,
+    /// Prompt text; `None` unless [`RequestLog::logs_prompts`].
+    pub prompt: Option<String>,
+    pub api_key_id: Option<String>,
+    /// Time limit from [`generation_timeout`], queueing included.
+    ///
+    /// [`generation_timeout`]: crate::services::inference::generation_timeout
+    pub timeout: Option<Duration>,
+    /// Token that stops this generation early, e.g. on a WebSocket
+    /// `cancel`.  It must be a [`child`] of the state's generation token
+    /// so shutdown still reaches it; by default a fresh child is used.
+    ///
+    /// [`child`]: llama_core::CancelToken::child
+    pub cancel: Option<llama_core::CancelToken>,
+}
+
+/// Sending half of the request log; a no-op when logging is disabled.
+#[derive(Clone, Default)]
+pub struct RequestLog {
+    tx: Option<mpsc::Sender<RequestLogEntry>>,
+    log_prompts: bool,
+}
+
+impl RequestLog {
+    /// A log and the receiver for [`spawn_request_log_writer`], or a
+    /// disabled log and `None`.
+    pub fn new(
+        enabled: bool,
+        log_prompts: bool,
+    ) -> (Self, Option<mpsc::Receiver<RequestLogEntry>>) {
+        if !enabled {
+            return (Self::default(), None);
+        }
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let log = Self {
+            tx: Some(tx),
+            log_prompts,
+        };
+        (log, Some(rx))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.tx.is_some()
+    }
+
+    /// Whether handlers should attach prompt text to [`RequestMeta`].
+    pub fn logs_prompts(&self) -> bool {
+        self.is_enabled() && self.log_prompts
+    }
+
+    /// Queue `entry` without waiting.
+    pub fn record(&self, entry: RequestLogEntry) {
+        let Some(tx) = &self.tx else { return };
+        if let Err(mpsc::error::TrySendError::Full(_)) = tx.try_send(entry) {
+            tracing::warn!("Request log queue full; dropping entry");
+        }
+    }
+}
+
+/// First 16 hex digits of the SHA-1 of `user`.
+pub fn user_hash(user: &str) -> String {
+    Sha1::digest(user.as_bytes())[..8]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Short name of a finish reason for the log.
+pub fn finish_reason_name(reason: &llama_core::FinishReason) -> &'static str {
+    match reason {
+        llama_core::FinishReason::Stop => "stop",
+        llama_core::FinishReason::Length => "length",
+        llama_core::FinishReason::StopWord(_) => "stop_word",
+        llama_core::FinishReason::Timeout => "timeout",
+    }
+}
+
+/// Spawn the task that drains `rx` into `persist` in batches and, when
+/// `retention_days` is non-zero, hourly calls `prune` with the oldest
+/// timestamp to keep.  Once `shutdown` fires it writes whatever is
+/// still queued and exits; await the handle to know the log is flushed.
+pub fn spawn_request_log_writer<E: std::fmt::Display>(
+    mut rx: mpsc::Receiver<RequestLogEntry>,
+    retention_days: u64,
+    mut shutdown: ShutdownSignal,
+    persist: impl Fn(&[RequestLogEntry]) -> Result<(), E> + Send + 'static,
+    prune: impl Fn(DateTime<Utc>) -> Result<usize, E> + Send + 'static,
+) -> JoinHandle<()> {
+    let write = move |batch: &[RequestLogEntry]| {
+        if let Err(e) = persist(batch) {
+            tracing::warn!(count = batch.len(), "Failed to write request log: {e}");
+        }
+    };
+    tokio::spawn(async move {
+        let mut prune_ticker = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            tokio::select! {
+                entry = rx.recv() => {
+                    let Some(entry) = entry else { break };
+                    let mut batch = vec![entry];
+                    while batch.len() < MAX_BATCH
+                        && let Ok(entry) = rx.try_recv()
+                    {
+                        batch.push(entry);
+                    }
+                    write(&batch);
+                }
+                _ = prune_ticker.tick(), if retention_days > 0 => {
+                    let cutoff = Utc::now() - chrono::Duration::days(retention_days as i64);
+                    match prune(cutoff) {
+                        Ok(0) => {}
+                        Ok(n) => tracing::info!(rows = n, "Pruned request log"),
+                        Err(e) => tracing::warn!("Failed to prune request log: {e}"),
+                    }
+                }
+                _ = shutdown_requested(&mut shutdown) => {
+                    // Late entries are refused; queued ones are still received.
+                    rx.close();
+                    let mut batch = Vec::new();
+                    while let Some(entry) = rx.recv().await {
+                        batch.push(entry);
+                        if batch.len() == MAX_BATCH {
+                            write(&batch);
+                            batch.clear();
+                        }
+                    }
+                    if !batch.is_empty() {
+                        write(&batch);
+                    }
+                    break;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_hash_is_short_and_stable() {
+        let h = user_hash("alice");
+        assert_eq!(h.len(), 16);
+        assert_eq!(h, user_hash("alice"));
+        assert_ne!(h, user_hash("bob"));
+    }
+
+    #[test]
+    fn disabled_log_ignores_entries() {
+        let (log, rx) = RequestLog::new(false, true);
+        assert!(rx.is_none());
+        assert!(!log.logs_prompts());
+        log.record(RequestLogEntry {
+            timestamp: Utc::now(),
+            endpoint: "/v1/completions".into(),
+            model_id: "m".into(),
+            prompt_tokens: 1,
+            completion_tokens: 1,
+            duration_ms: 1,
+            finish_reason: "stop".into(),
+            status: 200,
+            user_hash: None,
+            prompt: None,
+            api_key_id: None,
+        });
+    }
+
+    #[tokio::test]
+    async fn writer_flushes_queue_on_shutdown() {
+        let (log, rx) = RequestLog::new(true, false);
+        let (stop, stop_rx) = tokio::sync::watch::channel(false);
+        let written = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = written.clone();
+        let writer = spawn_request_log_writer(
+            rx.unwrap(),
+            0,
+            stop_rx,
+            move |entries: &[RequestLogEntry]| {
+                sink.lock().unwrap().extend_from_slice(entries);
+                Ok::<_, String>(())
+            },
+            |_| Ok(0),
+        );
+        for i in 0..3 {
+            log.record(RequestLogEntry {
+                timestamp: Utc::now(),
+                endpoint: "/v1/completions".into(),
+                model_id: format!("m{i}"),
+                prompt_tokens: 1,
+                completion_tokens: 1,
+                duration_ms: 1,
+                finish_reason: "stop".into(),
+                status: 200,
+                user_hash: None,
+                prompt: None,
+                api_key_id: None,
+            });
+        }
+        // The writer hasn't run yet, so all three are still queued.
+        stop.send_replace(true);
+        writer.await.unwrap();
+        assert_eq!(written.lock().unwrap().len(), 3);
+    }
+}
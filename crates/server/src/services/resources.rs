@@ -0,0 +1,175 @@
+//! Host resource sampling.
+//!
+//! Backs `GET /api/system/resources` and the periodic `system.metrics`
+//! WebSocket event.  GPU memory comes from ggml's device list, so it
+//! covers whichever backend (CUDA, ROCm, Vulkan, Metal) the binary was
+//! built with; CPU-only builds report no GPUs.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::services::model_manager::{ModelManager, ModelStatus};
+use crate::state::{ShutdownSignal, shutdown_requested};
+
+/// GPU backends this binary was built with.
+pub fn compiled_backends() -> Vec<&'static str> {
+    let mut backends = Vec::new();
+    if cfg!(feature = "cuda") {
+        backends.push("cuda");
+    }
+    if cfg!(feature = "vulkan") {
+        backends.push("vulkan");
+    }
+    if cfg!(feature = "rocm") {
+        backends.push("rocm");
+    }
+    // llama-sys links Metal whenever it's built on macOS.
+    if cfg!(target_os = "macos") {
+        backends.push("metal");
+    }
+    backends
+}
+
+/// One sample of host resource usage.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceSnapshot {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Average over all cores since the previous sample (0–100).
+    pub cpu_percent: f32,
+    pub cpu_count: usize,
+    pub ram_total_bytes: u64,
+    pub ram_used_bytes: u64,
+    pub ram_available_bytes: u64,
+    pub swap_total_bytes: u64,
+    pub swap_used_bytes: u64,
+    pub gpus: Vec<GpuUsage>,
+    pub models: Vec<ModelUsage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GpuUsage {
+    pub name: String,
+    pub description: String,
+    pub used_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// Memory attributed to a loaded model.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelUsage {
+    pub id: String,
+    pub estimated_bytes: u64,
+    pub resident_bytes: Option<u64>,
+}
+
+/// Keeps a `sysinfo::System` around so CPU usage is measured between
+/// consecutive samples.
+#[derive(Clone)]
+pub struct ResourceMonitor {
+    sys: Arc<Mutex<sysinfo::System>>,
+}
+
+impl Default for ResourceMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResourceMonitor {
+    pub fn new() -> Self {
+        let mut sys = sysinfo::System::new();
+        // Baseline for the first CPU usage delta.
+        sys.refresh_cpu_usage();
+        Self {
+            sys: Arc::new(Mutex::new(sys)),
+        }
+    }
+
+    /// Sample CPU, RAM, swap, GPU memory and per-model usage.
+    pub fn sample(&self, manager: &ModelManager) -> ResourceSnapshot {
+        let (cpu_percent, cpu_count, ram, swap) = {
+            let mut sys = self.sys.lock().unwrap();
+            sys.refresh_cpu_usage();
+            sys.refresh_memory();
+            (
+                sys.global_cpu_usage(),
+                sys.cpus().len(),
+                (
+                    sys.total_memory(),
+                    sys.used_memory(),
+                    sys.available_memory(),
+                ),
+                (sys.total_swap(), sys.used_swap()),
+            )
+        };
+
+        let gpus = llama_core::LlamaBackend::init()
+            .gpu_devices()
+            .into_iter()
+            .map(|d| GpuUsage {
+                used_bytes: d.total_bytes.saturating_sub(d.free_bytes),
+                total_bytes: d.total_bytes,
+                name: d.name,
+                description: d.description,
+            })
+            .collect();
+
+        let models = manager
+            .slot_info()
+            .into_iter()
+            .filter(|s| s.status == ModelStatus::Ready)
+            .map(|s| ModelUsage {
+                id: s.id,
+                estimated_bytes: s.estimated_bytes,
+                resident_bytes: s.resident_bytes,
+            })
+            .collect();
+
+        ResourceSnapshot {
+            timestamp: chrono::Utc::now(),
+            cpu_percent,
+            cpu_count,
+            ram_total_bytes: ram.0,
+            ram_used_bytes: ram.1,
+            ram_available_bytes: ram.2,
+            swap_total_bytes: swap.0,
+            swap_used_bytes: swap.1,
+            gpus,
+            models,
+        }
+    }
+}
+
+/// Spawn a background task that samples resources every `interval_secs`
+/// and hands each snapshot to `on_sample`.
+pub fn spawn_metrics_sampler(
+    monitor: ResourceMonitor,
+    manager: ModelManager,
+    interval_secs: u64,
+    mut shutdown: ShutdownSignal,
+    on_sample: impl Fn(ResourceSnapshot) + Send + 'static,
+) {
+    if interval_secs == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
+        ticker.tick().await; // skip first immediate tick
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let (monitor, manager) = (monitor.clone(), manager.clone());
+                    match tokio::task::spawn_blocking(move || monitor.sample(&manager)).await {
+                        Ok(snapshot) => on_sample(snapshot),
+                        Err(e) => tracing::warn!("Resource sampling failed: {e}"),
+                    }
+                }
+                _ = shutdown_requested(&mut shutdown) => {
+                    break;
+                }
+            }
+        }
+    });
+}
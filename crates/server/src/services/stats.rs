@@ -0,0 +1,251 @@
+//! Per-model runtime statistics.
+//!
+//! Counters are kept per model id rather than on [`LoadedModel`], so they
+//! survive unloads and reloads.  Changed entries are marked dirty and
+//! written to SQLite by [`spawn_stats_flusher`]; on startup the registry
+//! is seeded from the stored rows.
+//!
+//! [`LoadedModel`]: crate::services::model_manager::LoadedModel
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::state::{ShutdownSignal, shutdown_requested};
+
+/// Aggregated usage of one model.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ModelStats {
+    /// Generations that ran to completion.
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Wall time from taking the model's context to the end of
+    /// generation, prompt processing included.
+    pub generation_ms: u64,
+    pub last_request_at: Option<DateTime<Utc>>,
+}
+
+impl ModelStats {
+    /// Completion tokens per second of generation time.
+    pub fn tokens_per_sec(&self) -> f64 {
+        if self.generation_ms == 0 {
+            return 0.0;
+        }
+        self.completion_tokens as f64 * 1000.0 / self.generation_ms as f64
+    }
+}
+
+/// The subset of [`ModelStats`] shown alongside each slot.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsSummary {
+    pub requests: u64,
+    pub completion_tokens: u64,
+    pub tokens_per_sec: f64,
+    pub last_request_at: Option<DateTime<Utc>>,
+}
+
+impl From<&ModelStats> for StatsSummary {
+    fn from(s: &ModelStats) -> Self {
+        Self {
+            requests: s.requests,
+            completion_tokens: s.completion_tokens,
+            tokens_per_sec: s.tokens_per_sec(),
+            last_request_at: s.last_request_at,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Entry {
+    stats: ModelStats,
+    /// Changed since the last [`StatsRegistry::take_dirty`].
+    dirty: bool,
+}
+
+/// Shared, cheaply clonable stats table keyed by (lower-cased) model id.
+#[derive(Clone, Default)]
+pub struct StatsRegistry {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+    /// Generations started and not yet finished, across all models.
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl StatsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn key(id: &str) -> String {
+        id.to_lowercase()
+    }
+
+    /// Replace all entries with persisted rows (used at startup).
+    pub fn set_all(&self, rows: Vec<(String, ModelStats)>) {
+        let mut entries = self.entries.lock().unwrap();
+        *entries = rows
+            .into_iter()
+            .map(|(id, stats)| {
+                let entry = Entry {
+                    stats,
+                    dirty: false,
+                };
+                (Self::key(&id), entry)
+            })
+            .collect();
+    }
+
+    /// Account one finished generation to `id`.
+    pub fn record(&self, id: &str, prompt_tokens: u32, completion_tokens: u32, elapsed: Duration) {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.entry(Self::key(id)).or_default();
+        let stats = &mut entry.stats;
+        stats.requests += 1;
+        stats.prompt_tokens += u64::from(prompt_tokens);
+        stats.completion_tokens += u64::from(completion_tokens);
+        stats.generation_ms += elapsed.as_millis() as u64;
+        stats.last_request_at = Some(Utc::now());
+        entry.dirty = true;
+    }
+
+    /// Count a generation as in flight; returns the new count.
+    pub fn generation_started(&self) -> usize {
+        self.in_flight.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Count a generation as done; returns the new in-flight count.
+    pub fn generation_finished(&self) -> usize {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed) - 1
+    }
+
+    /// Generations running or waiting for a model's context.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    pub fn get(&self, id: &str) -> Option<ModelStats> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(&Self::key(id)).map(|e| e.stats.clone())
+    }
+
+    pub fn summary(&self, id: &str) -> Option<StatsSummary> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(&Self::key(id)).map(|e| (&e.stats).into())
+    }
+
+    /// Drop `id`'s counters.  Returns whether there were any.
+    pub fn reset(&self, id: &str) -> bool {
+        self.entries
+            .lock()
+            .unwrap()
+            .remove(&Self::key(id))
+            .is_some()
+    }
+
+    /// Entries changed since the last call, marking them clean.
+    pub fn take_dirty(&self) -> Vec<(String, ModelStats)> {
+        let mut entries = self.entries.lock().unwrap();
+        entries
+            .iter_mut()
+            .filter(|(_, e)| e.dirty)
+            .map(|(id, e)| {
+                e.dirty = false;
+                (id.clone(), e.stats.clone())
+            })
+            .collect()
+    }
+
+    /// Mark entries dirty again after a failed write.
+    pub fn mark_dirty(&self, ids: impl IntoIterator<Item = String>) {
+        let mut entries = self.entries.lock().unwrap();
+        for id in ids {
+            if let Some(entry) = entries.get_mut(&id) {
+                entry.dirty = true;
+            }
+        }
+    }
+}
+
+/// Spawn a background task that hands changed entries to `persist`
+/// every `interval_secs`.  Entries whose write fails are retried on the
+/// next tick.
+pub fn spawn_stats_flusher<E: std::fmt::Display>(
+    registry: StatsRegistry,
+    interval_secs: u64,
+    mut shutdown: ShutdownSignal,
+    persist: impl Fn(&[(String, ModelStats)]) -> Result<(), E> + Send + 'static,
+) {
+    if interval_secs == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
+        ticker.tick().await; // skip first immediate tick
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let dirty = registry.take_dirty();
+                    if dirty.is_empty() {
+                        continue;
+                    }
+                    if let Err(e) = persist(&dirty) {
+                        tracing::warn!("Failed to persist model stats: {e}");
+                        registry.mark_dirty(dirty.into_iter().map(|(id, _)| id));
+                    }
+                }
+                _ = shutdown_requested(&mut shutdown) => {
+                    break;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_accumulates_per_model() {
+        let reg = StatsRegistry::new();
+        reg.record("Qwen", 10, 20, Duration::from_millis(500));
+        reg.record("qwen", 5, 30, Duration::from_millis(500));
+        reg.record("other", 1, 1, Duration::from_millis(10));
+
+        let stats = reg.get("QWEN").unwrap();
+        assert_eq!(stats.requests, 2);
+        assert_eq!(stats.prompt_tokens, 15);
+        assert_eq!(stats.completion_tokens, 50);
+        assert_eq!(stats.generation_ms, 1000);
+        assert_eq!(stats.tokens_per_sec(), 50.0);
+        assert!(stats.last_request_at.is_some());
+    }
+
+    #[test]
+    fn take_dirty_returns_changes_once() {
+        let reg = StatsRegistry::new();
+        reg.set_all(vec![("stored".into(), ModelStats::default())]);
+        reg.record("a", 1, 1, Duration::from_millis(1));
+
+        let dirty = reg.take_dirty();
+        assert_eq!(dirty.len(), 1);
+        assert_eq!(dirty[0].0, "a");
+        assert!(reg.take_dirty().is_empty());
+
+        reg.mark_dirty(["a".to_string()]);
+        assert_eq!(reg.take_dirty().len(), 1);
+    }
+
+    #[test]
+    fn reset_drops_counters() {
+        let reg = StatsRegistry::new();
+        reg.record("a", 1, 1, Duration::from_millis(1));
+        assert!(reg.reset("A"));
+        assert!(reg.get("a").is_none());
+        assert!(!reg.reset("a"));
+    }
+}
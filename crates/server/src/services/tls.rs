@@ -0,0 +1,299 @@
+//! TLS termination.
+//!
+//! The certificate and key are PEM files given by `--tls-cert` /
+//! `--tls-key` or the config file.  [`TlsReloader`] swaps them on a live
+//! server, on SIGHUP or when the config endpoint changes the paths;
+//! connections already open keep the old certificate.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tracing::{info, warn};
+
+use crate::state::{ShutdownSignal, shutdown_requested};
+
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("TLS needs both a certificate (--tls-cert) and a key (--tls-key)")]
+    Incomplete,
+
+    #[error("Cannot read TLS {kind} {}: {source}", path.display())]
+    Read {
+        kind: &'static str,
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("No PEM certificates in {}", .0.display())]
+    NoCertificates(PathBuf),
+
+    #[error("No PEM private key in {}", .0.display())]
+    NoPrivateKey(PathBuf),
+
+    #[error("TLS certificate {} does not match key {}", cert.display(), key.display())]
+    Mismatch { cert: PathBuf, key: PathBuf },
+
+    #[error("Invalid TLS certificate or key: {0}")]
+    Invalid(rustls::Error),
+}
+
+/// Where the PEM certificate chain and private key live.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsPaths {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl TlsPaths {
+    /// Paths from a cert/key pair of options: `None` when neither is set,
+    /// [`TlsError::Incomplete`] when only one is.
+    pub fn from_options(
+        cert: Option<PathBuf>,
+        key: Option<PathBuf>,
+    ) -> Result<Option<Self>, TlsError> {
+        match (cert, key) {
+            (Some(cert), Some(key)) => Ok(Some(Self { cert, key })),
+            (None, None) => Ok(None),
+            _ => Err(TlsError::Incomplete),
+        }
+    }
+}
+
+fn read(kind: &'static str, path: &Path) -> Result<Vec<u8>, TlsError> {
+    std::fs::read(path).map_err(|source| TlsError::Read {
+        kind,
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Parse and check `paths` into a rustls server config offering HTTP/2
+/// and HTTP/1.1.
+pub fn server_config(paths: &TlsPaths) -> Result<rustls::ServerConfig, TlsError> {
+    let cert_pem = read("certificate", &paths.cert)?;
+    let key_pem = read("key", &paths.key)?;
+
+    let certs: Vec<CertificateDer<'static>> = CertificateDer::pem_slice_iter(&cert_pem)
+        .filter_map(Result::ok)
+        .collect();
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificates(paths.cert.clone()));
+    }
+    let key = PrivateKeyDer::from_pem_slice(&key_pem)
+        .map_err(|_| TlsError::NoPrivateKey(paths.key.clone()))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(TlsError::Invalid)?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| match e {
+            rustls::Error::InconsistentKeys(_) => TlsError::Mismatch {
+                cert: paths.cert.clone(),
+                key: paths.key.clone(),
+            },
+            e => TlsError::Invalid(e),
+        })?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// The live TLS config of a server and the files it came from.
+#[derive(Clone)]
+pub struct TlsReloader {
+    config: RustlsConfig,
+    paths: Arc<RwLock<TlsPaths>>,
+}
+
+impl TlsReloader {
+    pub fn new(paths: TlsPaths) -> Result<Self, TlsError> {
+        let config = RustlsConfig::from_config(Arc::new(server_config(&paths)?));
+        Ok(Self {
+            config,
+            paths: Arc::new(RwLock::new(paths)),
+        })
+    }
+
+    /// The config to hand to `axum_server::bind_rustls`.
+    pub fn rustls_config(&self) -> RustlsConfig {
+        self.config.clone()
+    }
+
+    pub fn paths(&self) -> TlsPaths {
+        self.paths.read().unwrap().clone()
+    }
+
+    /// Re-read the certificate, from `paths` if given or else from the
+    /// current files.  On error the running certificate stays in place.
+    pub fn reload(&self, paths: Option<TlsPaths>) -> Result<(), TlsError> {
+        let paths = paths.unwrap_or_else(|| self.paths());
+        let config = server_config(&paths)?;
+        self.config.reload_from_config(Arc::new(config));
+        info!(cert = %paths.cert.display(), "Reloaded TLS certificate");
+        *self.paths.write().unwrap() = paths;
+        Ok(())
+    }
+}
+
+/// Reload the certificate whenever the process gets SIGHUP.
+#[cfg(unix)]
+pub fn spawn_sighup_reloader(reloader: TlsReloader, mut shutdown: ShutdownSignal) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("Failed to listen for SIGHUP; TLS reload on signal disabled: {e}");
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                Some(()) = hangup.recv() => {
+                    if let Err(e) = reloader.reload(None) {
+                        warn!("Keeping the current TLS certificate: {e}");
+                    }
+                }
+                _ = shutdown_requested(&mut shutdown) => break,
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn spawn_sighup_reloader(_reloader: TlsReloader, _shutdown: ShutdownSignal) {}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::test_util::TempDir;
+
+    /// Write a self-signed `localhost` certificate and its key into `dir`
+    /// as `<name>.crt` / `<name>.key`.
+    fn self_signed(dir: &TempDir, name: &str) -> (TlsPaths, CertificateDer<'static>) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let paths = TlsPaths {
+            cert: dir.0.join(format!("{name}.crt")),
+            key: dir.0.join(format!("{name}.key")),
+        };
+        std::fs::write(&paths.cert, cert.cert.pem()).unwrap();
+        std::fs::write(&paths.key, cert.signing_key.serialize_pem()).unwrap();
+        (paths, cert.cert.der().clone())
+    }
+
+    #[test]
+    fn bad_files_are_reported() {
+        let tmp = TempDir::new("llama-dashboard-tls-errors");
+        let (a, _) = self_signed(&tmp, "a");
+        let (b, _) = self_signed(&tmp, "b");
+        assert!(server_config(&a).is_ok());
+
+        let mismatched = TlsPaths {
+            cert: a.cert.clone(),
+            key: b.key.clone(),
+        };
+        assert!(matches!(
+            server_config(&mismatched),
+            Err(TlsError::Mismatch { .. })
+        ));
+
+        let missing = TlsPaths {
+            cert: tmp.0.join("missing.crt"),
+            key: a.key.clone(),
+        };
+        assert!(matches!(
+            server_config(&missing),
+            Err(TlsError::Read {
+                kind: "certificate",
+                ..
+            })
+        ));
+
+        let swapped = TlsPaths {
+            cert: a.key.clone(),
+            key: a.cert.clone(),
+        };
+        assert!(matches!(
+            server_config(&swapped),
+            Err(TlsError::NoCertificates(_))
+        ));
+
+        assert!(matches!(
+            TlsPaths::from_options(Some(a.cert), None),
+            Err(TlsError::Incomplete)
+        ));
+        assert_eq!(TlsPaths::from_options(None, None).unwrap(), None);
+    }
+
+    /// Connect over TLS trusting only `root`, and send a WebSocket
+    /// handshake for `/ws/events`.  Returns the response status line and
+    /// the certificate the server presented.
+    async fn wss_handshake(
+        addr: SocketAddr,
+        root: &CertificateDer<'static>,
+    ) -> (String, CertificateDer<'static>) {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(root.clone()).unwrap();
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+
+        let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let name = rustls::pki_types::ServerName::try_from("localhost").unwrap();
+        let mut tls = connector.connect(name, tcp).await.unwrap();
+        let presented = tls.get_ref().1.peer_certificates().unwrap()[0].clone();
+
+        let request = "GET /ws/events HTTP/1.1\r\n\
+             Host: localhost\r\n\
+             Connection: Upgrade\r\n\
+             Upgrade: websocket\r\n\
+             Sec-WebSocket-Version: 13\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n";
+        tls.write_all(request.as_bytes()).await.unwrap();
+        let mut buf = vec![0; 1024];
+        let n = tls.read(&mut buf).await.unwrap();
+        let response = String::from_utf8_lossy(&buf[..n]).to_string();
+        let status = response.lines().next().unwrap_or_default().to_string();
+        (status, presented.into_owned())
+    }
+
+    #[tokio::test]
+    async fn websocket_upgrades_over_tls_and_certificate_reloads() {
+        let tmp = TempDir::new("llama-dashboard-tls-wss");
+        let (first, first_der) = self_signed(&tmp, "first");
+        let (second, second_der) = self_signed(&tmp, "second");
+
+        let reloader = TlsReloader::new(first).unwrap();
+        let state = crate::state::AppState::for_tests(Default::default(), None);
+        let app = crate::routes::app(state).into_make_service_with_connect_info::<SocketAddr>();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = axum_server::from_tcp_rustls(listener, reloader.rustls_config()).unwrap();
+        tokio::spawn(server.serve(app));
+
+        let (status, presented) = wss_handshake(addr, &first_der).await;
+        assert!(status.contains("101"), "{status}");
+        assert_eq!(presented, first_der);
+
+        reloader.reload(Some(second.clone())).unwrap();
+        assert_eq!(reloader.paths(), second);
+        let (status, presented) = wss_handshake(addr, &second_der).await;
+        assert!(status.contains("101"), "{status}");
+        assert_eq!(presented, second_der);
+    }
+}
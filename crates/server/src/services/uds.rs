@@ -0,0 +1,111 @@
+//! Unix domain socket listener.
+//!
+//! `--uds` serves the router on a socket file, beside or instead of the
+//! TCP listener, for local clients that shouldn't need a port.  A socket
+//! file left behind by a crashed server is replaced; one that still has
+//! a listener is not.  The file is removed again when the server stops.
+
+use std::io;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use tokio::net::UnixListener;
+use tracing::{info, warn};
+
+/// Removes the socket file when dropped.
+#[derive(Debug)]
+pub struct SocketFile(PathBuf);
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        match std::fs::remove_file(&self.0) {
+            Ok(()) => info!(path = %self.0.display(), "Removed Unix socket"),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => warn!(path = %self.0.display(), "Failed to remove Unix socket: {e}"),
+        }
+    }
+}
+
+/// Bind `path` with permissions `mode`, replacing a stale socket file.
+pub fn bind(path: &Path, mode: u32) -> io::Result<(UnixListener, SocketFile)> {
+    remove_stale(path)?;
+    let listener = UnixListener::bind(path)?;
+    let file = SocketFile(path.to_path_buf());
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    Ok((listener, file))
+}
+
+/// Delete `path` if it is a socket nobody is listening on.
+fn remove_stale(path: &Path) -> io::Result<()> {
+    let meta = match std::fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if !meta.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("another process is listening on {}", path.display()),
+        ));
+    }
+    info!(path = %path.display(), "Removing stale Unix socket");
+    std::fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[tokio::test]
+    async fn serves_the_router_and_cleans_up() {
+        let tmp = TempDir::new("llama-dashboard-uds");
+        let path = tmp.0.join("llama.sock");
+
+        // Left behind by a server that died without cleaning up
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let (listener, file) = bind(&path, 0o600).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // A live socket is not taken over
+        let err = bind(&path, 0o600).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+
+        let state = crate::state::AppState::for_tests(Default::default(), None);
+        let app = crate::routes::app(state);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+        drop(file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn refuses_to_replace_other_files() {
+        let tmp = TempDir::new("llama-dashboard-uds-file");
+        let path = tmp.0.join("not-a-socket");
+        std::fs::write(&path, b"data").unwrap();
+        let err = remove_stale(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(path.exists());
+    }
+}
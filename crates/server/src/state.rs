@@ -1,12 +1,30 @@
 //! Shared application state injected into Axum handlers.
 
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
-use tokio::sync::broadcast;
+use tokio::sync::watch;
 
 use crate::config::AppConfig;
 use crate::db::Database;
-use crate::services::model_manager::ModelManager;
+use crate::services::api_keys::ApiKeyRegistry;
+use crate::services::downloads::DownloadManager;
+use crate::services::events::EventBus;
+use crate::services::model_manager::{ModelManager, SlotInfo};
+use crate::services::rate_limit::RateLimiter;
+use crate::services::request_log::RequestLog;
+use crate::services::resources::ResourceMonitor;
+use crate::services::stats::StatsRegistry;
+use crate::services::tls::TlsReloader;
+
+/// Flips to `true` once the server starts shutting down; background
+/// tasks select on [`shutdown_requested`] to exit.
+pub type ShutdownSignal = watch::Receiver<bool>;
+
+/// Resolve once `signal` reads `true` (or its sender is gone).
+pub async fn shutdown_requested(signal: &mut ShutdownSignal) {
+    let _ = signal.wait_for(|&stop| stop).await;
+}
 
 #[derive(Clone)]
 pub struct AppState {
@@ -15,12 +33,21 @@ pub struct AppState {
 
 struct Inner {
     pub config: AppConfig,
-    #[allow(dead_code)]
-    pub db: Database,
+    pub db: Arc<Database>,
     pub model_manager: ModelManager,
-    #[allow(dead_code)]
+    pub downloads: DownloadManager,
+    pub resources: ResourceMonitor,
+    pub stats: StatsRegistry,
+    pub request_log: RequestLog,
     pub api_key: Option<String>,
-    pub event_tx: broadcast::Sender<String>,
+    pub api_keys: ApiKeyRegistry,
+    pub rate_limiter: RateLimiter,
+    pub events: EventBus,
+    pub shutdown_tx: watch::Sender<bool>,
+    /// Cancels every generation started through this state.
+    pub generation_cancel: llama_core::CancelToken,
+    /// Set once when serving over HTTPS.
+    pub tls: OnceLock<TlsReloader>,
 }
 
 impl AppState {
@@ -28,16 +55,33 @@ impl AppState {
         config: AppConfig,
         db: Database,
         model_manager: ModelManager,
+        stats: StatsRegistry,
+        request_log: RequestLog,
         api_key: Option<String>,
     ) -> Self {
-        let (event_tx, _) = broadcast::channel(256);
+        let events = EventBus::new();
+        let db = Arc::new(db);
+        model_manager.set_scan_store(db.clone());
+        let rate_limiter = RateLimiter::new(
+            config.rate_limit_per_minute,
+            config.model_ops_rate_limit_per_minute,
+        );
         Self {
             inner: Arc::new(Inner {
                 config,
+                downloads: DownloadManager::new(events.clone(), db.clone()),
                 db,
                 model_manager,
+                resources: ResourceMonitor::new(),
+                stats,
+                request_log,
                 api_key,
-                event_tx,
+                api_keys: ApiKeyRegistry::new(),
+                rate_limiter,
+                events,
+                shutdown_tx: watch::channel(false).0,
+                generation_cancel: llama_core::CancelToken::new(),
+                tls: OnceLock::new(),
             }),
         }
     }
@@ -45,36 +89,105 @@ impl AppState {
     pub fn config(&self) -> &AppConfig {
         &self.inner.config
     }
-    #[allow(dead_code)]
     pub fn db(&self) -> &Database {
         &self.inner.db
     }
     pub fn model_manager(&self) -> &ModelManager {
         &self.inner.model_manager
     }
-    #[allow(dead_code)]
+    pub fn downloads(&self) -> &DownloadManager {
+        &self.inner.downloads
+    }
+    pub fn resources(&self) -> &ResourceMonitor {
+        &self.inner.resources
+    }
+    pub fn stats(&self) -> &StatsRegistry {
+        &self.inner.stats
+    }
+    pub fn request_log(&self) -> &RequestLog {
+        &self.inner.request_log
+    }
+    /// Legacy single key from the config; acts as an admin key.
     pub fn api_key(&self) -> Option<&str> {
         self.inner.api_key.as_deref()
     }
+    /// Active keys from the `api_keys` table.
+    pub fn api_keys(&self) -> &ApiKeyRegistry {
+        &self.inner.api_keys
+    }
+
+    /// Per-client limiter; limits follow `PUT /api/config`.
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.inner.rate_limiter
+    }
+
+    /// Certificate of the HTTPS listener, if serving TLS.
+    pub fn tls(&self) -> Option<&TlsReloader> {
+        self.inner.tls.get()
+    }
+    pub fn set_tls(&self, reloader: TlsReloader) {
+        let _ = self.inner.tls.set(reloader);
+    }
 
-    /// Broadcast an event to all connected WebSocket clients.
-    pub fn broadcast_event(&self, event_type: &str, data: serde_json::Value) {
-        let event = serde_json::json!({
-            "type": event_type,
-            "timestamp": chrono::Utc::now().to_rfc3339(),
-            "data": data,
-        });
-        // Ignore send errors (no subscribers)
-        let _ = self.inner.event_tx.send(event.to_string());
+    /// Token passed to every generation; cancelled on shutdown.
+    pub fn generation_cancel(&self) -> &llama_core::CancelToken {
+        &self.inner.generation_cancel
     }
 
-    /// Subscribe to the event broadcast channel.
-    pub fn subscribe_events(&self) -> broadcast::Receiver<String> {
-        self.inner.event_tx.subscribe()
+    /// Receiver that resolves once [`Self::begin_shutdown`] is called.
+    pub fn shutdown_signal(&self) -> ShutdownSignal {
+        self.inner.shutdown_tx.subscribe()
     }
 
-    /// Get a clone of the event broadcast sender (used by idle checker).
-    pub fn event_tx(&self) -> broadcast::Sender<String> {
-        self.inner.event_tx.clone()
+    /// Announce the shutdown to dashboards, cancel running generations
+    /// and stop background tasks and WebSocket connections.
+    pub fn begin_shutdown(&self, drain_timeout: Duration) {
+        self.broadcast_event(
+            "server.shutting_down",
+            serde_json::json!({ "drain_timeout_secs": drain_timeout.as_secs() }),
+        );
+        self.inner.generation_cancel.cancel();
+        self.inner.shutdown_tx.send_replace(true);
+    }
+
+    /// Slots of the model manager with each model's usage summary.
+    pub fn slot_info(&self) -> Vec<SlotInfo> {
+        let mut slots = self.inner.model_manager.slot_info();
+        for slot in &mut slots {
+            slot.stats = self.inner.stats.summary(&slot.id);
+        }
+        slots
+    }
+
+    /// Broadcast an event to all connected WebSocket clients.  Events sent
+    /// while handling a request carry its `request_id`.
+    pub fn broadcast_event(&self, event_type: &str, mut data: serde_json::Value) {
+        if let Some(id) = crate::middleware::request_id::current()
+            && let Some(obj) = data.as_object_mut()
+        {
+            obj.insert("request_id".into(), id.into());
+        }
+        self.inner.events.send(event_type, data);
+    }
+
+    /// Recent events, for replay to reconnecting clients.
+    pub fn events(&self) -> &EventBus {
+        &self.inner.events
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    /// A state over an in-memory database, scanning `config.model_dirs`.
+    pub(crate) fn for_tests(config: AppConfig, api_key: Option<&str>) -> Self {
+        let model_dirs = config.model_dirs.clone();
+        Self::new(
+            config,
+            Database::open_in_memory(),
+            ModelManager::new(model_dirs, Default::default()),
+            StatsRegistry::new(),
+            RequestLog::default(),
+            api_key.map(String::from),
+        )
     }
 }
@@ -6,7 +6,9 @@ import type {
   DetokenizeResponse,
   HealthResponse,
   ModelEntry,
+  ModelSettings,
   OpenAIModelList,
+  ResourceSnapshot,
   TokenizeRequest,
   TokenizeResponse,
 } from '@/types'
@@ -81,6 +83,10 @@ export function chatCompletionStream(
           }
           try {
             const chunk = JSON.parse(payload)
+            if (chunk.error) {
+              onError(new Error(chunk.error.message))
+              return
+            }
             const content = chunk.choices?.[0]?.delta?.content
             if (content) onChunk(content)
             if (chunk.choices?.[0]?.finish_reason) {
@@ -101,6 +107,132 @@ export function chatCompletionStream(
   return controller
 }
 
+//  Chat sessions
+
+export interface ChatSession {
+  id: number
+  title: string
+  model_id: string | null
+  message_count: number
+  created_at: string
+  updated_at: string
+}
+
+export interface ChatSessionMessage {
+  id: number
+  session_id: number
+  model_id: string | null
+  role: string
+  content: string
+  tokens: number | null
+  finish_reason: string | null
+  created_at: string
+}
+
+export async function getChatSessions(): Promise<ChatSession[]> {
+  const { data } = await api.get<ChatSession[]>('/api/chat/sessions')
+  return data
+}
+
+export async function createChatSession(title?: string, model?: string): Promise<ChatSession> {
+  const { data } = await api.post<ChatSession>('/api/chat/sessions', { title, model })
+  return data
+}
+
+export async function deleteChatSession(id: number): Promise<void> {
+  await api.delete(`/api/chat/sessions/${id}`)
+}
+
+/** Delete sessions last active before `before` and/or using `model`. */
+export async function pruneChatHistory(params: {
+  before?: string
+  model?: string
+}): Promise<{ sessions: number; messages: number }> {
+  const { data } = await api.delete('/api/chat/history', { params })
+  return data
+}
+
+export async function getChatSessionMessages(id: number): Promise<ChatSessionMessage[]> {
+  const { data } = await api.get<ChatSessionMessage[]>(`/api/chat/sessions/${id}/messages`)
+  return data
+}
+
+/** Download URL of a session export. */
+export function chatSessionExportUrl(id: number, format: 'json' | 'markdown' = 'json'): string {
+  return `/api/chat/sessions/${id}/export?format=${format}`
+}
+
+/** Import a session export (or a ChatGPT `conversations.json`). */
+export async function importChatSessions(payload: unknown): Promise<ChatSession[]> {
+  const { data } = await api.post<ChatSession[]>('/api/chat/import', payload)
+  return data
+}
+
+/** Post a message and stream the reply; `onDone` receives the saved reply. */
+export function sendChatSessionMessage(
+  id: number,
+  req: { content: string; model?: string; max_tokens?: number; temperature?: number },
+  onChunk: (text: string) => void,
+  onDone: (message: ChatSessionMessage) => void,
+  onError: (err: Error) => void,
+): AbortController {
+  const controller = new AbortController()
+
+  fetch(`/api/chat/sessions/${id}/messages`, {
+    method: 'POST',
+    headers: {
+      'Content-Type': 'application/json',
+      ...(localStorage.getItem('api_key')
+        ? { Authorization: `Bearer ${localStorage.getItem('api_key')}` }
+        : {}),
+    },
+    body: JSON.stringify({ ...req, stream: true }),
+    signal: controller.signal,
+  })
+    .then(async (response) => {
+      if (!response.ok) {
+        throw new Error(`HTTP ${response.status}: ${response.statusText}`)
+      }
+      const reader = response.body?.getReader()
+      if (!reader) throw new Error('No response body')
+
+      const decoder = new TextDecoder()
+      let buffer = ''
+
+      while (true) {
+        const { done, value } = await reader.read()
+        if (done) break
+
+        buffer += decoder.decode(value, { stream: true })
+        const lines = buffer.split('\n')
+        buffer = lines.pop() || ''
+
+        for (const line of lines) {
+          const trimmed = line.trim()
+          if (!trimmed || !trimmed.startsWith('data: ')) continue
+          try {
+            const event = JSON.parse(trimmed.slice(6))
+            if (event.type === 'token') onChunk(event.content)
+            else if (event.type === 'done') {
+              onDone(event.message)
+              return
+            } else if (event.type === 'error') {
+              onError(new Error(event.message))
+              return
+            }
+          } catch {
+            // skip malformed events
+          }
+        }
+      }
+    })
+    .catch((err) => {
+      if (err.name !== 'AbortError') onError(err)
+    })
+
+  return controller
+}
+
 //  Model management API
 
 export async function getModels(): Promise<ModelEntry[]> {
@@ -108,6 +240,22 @@ export async function getModels(): Promise<ModelEntry[]> {
   return data
 }
 
+export interface AdapterEntry {
+  id: string
+  filename: string
+  path: string
+  size: number
+  architecture: string | null
+  /** The model the adapter was trained against, if recorded. */
+  base_model: string | null
+}
+
+/** LoRA adapters in the model directories (left out of `getModels`). */
+export async function getAdapters(): Promise<AdapterEntry[]> {
+  const { data } = await api.get<AdapterEntry[]>('/api/adapters')
+  return data
+}
+
 export async function getModelDetails(id: string): Promise<ModelEntry> {
   const { data } = await api.get<ModelEntry>(`/api/models/${encodeURIComponent(id)}/details`)
   return data
@@ -115,21 +263,308 @@ export async function getModelDetails(id: string): Promise<ModelEntry> {
 
 export async function loadModel(
   id: string,
-  params?: { ctx_size?: number; n_gpu_layers?: number },
+  params?: ModelSettings & { force?: boolean },
 ): Promise<void> {
   await api.post(`/api/models/${encodeURIComponent(id)}/load`, params)
 }
 
+export async function getModelSettings(
+  id: string,
+): Promise<{ id: string; settings: ModelSettings; effective: ModelSettings }> {
+  const { data } = await api.get(`/api/models/${encodeURIComponent(id)}/settings`)
+  return data
+}
+
+export async function updateModelSettings(id: string, settings: ModelSettings): Promise<void> {
+  await api.put(`/api/models/${encodeURIComponent(id)}/settings`, settings)
+}
+
+export interface MemoryEstimate {
+  weights_bytes: number
+  kv_cache_bytes: number
+  compute_bytes: number
+  total_bytes: number
+  /** The part of `total_bytes` in system RAM. */
+  host_bytes: number
+  /** The part of `total_bytes` on the GPU. */
+  device_bytes: number
+}
+
+export async function estimateModel(
+  id: string,
+  ctxSize?: number,
+  nGpuLayers?: number,
+): Promise<{
+  estimate: MemoryEstimate
+  fits: boolean
+  summary: string
+  available_bytes: number
+}> {
+  const { data } = await api.get(`/api/models/${encodeURIComponent(id)}/estimate`, {
+    params: { ctx_size: ctxSize || undefined, n_gpu_layers: nGpuLayers },
+  })
+  return data
+}
+
+export interface ModelMetadata {
+  id: string
+  path: string
+  source: 'llama.cpp' | 'gguf'
+  version: number
+  tensor_count: number
+  kv_count: number
+  /** Plain JSON values; 64-bit integers past 2^53 and NaN/±Infinity are strings. */
+  metadata: Record<string, unknown>
+  /** Arrays left out of `metadata`, with their lengths. */
+  elided: Record<string, number>
+}
+
+export async function getModelMetadata(id: string, includeArrays = false): Promise<ModelMetadata> {
+  const { data } = await api.get<ModelMetadata>(`/api/models/${encodeURIComponent(id)}/metadata`, {
+    params: { include_arrays: includeArrays || undefined },
+  })
+  return data
+}
+
+export interface FileVerification {
+  path: string
+  sha256: string
+  expected: string | null
+  status: 'ok' | 'mismatch' | 'recorded'
+}
+
+/** Re-hash a model's files against `sha256` or the recorded hashes. */
+export async function verifyModel(
+  id: string,
+  sha256?: string,
+): Promise<{ id: string; ok: boolean; files: FileVerification[] }> {
+  const { data } = await api.post(
+    `/api/models/${encodeURIComponent(id)}/verify`,
+    sha256 ? { sha256 } : undefined,
+  )
+  return data
+}
+
+export interface BenchStats {
+  mean: number
+  stddev: number
+}
+
+export interface BenchRecord {
+  id: number
+  model_id: string
+  n_prompt: number
+  n_gen: number
+  repetitions: number
+  pp: BenchStats
+  tg: BenchStats
+  ttft_ms: BenchStats
+  created_at: string
+}
+
+export async function runBenchmark(
+  id: string,
+  opts?: { pp?: number; tg?: number; repetitions?: number; force?: boolean },
+): Promise<{ id: string; result_id: number; report: Omit<BenchRecord, 'id' | 'model_id' | 'created_at'> }> {
+  const { data } = await api.post(`/api/models/${encodeURIComponent(id)}/bench`, opts ?? {})
+  return data
+}
+
+export async function getBenchResults(id: string): Promise<BenchRecord[]> {
+  const { data } = await api.get<BenchRecord[]>(`/api/models/${encodeURIComponent(id)}/bench`)
+  return data
+}
+
+export interface ModelStats {
+  id: string
+  loaded: boolean
+  requests: number
+  prompt_tokens: number
+  completion_tokens: number
+  generation_ms: number
+  tokens_per_sec: number
+  last_request_at: string | null
+}
+
+export async function getModelStats(id: string): Promise<ModelStats> {
+  const { data } = await api.get<ModelStats>(`/api/models/${encodeURIComponent(id)}/stats`)
+  return data
+}
+
+export async function resetModelStats(id: string): Promise<void> {
+  await api.delete(`/api/models/${encodeURIComponent(id)}/stats`)
+}
+
+export interface UsageRow {
+  key: string
+  requests: number
+  prompt_tokens: number
+  completion_tokens: number
+  duration_ms: number
+  errors: number
+}
+
+/** `api_key` buckets by key id; `none` collects unauthenticated requests. */
+export type UsageGroupBy = 'model' | 'day' | 'api_key'
+
+export interface UsageReport {
+  enabled: boolean
+  group_by: UsageGroupBy
+  from: string | null
+  to: string | null
+  rows: UsageRow[]
+}
+
+export async function getUsage(params?: {
+  from?: string
+  to?: string
+  group_by?: UsageGroupBy
+}): Promise<UsageReport> {
+  const { data } = await api.get<UsageReport>('/api/usage', { params })
+  return data
+}
+
+//  API keys
+
+export type ApiKeyScope = 'inference' | 'admin'
+
+export interface ApiKey {
+  id: string
+  label: string
+  prefix: string
+  scopes: ApiKeyScope[]
+  created_at: string
+  last_used_at: string | null
+  revoked: boolean
+}
+
+export async function listApiKeys(): Promise<ApiKey[]> {
+  const { data } = await api.get<ApiKey[]>('/api/keys')
+  return data
+}
+
+/** Create a key; `key` holds the plaintext, which is only returned once. */
+export async function createApiKey(
+  label: string,
+  scopes: ApiKeyScope[] = ['inference'],
+): Promise<ApiKey & { key: string }> {
+  const { data } = await api.post('/api/keys', { label, scopes })
+  return data
+}
+
+export async function revokeApiKey(id: string): Promise<void> {
+  await api.delete(`/api/keys/${encodeURIComponent(id)}`)
+}
+
+export async function getModelStatus(
+  id: string,
+): Promise<{ id: string; status: string; job_id?: string; error?: string }> {
+  const { data } = await api.get(`/api/models/${encodeURIComponent(id)}/status`)
+  return data
+}
+
 export async function unloadModel(id: string): Promise<void> {
   await api.post(`/api/models/${encodeURIComponent(id)}/unload`)
 }
 
+export interface UnloadAllResult {
+  draining: boolean
+  reason: string
+  unloaded: string[]
+  skipped: string[]
+}
+
+/** Enter maintenance drain mode and unload every model. */
+export async function unloadAllModels(opts?: {
+  timeout?: number
+  reason?: string
+}): Promise<UnloadAllResult> {
+  const { data } = await api.post('/api/models/unload-all', null, { params: opts })
+  return data
+}
+
+/** Leave maintenance drain mode. */
+export async function resumeMaintenance(): Promise<{ draining: boolean; was_draining: boolean }> {
+  const { data } = await api.post('/api/maintenance/resume')
+  return data
+}
+
+export interface FileDeletion {
+  path: string
+  bytes: number
+  deleted: boolean
+  error?: string
+}
+
+export async function deleteModelFile(
+  id: string,
+  opts?: { includeCompanions?: boolean; dryRun?: boolean },
+): Promise<{ id: string; dry_run: boolean; files: FileDeletion[]; total_bytes: number; freed_bytes?: number }> {
+  const { data } = await api.delete(`/api/models/${encodeURIComponent(id)}/file`, {
+    params: { include_companions: opts?.includeCompanions, dry_run: opts?.dryRun },
+  })
+  return data
+}
+
 export async function scanModels(): Promise<void> {
   await api.post('/api/models/scan')
 }
 
-export async function toggleFavorite(id: string): Promise<void> {
-  await api.put(`/api/models/${encodeURIComponent(id)}/favorite`)
+export async function toggleFavorite(id: string): Promise<boolean> {
+  const { data } = await api.put<{ id: string; favorite: boolean }>(
+    `/api/models/${encodeURIComponent(id)}/favorite`,
+  )
+  return data.favorite
+}
+
+export async function setModelAlias(id: string, alias: string | null): Promise<void> {
+  await api.put(`/api/models/${encodeURIComponent(id)}/alias`, { alias })
+}
+
+export async function setModelDisplayName(id: string, displayName: string | null): Promise<void> {
+  await api.put(`/api/models/${encodeURIComponent(id)}/name`, { display_name: displayName })
+}
+
+export async function getAliases(): Promise<{ alias: string; model_id: string }[]> {
+  const { data } = await api.get<{ alias: string; model_id: string }[]>('/api/aliases')
+  return data
+}
+
+//  Downloads
+
+export interface DownloadInfo {
+  id: string
+  url: string
+  path: string
+  status: 'downloading' | 'completed' | 'failed'
+  bytes_done: number
+  total_bytes: number | null
+  speed_bps: number
+  eta_secs: number | null
+  error: string | null
+  /** SHA-256 of the completed file. */
+  sha256: string | null
+  started_at: string
+  finished_at: string | null
+}
+
+export async function startDownload(req: {
+  url: string
+  filename?: string
+  dir?: string
+}): Promise<DownloadInfo> {
+  const { data } = await api.post<DownloadInfo>('/api/downloads', req)
+  return data
+}
+
+export async function getDownloads(): Promise<DownloadInfo[]> {
+  const { data } = await api.get<DownloadInfo[]>('/api/downloads')
+  return data
+}
+
+export async function getDownload(id: string): Promise<DownloadInfo> {
+  const { data } = await api.get<DownloadInfo>(`/api/downloads/${encodeURIComponent(id)}`)
+  return data
 }
 
 //  Tokenize
@@ -154,3 +589,10 @@ export async function getConfig(): Promise<AppConfig> {
 export async function updateConfig(config: Partial<AppConfig>): Promise<void> {
   await api.put('/api/config', config)
 }
+
+//  System
+
+export async function getSystemResources(): Promise<ResourceSnapshot> {
+  const { data } = await api.get<ResourceSnapshot>('/api/system/resources')
+  return data
+}
@@ -64,6 +64,17 @@ export default {
     name: 'Name',
     architecture: 'Architecture',
     quantization: 'Quantization',
+    embeddingsOnly: 'Embeddings',
+    visionProjector: 'Vision projector',
+    missingParts: 'Missing parts: {parts}',
+    fromFilename: 'Unreadable — guessed from file name',
+    feature: {
+      moe: 'MoE',
+      vision: 'Vision',
+      embedding: 'Embedding',
+      instruct: 'Instruct',
+      reasoning: 'Reasoning',
+    },
     size: 'Size',
     context: 'Context',
     status: 'Status',
@@ -86,6 +97,17 @@ export default {
     contextSize: 'Context Size',
     gpuLayers: 'GPU Layers (-1 = auto)',
     chatTemplate: 'Chat Template',
+    tokenizer: 'Tokenizer',
+    vocabSize: 'Vocabulary Size',
+    addBos: 'Adds BOS',
+    templateFormat: 'Template Format',
+    shape: 'Architecture',
+    layers: 'Layers',
+    embeddingSize: 'Embedding Size',
+    heads: 'Heads (Q / KV)',
+    ropeFreqBase: 'RoPE Base',
+    ropeFreqScale: 'RoPE Scale (trained)',
+    vocabType: 'Vocabulary Type',
     unknown: 'Unknown',
   },
   chat: {
@@ -64,6 +64,17 @@ export default {
     name: '名称',
     architecture: '架构',
     quantization: '量化',
+    embeddingsOnly: '嵌入',
+    visionProjector: '视觉投影器',
+    missingParts: '缺少分片：{parts}',
+    fromFilename: '无法读取 — 信息来自文件名',
+    feature: {
+      moe: 'MoE',
+      vision: '视觉',
+      embedding: '嵌入',
+      instruct: '指令',
+      reasoning: '推理',
+    },
     size: '大小',
     context: '上下文',
     status: '状态',
@@ -86,6 +97,17 @@ export default {
     contextSize: '上下文长度',
     gpuLayers: 'GPU 层数（-1 = 自动）',
     chatTemplate: '对话模板',
+    tokenizer: '分词器',
+    vocabSize: '词表大小',
+    addBos: '自动添加 BOS',
+    templateFormat: '模板格式',
+    shape: '模型结构',
+    layers: '层数',
+    embeddingSize: '嵌入维度',
+    heads: '注意力头 (Q / KV)',
+    ropeFreqBase: 'RoPE 基频',
+    ropeFreqScale: 'RoPE 缩放 (训练)',
+    vocabType: '词表类型',
     unknown: '未知',
   },
   chat: {
@@ -52,7 +52,7 @@ export const useModelStore = defineStore('models', () => {
     if (model) {
       model.favorite = !model.favorite
       try {
-        await api.toggleFavorite(id)
+        model.favorite = await api.toggleFavorite(id)
       } catch {
         model.favorite = !model.favorite
       }
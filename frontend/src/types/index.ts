@@ -7,12 +7,31 @@ export interface ModelInfo {
   size: number
   architecture: string | null
   parameters: string | null
+  param_count?: number | null
+  /** Average weight storage per parameter, from the tensor types. */
+  bytes_per_param?: number | null
+  /** SHA-256 of the model file, if recorded since it last changed. */
+  hash?: string | null
+  /** What the model can serve. */
+  capabilities?: Capability[]
+  /** `mmproj` for a vision projector no model was found for. */
+  kind?: ModelKind
+  /** Badges guessed from the metadata. */
+  features?: ModelFeature[]
   context_length: number | null
   file_type: string | null
   quantization: string | null
   chat_template: string | null
 }
 
+export type Capability = 'chat' | 'embeddings' | 'vision-projector'
+
+export type ModelKind = 'base' | 'lora-adapter' | 'mmproj'
+
+export type ModelFeature = 'moe' | 'vision' | 'embedding' | 'instruct' | 'reasoning'
+
+export type MetadataSource = 'scanned' | 'filename'
+
 export type ModelStatus = 'unloaded' | 'loading' | 'loaded' | 'error'
 
 export interface ModelEntry extends ModelInfo {
@@ -21,6 +40,84 @@ export interface ModelEntry extends ModelInfo {
   last_used?: string
   favorite?: boolean
   alias?: string
+  display_name?: string | null
+  collision?: boolean
+  /** `path` with symlinks resolved. */
+  canonical_path?: string
+  /** `filename` when the metadata couldn't be read and details are guesses. */
+  metadata_source?: MetadataSource
+  /** Why the metadata couldn't be read. */
+  scan_error?: string | null
+  /** Part numbers of a split model missing on disk; it can't be loaded. */
+  missing_parts?: number[]
+  /** Effective load settings (details endpoint only). */
+  settings?: ModelSettings
+  /** Special tokens and template format (details endpoint only). */
+  tokenizer?: TokenizerInfo
+  /** Layer/head counts, RoPE and vocab type (details endpoint only). */
+  model_info?: ModelShape
+}
+
+/** `ModelInfo` in the API: the model's shape. */
+export interface ModelShape {
+  n_ctx_train: number
+  n_embd: number
+  n_layer: number
+  n_head: number
+  /** Fewer than `n_head` with grouped-query attention. */
+  n_head_kv: number
+  rope_freq_base: number | null
+  rope_freq_scale_train: number
+  vocab_type: 'none' | 'spm' | 'bpe' | 'wpm' | 'ugm' | 'rwkv' | 'other'
+}
+
+export interface SpecialToken {
+  id: number
+  text: string | null
+}
+
+export interface TokenizerInfo {
+  /** `gguf` when read from file metadata rather than the loaded model. */
+  source: 'model' | 'gguf'
+  n_vocab: number | null
+  bos: SpecialToken | null
+  eos: SpecialToken | null
+  eot: SpecialToken | null
+  add_bos: boolean | null
+  chat_template_name: string | null
+}
+
+export type KvCacheType = 'f32' | 'f16' | 'bf16' | 'q8_0' | 'q4_0' | 'q4_1' | 'iq4_nl' | 'q5_0' | 'q5_1'
+
+export type SplitMode = 'none' | 'layer' | 'row'
+
+/** GGUF metadata replaced at load time. */
+export type KvOverride = { key: string } & (
+  | { type: 'int' | 'float'; value: number }
+  | { type: 'bool'; value: boolean }
+  | { type: 'str'; value: string }
+)
+
+export interface ModelSettings {
+  ctx_size?: number | null
+  n_gpu_layers?: number | null
+  split_mode?: SplitMode | null
+  main_gpu?: number | null
+  tensor_split?: number[] | null
+  kv_overrides?: KvOverride[] | null
+  rope_scaling_type?: 'none' | 'linear' | 'yarn' | null
+  rope_freq_base?: number | null
+  rope_freq_scale?: number | null
+  yarn_ext_factor?: number | null
+  yarn_attn_factor?: number | null
+  yarn_beta_fast?: number | null
+  yarn_beta_slow?: number | null
+  yarn_orig_ctx?: number | null
+  n_threads?: number | null
+  flash_attn?: boolean | null
+  cache_type?: KvCacheType | null
+  type_k?: KvCacheType | null
+  type_v?: KvCacheType | null
 }
 
 // ── Chat types ──────────────────────────────────────────
@@ -103,8 +200,28 @@ export interface AppConfig {
   default_n_gpu_layers: number
   default_temperature: number
   api_key?: string
+  /** Requests per minute per API key or IP (0 = unlimited). */
+  rate_limit_per_minute?: number
+  /** Model load/unload requests per minute (0 = unlimited). */
+  model_ops_rate_limit_per_minute?: number
+  /** Largest accepted request body in bytes. */
+  max_body_bytes?: number
+  /** Generation time limit in seconds (0 = unlimited). */
+  generation_timeout_secs?: number
+  /** gzip/brotli-compress API responses. */
+  compression_enabled?: boolean
+  /** PEM certificate path; HTTPS when set with `tls_key`. */
+  tls_cert?: string | null
+  /** PEM private key path. */
+  tls_key?: string | null
+  /** Plain-HTTP port redirecting to HTTPS. */
+  http_redirect_port?: number | null
+  /** NUMA strategy; takes effect after a restart. */
+  numa?: NumaStrategy
 }
 
+export type NumaStrategy = 'disabled' | 'distribute' | 'isolate' | 'numactl' | 'mirror'
+
 // ── System ──────────────────────────────────────────────
 
 export interface SystemInfo {
@@ -112,12 +229,43 @@ export interface SystemInfo {
   uptime_seconds: number
   models_loaded: number
   models_available: number
+  compiled_backends: string[]
+  devices: DeviceInfo[]
+  supports: { mmap: boolean; mlock: boolean; gpu_offload: boolean }
+  /** NUMA strategy in effect. */
+  numa: NumaStrategy
+  /** Generations running or queued; kept current by `generation.*` events. */
+  generations_in_flight: number
+}
+
+export interface DeviceInfo {
+  name: string
+  description: string
+  backend: string
+  is_gpu: boolean
+  free_bytes: number
+  total_bytes: number
+}
+
+export interface ResourceSnapshot {
+  timestamp: string
+  cpu_percent: number
+  cpu_count: number
+  ram_total_bytes: number
+  ram_used_bytes: number
+  ram_available_bytes: number
+  swap_total_bytes: number
+  swap_used_bytes: number
+  gpus: { name: string; description: string; used_bytes: number; total_bytes: number }[]
+  models: { id: string; estimated_bytes: number; resident_bytes: number | null }[]
 }
 
 // ── WebSocket events ────────────────────────────────────
 
 export interface WsEvent {
   type: string
+  /** Broadcast events only; send `{ since: seq }` after reconnecting. */
+  seq?: number
   timestamp: string
   data: Record<string, unknown>
 }